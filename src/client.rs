@@ -0,0 +1,259 @@
+// Off-chain helpers for wallets and other clients of the program.
+
+use solana_program::{
+    bpf_loader_upgradeable, instruction::Instruction, program_error::ProgramError, program_pack::Pack,
+    pubkey::Pubkey, rent::Rent, system_program, sysvar,
+};
+
+use spl_token::state::Account;
+
+use crate::{
+    error::LockTokenError,
+    instruction::{initialize_global_state, set_fee_params},
+    oracle::OraclePrice,
+    state::{find_global_state_address, get_locking_account_size, LockGlobalState, LockMetadata, LockScheduleHeader, TokenState, FEE_PARAMS_ALL, MAX_CANDIDATE_WALLETS},
+};
+
+// Lamports a user pays to create a lock, split by what they pay for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CreationCost {
+    // Rent of the locking account holding the schedules
+    pub locking_account_rent: u64,
+    // Rent of the locking token account, paid when it has to be created
+    pub vault_rent: u64,
+    // Rent of the token state account, paid by the first lock of a mint
+    pub token_state_rent: u64,
+    // Fee transferred to the company wallet
+    pub fee_lamports: u64,
+}
+
+impl CreationCost {
+    pub fn total(&self) -> u64 {
+        self.locking_account_rent
+            .saturating_add(self.vault_rent)
+            .saturating_add(self.token_state_rent)
+            .saturating_add(self.fee_lamports)
+    }
+}
+
+// Estimates the cost of Init followed by Create, using the same sizes and fee computation as the program.
+// `token_state` is None when the mint has never been locked, `sol_price` is the current SOL/USD oracle price
+// and is only needed when the mint is not free.
+pub fn estimate_creation_cost(
+    schedule_count: u32,
+    rent: &Rent,
+    global: &LockGlobalState,
+    token_state: Option<&TokenState>,
+    sol_price: Option<&OraclePrice>,
+) -> Result<CreationCost, ProgramError> {
+    let locking_account_rent = rent.minimum_balance(get_locking_account_size(schedule_count as usize));
+    let vault_rent = rent.minimum_balance(Account::LEN);
+
+    let new_token_state = TokenState {
+        mint_address: Pubkey::default(),
+        is_free: false,
+        fee_override_usd: 0,
+        has_override: false,
+        active_locks: 0,
+        total_locked_amount: 0,
+        last_snapshot_amount: 0,
+        last_snapshot_ts: 0,
+        is_initialized: true,
+    };
+    let (token_state, token_state_rent) = match token_state {
+        Some(token_state) => (token_state, 0),
+        None => (&new_token_state, rent.minimum_balance(TokenState::LEN)),
+    };
+
+    let fee_lamports = if token_state.is_free {
+        0
+    } else {
+        let price = sol_price.ok_or(LockTokenError::InvalidOraclePrice)?;
+        token_state.estimate_fees_in_sol(global.fees_in_usd, price)?
+    };
+
+    Ok(CreationCost {
+        locking_account_rent,
+        vault_rent,
+        token_state_rent,
+        fee_lamports,
+    })
+}
+
+// Fee parameters a new deployment starts with
+pub struct FeeParams {
+    pub price_estimator: Pubkey,
+    pub usd_token_address: Pubkey,
+    pub fees_in_usd: u64,
+    pub company_wallet: Pubkey,
+    pub candidate_wallets: [Pubkey; MAX_CANDIDATE_WALLETS],
+}
+
+// Builds the instructions which set up a fresh deployment, in the order they have to run:
+// InitializeGlobalState signed by the upgrade authority, then SetFeeParams signed by the new admin.
+// They can be sent in a single transaction signed by both keys.
+pub fn bootstrap(
+    locking_program_id: &Pubkey,
+    upgrade_authority: &Pubkey,
+    admin: &Pubkey,
+    fee_params: &FeeParams,
+) -> Result<Vec<Instruction>, ProgramError> {
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let (program_data_account_key, _) =
+        Pubkey::find_program_address(&[locking_program_id.as_ref()], &bpf_loader_upgradeable::id());
+
+    Ok(vec![
+        initialize_global_state(
+            locking_program_id,
+            &system_program::id(),
+            &sysvar::rent::id(),
+            upgrade_authority,
+            &program_state_account_key,
+            &program_data_account_key,
+            admin,
+            false,
+        )?,
+        set_fee_params(
+            locking_program_id,
+            admin,
+            &program_state_account_key,
+            &fee_params.price_estimator,
+            &fee_params.usd_token_address,
+            fee_params.fees_in_usd,
+            &fee_params.company_wallet,
+            fee_params.candidate_wallets,
+            FEE_PARAMS_ALL,
+        )?,
+    ])
+}
+
+// Reads the header of a locking account of either layout, e.g. to label NFT and vesting locks
+pub fn unpack_lock_header(locking_account_data: &[u8]) -> Result<LockScheduleHeader, ProgramError> {
+    let header_len = LockScheduleHeader::header_len(locking_account_data.len());
+    let header = LockScheduleHeader::unpack_from_slice(
+        locking_account_data.get(..header_len).ok_or(ProgramError::InvalidAccountData)?,
+    )?;
+    if !header.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    Ok(header)
+}
+
+// Reads the name and URI of a lock from its metadata account, see state::find_lock_metadata_address.
+// Fails with UninitializedAccount for a closed or never set metadata account.
+pub fn unpack_lock_metadata(lock_metadata_account_data: &[u8]) -> Result<LockMetadata, ProgramError> {
+    LockMetadata::unpack(lock_metadata_account_data)
+}
+
+// Whether the destination and release times of the lock are fixed for good, see Create
+pub fn is_immutable_lock(locking_account_data: &[u8]) -> Result<bool, ProgramError> {
+    Ok(unpack_lock_header(locking_account_data)?.is_immutable)
+}
+
+// Offsets in the data of a locking account for the memcmp filters of getProgramAccounts, see derived_lock_filters
+pub const LOCK_MINT_OFFSET: usize = 32;
+pub const LOCK_CREATOR_OFFSET: usize = 162;
+
+// memcmp filters, as offset and bytes, of the getProgramAccounts call finding the locking accounts of the creator
+// for the mint, e.g. to pick the next free nonce of pda::find_lock_address. Locking accounts initialized with
+// InitDerived and still waiting for Create match too, as their nonce is taken as well. So do locks of raw seeds
+// created by the creator, which derived_lock_nonce tells apart.
+pub fn derived_lock_filters(creator: &Pubkey, mint: &Pubkey) -> [(usize, Vec<u8>); 2] {
+    [
+        (LOCK_MINT_OFFSET, mint.to_bytes().to_vec()),
+        (LOCK_CREATOR_OFFSET, creator.to_bytes().to_vec()),
+    ]
+}
+
+// Nonce of the derivation of a locking account initialized with InitDerived, None for locking accounts of raw seeds.
+// The account has to be owned by the program and have the key the derivation gives, see pda::find_lock_address.
+pub fn derived_lock_nonce(locking_account_data: &[u8]) -> Option<u64> {
+    LockScheduleHeader::unpack_derivation(locking_account_data).map(|lock_signer| lock_signer.nonce())
+}
+
+// Rescue destination and earliest execution time of the emergency unlock scheduled for the lock, if any,
+// so the beneficiary can be warned in time to cancel it
+pub fn pending_emergency_unlock(locking_account_data: &[u8]) -> Result<Option<(Pubkey, u64)>, ProgramError> {
+    let header = unpack_lock_header(locking_account_data)?;
+    if !header.has_pending_rescue() {
+        return Ok(None);
+    }
+    Ok(Some((header.rescue_destination_address, header.rescue_execute_after)))
+}
+
+// Who created a lock and when, e.g. for explorers showing when it was locked and for how long
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LockInfo {
+    pub mint: Pubkey,
+    pub destination: Pubkey,
+    // The default pubkey for locks created before the creator was recorded
+    pub creator: Pubkey,
+    // Unix timestamp of Create, zero for locks created before it was recorded
+    pub created_at: i64,
+    pub total_amount: u64,
+    pub claimed_amount: u64,
+}
+
+// Reads the LockInfo of a lock from the data of its locking account
+pub fn lock_info(locking_account_data: &[u8]) -> Result<LockInfo, ProgramError> {
+    let header = unpack_lock_header(locking_account_data)?;
+    Ok(LockInfo {
+        mint: header.mint_address,
+        destination: header.destination_address,
+        creator: header.creator_address,
+        created_at: header.created_at,
+        total_amount: header.total_amount,
+        claimed_amount: header.claimed_amount,
+    })
+}
+
+// Locks of a mint as counted in its token state
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MintLockStats {
+    pub active_locks: u32,
+    pub total_locked_amount: u64,
+}
+
+// Reads the lock counts of the mint from the data of its token state account, see token_state_address.
+// Locks created before the token state counted them are not included.
+pub fn mint_lock_stats(mint_address: &Pubkey, token_state_data: &[u8]) -> Result<MintLockStats, ProgramError> {
+    let token_state = TokenState::unpack_from_slice(token_state_data)?;
+    if !token_state.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    if token_state.mint_address != *mint_address {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(MintLockStats {
+        active_locks: token_state.active_locks,
+        total_locked_amount: token_state.total_locked_amount,
+    })
+}
+
+// What the locks passed to the latest SnapshotMintLocked of a mint held, and the unix timestamp it ran at
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MintLockedSnapshot {
+    pub locked_amount: u64,
+    pub timestamp: i64,
+}
+
+// Reads the latest snapshot of the mint from the data of its token state account, None before the first one
+pub fn mint_locked_snapshot(
+    mint_address: &Pubkey,
+    token_state_data: &[u8],
+) -> Result<Option<MintLockedSnapshot>, ProgramError> {
+    let token_state = TokenState::unpack_from_slice(token_state_data)?;
+    if !token_state.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    if token_state.mint_address != *mint_address {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if token_state.last_snapshot_ts == 0 {
+        return Ok(None);
+    }
+    Ok(Some(MintLockedSnapshot {
+        locked_amount: token_state.last_snapshot_amount,
+        timestamp: token_state.last_snapshot_ts,
+    }))
+}
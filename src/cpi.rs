@@ -0,0 +1,194 @@
+// Helpers for programs invoking the locker through invoke or invoke_signed.
+//
+// Each instruction takes its accounts as a struct listing them in the order the processor reads them,
+// see account_requirements. The built instruction is passed to invoke along with to_account_infos:
+//
+//     let instruction = cpi::unlock(&accounts, seeds, deadline)?;
+//     invoke(&instruction, &accounts.to_account_infos())?;
+//
+// A PDA of the calling program can own the source token account of Create or the destination token
+// account of TransferLocks, in which case it signs through invoke_signed.
+
+use solana_program::{
+    account_info::AccountInfo,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::{
+    instruction::{account_requirements, LockSeed, LockTokenInstruction, Schedule, MAX_SCHEDULES},
+    time::TimeBasis,
+};
+
+// Accounts of Create
+pub struct CreateAccounts<'a> {
+    pub locking_program: AccountInfo<'a>,
+    pub token_program: AccountInfo<'a>,
+    pub program_state: AccountInfo<'a>,
+    pub locking_account: AccountInfo<'a>,
+    pub locking_token_account: AccountInfo<'a>,
+    // Signer of the instruction
+    pub source_token_account_owner: AccountInfo<'a>,
+    pub source_token_account: AccountInfo<'a>,
+    pub token_state: AccountInfo<'a>,
+    pub company_wallet: AccountInfo<'a>,
+    pub system_program: AccountInfo<'a>,
+    pub rent_sysvar: AccountInfo<'a>,
+    pub associated_token_program: AccountInfo<'a>,
+    pub mint: AccountInfo<'a>,
+    pub price_estimator: AccountInfo<'a>,
+    // Signer of the instruction, pays the fee and the rent
+    pub fee_payer: AccountInfo<'a>,
+}
+
+impl<'a> CreateAccounts<'a> {
+    // Accounts in the order of the instruction, followed by the locker program
+    pub fn to_account_infos(&self) -> Vec<AccountInfo<'a>> {
+        vec![
+            self.token_program.clone(),
+            self.program_state.clone(),
+            self.locking_account.clone(),
+            self.locking_token_account.clone(),
+            self.source_token_account_owner.clone(),
+            self.source_token_account.clone(),
+            self.token_state.clone(),
+            self.company_wallet.clone(),
+            self.system_program.clone(),
+            self.rent_sysvar.clone(),
+            self.associated_token_program.clone(),
+            self.mint.clone(),
+            self.price_estimator.clone(),
+            self.fee_payer.clone(),
+            self.locking_program.clone(),
+        ]
+    }
+}
+
+// Accounts of Unlock. Nobody signs an unlock, the tokens only ever go to the destination of the lock.
+pub struct UnlockAccounts<'a> {
+    pub locking_program: AccountInfo<'a>,
+    pub token_program: AccountInfo<'a>,
+    pub program_state: AccountInfo<'a>,
+    pub locking_account: AccountInfo<'a>,
+    pub locking_token_account: AccountInfo<'a>,
+    pub destination_token_account: AccountInfo<'a>,
+    pub mint: AccountInfo<'a>,
+    pub token_state: AccountInfo<'a>,
+}
+
+impl<'a> UnlockAccounts<'a> {
+    // Accounts in the order of the instruction, followed by the locker program
+    pub fn to_account_infos(&self) -> Vec<AccountInfo<'a>> {
+        vec![
+            self.token_program.clone(),
+            self.program_state.clone(),
+            self.locking_account.clone(),
+            self.locking_token_account.clone(),
+            self.destination_token_account.clone(),
+            self.mint.clone(),
+            self.token_state.clone(),
+            self.locking_program.clone(),
+        ]
+    }
+}
+
+// Accounts of TransferLocks
+pub struct TransferLocksAccounts<'a> {
+    pub locking_program: AccountInfo<'a>,
+    pub program_state: AccountInfo<'a>,
+    pub locking_account: AccountInfo<'a>,
+    pub destination_token_account: AccountInfo<'a>,
+    // Signer of the instruction
+    pub destination_token_account_owner: AccountInfo<'a>,
+    pub new_destination_token_account: AccountInfo<'a>,
+}
+
+impl<'a> TransferLocksAccounts<'a> {
+    // Accounts in the order of the instruction, followed by the locker program
+    pub fn to_account_infos(&self) -> Vec<AccountInfo<'a>> {
+        vec![
+            self.program_state.clone(),
+            self.locking_account.clone(),
+            self.destination_token_account.clone(),
+            self.destination_token_account_owner.clone(),
+            self.new_destination_token_account.clone(),
+            self.locking_program.clone(),
+        ]
+    }
+}
+
+// Locks the tokens of the source token account, authorizing exactly the total of the schedules
+pub fn create(
+    accounts: &CreateAccounts,
+    seeds: LockSeed,
+    destination_token_address: &Pubkey,
+    schedules: Vec<Schedule>,
+    deadline: u64,
+) -> Result<Instruction, ProgramError> {
+    if schedules.len() > MAX_SCHEDULES as usize {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let max_total = schedules
+        .iter()
+        .try_fold(0u64, |total, s| total.checked_add(s.amount))
+        .ok_or(ProgramError::InvalidArgument)?;
+    let data = LockTokenInstruction::Create {
+        mint_address: *accounts.mint.key,
+        seeds,
+        destination_token_address: *destination_token_address,
+        schedules,
+        deadline,
+        max_total: Some(max_total),
+        is_nft: false,
+        is_immutable: false,
+        pay_fee_in_usd: false,
+        pay_fee_in_token: false,
+        with_fee_discount: false,
+        time_basis: TimeBasis::UnixTimestamp,
+        split_destinations: Vec::new(),
+        with_wallet_state: false,
+        min_claim_interval_secs: 0,
+    }
+    .pack();
+    instruction(&accounts.locking_program, accounts.to_account_infos(), data)
+}
+
+pub fn unlock(accounts: &UnlockAccounts, seeds: LockSeed, deadline: u64) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::Unlock { seeds, deadline }.pack();
+    instruction(&accounts.locking_program, accounts.to_account_infos(), data)
+}
+
+// Unlocks exactly amount of what has matured, with the accounts of Unlock
+pub fn unlock_amount(accounts: &UnlockAccounts, seeds: LockSeed, amount: u64) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::UnlockAmount { seeds, amount }.pack();
+    instruction(&accounts.locking_program, accounts.to_account_infos(), data)
+}
+
+pub fn transfer_locks(accounts: &TransferLocksAccounts, seeds: LockSeed) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::TransferLocks { seeds }.pack();
+    instruction(&accounts.locking_program, accounts.to_account_infos(), data)
+}
+
+// Flags the accounts as the account requirements of the instruction ask.
+// The locker program closing account_infos is not an account of the instruction.
+fn instruction(
+    locking_program: &AccountInfo,
+    account_infos: Vec<AccountInfo>,
+    data: Vec<u8>,
+) -> Result<Instruction, ProgramError> {
+    let accounts = account_infos
+        .iter()
+        .zip(account_requirements(data[0]))
+        .map(|(account, requirement)| AccountMeta {
+            pubkey: *account.key,
+            is_signer: requirement.signer,
+            is_writable: requirement.writable,
+        })
+        .collect();
+    Ok(Instruction {
+        program_id: *locking_program.key,
+        accounts,
+        data,
+    })
+}
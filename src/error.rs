@@ -5,7 +5,111 @@ use thiserror::Error;
 #[derive(Clone, Debug, Eq, Error, FromPrimitive, PartialEq)]
 pub enum LockTokenError {
     #[error("Invalid Instruction")]
-    InvalidInstruction
+    InvalidInstruction,
+    #[error("Account Not Writable")]
+    AccountNotWritable,
+    #[error("Unsupported Token Extension")]
+    UnsupportedTokenExtension,
+    #[error("Transaction Expired")]
+    TransactionExpired,
+    #[error("Invalid Oracle Account")]
+    InvalidOracleAccount,
+    #[error("Stale Oracle Price")]
+    StaleOraclePrice,
+    #[error("Invalid Oracle Price")]
+    InvalidOraclePrice,
+    #[error("Math Overflow")]
+    MathOverflow,
+    #[error("Wallet Not Whitelisted")]
+    WalletNotWhitelisted,
+    #[error("Candidate Wallets Locked")]
+    CandidateWalletsLocked,
+    #[error("Total Exceeds Authorized")]
+    TotalExceedsAuthorized,
+    #[error("Would Violate Ordering")]
+    WouldViolateOrdering,
+    #[error("Lock Duration Too Short")]
+    LockDurationTooShort,
+    #[error("Lock Amount Too Small")]
+    LockAmountTooSmall,
+    #[error("Destination Mint Mismatch")]
+    DestinationMintMismatch,
+    #[error("Destination Frozen")]
+    DestinationFrozen,
+    #[error("Empty Schedules")]
+    EmptySchedules,
+    #[error("Schedules Not Sorted")]
+    SchedulesNotSorted,
+    #[error("Zero Amount Schedule")]
+    ZeroAmountSchedule,
+    #[error("Release Time In Past")]
+    ReleaseTimeInPast,
+    #[error("Invalid Timestamp")]
+    InvalidTimestamp,
+    #[error("Release Time Too Far")]
+    ReleaseTimeTooFar,
+    #[error("Source Mint Mismatch")]
+    SourceMintMismatch,
+    #[error("Vault Mint Mismatch")]
+    VaultMintMismatch,
+    #[error("Company Wallet Mismatch")]
+    CompanyWalletMismatch,
+    #[error("Duplicate Account")]
+    DuplicateAccount,
+    #[error("Invalid Basis Points")]
+    InvalidBasisPoints,
+    #[error("Not An NFT")]
+    NotAnNft,
+    #[error("Lock Fully Unlocked")]
+    LockFullyUnlocked,
+    #[error("Too Many Schedules")]
+    TooManySchedules,
+    #[error("Escrow Frozen")]
+    EscrowFrozen,
+    #[error("Source Authority Mismatch")]
+    SourceAuthorityMismatch,
+    #[error("Insufficient Delegated Amount")]
+    InsufficientDelegatedAmount,
+    #[error("Immutable Lock")]
+    ImmutableLock,
+    #[error("Emergency Unlock Timelock")]
+    EmergencyUnlockTimelock,
+    #[error("No Excess Tokens")]
+    NoExcessTokens,
+    #[error("Fee Mint Mismatch")]
+    FeeMintMismatch,
+    #[error("Multi Destination Lock")]
+    MultiDestinationLock,
+    #[error("Invalid Split")]
+    InvalidSplit,
+    #[error("Lock Name Too Long")]
+    LockNameTooLong,
+    #[error("Lock URI Too Long")]
+    LockUriTooLong,
+    #[error("Duration Too Long")]
+    DurationTooLong,
+    #[error("Permit Expired")]
+    PermitExpired,
+    #[error("Invalid Permit")]
+    InvalidPermit,
+    #[error("Claim Too Soon")]
+    ClaimTooSoon,
+    #[error("Invalid Global State")]
+    InvalidGlobalState,
+    #[error("Global State Uninitialized")]
+    GlobalStateUninitialized,
+    #[error("Contract Paused")]
+    ContractPaused,
+    #[error("Not Admin")]
+    NotAdmin,
+    #[error("Invalid Locking Account")]
+    InvalidLockingAccount,
+    #[error("Insufficient Unlocked")]
+    InsufficientUnlocked,
+    #[error("Invalid Claim Cursor")]
+    InvalidClaimCursor,
+    #[error("Candidate Wallets Timelock")]
+    CandidateWalletsTimelock,
 }
 
 impl From<LockTokenError> for ProgramError {
@@ -0,0 +1,244 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{msg, pubkey::Pubkey};
+
+// Events are logged as EVENT_LOG_PREFIX followed by the hex encoded discriminant byte and the
+// borsh serialized event. Discriminants are part of the public log format and must never change.
+pub const EVENT_LOG_PREFIX: &str = "LOCK_EVENT:";
+
+pub trait Event: BorshSerialize + BorshDeserialize {
+    const DISCRIMINANT: u8;
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct FeeCollected {
+    pub lamports: u64,
+    pub fees_in_usd: u64,
+    pub oracle_price: i64,
+    pub oracle_expo: i32,
+    pub payer: Pubkey,
+    pub company_wallet: Pubkey,
+}
+
+impl Event for FeeCollected {
+    const DISCRIMINANT: u8 = 0;
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct LockCreated {
+    // Hash of the seeds, which identifies the lock without revealing its seeds
+    pub seeds_hash: [u8; 32],
+    pub locking_account: Pubkey,
+    pub mint: Pubkey,
+    pub destination: Pubkey,
+    pub total_amount: u64,
+    pub schedule_count: u32,
+}
+
+impl Event for LockCreated {
+    const DISCRIMINANT: u8 = 1;
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct LockUnlocked {
+    pub locking_account: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub remaining_locked: u64,
+}
+
+impl Event for LockUnlocked {
+    const DISCRIMINANT: u8 = 2;
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct LockTransferred {
+    pub locking_account: Pubkey,
+    pub old_destination: Pubkey,
+    pub new_destination: Pubkey,
+}
+
+impl Event for LockTransferred {
+    const DISCRIMINANT: u8 = 3;
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct LockExtended {
+    pub locking_account: Pubkey,
+    pub index: u32,
+    pub old_release_time: u64,
+    pub new_release_time: u64,
+}
+
+impl Event for LockExtended {
+    const DISCRIMINANT: u8 = 4;
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct EmergencyUnlockScheduled {
+    pub locking_account: Pubkey,
+    pub destination: Pubkey,
+    pub rescue_destination: Pubkey,
+    pub execute_after: u64,
+}
+
+impl Event for EmergencyUnlockScheduled {
+    const DISCRIMINANT: u8 = 5;
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct EmergencyUnlockCancelled {
+    pub locking_account: Pubkey,
+    pub rescue_destination: Pubkey,
+}
+
+impl Event for EmergencyUnlockCancelled {
+    const DISCRIMINANT: u8 = 6;
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct EmergencyUnlockExecuted {
+    pub locking_account: Pubkey,
+    pub old_destination: Pubkey,
+    pub rescue_destination: Pubkey,
+    pub amount: u64,
+}
+
+impl Event for EmergencyUnlockExecuted {
+    const DISCRIMINANT: u8 = 7;
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct UsdFeeCollected {
+    // Base units of the USD token transferred to the company
+    pub token_amount: u64,
+    pub fees_in_usd: u64,
+    pub payer: Pubkey,
+    pub company_token_account: Pubkey,
+}
+
+impl Event for UsdFeeCollected {
+    const DISCRIMINANT: u8 = 8;
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct TokenFeeCollected {
+    pub mint: Pubkey,
+    // Base units of the locked token taken from the schedules
+    pub amount: u64,
+    // Owner of the source token account, or its delegate
+    pub payer: Pubkey,
+    pub company_token_account: Pubkey,
+}
+
+impl Event for TokenFeeCollected {
+    const DISCRIMINANT: u8 = 9;
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct ClaimableAmount {
+    pub locking_account: Pubkey,
+    pub amount: u64,
+    // Unix timestamp the amount was computed at
+    pub now: u64,
+}
+
+impl Event for ClaimableAmount {
+    const DISCRIMINANT: u8 = 10;
+}
+
+// What is left of the lock after an Unlock, see state::RemainingSchedules
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct LockRemaining {
+    pub locking_account: Pubkey,
+    pub remaining_locked: u64,
+    pub schedule_count: u32,
+    // Zero once nothing is left to release
+    pub next_release_time: u64,
+}
+
+impl Event for LockRemaining {
+    const DISCRIMINANT: u8 = 11;
+}
+
+// Schedules moved out of a lock into a new one by TransferScheduleIndices
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct SchedulesTransferred {
+    pub locking_account: Pubkey,
+    pub new_locking_account: Pubkey,
+    pub new_destination: Pubkey,
+    pub amount: u64,
+    pub schedule_count: u32,
+}
+
+impl Event for SchedulesTransferred {
+    const DISCRIMINANT: u8 = 12;
+}
+
+// A finished lock closed by ClaimAndClose, its rent going to the refund account
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct LockClosed {
+    pub locking_account: Pubkey,
+    pub refund_account: Pubkey,
+    pub lamports: u64,
+}
+
+impl Event for LockClosed {
+    const DISCRIMINANT: u8 = 13;
+}
+
+// Pause flags set by PauseContract, zero when everything resumed. paused_at is the start of the latest pause,
+// unlocks proceed from unlock_pause_ends_at whatever the flags, zero without an unlock pause.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct PauseChanged {
+    pub admin: Pubkey,
+    pub pause_flags: u8,
+    pub paused_at: u64,
+    pub unlock_pause_ends_at: u64,
+}
+
+impl Event for PauseChanged {
+    const DISCRIMINANT: u8 = 14;
+}
+
+// The admin handed over by AcceptOwnership
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct OwnershipTransferred {
+    pub previous_admin: Pubkey,
+    pub new_admin: Pubkey,
+}
+
+impl Event for OwnershipTransferred {
+    const DISCRIMINANT: u8 = 15;
+}
+
+pub fn emit<E: Event>(event: &E) {
+    let mut data = vec![E::DISCRIMINANT];
+    // Serializing into a Vec cannot fail
+    event.serialize(&mut data).unwrap();
+    msg!("{}{}", EVENT_LOG_PREFIX, to_hex(&data));
+}
+
+// Decodes an event from a program log line, with or without the runtime's "Program log: " prefix
+pub fn decode<E: Event>(log: &str) -> Option<E> {
+    let start = log.find(EVENT_LOG_PREFIX)? + EVENT_LOG_PREFIX.len();
+    let data = from_hex(&log[start..])?;
+    let (&discriminant, rest) = data.split_first()?;
+    if discriminant != E::DISCRIMINANT {
+        return None;
+    }
+    E::try_from_slice(rest).ok()
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,159 @@
+// Checks shared by the processors for the program state account, the pause flags, the admin and locking accounts.
+// Every processor loads these accounts through here, so they all check the same things and fail with the same errors.
+
+use solana_program::{
+    account_info::AccountInfo, clock::Clock, msg, program_error::ProgramError, program_pack::Pack, pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+use crate::{
+    error::LockTokenError,
+    instruction::LockSeed,
+    pda::LockSigner,
+    pod::{PodLockGlobalState, ZeroCopy},
+    state::{
+        find_global_state_address, LockGlobalState, LockScheduleHeader, GLOBAL_STATE_VERSION,
+        MAX_PAUSE_DURATION_SECS, PAUSE_CREATE, PAUSE_TRANSFER,
+    },
+    time::now_u64,
+    utils::assert_authority_signed,
+};
+
+// The program state account is the global state address, owned by the program and initialized
+pub fn assert_global_state(program_id: &Pubkey, program_state_account: &AccountInfo) -> Result<(), ProgramError> {
+    let (program_state_account_key, _) = find_global_state_address(program_id);
+    if program_state_account_key != *program_state_account.key {
+        msg!("Provided program state account is invalid");
+        return Err(LockTokenError::InvalidGlobalState.into());
+    }
+
+    // Before InitializeContract the account does not exist, and the system program owns it
+    if program_state_account.data_is_empty() {
+        msg!("The state of program is uninitialized");
+        return Err(LockTokenError::GlobalStateUninitialized.into());
+    }
+
+    if *program_state_account.owner != *program_id {
+        msg!("Program should own program state account");
+        return Err(LockTokenError::InvalidGlobalState.into());
+    }
+
+    if !LockGlobalState::is_packed_initialized(&program_state_account.try_borrow_data()?) {
+        msg!("The state of program is uninitialized");
+        return Err(LockTokenError::GlobalStateUninitialized.into());
+    }
+
+    Ok(())
+}
+
+pub fn load_global_state(program_id: &Pubkey, program_state_account: &AccountInfo) -> Result<LockGlobalState, ProgramError> {
+    assert_global_state(program_id, program_state_account)?;
+    LockGlobalState::unpack_from_slice(&program_state_account.try_borrow_data()?)
+}
+
+// Fails while the pause flag, PAUSE_CREATE or PAUSE_TRANSFER, is set. Unlocks check assert_unlock_not_paused.
+pub fn assert_not_paused(program_global_state: &LockGlobalState, pause_flag: u8) -> Result<(), ProgramError> {
+    let is_paused = match pause_flag {
+        PAUSE_CREATE => program_global_state.pause_create,
+        PAUSE_TRANSFER => program_global_state.pause_transfer,
+        _ => program_global_state.pause_unlock,
+    };
+    if is_paused {
+        match pause_flag {
+            PAUSE_CREATE => msg!("Creating locks is paused"),
+            PAUSE_TRANSFER => msg!("Transferring and extending locks is paused"),
+            _ => msg!("Unlocking is paused"),
+        }
+        return Err(LockTokenError::ContractPaused.into());
+    }
+    Ok(())
+}
+
+// Unlocks only need the pause flag and the start of the pause, which are read in place from global states
+// of the current layout. The clock is only read while unlocks are paused, and PAUSE_UNLOCK stops blocking
+// MAX_PAUSE_DURATION_SECS after paused_at. The caller checks the account with assert_global_state first.
+pub fn assert_unlock_not_paused(program_state_account: &AccountInfo) -> Result<(), ProgramError> {
+    let data = program_state_account.try_borrow_data()?;
+    let (pause_unlock, paused_at) = if data.len() < LockGlobalState::LEN {
+        let program_global_state = LockGlobalState::unpack_from_slice(&data)?;
+        (program_global_state.pause_unlock, program_global_state.paused_at)
+    } else {
+        let program_global_state = PodLockGlobalState::try_from_bytes(&data)?;
+        if program_global_state.version != GLOBAL_STATE_VERSION {
+            return Err(ProgramError::InvalidAccountData)
+        }
+        (program_global_state.pause_unlock == 1, u64::from(program_global_state.paused_at))
+    };
+    if !pause_unlock {
+        return Ok(());
+    }
+    let now = now_u64(&Clock::get()?)?;
+    if !LockGlobalState::is_unlock_paused_at(pause_unlock, paused_at, now) {
+        msg!("The unlock pause of {} has outlasted {} seconds", paused_at, MAX_PAUSE_DURATION_SECS);
+        return Ok(());
+    }
+    msg!("Unlocking is paused");
+    Err(LockTokenError::ContractPaused.into())
+}
+
+// The admin of the program state signed, through its signers when it is a multisig account
+pub fn assert_admin(
+    program_global_state: &LockGlobalState,
+    admin_account: &AccountInfo,
+    signer_accounts: &[AccountInfo],
+) -> Result<(), ProgramError> {
+    assert_authority_signed(admin_account, signer_accounts)?;
+
+    if program_global_state.admin != *admin_account.key {
+        msg!("Program admin account is not the admin of the program");
+        return Err(LockTokenError::NotAdmin.into());
+    }
+
+    Ok(())
+}
+
+// How the program signs for the locking account. Locking accounts initialized with InitDerived are signed for
+// from the derivation recorded in their header whatever the seeds, the others from the seeds.
+pub fn lock_signer(program_id: &Pubkey, locking_account: &AccountInfo, seeds: LockSeed) -> Result<LockSigner, ProgramError> {
+    if *locking_account.owner == *program_id {
+        if let Some(lock_signer) = LockScheduleHeader::unpack_derivation(&locking_account.try_borrow_data()?) {
+            return Ok(lock_signer);
+        }
+    }
+    Ok(LockSigner::Seeds(seeds))
+}
+
+// The locking account is the one of the seeds and owned by the program
+pub fn assert_lock(program_id: &Pubkey, locking_account: &AccountInfo, seeds: LockSeed) -> Result<LockSigner, ProgramError> {
+    let lock_signer = lock_signer(program_id, locking_account, seeds)?;
+    if lock_signer.address(program_id)? != *locking_account.key {
+        msg!("Invalid locking account key");
+        return Err(LockTokenError::InvalidLockingAccount.into());
+    }
+
+    if *locking_account.owner != *program_id {
+        msg!("Program should own locking account");
+        return Err(LockTokenError::InvalidLockingAccount.into());
+    }
+
+    Ok(lock_signer)
+}
+
+// Checks the locking account with assert_lock and returns how the program signs for it, its header and the
+// length of the header
+pub fn load_lock(
+    program_id: &Pubkey,
+    locking_account: &AccountInfo,
+    seeds: LockSeed,
+) -> Result<(LockSigner, LockScheduleHeader, usize), ProgramError> {
+    let lock_signer = assert_lock(program_id, locking_account, seeds)?;
+
+    let data = locking_account.try_borrow_data()?;
+    let header_len = LockScheduleHeader::header_len(data.len());
+    if data.len() < header_len {
+        return Err(ProgramError::InvalidAccountData)
+    }
+    let header = LockScheduleHeader::unpack_from_slice(&data[..header_len])?;
+
+    Ok((lock_signer, header, header_len))
+}
@@ -0,0 +1,671 @@
+use solana_program::{hash::hash, pubkey::Pubkey};
+
+use std::fmt::Write;
+
+use crate::{
+    instruction::account_requirements,
+    state::{LockGlobalState, LockScheduleHeader, TokenState, WalletState, MAX_CANDIDATE_WALLETS, MAX_FEE_DISCOUNT_TIERS},
+};
+
+// Version of the IDL specification of Anchor the generated IDL follows
+pub const IDL_SPEC: &str = "0.1.0";
+
+// Type of an instruction argument or a field, named like in an Anchor IDL
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IdlType {
+    Bool,
+    U8,
+    U16,
+    U32,
+    U64,
+    PublicKey,
+    String,
+    Array(&'static IdlType, usize),
+    Vec(&'static IdlType),
+    Option(&'static IdlType),
+    // One of TYPES
+    Defined(&'static str),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IdlField {
+    pub name: &'static str,
+    pub ty: IdlType,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IdlTypeKind {
+    Struct(&'static [IdlField]),
+    // Variants without fields, encoded as their u8 index
+    Enum(&'static [&'static str]),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IdlTypeDef {
+    pub name: &'static str,
+    pub kind: IdlTypeKind,
+}
+
+// An instruction of the Anchor encoding: the discriminator followed by the borsh serialized args.
+// The accounts are the ones of account_requirements for the legacy tag.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IdlInstruction {
+    pub name: &'static str,
+    pub discriminator: [u8; 8],
+    pub tag: u8,
+    pub args: &'static [IdlField],
+}
+
+const fn field(name: &'static str, ty: IdlType) -> IdlField {
+    IdlField { name, ty }
+}
+
+const SEEDS: IdlField = field("seeds", IdlType::Array(&IdlType::U8, 32));
+
+// Every LockTokenInstruction in declaration order, which is the variant index borsh encodes.
+// Each discriminator is the first 8 bytes of the sha256 of "global:" and the name, see sighash.
+// Legacy data starting with a discriminator is taken as that instruction, so its seeds or keys would have
+// to start with the 7 remaining bytes of it.
+pub const INSTRUCTIONS: &[IdlInstruction] = &[
+    IdlInstruction {
+        name: "init",
+        discriminator: [220, 59, 207, 236, 108, 250, 47, 100],
+        tag: 0,
+        args: &[SEEDS, field("number_of_schedules", IdlType::U32)],
+    },
+    IdlInstruction {
+        name: "create",
+        discriminator: [24, 30, 200, 40, 5, 28, 7, 119],
+        tag: 28,
+        args: &[
+            SEEDS,
+            field("mint_address", IdlType::PublicKey),
+            field("destination_token_address", IdlType::PublicKey),
+            field("schedules", IdlType::Vec(&IdlType::Defined("Schedule"))),
+            field("deadline", IdlType::U64),
+            field("max_total", IdlType::Option(&IdlType::U64)),
+            field("is_nft", IdlType::Bool),
+            field("is_immutable", IdlType::Bool),
+            field("pay_fee_in_usd", IdlType::Bool),
+            field("pay_fee_in_token", IdlType::Bool),
+            field("with_fee_discount", IdlType::Bool),
+            field("time_basis", IdlType::Defined("TimeBasis")),
+            field("split_destinations", IdlType::Vec(&IdlType::Defined("SplitDestination"))),
+            field("with_wallet_state", IdlType::Bool),
+            field("min_claim_interval_secs", IdlType::U32),
+        ],
+    },
+    IdlInstruction {
+        name: "create_native",
+        discriminator: [123, 73, 3, 28, 255, 180, 106, 63],
+        tag: 12,
+        args: &[
+            SEEDS,
+            field("destination_address", IdlType::PublicKey),
+            field("schedules", IdlType::Vec(&IdlType::Defined("Schedule"))),
+        ],
+    },
+    IdlInstruction {
+        name: "unlock",
+        discriminator: [101, 155, 40, 21, 158, 189, 56, 203],
+        tag: 2,
+        args: &[SEEDS, field("deadline", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "transfer_locks",
+        discriminator: [21, 77, 22, 57, 54, 178, 49, 158],
+        tag: 3,
+        args: &[SEEDS],
+    },
+    IdlInstruction {
+        name: "extend_lock_duration",
+        discriminator: [177, 105, 196, 129, 153, 137, 136, 230],
+        tag: 4,
+        args: &[SEEDS, field("index", IdlType::U32), field("release_time", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "pause_contract",
+        discriminator: [210, 36, 5, 85, 177, 65, 35, 89],
+        tag: 5,
+        args: &[field("pause_flags", IdlType::U8)],
+    },
+    IdlInstruction {
+        name: "set_fee_params",
+        discriminator: [217, 73, 193, 227, 183, 14, 200, 188],
+        tag: 6,
+        args: &[
+            field("price_estimator", IdlType::PublicKey),
+            field("usd_token_address", IdlType::PublicKey),
+            field("fees_in_usd", IdlType::U64),
+            field("company_wallet", IdlType::PublicKey),
+            field("candidate_wallets", IdlType::Array(&IdlType::PublicKey, MAX_CANDIDATE_WALLETS)),
+            field("present_fields", IdlType::U8),
+        ],
+    },
+    IdlInstruction {
+        name: "set_fees_in_usd",
+        discriminator: [160, 88, 139, 193, 222, 191, 216, 13],
+        tag: 7,
+        args: &[field("fees_in_usd", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "set_company_wallet",
+        discriminator: [103, 25, 27, 36, 174, 16, 180, 55],
+        tag: 8,
+        args: &[field("company_wallet", IdlType::PublicKey)],
+    },
+    IdlInstruction {
+        name: "set_max_price_age",
+        discriminator: [10, 95, 102, 138, 170, 177, 225, 67],
+        tag: 13,
+        args: &[field("max_price_age_slots", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "set_free_token",
+        discriminator: [120, 54, 142, 100, 161, 247, 75, 151],
+        tag: 9,
+        args: &[field("mint_address", IdlType::PublicKey), field("is_free", IdlType::Bool)],
+    },
+    IdlInstruction {
+        name: "set_token_fee",
+        discriminator: [219, 237, 16, 50, 227, 85, 231, 243],
+        tag: 14,
+        args: &[
+            field("mint_address", IdlType::PublicKey),
+            field("fee_in_usd", IdlType::Option(&IdlType::U64)),
+        ],
+    },
+    IdlInstruction {
+        name: "initialize_global_state",
+        discriminator: [232, 254, 209, 244, 123, 89, 154, 207],
+        tag: 15,
+        args: &[field("admin", IdlType::PublicKey)],
+    },
+    IdlInstruction {
+        name: "propose_owner",
+        discriminator: [90, 57, 141, 110, 196, 241, 172, 39],
+        tag: 16,
+        args: &[field("new_admin", IdlType::PublicKey)],
+    },
+    IdlInstruction {
+        name: "accept_ownership",
+        discriminator: [172, 23, 43, 13, 238, 213, 85, 150],
+        tag: 17,
+        args: &[],
+    },
+    IdlInstruction {
+        name: "cancel_ownership_transfer",
+        discriminator: [2, 184, 195, 105, 138, 142, 154, 75],
+        tag: 18,
+        args: &[],
+    },
+    IdlInstruction {
+        name: "set_lock_minimums",
+        discriminator: [165, 232, 96, 64, 165, 112, 179, 83],
+        tag: 19,
+        args: &[
+            field("min_lock_duration_secs", IdlType::U64),
+            field("min_lock_amount", IdlType::U64),
+        ],
+    },
+    IdlInstruction {
+        name: "propose_transfer_locks",
+        discriminator: [20, 237, 53, 109, 22, 210, 15, 200],
+        tag: 20,
+        args: &[SEEDS, field("new_destination", IdlType::PublicKey)],
+    },
+    IdlInstruction {
+        name: "accept_transfer_locks",
+        discriminator: [116, 135, 6, 181, 234, 118, 248, 231],
+        tag: 21,
+        args: &[SEEDS],
+    },
+    IdlInstruction {
+        name: "cancel_transfer_locks",
+        discriminator: [68, 238, 253, 88, 168, 209, 30, 180],
+        tag: 22,
+        args: &[SEEDS],
+    },
+    IdlInstruction {
+        name: "extend_lock_durations",
+        discriminator: [243, 94, 95, 245, 110, 234, 72, 50],
+        tag: 23,
+        args: &[SEEDS, field("extensions", IdlType::Vec(&IdlType::Defined("Extension")))],
+    },
+    IdlInstruction {
+        name: "relock",
+        discriminator: [235, 187, 184, 112, 143, 63, 95, 102],
+        tag: 24,
+        args: &[SEEDS, field("index", IdlType::U32), field("new_release_time", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "batch_unlock",
+        discriminator: [55, 79, 121, 12, 106, 201, 35, 92],
+        tag: 25,
+        args: &[field("seeds_list", IdlType::Vec(&IdlType::Array(&IdlType::U8, 32)))],
+    },
+    IdlInstruction {
+        name: "create_recurring",
+        discriminator: [223, 187, 78, 98, 166, 235, 2, 166],
+        tag: 26,
+        args: &[
+            SEEDS,
+            field("mint_address", IdlType::PublicKey),
+            field("destination_token_address", IdlType::PublicKey),
+            field("start_time", IdlType::U64),
+            field("period_secs", IdlType::U64),
+            field("periods", IdlType::U32),
+            field("amount_per_period", IdlType::U64),
+        ],
+    },
+    IdlInstruction {
+        name: "create_vesting",
+        discriminator: [135, 184, 171, 156, 197, 162, 246, 44],
+        tag: 27,
+        args: &[
+            SEEDS,
+            field("mint_address", IdlType::PublicKey),
+            field("destination_token_address", IdlType::PublicKey),
+            field("cliff_time", IdlType::U64),
+            field("cliff_bps", IdlType::U16),
+            field("vesting_end", IdlType::U64),
+            field("total_amount", IdlType::U64),
+        ],
+    },
+    IdlInstruction {
+        name: "migrate_lock",
+        discriminator: [65, 245, 153, 220, 203, 85, 155, 165],
+        tag: 29,
+        args: &[SEEDS],
+    },
+    IdlInstruction {
+        name: "schedule_emergency_unlock",
+        discriminator: [232, 173, 81, 247, 15, 161, 14, 229],
+        tag: 30,
+        args: &[SEEDS, field("rescue_destination", IdlType::PublicKey)],
+    },
+    IdlInstruction {
+        name: "execute_emergency_unlock",
+        discriminator: [127, 17, 250, 210, 29, 168, 208, 38],
+        tag: 31,
+        args: &[SEEDS],
+    },
+    IdlInstruction {
+        name: "cancel_emergency_unlock",
+        discriminator: [213, 35, 195, 118, 142, 60, 186, 140],
+        tag: 32,
+        args: &[SEEDS],
+    },
+    // Its first byte is BORSH_FORMAT, which is no borsh instruction as the second byte is past the last variant index
+    IdlInstruction {
+        name: "sweep_excess",
+        discriminator: [255, 74, 219, 182, 1, 126, 233, 6],
+        tag: 33,
+        args: &[SEEDS],
+    },
+    IdlInstruction {
+        name: "set_fee_bps_in_token",
+        discriminator: [48, 214, 66, 12, 157, 84, 13, 212],
+        tag: 34,
+        args: &[field("fee_bps_in_token", IdlType::U16)],
+    },
+    IdlInstruction {
+        name: "set_fee_discount_tiers",
+        discriminator: [194, 149, 242, 70, 0, 136, 77, 167],
+        tag: 35,
+        args: &[field(
+            "fee_discount_tiers",
+            IdlType::Array(&IdlType::Defined("FeeDiscountTier"), MAX_FEE_DISCOUNT_TIERS),
+        )],
+    },
+    IdlInstruction {
+        name: "get_claimable_amount",
+        discriminator: [216, 219, 61, 62, 140, 223, 122, 15],
+        tag: 36,
+        args: &[SEEDS],
+    },
+    IdlInstruction {
+        name: "create_multi",
+        discriminator: [147, 86, 166, 197, 110, 143, 57, 218],
+        tag: 37,
+        args: &[
+            SEEDS,
+            field("mint_address", IdlType::PublicKey),
+            field("destination_token_address", IdlType::PublicKey),
+            field("schedules", IdlType::Vec(&IdlType::Defined("MultiSchedule"))),
+        ],
+    },
+    IdlInstruction {
+        name: "set_lock_metadata",
+        discriminator: [240, 234, 21, 243, 41, 14, 28, 112],
+        tag: 38,
+        args: &[SEEDS, field("name", IdlType::String), field("uri", IdlType::String)],
+    },
+    IdlInstruction {
+        name: "close_lock_metadata",
+        discriminator: [57, 35, 171, 57, 16, 167, 164, 37],
+        tag: 39,
+        args: &[SEEDS],
+    },
+    IdlInstruction {
+        name: "reclaim_uninitialized_lock",
+        discriminator: [103, 139, 177, 83, 220, 82, 144, 54],
+        tag: 40,
+        args: &[SEEDS],
+    },
+    IdlInstruction {
+        name: "set_fee_exempt_wallet",
+        discriminator: [103, 238, 177, 195, 42, 181, 102, 188],
+        tag: 41,
+        args: &[field("wallet", IdlType::PublicKey), field("is_exempt", IdlType::Bool)],
+    },
+    IdlInstruction {
+        name: "set_max_lock_duration",
+        discriminator: [0, 84, 139, 184, 228, 94, 66, 186],
+        tag: 42,
+        args: &[field("max_lock_duration_secs", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "transfer_schedule_indices",
+        discriminator: [140, 131, 78, 241, 143, 140, 106, 227],
+        tag: 43,
+        args: &[
+            SEEDS,
+            field("new_seeds", IdlType::Array(&IdlType::U8, 32)),
+            field("indices", IdlType::Vec(&IdlType::U32)),
+        ],
+    },
+    IdlInstruction {
+        name: "approve_lock_delegate",
+        discriminator: [25, 124, 1, 204, 104, 100, 186, 16],
+        tag: 44,
+        args: &[SEEDS, field("delegate", IdlType::PublicKey)],
+    },
+    IdlInstruction {
+        name: "revoke_lock_delegate",
+        discriminator: [10, 136, 163, 242, 132, 237, 149, 46],
+        tag: 45,
+        args: &[SEEDS],
+    },
+    IdlInstruction {
+        name: "claim_and_close",
+        discriminator: [177, 41, 244, 95, 42, 114, 27, 99],
+        tag: 46,
+        args: &[SEEDS],
+    },
+    IdlInstruction {
+        name: "snapshot_mint_locked",
+        discriminator: [64, 101, 120, 21, 136, 145, 143, 45],
+        tag: 47,
+        args: &[field("mint", IdlType::PublicKey)],
+    },
+    IdlInstruction {
+        name: "unlock_with_permit",
+        discriminator: [90, 109, 129, 253, 69, 10, 47, 185],
+        tag: 48,
+        args: &[SEEDS, field("expiry", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "init_derived",
+        discriminator: [180, 175, 206, 134, 165, 123, 212, 55],
+        tag: 49,
+        args: &[
+            field("creator", IdlType::PublicKey),
+            field("mint_address", IdlType::PublicKey),
+            field("nonce", IdlType::U64),
+            field("number_of_schedules", IdlType::U32),
+        ],
+    },
+    IdlInstruction {
+        name: "set_owner_token_mint",
+        discriminator: [49, 181, 213, 155, 115, 14, 74, 79],
+        tag: 50,
+        args: &[field("new_mint", IdlType::PublicKey)],
+    },
+    IdlInstruction {
+        name: "unlock_amount",
+        discriminator: [129, 222, 224, 95, 87, 235, 212, 227],
+        tag: 51,
+        args: &[SEEDS, field("amount", IdlType::U64)],
+    },
+    IdlInstruction {
+        name: "propose_candidate_wallets",
+        discriminator: [32, 6, 73, 10, 195, 61, 132, 190],
+        tag: 52,
+        args: &[field("candidate_wallets", IdlType::Array(&IdlType::PublicKey, MAX_CANDIDATE_WALLETS))],
+    },
+    IdlInstruction {
+        name: "accept_candidate_wallets",
+        discriminator: [73, 207, 27, 170, 52, 36, 25, 151],
+        tag: 53,
+        args: &[],
+    },
+];
+
+// Types of the arguments, with ExtendLockDurations entries as Extension
+pub const TYPES: &[IdlTypeDef] = &[
+    IdlTypeDef {
+        name: "Schedule",
+        kind: IdlTypeKind::Struct(&[field("release_time", IdlType::U64), field("amount", IdlType::U64)]),
+    },
+    IdlTypeDef {
+        name: "MultiSchedule",
+        kind: IdlTypeKind::Struct(&[
+            field("release_time", IdlType::U64),
+            field("amount", IdlType::U64),
+            field("destination_token_address", IdlType::PublicKey),
+        ]),
+    },
+    IdlTypeDef {
+        name: "SplitDestination",
+        kind: IdlTypeKind::Struct(&[
+            field("destination_address", IdlType::PublicKey),
+            field("share_bps", IdlType::U16),
+        ]),
+    },
+    IdlTypeDef {
+        name: "FeeDiscountTier",
+        kind: IdlTypeKind::Struct(&[field("min_balance", IdlType::U64), field("discount_bps", IdlType::U16)]),
+    },
+    IdlTypeDef {
+        name: "Extension",
+        kind: IdlTypeKind::Struct(&[field("index", IdlType::U32), field("release_time", IdlType::U64)]),
+    },
+    IdlTypeDef {
+        name: "TimeBasis",
+        kind: IdlTypeKind::Enum(&["UnixTimestamp", "Slot"]),
+    },
+];
+
+// Anchor discriminator of the name in the namespace, "global" for instructions and "account" for accounts
+pub fn sighash(namespace: &str, name: &str) -> [u8; 8] {
+    let mut discriminator = [0; 8];
+    discriminator.copy_from_slice(&hash(format!("{}:{}", namespace, name).as_bytes()).to_bytes()[..8]);
+    discriminator
+}
+
+// Position in INSTRUCTIONS of the instruction with the discriminator
+pub fn instruction_index(discriminator: &[u8]) -> Option<usize> {
+    INSTRUCTIONS
+        .iter()
+        .position(|instruction| instruction.discriminator[..] == *discriminator)
+}
+
+// Anchor account discriminators of the program accounts, the sighash of "account" and the name.
+// The accounts keep their Pack layouts without a discriminator, so they are not part of the IDL. Clients
+// tagging decoded accounts, e.g. in a cache shared with Anchor programs, use these.
+pub trait AccountDiscriminator {
+    const NAME: &'static str;
+    const DISCRIMINATOR: [u8; 8];
+}
+
+impl AccountDiscriminator for LockScheduleHeader {
+    const NAME: &'static str = "LockScheduleHeader";
+    const DISCRIMINATOR: [u8; 8] = [16, 123, 163, 6, 250, 88, 148, 30];
+}
+
+impl AccountDiscriminator for LockGlobalState {
+    const NAME: &'static str = "LockGlobalState";
+    const DISCRIMINATOR: [u8; 8] = [96, 43, 246, 210, 39, 162, 42, 181];
+}
+
+impl AccountDiscriminator for TokenState {
+    const NAME: &'static str = "TokenState";
+    const DISCRIMINATOR: [u8; 8] = [218, 112, 6, 149, 55, 186, 168, 163];
+}
+
+impl AccountDiscriminator for WalletState {
+    const NAME: &'static str = "WalletState";
+    const DISCRIMINATOR: [u8; 8] = [126, 186, 0, 158, 92, 223, 167, 68];
+}
+
+fn write_json_string(json: &mut String, value: &str) {
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if (c as u32) < 0x20 => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+fn write_json_bytes(json: &mut String, bytes: &[u8]) {
+    json.push('[');
+    for (i, byte) in bytes.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        write!(json, "{}", byte).unwrap();
+    }
+    json.push(']');
+}
+
+fn write_json_type(json: &mut String, ty: &IdlType) {
+    match ty {
+        IdlType::Bool => json.push_str("\"bool\""),
+        IdlType::U8 => json.push_str("\"u8\""),
+        IdlType::U16 => json.push_str("\"u16\""),
+        IdlType::U32 => json.push_str("\"u32\""),
+        IdlType::U64 => json.push_str("\"u64\""),
+        IdlType::PublicKey => json.push_str("\"pubkey\""),
+        IdlType::String => json.push_str("\"string\""),
+        IdlType::Array(item, len) => {
+            json.push_str("{\"array\":[");
+            write_json_type(json, item);
+            write!(json, ",{}]}}", len).unwrap();
+        }
+        IdlType::Vec(item) => {
+            json.push_str("{\"vec\":");
+            write_json_type(json, item);
+            json.push('}');
+        }
+        IdlType::Option(item) => {
+            json.push_str("{\"option\":");
+            write_json_type(json, item);
+            json.push('}');
+        }
+        IdlType::Defined(name) => {
+            json.push_str("{\"defined\":{\"name\":");
+            write_json_string(json, name);
+            json.push_str("}}");
+        }
+    }
+}
+
+fn write_json_fields(json: &mut String, fields: &[IdlField]) {
+    json.push('[');
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        json.push_str("{\"name\":");
+        write_json_string(json, field.name);
+        json.push_str(",\"type\":");
+        write_json_type(json, &field.ty);
+        json.push('}');
+    }
+    json.push(']');
+}
+
+// The IDL of the program deployed at program_id in the format of Anchor, with the instructions, their accounts
+// and args, and the types
+pub fn idl_json(program_id: &Pubkey) -> String {
+    let mut json = String::new();
+    json.push_str("{\"address\":");
+    write_json_string(&mut json, &program_id.to_string());
+    json.push_str(",\"metadata\":{\"name\":\"lock_token\",\"version\":");
+    write_json_string(&mut json, env!("CARGO_PKG_VERSION"));
+    json.push_str(",\"spec\":");
+    write_json_string(&mut json, IDL_SPEC);
+    json.push_str("},\"instructions\":[");
+    for (i, instruction) in INSTRUCTIONS.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        json.push_str("{\"name\":");
+        write_json_string(&mut json, instruction.name);
+        json.push_str(",\"discriminator\":");
+        write_json_bytes(&mut json, &instruction.discriminator);
+        json.push_str(",\"accounts\":[");
+        for (j, account) in account_requirements(instruction.tag).iter().enumerate() {
+            if j > 0 {
+                json.push(',');
+            }
+            json.push_str("{\"name\":");
+            write_json_string(&mut json, account.name);
+            if account.writable {
+                json.push_str(",\"writable\":true");
+            }
+            if account.signer {
+                json.push_str(",\"signer\":true");
+            }
+            json.push_str(",\"docs\":[");
+            write_json_string(&mut json, account.description);
+            json.push_str("]}");
+        }
+        json.push_str("],\"args\":");
+        write_json_fields(&mut json, instruction.args);
+        json.push('}');
+    }
+    json.push_str("],\"types\":[");
+    for (i, type_def) in TYPES.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        json.push_str("{\"name\":");
+        write_json_string(&mut json, type_def.name);
+        match type_def.kind {
+            IdlTypeKind::Struct(fields) => {
+                json.push_str(",\"type\":{\"kind\":\"struct\",\"fields\":");
+                write_json_fields(&mut json, fields);
+                json.push_str("}}");
+            }
+            IdlTypeKind::Enum(variants) => {
+                json.push_str(",\"type\":{\"kind\":\"enum\",\"variants\":[");
+                for (j, variant) in variants.iter().enumerate() {
+                    if j > 0 {
+                        json.push(',');
+                    }
+                    json.push_str("{\"name\":");
+                    write_json_string(&mut json, variant);
+                    json.push('}');
+                }
+                json.push_str("]}}");
+            }
+        }
+    }
+    json.push_str("]}");
+    json
+}
+
+// Writes the IDL, e.g. to target/idl/lock_token.json for the Anchor TypeScript client
+#[cfg(not(target_arch = "bpf"))]
+pub fn write_idl(path: &std::path::Path, program_id: &Pubkey) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, idl_json(program_id))
+}
@@ -1,17 +1,93 @@
-use crate::error::LockTokenError;
+#[cfg(feature = "anchor-compat")]
+use crate::idl;
+use crate::{error::LockTokenError, pda::find_lock_address, state::{find_global_state_address, legacy_global_state_address, find_lock_metadata_address, find_wallet_state_address, token_state_address, FeeDiscountTier, SplitDestination, FEE_PARAMS_ALL, MAX_CANDIDATE_WALLETS, MAX_FEE_DISCOUNT_TIERS, MAX_SPLIT_DESTINATIONS}, time::TimeBasis};
 
 use solana_program::{
+    ed25519_program,
+    hash::hashv,
     instruction::{AccountMeta, Instruction},
     msg,
     program_error::ProgramError,
-    pubkey::Pubkey
+    pubkey::Pubkey,
+    sysvar,
 };
 
-use std::convert::TryInto;
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
 use std::mem::size_of;
 
+// Seeds of a locking account, which is the program address derived from them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+pub struct LockSeed(pub [u8; 32]);
+
+impl LockSeed {
+    // Seeds derived from the lock creator, the mint and a nonce chosen by the creator.
+    // Not every hash derives a program address, so the caller moves on to the next nonce when
+    // locking_account_address fails. New locks are better derived with InitDerived, see pda::find_lock_address.
+    pub fn from_parts(creator: &Pubkey, mint: &Pubkey, nonce: u64) -> Self {
+        Self(hashv(&[creator.as_ref(), mint.as_ref(), &nonce.to_le_bytes()]).to_bytes())
+    }
+
+    // Random seeds which are guaranteed to derive a locking account of the program. Derives the address
+    // without locking_account_address, whose log on failure panics outside of a program under ProgramTest.
+    #[cfg(not(target_arch = "bpf"))]
+    pub fn random(program_id: &Pubkey) -> Self {
+        loop {
+            let seed = Self(rand::random());
+            if Pubkey::create_program_address(&[&seed.0], program_id).is_ok() {
+                return seed;
+            }
+        }
+    }
+
+    pub fn locking_account_address(&self, program_id: &Pubkey) -> Result<Pubkey, ProgramError> {
+        Pubkey::create_program_address(&[&self.0], program_id).map_err(|_| {
+            msg!("The seeds {} do not derive a locking account", self);
+            ProgramError::InvalidSeeds
+        })
+    }
+
+    pub fn to_bytes(self) -> [u8; 32] {
+        self.0
+    }
+}
+
+impl AsRef<[u8]> for LockSeed {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<[u8; 32]> for LockSeed {
+    fn from(seed: [u8; 32]) -> Self {
+        Self(seed)
+    }
+}
+
+impl TryFrom<&[u8]> for LockSeed {
+    type Error = ProgramError;
+
+    fn try_from(seed: &[u8]) -> Result<Self, Self::Error> {
+        seed.try_into()
+            .map(Self)
+            .map_err(|_| LockTokenError::InvalidInstruction.into())
+    }
+}
+
+// Base58, like a pubkey
+impl fmt::Display for LockSeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Pubkey::new(&self.0))
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
 pub struct Schedule {
     pub release_time: u64,
     pub amount: u64,
@@ -19,67 +95,366 @@ pub struct Schedule {
 
 pub const SCHEDULE_SIZE: usize = 16;
 
+// Schedule of a CreateMulti, released to its own destination token account.
+// The default pubkey releases it to the destination of the lock.
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+pub struct MultiSchedule {
+    pub release_time: u64,
+    pub amount: u64,
+    pub destination_token_address: Pubkey,
+}
+
+pub const MULTI_SCHEDULE_SIZE: usize = 48;
+
+// Most schedules of a Create, so the schedules fit in an account created through a CPI
+pub const MAX_SCHEDULES: u16 = 600;
+
+// Most periods of a CreateRecurring, which expands into as many schedules
+pub const MAX_RECURRING_PERIODS: u32 = MAX_SCHEDULES as u32;
+
+// Schedules of a recurring lock: amount_per_period is released every period_secs, the first one at start_time
+pub fn recurring_schedules(
+    start_time: u64,
+    period_secs: u64,
+    periods: u32,
+    amount_per_period: u64,
+) -> Result<Vec<Schedule>, ProgramError> {
+    if period_secs == 0 {
+        msg!("The period of a recurring lock can not be zero");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if periods == 0 || periods > MAX_RECURRING_PERIODS {
+        msg!("A recurring lock has between 1 and {} periods", MAX_RECURRING_PERIODS);
+        return Err(ProgramError::InvalidArgument);
+    }
+    (0..periods as u64)
+        .map(|period| {
+            let release_time = period
+                .checked_mul(period_secs)
+                .and_then(|offset| start_time.checked_add(offset))
+                .ok_or(LockTokenError::MathOverflow)?;
+            Ok(Schedule {
+                release_time,
+                amount: amount_per_period,
+            })
+        })
+        .collect()
+}
+
+// Create is encoded as tag 28, the seeds, the mint, the destination, the u16 schedule count, the options block
+// and exactly that many schedules. Tag 11 has no schedule count and is still accepted.
+// Length of the options block of the extended Create encoding.
+// The deadline takes the first 8 bytes, the optional max total the next 8 bytes and the flags the byte after.
+// Setting the flags requires the max total, None is then written as u64::MAX.
+// Split destinations follow the flags as a u8 count and that many SPLIT_DESTINATION_SIZE entries.
+// The u32 minimum claim interval follows the split destinations, which are then written even when there are none.
+pub const CREATE_DEADLINE_OPTION_LEN: usize = 8;
+pub const CREATE_OPTIONS_LEN: usize = 16;
+pub const CREATE_FLAGS_OPTION_LEN: usize = 17;
+
+// The destination token account and the u16 share in basis points
+pub const SPLIT_DESTINATION_SIZE: usize = 34;
+
+// Bits of the Create flags option
+pub const CREATE_FLAG_NFT: u8 = 1;
+pub const CREATE_FLAG_IMMUTABLE: u8 = 1 << 1;
+pub const CREATE_FLAG_PAY_FEE_IN_USD: u8 = 1 << 2;
+pub const CREATE_FLAG_PAY_FEE_IN_TOKEN: u8 = 1 << 3;
+pub const CREATE_FLAG_FEE_DISCOUNT: u8 = 1 << 4;
+pub const CREATE_FLAG_SLOT_BASIS: u8 = 1 << 5;
+pub const CREATE_FLAG_WALLET_STATE: u8 = 1 << 6;
+
+// Token accounts of a Create fee paid in the USD token, see CREATE_FLAG_PAY_FEE_IN_USD
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UsdFeePayment {
+    pub payer_token_account: Pubkey,
+    pub usd_mint: Pubkey,
+    pub company_token_account: Pubkey,
+}
+
+// How the create builder pays the fee of the company
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CreateFeePayment {
+    Sol,
+    Usd(UsdFeePayment),
+    // Paid out of the schedules into a token account of the locked mint owned by the company wallet,
+    // see CREATE_FLAG_PAY_FEE_IN_TOKEN
+    Token { company_token_account: Pubkey },
+}
+
+// Min balance and discount of a SetFeeDiscountTiers tier
+pub const FEE_DISCOUNT_TIER_SIZE: usize = 10;
+
+// Index and release time of an ExtendLockDurations entry
+pub const EXTENSION_SIZE: usize = 12;
+
+// Accounts of every lock of a BatchUnlock: the locking account, the locking token account and the destination token account
+pub const BATCH_UNLOCK_GROUP_LEN: usize = 3;
+
+// First byte of instruction data in the borsh format, which no tag of the legacy format uses.
+// It is followed by the borsh serialized LockTokenInstruction, see pack_borsh. The legacy format stays the default.
+pub const BORSH_FORMAT: u8 = 0xff;
+
+// Start of every UnlockWithPermit permit message, so no other message signed by the owner can pass for a permit
+pub const PERMIT_MESSAGE_PREFIX: &[u8] = b"lock-token unlock permit";
+
+// Length of the ed25519 program instruction data before the public key, the signature and the message of its
+// only signature: the signature count, a padding byte and the seven u16 offsets of the signature
+pub const ED25519_DATA_START: usize = 16;
+
+// Borsh encodes the variant by its position, so new instructions are only ever added at the end
 #[repr(C)]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
 pub enum LockTokenInstruction {
     /* Inits a new lock schedule.
     *  A lock schedule consists of a LockScheduleHeader and array of LockSchedule s.
     *  The header consists of destination address, token mint address and initialized flag.
     *  LockTokenInstruction::Init instruction creates a program account from the seeds array which has data size to fit the number of schedule data.
+    *  The number of schedules is between 1 and MAX_LOCKING_ACCOUNT_SCHEDULES, and the locking account must not hold data yet.
+    *  Lamports already sent to the locking account are kept, the payer only tops it up to rent exemption.
+    *  The payer is recorded in the locking account until Create, so it can take the rent back with
+    *  ReclaimUninitializedLock when no lock is created.
     *
     *  - Accounts
     *  0. `[]` The system program account
-    *  1. `[]` The sysvar Rent account
-    *  2. `[signer]` The fee payer account
-    *  3. `[]` The locking account
+    *  1. `[]` The program state account
+    *  2. `[]` The sysvar Rent account
+    *  3. `[writable, signer]` The fee payer account
+    *  4. `[writable]` The locking account
     */
     Init {
-        seeds: [u8; 32],
+        seeds: LockSeed,
         number_of_schedules: u32,
     },
 
     /* Creates a new lock schedule.
     *  Actually, fills data into account which is created by Init instruction.
     *  LockTokenInstruction::Init instruction creates a program account from the seeds array which has data size to fit the number of schedule data.
+    *  A locking account initialized with more schedules than the lock holds is shrunk to fit them, and the rent of the
+    *  unused slots goes back to the payer of Init when it is the fee payer.
     *  The locking token account is needed to be derived from the locking account and token mint address by associated token account porogram.
-    *  The source token account owner need to pay transaction fee for both solana network and company.
+    *  If the locking token account does not exist yet, it is created by the associated token account program and funded by the fee payer.
+    *  The fee payer pays the fee of the company and the rent of the accounts created along the lock, the source token account
+    *  owner only authorizes the token transfer. Both can be the same account.
+    *  A non-zero deadline rejects the instruction once the current unix timestamp is past it.
+    *  The lock and its total are counted in the token state of the mint.
+    *  When max_total is set, the instruction is rejected if the total of the schedules is larger than it.
+    *  With is_nft the mint has to have 0 decimals and a supply of 1, and the lock a single schedule of amount 1.
+    *  With is_immutable the lock can never be transferred, extended or relocked, which fails with ImmutableLock.
+    *  Locking accounts initialized before the flags byte can not hold immutable locks.
+    *  The schedules must be non-empty and strictly ascending by release time, with non-zero amounts and release times
+    *  which are not in the past. Every schedule has to respect the minimum lock duration and amount set with SetLockMinimums.
+    *  No release time, nor the end of a vesting schedule, can be later than the maximum lock duration set with
+    *  SetMaxLockDuration from now, which fails with DurationTooLong.
+    *  The source token account owner can be a spl token multisig account, which signs through at least m of its signers.
+    *  Instead of the owner, the approved delegate of the source token account can sign, as long as its delegated amount
+    *  covers the total of the schedules. Fails with SourceAuthorityMismatch or InsufficientDelegatedAmount otherwise.
+    *  With pay_fee_in_usd the fee payer pays fees_in_usd in the USD token of the program state instead of SOL, converted
+    *  to the decimals of the USD mint, and the price estimator account is not read. The USD mint has to be owned by the
+    *  spl token program account, and both USD token accounts have to be of that mint, or it fails with FeeMintMismatch.
+    *  With pay_fee_in_token fee_bps_in_token of the program state is taken from every schedule instead, rounded down,
+    *  and transferred from the source token account to a token account of the mint owned by the company wallet.
+    *  The lock holds the rest of every schedule, which has to respect the minimums. Only one of pay_fee_in_usd and
+    *  pay_fee_in_token can be set, and paying in the locked token fails while fee_bps_in_token is zero.
+    *  With with_fee_discount the fee, in any currency, is discounted by the best fee discount tier of the program state
+    *  which the balance of a token account of the owner token reaches. The token account has to be of the owner token
+    *  and owned by the fee payer, or it fails with FeeMintMismatch and InvalidArgument. A balance below every tier, or
+    *  no owner token set with SetOwnerTokenMint, pays the full fee.
+    *  Free tokens pay nothing in any currency.
+    *  With with_wallet_state the wallet state account of the fee payer is passed, see state::find_wallet_state_address.
+    *  When the admin made the fee payer exempt with SetFeeExemptWallet no fee is charged at all, like for free tokens.
+    *  A wallet state which was never created charges the fee as usual, another account fails with InvalidArgument.
+    *  With time_basis Slot every release time is a slot height instead of a unix timestamp, compared against the
+    *  current slot on unlock, extension and relock, and the lock durations are converted to slots.
+    *  The basis is stored in the header and applies to every schedule of the lock. Locking accounts initialized
+    *  before the time basis can only hold unix timestamps, and fail with AccountDataTooSmall otherwise.
+    *  With split_destinations every unlocked amount is divided across up to MAX_SPLIT_DESTINATIONS token accounts of
+    *  the mint by their shares in basis points, which have to be non-zero and sum to MAX_BPS. The first one has to be
+    *  the destination token account and receives the rounding dust. The destinations have to be distinct and can not
+    *  be the locking token account, or it fails with InvalidSplit. Splits can not lock the native mint, and locking
+    *  accounts initialized before split destinations fail with AccountDataTooSmall.
+    *  A non-zero min_claim_interval_secs rate limits the unlocks: an unlock less than that long after the previous one
+    *  which released tokens fails with ClaimTooSoon. The interval counts in the time basis of the lock, so it is a
+    *  number of slots for slot locks. Locking accounts initialized before claim cooldowns fail with AccountDataTooSmall.
     *
     *  - Accounts
-    *  0. `[]` The spl token program account
-    *  1. `[]` The locking account
-    *  2. `[]` The locking token account
-    *  3. `[signer]` The source token account owner
-    *  4. `[]` The source token account
-    *  5. `[]` The token state account
-    *  6. `[]` The company wallet account
+    *  0. `[]` The spl token program account, either spl token or token-2022
+    *  1. `[]` The program state account
+    *  2. `[writable]` The locking account
+    *  3. `[writable]` The locking token account
+    *  4. `[signer]` The source token account owner or its delegate, not a signer when it is a multisig account
+    *  5. `[writable]` The source token account
+    *  6. `[writable]` The token state account, created on the first lock of the mint
+    *  7. `[writable]` The company wallet account
+    *  8. `[]` The system program account
+    *  9. `[]` The sysvar Rent account
+    *  10. `[]` The associated token account program account
+    *  11. `[]` The token mint account
+    *  12. `[]` The price estimator account, a Pyth price account of SOL/USD
+    *  13. `[writable, signer]` The fee payer account
+    *  With pay_fee_in_usd:
+    *  14. `[writable]` The USD token account of the fee payer
+    *  15. `[]` The USD token mint account
+    *  16. `[writable]` The USD token account of the company wallet
+    *  With pay_fee_in_token:
+    *  14. `[writable]` The token account of the mint owned by the company wallet
+    *  With with_fee_discount, after the accounts of the fee:
+    *  ..N `[]` The owner token account of the fee payer
+    *  With with_wallet_state, after the owner token account:
+    *  ..N `[]` The wallet state account of the fee payer
+    *  Followed by:
+    *  ..N `[signer]` The multisig signer accounts, only when the source token account owner is a multisig account
     */
     Create {
-        seeds: [u8; 32],
+        seeds: LockSeed,
         mint_address: Pubkey,
         destination_token_address: Pubkey,
         schedules: Vec<Schedule>,
+        deadline: u64,
+        max_total: Option<u64>,
+        is_nft: bool,
+        is_immutable: bool,
+        pay_fee_in_usd: bool,
+        pay_fee_in_token: bool,
+        with_fee_discount: bool,
+        time_basis: TimeBasis,
+        split_destinations: Vec<SplitDestination>,
+        with_wallet_state: bool,
+        min_claim_interval_secs: u32,
     },
 
-    Unlock { seeds: [u8; 32] },
+    /* Creates a new lock schedule of native SOL.
+    *  The payer's lamports are wrapped into a new wrapped SOL token account owned by the locking account,
+    *  which is created with its rent exempt reserve on top of the total scheduled amount.
+    *  The destination can be either a wrapped SOL token account or a system account. Unlocking into a system account
+    *  closes the wrapped SOL account into the locking account and pays the released lamports out of it.
+    *  The schedules are validated the same way as in Create, and unused schedule slots of the locking account are cut
+    *  off the same way.
+    *
+    *  - Accounts
+    *  0. `[]` The spl token program account
+    *  1. `[]` The program state account
+    *  2. `[writable]` The locking account
+    *  3. `[writable, signer]` The new locking token account
+    *  4. `[writable, signer]` The payer account
+    *  5. `[writable]` The token state account of the native mint, created on its first lock
+    *  6. `[writable]` The company wallet account
+    *  7. `[]` The system program account
+    *  8. `[]` The sysvar Rent account
+    *  9. `[]` The native mint account
+    *  10. `[]` The price estimator account, a Pyth price account of SOL/USD
+    */
+    CreateNative {
+        seeds: LockSeed,
+        destination_address: Pubkey,
+        schedules: Vec<Schedule>,
+    },
+
+    /* Unlocks the schedules which have reached their release time.
+    *  Fails with LockFullyUnlocked once every schedule of the lock was unlocked, and with EscrowFrozen while the
+    *  freeze authority of the mint keeps the locking token account frozen.
+    *  Fails with ClaimTooSoon less than the min_claim_interval_secs of the lock after the previous unlock which
+    *  released tokens, see Create.
+    *  A non-zero deadline rejects the instruction once the current unix timestamp is past it.
+    *  The destination token account must hold the locked mint and must not be frozen.
+    *  Delegates and close authorities on it are allowed, unlike on the locking token account.
+    *  The clock is read with Clock::get. Older clients passing the sysvar Clock account after the spl token
+    *  program account are still accepted, the account is skipped.
+    *  What the lock still holds is logged as a LockRemaining event and set as return data in the layout of
+    *  state::RemainingSchedules: the remaining locked amount, the schedules still holding an amount and the next
+    *  release time.
+    *
+    *  - Accounts
+    *  0. `[]` The spl token program account
+    *  1. `[]` The program state account
+    *  2. `[writable]` The locking account
+    *  3. `[writable]` The locking token account
+    *  4. `[writable]` The destination token account
+    *  5. `[]` The token mint account
+    *  6. `[writable]` The token state account of the mint, whose locked total goes down by the released amount
+    *  7. ..7+N `[writable]` The destination token accounts of the matured schedules of a CreateMulti lock,
+    *  or the split destinations of a split lock, other than the destination of the lock. Each is matched against
+    *  the destinations stored in the locking account, and a missing one fails with NotEnoughAccountKeys.
+    */
+    Unlock {
+        seeds: LockSeed,
+        deadline: u64,
+    },
 
-    TransferLocks { seeds: [u8; 32] },
+    /* Moves the lock to a new destination, signed by the owner of the current destination.
+    *  Immutable locks can not be moved, like with ProposeTransferLocks and AcceptTransferLocks.
+    *  For a split lock the current destination can also be any split destination other than the first, which then
+    *  only replaces that split destination and keeps its share. The new destination can not be one of the split
+    *  destinations already.
+    *  The delegate approved with ApproveLockDelegate can sign in place of the owner of the current destination,
+    *  though not for a split destination other than the first.
+    *
+    *  - Accounts
+    *  0. `[]` The program state account
+    *  1. `[writable]` The locking account
+    *  2. `[]` The current destination token account
+    *  3. `[signer]` The owner of the current destination token account, or the delegate of the lock
+    *  4. `[]` The new destination token account
+    */
+    TransferLocks { seeds: LockSeed },
 
+    /* Postpones the release time of a schedule, signed by the owner of the destination.
+    *  Immutable locks can not be extended, like with ExtendLockDurations and Relock.
+    *  The release time can not be later than the maximum lock duration set with SetMaxLockDuration from now,
+    *  which fails with DurationTooLong, and sorted schedules have to stay sorted, or it fails with WouldViolateOrdering.
+    *
+    *  - Accounts
+    *  0. `[]` The program state account
+    *  1. `[writable]` The locking account
+    *  2. `[]` The destination token account
+    *  3. `[signer]` The owner of the destination token account
+    */
     ExtendLockDuration {
-        seeds: [u8; 32],
+        seeds: LockSeed,
         index: u32,
         release_time: u64,
     },
 
 //////////////////////////////////////
+    /* The admin instructions below are signed by the admin stored in the program state account.
+    *  The admin can be a spl token multisig account, which signs through at least m of its signers.
+    *
+    *  - Accounts
+    *  0. `[signer]` The program admin account, not a signer when it is a multisig account
+    *  1. `[writable]` The program state account
+    *  2. `[writable]` The token state account, only for SetFreeToken and SetTokenFee
+    *  3. ..3+N `[signer]` The multisig signer accounts, only when the admin is a multisig account
+    */
+
+    /* Sets the pause flags, a bitmask of state::PAUSE_CREATE, state::PAUSE_UNLOCK and state::PAUSE_TRANSFER.
+    *  Zero resumes everything. The former "pause" value of 1 now only pauses creating locks,
+    *  so vested tokens stay claimable unless PAUSE_UNLOCK is set explicitly. The program state records when the
+    *  pause started and which admin started it, and PAUSE_UNLOCK stops blocking unlocks state::MAX_PAUSE_DURATION_SECS
+    *  after that. Adding flags to a pause keeps its start, and so does resuming: a new pause only gets a new start
+    *  state::PAUSE_COOLDOWN_SECS after the previous one stopped blocking unlocks. Setting PAUSE_UNLOCK fails with
+    *  AccountDataTooSmall for program state accounts of older layouts, which have no room for the record.
+    */
     PauseContract {
-        is_pause: bool,
+        pause_flags: u8,
     },
 
+    /* Sets the fee parameters whose bit of state::FEE_PARAM_PRICE_ESTIMATOR, FEE_PARAM_USD_TOKEN_ADDRESS,
+    *  FEE_PARAM_FEES_IN_USD and FEE_PARAM_COMPANY_WALLET is set in present_fields, leaving the others untouched.
+    *  Data without the present_fields byte sets every field, like before it existed. The default pubkey fails with
+    *  InvalidArgument as the price estimator or the company wallet, and unknown bits with InvalidInstructionData.
+    *  The candidate wallets are the only wallets the company wallet can ever be set to.
+    *  They are stored by the first call after initialization and must be passed unchanged afterwards.
+    */
     SetFeeParams {
         price_estimator: Pubkey,
         usd_token_address: Pubkey,
         fees_in_usd: u64,
         company_wallet: Pubkey,
+        candidate_wallets: [Pubkey; MAX_CANDIDATE_WALLETS],
+        present_fields: u8,
     },
 
     SetFeesInUSD {
@@ -90,24 +465,589 @@ pub enum LockTokenInstruction {
         company_wallet: Pubkey,
     },
 
+    /* Sets how many slots old the oracle price can be when fees are converted to SOL.
+    *  Zero falls back to the default of oracle::DEFAULT_MAX_PRICE_AGE_SLOTS.
+    */
+    SetMaxPriceAge {
+        max_price_age_slots: u64,
+    },
+
     SetFreeToken {
         mint_address: Pubkey,
         is_free: bool,
     },
 
-    TransferOwnership {},
+    /* Overrides the global fee for a single token mint.
+    *  None removes the override so the mint falls back to the global fee again.
+    */
+    SetTokenFee {
+        mint_address: Pubkey,
+        fee_in_usd: Option<u64>,
+    },
+
+    /* Creates the program state account at the address derived from state::GLOBAL_STATE_SEED
+    *  and stores the admin which signs every admin instruction. Can only be called once.
+    *  Programs deployed before the admin was stored kept their state in the version 1 layout at the address of
+    *  state::LEGACY_GLOBAL_STATE_SEED. Passing that account migrates it: its fee parameters and pause are written
+    *  to the new account in the current layout, and it is closed into the upgrade authority.
+    *
+    *  - Accounts
+    *  0. `[]` The system program account
+    *  1. `[]` The sysvar Rent account
+    *  2. `[writable, signer]` The upgrade authority of the program, which pays for the program state account
+    *  3. `[writable]` The program state account
+    *  4. `[]` The program data account of the program
+    *  5. `[writable]` Optional, the program state account of the version 1 layout
+    */
+    InitializeGlobalState {
+        admin: Pubkey,
+    },
+
+    /* Proposes a new admin, which takes over once it accepts with AcceptOwnership.
+    *  Signed by the current admin, with the same accounts as the other admin instructions.
+    */
+    ProposeOwner {
+        new_admin: Pubkey,
+    },
+
+    /* Promotes the proposed admin to admin and emits events::OwnershipTransferred.
+    *
+    *  - Accounts
+    *  0. `[signer]` The proposed admin account, not a signer when it is a multisig account
+    *  1. `[writable]` The program state account
+    *  2. ..2+N `[signer]` The multisig signer accounts, only when the proposed admin is a multisig account
+    */
+    AcceptOwnership {},
+
+    /* Withdraws the pending admin proposal.
+    *  Signed by the current admin, with the same accounts as the other admin instructions.
+    */
+    CancelOwnershipTransfer {},
+
+    /* Sets the minimum time between Create and the release of every schedule, and the minimum amount of every schedule.
+    *  Zero disables the corresponding minimum. Signed by the admin, with the same accounts as the other admin instructions.
+    */
+    SetLockMinimums {
+        min_lock_duration_secs: u64,
+        min_lock_amount: u64,
+    },
+
+    /* Proposes a new destination of the lock, which takes over once its owner accepts with AcceptTransferLocks.
+    *  Replaces an earlier proposal. Locking accounts created before pending transfers only support TransferLocks.
+    *
+    *  - Accounts
+    *  0. `[]` The program state account
+    *  1. `[writable]` The locking account
+    *  2. `[]` The current destination token account
+    *  3. `[signer]` The owner of the current destination token account
+    */
+    ProposeTransferLocks {
+        seeds: LockSeed,
+        new_destination: Pubkey,
+    },
+
+    /* Makes the proposed destination the destination of the lock.
+    *  A system account proposed as the destination of a native lock signs as its own owner.
+    *
+    *  - Accounts
+    *  0. `[]` The program state account
+    *  1. `[writable]` The locking account
+    *  2. `[]` The proposed destination token account
+    *  3. `[signer]` The owner of the proposed destination token account
+    */
+    AcceptTransferLocks { seeds: LockSeed },
+
+    /* Withdraws the pending transfer, with the same accounts as ProposeTransferLocks.
+    */
+    CancelTransferLocks { seeds: LockSeed },
+
+    /* Postpones the release time of several schedules at once, given as (index, release time) pairs.
+    *  Every pair is validated like ExtendLockDuration and indices can not repeat. Nothing is written
+    *  unless every pair is valid. Sorted schedules have to stay sorted once all pairs are applied.
+    *  Same accounts as ExtendLockDuration.
+    */
+    ExtendLockDurations {
+        seeds: LockSeed,
+        extensions: Vec<(u32, u64)>,
+    },
+
+    /* Locks a schedule again which has reached its release time but has not been unlocked yet.
+    *  The new release time has to be strictly after the current unix timestamp, the amount is kept.
+    *  Sorted schedules are sorted again afterwards, so the index of the schedule can change. The change is
+    *  logged as a LockExtended event with the index the schedule ends up at.
+    *  Same accounts as ExtendLockDuration.
+    */
+    Relock {
+        seeds: LockSeed,
+        index: u32,
+        new_release_time: u64,
+    },
+
+    /* Unlocks the matured schedules of several locks of the same mint.
+    *  Locks with nothing to unlock yet, or nothing left to unlock, are skipped. The number of account groups has to match the number of seeds.
+    *  Like Unlock, a sysvar Clock account after the spl token program account is skipped.
+    *
+    *  - Accounts
+    *  0. `[]` The spl token program account
+    *  1. `[]` The program state account
+    *  2. `[]` The token mint account
+    *  3. `[writable]` The token state account of the mint
+    *  4+3i. `[writable]` The locking account of the i-th seeds
+    *  5+3i. `[writable]` The locking token account of the i-th seeds
+    *  6+3i. `[writable]` The destination token account of the i-th seeds
+    */
+    BatchUnlock {
+        seeds_list: Vec<LockSeed>,
+    },
+
+    /* Creates a lock releasing amount_per_period every period_secs, the first one at start_time.
+    *  The periods are stored as regular schedules, so the locking account has to be initialized with periods schedules.
+    *  The periods are validated like the schedules of Create. Same accounts as Create.
+    */
+    CreateRecurring {
+        seeds: LockSeed,
+        mint_address: Pubkey,
+        destination_token_address: Pubkey,
+        start_time: u64,
+        period_secs: u64,
+        periods: u32,
+        amount_per_period: u64,
+    },
+
+    /* Creates a vesting lock of total_amount: cliff_bps basis points of it are released at cliff_time
+    *  and the rest pro rata until vesting_end. Amounts are rounded down, so the dust is released at vesting_end.
+    *  The locking account has to be initialized with state::VESTING_SCHEDULE_SLOTS schedules.
+    *  Vesting locks can not be extended or relocked. Same accounts as Create.
+    */
+    CreateVesting {
+        seeds: LockSeed,
+        mint_address: Pubkey,
+        destination_token_address: Pubkey,
+        cliff_time: u64,
+        cliff_bps: u16,
+        vesting_end: u64,
+        total_amount: u64,
+    },
+
+    /* Moves a locking account created before the current header layout to it. The account is reallocated,
+    *  the schedules are shifted behind the longer header and the payer covers the extra rent.
+    *  Locks without totals get what they still hold as their total and a claimed amount of zero,
+    *  except for vesting locks which keep the amounts of their vesting schedule.
+    *  The schedule counters are set from the schedules, the leading ones with a zero amount being unlocked.
+    *  Locks of every layout can be unlocked, migrating is only needed to track the totals and counters
+    *  and to schedule an emergency unlock or approve a delegate.
+    *  Legacy locks with schedules which are not sorted by release time can not be migrated.
+    *  The creation time of migrated locks is unknown and left at zero, as is the creator of locks created before the
+    *  V4_LEN layout, see LockScheduleHeader.
+    *
+    *  - Accounts
+    *  0. `[]` The system program account
+    *  1. `[writable, signer]` The payer account
+    *  2. `[writable]` The locking account
+    */
+    MigrateLock { seeds: LockSeed },
+
+    /* Schedules the rescue of a lock whose beneficiary lost the keypair of its destination, signed by the admin.
+    *  The rescue destination and the time from which ExecuteEmergencyUnlock can run, state::EMERGENCY_UNLOCK_DELAY_SECS
+    *  from now, are written into the header. Scheduling again replaces the rescue and restarts the delay.
+    *  Immutable locks can not be rescued and locks of the older header layouts have to be migrated first.
+    *
+    *  - Accounts
+    *  0. `[signer]` The program admin account, not a signer when it is a multisig account
+    *  1. `[]` The program state account
+    *  2. `[writable]` The locking account
+    *  3. ..3+N `[signer]` The multisig signer accounts, only when the admin is a multisig account
+    */
+    ScheduleEmergencyUnlock {
+        seeds: LockSeed,
+        rescue_destination: Pubkey,
+    },
+
+    /* Transfers everything left in the locking token account to the rescue destination once the delay of
+    *  ScheduleEmergencyUnlock has passed, whatever the release times of the schedules. Anyone can send it.
+    *  The lock is fully unlocked afterwards and pays out to the rescue destination. Blocked by PAUSE_UNLOCK like Unlock.
+    *
+    *  - Accounts
+    *  0. `[]` The spl token program account
+    *  1. `[]` The program state account
+    *  2. `[writable]` The locking account
+    *  3. `[writable]` The locking token account
+    *  4. `[writable]` The rescue destination token account
+    *  5. `[]` The token mint account
+    *  6. `[writable]` The token state account of the mint
+    */
+    ExecuteEmergencyUnlock { seeds: LockSeed },
+
+    /* Withdraws the scheduled rescue, signed by the owner of the destination at any time before it is executed.
+    *  Moving the lock with TransferLocks or AcceptTransferLocks withdraws it as well. Not blocked by the pause.
+    *  Same accounts as ExtendLockDuration.
+    */
+    CancelEmergencyUnlock { seeds: LockSeed },
+
+    /* Returns the tokens sent straight to the locking token account to the creator of the lock, which is the
+    *  owner of the source token account of Create. Only what the vault holds on top of the amounts the schedules
+    *  still release can be swept, so the schedules stay fully funded. Anyone can send it.
+    *  Locks created before the current header layout do not record their creator and can not be swept.
+    *
+    *  - Accounts
+    *  0. `[]` The spl token program account
+    *  1. `[]` The program state account
+    *  2. `[]` The locking account
+    *  3. `[writable]` The locking token account
+    *  4. `[writable]` A token account of the mint owned by the creator of the lock
+    *  5. `[]` The token mint account
+    */
+    SweepExcess { seeds: LockSeed },
+
+    /* Sets the basis points of every schedule which Create takes as fee when it pays the fee in the locked token.
+    *  Zero disables paying in the locked token, more than state::MAX_FEE_BPS_IN_TOKEN fails with InvalidBasisPoints.
+    *  Program state accounts of the version 2, 3 and 4 layouts are grown to the current layout, the payer covering
+    *  the extra rent.
+    *
+    *  - Accounts
+    *  0. `[signer]` The program admin account, not a signer when it is a multisig account
+    *  1. `[writable]` The program state account
+    *  2. `[]` The system program account
+    *  3. `[writable, signer]` The payer of the extra rent
+    *  4. ..4+N `[signer]` The multisig signer accounts, only when the admin is a multisig account
+    */
+    SetFeeBpsInToken { fee_bps_in_token: u16 },
+
+    /* Sets the tiers of the Create fee discount for fee payers holding the owner token.
+    *  Every tier discounts the fee by discount_bps once the balance of the owner token reaches min_balance, the best
+    *  matching tier applies. Tiers with a zero discount are unused, a discount above MAX_BPS fails with InvalidBasisPoints.
+    *  The owner token itself is only set with SetOwnerTokenMint, which proves the admin holds it.
+    *  Program state accounts of the version 2, 3 and 4 layouts are grown to the current layout, the payer covering
+    *  the extra rent.
+    *
+    *  - Accounts
+    *  0. `[signer]` The program admin account, not a signer when it is a multisig account
+    *  1. `[writable]` The program state account
+    *  2. `[]` The system program account
+    *  3. `[writable, signer]` The payer of the extra rent
+    *  4. ..4+N `[signer]` The multisig signer accounts, only when the admin is a multisig account
+    */
+    SetFeeDiscountTiers {
+        fee_discount_tiers: [FeeDiscountTier; MAX_FEE_DISCOUNT_TIERS],
+    },
+
+    /* Computes the amount an Unlock would release now, without changing any account.
+    *  That is the sum of the matured schedules which have not been unlocked yet, or what vested since the last
+    *  unlock of a vesting lock. The amount is logged as a ClaimableAmount event and set as the return data,
+    *  a little endian u64, so it can be read by simulating the instruction. Works while the program is paused.
+    *
+    *  - Accounts
+    *  0. `[]` The locking account
+    */
+    GetClaimableAmount { seeds: LockSeed },
+
+    /* Creates a lock whose schedules are released to destination token accounts of their own, so one lock
+    *  vests to several beneficiaries on the same timeline. A schedule with the default pubkey as destination
+    *  is released to destination_token_address, the destination of the lock. Unlock transfers once to every
+    *  distinct destination of the matured schedules.
+    *  The locking account has to be initialized with state::MULTI_SCHEDULE_SLOTS schedules for every schedule.
+    *  The schedules are validated like the schedules of Create, and the fee is paid in SOL.
+    *  Such locks can not hold native SOL, and their schedules can not be extended or relocked, which fails
+    *  with MultiDestinationLock. TransferLocks only moves the destination of the lock. Same accounts as Create.
+    */
+    CreateMulti {
+        seeds: LockSeed,
+        mint_address: Pubkey,
+        destination_token_address: Pubkey,
+        schedules: Vec<MultiSchedule>,
+    },
+
+    /* Sets the name and URI explorers show for the lock, creating its metadata account on first use.
+    *  The metadata account is the program address of state::LOCK_METADATA_SEED and the locking account key, see
+    *  state::find_lock_metadata_address. The name takes at most state::MAX_LOCK_NAME_LEN bytes of UTF-8 and the URI
+    *  state::MAX_LOCK_URI_LEN, or it fails with LockNameTooLong and LockUriTooLong.
+    *  Signed by the creator of the lock or the owner of its destination token account. Locks created before the
+    *  creator was recorded can only be described by the owner of the destination.
+    *
+    *  - Accounts
+    *  0. `[]` The locking account
+    *  1. `[writable]` The lock metadata account
+    *  2. `[]` The destination token account of the lock
+    *  3. `[signer]` The creator of the lock or the owner of the destination token account
+    *  4. `[writable, signer]` The payer of the rent of a new metadata account
+    *  5. `[]` The system program account
+    */
+    SetLockMetadata {
+        seeds: LockSeed,
+        name: String,
+        uri: String,
+    },
+
+    /* Deletes the metadata account of the lock, sending its rent to the receiver.
+    *  Signed by the creator of the lock or the owner of its destination token account, like SetLockMetadata.
+    *
+    *  - Accounts
+    *  0. `[]` The locking account
+    *  1. `[writable]` The lock metadata account
+    *  2. `[]` The destination token account of the lock
+    *  3. `[signer]` The creator of the lock or the owner of the destination token account
+    *  4. `[writable]` The receiver of the rent
+    */
+    CloseLockMetadata { seeds: LockSeed },
+
+    /* Closes a locking account which Init created but no Create initialized, sending all its lamports to the
+    *  payer of Init recorded in it. Fails with AccountAlreadyInitialized once a lock was created.
+    *  Locking accounts initialized before the payer was recorded can not be reclaimed.
+    *
+    *  - Accounts
+    *  0. `[writable]` The locking account
+    *  1. `[writable, signer]` The payer of Init
+    */
+    ReclaimUninitializedLock { seeds: LockSeed },
+
+    /* Exempts the wallet from the Create fee of every mint, or charges it again, creating its wallet state account
+    *  on first use. The wallet state account is the program address of state::WALLET_STATE_SEED and the wallet key,
+    *  see state::find_wallet_state_address. Signed by the admin, which can be a multisig account.
+    *
+    *  - Accounts
+    *  0. `[signer]` The program admin account, not a signer when it is a multisig account
+    *  1. `[]` The program state account
+    *  2. `[writable]` The wallet state account
+    *  3. `[]` The system program account
+    *  4. `[writable, signer]` The payer of the rent of a new wallet state account
+    *  5. ..5+N `[signer]` The multisig signer accounts, only when the admin is a multisig account
+    */
+    SetFeeExemptWallet {
+        wallet: Pubkey,
+        is_exempt: bool,
+    },
+
+    /* Sets the longest time from now at which Create, ExtendLockDuration, ExtendLockDurations and Relock can set
+    *  a release time, in seconds, converted to slots for locks released at slot heights. Zero leaves lock durations
+    *  uncapped. A maximum below the minimum lock duration fails with InvalidArgument.
+    *  Program state accounts of the version 2, 3 and 4 layouts are grown to the current layout, the payer covering
+    *  the extra rent. Same accounts as SetFeeBpsInToken.
+    */
+    SetMaxLockDuration { max_lock_duration_secs: u64 },
+
+    /* Moves the schedules at the given indices into a new lock released to another destination, signed by the owner
+    *  of the destination. The new locking account has to be initialized with new_seeds and as many schedules as
+    *  there are indices, and its locking token account, the associated token account of the new locking account,
+    *  has to exist. The amounts of the moved schedules are transferred between the two locking token accounts and
+    *  the moved schedules are emptied in the lock, which keeps its other schedules.
+    *  Only schedules still locked can be moved, and indices can not repeat. Nothing is moved unless every index is
+    *  valid. The new lock keeps the time basis and creator of the lock, and is mutable without split destinations.
+    *  Immutable, vesting, split and native SOL locks and locks with per-schedule destinations can not be divided.
+    *
+    *  - Accounts
+    *  0. `[]` The spl token program account
+    *  1. `[]` The program state account
+    *  2. `[writable]` The locking account
+    *  3. `[writable]` The locking token account
+    *  4. `[]` The destination token account
+    *  5. `[signer]` The owner of the destination token account
+    *  6. `[writable]` The new locking account
+    *  7. `[writable]` The new locking token account
+    *  8. `[]` The destination token account of the new lock
+    *  9. `[]` The token mint account
+    */
+    TransferScheduleIndices {
+        seeds: LockSeed,
+        new_seeds: LockSeed,
+        indices: Vec<u32>,
+    },
+
+    /* Lets the delegate call TransferLocks for the beneficiary, signed by the owner of the destination.
+    *  Replaces an earlier delegate. The delegate is cleared whenever the destination of the lock changes.
+    *  Immutable locks can not have a delegate. Locking accounts created before lock delegates have to be migrated
+    *  with MigrateLock first. Same accounts as ProposeTransferLocks.
+    */
+    ApproveLockDelegate {
+        seeds: LockSeed,
+        delegate: Pubkey,
+    },
+
+    /* Clears the delegate of the lock, with the same accounts as ProposeTransferLocks.
+    */
+    RevokeLockDelegate { seeds: LockSeed },
+
+    /* Unlocks like Unlock without a deadline, then closes the lock if every schedule is empty and the locking token
+    *  account holds nothing: the locking token account is closed and the lamports of the locking account are
+    *  drained, both to the refund account. The refund account has to be the owner of the destination token account,
+    *  or the destination itself for a native lock paying out lamports. A lock which still holds an amount is left
+    *  in place, and the instruction then fails like Unlock when nothing has matured. A lock already drained by
+    *  earlier unlocks is closed without failing with LockFullyUnlocked.
+    *  Tokens sent to the locking token account beyond the lock keep it open, see SweepExcess.
+    *  What the lock still holds is logged and set as return data like with Unlock, all zero once the lock is closed,
+    *  which is also logged as a LockClosed event.
+    *
+    *  - Accounts
+    *  0. `[]` The spl token program account
+    *  1. `[]` The program state account
+    *  2. `[writable]` The locking account
+    *  3. `[writable]` The locking token account
+    *  4. `[writable]` The destination token account
+    *  5. `[]` The token mint account
+    *  6. `[writable]` The token state account of the mint
+    *  7. `[writable]` The refund account
+    *  8. ..8+N `[writable]` The other destination token accounts, as with Unlock
+    */
+    ClaimAndClose { seeds: LockSeed },
+
+    /* Records what the given locking token accounts of the mint hold, with the unix timestamp, in the token state of
+    *  the mint, so circulating supply and governance snapshots can leave locked tokens out. Anyone can send it.
+    *  Every locking token account follows its locking account, which has to be a lock of the mint, and has to be
+    *  owned by it. Accounts which are not locks, locks of another mint and repeated locks fail with
+    *  InvalidArgument. Only the locks passed are summed, so a snapshot covering every lock of the mint may take
+    *  them all. Token states of the older layouts are grown to the current layout, the payer covering the extra
+    *  rent, and only then does the payer have to sign.
+    *
+    *  - Accounts
+    *  0. `[]` The spl token program account
+    *  1. `[]` The system program account
+    *  2. `[writable, signer]` The payer account
+    *  3. `[writable]` The token state account of the mint
+    *  4. ..4+2N `[]` The locking account and the locking token account of every lock
+    */
+    SnapshotMintLocked { mint: Pubkey },
+
+    /* Unlocks like Unlock without the owner of the destination token account sending the transaction, on a permit
+    *  it signed off-chain. The instruction right before it in the transaction has to be an ed25519 program
+    *  instruction verifying a single signature of that owner over permit_message with the nonce of the lock, so
+    *  anyone can relay the permit. The destination of a native lock paying out lamports signs it itself.
+    *  The nonce is incremented by every permit used, which can not be used again, and a permit past its expiry
+    *  unix timestamp fails with PermitExpired. Permits which do not match fail with InvalidPermit.
+    *  Locks created before claim permits have to be migrated first.
+    *
+    *  - Accounts
+    *  0. `[]` The spl token program account
+    *  1. `[]` The program state account
+    *  2. `[writable]` The locking account
+    *  3. `[writable]` The locking token account
+    *  4. `[writable]` The destination token account
+    *  5. `[]` The token mint account
+    *  6. `[writable]` The token state account of the mint
+    *  7. `[]` The sysvar Instructions account
+    *  8. ..8+N `[writable]` The other destination token accounts, as with Unlock
+    */
+    UnlockWithPermit { seeds: LockSeed, expiry: u64 },
+
+    /* Inits a locking account like Init, derived from the creator, the mint and the nonce instead of seeds, see
+    *  pda::find_lock_address. The derivation is recorded in the header, and every later instruction on the lock,
+    *  Create included, signs for it from there: their seeds are ignored and can be LockSeed::default().
+    *  Create fails with InvalidArgument unless the creator is the owner of the source token account and the mint
+    *  is the mint of the derivation. Anyone can init the locking account of any creator, who moves on to the next
+    *  nonce when a locking account does not fit the lock.
+    *
+    *  - Accounts
+    *  0. `[]` The system program account
+    *  1. `[]` The program state account
+    *  2. `[]` The sysvar Rent account
+    *  3. `[writable, signer]` The fee payer account
+    *  4. `[writable]` The locking account
+    */
+    InitDerived {
+        creator: Pubkey,
+        mint_address: Pubkey,
+        nonce: u64,
+        number_of_schedules: u32,
+    },
+
+    /* Rotates the owner token of the Create fee discount to new_mint, e.g. after the previous one was compromised,
+    *  keeping the fee discount tiers. The admin proves it holds the new owner token with a token account of
+    *  new_mint it owns holding a non-zero balance, so the owner token can not be rotated to a mint nobody holds.
+    *  A token account of another mint fails with FeeMintMismatch, of another owner with InvalidArgument and an
+    *  empty one with InsufficientFunds. Program state accounts of the older layouts are grown to the current
+    *  layout, the payer covering the extra rent.
+    *
+    *  - Accounts
+    *  0. `[signer]` The program admin account, not a signer when it is a multisig account
+    *  1. `[writable]` The program state account
+    *  2. `[]` The system program account
+    *  3. `[writable, signer]` The payer of the extra rent
+    *  4. `[]` The spl token program account of the new owner token, either spl token or token-2022
+    *  5. `[]` The token account of the new owner token owned by the admin
+    *  6. ..6+N `[signer]` The multisig signer accounts, only when the admin is a multisig account
+    */
+    SetOwnerTokenMint { new_mint: Pubkey },
+
+    /* Unlocks exactly amount of what has matured, e.g. for payroll withdrawing a fixed sum, where Unlock releases
+    *  everything matured. The matured schedules are released oldest first, and the schedule the amount runs out in
+    *  keeps the rest of its amount with its past release time, released by the next Unlock or UnlockAmount.
+    *  An amount above what has matured fails with InsufficientUnlocked and an amount of 0 with
+    *  InvalidInstructionData. Otherwise like Unlock, without a deadline.
+    *
+    *  - Accounts
+    *  0. `[]` The spl token program account
+    *  1. `[]` The program state account
+    *  2. `[writable]` The locking account
+    *  3. `[writable]` The locking token account
+    *  4. `[writable]` The destination token account
+    *  5. `[]` The token mint account
+    *  6. `[writable]` The token state account of the mint
+    *  7. ..7+N `[writable]` The other destination token accounts, as with Unlock
+    */
+    UnlockAmount { seeds: LockSeed, amount: u64 },
+
+    /* Proposes new candidate wallets, which replace the candidate wallets stored by SetFeeParams once
+    *  AcceptCandidateWallets is called state::CANDIDATE_WALLETS_DELAY_SECS later. A new proposal replaces the pending
+    *  one and restarts the delay. Fails with InvalidArgument when every wallet is the default pubkey.
+    *  Program state accounts of older layouts are grown to the current layout.
+    *
+    *  - Accounts
+    *  0. `[signer]` The program admin account, not a signer when it is a multisig account
+    *  1. `[writable]` The program state account
+    *  2. `[]` The system program account
+    *  3. `[writable, signer]` The payer of the extra rent
+    *  4. ..4+N `[signer]` The multisig signer accounts, only when the admin is a multisig account
+    */
+    ProposeCandidateWallets { candidate_wallets: [Pubkey; MAX_CANDIDATE_WALLETS] },
+
+    /* Replaces the candidate wallets with the proposed ones. Fails with CandidateWalletsTimelock before the delay
+    *  of ProposeCandidateWallets has passed. The company wallet is left as it is, SetCompanyWallet moves it to one
+    *  of the new candidates. Signed by the admin, with the same accounts as the other admin instructions.
+    */
+    AcceptCandidateWallets {},
+}
+
+// Everything needed to reproduce a Create call, logged on one line by the verbose build.
+pub struct CreateSummary<'a> {
+    pub mint_address: &'a Pubkey,
+    pub destination_token_address: &'a Pubkey,
+    pub schedules: &'a [Schedule],
+    pub total_amount: u64,
+    pub fee_lamports: u64,
+}
+
+impl fmt::Display for CreateSummary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let first_release_time = self.schedules.iter().map(|s| s.release_time).min().unwrap_or(0);
+        let last_release_time = self.schedules.iter().map(|s| s.release_time).max().unwrap_or(0);
+        write!(
+            f,
+            "Create summary: schedules={} total_amount={} first_release_time={} last_release_time={} mint={} destination={} fee_lamports={}",
+            self.schedules.len(),
+            self.total_amount,
+            first_release_time,
+            last_release_time,
+            self.mint_address,
+            self.destination_token_address,
+            self.fee_lamports,
+        )
+    }
 }
 
 impl LockTokenInstruction {
     pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
         use LockTokenError::InvalidInstruction;
+        #[cfg(feature = "anchor-compat")]
+        if Self::is_anchor(input) {
+            return Self::unpack_anchor(input);
+        }
         let (&tag, rest) = input.split_first().ok_or(InvalidInstruction)?;
         Ok(match tag {
+            #[cfg(feature = "borsh")]
+            BORSH_FORMAT => return Self::unpack_borsh(input),
             0 => {
-                let seeds: [u8; 32] = rest
+                let seeds: LockSeed = rest
                     .get(..32)
                     .and_then(|slice| slice.try_into().ok())
-                    .unwrap();
+                    .ok_or(InvalidInstruction)?;
                 let number_of_schedules = rest
                     .get(32..36)
                     .and_then(|slice| slice.try_into().ok())
@@ -118,11 +1058,11 @@ impl LockTokenInstruction {
                     number_of_schedules,
                 }
             }
-            1 => {
-                let seeds: [u8; 32] = rest
+            1 | 11 | 28 => {
+                let seeds: LockSeed = rest
                     .get(..32)
                     .and_then(|slice| slice.try_into().ok())
-                    .unwrap();
+                    .ok_or(InvalidInstruction)?;
                 let mint_address = rest
                     .get(32..64)
                     .and_then(|slice| slice.try_into().ok())
@@ -133,9 +1073,91 @@ impl LockTokenInstruction {
                     .and_then(|slice| slice.try_into().ok())
                     .map(Pubkey::new)
                     .ok_or(InvalidInstruction)?;
-                let number_of_schedules = rest[96..].len() / SCHEDULE_SIZE;
+                // Tag 1 is the legacy encoding without options, tag 11 infers the schedule count from the length
+                // and tag 28 gives it explicitly before the options
+                let (schedule_count, options_offset) = match tag {
+                    28 => {
+                        let schedule_count = rest
+                            .get(96..98)
+                            .and_then(|slice| slice.try_into().ok())
+                            .map(u16::from_le_bytes)
+                            .ok_or(InvalidInstruction)?;
+                        if schedule_count > MAX_SCHEDULES {
+                            msg!("A lock has at most {} schedules", MAX_SCHEDULES);
+                            return Err(InvalidInstruction.into());
+                        }
+                        (Some(schedule_count as usize), 98)
+                    }
+                    _ => (None, 96),
+                };
+                let (deadline, max_total, flags, split_destinations, min_claim_interval_secs, schedules_offset) = match tag {
+                    1 => (0, None, 0, Vec::new(), 0, 96),
+                    _ => {
+                        let options_len = rest.get(options_offset).copied().ok_or(InvalidInstruction)? as usize;
+                        let options = rest
+                            .get(options_offset + 1..options_offset + 1 + options_len)
+                            .ok_or(InvalidInstruction)?;
+                        let deadline = options
+                            .get(..8)
+                            .and_then(|slice| slice.try_into().ok())
+                            .map(u64::from_le_bytes)
+                            .unwrap_or(0);
+                        let max_total = options
+                            .get(8..16)
+                            .and_then(|slice| slice.try_into().ok())
+                            .map(u64::from_le_bytes);
+                        let flags = options.get(16).copied().unwrap_or(0);
+                        // Options of 8, 16 or 17 bytes end before the split count, which leaves no split destinations
+                        let (split_destinations, splits_end) = if options.len() > 17 {
+                            let split_count = options[17] as usize;
+                            if split_count > MAX_SPLIT_DESTINATIONS {
+                                msg!("A lock is split across at most {} destinations", MAX_SPLIT_DESTINATIONS);
+                                return Err(InvalidInstruction.into());
+                            }
+                            let splits_end = 18 + split_count * SPLIT_DESTINATION_SIZE;
+                            let split_destinations = options
+                                .get(18..splits_end)
+                                .ok_or(InvalidInstruction)?
+                                .chunks_exact(SPLIT_DESTINATION_SIZE)
+                                .map(|entry| SplitDestination {
+                                    destination_address: Pubkey::new(&entry[..32]),
+                                    share_bps: u16::from_le_bytes(entry[32..].try_into().unwrap()),
+                                })
+                                .collect();
+                            (split_destinations, splits_end)
+                        } else {
+                            (Vec::new(), options.len())
+                        };
+                        let min_claim_interval_secs = options
+                            .get(splits_end..splits_end + 4)
+                            .and_then(|slice| slice.try_into().ok())
+                            .map(u32::from_le_bytes)
+                            .unwrap_or(0);
+                        (
+                            deadline,
+                            max_total,
+                            flags,
+                            split_destinations,
+                            min_claim_interval_secs,
+                            options_offset + 1 + options_len,
+                        )
+                    }
+                };
+                let schedules_len = rest.get(schedules_offset..).ok_or(InvalidInstruction)?.len();
+                let number_of_schedules = match schedule_count {
+                    Some(schedule_count) if schedules_len != schedule_count * SCHEDULE_SIZE => {
+                        msg!("Expected {} bytes for {} schedules, got {}", schedule_count * SCHEDULE_SIZE, schedule_count, schedules_len);
+                        return Err(InvalidInstruction.into());
+                    }
+                    Some(schedule_count) => schedule_count,
+                    None if schedules_len % SCHEDULE_SIZE != 0 => {
+                        msg!("The schedules take {} bytes, not a multiple of {}", schedules_len, SCHEDULE_SIZE);
+                        return Err(InvalidInstruction.into());
+                    }
+                    None => schedules_len / SCHEDULE_SIZE,
+                };
                 let mut schedules: Vec<Schedule> = Vec::with_capacity(number_of_schedules);
-                let mut offset = 96;
+                let mut offset = schedules_offset;
                 for _ in 0..number_of_schedules {
                     let release_time = rest
                         .get(offset..offset + 8)
@@ -158,23 +1180,48 @@ impl LockTokenInstruction {
                     mint_address,
                     destination_token_address,
                     schedules,
+                    deadline,
+                    max_total,
+                    is_nft: flags & CREATE_FLAG_NFT != 0,
+                    is_immutable: flags & CREATE_FLAG_IMMUTABLE != 0,
+                    pay_fee_in_usd: flags & CREATE_FLAG_PAY_FEE_IN_USD != 0,
+                    pay_fee_in_token: flags & CREATE_FLAG_PAY_FEE_IN_TOKEN != 0,
+                    with_fee_discount: flags & CREATE_FLAG_FEE_DISCOUNT != 0,
+                    time_basis: if flags & CREATE_FLAG_SLOT_BASIS != 0 {
+                        TimeBasis::Slot
+                    } else {
+                        TimeBasis::UnixTimestamp
+                    },
+                    split_destinations,
+                    with_wallet_state: flags & CREATE_FLAG_WALLET_STATE != 0,
+                    min_claim_interval_secs,
                 }
             }
-            2 | 3 => {
-                let seeds: [u8; 32] = rest
+            2 => {
+                let seeds: LockSeed = rest
                     .get(..32)
                     .and_then(|slice| slice.try_into().ok())
-                    .unwrap();
-                match tag {
-                    2 => Self::Unlock { seeds },
-                    _ => Self::TransferLocks { seeds },
-                }
+                    .ok_or(InvalidInstruction)?;
+                // The deadline is optional for compatibility with the legacy encoding
+                let deadline = rest
+                    .get(32..40)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .unwrap_or(0);
+                Self::Unlock { seeds, deadline }
+            }
+            3 => {
+                let seeds: LockSeed = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                Self::TransferLocks { seeds }
             }
             4 => {
-                let seeds: [u8; 32] = rest
+                let seeds: LockSeed = rest
                     .get(..32)
                     .and_then(|slice| slice.try_into().ok())
-                    .unwrap();
+                    .ok_or(InvalidInstruction)?;
                 let index: u32 = rest
                     .get(32..36)
                     .and_then(|slice| slice.try_into().ok())
@@ -192,14 +1239,13 @@ impl LockTokenInstruction {
                 }
             }
             5 => {
-                let is_pause_u8: u8 = rest
+                let pause_flags: u8 = rest
                     .get(..1)
                     .and_then(|slice| slice.try_into().ok())
                     .map(u8::from_le_bytes)
                     .ok_or(InvalidInstruction)?;
-                let is_pause: bool = is_pause_u8 == 1;
                 Self::PauseContract {
-                    is_pause,
+                    pause_flags,
                 }
             }
             6 => {
@@ -223,11 +1269,22 @@ impl LockTokenInstruction {
                     .and_then(|slice| slice.try_into().ok())
                     .map(Pubkey::new)
                     .ok_or(InvalidInstruction)?;
+                let mut candidate_wallets = [Pubkey::default(); MAX_CANDIDATE_WALLETS];
+                for (i, wallet) in candidate_wallets.iter_mut().enumerate() {
+                    *wallet = rest
+                        .get(104 + i * 32..136 + i * 32)
+                        .and_then(|slice| slice.try_into().ok())
+                        .map(Pubkey::new)
+                        .ok_or(InvalidInstruction)?;
+                }
+                let present_fields = rest.get(104 + MAX_CANDIDATE_WALLETS * 32).copied().unwrap_or(FEE_PARAMS_ALL);
                 Self::SetFeeParams {
                     price_estimator,
                     usd_token_address,
                     fees_in_usd,
                     company_wallet,
+                    candidate_wallets,
+                    present_fields,
                 }
             }
             7 => {
@@ -267,230 +1324,2960 @@ impl LockTokenInstruction {
                     is_free,
                 }
             }
-            10 => {
-                Self::TransferOwnership {}
+            12 => {
+                let seeds: LockSeed = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                let destination_address = rest
+                    .get(32..64)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new)
+                    .ok_or(InvalidInstruction)?;
+                let schedules_len = rest.get(64..).ok_or(InvalidInstruction)?.len();
+                if schedules_len % SCHEDULE_SIZE != 0 {
+                    msg!("The schedules take {} bytes, not a multiple of {}", schedules_len, SCHEDULE_SIZE);
+                    return Err(InvalidInstruction.into());
+                }
+                let number_of_schedules = schedules_len / SCHEDULE_SIZE;
+                let mut schedules: Vec<Schedule> = Vec::with_capacity(number_of_schedules);
+                let mut offset = 64;
+                for _ in 0..number_of_schedules {
+                    let release_time = rest
+                        .get(offset..offset + 8)
+                        .and_then(|slice| slice.try_into().ok())
+                        .map(u64::from_le_bytes)
+                        .ok_or(InvalidInstruction)?;
+                    let amount = rest
+                        .get(offset + 8..offset + 16)
+                        .and_then(|slice| slice.try_into().ok())
+                        .map(u64::from_le_bytes)
+                        .ok_or(InvalidInstruction)?;
+                    offset += SCHEDULE_SIZE;
+                    schedules.push(Schedule {
+                        release_time,
+                        amount,
+                    })
+                }
+                Self::CreateNative {
+                    seeds,
+                    destination_address,
+                    schedules,
+                }
             }
-            _ => {
-                msg!("Unsupported tag");
-                return Err(InvalidInstruction.into());
+            13 => {
+                let max_price_age_slots = rest
+                    .get(..8)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                Self::SetMaxPriceAge {
+                    max_price_age_slots,
+                }
             }
-        })
-    }
-
-    pub fn pack(&self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(size_of::<Self>());
-        match self {
-            &Self::Init {
-                seeds,
-                number_of_schedules,
-            } => {
-                buf.push(0);
-                buf.extend_from_slice(&seeds);
-                buf.extend_from_slice(&number_of_schedules.to_le_bytes())
+            14 => {
+                let mint_address = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new)
+                    .ok_or(InvalidInstruction)?;
+                let has_override = rest.get(32).copied().ok_or(InvalidInstruction)? == 1;
+                let fee_in_usd = rest
+                    .get(33..41)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                Self::SetTokenFee {
+                    mint_address,
+                    fee_in_usd: if has_override { Some(fee_in_usd) } else { None },
+                }
             }
-            Self::Create {
-                seeds,
-                mint_address,
-                destination_token_address,
-                schedules,
-            } => {
-                buf.push(1);
-                buf.extend_from_slice(seeds);
-                buf.extend_from_slice(&mint_address.to_bytes());
-                buf.extend_from_slice(&destination_token_address.to_bytes());
-                for s in schedules.iter() {
-                    buf.extend_from_slice(&s.release_time.to_le_bytes());
-                    buf.extend_from_slice(&s.amount.to_le_bytes());
+            15 => {
+                let admin = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new)
+                    .ok_or(InvalidInstruction)?;
+                Self::InitializeGlobalState {
+                    admin,
                 }
             }
-            &Self::Unlock { seeds } => {
-                buf.push(2);
-                buf.extend_from_slice(&seeds);
+            16 => {
+                let new_admin = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new)
+                    .ok_or(InvalidInstruction)?;
+                Self::ProposeOwner {
+                    new_admin,
+                }
             }
-            &Self::TransferLocks { seeds } => {
-                buf.push(3);
-                buf.extend_from_slice(&seeds);
+            17 => {
+                Self::AcceptOwnership {}
             }
-            &Self::ExtendLockDuration {
-                seeds,
-                index,
-                release_time,
-            } => {
-                buf.push(4);
-                buf.extend_from_slice(&seeds);
-                buf.extend_from_slice(&index.to_le_bytes());
-                buf.extend_from_slice(&release_time.to_le_bytes());
+            18 => {
+                Self::CancelOwnershipTransfer {}
             }
-            &Self::PauseContract {
-                is_pause,
-            } => {
-                buf.push(5);
-                buf.extend_from_slice(&(is_pause as u8).to_le_bytes());
+            19 => {
+                let min_lock_duration_secs = rest
+                    .get(..8)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let min_lock_amount = rest
+                    .get(8..16)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                Self::SetLockMinimums {
+                    min_lock_duration_secs,
+                    min_lock_amount,
+                }
             }
-            &Self::SetFeeParams {
-                price_estimator,
-                usd_token_address,
-                fees_in_usd,
-                company_wallet,
-            } => {
-                buf.push(6);
-                buf.extend_from_slice(&price_estimator.to_bytes());
-                buf.extend_from_slice(&usd_token_address.to_bytes());
-                buf.extend_from_slice(&fees_in_usd.to_le_bytes());
-                buf.extend_from_slice(&company_wallet.to_bytes());
+            20 => {
+                let seeds: LockSeed = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                let new_destination = rest
+                    .get(32..64)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new)
+                    .ok_or(InvalidInstruction)?;
+                Self::ProposeTransferLocks {
+                    seeds,
+                    new_destination,
+                }
             }
-            &Self::SetFeesInUSD {
-                fees_in_usd,
-            } => {
-                buf.push(7);
-                buf.extend_from_slice(&fees_in_usd.to_le_bytes());
+            23 => {
+                let seeds: LockSeed = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                let entries = rest.get(32..).ok_or(InvalidInstruction)?;
+                if entries.len() % EXTENSION_SIZE != 0 {
+                    return Err(InvalidInstruction.into());
+                }
+                let extensions = entries
+                    .chunks_exact(EXTENSION_SIZE)
+                    .map(|entry| {
+                        let index = entry
+                            .get(..4)
+                            .and_then(|slice| slice.try_into().ok())
+                            .map(u32::from_le_bytes)
+                            .ok_or(InvalidInstruction)?;
+                        let release_time = entry
+                            .get(4..)
+                            .and_then(|slice| slice.try_into().ok())
+                            .map(u64::from_le_bytes)
+                            .ok_or(InvalidInstruction)?;
+                        Ok((index, release_time))
+                    })
+                    .collect::<Result<_, ProgramError>>()?;
+                Self::ExtendLockDurations {
+                    seeds,
+                    extensions,
+                }
             }
-            &Self::SetCompanyWallet {
-                company_wallet,
-            } => {
-                buf.push(8);
-                buf.extend_from_slice(&company_wallet.to_bytes());
+            24 => {
+                let seeds: LockSeed = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                let index: u32 = rest
+                    .get(32..36)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u32::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let new_release_time: u64 = rest
+                    .get(36..44)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                Self::Relock {
+                    seeds,
+                    index,
+                    new_release_time,
+                }
             }
-            &Self::SetFreeToken {
-                mint_address,
-                is_free,
-            } => {
-                buf.push(9);
-                buf.extend_from_slice(&mint_address.to_bytes());
-                buf.extend_from_slice(&(is_free as u8).to_le_bytes());
+            25 => {
+                if rest.is_empty() || rest.len() % 32 != 0 {
+                    return Err(InvalidInstruction.into());
+                }
+                let seeds_list = rest
+                    .chunks_exact(32)
+                    .map(LockSeed::try_from)
+                    .collect::<Result<_, _>>()?;
+                Self::BatchUnlock { seeds_list }
             }
-            &Self::TransferOwnership {} => {
-                buf.push(10);
+            26 => {
+                let seeds: LockSeed = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                let mint_address = rest
+                    .get(32..64)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new)
+                    .ok_or(InvalidInstruction)?;
+                let destination_token_address = rest
+                    .get(64..96)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new)
+                    .ok_or(InvalidInstruction)?;
+                let start_time = rest
+                    .get(96..104)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let period_secs = rest
+                    .get(104..112)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let periods = rest
+                    .get(112..116)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u32::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let amount_per_period = rest
+                    .get(116..124)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                Self::CreateRecurring {
+                    seeds,
+                    mint_address,
+                    destination_token_address,
+                    start_time,
+                    period_secs,
+                    periods,
+                    amount_per_period,
+                }
             }
-        };
-        buf
-    }
-}
-
-pub fn init(
-    system_program_id: &Pubkey,
-    rent_program_id: &Pubkey,
-    locking_program_id: &Pubkey,
-    payer_key: &Pubkey,
-    locking_account: &Pubkey,
-    seeds: [u8; 32],
-    number_of_schedules: u32,
-) -> Result<Instruction, ProgramError> {
-    let data = LockTokenInstruction::Init {
-        seeds,
-        number_of_schedules,
-    }
-    .pack();
-    let accounts = vec![
-        AccountMeta::new_readonly(*system_program_id, false),
-        AccountMeta::new_readonly(*rent_program_id, false),
-        AccountMeta::new(*payer_key, true),
-        AccountMeta::new(*locking_account, false),
-    ];
-    Ok(Instruction {
-        program_id: *locking_program_id,
-        accounts,
-        data,
-    })
-}
-
-pub fn create(
-    locking_program_id: &Pubkey,
-    token_program_id: &Pubkey,
-    locking_account_key: &Pubkey,
-    locking_token_account_key: &Pubkey,
-    source_token_account_owner_key: &Pubkey,
-    source_token_account_key: &Pubkey,
-    destination_token_account_key: &Pubkey,
-    mint_address: &Pubkey,
-    schedules: Vec<Schedule>,
-    seeds: [u8; 32],
-) -> Result<Instruction, ProgramError> {
-    let data = LockTokenInstruction::Create {
-        mint_address: *mint_address,
-        seeds,
-        destination_token_address: *destination_token_account_key,
-        schedules,
-    }
-    .pack();
-    let accounts = vec![
-        AccountMeta::new_readonly(*token_program_id, false),
-        AccountMeta::new(*locking_account_key, false),
-        AccountMeta::new(*locking_token_account_key, false),
-        AccountMeta::new_readonly(*source_token_account_owner_key, true),
-        AccountMeta::new(*source_token_account_key, false),
-    ];
-    Ok(Instruction {
+            27 => {
+                let seeds: LockSeed = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                let mint_address = rest
+                    .get(32..64)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new)
+                    .ok_or(InvalidInstruction)?;
+                let destination_token_address = rest
+                    .get(64..96)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new)
+                    .ok_or(InvalidInstruction)?;
+                let cliff_time = rest
+                    .get(96..104)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let cliff_bps = rest
+                    .get(104..106)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u16::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let vesting_end = rest
+                    .get(106..114)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let total_amount = rest
+                    .get(114..122)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                Self::CreateVesting {
+                    seeds,
+                    mint_address,
+                    destination_token_address,
+                    cliff_time,
+                    cliff_bps,
+                    vesting_end,
+                    total_amount,
+                }
+            }
+            21 | 22 => {
+                let seeds: LockSeed = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                match tag {
+                    21 => Self::AcceptTransferLocks { seeds },
+                    _ => Self::CancelTransferLocks { seeds },
+                }
+            }
+            29 => {
+                let seeds: LockSeed = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                Self::MigrateLock { seeds }
+            }
+            30 => {
+                let seeds: LockSeed = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                let rescue_destination = rest
+                    .get(32..64)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new)
+                    .ok_or(InvalidInstruction)?;
+                Self::ScheduleEmergencyUnlock {
+                    seeds,
+                    rescue_destination,
+                }
+            }
+            31 | 32 | 33 | 36 | 39 | 40 | 45 | 46 => {
+                let seeds: LockSeed = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                match tag {
+                    31 => Self::ExecuteEmergencyUnlock { seeds },
+                    32 => Self::CancelEmergencyUnlock { seeds },
+                    33 => Self::SweepExcess { seeds },
+                    36 => Self::GetClaimableAmount { seeds },
+                    39 => Self::CloseLockMetadata { seeds },
+                    40 => Self::ReclaimUninitializedLock { seeds },
+                    45 => Self::RevokeLockDelegate { seeds },
+                    _ => Self::ClaimAndClose { seeds },
+                }
+            }
+            34 => {
+                let fee_bps_in_token = rest
+                    .get(..2)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u16::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                Self::SetFeeBpsInToken { fee_bps_in_token }
+            }
+            35 => {
+                let mut fee_discount_tiers = [FeeDiscountTier::default(); MAX_FEE_DISCOUNT_TIERS];
+                for (i, tier) in fee_discount_tiers.iter_mut().enumerate() {
+                    let offset = i * FEE_DISCOUNT_TIER_SIZE;
+                    tier.min_balance = rest
+                        .get(offset..offset + 8)
+                        .and_then(|slice| slice.try_into().ok())
+                        .map(u64::from_le_bytes)
+                        .ok_or(InvalidInstruction)?;
+                    tier.discount_bps = rest
+                        .get(offset + 8..offset + FEE_DISCOUNT_TIER_SIZE)
+                        .and_then(|slice| slice.try_into().ok())
+                        .map(u16::from_le_bytes)
+                        .ok_or(InvalidInstruction)?;
+                }
+                Self::SetFeeDiscountTiers { fee_discount_tiers }
+            }
+            37 => {
+                let seeds: LockSeed = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                let mint_address = rest
+                    .get(32..64)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new)
+                    .ok_or(InvalidInstruction)?;
+                let destination_token_address = rest
+                    .get(64..96)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new)
+                    .ok_or(InvalidInstruction)?;
+                let schedule_count = rest
+                    .get(96..98)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u16::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                if schedule_count > MAX_SCHEDULES {
+                    msg!("A lock has at most {} schedules", MAX_SCHEDULES);
+                    return Err(InvalidInstruction.into());
+                }
+                let schedules_len = rest.get(98..).ok_or(InvalidInstruction)?.len();
+                if schedules_len != schedule_count as usize * MULTI_SCHEDULE_SIZE {
+                    msg!(
+                        "Expected {} bytes for {} schedules, got {}",
+                        schedule_count as usize * MULTI_SCHEDULE_SIZE,
+                        schedule_count,
+                        schedules_len
+                    );
+                    return Err(InvalidInstruction.into());
+                }
+                let schedules = rest[98..]
+                    .chunks_exact(MULTI_SCHEDULE_SIZE)
+                    .map(|entry| MultiSchedule {
+                        release_time: u64::from_le_bytes(entry[..8].try_into().unwrap()),
+                        amount: u64::from_le_bytes(entry[8..16].try_into().unwrap()),
+                        destination_token_address: Pubkey::new(&entry[16..MULTI_SCHEDULE_SIZE]),
+                    })
+                    .collect();
+                Self::CreateMulti {
+                    seeds,
+                    mint_address,
+                    destination_token_address,
+                    schedules,
+                }
+            }
+            38 => {
+                let seeds: LockSeed = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                // The name and the URI are each a u8 length followed by that many bytes of UTF-8
+                let name_len = rest.get(32).copied().ok_or(InvalidInstruction)? as usize;
+                let name = rest.get(33..33 + name_len).ok_or(InvalidInstruction)?;
+                let uri_offset = 33 + name_len;
+                let uri_len = rest.get(uri_offset).copied().ok_or(InvalidInstruction)? as usize;
+                let uri = rest.get(uri_offset + 1..uri_offset + 1 + uri_len).ok_or(InvalidInstruction)?;
+                if rest.len() != uri_offset + 1 + uri_len {
+                    msg!("Unexpected bytes after the URI");
+                    return Err(InvalidInstruction.into());
+                }
+                Self::SetLockMetadata {
+                    seeds,
+                    name: String::from_utf8(name.to_vec()).map_err(|_| InvalidInstruction)?,
+                    uri: String::from_utf8(uri.to_vec()).map_err(|_| InvalidInstruction)?,
+                }
+            }
+            41 => {
+                let wallet = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new)
+                    .ok_or(InvalidInstruction)?;
+                let is_exempt = match rest.get(32) {
+                    Some(0) => false,
+                    Some(1) => true,
+                    _ => return Err(InvalidInstruction.into()),
+                };
+                Self::SetFeeExemptWallet { wallet, is_exempt }
+            }
+            42 => {
+                let max_lock_duration_secs = rest
+                    .get(..8)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                Self::SetMaxLockDuration { max_lock_duration_secs }
+            }
+            43 => {
+                let seeds: LockSeed = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                let new_seeds: LockSeed = rest
+                    .get(32..64)
+                    .and_then(|slice| slice.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                let entries = rest.get(64..).ok_or(InvalidInstruction)?;
+                if entries.len() % 4 != 0 {
+                    return Err(InvalidInstruction.into());
+                }
+                let indices = entries
+                    .chunks_exact(4)
+                    .map(|entry| u32::from_le_bytes(entry.try_into().unwrap()))
+                    .collect();
+                Self::TransferScheduleIndices {
+                    seeds,
+                    new_seeds,
+                    indices,
+                }
+            }
+            44 => {
+                let seeds: LockSeed = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                let delegate = rest
+                    .get(32..64)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new)
+                    .ok_or(InvalidInstruction)?;
+                Self::ApproveLockDelegate { seeds, delegate }
+            }
+            47 => {
+                let mint = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new)
+                    .ok_or(InvalidInstruction)?;
+                Self::SnapshotMintLocked { mint }
+            }
+            48 => {
+                let seeds: LockSeed = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                let expiry = rest
+                    .get(32..40)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                Self::UnlockWithPermit { seeds, expiry }
+            }
+            49 => {
+                let creator = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new)
+                    .ok_or(InvalidInstruction)?;
+                let mint_address = rest
+                    .get(32..64)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new)
+                    .ok_or(InvalidInstruction)?;
+                let nonce = rest
+                    .get(64..72)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let number_of_schedules = rest
+                    .get(72..76)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u32::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                Self::InitDerived {
+                    creator,
+                    mint_address,
+                    nonce,
+                    number_of_schedules,
+                }
+            }
+            50 => {
+                let new_mint = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new)
+                    .ok_or(InvalidInstruction)?;
+                Self::SetOwnerTokenMint { new_mint }
+            }
+            51 => {
+                let seeds: LockSeed = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .ok_or(InvalidInstruction)?;
+                let amount = rest
+                    .get(32..40)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                Self::UnlockAmount { seeds, amount }
+            }
+            52 => {
+                let mut candidate_wallets = [Pubkey::default(); MAX_CANDIDATE_WALLETS];
+                for (i, wallet) in candidate_wallets.iter_mut().enumerate() {
+                    *wallet = rest
+                        .get(i * 32..(i + 1) * 32)
+                        .and_then(|slice| slice.try_into().ok())
+                        .map(Pubkey::new)
+                        .ok_or(InvalidInstruction)?;
+                }
+                Self::ProposeCandidateWallets { candidate_wallets }
+            }
+            53 => Self::AcceptCandidateWallets {},
+            _ => {
+                msg!("Unsupported tag");
+                return Err(InvalidInstruction.into());
+            }
+        })
+    }
+
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(size_of::<Self>());
+        match self {
+            &Self::Init {
+                seeds,
+                number_of_schedules,
+            } => {
+                buf.push(0);
+                buf.extend_from_slice(seeds.as_ref());
+                buf.extend_from_slice(&number_of_schedules.to_le_bytes())
+            }
+            Self::Create {
+                seeds,
+                mint_address,
+                destination_token_address,
+                schedules,
+                deadline,
+                max_total,
+                is_nft,
+                is_immutable,
+                pay_fee_in_usd,
+                pay_fee_in_token,
+                with_fee_discount,
+                time_basis,
+                split_destinations,
+                with_wallet_state,
+                min_claim_interval_secs,
+            } => {
+                let mut flags = 0;
+                if *is_nft {
+                    flags |= CREATE_FLAG_NFT;
+                }
+                if *is_immutable {
+                    flags |= CREATE_FLAG_IMMUTABLE;
+                }
+                if *pay_fee_in_usd {
+                    flags |= CREATE_FLAG_PAY_FEE_IN_USD;
+                }
+                if *pay_fee_in_token {
+                    flags |= CREATE_FLAG_PAY_FEE_IN_TOKEN;
+                }
+                if *with_fee_discount {
+                    flags |= CREATE_FLAG_FEE_DISCOUNT;
+                }
+                if *time_basis == TimeBasis::Slot {
+                    flags |= CREATE_FLAG_SLOT_BASIS;
+                }
+                if *with_wallet_state {
+                    flags |= CREATE_FLAG_WALLET_STATE;
+                }
+                // The split destinations follow the flags, which are then written even when zero,
+                // and the minimum claim interval follows the split destinations
+                let with_splits = !split_destinations.is_empty() || *min_claim_interval_secs != 0;
+                let with_flags = flags != 0 || with_splits;
+                let options_len = match (max_total, with_flags) {
+                    (_, true) if with_splits => {
+                        let interval_len = if *min_claim_interval_secs != 0 { 4 } else { 0 };
+                        CREATE_FLAGS_OPTION_LEN + 1 + split_destinations.len() * SPLIT_DESTINATION_SIZE + interval_len
+                    }
+                    (_, true) => CREATE_FLAGS_OPTION_LEN,
+                    (Some(_), false) => CREATE_OPTIONS_LEN,
+                    (None, false) => CREATE_DEADLINE_OPTION_LEN,
+                };
+                buf.reserve_exact(1 + 32 * 3 + 2 + 1 + options_len + schedules.len() * SCHEDULE_SIZE);
+                buf.push(28);
+                buf.extend_from_slice(seeds.as_ref());
+                buf.extend_from_slice(&mint_address.to_bytes());
+                buf.extend_from_slice(&destination_token_address.to_bytes());
+                buf.extend_from_slice(&(schedules.len() as u16).to_le_bytes());
+                match (max_total, with_flags) {
+                    (_, true) => {
+                        buf.push(options_len as u8);
+                        buf.extend_from_slice(&deadline.to_le_bytes());
+                        buf.extend_from_slice(&max_total.unwrap_or(u64::MAX).to_le_bytes());
+                        buf.push(flags);
+                        if with_splits {
+                            buf.push(split_destinations.len() as u8);
+                            for split in split_destinations.iter() {
+                                buf.extend_from_slice(&split.destination_address.to_bytes());
+                                buf.extend_from_slice(&split.share_bps.to_le_bytes());
+                            }
+                        }
+                        if *min_claim_interval_secs != 0 {
+                            buf.extend_from_slice(&min_claim_interval_secs.to_le_bytes());
+                        }
+                    }
+                    (Some(max_total), false) => {
+                        buf.push(CREATE_OPTIONS_LEN as u8);
+                        buf.extend_from_slice(&deadline.to_le_bytes());
+                        buf.extend_from_slice(&max_total.to_le_bytes());
+                    }
+                    (None, false) => {
+                        buf.push(CREATE_DEADLINE_OPTION_LEN as u8);
+                        buf.extend_from_slice(&deadline.to_le_bytes());
+                    }
+                }
+                for s in schedules.iter() {
+                    buf.extend_from_slice(&s.release_time.to_le_bytes());
+                    buf.extend_from_slice(&s.amount.to_le_bytes());
+                }
+            }
+            Self::CreateNative {
+                seeds,
+                destination_address,
+                schedules,
+            } => {
+                buf.push(12);
+                buf.extend_from_slice(seeds.as_ref());
+                buf.extend_from_slice(&destination_address.to_bytes());
+                for s in schedules.iter() {
+                    buf.extend_from_slice(&s.release_time.to_le_bytes());
+                    buf.extend_from_slice(&s.amount.to_le_bytes());
+                }
+            }
+            &Self::Unlock { seeds, deadline } => {
+                buf.push(2);
+                buf.extend_from_slice(seeds.as_ref());
+                buf.extend_from_slice(&deadline.to_le_bytes());
+            }
+            &Self::TransferLocks { seeds } => {
+                buf.push(3);
+                buf.extend_from_slice(seeds.as_ref());
+            }
+            &Self::ExtendLockDuration {
+                seeds,
+                index,
+                release_time,
+            } => {
+                buf.push(4);
+                buf.extend_from_slice(seeds.as_ref());
+                buf.extend_from_slice(&index.to_le_bytes());
+                buf.extend_from_slice(&release_time.to_le_bytes());
+            }
+            &Self::PauseContract {
+                pause_flags,
+            } => {
+                buf.push(5);
+                buf.extend_from_slice(&pause_flags.to_le_bytes());
+            }
+            &Self::SetFeeParams {
+                price_estimator,
+                usd_token_address,
+                fees_in_usd,
+                company_wallet,
+                candidate_wallets,
+                present_fields,
+            } => {
+                buf.push(6);
+                buf.extend_from_slice(&price_estimator.to_bytes());
+                buf.extend_from_slice(&usd_token_address.to_bytes());
+                buf.extend_from_slice(&fees_in_usd.to_le_bytes());
+                buf.extend_from_slice(&company_wallet.to_bytes());
+                for wallet in candidate_wallets.iter() {
+                    buf.extend_from_slice(&wallet.to_bytes());
+                }
+                buf.push(present_fields);
+            }
+            &Self::SetFeesInUSD {
+                fees_in_usd,
+            } => {
+                buf.push(7);
+                buf.extend_from_slice(&fees_in_usd.to_le_bytes());
+            }
+            &Self::SetCompanyWallet {
+                company_wallet,
+            } => {
+                buf.push(8);
+                buf.extend_from_slice(&company_wallet.to_bytes());
+            }
+            &Self::SetMaxPriceAge {
+                max_price_age_slots,
+            } => {
+                buf.push(13);
+                buf.extend_from_slice(&max_price_age_slots.to_le_bytes());
+            }
+            &Self::SetFreeToken {
+                mint_address,
+                is_free,
+            } => {
+                buf.push(9);
+                buf.extend_from_slice(&mint_address.to_bytes());
+                buf.extend_from_slice(&(is_free as u8).to_le_bytes());
+            }
+            &Self::SetTokenFee {
+                mint_address,
+                fee_in_usd,
+            } => {
+                buf.push(14);
+                buf.extend_from_slice(&mint_address.to_bytes());
+                buf.push(fee_in_usd.is_some() as u8);
+                buf.extend_from_slice(&fee_in_usd.unwrap_or(0).to_le_bytes());
+            }
+            &Self::InitializeGlobalState {
+                admin,
+            } => {
+                buf.push(15);
+                buf.extend_from_slice(&admin.to_bytes());
+            }
+            &Self::ProposeOwner {
+                new_admin,
+            } => {
+                buf.push(16);
+                buf.extend_from_slice(&new_admin.to_bytes());
+            }
+            &Self::AcceptOwnership {} => {
+                buf.push(17);
+            }
+            &Self::CancelOwnershipTransfer {} => {
+                buf.push(18);
+            }
+            &Self::SetLockMinimums {
+                min_lock_duration_secs,
+                min_lock_amount,
+            } => {
+                buf.push(19);
+                buf.extend_from_slice(&min_lock_duration_secs.to_le_bytes());
+                buf.extend_from_slice(&min_lock_amount.to_le_bytes());
+            }
+            &Self::ProposeTransferLocks {
+                seeds,
+                new_destination,
+            } => {
+                buf.push(20);
+                buf.extend_from_slice(seeds.as_ref());
+                buf.extend_from_slice(new_destination.as_ref());
+            }
+            &Self::AcceptTransferLocks { seeds } => {
+                buf.push(21);
+                buf.extend_from_slice(seeds.as_ref());
+            }
+            &Self::CancelTransferLocks { seeds } => {
+                buf.push(22);
+                buf.extend_from_slice(seeds.as_ref());
+            }
+            &Self::MigrateLock { seeds } => {
+                buf.push(29);
+                buf.extend_from_slice(seeds.as_ref());
+            }
+            &Self::ScheduleEmergencyUnlock {
+                seeds,
+                rescue_destination,
+            } => {
+                buf.push(30);
+                buf.extend_from_slice(seeds.as_ref());
+                buf.extend_from_slice(rescue_destination.as_ref());
+            }
+            &Self::ExecuteEmergencyUnlock { seeds } => {
+                buf.push(31);
+                buf.extend_from_slice(seeds.as_ref());
+            }
+            &Self::CancelEmergencyUnlock { seeds } => {
+                buf.push(32);
+                buf.extend_from_slice(seeds.as_ref());
+            }
+            &Self::SweepExcess { seeds } => {
+                buf.push(33);
+                buf.extend_from_slice(seeds.as_ref());
+            }
+            &Self::GetClaimableAmount { seeds } => {
+                buf.push(36);
+                buf.extend_from_slice(seeds.as_ref());
+            }
+            Self::SetLockMetadata { seeds, name, uri } => {
+                buf.push(38);
+                buf.extend_from_slice(seeds.as_ref());
+                buf.push(name.len() as u8);
+                buf.extend_from_slice(name.as_bytes());
+                buf.push(uri.len() as u8);
+                buf.extend_from_slice(uri.as_bytes());
+            }
+            &Self::CloseLockMetadata { seeds } => {
+                buf.push(39);
+                buf.extend_from_slice(seeds.as_ref());
+            }
+            &Self::ReclaimUninitializedLock { seeds } => {
+                buf.push(40);
+                buf.extend_from_slice(seeds.as_ref());
+            }
+            &Self::SetFeeExemptWallet { wallet, is_exempt } => {
+                buf.push(41);
+                buf.extend_from_slice(&wallet.to_bytes());
+                buf.push(is_exempt as u8);
+            }
+            &Self::SetMaxLockDuration { max_lock_duration_secs } => {
+                buf.push(42);
+                buf.extend_from_slice(&max_lock_duration_secs.to_le_bytes());
+            }
+            Self::TransferScheduleIndices {
+                seeds,
+                new_seeds,
+                indices,
+            } => {
+                buf.push(43);
+                buf.extend_from_slice(seeds.as_ref());
+                buf.extend_from_slice(new_seeds.as_ref());
+                for index in indices.iter() {
+                    buf.extend_from_slice(&index.to_le_bytes());
+                }
+            }
+            &Self::ApproveLockDelegate { seeds, delegate } => {
+                buf.push(44);
+                buf.extend_from_slice(seeds.as_ref());
+                buf.extend_from_slice(delegate.as_ref());
+            }
+            &Self::RevokeLockDelegate { seeds } => {
+                buf.push(45);
+                buf.extend_from_slice(seeds.as_ref());
+            }
+            &Self::ClaimAndClose { seeds } => {
+                buf.push(46);
+                buf.extend_from_slice(seeds.as_ref());
+            }
+            &Self::SnapshotMintLocked { mint } => {
+                buf.push(47);
+                buf.extend_from_slice(mint.as_ref());
+            }
+            &Self::UnlockWithPermit { seeds, expiry } => {
+                buf.push(48);
+                buf.extend_from_slice(seeds.as_ref());
+                buf.extend_from_slice(&expiry.to_le_bytes());
+            }
+            &Self::InitDerived {
+                creator,
+                mint_address,
+                nonce,
+                number_of_schedules,
+            } => {
+                buf.push(49);
+                buf.extend_from_slice(creator.as_ref());
+                buf.extend_from_slice(mint_address.as_ref());
+                buf.extend_from_slice(&nonce.to_le_bytes());
+                buf.extend_from_slice(&number_of_schedules.to_le_bytes());
+            }
+            &Self::SetOwnerTokenMint { new_mint } => {
+                buf.push(50);
+                buf.extend_from_slice(new_mint.as_ref());
+            }
+            &Self::UnlockAmount { seeds, amount } => {
+                buf.push(51);
+                buf.extend_from_slice(seeds.as_ref());
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::ProposeCandidateWallets { candidate_wallets } => {
+                buf.push(52);
+                for wallet in candidate_wallets.iter() {
+                    buf.extend_from_slice(wallet.as_ref());
+                }
+            }
+            &Self::AcceptCandidateWallets {} => {
+                buf.push(53);
+            }
+            &Self::SetFeeBpsInToken { fee_bps_in_token } => {
+                buf.push(34);
+                buf.extend_from_slice(&fee_bps_in_token.to_le_bytes());
+            }
+            Self::SetFeeDiscountTiers { fee_discount_tiers } => {
+                buf.push(35);
+                for tier in fee_discount_tiers.iter() {
+                    buf.extend_from_slice(&tier.min_balance.to_le_bytes());
+                    buf.extend_from_slice(&tier.discount_bps.to_le_bytes());
+                }
+            }
+            Self::CreateMulti {
+                seeds,
+                mint_address,
+                destination_token_address,
+                schedules,
+            } => {
+                buf.reserve_exact(1 + 32 * 3 + 2 + schedules.len() * MULTI_SCHEDULE_SIZE);
+                buf.push(37);
+                buf.extend_from_slice(seeds.as_ref());
+                buf.extend_from_slice(&mint_address.to_bytes());
+                buf.extend_from_slice(&destination_token_address.to_bytes());
+                buf.extend_from_slice(&(schedules.len() as u16).to_le_bytes());
+                for s in schedules.iter() {
+                    buf.extend_from_slice(&s.release_time.to_le_bytes());
+                    buf.extend_from_slice(&s.amount.to_le_bytes());
+                    buf.extend_from_slice(&s.destination_token_address.to_bytes());
+                }
+            }
+            Self::ExtendLockDurations {
+                seeds,
+                extensions,
+            } => {
+                buf.push(23);
+                buf.extend_from_slice(seeds.as_ref());
+                for (index, release_time) in extensions.iter() {
+                    buf.extend_from_slice(&index.to_le_bytes());
+                    buf.extend_from_slice(&release_time.to_le_bytes());
+                }
+            }
+            &Self::Relock {
+                seeds,
+                index,
+                new_release_time,
+            } => {
+                buf.push(24);
+                buf.extend_from_slice(seeds.as_ref());
+                buf.extend_from_slice(&index.to_le_bytes());
+                buf.extend_from_slice(&new_release_time.to_le_bytes());
+            }
+            Self::BatchUnlock { seeds_list } => {
+                buf.push(25);
+                for seeds in seeds_list.iter() {
+                    buf.extend_from_slice(seeds.as_ref());
+                }
+            }
+            &Self::CreateRecurring {
+                seeds,
+                mint_address,
+                destination_token_address,
+                start_time,
+                period_secs,
+                periods,
+                amount_per_period,
+            } => {
+                buf.push(26);
+                buf.extend_from_slice(seeds.as_ref());
+                buf.extend_from_slice(&mint_address.to_bytes());
+                buf.extend_from_slice(&destination_token_address.to_bytes());
+                buf.extend_from_slice(&start_time.to_le_bytes());
+                buf.extend_from_slice(&period_secs.to_le_bytes());
+                buf.extend_from_slice(&periods.to_le_bytes());
+                buf.extend_from_slice(&amount_per_period.to_le_bytes());
+            }
+            &Self::CreateVesting {
+                seeds,
+                mint_address,
+                destination_token_address,
+                cliff_time,
+                cliff_bps,
+                vesting_end,
+                total_amount,
+            } => {
+                buf.push(27);
+                buf.extend_from_slice(seeds.as_ref());
+                buf.extend_from_slice(&mint_address.to_bytes());
+                buf.extend_from_slice(&destination_token_address.to_bytes());
+                buf.extend_from_slice(&cliff_time.to_le_bytes());
+                buf.extend_from_slice(&cliff_bps.to_le_bytes());
+                buf.extend_from_slice(&vesting_end.to_le_bytes());
+                buf.extend_from_slice(&total_amount.to_le_bytes());
+            }
+        };
+        buf
+    }
+
+    // Encodes the instruction as BORSH_FORMAT followed by its borsh serialization
+    #[cfg(feature = "borsh")]
+    pub fn pack_borsh(&self) -> Vec<u8> {
+        let mut buf = vec![BORSH_FORMAT];
+        // Serializing into a Vec cannot fail
+        self.serialize(&mut buf).unwrap();
+        buf
+    }
+
+    // Decodes instruction data of the borsh format, see pack_borsh.
+    // Accepts the same instructions as the legacy format, whose counts and lengths are bounded.
+    #[cfg(feature = "borsh")]
+    pub fn unpack_borsh(input: &[u8]) -> Result<Self, ProgramError> {
+        use LockTokenError::InvalidInstruction;
+        let (&format, rest) = input.split_first().ok_or(InvalidInstruction)?;
+        if format != BORSH_FORMAT {
+            msg!("Expected the borsh format {}, got {}", BORSH_FORMAT, format);
+            return Err(InvalidInstruction.into());
+        }
+        let instruction = Self::try_from_slice(rest).map_err(|_| {
+            msg!("Invalid borsh instruction data");
+            InvalidInstruction
+        })?;
+        instruction.assert_legacy_bounds()?;
+        Ok(instruction)
+    }
+
+    // Whether the data starts with the Anchor discriminator of an instruction, see idl::INSTRUCTIONS.
+    // Checked before the legacy tags and BORSH_FORMAT.
+    #[cfg(feature = "anchor-compat")]
+    fn is_anchor(input: &[u8]) -> bool {
+        input.get(..8).and_then(idl::instruction_index).is_some()
+    }
+
+    // Encodes the instruction as its Anchor discriminator followed by its borsh serialized fields
+    #[cfg(feature = "anchor-compat")]
+    pub fn pack_anchor(&self) -> Vec<u8> {
+        // Serializing into a Vec cannot fail
+        let borsh = self.try_to_vec().unwrap();
+        let mut buf = idl::INSTRUCTIONS[borsh[0] as usize].discriminator.to_vec();
+        buf.extend_from_slice(&borsh[1..]);
+        buf
+    }
+
+    // Decodes instruction data of the Anchor encoding, see pack_anchor
+    #[cfg(feature = "anchor-compat")]
+    pub fn unpack_anchor(input: &[u8]) -> Result<Self, ProgramError> {
+        use LockTokenError::InvalidInstruction;
+        let index = input.get(..8).and_then(idl::instruction_index).ok_or_else(|| {
+            msg!("Unknown instruction discriminator");
+            InvalidInstruction
+        })?;
+        // The borsh encoding of the enum is the variant index followed by the same fields
+        let mut data = Vec::with_capacity(input.len() - 7);
+        data.push(index as u8);
+        data.extend_from_slice(&input[8..]);
+        let instruction = Self::try_from_slice(&data).map_err(|_| {
+            msg!("Invalid instruction data for {}", idl::INSTRUCTIONS[index].name);
+            InvalidInstruction
+        })?;
+        instruction.assert_legacy_bounds()?;
+        Ok(instruction)
+    }
+
+    // Checks what the legacy format can not encode, so both formats accept the same instructions
+    #[cfg(feature = "borsh")]
+    fn assert_legacy_bounds(&self) -> Result<(), ProgramError> {
+        use LockTokenError::InvalidInstruction;
+        match self {
+            Self::Create { schedules, split_destinations, .. } => {
+                if schedules.len() > MAX_SCHEDULES as usize {
+                    msg!("A lock has at most {} schedules", MAX_SCHEDULES);
+                    return Err(InvalidInstruction.into());
+                }
+                if split_destinations.len() > MAX_SPLIT_DESTINATIONS {
+                    msg!("A lock is split across at most {} destinations", MAX_SPLIT_DESTINATIONS);
+                    return Err(InvalidInstruction.into());
+                }
+            }
+            Self::CreateMulti { schedules, .. } if schedules.len() > MAX_SCHEDULES as usize => {
+                msg!("A lock has at most {} schedules", MAX_SCHEDULES);
+                return Err(InvalidInstruction.into());
+            }
+            Self::SetLockMetadata { name, uri, .. } if name.len() > u8::MAX as usize || uri.len() > u8::MAX as usize => {
+                msg!("The name and the URI take at most {} bytes each", u8::MAX);
+                return Err(InvalidInstruction.into());
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+// Requirements of one account of an instruction, in the order the processor reads them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AccountRequirement {
+    pub name: &'static str,
+    pub writable: bool,
+    pub signer: bool,
+    pub description: &'static str,
+}
+
+const fn account(name: &'static str, writable: bool, signer: bool, description: &'static str) -> AccountRequirement {
+    AccountRequirement { name, writable, signer, description }
+}
+
+const INIT_ACCOUNTS: &[AccountRequirement] = &[
+    account("system_program", false, false, "The system program account"),
+    account("program_state", false, false, "The program state account"),
+    account("rent_sysvar", false, false, "The sysvar Rent account"),
+    account("payer", true, true, "The fee payer account, a keypair or a PDA signing through invoke_signed"),
+    account("locking_account", true, false, "The locking account"),
+];
+
+// Multisig signers of the source token account owner follow these accounts and are not part of the table
+const CREATE_ACCOUNTS: &[AccountRequirement] = &[
+    account("token_program", false, false, "The spl token program account, either spl token or token-2022"),
+    account("program_state", false, false, "The program state account"),
+    account("locking_account", true, false, "The locking account"),
+    account("locking_token_account", true, false, "The locking token account"),
+    account("source_token_account_owner", false, true, "The source token account owner or its delegate, not a signer when it is a multisig account"),
+    account("source_token_account", true, false, "The source token account"),
+    account("token_state", true, false, "The token state account, created on the first lock of the mint"),
+    account("company_wallet", true, false, "The company wallet account"),
+    account("system_program", false, false, "The system program account"),
+    account("rent_sysvar", false, false, "The sysvar Rent account"),
+    account("associated_token_program", false, false, "The associated token account program account"),
+    account("mint", false, false, "The token mint account"),
+    account("price_estimator", false, false, "The price estimator account, a Pyth price account of SOL/USD"),
+    account("fee_payer", true, true, "The fee payer account"),
+];
+
+const CREATE_NATIVE_ACCOUNTS: &[AccountRequirement] = &[
+    account("token_program", false, false, "The spl token program account"),
+    account("program_state", false, false, "The program state account"),
+    account("locking_account", true, false, "The locking account"),
+    account("locking_token_account", true, true, "The new locking token account"),
+    account("payer", true, true, "The payer account"),
+    account("token_state", true, false, "The token state account of the native mint, created on its first lock"),
+    account("company_wallet", true, false, "The company wallet account"),
+    account("system_program", false, false, "The system program account"),
+    account("rent_sysvar", false, false, "The sysvar Rent account"),
+    account("mint", false, false, "The native mint account"),
+    account("price_estimator", false, false, "The price estimator account, a Pyth price account of SOL/USD"),
+];
+
+const UNLOCK_ACCOUNTS: &[AccountRequirement] = &[
+    account("token_program", false, false, "The spl token program account"),
+    account("program_state", false, false, "The program state account"),
+    account("locking_account", true, false, "The locking account"),
+    account("locking_token_account", true, false, "The locking token account"),
+    account("destination_token_account", true, false, "The destination token account"),
+    account("mint", false, false, "The token mint account"),
+    account("token_state", true, false, "The token state account of the mint"),
+];
+
+const CLAIM_AND_CLOSE_ACCOUNTS: &[AccountRequirement] = &[
+    account("token_program", false, false, "The spl token program account"),
+    account("program_state", false, false, "The program state account"),
+    account("locking_account", true, false, "The locking account"),
+    account("locking_token_account", true, false, "The locking token account"),
+    account("destination_token_account", true, false, "The destination token account"),
+    account("mint", false, false, "The token mint account"),
+    account("token_state", true, false, "The token state account of the mint"),
+    account("refund_account", true, false, "The owner of the destination token account, receiving the rent"),
+];
+
+const UNLOCK_WITH_PERMIT_ACCOUNTS: &[AccountRequirement] = &[
+    account("token_program", false, false, "The spl token program account"),
+    account("program_state", false, false, "The program state account"),
+    account("locking_account", true, false, "The locking account"),
+    account("locking_token_account", true, false, "The locking token account"),
+    account("destination_token_account", true, false, "The destination token account"),
+    account("mint", false, false, "The token mint account"),
+    account("token_state", true, false, "The token state account of the mint"),
+    account("instructions_sysvar", false, false, "The sysvar Instructions account"),
+];
+
+// Followed by the locking account and the locking token account of every lock
+const SNAPSHOT_MINT_LOCKED_ACCOUNTS: &[AccountRequirement] = &[
+    account("token_program", false, false, "The spl token program account"),
+    account("system_program", false, false, "The system program account"),
+    account("payer", true, true, "The payer of the extra rent"),
+    account("token_state", true, false, "The token state account of the mint"),
+];
+
+// Followed by BATCH_UNLOCK_GROUP_LEN writable accounts for every lock
+const BATCH_UNLOCK_ACCOUNTS: &[AccountRequirement] = &[
+    account("token_program", false, false, "The spl token program account"),
+    account("program_state", false, false, "The program state account"),
+    account("mint", false, false, "The token mint account shared by every lock"),
+    account("token_state", true, false, "The token state account of the mint"),
+];
+
+const TRANSFER_LOCKS_ACCOUNTS: &[AccountRequirement] = &[
+    account("program_state", false, false, "The program state account"),
+    account("locking_account", true, false, "The locking account"),
+    account("destination_token_account", false, false, "The current destination token account"),
+    account("destination_token_account_owner", false, true, "The owner of the current destination token account"),
+    account("new_destination_token_account", false, false, "The new destination token account"),
+];
+
+const ACCEPT_TRANSFER_LOCKS_ACCOUNTS: &[AccountRequirement] = &[
+    account("program_state", false, false, "The program state account"),
+    account("locking_account", true, false, "The locking account"),
+    account("new_destination_token_account", false, false, "The proposed destination token account"),
+    account("new_destination_token_account_owner", false, true, "The owner of the proposed destination token account"),
+];
+
+const TRANSFER_SCHEDULE_INDICES_ACCOUNTS: &[AccountRequirement] = &[
+    account("token_program", false, false, "The spl token program account"),
+    account("program_state", false, false, "The program state account"),
+    account("locking_account", true, false, "The locking account"),
+    account("locking_token_account", true, false, "The locking token account"),
+    account("destination_token_account", false, false, "The destination token account"),
+    account("destination_token_account_owner", false, true, "The owner of the destination token account"),
+    account("new_locking_account", true, false, "The new locking account"),
+    account("new_locking_token_account", true, false, "The new locking token account"),
+    account("new_destination_token_account", false, false, "The destination token account of the new lock"),
+    account("mint", false, false, "The token mint account"),
+];
+
+const EXTEND_LOCK_DURATION_ACCOUNTS: &[AccountRequirement] = &[
+    account("program_state", false, false, "The program state account"),
+    account("locking_account", true, false, "The locking account"),
+    account("destination_token_account", false, false, "The destination token account"),
+    account("destination_token_account_owner", false, true, "The owner of the destination token account"),
+];
+
+// Multisig signers of the admin follow these accounts and are not part of the table
+const ADMIN_ACCOUNTS: &[AccountRequirement] = &[
+    account("admin", false, true, "The program admin account, not a signer when it is a multisig account"),
+    account("program_state", true, false, "The program state account"),
+];
+
+const ADMIN_TOKEN_STATE_ACCOUNTS: &[AccountRequirement] = &[
+    account("admin", false, true, "The program admin account, not a signer when it is a multisig account"),
+    account("program_state", true, false, "The program state account"),
+    account("token_state", true, false, "The token state account of the mint"),
+];
+
+const INITIALIZE_GLOBAL_STATE_ACCOUNTS: &[AccountRequirement] = &[
+    account("system_program", false, false, "The system program account"),
+    account("rent_sysvar", false, false, "The sysvar Rent account"),
+    account("upgrade_authority", true, true, "The upgrade authority of the program, which pays for the program state account"),
+    account("program_state", true, false, "The program state account"),
+    account("program_data", false, false, "The program data account of the program"),
+];
+
+const MIGRATE_LOCK_ACCOUNTS: &[AccountRequirement] = &[
+    account("system_program", false, false, "The system program account"),
+    account("payer", true, true, "The payer of the extra rent"),
+    account("locking_account", true, false, "The locking account"),
+];
+
+// Multisig signers of the admin follow these accounts and are not part of the table
+const SCHEDULE_EMERGENCY_UNLOCK_ACCOUNTS: &[AccountRequirement] = &[
+    account("admin", false, true, "The program admin account, not a signer when it is a multisig account"),
+    account("program_state", false, false, "The program state account"),
+    account("locking_account", true, false, "The locking account"),
+];
+
+const EXECUTE_EMERGENCY_UNLOCK_ACCOUNTS: &[AccountRequirement] = &[
+    account("token_program", false, false, "The spl token program account"),
+    account("program_state", false, false, "The program state account"),
+    account("locking_account", true, false, "The locking account"),
+    account("locking_token_account", true, false, "The locking token account"),
+    account("rescue_destination_token_account", true, false, "The rescue destination token account"),
+    account("mint", false, false, "The token mint account"),
+    account("token_state", true, false, "The token state account of the mint"),
+];
+
+const SWEEP_EXCESS_ACCOUNTS: &[AccountRequirement] = &[
+    account("token_program", false, false, "The spl token program account"),
+    account("program_state", false, false, "The program state account"),
+    account("locking_account", false, false, "The locking account"),
+    account("locking_token_account", true, false, "The locking token account"),
+    account("creator_token_account", true, false, "A token account of the mint owned by the creator of the lock"),
+    account("mint", false, false, "The token mint account"),
+];
+
+const GET_CLAIMABLE_AMOUNT_ACCOUNTS: &[AccountRequirement] = &[
+    account("locking_account", false, false, "The locking account"),
+];
+
+// Multisig signers of the admin follow these accounts and are not part of the table
+const SET_FEE_BPS_IN_TOKEN_ACCOUNTS: &[AccountRequirement] = &[
+    account("admin", false, true, "The program admin account, not a signer when it is a multisig account"),
+    account("program_state", true, false, "The program state account"),
+    account("system_program", false, false, "The system program account"),
+    account("payer", true, true, "The payer of the extra rent"),
+];
+
+const SET_LOCK_METADATA_ACCOUNTS: &[AccountRequirement] = &[
+    account("locking_account", false, false, "The locking account"),
+    account("lock_metadata", true, false, "The lock metadata account"),
+    account("destination_token_account", false, false, "The destination token account of the lock"),
+    account("authority", false, true, "The creator of the lock or the owner of the destination token account"),
+    account("payer", true, true, "The payer of the rent of a new metadata account"),
+    account("system_program", false, false, "The system program account"),
+];
+
+const CLOSE_LOCK_METADATA_ACCOUNTS: &[AccountRequirement] = &[
+    account("locking_account", false, false, "The locking account"),
+    account("lock_metadata", true, false, "The lock metadata account"),
+    account("destination_token_account", false, false, "The destination token account of the lock"),
+    account("authority", false, true, "The creator of the lock or the owner of the destination token account"),
+    account("receiver", true, false, "The receiver of the rent"),
+];
+
+const RECLAIM_UNINITIALIZED_LOCK_ACCOUNTS: &[AccountRequirement] = &[
+    account("locking_account", true, false, "The locking account"),
+    account("payer", true, true, "The payer of Init"),
+];
+
+// Multisig signers of the admin follow these accounts and are not part of the table
+const SET_FEE_EXEMPT_WALLET_ACCOUNTS: &[AccountRequirement] = &[
+    account("admin", false, true, "The program admin account, not a signer when it is a multisig account"),
+    account("program_state", false, false, "The program state account"),
+    account("wallet_state", true, false, "The wallet state account"),
+    account("system_program", false, false, "The system program account"),
+    account("payer", true, true, "The payer of the rent of a new wallet state account"),
+];
+
+const ACCEPT_OWNERSHIP_ACCOUNTS: &[AccountRequirement] = &[
+    account("pending_admin", false, true, "The proposed admin account, not a signer when it is a multisig account"),
+    account("program_state", true, false, "The program state account"),
+];
+
+// Multisig signers of the admin follow these accounts and are not part of the table
+const SET_OWNER_TOKEN_MINT_ACCOUNTS: &[AccountRequirement] = &[
+    account("admin", false, true, "The program admin account, not a signer when it is a multisig account"),
+    account("program_state", true, false, "The program state account"),
+    account("system_program", false, false, "The system program account"),
+    account("payer", true, true, "The payer of the extra rent"),
+    account("token_program", false, false, "The spl token program account of the new owner token, either spl token or token-2022"),
+    account("admin_token_account", false, false, "The token account of the new owner token owned by the admin"),
+];
+
+// Accounts of the instruction with the given tag. Unknown tags have none.
+pub fn account_requirements(tag: u8) -> &'static [AccountRequirement] {
+    match tag {
+        0 => INIT_ACCOUNTS,
+        1 | 11 | 26 | 27 | 28 | 37 => CREATE_ACCOUNTS,
+        2 | 51 => UNLOCK_ACCOUNTS,
+        3 => TRANSFER_LOCKS_ACCOUNTS,
+        4 | 20 | 22 | 23 | 24 | 32 | 44 | 45 => EXTEND_LOCK_DURATION_ACCOUNTS,
+        5 | 6 | 7 | 8 | 13 | 16 | 18 | 19 | 53 => ADMIN_ACCOUNTS,
+        9 | 14 => ADMIN_TOKEN_STATE_ACCOUNTS,
+        12 => CREATE_NATIVE_ACCOUNTS,
+        15 => INITIALIZE_GLOBAL_STATE_ACCOUNTS,
+        17 => ACCEPT_OWNERSHIP_ACCOUNTS,
+        21 => ACCEPT_TRANSFER_LOCKS_ACCOUNTS,
+        25 => BATCH_UNLOCK_ACCOUNTS,
+        29 => MIGRATE_LOCK_ACCOUNTS,
+        30 => SCHEDULE_EMERGENCY_UNLOCK_ACCOUNTS,
+        31 => EXECUTE_EMERGENCY_UNLOCK_ACCOUNTS,
+        33 => SWEEP_EXCESS_ACCOUNTS,
+        34 | 35 | 42 | 52 => SET_FEE_BPS_IN_TOKEN_ACCOUNTS,
+        43 => TRANSFER_SCHEDULE_INDICES_ACCOUNTS,
+        46 => CLAIM_AND_CLOSE_ACCOUNTS,
+        47 => SNAPSHOT_MINT_LOCKED_ACCOUNTS,
+        48 => UNLOCK_WITH_PERMIT_ACCOUNTS,
+        49 => INIT_ACCOUNTS,
+        50 => SET_OWNER_TOKEN_MINT_ACCOUNTS,
+        36 => GET_CLAIMABLE_AMOUNT_ACCOUNTS,
+        38 => SET_LOCK_METADATA_ACCOUNTS,
+        39 => CLOSE_LOCK_METADATA_ACCOUNTS,
+        40 => RECLAIM_UNINITIALIZED_LOCK_ACCOUNTS,
+        41 => SET_FEE_EXEMPT_WALLET_ACCOUNTS,
+        _ => &[],
+    }
+}
+
+// Checks a built instruction against its account requirements.
+// Extra trailing accounts are multisig signers, the accounts of every lock of a BatchUnlock or SnapshotMintLocked or
+// the schedule destinations of an Unlock, ClaimAndClose or UnlockWithPermit.
+// A multisig account does not sign, so signers may be missing when trailing accounts follow.
+fn debug_assert_account_requirements(instruction: &Instruction) {
+    if cfg!(debug_assertions) {
+        let requirements = account_requirements(instruction.data[0]);
+        assert!(instruction.accounts.len() >= requirements.len(), "missing accounts for tag {}", instruction.data[0]);
+        let has_trailing_accounts = instruction.accounts.len() > requirements.len();
+        for (meta, requirement) in instruction.accounts.iter().zip(requirements) {
+            assert_eq!(meta.is_writable, requirement.writable, "writable flag of {}", requirement.name);
+            if requirement.signer && !meta.is_signer && has_trailing_accounts {
+                continue;
+            }
+            assert_eq!(meta.is_signer, requirement.signer, "signer flag of {}", requirement.name);
+        }
+    }
+}
+
+pub fn init(
+    system_program_id: &Pubkey,
+    rent_program_id: &Pubkey,
+    locking_program_id: &Pubkey,
+    payer_key: &Pubkey,
+    locking_account: &Pubkey,
+    seeds: LockSeed,
+    number_of_schedules: u32,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::Init {
+        seeds,
+        number_of_schedules,
+    }
+    .pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let accounts = vec![
+        AccountMeta::new_readonly(*system_program_id, false),
+        AccountMeta::new_readonly(program_state_account_key, false),
+        AccountMeta::new_readonly(*rent_program_id, false),
+        AccountMeta::new(*payer_key, true),
+        AccountMeta::new(*locking_account, false),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+// Init of the locking account of the nonce-th lock of the creator for the mint, see pda::find_lock_address.
+// The later instructions on the lock take that locking account and LockSeed::default() as seeds.
+pub fn init_derived(
+    system_program_id: &Pubkey,
+    rent_program_id: &Pubkey,
+    locking_program_id: &Pubkey,
+    payer_key: &Pubkey,
+    creator: &Pubkey,
+    mint_address: &Pubkey,
+    nonce: u64,
+    number_of_schedules: u32,
+) -> Result<Instruction, ProgramError> {
+    let (locking_account, _) = find_lock_address(locking_program_id, creator, mint_address, nonce);
+    let data = LockTokenInstruction::InitDerived {
+        creator: *creator,
+        mint_address: *mint_address,
+        nonce,
+        number_of_schedules,
+    }
+    .pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let accounts = vec![
+        AccountMeta::new_readonly(*system_program_id, false),
+        AccountMeta::new_readonly(program_state_account_key, false),
+        AccountMeta::new_readonly(*rent_program_id, false),
+        AccountMeta::new(*payer_key, true),
+        AccountMeta::new(locking_account, false),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn create(
+    locking_program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    system_program_id: &Pubkey,
+    rent_program_id: &Pubkey,
+    associated_token_program_id: &Pubkey,
+    price_estimator_key: &Pubkey,
+    locking_account_key: &Pubkey,
+    locking_token_account_key: &Pubkey,
+    source_token_account_owner_key: &Pubkey,
+    source_token_account_key: &Pubkey,
+    fee_payer_key: &Pubkey,
+    destination_token_account_key: &Pubkey,
+    company_wallet_key: &Pubkey,
+    mint_address: &Pubkey,
+    schedules: Vec<Schedule>,
+    seeds: LockSeed,
+    deadline: u64,
+    is_immutable: bool,
+    time_basis: TimeBasis,
+    split_destinations: Vec<SplitDestination>,
+    min_claim_interval_secs: u32,
+    fee_payment: CreateFeePayment,
+    fee_discount_token_account: Option<Pubkey>,
+    with_wallet_state: bool,
+    signers: &[Pubkey],
+) -> Result<Instruction, ProgramError> {
+    if schedules.len() > MAX_SCHEDULES as usize || split_destinations.len() > MAX_SPLIT_DESTINATIONS {
+        return Err(ProgramError::InvalidArgument);
+    }
+    // The builder authorizes exactly the total of the given schedules
+    let max_total = schedules
+        .iter()
+        .try_fold(0u64, |total, s| total.checked_add(s.amount))
+        .ok_or(ProgramError::InvalidArgument)?;
+    let data = LockTokenInstruction::Create {
+        mint_address: *mint_address,
+        seeds,
+        destination_token_address: *destination_token_account_key,
+        schedules,
+        deadline,
+        max_total: Some(max_total),
+        is_nft: false,
+        is_immutable,
+        pay_fee_in_usd: matches!(fee_payment, CreateFeePayment::Usd(_)),
+        pay_fee_in_token: matches!(fee_payment, CreateFeePayment::Token { .. }),
+        with_fee_discount: fee_discount_token_account.is_some(),
+        time_basis,
+        split_destinations,
+        with_wallet_state,
+        min_claim_interval_secs,
+    }
+    .pack();
+    let mut accounts = create_account_metas(
+        locking_program_id,
+        token_program_id,
+        system_program_id,
+        rent_program_id,
+        associated_token_program_id,
+        price_estimator_key,
+        locking_account_key,
+        locking_token_account_key,
+        source_token_account_owner_key,
+        source_token_account_key,
+        fee_payer_key,
+        company_wallet_key,
+        mint_address,
+    )?;
+    match fee_payment {
+        CreateFeePayment::Sol => {}
+        CreateFeePayment::Usd(usd_fee_payment) => {
+            accounts.push(AccountMeta::new(usd_fee_payment.payer_token_account, false));
+            accounts.push(AccountMeta::new_readonly(usd_fee_payment.usd_mint, false));
+            accounts.push(AccountMeta::new(usd_fee_payment.company_token_account, false));
+        }
+        CreateFeePayment::Token { company_token_account } => {
+            accounts.push(AccountMeta::new(company_token_account, false));
+        }
+    }
+    if let Some(fee_discount_token_account) = fee_discount_token_account {
+        accounts.push(AccountMeta::new_readonly(fee_discount_token_account, false));
+    }
+    if with_wallet_state {
+        let (wallet_state_key, _) = find_wallet_state_address(locking_program_id, fee_payer_key);
+        accounts.push(AccountMeta::new_readonly(wallet_state_key, false));
+    }
+    // Signers of a multisig source token account owner
+    if !signers.is_empty() {
+        accounts[4].is_signer = false;
+        accounts.extend(signers.iter().map(|signer| AccountMeta::new_readonly(*signer, true)));
+    }
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+// Locks a single NFT until release_time
+pub fn create_nft(
+    locking_program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    system_program_id: &Pubkey,
+    rent_program_id: &Pubkey,
+    associated_token_program_id: &Pubkey,
+    price_estimator_key: &Pubkey,
+    locking_account_key: &Pubkey,
+    locking_token_account_key: &Pubkey,
+    source_token_account_owner_key: &Pubkey,
+    source_token_account_key: &Pubkey,
+    fee_payer_key: &Pubkey,
+    destination_token_account_key: &Pubkey,
+    company_wallet_key: &Pubkey,
+    mint_address: &Pubkey,
+    release_time: u64,
+    seeds: LockSeed,
+    deadline: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::Create {
+        mint_address: *mint_address,
+        seeds,
+        destination_token_address: *destination_token_account_key,
+        schedules: vec![Schedule { release_time, amount: 1 }],
+        deadline,
+        max_total: Some(1),
+        is_nft: true,
+        is_immutable: false,
+        pay_fee_in_usd: false,
+        pay_fee_in_token: false,
+        with_fee_discount: false,
+        time_basis: TimeBasis::UnixTimestamp,
+        split_destinations: Vec::new(),
+        with_wallet_state: false,
+        min_claim_interval_secs: 0,
+    }
+    .pack();
+    let accounts = create_account_metas(
+        locking_program_id,
+        token_program_id,
+        system_program_id,
+        rent_program_id,
+        associated_token_program_id,
+        price_estimator_key,
+        locking_account_key,
+        locking_token_account_key,
+        source_token_account_owner_key,
+        source_token_account_key,
+        fee_payer_key,
+        company_wallet_key,
+        mint_address,
+    )?;
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn create_recurring(
+    locking_program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    system_program_id: &Pubkey,
+    rent_program_id: &Pubkey,
+    associated_token_program_id: &Pubkey,
+    price_estimator_key: &Pubkey,
+    locking_account_key: &Pubkey,
+    locking_token_account_key: &Pubkey,
+    source_token_account_owner_key: &Pubkey,
+    source_token_account_key: &Pubkey,
+    fee_payer_key: &Pubkey,
+    destination_token_account_key: &Pubkey,
+    company_wallet_key: &Pubkey,
+    mint_address: &Pubkey,
+    seeds: LockSeed,
+    start_time: u64,
+    period_secs: u64,
+    periods: u32,
+    amount_per_period: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::CreateRecurring {
+        seeds,
+        mint_address: *mint_address,
+        destination_token_address: *destination_token_account_key,
+        start_time,
+        period_secs,
+        periods,
+        amount_per_period,
+    }
+    .pack();
+    let accounts = create_account_metas(
+        locking_program_id,
+        token_program_id,
+        system_program_id,
+        rent_program_id,
+        associated_token_program_id,
+        price_estimator_key,
+        locking_account_key,
+        locking_token_account_key,
+        source_token_account_owner_key,
+        source_token_account_key,
+        fee_payer_key,
+        company_wallet_key,
+        mint_address,
+    )?;
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn create_vesting(
+    locking_program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    system_program_id: &Pubkey,
+    rent_program_id: &Pubkey,
+    associated_token_program_id: &Pubkey,
+    price_estimator_key: &Pubkey,
+    locking_account_key: &Pubkey,
+    locking_token_account_key: &Pubkey,
+    source_token_account_owner_key: &Pubkey,
+    source_token_account_key: &Pubkey,
+    fee_payer_key: &Pubkey,
+    destination_token_account_key: &Pubkey,
+    company_wallet_key: &Pubkey,
+    mint_address: &Pubkey,
+    seeds: LockSeed,
+    cliff_time: u64,
+    cliff_bps: u16,
+    vesting_end: u64,
+    total_amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::CreateVesting {
+        seeds,
+        mint_address: *mint_address,
+        destination_token_address: *destination_token_account_key,
+        cliff_time,
+        cliff_bps,
+        vesting_end,
+        total_amount,
+    }
+    .pack();
+    let accounts = create_account_metas(
+        locking_program_id,
+        token_program_id,
+        system_program_id,
+        rent_program_id,
+        associated_token_program_id,
+        price_estimator_key,
+        locking_account_key,
+        locking_token_account_key,
+        source_token_account_owner_key,
+        source_token_account_key,
+        fee_payer_key,
+        company_wallet_key,
+        mint_address,
+    )?;
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn create_multi(
+    locking_program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    system_program_id: &Pubkey,
+    rent_program_id: &Pubkey,
+    associated_token_program_id: &Pubkey,
+    price_estimator_key: &Pubkey,
+    locking_account_key: &Pubkey,
+    locking_token_account_key: &Pubkey,
+    source_token_account_owner_key: &Pubkey,
+    source_token_account_key: &Pubkey,
+    fee_payer_key: &Pubkey,
+    destination_token_account_key: &Pubkey,
+    company_wallet_key: &Pubkey,
+    mint_address: &Pubkey,
+    seeds: LockSeed,
+    schedules: Vec<MultiSchedule>,
+) -> Result<Instruction, ProgramError> {
+    if schedules.len() > MAX_SCHEDULES as usize {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let data = LockTokenInstruction::CreateMulti {
+        seeds,
+        mint_address: *mint_address,
+        destination_token_address: *destination_token_account_key,
+        schedules,
+    }
+    .pack();
+    let accounts = create_account_metas(
+        locking_program_id,
+        token_program_id,
+        system_program_id,
+        rent_program_id,
+        associated_token_program_id,
+        price_estimator_key,
+        locking_account_key,
+        locking_token_account_key,
+        source_token_account_owner_key,
+        source_token_account_key,
+        fee_payer_key,
+        company_wallet_key,
+        mint_address,
+    )?;
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+fn create_account_metas(
+    locking_program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    system_program_id: &Pubkey,
+    rent_program_id: &Pubkey,
+    associated_token_program_id: &Pubkey,
+    price_estimator_key: &Pubkey,
+    locking_account_key: &Pubkey,
+    locking_token_account_key: &Pubkey,
+    source_token_account_owner_key: &Pubkey,
+    source_token_account_key: &Pubkey,
+    fee_payer_key: &Pubkey,
+    company_wallet_key: &Pubkey,
+    mint_address: &Pubkey,
+) -> Result<Vec<AccountMeta>, ProgramError> {
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let token_state_account_key = token_state_address(locking_program_id, mint_address)?;
+    Ok(vec![
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(program_state_account_key, false),
+        AccountMeta::new(*locking_account_key, false),
+        AccountMeta::new(*locking_token_account_key, false),
+        AccountMeta::new_readonly(*source_token_account_owner_key, true),
+        AccountMeta::new(*source_token_account_key, false),
+        AccountMeta::new(token_state_account_key, false),
+        AccountMeta::new(*company_wallet_key, false),
+        AccountMeta::new_readonly(*system_program_id, false),
+        AccountMeta::new_readonly(*rent_program_id, false),
+        AccountMeta::new_readonly(*associated_token_program_id, false),
+        AccountMeta::new_readonly(*mint_address, false),
+        AccountMeta::new_readonly(*price_estimator_key, false),
+        AccountMeta::new(*fee_payer_key, true),
+    ])
+}
+
+pub fn create_native(
+    locking_program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    locking_account_key: &Pubkey,
+    locking_token_account_key: &Pubkey,
+    payer_key: &Pubkey,
+    company_wallet_key: &Pubkey,
+    system_program_id: &Pubkey,
+    rent_program_id: &Pubkey,
+    price_estimator_key: &Pubkey,
+    destination_address: &Pubkey,
+    schedules: Vec<Schedule>,
+    seeds: LockSeed,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::CreateNative {
+        seeds,
+        destination_address: *destination_address,
+        schedules,
+    }
+    .pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let token_state_account_key = token_state_address(locking_program_id, &spl_token::native_mint::id())?;
+    let accounts = vec![
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(program_state_account_key, false),
+        AccountMeta::new(*locking_account_key, false),
+        AccountMeta::new(*locking_token_account_key, true),
+        AccountMeta::new(*payer_key, true),
+        AccountMeta::new(token_state_account_key, false),
+        AccountMeta::new(*company_wallet_key, false),
+        AccountMeta::new_readonly(*system_program_id, false),
+        AccountMeta::new_readonly(*rent_program_id, false),
+        AccountMeta::new_readonly(spl_token::native_mint::id(), false),
+        AccountMeta::new_readonly(*price_estimator_key, false),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn unlock(
+    locking_program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    locking_account_key: &Pubkey,
+    locking_token_account_key: &Pubkey,
+    destination_token_account_key: &Pubkey,
+    mint_address: &Pubkey,
+    seeds: LockSeed,
+    deadline: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::Unlock { seeds, deadline }.pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let token_state_account_key = token_state_address(locking_program_id, mint_address)?;
+    let accounts = vec![
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(program_state_account_key, false),
+        AccountMeta::new(*locking_account_key, false),
+        AccountMeta::new(*locking_token_account_key, false),
+        AccountMeta::new(*destination_token_account_key, false),
+        AccountMeta::new_readonly(*mint_address, false),
+        AccountMeta::new(token_state_account_key, false),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+// Unlock of a CreateMulti lock, given the destination token accounts of its schedules other than the destination
+// of the lock. Passing the destinations of every schedule still locked works whichever of them have matured.
+// A split lock is unlocked the same way, given its split destinations.
+pub fn unlock_multi(
+    locking_program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    locking_account_key: &Pubkey,
+    locking_token_account_key: &Pubkey,
+    destination_token_account_key: &Pubkey,
+    mint_address: &Pubkey,
+    seeds: LockSeed,
+    deadline: u64,
+    schedule_destination_keys: &[Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = unlock(
+        locking_program_id,
+        token_program_id,
+        locking_account_key,
+        locking_token_account_key,
+        destination_token_account_key,
+        mint_address,
+        seeds,
+        deadline,
+    )?;
+    for key in schedule_destination_keys {
+        if key != destination_token_account_key && instruction.accounts.iter().all(|meta| meta.pubkey != *key) {
+            instruction.accounts.push(AccountMeta::new(*key, false));
+        }
+    }
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+// The schedule destinations are given like with unlock_multi, none for a lock with a single destination
+pub fn claim_and_close(
+    locking_program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    locking_account_key: &Pubkey,
+    locking_token_account_key: &Pubkey,
+    destination_token_account_key: &Pubkey,
+    mint_address: &Pubkey,
+    refund_key: &Pubkey,
+    seeds: LockSeed,
+    schedule_destination_keys: &[Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::ClaimAndClose { seeds }.pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let token_state_account_key = token_state_address(locking_program_id, mint_address)?;
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(program_state_account_key, false),
+        AccountMeta::new(*locking_account_key, false),
+        AccountMeta::new(*locking_token_account_key, false),
+        AccountMeta::new(*destination_token_account_key, false),
+        AccountMeta::new_readonly(*mint_address, false),
+        AccountMeta::new(token_state_account_key, false),
+        AccountMeta::new(*refund_key, false),
+    ];
+    for key in schedule_destination_keys {
+        if key != destination_token_account_key && accounts.iter().all(|meta| meta.pubkey != *key) {
+            accounts.push(AccountMeta::new(*key, false));
+        }
+    }
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+// What the owner of the destination token account signs to permit an UnlockWithPermit of the lock.
+// The nonce is the permit_nonce of the lock header.
+pub fn permit_message(locking_program_id: &Pubkey, locking_account_key: &Pubkey, nonce: u64, expiry: u64) -> Vec<u8> {
+    let mut message = Vec::with_capacity(PERMIT_MESSAGE_PREFIX.len() + 80);
+    message.extend_from_slice(PERMIT_MESSAGE_PREFIX);
+    message.extend_from_slice(locking_program_id.as_ref());
+    message.extend_from_slice(locking_account_key.as_ref());
+    message.extend_from_slice(&nonce.to_le_bytes());
+    message.extend_from_slice(&expiry.to_le_bytes());
+    message
+}
+
+// The ed25519 program instruction preceding an UnlockWithPermit, verifying the signature of the permit message by
+// the owner. Every offset points into this instruction.
+pub fn ed25519_permit_instruction(owner: &Pubkey, signature: &[u8; 64], message: &[u8]) -> Instruction {
+    let mut data = Vec::with_capacity(ED25519_DATA_START + 32 + 64 + message.len());
+    data.push(1);
+    data.push(0);
+    let public_key_offset = ED25519_DATA_START;
+    let signature_offset = public_key_offset + 32;
+    let message_offset = signature_offset + 64;
+    for value in [
+        signature_offset as u16,
+        u16::MAX,
+        public_key_offset as u16,
+        u16::MAX,
+        message_offset as u16,
+        message.len() as u16,
+        u16::MAX,
+    ]
+    .iter()
+    {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.extend_from_slice(owner.as_ref());
+    data.extend_from_slice(signature);
+    data.extend_from_slice(message);
+    Instruction {
+        program_id: ed25519_program::id(),
+        accounts: vec![],
+        data,
+    }
+}
+
+// The schedule destinations are given like with unlock_multi, none for a lock with a single destination
+pub fn unlock_with_permit(
+    locking_program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    locking_account_key: &Pubkey,
+    locking_token_account_key: &Pubkey,
+    destination_token_account_key: &Pubkey,
+    mint_address: &Pubkey,
+    seeds: LockSeed,
+    expiry: u64,
+    schedule_destination_keys: &[Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::UnlockWithPermit { seeds, expiry }.pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let token_state_account_key = token_state_address(locking_program_id, mint_address)?;
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(program_state_account_key, false),
+        AccountMeta::new(*locking_account_key, false),
+        AccountMeta::new(*locking_token_account_key, false),
+        AccountMeta::new(*destination_token_account_key, false),
+        AccountMeta::new_readonly(*mint_address, false),
+        AccountMeta::new(token_state_account_key, false),
+        AccountMeta::new_readonly(sysvar::instructions::id(), false),
+    ];
+    for key in schedule_destination_keys {
+        if key != destination_token_account_key && accounts.iter().all(|meta| meta.pubkey != *key) {
+            accounts.push(AccountMeta::new(*key, false));
+        }
+    }
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+// The schedule destination keys are the destination token accounts of a CreateMulti lock or the split
+// destinations, as with unlock_with_permit
+pub fn unlock_amount(
+    locking_program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    locking_account_key: &Pubkey,
+    locking_token_account_key: &Pubkey,
+    destination_token_account_key: &Pubkey,
+    mint_address: &Pubkey,
+    seeds: LockSeed,
+    amount: u64,
+    schedule_destination_keys: &[Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::UnlockAmount { seeds, amount }.pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let token_state_account_key = token_state_address(locking_program_id, mint_address)?;
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(program_state_account_key, false),
+        AccountMeta::new(*locking_account_key, false),
+        AccountMeta::new(*locking_token_account_key, false),
+        AccountMeta::new(*destination_token_account_key, false),
+        AccountMeta::new_readonly(*mint_address, false),
+        AccountMeta::new(token_state_account_key, false),
+    ];
+    for key in schedule_destination_keys {
+        if key != destination_token_account_key && accounts.iter().all(|meta| meta.pubkey != *key) {
+            accounts.push(AccountMeta::new(*key, false));
+        }
+    }
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+// Every lock is given as its locking account and locking token account
+pub fn snapshot_mint_locked(
+    locking_program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    system_program_id: &Pubkey,
+    payer_key: &Pubkey,
+    mint_address: &Pubkey,
+    locks: &[(Pubkey, Pubkey)],
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::SnapshotMintLocked { mint: *mint_address }.pack();
+    let token_state_account_key = token_state_address(locking_program_id, mint_address)?;
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(*system_program_id, false),
+        AccountMeta::new(*payer_key, true),
+        AccountMeta::new(token_state_account_key, false),
+    ];
+    for (locking_account_key, locking_token_account_key) in locks {
+        accounts.push(AccountMeta::new_readonly(*locking_account_key, false));
+        accounts.push(AccountMeta::new_readonly(*locking_token_account_key, false));
+    }
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+// Every lock is given as its seeds, locking account, locking token account and destination token account
+pub fn batch_unlock(
+    locking_program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    mint_address: &Pubkey,
+    locks: &[(LockSeed, Pubkey, Pubkey, Pubkey)],
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::BatchUnlock {
+        seeds_list: locks.iter().map(|lock| lock.0).collect(),
+    }
+    .pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let token_state_account_key = token_state_address(locking_program_id, mint_address)?;
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(program_state_account_key, false),
+        AccountMeta::new_readonly(*mint_address, false),
+        AccountMeta::new(token_state_account_key, false),
+    ];
+    for (_, locking_account_key, locking_token_account_key, destination_token_account_key) in locks.iter() {
+        accounts.push(AccountMeta::new(*locking_account_key, false));
+        accounts.push(AccountMeta::new(*locking_token_account_key, false));
+        accounts.push(AccountMeta::new(*destination_token_account_key, false));
+    }
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn transfer_locks(
+    locking_program_id: &Pubkey,
+    locking_account_key: &Pubkey,
+    current_destination_token_account_owner: &Pubkey,
+    current_destination_token_account: &Pubkey,
+    target_destination_token_account: &Pubkey,
+    seeds: LockSeed,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::TransferLocks { seeds }.pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let accounts = vec![
+        AccountMeta::new_readonly(program_state_account_key, false),
+        AccountMeta::new(*locking_account_key, false),
+        AccountMeta::new_readonly(*current_destination_token_account, false),
+        AccountMeta::new_readonly(*current_destination_token_account_owner, true),
+        AccountMeta::new_readonly(*target_destination_token_account, false),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn extend_lock_duration(
+    locking_program_id: &Pubkey,
+    locking_account_key: &Pubkey,
+    destination_token_account_owner: &Pubkey,
+    destination_token_account: &Pubkey,
+    seeds: LockSeed,
+    index: u32,
+    release_time: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::ExtendLockDuration { seeds, index, release_time }.pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let accounts = vec![
+        AccountMeta::new_readonly(program_state_account_key, false),
+        AccountMeta::new(*locking_account_key, false),
+        AccountMeta::new_readonly(*destination_token_account, false),
+        AccountMeta::new_readonly(*destination_token_account_owner, true),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn extend_lock_durations(
+    locking_program_id: &Pubkey,
+    locking_account_key: &Pubkey,
+    destination_token_account_owner: &Pubkey,
+    destination_token_account: &Pubkey,
+    seeds: LockSeed,
+    extensions: Vec<(u32, u64)>,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::ExtendLockDurations { seeds, extensions }.pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let accounts = vec![
+        AccountMeta::new_readonly(program_state_account_key, false),
+        AccountMeta::new(*locking_account_key, false),
+        AccountMeta::new_readonly(*destination_token_account, false),
+        AccountMeta::new_readonly(*destination_token_account_owner, true),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn relock(
+    locking_program_id: &Pubkey,
+    locking_account_key: &Pubkey,
+    destination_token_account_owner: &Pubkey,
+    destination_token_account: &Pubkey,
+    seeds: LockSeed,
+    index: u32,
+    new_release_time: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::Relock { seeds, index, new_release_time }.pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let accounts = vec![
+        AccountMeta::new_readonly(program_state_account_key, false),
+        AccountMeta::new(*locking_account_key, false),
+        AccountMeta::new_readonly(*destination_token_account, false),
+        AccountMeta::new_readonly(*destination_token_account_owner, true),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn propose_transfer_locks(
+    locking_program_id: &Pubkey,
+    locking_account_key: &Pubkey,
+    destination_token_account_owner: &Pubkey,
+    destination_token_account: &Pubkey,
+    new_destination: &Pubkey,
+    seeds: LockSeed,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::ProposeTransferLocks {
+        seeds,
+        new_destination: *new_destination,
+    }
+    .pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let accounts = vec![
+        AccountMeta::new_readonly(program_state_account_key, false),
+        AccountMeta::new(*locking_account_key, false),
+        AccountMeta::new_readonly(*destination_token_account, false),
+        AccountMeta::new_readonly(*destination_token_account_owner, true),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn accept_transfer_locks(
+    locking_program_id: &Pubkey,
+    locking_account_key: &Pubkey,
+    new_destination_token_account_owner: &Pubkey,
+    new_destination_token_account: &Pubkey,
+    seeds: LockSeed,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::AcceptTransferLocks { seeds }.pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let accounts = vec![
+        AccountMeta::new_readonly(program_state_account_key, false),
+        AccountMeta::new(*locking_account_key, false),
+        AccountMeta::new_readonly(*new_destination_token_account, false),
+        AccountMeta::new_readonly(*new_destination_token_account_owner, true),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn cancel_transfer_locks(
+    locking_program_id: &Pubkey,
+    locking_account_key: &Pubkey,
+    destination_token_account_owner: &Pubkey,
+    destination_token_account: &Pubkey,
+    seeds: LockSeed,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::CancelTransferLocks { seeds }.pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let accounts = vec![
+        AccountMeta::new_readonly(program_state_account_key, false),
+        AccountMeta::new(*locking_account_key, false),
+        AccountMeta::new_readonly(*destination_token_account, false),
+        AccountMeta::new_readonly(*destination_token_account_owner, true),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn migrate_lock(
+    locking_program_id: &Pubkey,
+    system_program_id: &Pubkey,
+    payer_key: &Pubkey,
+    locking_account_key: &Pubkey,
+    seeds: LockSeed,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::MigrateLock { seeds }.pack();
+    let accounts = vec![
+        AccountMeta::new_readonly(*system_program_id, false),
+        AccountMeta::new(*payer_key, true),
+        AccountMeta::new(*locking_account_key, false),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn schedule_emergency_unlock(
+    locking_program_id: &Pubkey,
+    admin_key: &Pubkey,
+    locking_account_key: &Pubkey,
+    rescue_destination: &Pubkey,
+    seeds: LockSeed,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::ScheduleEmergencyUnlock {
+        seeds,
+        rescue_destination: *rescue_destination,
+    }
+    .pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let accounts = vec![
+        AccountMeta::new_readonly(*admin_key, true),
+        AccountMeta::new_readonly(program_state_account_key, false),
+        AccountMeta::new(*locking_account_key, false),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn execute_emergency_unlock(
+    locking_program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    locking_account_key: &Pubkey,
+    locking_token_account_key: &Pubkey,
+    rescue_destination_token_account_key: &Pubkey,
+    mint_address: &Pubkey,
+    seeds: LockSeed,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::ExecuteEmergencyUnlock { seeds }.pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let token_state_account_key = token_state_address(locking_program_id, mint_address)?;
+    let accounts = vec![
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(program_state_account_key, false),
+        AccountMeta::new(*locking_account_key, false),
+        AccountMeta::new(*locking_token_account_key, false),
+        AccountMeta::new(*rescue_destination_token_account_key, false),
+        AccountMeta::new_readonly(*mint_address, false),
+        AccountMeta::new(token_state_account_key, false),
+    ];
+    let instruction = Instruction {
         program_id: *locking_program_id,
         accounts,
         data,
-    })
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
 }
 
-pub fn unlock(
+pub fn cancel_emergency_unlock(
+    locking_program_id: &Pubkey,
+    locking_account_key: &Pubkey,
+    destination_token_account_owner: &Pubkey,
+    destination_token_account: &Pubkey,
+    seeds: LockSeed,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::CancelEmergencyUnlock { seeds }.pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let accounts = vec![
+        AccountMeta::new_readonly(program_state_account_key, false),
+        AccountMeta::new(*locking_account_key, false),
+        AccountMeta::new_readonly(*destination_token_account, false),
+        AccountMeta::new_readonly(*destination_token_account_owner, true),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn sweep_excess(
     locking_program_id: &Pubkey,
     token_program_id: &Pubkey,
-    clock_sysvar_id: &Pubkey,
     locking_account_key: &Pubkey,
     locking_token_account_key: &Pubkey,
+    creator_token_account_key: &Pubkey,
+    mint_address: &Pubkey,
+    seeds: LockSeed,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::SweepExcess { seeds }.pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let accounts = vec![
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(program_state_account_key, false),
+        AccountMeta::new_readonly(*locking_account_key, false),
+        AccountMeta::new(*locking_token_account_key, false),
+        AccountMeta::new(*creator_token_account_key, false),
+        AccountMeta::new_readonly(*mint_address, false),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+// Meant to be simulated, the claimable amount is read from the return data or the ClaimableAmount event
+pub fn get_claimable_amount(
+    locking_program_id: &Pubkey,
+    locking_account_key: &Pubkey,
+    seeds: LockSeed,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::GetClaimableAmount { seeds }.pack();
+    let accounts = vec![AccountMeta::new_readonly(*locking_account_key, false)];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+// The name and the URI are checked against their maximum lengths by the program, see SetLockMetadata
+pub fn set_lock_metadata(
+    locking_program_id: &Pubkey,
+    locking_account_key: &Pubkey,
+    destination_token_account_key: &Pubkey,
+    authority_key: &Pubkey,
+    payer_key: &Pubkey,
+    system_program_id: &Pubkey,
+    seeds: LockSeed,
+    name: &str,
+    uri: &str,
+) -> Result<Instruction, ProgramError> {
+    // Longer strings do not fit their length byte
+    if name.len() > u8::MAX as usize || uri.len() > u8::MAX as usize {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let data = LockTokenInstruction::SetLockMetadata {
+        seeds,
+        name: name.to_string(),
+        uri: uri.to_string(),
+    }
+    .pack();
+    let (lock_metadata_key, _) = find_lock_metadata_address(locking_program_id, locking_account_key);
+    let accounts = vec![
+        AccountMeta::new_readonly(*locking_account_key, false),
+        AccountMeta::new(lock_metadata_key, false),
+        AccountMeta::new_readonly(*destination_token_account_key, false),
+        AccountMeta::new_readonly(*authority_key, true),
+        AccountMeta::new(*payer_key, true),
+        AccountMeta::new_readonly(*system_program_id, false),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn close_lock_metadata(
+    locking_program_id: &Pubkey,
+    locking_account_key: &Pubkey,
     destination_token_account_key: &Pubkey,
-    seeds: [u8; 32],
+    authority_key: &Pubkey,
+    receiver_key: &Pubkey,
+    seeds: LockSeed,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::CloseLockMetadata { seeds }.pack();
+    let (lock_metadata_key, _) = find_lock_metadata_address(locking_program_id, locking_account_key);
+    let accounts = vec![
+        AccountMeta::new_readonly(*locking_account_key, false),
+        AccountMeta::new(lock_metadata_key, false),
+        AccountMeta::new_readonly(*destination_token_account_key, false),
+        AccountMeta::new_readonly(*authority_key, true),
+        AccountMeta::new(*receiver_key, false),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn reclaim_uninitialized_lock(
+    locking_program_id: &Pubkey,
+    locking_account_key: &Pubkey,
+    payer_key: &Pubkey,
+    seeds: LockSeed,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::ReclaimUninitializedLock { seeds }.pack();
+    let accounts = vec![
+        AccountMeta::new(*locking_account_key, false),
+        AccountMeta::new(*payer_key, true),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn propose_owner(
+    locking_program_id: &Pubkey,
+    admin_key: &Pubkey,
+    program_state_account_key: &Pubkey,
+    new_admin: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::ProposeOwner { new_admin: *new_admin }.pack();
+    let accounts = vec![
+        AccountMeta::new_readonly(*admin_key, true),
+        AccountMeta::new(*program_state_account_key, false),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn accept_ownership(
+    locking_program_id: &Pubkey,
+    pending_admin_key: &Pubkey,
+    program_state_account_key: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::AcceptOwnership {}.pack();
+    let accounts = vec![
+        AccountMeta::new_readonly(*pending_admin_key, true),
+        AccountMeta::new(*program_state_account_key, false),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn cancel_ownership_transfer(
+    locking_program_id: &Pubkey,
+    admin_key: &Pubkey,
+    program_state_account_key: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::CancelOwnershipTransfer {}.pack();
+    let accounts = vec![
+        AccountMeta::new_readonly(*admin_key, true),
+        AccountMeta::new(*program_state_account_key, false),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn initialize_global_state(
+    locking_program_id: &Pubkey,
+    system_program_id: &Pubkey,
+    rent_program_id: &Pubkey,
+    upgrade_authority_key: &Pubkey,
+    program_state_account_key: &Pubkey,
+    program_data_account_key: &Pubkey,
+    admin: &Pubkey,
+    with_legacy_global_state: bool,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::InitializeGlobalState { admin: *admin }.pack();
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*system_program_id, false),
+        AccountMeta::new_readonly(*rent_program_id, false),
+        AccountMeta::new(*upgrade_authority_key, true),
+        AccountMeta::new(*program_state_account_key, false),
+        AccountMeta::new_readonly(*program_data_account_key, false),
+    ];
+    if with_legacy_global_state {
+        accounts.push(AccountMeta::new(legacy_global_state_address(locking_program_id)?, false));
+    }
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn set_fee_bps_in_token(
+    locking_program_id: &Pubkey,
+    system_program_id: &Pubkey,
+    admin_key: &Pubkey,
+    payer_key: &Pubkey,
+    fee_bps_in_token: u16,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::SetFeeBpsInToken { fee_bps_in_token }.pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let accounts = vec![
+        AccountMeta::new_readonly(*admin_key, true),
+        AccountMeta::new(program_state_account_key, false),
+        AccountMeta::new_readonly(*system_program_id, false),
+        AccountMeta::new(*payer_key, true),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn propose_candidate_wallets(
+    locking_program_id: &Pubkey,
+    system_program_id: &Pubkey,
+    admin_key: &Pubkey,
+    payer_key: &Pubkey,
+    candidate_wallets: [Pubkey; MAX_CANDIDATE_WALLETS],
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::ProposeCandidateWallets { candidate_wallets }.pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let accounts = vec![
+        AccountMeta::new_readonly(*admin_key, true),
+        AccountMeta::new(program_state_account_key, false),
+        AccountMeta::new_readonly(*system_program_id, false),
+        AccountMeta::new(*payer_key, true),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn accept_candidate_wallets(locking_program_id: &Pubkey, admin_key: &Pubkey) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::AcceptCandidateWallets {}.pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let accounts = vec![
+        AccountMeta::new_readonly(*admin_key, true),
+        AccountMeta::new(program_state_account_key, false),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn set_fee_discount_tiers(
+    locking_program_id: &Pubkey,
+    system_program_id: &Pubkey,
+    admin_key: &Pubkey,
+    payer_key: &Pubkey,
+    fee_discount_tiers: [FeeDiscountTier; MAX_FEE_DISCOUNT_TIERS],
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::SetFeeDiscountTiers { fee_discount_tiers }.pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let accounts = vec![
+        AccountMeta::new_readonly(*admin_key, true),
+        AccountMeta::new(program_state_account_key, false),
+        AccountMeta::new_readonly(*system_program_id, false),
+        AccountMeta::new(*payer_key, true),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+// The admin proves its balance of the new owner token with admin_token_account, see SetOwnerTokenMint
+pub fn set_owner_token_mint(
+    locking_program_id: &Pubkey,
+    system_program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    admin_key: &Pubkey,
+    payer_key: &Pubkey,
+    new_mint: &Pubkey,
+    admin_token_account: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::SetOwnerTokenMint { new_mint: *new_mint }.pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let accounts = vec![
+        AccountMeta::new_readonly(*admin_key, true),
+        AccountMeta::new(program_state_account_key, false),
+        AccountMeta::new_readonly(*system_program_id, false),
+        AccountMeta::new(*payer_key, true),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(*admin_token_account, false),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn set_fee_exempt_wallet(
+    locking_program_id: &Pubkey,
+    system_program_id: &Pubkey,
+    admin_key: &Pubkey,
+    payer_key: &Pubkey,
+    wallet: &Pubkey,
+    is_exempt: bool,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::SetFeeExemptWallet {
+        wallet: *wallet,
+        is_exempt,
+    }
+    .pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let (wallet_state_key, _) = find_wallet_state_address(locking_program_id, wallet);
+    let accounts = vec![
+        AccountMeta::new_readonly(*admin_key, true),
+        AccountMeta::new_readonly(program_state_account_key, false),
+        AccountMeta::new(wallet_state_key, false),
+        AccountMeta::new_readonly(*system_program_id, false),
+        AccountMeta::new(*payer_key, true),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn set_max_lock_duration(
+    locking_program_id: &Pubkey,
+    system_program_id: &Pubkey,
+    admin_key: &Pubkey,
+    payer_key: &Pubkey,
+    max_lock_duration_secs: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::SetMaxLockDuration { max_lock_duration_secs }.pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
+    let accounts = vec![
+        AccountMeta::new_readonly(*admin_key, true),
+        AccountMeta::new(program_state_account_key, false),
+        AccountMeta::new_readonly(*system_program_id, false),
+        AccountMeta::new(*payer_key, true),
+    ];
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn transfer_schedule_indices(
+    locking_program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    locking_account_key: &Pubkey,
+    locking_token_account_key: &Pubkey,
+    destination_token_account_owner: &Pubkey,
+    destination_token_account: &Pubkey,
+    new_locking_account_key: &Pubkey,
+    new_locking_token_account_key: &Pubkey,
+    new_destination_token_account: &Pubkey,
+    mint_address: &Pubkey,
+    seeds: LockSeed,
+    new_seeds: LockSeed,
+    indices: Vec<u32>,
 ) -> Result<Instruction, ProgramError> {
-    let data = LockTokenInstruction::Unlock { seeds }.pack();
+    let data = LockTokenInstruction::TransferScheduleIndices { seeds, new_seeds, indices }.pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
     let accounts = vec![
         AccountMeta::new_readonly(*token_program_id, false),
-        AccountMeta::new_readonly(*clock_sysvar_id, false),
+        AccountMeta::new_readonly(program_state_account_key, false),
         AccountMeta::new(*locking_account_key, false),
         AccountMeta::new(*locking_token_account_key, false),
-        AccountMeta::new(*destination_token_account_key, false),
+        AccountMeta::new_readonly(*destination_token_account, false),
+        AccountMeta::new_readonly(*destination_token_account_owner, true),
+        AccountMeta::new(*new_locking_account_key, false),
+        AccountMeta::new(*new_locking_token_account_key, false),
+        AccountMeta::new_readonly(*new_destination_token_account, false),
+        AccountMeta::new_readonly(*mint_address, false),
     ];
-    Ok(Instruction {
+    let instruction = Instruction {
         program_id: *locking_program_id,
         accounts,
         data,
-    })
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
 }
 
-pub fn transfer_locks(
+pub fn approve_lock_delegate(
     locking_program_id: &Pubkey,
     locking_account_key: &Pubkey,
-    current_destination_token_account_owner: &Pubkey,
-    current_destination_token_account: &Pubkey,
-    target_destination_token_account: &Pubkey,
-    seeds: [u8; 32],
+    destination_token_account_owner: &Pubkey,
+    destination_token_account: &Pubkey,
+    seeds: LockSeed,
+    delegate: &Pubkey,
 ) -> Result<Instruction, ProgramError> {
-    let data = LockTokenInstruction::TransferLocks { seeds }.pack();
+    let data = LockTokenInstruction::ApproveLockDelegate {
+        seeds,
+        delegate: *delegate,
+    }
+    .pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
     let accounts = vec![
+        AccountMeta::new_readonly(program_state_account_key, false),
         AccountMeta::new(*locking_account_key, false),
-        AccountMeta::new_readonly(*current_destination_token_account, false),
-        AccountMeta::new_readonly(*current_destination_token_account_owner, true),
-        AccountMeta::new_readonly(*target_destination_token_account, false),
+        AccountMeta::new_readonly(*destination_token_account, false),
+        AccountMeta::new_readonly(*destination_token_account_owner, true),
     ];
-    Ok(Instruction {
+    let instruction = Instruction {
         program_id: *locking_program_id,
         accounts,
         data,
-    })
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
 }
 
-pub fn extend_lock_duration(
+pub fn revoke_lock_delegate(
     locking_program_id: &Pubkey,
     locking_account_key: &Pubkey,
     destination_token_account_owner: &Pubkey,
     destination_token_account: &Pubkey,
-    seeds: [u8; 32],
-    index: u32,
-    release_time: u64,
+    seeds: LockSeed,
 ) -> Result<Instruction, ProgramError> {
-    let data = LockTokenInstruction::ExtendLockDuration { seeds, index, release_time }.pack();
+    let data = LockTokenInstruction::RevokeLockDelegate { seeds }.pack();
+    let (program_state_account_key, _) = find_global_state_address(locking_program_id);
     let accounts = vec![
+        AccountMeta::new_readonly(program_state_account_key, false),
         AccountMeta::new(*locking_account_key, false),
         AccountMeta::new_readonly(*destination_token_account, false),
         AccountMeta::new_readonly(*destination_token_account_owner, true),
     ];
-    Ok(Instruction {
+    let instruction = Instruction {
+        program_id: *locking_program_id,
+        accounts,
+        data,
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
+}
+
+pub fn set_fee_params(
+    locking_program_id: &Pubkey,
+    admin_key: &Pubkey,
+    program_state_account_key: &Pubkey,
+    price_estimator: &Pubkey,
+    usd_token_address: &Pubkey,
+    fees_in_usd: u64,
+    company_wallet: &Pubkey,
+    candidate_wallets: [Pubkey; MAX_CANDIDATE_WALLETS],
+    present_fields: u8,
+) -> Result<Instruction, ProgramError> {
+    let data = LockTokenInstruction::SetFeeParams {
+        price_estimator: *price_estimator,
+        usd_token_address: *usd_token_address,
+        fees_in_usd,
+        company_wallet: *company_wallet,
+        candidate_wallets,
+        present_fields,
+    }
+    .pack();
+    let accounts = vec![
+        AccountMeta::new_readonly(*admin_key, true),
+        AccountMeta::new(*program_state_account_key, false),
+    ];
+    let instruction = Instruction {
         program_id: *locking_program_id,
         accounts,
         data,
-    })
+    };
+    debug_assert_account_requirements(&instruction);
+    Ok(instruction)
 }
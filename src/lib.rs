@@ -1,8 +1,19 @@
 #[cfg(not(feature = "no-entrypoint"))]
 pub mod entrypoint;
 
+pub mod client;
+pub mod cpi;
 pub mod error;
+pub mod events;
+pub mod guards;
+#[cfg(feature = "anchor-compat")]
+pub mod idl;
 pub mod instruction;
+pub mod oracle;
+pub mod pda;
+pub mod pod;
 pub mod state;
+pub mod time;
+pub mod utils;
 
 pub mod processor;
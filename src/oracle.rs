@@ -0,0 +1,77 @@
+use solana_program::{
+    account_info::AccountInfo, clock::Clock, msg, program_error::ProgramError,
+};
+
+use std::convert::TryInto;
+
+use crate::error::LockTokenError;
+
+// Layout of a Pyth v2 price account
+const MAGIC: u32 = 0xa1b2c3d4;
+const VERSION: u32 = 2;
+const ACCOUNT_TYPE_PRICE: u32 = 3;
+const STATUS_TRADING: u32 = 1;
+const EXPO_OFFSET: usize = 20;
+const AGGREGATE_PRICE_OFFSET: usize = 208;
+const AGGREGATE_STATUS_OFFSET: usize = 224;
+const AGGREGATE_PUBLISH_SLOT_OFFSET: usize = 232;
+const PRICE_ACCOUNT_MIN_LEN: usize = 240;
+
+// Used when LockGlobalState.max_price_age_slots is not configured
+pub const DEFAULT_MAX_PRICE_AGE_SLOTS: u64 = 25;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OraclePrice {
+    pub price: i64,
+    pub expo: i32,
+    pub publish_slot: u64,
+}
+
+pub fn load_price(
+    price_account: &AccountInfo,
+    clock: &Clock,
+    max_price_age_slots: u64,
+) -> Result<OraclePrice, ProgramError> {
+    let data = price_account.try_borrow_data()?;
+    if data.len() < PRICE_ACCOUNT_MIN_LEN
+        || read_u32(&data, 0) != MAGIC
+        || read_u32(&data, 4) != VERSION
+        || read_u32(&data, 8) != ACCOUNT_TYPE_PRICE
+    {
+        msg!("Provided price estimator account is not a price account");
+        return Err(LockTokenError::InvalidOracleAccount.into());
+    }
+
+    let price = i64::from_le_bytes(data[AGGREGATE_PRICE_OFFSET..AGGREGATE_PRICE_OFFSET + 8].try_into().unwrap());
+    let expo = i32::from_le_bytes(data[EXPO_OFFSET..EXPO_OFFSET + 4].try_into().unwrap());
+    let status = read_u32(&data, AGGREGATE_STATUS_OFFSET);
+    let publish_slot = u64::from_le_bytes(
+        data[AGGREGATE_PUBLISH_SLOT_OFFSET..AGGREGATE_PUBLISH_SLOT_OFFSET + 8].try_into().unwrap(),
+    );
+
+    let max_price_age_slots = if max_price_age_slots == 0 {
+        DEFAULT_MAX_PRICE_AGE_SLOTS
+    } else {
+        max_price_age_slots
+    };
+
+    if status != STATUS_TRADING || clock.slot.saturating_sub(publish_slot) > max_price_age_slots {
+        msg!("The oracle price was published at slot {} and is stale", publish_slot);
+        return Err(LockTokenError::StaleOraclePrice.into());
+    }
+
+    if price <= 0 {
+        msg!("The oracle price {} is not positive", price);
+        return Err(LockTokenError::InvalidOraclePrice.into());
+    }
+
+    Ok(OraclePrice {
+        price,
+        expo,
+        publish_slot,
+    })
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
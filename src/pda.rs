@@ -0,0 +1,104 @@
+// Locking accounts derived from their creator, the mint and a nonce, instead of seeds chosen by the client.
+//
+// The locking account of the nonce-th lock of a creator for a mint is the program address of
+// [LOCK_SEED_PREFIX, creator, mint, nonce as u64 le] with the canonical bump, so the locks of a creator can be
+// found by counting the nonces up from 0 without keeping any seeds. InitDerived records the derivation in the
+// header of the locking account, and every later instruction signs for the lock from it: the seeds of those
+// instructions are ignored and can be LockSeed::default(). Locks of raw seeds keep working as before.
+
+use solana_program::{msg, program_error::ProgramError, pubkey::Pubkey};
+
+use crate::instruction::LockSeed;
+
+pub const LOCK_SEED_PREFIX: &[u8] = b"lock";
+
+// Locking account of the nonce-th lock of the creator for the mint, and its bump seed
+pub fn find_lock_address(program_id: &Pubkey, creator: &Pubkey, mint: &Pubkey, nonce: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[LOCK_SEED_PREFIX, creator.as_ref(), mint.as_ref(), &nonce.to_le_bytes()],
+        program_id,
+    )
+}
+
+// How the program signs for a locking account
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LockSigner {
+    // Locking accounts initialized with Init, the program address of the seeds
+    Seeds(LockSeed),
+    // Locking accounts initialized with InitDerived, see find_lock_address. The nonce is little endian.
+    Derived {
+        creator: Pubkey,
+        mint: Pubkey,
+        nonce: [u8; 8],
+        bump: [u8; 1],
+    },
+}
+
+impl LockSigner {
+    pub fn derived(creator: Pubkey, mint: Pubkey, nonce: u64, bump: u8) -> Self {
+        Self::Derived {
+            creator,
+            mint,
+            nonce: nonce.to_le_bytes(),
+            bump: [bump],
+        }
+    }
+
+    pub fn is_derived(&self) -> bool {
+        matches!(self, Self::Derived { .. })
+    }
+
+    // Nonce and bump seed of the derivation, zero for locking accounts of raw seeds
+    pub fn nonce(&self) -> u64 {
+        match self {
+            Self::Seeds(_) => 0,
+            Self::Derived { nonce, .. } => u64::from_le_bytes(*nonce),
+        }
+    }
+
+    pub fn bump(&self) -> u8 {
+        match self {
+            Self::Seeds(_) => 0,
+            Self::Derived { bump, .. } => bump[0],
+        }
+    }
+
+    // A derived locking account only holds a lock of its creator for its mint
+    pub fn assert_derived_for(&self, creator_address: &Pubkey, mint_address: &Pubkey) -> Result<(), ProgramError> {
+        match self {
+            Self::Derived { creator, mint, .. } if creator != creator_address || mint != mint_address => {
+                msg!("The locking account is derived for the creator {} and the mint {}", creator, mint);
+                Err(ProgramError::InvalidArgument)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn seeds(&self) -> Vec<&[u8]> {
+        match self {
+            Self::Seeds(seeds) => vec![seeds.as_ref()],
+            Self::Derived { creator, mint, nonce, bump } => {
+                vec![LOCK_SEED_PREFIX, creator.as_ref(), mint.as_ref(), &nonce[..], &bump[..]]
+            }
+        }
+    }
+
+    pub fn address(&self, program_id: &Pubkey) -> Result<Pubkey, ProgramError> {
+        match self {
+            Self::Seeds(seeds) => seeds.locking_account_address(program_id),
+            Self::Derived { .. } => Pubkey::create_program_address(&self.seeds(), program_id).map_err(|_| {
+                msg!("The recorded derivation does not derive a locking account");
+                ProgramError::InvalidSeeds
+            }),
+        }
+    }
+
+    // Same as utils::signed_seeds_for
+    pub fn signed_seeds_for(&self, expected_key: &Pubkey, program_id: &Pubkey) -> Result<Vec<&[u8]>, ProgramError> {
+        if self.address(program_id)? != *expected_key {
+            msg!("The signer seeds do not derive the account {}", expected_key);
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(self.seeds())
+    }
+}
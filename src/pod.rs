@@ -0,0 +1,212 @@
+// Zero-copy views of the current account layouts, read and written in place in the account data.
+//
+// Every field has an alignment of 1, so a view can start at any offset of the data, like the schedules
+// following a header. Integers are little endian as in the packed layouts and flags are single bytes.
+// Only the current layouts have views, accounts of the older layouts are read with Pack.
+
+use bytemuck::{Pod, Zeroable};
+use solana_program::{program_error::ProgramError, program_pack::Pack, pubkey::Pubkey};
+use std::mem::size_of;
+
+use crate::{
+    error::LockTokenError,
+    state::{
+        LockGlobalState, LockSchedule, LockScheduleHeader, MAX_CANDIDATE_WALLETS, MAX_FEE_DISCOUNT_TIERS,
+        MAX_SPLIT_DESTINATIONS,
+    },
+    time::is_matured,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+#[repr(transparent)]
+pub struct PodU64([u8; 8]);
+
+impl From<u64> for PodU64 {
+    fn from(value: u64) -> Self {
+        Self(value.to_le_bytes())
+    }
+}
+
+impl From<PodU64> for u64 {
+    fn from(value: PodU64) -> Self {
+        u64::from_le_bytes(value.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+#[repr(transparent)]
+pub struct PodI64([u8; 8]);
+
+impl From<i64> for PodI64 {
+    fn from(value: i64) -> Self {
+        Self(value.to_le_bytes())
+    }
+}
+
+impl From<PodI64> for i64 {
+    fn from(value: PodI64) -> Self {
+        i64::from_le_bytes(value.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+#[repr(transparent)]
+pub struct PodU32([u8; 4]);
+
+impl From<u32> for PodU32 {
+    fn from(value: u32) -> Self {
+        Self(value.to_le_bytes())
+    }
+}
+
+impl From<PodU32> for u32 {
+    fn from(value: PodU32) -> Self {
+        u32::from_le_bytes(value.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+#[repr(transparent)]
+pub struct PodU16([u8; 2]);
+
+impl From<u16> for PodU16 {
+    fn from(value: u16) -> Self {
+        Self(value.to_le_bytes())
+    }
+}
+
+impl From<PodU16> for u16 {
+    fn from(value: PodU16) -> Self {
+        u16::from_le_bytes(value.0)
+    }
+}
+
+// Views borrow the first size_of::<Self>() bytes of the data
+pub trait ZeroCopy: Pod {
+    fn try_from_bytes(data: &[u8]) -> Result<&Self, ProgramError> {
+        data.get(..size_of::<Self>())
+            .and_then(|data| bytemuck::try_from_bytes(data).ok())
+            .ok_or(ProgramError::InvalidAccountData)
+    }
+
+    fn try_from_bytes_mut(data: &mut [u8]) -> Result<&mut Self, ProgramError> {
+        data.get_mut(..size_of::<Self>())
+            .and_then(|data| bytemuck::try_from_bytes_mut(data).ok())
+            .ok_or(ProgramError::InvalidAccountData)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Pod, Zeroable)]
+#[repr(C)]
+pub struct PodLockSchedule {
+    pub release_time: PodU64,
+    pub amount: PodU64,
+}
+
+impl ZeroCopy for PodLockSchedule {}
+
+impl PodLockSchedule {
+    // The schedules following the header of a locking account
+    pub fn try_cast_slice_mut(data: &mut [u8]) -> Result<&mut [Self], ProgramError> {
+        bytemuck::try_cast_slice_mut(data).map_err(|_| ProgramError::InvalidAccountData)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Pod, Zeroable)]
+#[repr(C)]
+pub struct PodSplitDestination {
+    pub destination_address: Pubkey,
+    pub share_bps: PodU16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Pod, Zeroable)]
+#[repr(C)]
+pub struct PodLockScheduleHeader {
+    pub destination_address: Pubkey,
+    pub mint_address: Pubkey,
+    pub is_initialized: u8,
+    pub pending_destination_address: Pubkey,
+    // HEADER_FLAG_* bits
+    pub flags: u8,
+    pub total_amount: PodU64,
+    pub claimed_amount: PodU64,
+    pub schedule_count: PodU32,
+    pub unlocked_count: PodU32,
+    pub rescue_destination_address: Pubkey,
+    pub rescue_execute_after: PodU64,
+    pub creator_address: Pubkey,
+    // TimeBasis as u8
+    pub time_basis: u8,
+    pub split_destinations: [PodSplitDestination; MAX_SPLIT_DESTINATIONS],
+    pub version: u8,
+    pub delegate_address: Pubkey,
+    pub padding: u8,
+    pub permit_nonce: PodU64,
+    pub min_claim_interval_secs: PodU32,
+    pub last_claim_ts: PodU64,
+    pub padding2: [u8; 5],
+    pub lock_nonce: PodU64,
+    pub lock_bump: u8,
+    pub created_at: PodI64,
+}
+
+impl ZeroCopy for PodLockScheduleHeader {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Pod, Zeroable)]
+#[repr(C)]
+pub struct PodFeeDiscountTier {
+    pub min_balance: PodU64,
+    pub discount_bps: PodU16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Pod, Zeroable)]
+#[repr(C)]
+pub struct PodLockGlobalState {
+    pub price_estimator: Pubkey,
+    pub usd_token_address: Pubkey,
+    pub fees_in_usd: PodU64,
+    pub company_wallet: Pubkey,
+    pub max_price_age_slots: PodU64,
+    pub candidate_wallets: [Pubkey; MAX_CANDIDATE_WALLETS],
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+    pub min_lock_duration_secs: PodU64,
+    pub min_lock_amount: PodU64,
+    pub fee_bps_in_token: PodU16,
+    pub owner_token_mint: Pubkey,
+    pub fee_discount_tiers: [PodFeeDiscountTier; MAX_FEE_DISCOUNT_TIERS],
+    pub max_lock_duration_secs: PodU64,
+    pub paused_at: PodU64,
+    pub paused_by: Pubkey,
+    pub pending_candidate_wallets: [Pubkey; MAX_CANDIDATE_WALLETS],
+    pub candidate_wallets_execute_after: PodU64,
+    pub version: u8,
+    pub pause_create: u8,
+    pub pause_unlock: u8,
+    pub pause_transfer: u8,
+    pub is_initialized: u8,
+}
+
+impl ZeroCopy for PodLockGlobalState {}
+
+// The views have to match the packed layouts byte for byte
+const _: () = assert!(size_of::<PodLockSchedule>() == LockSchedule::LEN);
+const _: () = assert!(size_of::<PodLockScheduleHeader>() == LockScheduleHeader::LEN);
+const _: () = assert!(size_of::<PodLockGlobalState>() == LockGlobalState::LEN);
+
+// Number of schedules at the start of schedules sorted by release time which have matured
+pub fn matured_schedule_count(schedules: &[PodLockSchedule], now: u64) -> usize {
+    schedules.partition_point(|s| is_matured(s.release_time.into(), now))
+}
+
+// Checks the unlocked_count of the header of a lock with schedule counters against its schedules: every
+// schedule it skips has been emptied, and it does not point past the last one
+pub fn validate_cursor(header: &LockScheduleHeader, schedules: &[PodLockSchedule]) -> Result<(), LockTokenError> {
+    let skipped = schedules
+        .get(..header.unlocked_count as usize)
+        .ok_or(LockTokenError::InvalidClaimCursor)?;
+    if skipped.iter().any(|s| u64::from(s.amount) != 0) {
+        return Err(LockTokenError::InvalidClaimCursor);
+    }
+    Ok(())
+}
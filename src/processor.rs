@@ -2,36 +2,107 @@ use solana_program::{
     account_info::{next_account_info, AccountInfo},
     decode_error::DecodeError,
     entrypoint::ProgramResult,
+    hash::hashv,
     msg,
-    program::{invoke, invoke_signed},
+    program::{invoke, invoke_signed, set_return_data},
     program_error::PrintProgramError,
     program_error::ProgramError,
     program_pack::Pack,
     pubkey::Pubkey,
     rent::Rent,
-    system_instruction::{create_account, transfer as transfer_sol},
+    system_instruction::{allocate, assign, create_account, transfer as transfer_sol},
+    system_program,
     sysvar::{clock::Clock, Sysvar},
 };
 
-use std::str::FromStr;
-
 use num_traits::FromPrimitive;
-use spl_token::{instruction::transfer, state::Account};
+use spl_token::{
+    instruction::{close_account, initialize_account, sync_native},
+    native_mint,
+    state::Account,
+};
 
 use crate::{
     error::LockTokenError,
-    instruction::{Schedule, LockTokenInstruction, SCHEDULE_SIZE},
-    state::{OWNER_TOKEN_MINT_ADDRESS, pack_schedules_into_slice, unpack_schedules, LockGlobalState, LockSchedule, LockScheduleHeader, TokenState},
+    events::{
+        emit, ClaimableAmount, EmergencyUnlockCancelled, EmergencyUnlockExecuted, EmergencyUnlockScheduled, FeeCollected, LockCreated,
+        LockClosed, LockExtended, LockRemaining, LockTransferred, LockUnlocked, OwnershipTransferred, PauseChanged, SchedulesTransferred,
+        TokenFeeCollected, UsdFeeCollected,
+    },
+    guards::{
+        assert_admin, assert_global_state, assert_lock, assert_not_paused, assert_unlock_not_paused, load_global_state,
+        load_lock, lock_signer,
+    },
+    oracle::load_price,
+    pda::{find_lock_address, LockSigner},
+    pod::{matured_schedule_count, validate_cursor, PodLockSchedule, PodU64},
+    instruction::{permit_message, recurring_schedules, LockSeed, MultiSchedule, Schedule, LockTokenInstruction, SCHEDULE_SIZE, BATCH_UNLOCK_GROUP_LEN},
+    state::{find_global_state_address, legacy_global_state_address, find_lock_metadata_address, find_wallet_state_address, get_locking_account_size, GLOBAL_STATE_SEED, LOCK_METADATA_SEED, LockMetadata, MAX_LOCKING_ACCOUNT_SCHEDULES, MAX_CANDIDATE_WALLETS, unpack_schedules, unpack_lock_schedules, LockGlobalState, LockSchedule, LockScheduleHeader, MultiLockSchedule, RemainingSchedules, TokenState, VestingSchedule, MAX_BPS, MAX_FEE_BPS_IN_TOKEN, EMERGENCY_UNLOCK_DELAY_SECS, CANDIDATE_WALLETS_DELAY_SECS, token_state_address, usd_token_amount, claimable_amount, usd_to_lamports, apply_fee_discount, split_amounts, locked_amount, FeeDiscountTier, SplitDestination, MAX_FEE_DISCOUNT_TIERS, MAX_SPLIT_DESTINATIONS, WalletState, WALLET_STATE_SEED, MAX_PAUSE_DURATION_SECS, PAUSE_CREATE, PAUSE_TRANSFER, PAUSE_UNLOCK, FEE_PARAM_COMPANY_WALLET, FEE_PARAM_FEES_IN_USD, FEE_PARAM_PRICE_ESTIMATOR, FEE_PARAM_USD_TOKEN_ADDRESS, FEE_PARAMS_ALL},
+    time::{assert_future, assert_seconds, is_matured, now_u64, TimeBasis},
+    utils::{
+        assert_authority_signed, assert_distinct_accounts, assert_ed25519_permit, assert_not_expired, assert_rent_sysvar, assert_system_program,
+        assert_source_authority, assert_token_program, assert_upgrade_authority,
+        assert_valid_destination, assert_valid_new_destination, assert_valid_source, assert_valid_vault, assert_writable, create_associated_token_account_with_program_id,
+        get_associated_token_address_with_program_id, is_token_multisig, signed_seeds_for, signed_seeds_with_bump_for,
+        skip_clock_sysvar, transfer_checked_instruction, unpack_mint, unpack_token_account,
+    },
 };
 
+#[cfg(feature = "verbose")]
+use crate::instruction::CreateSummary;
+
+// What a new lock releases: either a list of schedules, a list of schedules with a destination each
+// or a cliff plus linear vesting
+enum LockBody {
+    Schedules(Vec<Schedule>),
+    Multi(Vec<MultiSchedule>),
+    Vesting(VestingSchedule),
+}
+
+// Accounts paying the Create fee in the USD token instead of SOL
+struct UsdFeeAccounts<'b, 'a> {
+    payer_token_account: &'b AccountInfo<'a>,
+    mint_account: &'b AccountInfo<'a>,
+    company_token_account: &'b AccountInfo<'a>,
+}
+
+// How a Create pays the fee of the company
+enum FeePayment<'b, 'a> {
+    Sol,
+    Usd(UsdFeeAccounts<'b, 'a>),
+    // Token account of the locked mint owned by the company wallet
+    Token(&'b AccountInfo<'a>),
+}
+
 pub struct Processor {}
 
 impl Processor {
     pub fn process_init(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        seeds: [u8; 32],
+        seeds: LockSeed,
         schedules: u32
+    ) -> ProgramResult {
+        Self::init_locking_account(program_id, accounts, LockSigner::Seeds(seeds), schedules)
+    }
+
+    pub fn process_init_derived(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        creator: &Pubkey,
+        mint_address: &Pubkey,
+        nonce: u64,
+        schedules: u32,
+    ) -> ProgramResult {
+        let (_, bump) = find_lock_address(program_id, creator, mint_address, nonce);
+        Self::init_locking_account(program_id, accounts, LockSigner::derived(*creator, *mint_address, nonce, bump), schedules)
+    }
+
+    fn init_locking_account(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        lock_signer: LockSigner,
+        schedules: u32,
     ) -> ProgramResult {
         let accounts_iter = &mut accounts.iter();
 
@@ -41,53 +112,178 @@ impl Processor {
         let payer = next_account_info(accounts_iter)?;
         let locking_account = next_account_info(accounts_iter)?;
 
+        assert_distinct_accounts(&[
+            ("program state account", program_state_account.key),
+            ("payer", payer.key),
+            ("locking account", locking_account.key),
+        ])?;
+
+        assert_writable(payer, "payer")?;
+        assert_writable(locking_account, "locking account")?;
+
+        assert_system_program(system_program_account)?;
+        assert_rent_sysvar(rent_sysvar_account)?;
         let rent = Rent::from_account_info(rent_sysvar_account)?;
 
-        let program_state_account_key = Pubkey::create_program_address(&[String::from(OWNER_TOKEN_MINT_ADDRESS).as_bytes()], program_id)?;
+        let program_global_state = load_global_state(program_id, program_state_account)?;
 
-        if program_state_account_key != *program_state_account.key {
-            msg!("Provided program state account is invalid");
+        assert_not_paused(&program_global_state, PAUSE_CREATE)?;
+
+        let locking_account_key = lock_signer.address(program_id)?;
+        if locking_account_key != *locking_account.key {
+            msg!("Provided locking account is invalid");
             return Err(ProgramError::InvalidArgument);
         }
 
-        let is_state_initialized = program_state_account.try_borrow_data()?[LockGlobalState::LEN - 1] == 1;
+        // The payer can be a keypair or a PDA of a calling program that signed it through invoke_signed.
+        // Either way create_account needs it to be a signer owned by the system program and holding no data.
+        if !payer.is_signer {
+            msg!("The payer should sign, either directly or through invoke_signed of the calling program");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
 
-        if !is_state_initialized {
-            msg!("The state of program is uninitialized");
+        if *payer.owner != system_program::id() || !payer.data_is_empty() {
+            msg!("The payer should be a system account without data");
             return Err(ProgramError::InvalidArgument);
         }
 
-        let program_global_state = LockGlobalState::unpack(&program_state_account.data.borrow())?;
+        if schedules == 0 {
+            msg!("A lock needs at least one schedule");
+            return Err(LockTokenError::EmptySchedules.into());
+        }
 
-        if program_global_state.is_paused {
-            msg!("The program is paused");
+        if schedules as usize > MAX_LOCKING_ACCOUNT_SCHEDULES {
+            msg!("A locking account holds at most {} schedules", MAX_LOCKING_ACCOUNT_SCHEDULES);
+            return Err(LockTokenError::TooManySchedules.into());
+        }
+
+        // Only Init assigns the locking account to the program, whatever its number of schedules
+        if *locking_account.owner == *program_id {
+            msg!("The locking account is already initialized");
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        if *locking_account.owner != system_program::id() || !locking_account.data_is_empty() {
+            msg!("The locking account should not hold data yet");
             return Err(ProgramError::InvalidArgument);
         }
 
-        let locking_account_key = Pubkey::create_program_address(&[&seeds], &program_id).unwrap();
-        if locking_account_key != *locking_account.key {
-            msg!("Provided locking account is invalid");
+        let state_size = get_locking_account_size(schedules as usize);
+        let signer_seeds = lock_signer.signed_seeds_for(locking_account.key, program_id)?;
+
+        Self::create_program_account(
+            program_id,
+            system_program_account,
+            payer,
+            locking_account,
+            &rent,
+            state_size,
+            &signer_seeds,
+        )?;
+
+        let mut data = locking_account.data.borrow_mut();
+        LockScheduleHeader::pack_init_payer(payer.key, &mut data);
+        LockScheduleHeader::pack_init_derivation(&lock_signer, &mut data);
+        Ok(())
+    }
+
+    // Gives the rent of a locking account back to the payer of Init when no lock was created in it
+    pub fn process_reclaim_uninitialized_lock(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let locking_account = next_account_info(accounts_iter)?;
+        let payer = next_account_info(accounts_iter)?;
+
+        assert_distinct_accounts(&[
+            ("locking account", locking_account.key),
+            ("payer", payer.key),
+        ])?;
+
+        assert_writable(locking_account, "locking account")?;
+        assert_writable(payer, "payer")?;
+
+        assert_lock(program_id, locking_account, seeds)?;
+
+        let init_payer = match LockScheduleHeader::unpack_init_payer(&locking_account.try_borrow_data()?) {
+            Some(init_payer) => init_payer,
+            None => {
+                msg!("Locking accounts holding a lock can not be reclaimed");
+                return Err(ProgramError::AccountAlreadyInitialized);
+            }
+        };
+
+        if init_payer == Pubkey::default() {
+            msg!("The locking account was initialized before the payer of Init was recorded");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if init_payer != *payer.key {
+            msg!("Only the payer of Init can reclaim the locking account");
             return Err(ProgramError::InvalidArgument);
         }
 
-        let state_size = (schedules as usize) * LockSchedule::LEN + LockScheduleHeader::LEN;
+        if !payer.is_signer {
+            msg!("The payer of Init should sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
 
-        let init_locking_account = create_account(
-            &payer.key,
-            &locking_account_key,
-            rent.minimum_balance(state_size),
-            state_size as u64,
-            &program_id,
-        );
+        let lamports = locking_account.lamports();
+        **payer.try_borrow_mut_lamports()? = payer
+            .lamports()
+            .checked_add(lamports)
+            .ok_or(LockTokenError::MathOverflow)?;
+        **locking_account.try_borrow_mut_lamports()? = 0;
+        locking_account.data.borrow_mut().fill(0);
+
+        Ok(())
+    }
+
+    // Creates the program derived account with size bytes owned by the program, paid by the payer
+    fn create_program_account<'a>(
+        program_id: &Pubkey,
+        system_program_account: &AccountInfo<'a>,
+        payer: &AccountInfo<'a>,
+        account: &AccountInfo<'a>,
+        rent: &Rent,
+        size: usize,
+        signer_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        assert_writable(payer, "payer")?;
+        assert_writable(account, "new account")?;
+
+        if account.lamports() == 0 {
+            invoke_signed(
+                &create_account(payer.key, account.key, rent.minimum_balance(size), size as u64, program_id),
+                &[system_program_account.clone(), payer.clone(), account.clone()],
+                &[signer_seeds],
+            )?;
+            return Ok(());
+        }
+
+        // create_account fails on an address which already holds lamports, which anyone can send to
+        // the account before it is created. The account is topped up to rent exemption and set up instead.
+        let extra_rent = rent.minimum_balance(size).saturating_sub(account.lamports());
+        if extra_rent > 0 {
+            invoke(
+                &transfer_sol(payer.key, account.key, extra_rent),
+                &[payer.clone(), account.clone(), system_program_account.clone()],
+            )?;
+        }
 
         invoke_signed(
-            &init_locking_account,
-            &[
-                system_program_account.clone(),
-                payer.clone(),
-                locking_account.clone(),
-            ],
-            &[&[&seeds]],
+            &allocate(account.key, size as u64),
+            &[account.clone(), system_program_account.clone()],
+            &[signer_seeds],
+        )?;
+
+        invoke_signed(
+            &assign(account.key, program_id),
+            &[account.clone(), system_program_account.clone()],
+            &[signer_seeds],
         )?;
         Ok(())
     }
@@ -95,10 +291,128 @@ impl Processor {
     pub fn process_create(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        seeds: [u8; 32],
+        seeds: LockSeed,
         mint_address: &Pubkey,
         destination_token_address: &Pubkey,
         schedules: Vec<Schedule>,
+        deadline: u64,
+        max_total: Option<u64>,
+        is_nft: bool,
+        is_immutable: bool,
+        pay_fee_in_usd: bool,
+        pay_fee_in_token: bool,
+        with_fee_discount: bool,
+        time_basis: TimeBasis,
+        split_destinations: Vec<SplitDestination>,
+        with_wallet_state: bool,
+        min_claim_interval_secs: u32,
+    ) -> ProgramResult {
+        Self::create_lock(
+            program_id,
+            accounts,
+            seeds,
+            mint_address,
+            destination_token_address,
+            LockBody::Schedules(schedules),
+            deadline,
+            max_total,
+            is_nft,
+            is_immutable,
+            pay_fee_in_usd,
+            pay_fee_in_token,
+            with_fee_discount,
+            time_basis,
+            split_destinations,
+            with_wallet_state,
+            min_claim_interval_secs,
+        )
+    }
+
+    pub fn process_create_vesting(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+        mint_address: &Pubkey,
+        destination_token_address: &Pubkey,
+        cliff_time: u64,
+        cliff_bps: u16,
+        vesting_end: u64,
+        total_amount: u64,
+    ) -> ProgramResult {
+        Self::create_lock(
+            program_id,
+            accounts,
+            seeds,
+            mint_address,
+            destination_token_address,
+            LockBody::Vesting(VestingSchedule {
+                cliff_time,
+                vesting_end,
+                total_amount,
+                claimed_amount: 0,
+                cliff_bps,
+            }),
+            0,
+            None,
+            false,
+            false,
+            false,
+            false,
+            false,
+            TimeBasis::UnixTimestamp,
+            Vec::new(),
+            false,
+            0,
+        )
+    }
+
+    pub fn process_create_multi(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+        mint_address: &Pubkey,
+        destination_token_address: &Pubkey,
+        schedules: Vec<MultiSchedule>,
+    ) -> ProgramResult {
+        Self::create_lock(
+            program_id,
+            accounts,
+            seeds,
+            mint_address,
+            destination_token_address,
+            LockBody::Multi(schedules),
+            0,
+            None,
+            false,
+            false,
+            false,
+            false,
+            false,
+            TimeBasis::UnixTimestamp,
+            Vec::new(),
+            false,
+            0,
+        )
+    }
+
+    fn create_lock(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+        mint_address: &Pubkey,
+        destination_token_address: &Pubkey,
+        body: LockBody,
+        deadline: u64,
+        max_total: Option<u64>,
+        is_nft: bool,
+        is_immutable: bool,
+        pay_fee_in_usd: bool,
+        pay_fee_in_token: bool,
+        with_fee_discount: bool,
+        time_basis: TimeBasis,
+        split_destinations: Vec<SplitDestination>,
+        with_wallet_state: bool,
+        min_claim_interval_secs: u32,
     ) -> ProgramResult {
         let accounts_iter = &mut accounts.iter();
 
@@ -110,688 +424,4474 @@ impl Processor {
         let source_token_account = next_account_info(accounts_iter)?;
         let token_state_account = next_account_info(accounts_iter)?;
         let company_wallet = next_account_info(accounts_iter)?;
+        let system_program_account = next_account_info(accounts_iter)?;
+        let rent_sysvar_account = next_account_info(accounts_iter)?;
+        let associated_token_program_account = next_account_info(accounts_iter)?;
+        let mint_account = next_account_info(accounts_iter)?;
+        let price_estimator_account = next_account_info(accounts_iter)?;
+        let fee_payer = next_account_info(accounts_iter)?;
+        let fee_payment = match (pay_fee_in_usd, pay_fee_in_token) {
+            (false, false) => FeePayment::Sol,
+            (true, false) => FeePayment::Usd(UsdFeeAccounts {
+                payer_token_account: next_account_info(accounts_iter)?,
+                mint_account: next_account_info(accounts_iter)?,
+                company_token_account: next_account_info(accounts_iter)?,
+            }),
+            (false, true) => FeePayment::Token(next_account_info(accounts_iter)?),
+            (true, true) => {
+                msg!("The fee can only be paid in one currency");
+                return Err(ProgramError::InvalidInstructionData);
+            }
+        };
+        let fee_discount_token_account = if with_fee_discount {
+            Some(next_account_info(accounts_iter)?)
+        } else {
+            None
+        };
+        let wallet_state_account = if with_wallet_state {
+            Some(next_account_info(accounts_iter)?)
+        } else {
+            None
+        };
 
-        let program_state_account_key = Pubkey::create_program_address(&[String::from(OWNER_TOKEN_MINT_ADDRESS).as_bytes()], program_id)?;
-
-        if program_state_account_key != *program_state_account.key {
-            msg!("Provided program state account is invalid");
-            return Err(ProgramError::InvalidArgument);
+        // The company wallet may lock its own tokens, so it can also be the source token account owner
+        assert_distinct_accounts(&[
+            ("program state account", program_state_account.key),
+            ("locking account", locking_account.key),
+            ("locking token account", locking_token_account.key),
+            ("source token account", source_token_account.key),
+            ("source token account owner", source_token_account_owner.key),
+            ("token state account", token_state_account.key),
+        ])?;
+        assert_distinct_accounts(&[
+            ("program state account", program_state_account.key),
+            ("locking account", locking_account.key),
+            ("locking token account", locking_token_account.key),
+            ("source token account", source_token_account.key),
+            ("token state account", token_state_account.key),
+            ("company wallet", company_wallet.key),
+        ])?;
+
+        assert_writable(locking_account, "locking account")?;
+        assert_writable(locking_token_account, "locking token account")?;
+        assert_writable(source_token_account, "source token account")?;
+        assert_writable(token_state_account, "token state account")?;
+        assert_writable(fee_payer, "fee payer")?;
+        match &fee_payment {
+            FeePayment::Sol => assert_writable(company_wallet, "company wallet")?,
+            FeePayment::Usd(usd_fee_accounts) => {
+                assert_writable(usd_fee_accounts.payer_token_account, "fee payer token account")?;
+                assert_writable(usd_fee_accounts.company_token_account, "company token account")?;
+            }
+            FeePayment::Token(company_token_account) => assert_writable(company_token_account, "company token account")?,
         }
 
-        let is_state_initialized = program_state_account.try_borrow_data()?[LockGlobalState::LEN - 1] == 1;
-
-        if !is_state_initialized {
-            msg!("The state of program is uninitialized");
-            return Err(ProgramError::InvalidArgument);
-        }
+        let program_global_state = load_global_state(program_id, program_state_account)?;
 
-        let program_global_state = LockGlobalState::unpack(&program_state_account.data.borrow())?;
+        assert_not_paused(&program_global_state, PAUSE_CREATE)?;
 
-        if program_global_state.is_paused {
-            msg!("The program is paused");
-            return Err(ProgramError::InvalidArgument);
-        }
+        let clock = Clock::get()?;
+        assert_not_expired(deadline, &clock)?;
 
-        let locking_account_key = Pubkey::create_program_address(&[&seeds], program_id)?;
-        if locking_account_key != *locking_account.key {
-            msg!("Provided locking account is invalid");
-            return Err(ProgramError::InvalidArgument);
-        }
+        let lock_signer = assert_lock(program_id, locking_account, seeds)?;
+        let locking_account_key = *locking_account.key;
 
-        if !source_token_account_owner.is_signer {
-            msg!("Source token account owner should be a signer.");
-            return Err(ProgramError::InvalidArgument);
-        }
+        // A source token account owner which is a spl token multisig account is followed by its signers. Only
+        // those which signed are passed on to the token program, which rejects the others.
+        let multisig_signer_accounts: Vec<AccountInfo> = if is_token_multisig(source_token_account_owner) {
+            accounts_iter.as_slice().iter().filter(|account| account.is_signer).cloned().collect()
+        } else {
+            Vec::new()
+        };
+        assert_authority_signed(source_token_account_owner, &multisig_signer_accounts)?;
 
-        if *locking_account.owner != *program_id {
-            msg!("Program should own locking account");
-            return Err(ProgramError::InvalidArgument);
+        // The source token account owner only authorizes the token transfer, the rent and the fees are paid
+        // by the fee payer, which may be the same account
+        if !fee_payer.is_signer {
+            msg!("The fee payer should be a signer");
+            return Err(ProgramError::MissingRequiredSignature);
         }
 
         // Verifying that no SVC was already created with this seed
         let is_initialized =
-            locking_account.try_borrow_data()?[LockScheduleHeader::LEN - 1] == 1;
+            LockScheduleHeader::is_packed_initialized(&locking_account.try_borrow_data()?);
 
         if is_initialized {
             msg!("Cannot overwrite an existing locking contract.");
             return Err(ProgramError::InvalidArgument);
         }
 
-        let locking_token_account_data = Account::unpack(&locking_token_account.data.borrow())?;
+        assert_token_program(spl_token_account)?;
 
-        if locking_token_account_data.owner != locking_account_key {
-            msg!("The locking token account should be owned by the locking account.");
+        if *mint_account.key != *mint_address {
+            msg!("Provided mint account does not match the mint address");
             return Err(ProgramError::InvalidArgument);
         }
 
-        if locking_token_account_data.delegate.is_some() {
-            msg!("The locking token account should not have a delegate authority");
-            return Err(ProgramError::InvalidAccountData);
+        let mint = unpack_mint(mint_account, spl_token_account.key)?;
+
+        // A vesting schedule is checked against the minimums as if everything was released at the cliff
+        let mut schedules = match &body {
+            LockBody::Schedules(schedules) => {
+                Self::assert_valid_schedules(schedules, time_basis, &clock, false)?;
+                schedules.clone()
+            }
+            LockBody::Multi(multi_schedules) => {
+                let schedules: Vec<Schedule> = multi_schedules
+                    .iter()
+                    .map(|s| Schedule {
+                        release_time: s.release_time,
+                        amount: s.amount,
+                    })
+                    .collect();
+                Self::assert_valid_schedules(&schedules, time_basis, &clock, true)?;
+                schedules
+            }
+            LockBody::Vesting(vesting) => {
+                Self::assert_valid_vesting(vesting, &clock)?;
+                vec![Schedule {
+                    release_time: vesting.cliff_time,
+                    amount: vesting.total_amount,
+                }]
+            }
+        };
+
+        let total_amount = schedules
+            .iter()
+            .try_fold(0u64, |total, s| total.checked_add(s.amount))
+            .ok_or(ProgramError::InvalidInstructionData)?; // Total amount overflows u64
+
+        if let Some(max_total) = max_total {
+            if total_amount > max_total {
+                msg!("The total amount {} exceeds the authorized maximum {}", total_amount, max_total);
+                return Err(LockTokenError::TotalExceedsAuthorized.into());
+            }
         }
 
-        if locking_token_account_data.close_authority.is_some() {
-            msg!("The locking token account should not have a close authority");
-            return Err(ProgramError::InvalidAccountData);
+        if is_nft {
+            if mint.decimals != 0 || mint.supply != 1 {
+                msg!("The mint has {} decimals and a supply of {}", mint.decimals, mint.supply);
+                return Err(LockTokenError::NotAnNft.into());
+            }
+            if !matches!(body, LockBody::Schedules(_)) || schedules.len() != 1 || schedules[0].amount != 1 {
+                msg!("An NFT lock has a single schedule of amount 1");
+                return Err(LockTokenError::NotAnNft.into());
+            }
         }
 
-        let token_state_account_key = Pubkey::create_program_address(&[&mint_address.to_bytes()], program_id)?;
-        if token_state_account_key != *token_state_account.key {
-            msg!("Provided token state account is invalid");
-            return Err(ProgramError::InvalidArgument);
+        if let LockBody::Multi(multi_schedules) = &body {
+            // Unlocking into a system account closes the vault, which the other destinations still need
+            if *mint_address == native_mint::id() {
+                msg!("Locks with per-schedule destinations can not hold native SOL");
+                return Err(ProgramError::InvalidArgument);
+            }
+            if multi_schedules.iter().any(|s| s.destination_token_address == *locking_token_account.key) {
+                msg!("A schedule can not be released to the locking token account");
+                return Err(ProgramError::InvalidArgument);
+            }
         }
 
-        let mut token_state_data = TokenState {
-            mint_address: *mint_address,
-            is_free: false,
-            is_initialized: false,
+        if !split_destinations.is_empty() {
+            Self::assert_valid_split(&split_destinations, destination_token_address, locking_token_account.key)?;
+            // Unlocking into a system account closes the vault, which the other destinations still need
+            if *mint_address == native_mint::id() {
+                msg!("Split locks can not hold native SOL");
+                return Err(ProgramError::InvalidArgument);
+            }
+        }
+
+        let fee_discount_bps = match fee_discount_token_account {
+            Some(fee_discount_token_account) => Self::load_fee_discount_bps(
+                &program_global_state,
+                spl_token_account,
+                fee_payer,
+                fee_discount_token_account,
+            )?,
+            None => 0,
         };
-        let is_free_token_initialized = token_state_account.try_borrow_data()?[TokenState::LEN - 1] == 1;
-        if is_free_token_initialized == true {
-            let packed_state = &token_state_account.data;
-            token_state_data = TokenState::unpack(&packed_state.borrow()[..TokenState::LEN])?;
-            if token_state_data.mint_address != *mint_address {
-                msg!("Provided token state account is invalid");
+        let is_fee_exempt = match wallet_state_account {
+            Some(wallet_state_account) => Self::load_fee_exemption(program_id, fee_payer, wallet_state_account)?,
+            None => false,
+        };
+
+        let token_state_data = Self::load_fee_token_state(program_id, mint_address, token_state_account)?;
+        let fee = Self::quote_fees(
+            &program_global_state,
+            &token_state_data,
+            price_estimator_account,
+            fee_payer.key,
+            company_wallet.key,
+            matches!(fee_payment, FeePayment::Sol),
+            fee_discount_bps,
+            is_fee_exempt,
+        )?;
+
+        #[cfg(feature = "verbose")]
+        msg!("{}", CreateSummary {
+            mint_address,
+            destination_token_address,
+            schedules: &schedules,
+            total_amount,
+            fee_lamports: fee.lamports,
+        });
+
+        // A fee paid in the locked token leaves the source along with the schedules, which only lock the rest
+        let token_fee = match fee_payment {
+            FeePayment::Token(_) if !token_state_data.is_free && !is_fee_exempt => {
+                if program_global_state.fee_bps_in_token == 0 {
+                    msg!("Paying the fee in the locked token is disabled");
+                    return Err(ProgramError::InvalidArgument);
+                }
+                Self::take_token_fee(&mut schedules, program_global_state.fee_bps_in_token, fee_discount_bps)?
+            }
+            _ => 0,
+        };
+        let locked_amount = total_amount - token_fee;
+
+        Self::assert_schedule_minimums(&program_global_state, &schedules, time_basis, &clock)?;
+        // Only the cliff of a vesting schedule is among the schedules, the rest vests until its end
+        if let LockBody::Vesting(vesting) = &body {
+            Self::assert_max_lock_duration(&program_global_state, vesting.vesting_end, time_basis, time_basis.now(&clock)?)?;
+        }
+
+        // The locking token account is the associated token account of the locking account.
+        // Create it on the fly when the caller did not pre-create it.
+        if locking_token_account.data_is_empty() {
+            if *associated_token_program_account.key != spl_associated_token_account::id() {
+                msg!("The provided associated token program account is invalid");
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            let locking_token_account_key = get_associated_token_address_with_program_id(
+                &locking_account_key,
+                mint_address,
+                spl_token_account.key,
+            );
+            if locking_token_account_key != *locking_token_account.key {
+                msg!("Provided locking token account is not the associated token account of the locking account");
                 return Err(ProgramError::InvalidArgument);
             }
+
+            let create_locking_token_account = create_associated_token_account_with_program_id(
+                fee_payer.key,
+                &locking_account_key,
+                mint_address,
+                spl_token_account.key,
+            );
+
+            invoke(
+                &create_locking_token_account,
+                &[
+                    associated_token_program_account.clone(),
+                    fee_payer.clone(),
+                    locking_token_account.clone(),
+                    locking_account.clone(),
+                    mint_account.clone(),
+                    system_program_account.clone(),
+                    spl_token_account.clone(),
+                    rent_sysvar_account.clone(),
+                ],
+            )?;
         }
-        
-        let transfer_sol_to_company_wallet = transfer_sol(
-            &source_token_account_owner.key,
-            &company_wallet.key,
-            token_state_data.estimate_fees_in_sol()?,
-        );
 
-        invoke(
-            &transfer_sol_to_company_wallet,
-            &[
-                source_token_account_owner.clone(),
-                company_wallet.clone(),
-            ],
+        let locking_token_account_data = unpack_token_account(locking_token_account, spl_token_account.key)?;
+        assert_valid_vault(&locking_token_account_data, &locking_account_key, mint_address)?;
+
+        Self::init_token_state(
+            program_id,
+            mint_address,
+            token_state_account,
+            fee_payer,
+            system_program_account,
+            &Rent::from_account_info(rent_sysvar_account)?,
         )?;
+        match &fee_payment {
+            FeePayment::Sol => Self::collect_fees(&fee, fee_payer, company_wallet)?,
+            FeePayment::Usd(usd_fee_accounts) => Self::collect_usd_fees(
+                &fee,
+                &program_global_state,
+                spl_token_account,
+                fee_payer,
+                usd_fee_accounts,
+            )?,
+            // Taken from the source token account once it is checked
+            FeePayment::Token(_) => {}
+        }
+
+        let source_token_account_data = unpack_token_account(source_token_account, spl_token_account.key)?;
+        assert_valid_source(&source_token_account_data, mint_address)?;
+        assert_source_authority(&source_token_account_data, source_token_account_owner.key, total_amount)?;
+        lock_signer.assert_derived_for(&source_token_account_data.owner, mint_address)?;
+
+        if source_token_account_data.amount < total_amount {
+            msg!("The source token account has insufficient funds.");
+            return Err(ProgramError::InsufficientFunds)
+        };
+
+        // Headers of the legacy layout have no flags byte to remember that the lock was counted
+        let header_len = LockScheduleHeader::header_len(locking_account.data_len());
+        let is_registered = header_len >= LockScheduleHeader::V1_LEN;
+        if is_registered {
+            Self::record_lock(
+                token_state_account,
+                fee_payer,
+                system_program_account,
+                &Rent::from_account_info(rent_sysvar_account)?,
+                locked_amount,
+            )?;
+        }
 
         let state_header = LockScheduleHeader {
             destination_address: *destination_token_address,
             mint_address: *mint_address,
             is_initialized: true,
+            pending_destination_address: Pubkey::default(),
+            is_vesting: matches!(body, LockBody::Vesting(_)),
+            is_nft,
+            is_registered,
+            is_immutable,
+            is_multi: matches!(body, LockBody::Multi(_)),
+            is_derived: lock_signer.is_derived(),
+            total_amount: locked_amount,
+            claimed_amount: 0,
+            schedule_count: match &body {
+                LockBody::Schedules(_) | LockBody::Multi(_) => schedules.len() as u32,
+                LockBody::Vesting(_) => 0,
+            },
+            unlocked_count: 0,
+            rescue_destination_address: Pubkey::default(),
+            rescue_execute_after: 0,
+            creator_address: source_token_account_data.owner,
+            time_basis,
+            split_destinations: {
+                let mut split = [SplitDestination::default(); MAX_SPLIT_DESTINATIONS];
+                split[..split_destinations.len()].copy_from_slice(&split_destinations);
+                split
+            },
+            delegate_address: Pubkey::default(),
+            permit_nonce: 0,
+            min_claim_interval_secs,
+            last_claim_ts: 0,
+            lock_nonce: lock_signer.nonce(),
+            lock_bump: lock_signer.bump(),
+            created_at: clock.unix_timestamp,
         };
 
-        let mut data = locking_account.data.borrow_mut();
-        if data.len() != LockScheduleHeader::LEN + schedules.len() * LockSchedule::LEN {
+        let body_len = match &body {
+            LockBody::Schedules(schedules) => schedules.len() * LockSchedule::LEN,
+            LockBody::Multi(schedules) => schedules.len() * MultiLockSchedule::LEN,
+            LockBody::Vesting(_) => VestingSchedule::LEN,
+        };
+        let lock_len = header_len + body_len;
+        if locking_account.data_len() < lock_len {
             return Err(ProgramError::InvalidAccountData)
         }
-        state_header.pack_into_slice(&mut data);
-
-        let mut offset = LockScheduleHeader::LEN;
-        let mut total_amount: u64 = 0;
+        // Unused schedule slots would be read as empty schedules, so they are cut off
+        let unused_rent = if locking_account.data_len() > lock_len {
+            let rent = Rent::from_account_info(rent_sysvar_account)?;
+            Self::trim_locking_account(locking_account, fee_payer, lock_len, &rent)?
+        } else {
+            0
+        };
 
-        for s in schedules.iter() {
-            let state_schedule = LockSchedule {
-                release_time: s.release_time,
-                amount: s.amount,
-            };
-            state_schedule.pack_into_slice(&mut data[offset..]);
-            let delta = total_amount.checked_add(s.amount);
-            match delta {
-                Some(n) => total_amount = n,
-                None => return Err(ProgramError::InvalidInstructionData), // Total amount overflows u64
+        let mut data = locking_account.data.borrow_mut();
+        let has_flags =
+            state_header.is_vesting || state_header.is_nft || state_header.is_immutable || state_header.is_multi;
+        if has_flags && header_len < LockScheduleHeader::V1_LEN {
+            msg!("The locking account was initialized before the header flags and can only hold mutable schedules");
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        if time_basis != TimeBasis::UnixTimestamp && header_len < LockScheduleHeader::V4_LEN {
+            msg!("The locking account was initialized before slot release times and can only hold unix timestamps");
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        // Unlock relies on the schedule counters to find the matured schedules of every destination
+        if state_header.is_multi && header_len < LockScheduleHeader::V4_LEN {
+            msg!("The locking account was initialized before per-schedule destinations");
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        if state_header.has_split() && header_len < LockScheduleHeader::V5_LEN {
+            msg!("The locking account was initialized before split destinations");
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        if min_claim_interval_secs != 0 && header_len < LockScheduleHeader::V8_LEN {
+            msg!("The locking account was initialized before claim cooldowns");
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        state_header.pack_into_slice(&mut data[..header_len]);
+
+        match &body {
+            LockBody::Schedules(_) => {
+                let mut offset = header_len;
+
+                for s in schedules.iter() {
+                    let state_schedule = LockSchedule {
+                        release_time: s.release_time,
+                        amount: s.amount,
+                    };
+                    state_schedule.pack_into_slice(&mut data[offset..]);
+                    offset += SCHEDULE_SIZE;
+                }
             }
-            offset += SCHEDULE_SIZE;
+            LockBody::Multi(multi_schedules) => {
+                let records = data[header_len..].chunks_exact_mut(MultiLockSchedule::LEN);
+                for ((record, s), multi_schedule) in records.zip(schedules.iter()).zip(multi_schedules.iter()) {
+                    MultiLockSchedule {
+                        release_time: s.release_time,
+                        amount: s.amount,
+                        destination_address: multi_schedule.destination_token_address,
+                    }
+                    .pack_into_slice(record);
+                }
+            }
+            LockBody::Vesting(vesting) => vesting.pack_into_slice(&mut data[header_len..]),
+        }
+
+        if let FeePayment::Token(company_token_account) = fee_payment {
+            Self::collect_token_fees(
+                &program_global_state,
+                spl_token_account,
+                source_token_account,
+                source_token_account_owner,
+                &multisig_signer_accounts,
+                locking_token_account,
+                mint_account,
+                company_token_account,
+                mint.decimals,
+                token_fee,
+            )?;
         }
-        
-        if Account::unpack(&source_token_account.data.borrow())?.amount < total_amount {
-            msg!("The source token account has insufficient funds.");
-            return Err(ProgramError::InsufficientFunds)
-        };
 
-        let transfer_tokens_to_locking_account = transfer(
+        let transfer_tokens_to_locking_account = transfer_checked_instruction(
             spl_token_account.key,
             source_token_account.key,
+            mint_account.key,
             locking_token_account.key,
             source_token_account_owner.key,
-            &[],
-            total_amount,
+            &multisig_signer_accounts.iter().map(|account| account.key).collect::<Vec<_>>(),
+            locked_amount,
+            mint.decimals,
         )?;
 
-        invoke(
-            &transfer_tokens_to_locking_account,
-            &[
-                source_token_account.clone(),
-                locking_token_account.clone(),
-                spl_token_account.clone(),
-                source_token_account_owner.clone(),
-            ],
-        )?;
+        let mut transfer_accounts = vec![
+            source_token_account.clone(),
+            mint_account.clone(),
+            locking_token_account.clone(),
+            spl_token_account.clone(),
+            source_token_account_owner.clone(),
+        ];
+        transfer_accounts.extend_from_slice(&multisig_signer_accounts);
+        invoke(&transfer_tokens_to_locking_account, &transfer_accounts)?;
+
+        // Refunded once the CPIs are done, as a CPI holding only one of the two accounts fails on the moved lamports
+        if unused_rent != 0 {
+            Self::refund_unused_rent(locking_account, fee_payer, unused_rent)?;
+        }
+
+        emit(&LockCreated {
+            seeds_hash: hashv(&lock_signer.seeds()).to_bytes(),
+            locking_account: locking_account_key,
+            mint: *mint_address,
+            destination: *destination_token_address,
+            total_amount: locked_amount,
+            schedule_count: schedules.len() as u32,
+        });
         Ok(())
     }
 
-    pub fn process_unlock(
-        program_id: &Pubkey,
-        _accounts: &[AccountInfo],
-        seeds: [u8; 32],
-    ) -> ProgramResult {
-        let accounts_iter = &mut _accounts.iter();
-
-        let spl_token_account = next_account_info(accounts_iter)?;
-        let clock_sysvar_account = next_account_info(accounts_iter)?;
-        let program_state_account = next_account_info(accounts_iter)?;
-        let locking_account = next_account_info(accounts_iter)?;
-        let locking_token_account = next_account_info(accounts_iter)?;
-        let destination_token_account = next_account_info(accounts_iter)?;
-
-        let program_state_account_key = Pubkey::create_program_address(&[String::from(OWNER_TOKEN_MINT_ADDRESS).as_bytes()], program_id)?;
-
-        if program_state_account_key != *program_state_account.key {
-            msg!("Provided program state account is invalid");
-            return Err(ProgramError::InvalidArgument);
+    // Shrinks a locking account initialized with more schedules than its lock holds to lock_len bytes.
+    // The rent of the unused slots goes back to the payer of Init when it pays for Create, otherwise it stays
+    // in the locking account until the lock is closed. Returns the rent owed to the fee payer, see refund_unused_rent.
+    fn trim_locking_account(
+        locking_account: &AccountInfo,
+        fee_payer: &AccountInfo,
+        lock_len: usize,
+        rent: &Rent,
+    ) -> Result<u64, ProgramError> {
+        let old_len = locking_account.data_len();
+        let init_payer = LockScheduleHeader::unpack_init_payer(&locking_account.try_borrow_data()?);
+        locking_account.realloc(lock_len, false)?;
+
+        if init_payer != Some(*fee_payer.key) {
+            return Ok(0);
         }
+        Ok(rent.minimum_balance(old_len).saturating_sub(rent.minimum_balance(lock_len)))
+    }
 
-        let is_state_initialized = program_state_account.try_borrow_data()?[LockGlobalState::LEN - 1] == 1;
+    fn refund_unused_rent(locking_account: &AccountInfo, fee_payer: &AccountInfo, unused_rent: u64) -> ProgramResult {
+        **locking_account.try_borrow_mut_lamports()? = locking_account
+            .lamports()
+            .checked_sub(unused_rent)
+            .ok_or(LockTokenError::MathOverflow)?;
+        **fee_payer.try_borrow_mut_lamports()? = fee_payer
+            .lamports()
+            .checked_add(unused_rent)
+            .ok_or(LockTokenError::MathOverflow)?;
+        Ok(())
+    }
+
+    fn assert_valid_vesting(vesting: &VestingSchedule, clock: &Clock) -> ProgramResult {
+        if vesting.total_amount == 0 {
+            msg!("The vesting schedule has a zero amount");
+            return Err(LockTokenError::ZeroAmountSchedule.into());
+        }
+        if vesting.cliff_bps > MAX_BPS {
+            msg!("The cliff of {} basis points is more than {}", vesting.cliff_bps, MAX_BPS);
+            return Err(LockTokenError::InvalidBasisPoints.into());
+        }
+        assert_future(vesting.cliff_time, now_u64(clock)?, 0)?;
+        assert_seconds(vesting.vesting_end)?;
+        if vesting.vesting_end < vesting.cliff_time {
+            msg!("The vesting can not end before the cliff");
+            return Err(LockTokenError::SchedulesNotSorted.into());
+        }
+        Ok(())
+    }
+
+    // Schedules of a new lock have to be non-empty, strictly ascending by release time, in the future and non-zero
+    // Split destinations have non-zero shares summing to MAX_BPS, the first one being the destination of the lock
+    fn assert_valid_split(
+        split_destinations: &[SplitDestination],
+        destination_token_address: &Pubkey,
+        locking_token_account_key: &Pubkey,
+    ) -> ProgramResult {
+        if split_destinations.len() > MAX_SPLIT_DESTINATIONS {
+            msg!("A lock is split across at most {} destinations", MAX_SPLIT_DESTINATIONS);
+            return Err(LockTokenError::InvalidSplit.into());
+        }
+        if split_destinations[0].destination_address != *destination_token_address {
+            msg!("The first split destination has to be the destination of the lock");
+            return Err(LockTokenError::InvalidSplit.into());
+        }
+        let mut total_bps: u32 = 0;
+        for (i, split) in split_destinations.iter().enumerate() {
+            if split.share_bps == 0 {
+                msg!("Split destination {} has no share", i);
+                return Err(LockTokenError::InvalidSplit.into());
+            }
+            if split.destination_address == Pubkey::default() || split.destination_address == *locking_token_account_key {
+                msg!("Split destination {} is not a valid destination", i);
+                return Err(LockTokenError::InvalidSplit.into());
+            }
+            if split_destinations[..i].iter().any(|other| other.destination_address == split.destination_address) {
+                msg!("Split destination {} is given twice", split.destination_address);
+                return Err(LockTokenError::InvalidSplit.into());
+            }
+            total_bps += split.share_bps as u32;
+        }
+        if total_bps != MAX_BPS as u32 {
+            msg!("The split shares add up to {} instead of {} basis points", total_bps, MAX_BPS);
+            return Err(LockTokenError::InvalidSplit.into());
+        }
+        Ok(())
+    }
+
+    // Schedules of a CreateMulti lock may share a release time, as they go to several destinations
+    fn assert_valid_schedules(
+        schedules: &[Schedule],
+        time_basis: TimeBasis,
+        clock: &Clock,
+        allow_equal_release_times: bool,
+    ) -> ProgramResult {
+        if schedules.is_empty() {
+            msg!("A lock needs at least one schedule");
+            return Err(LockTokenError::EmptySchedules.into());
+        }
+
+        let now = time_basis.now(clock)?;
+        for (index, s) in schedules.iter().enumerate() {
+            if s.amount == 0 {
+                msg!("Schedule {} has a zero amount", index);
+                return Err(LockTokenError::ZeroAmountSchedule.into());
+            }
+            if let Err(error) = time_basis.assert_future(s.release_time, now) {
+                msg!("Schedule {} has an invalid release time", index);
+                return Err(error);
+            }
+            let is_after_previous = index == 0
+                || s.release_time > schedules[index - 1].release_time
+                || (allow_equal_release_times && s.release_time == schedules[index - 1].release_time);
+            if !is_after_previous {
+                msg!("Schedule {} is not released after schedule {}", index, index - 1);
+                return Err(LockTokenError::SchedulesNotSorted.into());
+            }
+        }
+        Ok(())
+    }
+
+    // Checks every schedule against the minimum lock duration and amount and the maximum lock duration
+    // of the global config.
+    // Locks released at slot heights count the durations in slots, see TimeBasis::duration_from_secs.
+    fn assert_schedule_minimums(
+        program_global_state: &LockGlobalState,
+        schedules: &[Schedule],
+        time_basis: TimeBasis,
+        clock: &Clock,
+    ) -> ProgramResult {
+        let now = time_basis.now(clock)?;
+        let min_release_time = now.saturating_add(time_basis.duration_from_secs(program_global_state.min_lock_duration_secs));
+        for (index, s) in schedules.iter().enumerate() {
+            Self::assert_max_lock_duration(program_global_state, s.release_time, time_basis, now)?;
+            if program_global_state.min_lock_duration_secs != 0 && s.release_time < min_release_time {
+                msg!(
+                    "Schedule {} is released at {}, before the minimum lock duration ends at {}",
+                    index,
+                    s.release_time,
+                    min_release_time
+                );
+                return Err(LockTokenError::LockDurationTooShort.into());
+            }
+            if program_global_state.min_lock_amount != 0 && s.amount < program_global_state.min_lock_amount {
+                msg!(
+                    "Schedule {} locks {}, less than the minimum amount {}",
+                    index,
+                    s.amount,
+                    program_global_state.min_lock_amount
+                );
+                return Err(LockTokenError::LockAmountTooSmall.into());
+            }
+        }
+        Ok(())
+    }
+
+    // Release times further than the maximum lock duration of the global config from now are rejected
+    fn assert_max_lock_duration(
+        program_global_state: &LockGlobalState,
+        release_time: u64,
+        time_basis: TimeBasis,
+        now: u64,
+    ) -> ProgramResult {
+        if program_global_state.max_lock_duration_secs == 0 {
+            return Ok(());
+        }
+        let max_release_time = now.saturating_add(time_basis.duration_from_secs(program_global_state.max_lock_duration_secs));
+        if release_time > max_release_time {
+            msg!(
+                "The release time {} is after the maximum lock duration ends at {}",
+                release_time,
+                max_release_time
+            );
+            return Err(LockTokenError::DurationTooLong.into());
+        }
+        Ok(())
+    }
+
+    // Reads the token state deciding the fee of the mint, which is not created before the first lock of the mint
+    fn load_fee_token_state(
+        program_id: &Pubkey,
+        mint_address: &Pubkey,
+        token_state_account: &AccountInfo,
+    ) -> Result<TokenState, ProgramError> {
+        let token_state_account_key = token_state_address(program_id, mint_address)?;
+        if token_state_account_key != *token_state_account.key {
+            msg!("Provided token state account is invalid");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let mut token_state_data = TokenState {
+            mint_address: *mint_address,
+            is_free: false,
+            fee_override_usd: 0,
+            has_override: false,
+            active_locks: 0,
+            total_locked_amount: 0,
+            last_snapshot_amount: 0,
+            last_snapshot_ts: 0,
+            is_initialized: false,
+        };
+        // is_initialized is the last byte of every layout
+        let is_free_token_initialized = token_state_account.try_borrow_data()?.last() == Some(&1);
+        if is_free_token_initialized == true {
+            let packed_state = &token_state_account.data;
+            token_state_data = TokenState::unpack_from_slice(&packed_state.borrow())?;
+            if token_state_data.mint_address != *mint_address {
+                msg!("Provided token state account is invalid");
+                return Err(ProgramError::InvalidArgument);
+            }
+        }
+
+        Ok(token_state_data)
+    }
+
+    // Works out the fee owed for the mint without moving any funds. Exempt payers owe nothing, like free tokens.
+    fn quote_fees(
+        program_global_state: &LockGlobalState,
+        token_state_data: &TokenState,
+        price_estimator_account: &AccountInfo,
+        payer: &Pubkey,
+        company_wallet: &Pubkey,
+        pay_fee_in_sol: bool,
+        fee_discount_bps: u16,
+        is_fee_exempt: bool,
+    ) -> Result<FeeCollected, ProgramError> {
+        if *company_wallet != program_global_state.company_wallet {
+            msg!("The fee should be paid to the company wallet {}", program_global_state.company_wallet);
+            return Err(LockTokenError::CompanyWalletMismatch.into());
+        }
+
+        let is_free = token_state_data.is_free || is_fee_exempt;
+
+        // Free tokens and fees paid in another currency do not depend on the oracle at all
+        let price = if is_free || !pay_fee_in_sol {
+            None
+        } else {
+            if *price_estimator_account.key != program_global_state.price_estimator {
+                msg!("Provided price estimator account is invalid");
+                return Err(LockTokenError::InvalidOracleAccount.into());
+            }
+            Some(load_price(
+                price_estimator_account,
+                &Clock::get()?,
+                program_global_state.max_price_age_slots,
+            )?)
+        };
+
+        // Free tokens owe nothing in either currency
+        let fees_in_usd = if is_free {
+            0
+        } else {
+            apply_fee_discount(token_state_data.fees_in_usd(program_global_state.fees_in_usd), fee_discount_bps)?
+        };
+
+        let fees_in_sol = match price {
+            Some(price) => usd_to_lamports(fees_in_usd, &price)?,
+            None => 0,
+        };
+
+        Ok(FeeCollected {
+            lamports: fees_in_sol,
+            fees_in_usd,
+            oracle_price: price.map_or(0, |price| price.price),
+            oracle_expo: price.map_or(0, |price| price.expo),
+            payer: *payer,
+            company_wallet: *company_wallet,
+        })
+    }
+
+    fn collect_fees<'a>(
+        fee: &FeeCollected,
+        payer: &AccountInfo<'a>,
+        company_wallet: &AccountInfo<'a>,
+    ) -> ProgramResult {
+        msg!("Fee charged: {} lamports", fee.lamports);
+        if fee.lamports == 0 {
+            return Ok(());
+        }
+
+        let transfer_sol_to_company_wallet = transfer_sol(
+            &payer.key,
+            &company_wallet.key,
+            fee.lamports,
+        );
+
+        invoke(
+            &transfer_sol_to_company_wallet,
+            &[
+                payer.clone(),
+                company_wallet.clone(),
+            ],
+        )?;
+
+        emit(fee);
+
+        Ok(())
+    }
+
+    // Takes fee_bps of every schedule, rounded down and less the discount, and returns the total taken. Rounding
+    // every schedule down never takes more than total_amount * fee_bps / MAX_BPS, and every schedule keeps a
+    // non-zero amount.
+    fn take_token_fee(schedules: &mut [Schedule], fee_bps: u16, fee_discount_bps: u16) -> Result<u64, ProgramError> {
+        let mut fee = 0u64;
+        for s in schedules.iter_mut() {
+            let share = s.amount as u128 * fee_bps as u128 / MAX_BPS as u128;
+            // The share is at most the amount, so it fits in u64
+            let share = apply_fee_discount(share as u64, fee_discount_bps)?;
+            s.amount -= share;
+            fee = fee.checked_add(share).ok_or(LockTokenError::MathOverflow)?;
+        }
+        Ok(fee)
+    }
+
+    // Discount of the Create fee for the balance of the owner token held by the fee payer.
+    // An account of another mint or owner is rejected, while a balance below every tier pays the full fee.
+    fn load_fee_discount_bps(
+        program_global_state: &LockGlobalState,
+        spl_token_account: &AccountInfo,
+        fee_payer: &AccountInfo,
+        fee_discount_token_account: &AccountInfo,
+    ) -> Result<u16, ProgramError> {
+        let owner_token_mint = program_global_state.owner_token_mint;
+        if owner_token_mint == Pubkey::default() {
+            msg!("No owner token is configured, the full fee is charged");
+            return Ok(0);
+        }
+
+        let fee_discount_token_account_data = unpack_token_account(fee_discount_token_account, spl_token_account.key)?;
+        if fee_discount_token_account_data.mint != owner_token_mint {
+            msg!("The fee discount token account is not a token account of the owner token {}", owner_token_mint);
+            return Err(LockTokenError::FeeMintMismatch.into());
+        }
+        if fee_discount_token_account_data.owner != *fee_payer.key {
+            msg!("The fee discount token account is not owned by the fee payer");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let fee_discount_bps = program_global_state.fee_discount_bps(fee_discount_token_account_data.amount);
+        msg!("Fee discount: {} basis points", fee_discount_bps);
+        Ok(fee_discount_bps)
+    }
+
+    // Whether the admin exempted the fee payer from the fee with SetFeeExemptWallet.
+    // A wallet state account which was never created belongs to the system program and is not exempt.
+    fn load_fee_exemption(
+        program_id: &Pubkey,
+        fee_payer: &AccountInfo,
+        wallet_state_account: &AccountInfo,
+    ) -> Result<bool, ProgramError> {
+        let (wallet_state_key, _) = find_wallet_state_address(program_id, fee_payer.key);
+        if wallet_state_key != *wallet_state_account.key {
+            msg!("Provided wallet state account is not the wallet state of the fee payer");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if *wallet_state_account.owner != *program_id {
+            return Ok(false);
+        }
+
+        let wallet_state_data = WalletState::unpack_from_slice(&wallet_state_account.data.borrow())?;
+        let is_fee_exempt = wallet_state_data.is_initialized && wallet_state_data.is_fee_exempt;
+        if is_fee_exempt {
+            msg!("The fee payer is exempt from the fee");
+        }
+        Ok(is_fee_exempt)
+    }
+
+    // Transfers the fee taken from the schedules from the source token account to the company's token account
+    fn collect_token_fees<'a>(
+        program_global_state: &LockGlobalState,
+        spl_token_account: &AccountInfo<'a>,
+        source_token_account: &AccountInfo<'a>,
+        source_token_account_owner: &AccountInfo<'a>,
+        multisig_signer_accounts: &[AccountInfo<'a>],
+        locking_token_account: &AccountInfo<'a>,
+        mint_account: &AccountInfo<'a>,
+        company_token_account: &AccountInfo<'a>,
+        decimals: u8,
+        amount: u64,
+    ) -> ProgramResult {
+        // A fee paid into the vault would be swept back to the creator
+        assert_distinct_accounts(&[
+            ("source token account", source_token_account.key),
+            ("locking token account", locking_token_account.key),
+            ("company's token account", company_token_account.key),
+        ])?;
+
+        let company_token_account_data = unpack_token_account(company_token_account, spl_token_account.key)?;
+        if company_token_account_data.mint != *mint_account.key {
+            msg!("The company's token account is not a token account of the locked mint");
+            return Err(LockTokenError::FeeMintMismatch.into());
+        }
+        if company_token_account_data.owner != program_global_state.company_wallet {
+            msg!("The fee should be paid to the company wallet {}", program_global_state.company_wallet);
+            return Err(LockTokenError::CompanyWalletMismatch.into());
+        }
+
+        msg!("Fee charged: {} locked token base units", amount);
+        if amount == 0 {
+            return Ok(());
+        }
+
+        let transfer_fee_to_company = transfer_checked_instruction(
+            spl_token_account.key,
+            source_token_account.key,
+            mint_account.key,
+            company_token_account.key,
+            source_token_account_owner.key,
+            &multisig_signer_accounts.iter().map(|account| account.key).collect::<Vec<_>>(),
+            amount,
+            decimals,
+        )?;
+
+        let mut transfer_accounts = vec![
+            source_token_account.clone(),
+            mint_account.clone(),
+            company_token_account.clone(),
+            spl_token_account.clone(),
+            source_token_account_owner.clone(),
+        ];
+        transfer_accounts.extend_from_slice(multisig_signer_accounts);
+        invoke(&transfer_fee_to_company, &transfer_accounts)?;
+
+        emit(&TokenFeeCollected {
+            mint: *mint_account.key,
+            amount,
+            payer: *source_token_account_owner.key,
+            company_token_account: *company_token_account.key,
+        });
+
+        Ok(())
+    }
+
+    // Transfers fees_in_usd in the USD token from the payer's token account to the company's token account
+    fn collect_usd_fees<'a>(
+        fee: &FeeCollected,
+        program_global_state: &LockGlobalState,
+        spl_token_account: &AccountInfo<'a>,
+        payer: &AccountInfo<'a>,
+        usd_fee_accounts: &UsdFeeAccounts<'_, 'a>,
+    ) -> ProgramResult {
+        let usd_token_address = program_global_state.usd_token_address;
+        if usd_token_address == Pubkey::default() {
+            msg!("Paying fees in the USD token is not configured");
+            return Err(LockTokenError::FeeMintMismatch.into());
+        }
+        if *usd_fee_accounts.mint_account.key != usd_token_address {
+            msg!("The fee should be paid in the USD token {}", usd_token_address);
+            return Err(LockTokenError::FeeMintMismatch.into());
+        }
+        let usd_mint = unpack_mint(usd_fee_accounts.mint_account, spl_token_account.key)?;
+        // A transfer into the same account would leave the fee unpaid
+        assert_distinct_accounts(&[
+            ("fee payer's USD token account", usd_fee_accounts.payer_token_account.key),
+            ("company's USD token account", usd_fee_accounts.company_token_account.key),
+        ])?;
+
+        let payer_token_account_data = unpack_token_account(usd_fee_accounts.payer_token_account, spl_token_account.key)?;
+        if payer_token_account_data.mint != usd_token_address {
+            msg!("The fee payer's token account is not a USD token account");
+            return Err(LockTokenError::FeeMintMismatch.into());
+        }
+
+        let company_token_account_data =
+            unpack_token_account(usd_fee_accounts.company_token_account, spl_token_account.key)?;
+        if company_token_account_data.mint != usd_token_address {
+            msg!("The company's token account is not a USD token account");
+            return Err(LockTokenError::FeeMintMismatch.into());
+        }
+        if company_token_account_data.owner != program_global_state.company_wallet {
+            msg!("The fee should be paid to the company wallet {}", program_global_state.company_wallet);
+            return Err(LockTokenError::CompanyWalletMismatch.into());
+        }
+
+        let token_amount = usd_token_amount(fee.fees_in_usd, usd_mint.decimals)?;
+        msg!("Fee charged: {} USD token base units", token_amount);
+        if token_amount == 0 {
+            return Ok(());
+        }
+
+        let transfer_usd_to_company = transfer_checked_instruction(
+            spl_token_account.key,
+            usd_fee_accounts.payer_token_account.key,
+            usd_fee_accounts.mint_account.key,
+            usd_fee_accounts.company_token_account.key,
+            payer.key,
+            &[],
+            token_amount,
+            usd_mint.decimals,
+        )?;
+
+        invoke(
+            &transfer_usd_to_company,
+            &[
+                usd_fee_accounts.payer_token_account.clone(),
+                usd_fee_accounts.mint_account.clone(),
+                usd_fee_accounts.company_token_account.clone(),
+                spl_token_account.clone(),
+                payer.clone(),
+            ],
+        )?;
+
+        emit(&UsdFeeCollected {
+            token_amount,
+            fees_in_usd: fee.fees_in_usd,
+            payer: *payer.key,
+            company_token_account: *usd_fee_accounts.company_token_account.key,
+        });
+
+        Ok(())
+    }
+
+    // Creates the token state of a mint the first time it is locked, so it can be marked free later
+    fn init_token_state<'a>(
+        program_id: &Pubkey,
+        mint_address: &Pubkey,
+        token_state_account: &AccountInfo<'a>,
+        payer: &AccountInfo<'a>,
+        system_program_account: &AccountInfo<'a>,
+        rent: &Rent,
+    ) -> ProgramResult {
+        if !token_state_account.data_is_empty() {
+            return Ok(());
+        }
+
+        assert_writable(token_state_account, "token state account")?;
+
+        let mint_seed = mint_address.to_bytes();
+        let signer_seeds = signed_seeds_for(token_state_account.key, &mint_seed, program_id)?;
+
+        let create_token_state_account = create_account(
+            &payer.key,
+            token_state_account.key,
+            rent.minimum_balance(TokenState::LEN),
+            TokenState::LEN as u64,
+            &program_id,
+        );
+
+        invoke_signed(
+            &create_token_state_account,
+            &[
+                system_program_account.clone(),
+                payer.clone(),
+                token_state_account.clone(),
+            ],
+            &[&signer_seeds],
+        )?;
+
+        let token_state_data = TokenState {
+            mint_address: *mint_address,
+            is_free: false,
+            fee_override_usd: 0,
+            has_override: false,
+            active_locks: 0,
+            total_locked_amount: 0,
+            last_snapshot_amount: 0,
+            last_snapshot_ts: 0,
+            is_initialized: true,
+        };
+        token_state_data.pack_into_slice(&mut token_state_account.data.borrow_mut());
+
+        Ok(())
+    }
+
+    // Counts a new lock of the mint in its token state. Token states of the older layouts are grown to the
+    // current layout first, see grow_token_state.
+    fn record_lock<'a>(
+        token_state_account: &AccountInfo<'a>,
+        payer: &AccountInfo<'a>,
+        system_program_account: &AccountInfo<'a>,
+        rent: &Rent,
+        amount: u64,
+    ) -> ProgramResult {
+        assert_writable(token_state_account, "token state account")?;
+
+        let mut token_state_data = TokenState::unpack_from_slice(&token_state_account.data.borrow())?;
+        Self::grow_token_state(token_state_account, payer, system_program_account, rent)?;
+
+        token_state_data.active_locks = token_state_data
+            .active_locks
+            .checked_add(1)
+            .ok_or(LockTokenError::MathOverflow)?;
+        token_state_data.total_locked_amount = token_state_data
+            .total_locked_amount
+            .checked_add(amount)
+            .ok_or(LockTokenError::MathOverflow)?;
+        token_state_data.pack_into_slice(&mut token_state_account.data.borrow_mut());
+
+        Ok(())
+    }
+
+    // Token states of the older layouts are grown to the current layout, the payer covering the extra rent
+    fn grow_token_state<'a>(
+        token_state_account: &AccountInfo<'a>,
+        payer: &AccountInfo<'a>,
+        system_program_account: &AccountInfo<'a>,
+        rent: &Rent,
+    ) -> ProgramResult {
+        if token_state_account.data_len() >= TokenState::LEN {
+            return Ok(());
+        }
+        assert_writable(token_state_account, "token state account")?;
+
+        let extra_rent = rent
+            .minimum_balance(TokenState::LEN)
+            .saturating_sub(token_state_account.lamports());
+        if extra_rent > 0 {
+            assert_writable(payer, "payer")?;
+            invoke(
+                &transfer_sol(payer.key, token_state_account.key, extra_rent),
+                &[
+                    payer.clone(),
+                    token_state_account.clone(),
+                    system_program_account.clone(),
+                ],
+            )?;
+        }
+        token_state_account.realloc(TokenState::LEN, true)
+    }
+
+    pub fn process_create_native(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+        destination_address: &Pubkey,
+        schedules: Vec<Schedule>,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let spl_token_account = next_account_info(accounts_iter)?;
+        let program_state_account = next_account_info(accounts_iter)?;
+        let locking_account = next_account_info(accounts_iter)?;
+        let locking_token_account = next_account_info(accounts_iter)?;
+        let payer = next_account_info(accounts_iter)?;
+        let token_state_account = next_account_info(accounts_iter)?;
+        let company_wallet = next_account_info(accounts_iter)?;
+        let system_program_account = next_account_info(accounts_iter)?;
+        let rent_sysvar_account = next_account_info(accounts_iter)?;
+        let mint_account = next_account_info(accounts_iter)?;
+        let price_estimator_account = next_account_info(accounts_iter)?;
+
+        // The company wallet may lock its own SOL, so it can also be the payer
+        assert_distinct_accounts(&[
+            ("program state account", program_state_account.key),
+            ("locking account", locking_account.key),
+            ("locking token account", locking_token_account.key),
+            ("payer", payer.key),
+            ("token state account", token_state_account.key),
+        ])?;
+        assert_distinct_accounts(&[
+            ("program state account", program_state_account.key),
+            ("locking account", locking_account.key),
+            ("locking token account", locking_token_account.key),
+            ("token state account", token_state_account.key),
+            ("company wallet", company_wallet.key),
+        ])?;
+
+        assert_writable(locking_account, "locking account")?;
+        assert_writable(locking_token_account, "locking token account")?;
+        assert_writable(payer, "payer")?;
+        assert_writable(token_state_account, "token state account")?;
+        assert_writable(company_wallet, "company wallet")?;
+
+        let rent = Rent::from_account_info(rent_sysvar_account)?;
+
+        let program_global_state = load_global_state(program_id, program_state_account)?;
+
+        assert_not_paused(&program_global_state, PAUSE_CREATE)?;
+
+        let lock_signer = assert_lock(program_id, locking_account, seeds)?;
+        let locking_account_key = *locking_account.key;
+
+        if !payer.is_signer {
+            msg!("Payer should be a signer.");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let is_initialized =
+            LockScheduleHeader::is_packed_initialized(&locking_account.try_borrow_data()?);
+
+        if is_initialized {
+            msg!("Cannot overwrite an existing locking contract.");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        // Wrapped SOL only exists under the legacy token program
+        if *spl_token_account.key != spl_token::id() {
+            msg!("The provided spl token program account is invalid");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if *mint_account.key != native_mint::id() {
+            msg!("Provided mint account is not the native mint");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let clock = Clock::get()?;
+        Self::assert_valid_schedules(&schedules, TimeBasis::UnixTimestamp, &clock, false)?;
+        Self::assert_schedule_minimums(&program_global_state, &schedules, TimeBasis::UnixTimestamp, &clock)?;
+
+        // The wrapped SOL account is created here, so it has to be a fresh keypair account.
+        if !locking_token_account.is_signer || !locking_token_account.data_is_empty() {
+            msg!("The locking token account should be a new signer account");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let token_state_data = Self::load_fee_token_state(program_id, mint_account.key, token_state_account)?;
+        let fee = Self::quote_fees(
+            &program_global_state,
+            &token_state_data,
+            price_estimator_account,
+            payer.key,
+            company_wallet.key,
+            true,
+            0,
+            false,
+        )?;
+        Self::init_token_state(
+            program_id,
+            mint_account.key,
+            token_state_account,
+            payer,
+            system_program_account,
+            &rent,
+        )?;
+        Self::collect_fees(&fee, payer, company_wallet)?;
+
+        let total_amount = schedules
+            .iter()
+            .try_fold(0u64, |total, s| total.checked_add(s.amount))
+            .ok_or(ProgramError::InvalidInstructionData)?; // Total amount overflows u64
+
+        lock_signer.assert_derived_for(payer.key, &native_mint::id())?;
+
+        let header_len = LockScheduleHeader::header_len(locking_account.data_len());
+        let is_registered = header_len >= LockScheduleHeader::V1_LEN;
+        if is_registered {
+            Self::record_lock(token_state_account, payer, system_program_account, &rent, total_amount)?;
+        }
+
+        let state_header = LockScheduleHeader {
+            destination_address: *destination_address,
+            mint_address: native_mint::id(),
+            is_initialized: true,
+            pending_destination_address: Pubkey::default(),
+            is_vesting: false,
+            is_nft: false,
+            is_registered,
+            is_immutable: false,
+            is_multi: false,
+            is_derived: lock_signer.is_derived(),
+            total_amount,
+            claimed_amount: 0,
+            schedule_count: schedules.len() as u32,
+            unlocked_count: 0,
+            rescue_destination_address: Pubkey::default(),
+            rescue_execute_after: 0,
+            creator_address: *payer.key,
+            time_basis: TimeBasis::UnixTimestamp,
+            split_destinations: Default::default(),
+            delegate_address: Pubkey::default(),
+            permit_nonce: 0,
+            min_claim_interval_secs: 0,
+            last_claim_ts: 0,
+            lock_nonce: lock_signer.nonce(),
+            lock_bump: lock_signer.bump(),
+            created_at: clock.unix_timestamp,
+        };
+
+        let lock_len = header_len + schedules.len() * LockSchedule::LEN;
+        if locking_account.data_len() < lock_len {
+            return Err(ProgramError::InvalidAccountData)
+        }
+        let unused_rent = if locking_account.data_len() > lock_len {
+            Self::trim_locking_account(locking_account, payer, lock_len, &rent)?
+        } else {
+            0
+        };
+
+        {
+            // The data borrow has to end before the locking account is passed to the token program
+            let mut data = locking_account.data.borrow_mut();
+            state_header.pack_into_slice(&mut data[..header_len]);
+
+            let mut offset = header_len;
+
+            for s in schedules.iter() {
+                let state_schedule = LockSchedule {
+                    release_time: s.release_time,
+                    amount: s.amount,
+                };
+                state_schedule.pack_into_slice(&mut data[offset..]);
+                offset += SCHEDULE_SIZE;
+            }
+        }
+
+        // The wrapped SOL account holds its own rent exempt reserve on top of the locked lamports.
+        // Initializing a native account sets its token amount to the lamports above that reserve.
+        let lamports = rent
+            .minimum_balance(Account::LEN)
+            .checked_add(total_amount)
+            .ok_or(ProgramError::InvalidInstructionData)?;
+
+        let create_locking_token_account = create_account(
+            payer.key,
+            locking_token_account.key,
+            lamports,
+            Account::LEN as u64,
+            &spl_token::id(),
+        );
+
+        invoke(
+            &create_locking_token_account,
+            &[
+                system_program_account.clone(),
+                payer.clone(),
+                locking_token_account.clone(),
+            ],
+        )?;
+
+        let init_locking_token_account = initialize_account(
+            &spl_token::id(),
+            locking_token_account.key,
+            &native_mint::id(),
+            &locking_account_key,
+        )?;
+
+        invoke(
+            &init_locking_token_account,
+            &[
+                spl_token_account.clone(),
+                locking_token_account.clone(),
+                mint_account.clone(),
+                locking_account.clone(),
+                rent_sysvar_account.clone(),
+            ],
+        )?;
+
+        if unused_rent != 0 {
+            Self::refund_unused_rent(locking_account, payer, unused_rent)?;
+        }
+
+        emit(&LockCreated {
+            seeds_hash: hashv(&lock_signer.seeds()).to_bytes(),
+            locking_account: locking_account_key,
+            mint: native_mint::id(),
+            destination: *destination_address,
+            total_amount,
+            schedule_count: schedules.len() as u32,
+        });
+        Ok(())
+    }
+
+    pub fn process_unlock(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+        deadline: u64,
+    ) -> ProgramResult {
+        Self::unlock_up_to(program_id, accounts, seeds, deadline, None)
+    }
+
+    pub fn process_unlock_amount(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+        amount: u64,
+    ) -> ProgramResult {
+        if amount == 0 {
+            msg!("The amount to unlock should be greater than 0");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        Self::unlock_up_to(program_id, accounts, seeds, 0, Some(amount))
+    }
+
+    // Unlocks what has matured, at most amount when given
+    fn unlock_up_to(
+        program_id: &Pubkey,
+        _accounts: &[AccountInfo],
+        seeds: LockSeed,
+        deadline: u64,
+        amount: Option<u64>,
+    ) -> ProgramResult {
+        let accounts_iter = &mut _accounts.iter();
+
+        let spl_token_account = next_account_info(accounts_iter)?;
+        skip_clock_sysvar(accounts_iter);
+        let program_state_account = next_account_info(accounts_iter)?;
+        let locking_account = next_account_info(accounts_iter)?;
+        let locking_token_account = next_account_info(accounts_iter)?;
+        let destination_token_account = next_account_info(accounts_iter)?;
+        let mint_account = next_account_info(accounts_iter)?;
+        let token_state_account = next_account_info(accounts_iter)?;
+        let other_destination_accounts = accounts_iter.as_slice();
+
+        assert_distinct_accounts(&[
+            ("program state account", program_state_account.key),
+            ("locking account", locking_account.key),
+            ("locking token account", locking_token_account.key),
+            ("destination token account", destination_token_account.key),
+            ("token state account", token_state_account.key),
+        ])?;
+
+        assert_writable(locking_account, "locking account")?;
+
+        assert_global_state(program_id, program_state_account)?;
+
+        assert_unlock_not_paused(program_state_account)?;
+
+        let clock = Clock::get()?;
+        assert_not_expired(deadline, &clock)?;
+
+        let (total_amount_to_transfer, remaining) = Self::unlock_matured(
+            program_id,
+            spl_token_account,
+            locking_account,
+            locking_token_account,
+            destination_token_account,
+            other_destination_accounts,
+            mint_account,
+            token_state_account,
+            seeds,
+            amount,
+            &clock,
+        )?;
+        if total_amount_to_transfer == 0 {
+            msg!("locking contract has not yet reached release time");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        // Wallets show what is left of the lock without decoding the locking account again
+        emit(&LockRemaining {
+            locking_account: *locking_account.key,
+            remaining_locked: remaining.remaining_locked,
+            schedule_count: remaining.schedule_count,
+            next_release_time: remaining.next_release_time,
+        });
+        let mut return_data = [0; RemainingSchedules::LEN];
+        remaining.pack_into_slice(&mut return_data);
+        set_return_data(&return_data);
+
+        Ok(())
+    }
+
+    pub fn process_batch_unlock(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds_list: &[LockSeed],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let spl_token_account = next_account_info(accounts_iter)?;
+        skip_clock_sysvar(accounts_iter);
+        let program_state_account = next_account_info(accounts_iter)?;
+        let mint_account = next_account_info(accounts_iter)?;
+        let token_state_account = next_account_info(accounts_iter)?;
+        let lock_accounts = accounts_iter.as_slice();
+
+        if seeds_list.is_empty() || lock_accounts.len() != seeds_list.len() * BATCH_UNLOCK_GROUP_LEN {
+            msg!(
+                "Expected {} accounts for {} locks, got {}",
+                seeds_list.len() * BATCH_UNLOCK_GROUP_LEN,
+                seeds_list.len(),
+                lock_accounts.len()
+            );
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+
+        assert_global_state(program_id, program_state_account)?;
+
+        assert_unlock_not_paused(program_state_account)?;
+
+        let clock = Clock::get()?;
+
+        // Every group is checked before the first transfer
+        for group in lock_accounts.chunks_exact(BATCH_UNLOCK_GROUP_LEN) {
+            assert_distinct_accounts(&[
+                ("program state account", program_state_account.key),
+                ("locking account", group[0].key),
+                ("locking token account", group[1].key),
+                ("destination token account", group[2].key),
+                ("token state account", token_state_account.key),
+            ])?;
+            assert_writable(&group[0], "locking account")?;
+        }
+
+        let mut unlocked_count = 0;
+        for (seeds, group) in seeds_list.iter().zip(lock_accounts.chunks_exact(BATCH_UNLOCK_GROUP_LEN)) {
+            let (amount, _) = match Self::unlock_matured(
+                program_id,
+                spl_token_account,
+                &group[0],
+                &group[1],
+                &group[2],
+                &[],
+                mint_account,
+                token_state_account,
+                *seeds,
+                None,
+                &clock,
+            ) {
+                Err(error) if error == ProgramError::from(LockTokenError::LockFullyUnlocked) => (0, RemainingSchedules::default()),
+                result => result?,
+            };
+            if amount > 0 {
+                unlocked_count += 1;
+            }
+        }
+        msg!("Unlocked {} of {} locks", unlocked_count, seeds_list.len());
+
+        Ok(())
+    }
+
+    // Unlock which also closes the lock once nothing is left in it, see LockTokenInstruction::ClaimAndClose
+    pub fn process_claim_and_close(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let spl_token_account = next_account_info(accounts_iter)?;
+        let program_state_account = next_account_info(accounts_iter)?;
+        let locking_account = next_account_info(accounts_iter)?;
+        let locking_token_account = next_account_info(accounts_iter)?;
+        let destination_token_account = next_account_info(accounts_iter)?;
+        let mint_account = next_account_info(accounts_iter)?;
+        let token_state_account = next_account_info(accounts_iter)?;
+        let refund_account = next_account_info(accounts_iter)?;
+        let other_destination_accounts = accounts_iter.as_slice();
+
+        assert_distinct_accounts(&[
+            ("program state account", program_state_account.key),
+            ("locking account", locking_account.key),
+            ("locking token account", locking_token_account.key),
+            ("destination token account", destination_token_account.key),
+            ("token state account", token_state_account.key),
+            ("refund account", refund_account.key),
+        ])?;
+
+        assert_writable(locking_account, "locking account")?;
+        assert_writable(refund_account, "refund account")?;
+
+        assert_global_state(program_id, program_state_account)?;
+
+        assert_unlock_not_paused(program_state_account)?;
+
+        let clock = Clock::get()?;
+
+        // A lock drained by earlier unlocks can still be closed
+        let (total_amount_to_transfer, remaining) = match Self::unlock_matured(
+            program_id,
+            spl_token_account,
+            locking_account,
+            locking_token_account,
+            destination_token_account,
+            other_destination_accounts,
+            mint_account,
+            token_state_account,
+            seeds,
+            None,
+            &clock,
+        ) {
+            Err(error) if error == ProgramError::from(LockTokenError::LockFullyUnlocked) => (0, RemainingSchedules::default()),
+            result => result?,
+        };
+
+        let (header, locked) = {
+            let data = locking_account.data.borrow();
+            let header_len = LockScheduleHeader::header_len(data.len());
+            let header = LockScheduleHeader::unpack_from_slice(&data[..header_len])?;
+            let locked = locked_amount(&header, &data[header_len..])?;
+            (header, locked)
+        };
+        let is_finished = locked == 0;
+        // Native locks paying out lamports close the locking token account on their first unlock.
+        // The locking token account is checked again as unlocking a drained lock stops before it.
+        let is_vault_empty = if locking_token_account.data_is_empty() {
+            true
+        } else {
+            let vault = unpack_token_account(locking_token_account, spl_token_account.key)?;
+            assert_valid_vault(&vault, locking_account.key, &header.mint_address)?;
+            vault.amount == 0
+        };
+
+        if !is_finished || !is_vault_empty {
+            if total_amount_to_transfer == 0 {
+                msg!("locking contract has not yet reached release time");
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            emit(&LockRemaining {
+                locking_account: *locking_account.key,
+                remaining_locked: remaining.remaining_locked,
+                schedule_count: remaining.schedule_count,
+                next_release_time: remaining.next_release_time,
+            });
+            let mut return_data = [0; RemainingSchedules::LEN];
+            remaining.pack_into_slice(&mut return_data);
+            set_return_data(&return_data);
+            return Ok(());
+        }
+
+        // The payer of Init is overwritten by Create, so the rent goes to the owner of the destination, which is
+        // the system account itself for native locks paying out lamports
+        let refund_address = if *destination_token_account.owner == system_program::id() {
+            *destination_token_account.key
+        } else {
+            unpack_token_account(destination_token_account, spl_token_account.key)?.owner
+        };
+        if refund_address != *refund_account.key {
+            msg!("The rent of the lock goes to the owner of its destination {}", refund_address);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let locking_account_key = *locking_account.key;
+        if !locking_token_account.data_is_empty() {
+            assert_writable(locking_token_account, "locking token account")?;
+            let close_locking_token_account = close_account(
+                spl_token_account.key,
+                locking_token_account.key,
+                refund_account.key,
+                &locking_account_key,
+                &[],
+            )?;
+
+            let lock_signer = lock_signer(program_id, locking_account, seeds)?;
+
+            let signer_seeds = lock_signer.signed_seeds_for(locking_account.key, program_id)?;
+            invoke_signed(
+                &close_locking_token_account,
+                &[
+                    spl_token_account.clone(),
+                    locking_token_account.clone(),
+                    refund_account.clone(),
+                    locking_account.clone(),
+                ],
+                &[&signer_seeds],
+            )?;
+        }
+
+        let lamports = locking_account.lamports();
+        **refund_account.try_borrow_mut_lamports()? = refund_account
+            .lamports()
+            .checked_add(lamports)
+            .ok_or(LockTokenError::MathOverflow)?;
+        **locking_account.try_borrow_mut_lamports()? = 0;
+        locking_account.data.borrow_mut().fill(0);
+
+        emit(&LockClosed {
+            locking_account: locking_account_key,
+            refund_account: *refund_account.key,
+            lamports,
+        });
+        let mut return_data = [0; RemainingSchedules::LEN];
+        RemainingSchedules::default().pack_into_slice(&mut return_data);
+        set_return_data(&return_data);
+
+        Ok(())
+    }
+
+    // Unlock relayed on a permit of the owner of the destination, see LockTokenInstruction::UnlockWithPermit
+    pub fn process_unlock_with_permit(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+        expiry: u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let spl_token_account = next_account_info(accounts_iter)?;
+        let program_state_account = next_account_info(accounts_iter)?;
+        let locking_account = next_account_info(accounts_iter)?;
+        let locking_token_account = next_account_info(accounts_iter)?;
+        let destination_token_account = next_account_info(accounts_iter)?;
+        let mint_account = next_account_info(accounts_iter)?;
+        let token_state_account = next_account_info(accounts_iter)?;
+        let instructions_sysvar_account = next_account_info(accounts_iter)?;
+        let other_destination_accounts = accounts_iter.as_slice();
+
+        assert_distinct_accounts(&[
+            ("program state account", program_state_account.key),
+            ("locking account", locking_account.key),
+            ("locking token account", locking_token_account.key),
+            ("destination token account", destination_token_account.key),
+            ("token state account", token_state_account.key),
+        ])?;
+
+        assert_writable(locking_account, "locking account")?;
+
+        assert_global_state(program_id, program_state_account)?;
+
+        assert_unlock_not_paused(program_state_account)?;
+
+        let clock = Clock::get()?;
+        if now_u64(&clock)? > expiry {
+            msg!("The permit expired at {}", expiry);
+            return Err(LockTokenError::PermitExpired.into());
+        }
+
+        assert_lock(program_id, locking_account, seeds)?;
+
+        let header_len = LockScheduleHeader::header_len(locking_account.data_len());
+        if header_len < LockScheduleHeader::V7_LEN {
+            msg!("The locking account was created before claim permits, it has to be migrated first");
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+
+        let mut header = LockScheduleHeader::unpack_from_slice(&locking_account.data.borrow()[..header_len])?;
+        if header.destination_address != *destination_token_account.key {
+            msg!("Contract destination account does not matched provided account");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        // Native locks paying out lamports have a system account as destination, which signs the permit itself
+        let signer = if *destination_token_account.owner == system_program::id() {
+            *destination_token_account.key
+        } else {
+            unpack_token_account(destination_token_account, spl_token_account.key)?.owner
+        };
+        let message = permit_message(program_id, locking_account.key, header.permit_nonce, expiry);
+        assert_ed25519_permit(instructions_sysvar_account, &signer, &message)?;
+
+        // The permit is used up before unlocking, which reads the header again
+        header.permit_nonce = header.permit_nonce.checked_add(1).ok_or(LockTokenError::MathOverflow)?;
+        header.pack_into_slice(&mut locking_account.data.borrow_mut()[..header_len]);
+
+        let (total_amount_to_transfer, remaining) = Self::unlock_matured(
+            program_id,
+            spl_token_account,
+            locking_account,
+            locking_token_account,
+            destination_token_account,
+            other_destination_accounts,
+            mint_account,
+            token_state_account,
+            seeds,
+            None,
+            &clock,
+        )?;
+        if total_amount_to_transfer == 0 {
+            msg!("locking contract has not yet reached release time");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        emit(&LockRemaining {
+            locking_account: *locking_account.key,
+            remaining_locked: remaining.remaining_locked,
+            schedule_count: remaining.schedule_count,
+            next_release_time: remaining.next_release_time,
+        });
+        let mut return_data = [0; RemainingSchedules::LEN];
+        remaining.pack_into_slice(&mut return_data);
+        set_return_data(&return_data);
+
+        Ok(())
+    }
+
+    // Transfers the matured schedules of one lock to its destination and returns the amount released,
+    // zero when nothing has matured yet, along with what the lock still holds. The matured schedules of a
+    // CreateMulti lock are transferred to their own destinations and a split lock divides the amount across
+    // its split destinations, both looked up in other_destination_accounts.
+    fn unlock_matured<'a>(
+        program_id: &Pubkey,
+        spl_token_account: &AccountInfo<'a>,
+        locking_account: &AccountInfo<'a>,
+        locking_token_account: &AccountInfo<'a>,
+        destination_token_account: &AccountInfo<'a>,
+        other_destination_accounts: &[AccountInfo<'a>],
+        mint_account: &AccountInfo<'a>,
+        token_state_account: &AccountInfo<'a>,
+        seeds: LockSeed,
+        amount: Option<u64>,
+        clock: &Clock,
+    ) -> Result<(u64, RemainingSchedules), ProgramError> {
+        let lock_signer = assert_lock(program_id, locking_account, seeds)?;
+        let locking_account_key = *locking_account.key;
+
+        assert_token_program(spl_token_account)?;
+
+        let packed_state = &locking_account.data;
+        let header_len = LockScheduleHeader::header_len(packed_state.borrow().len());
+        let mut header_state =
+            LockScheduleHeader::unpack_from_slice(&packed_state.borrow()[..header_len])?;
+        let now = header_state.time_basis.now(clock)?;
+
+        if header_state.destination_address != *destination_token_account.key {
+            msg!("Contract destination account does not matched provided account");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        // Headers of the current layout count the unlocked schedules, so a drained lock is rejected
+        // before any schedule is read
+        let has_counters = header_len >= LockScheduleHeader::V3_LEN;
+        if has_counters && header_state.is_drained() {
+            msg!("Every schedule of the lock has already been unlocked");
+            return Err(LockTokenError::LockFullyUnlocked.into());
+        }
+
+        // The cooldown counts in the time basis of the lock like the release times, and the first claim is never held back
+        if header_state.min_claim_interval_secs != 0
+            && header_state.last_claim_ts != 0
+            && now.saturating_sub(header_state.last_claim_ts) < u64::from(header_state.min_claim_interval_secs)
+        {
+            msg!(
+                "The lock was claimed at {}, the next claim is possible at {}",
+                header_state.last_claim_ts,
+                header_state.last_claim_ts.saturating_add(u64::from(header_state.min_claim_interval_secs))
+            );
+            return Err(LockTokenError::ClaimTooSoon.into());
+        }
+
+        // Native locks unlocking into a system account are paid out in lamports: the wrapped SOL
+        // account is closed into the locking account on the first such unlock. Unlocking into a
+        // token account closes it once the lock is drained.
+        let unwrap_native = header_state.mint_address == native_mint::id()
+            && *destination_token_account.owner == system_program::id();
+
+        if header_state.mint_address != native_mint::id() || !locking_token_account.data_is_empty() {
+            let locking_token_account_data = unpack_token_account(locking_token_account, spl_token_account.key)?;
+            assert_valid_vault(&locking_token_account_data, &locking_account_key, &header_state.mint_address)?;
+        }
+
+        if !unwrap_native {
+            let destination_token_account_data = unpack_token_account(destination_token_account, spl_token_account.key)?;
+            assert_valid_destination(&destination_token_account_data, &header_state.mint_address)?;
+        }
+
+        if header_state.mint_address != *mint_account.key {
+            msg!("Provided mint account does not match the locked mint");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let mint = unpack_mint(mint_account, spl_token_account.key)?;
+
+        // Unlock the schedules that have reached maturity and reset their amounts to 0.
+        // Schedules are sorted by release time, so the scan stops at the first one still locked
+        // and only the slots that are actually released get rewritten.
+        // A vesting lock releases what vested since the previous unlock.
+        // The schedule the scan stops at is the next one to be released.
+        // With an amount, the matured schedules are released oldest first until the amount is reached, and the
+        // schedule it runs out in keeps the rest with its past release time, so the scan stops there.
+        let mut total_amount_to_transfer: u64 = 0;
+        let mut remaining_locked: u64 = 0;
+        let mut schedule_count: u32 = 0;
+        let mut next_release_time: u64 = 0;
+        // What is released of a matured schedule holding schedule_amount
+        let release = |total_amount_to_transfer: u64, schedule_amount: u64| match amount {
+            Some(amount) => schedule_amount.min(amount.saturating_sub(total_amount_to_transfer)),
+            None => schedule_amount,
+        };
+        // Amount released to every destination other than the destination of the lock
+        let mut other_payouts: Vec<(Pubkey, u64)> = Vec::new();
+        if header_state.is_vesting {
+            let mut data = packed_state.borrow_mut();
+            let mut vesting = VestingSchedule::unpack_from_slice(&data[header_len..])?;
+            total_amount_to_transfer = release(0, vesting.claimable_amount(now));
+            vesting.claimed_amount = vesting
+                .claimed_amount
+                .checked_add(total_amount_to_transfer)
+                .ok_or(LockTokenError::MathOverflow)?;
+            remaining_locked = vesting.total_amount.saturating_sub(vesting.claimed_amount);
+            // The rest vests continuously after the cliff and is fully released at the vesting end
+            if remaining_locked > 0 {
+                schedule_count = 1;
+                next_release_time = if is_matured(vesting.cliff_time, now) { vesting.vesting_end } else { vesting.cliff_time };
+            }
+            vesting.pack_into_slice(&mut data[header_len..]);
+        } else if header_state.is_multi {
+            // Locks with per-schedule destinations always have schedule counters, see create_lock
+            let mut data = packed_state.borrow_mut();
+            let records = data[header_len..]
+                .chunks_exact_mut(MultiLockSchedule::LEN)
+                .skip(header_state.unlocked_count as usize);
+            for record in records {
+                let s = MultiLockSchedule::unpack_from_slice(record)?;
+                if !is_matured(s.release_time, now) {
+                    next_release_time = s.release_time;
+                    break;
+                }
+                let released = release(total_amount_to_transfer, s.amount);
+                total_amount_to_transfer = total_amount_to_transfer
+                    .checked_add(released)
+                    .ok_or(LockTokenError::MathOverflow)?;
+                let destination = s.destination(&header_state.destination_address);
+                if destination != header_state.destination_address && released > 0 {
+                    match other_payouts.iter_mut().find(|(key, _)| *key == destination) {
+                        Some((_, amount)) => {
+                            *amount = amount.checked_add(released).ok_or(LockTokenError::MathOverflow)?
+                        }
+                        None => other_payouts.push((destination, released)),
+                    }
+                }
+                MultiLockSchedule { amount: s.amount - released, ..s }.pack_into_slice(record);
+                if released < s.amount {
+                    next_release_time = s.release_time;
+                    break;
+                }
+                header_state.unlocked_count = header_state
+                    .unlocked_count
+                    .checked_add(1)
+                    .ok_or(LockTokenError::MathOverflow)?;
+            }
+        } else if has_counters {
+            // Only locks of the older layouts can hold unsorted schedules, so the matured schedules
+            // following the unlocked ones are found by binary search and emptied in place
+            let mut data = packed_state.borrow_mut();
+            let schedules = PodLockSchedule::try_cast_slice_mut(&mut data[header_len..])?;
+            // The cursor is trusted to skip schedules, so a cursor past a schedule still holding tokens is rejected
+            validate_cursor(&header_state, schedules).map_err(|error| {
+                msg!("The lock counts {} unlocked schedules of {}", header_state.unlocked_count, schedules.len());
+                error
+            })?;
+            let schedules = &mut schedules[header_state.unlocked_count as usize..];
+            let matured_count = matured_schedule_count(schedules, now);
+            let mut drained_count = 0;
+            for s in schedules[..matured_count].iter_mut() {
+                let schedule_amount = u64::from(s.amount);
+                let released = release(total_amount_to_transfer, schedule_amount);
+                total_amount_to_transfer = total_amount_to_transfer
+                    .checked_add(released)
+                    .ok_or(LockTokenError::MathOverflow)?;
+                s.amount = PodU64::from(schedule_amount - released);
+                if released < schedule_amount {
+                    break;
+                }
+                drained_count += 1;
+            }
+            next_release_time = schedules.get(drained_count).map_or(0, |s| s.release_time.into());
+            header_state.unlocked_count = header_state
+                .unlocked_count
+                .checked_add(drained_count as u32)
+                .ok_or(LockTokenError::MathOverflow)?;
+        } else {
+            let mut data = packed_state.borrow_mut();
+            let mut slots = data[header_len..].chunks_exact_mut(LockSchedule::LEN);
+            let mut first_locked = None;
+            for slot in &mut slots {
+                let s = LockSchedule::unpack_from_slice(slot)?;
+                if !is_matured(s.release_time, now) {
+                    first_locked = Some(s);
+                    break;
+                }
+                if s.amount == 0 {
+                    continue;
+                }
+                let released = release(total_amount_to_transfer, s.amount);
+                total_amount_to_transfer = total_amount_to_transfer
+                    .checked_add(released)
+                    .ok_or(LockTokenError::MathOverflow)?;
+                let rest = LockSchedule {
+                    release_time: s.release_time,
+                    amount: s.amount - released,
+                };
+                rest.pack_into_slice(slot);
+                // The rest of a partially released schedule is still in the lock
+                if rest.amount > 0 {
+                    first_locked = Some(rest);
+                    break;
+                }
+            }
+            // Only the schedules still locked are read for the event, and these layouts can hold them unsorted
+            let still_locked = first_locked.into_iter().map(Ok).chain(slots.map(|slot| LockSchedule::unpack_from_slice(slot)));
+            for s in still_locked {
+                let s = s?;
+                if s.amount == 0 {
+                    continue;
+                }
+                remaining_locked = remaining_locked
+                    .checked_add(s.amount)
+                    .ok_or(LockTokenError::MathOverflow)?;
+                schedule_count += 1;
+                if next_release_time == 0 || s.release_time < next_release_time {
+                    next_release_time = s.release_time;
+                }
+            }
+        }
+
+        if let Some(amount) = amount {
+            if total_amount_to_transfer < amount {
+                msg!("Only {} of the {} to unlock has matured", total_amount_to_transfer, amount);
+                return Err(LockTokenError::InsufficientUnlocked.into());
+            }
+        }
+
+        // Headers of the older layouts have no room for the claimed amount and are left as they are
+        header_state.claimed_amount = header_state
+            .claimed_amount
+            .checked_add(total_amount_to_transfer)
+            .ok_or(LockTokenError::MathOverflow)?;
+        if has_counters && !header_state.is_vesting {
+            remaining_locked = header_state.total_amount.saturating_sub(header_state.claimed_amount);
+            schedule_count = header_state.schedule_count.saturating_sub(header_state.unlocked_count);
+        }
+        let remaining = RemainingSchedules {
+            remaining_locked,
+            schedule_count,
+            next_release_time,
+        };
+        if total_amount_to_transfer == 0 {
+            return Ok((0, remaining));
+        }
+        // The destination and the locking token account are only written once something has matured
+        assert_writable(destination_token_account, "destination token account")?;
+        if !locking_token_account.data_is_empty() {
+            assert_writable(locking_token_account, "locking token account")?;
+        }
+        // Headers of the older layouts have no room for the claim time and are left as they are
+        header_state.last_claim_ts = now;
+        header_state.pack_into_slice(&mut packed_state.borrow_mut()[..header_len]);
+
+        // Locks counted in the token state of their mint take what they release off the mint total
+        if header_state.is_registered {
+            let token_state_account_key = token_state_address(program_id, &header_state.mint_address)?;
+            if token_state_account_key != *token_state_account.key {
+                msg!("Provided token state account is invalid");
+                return Err(ProgramError::InvalidArgument);
+            }
+            assert_writable(token_state_account, "token state account")?;
+
+            let mut token_state_data = TokenState::unpack_from_slice(&token_state_account.data.borrow())?;
+            token_state_data.total_locked_amount = token_state_data
+                .total_locked_amount
+                .checked_sub(total_amount_to_transfer)
+                .ok_or(LockTokenError::MathOverflow)?;
+            token_state_data.pack_into_slice(&mut token_state_account.data.borrow_mut());
+        }
+
+        if unwrap_native {
+            if !locking_token_account.data_is_empty() {
+                let close_locking_token_account = close_account(
+                    spl_token_account.key,
+                    locking_token_account.key,
+                    &locking_account_key,
+                    &locking_account_key,
+                    &[],
+                )?;
+
+                let signer_seeds = lock_signer.signed_seeds_for(locking_account.key, program_id)?;
+                invoke_signed(
+                    &close_locking_token_account,
+                    &[
+                        spl_token_account.clone(),
+                        locking_token_account.clone(),
+                        locking_account.clone(),
+                    ],
+                    &[&signer_seeds],
+                )?;
+            }
+
+            // The last unlock also pays out the rent exempt reserve of the closed wrapped SOL account, so the
+            // locking account keeps only its own rent exempt minimum
+            let payout = if remaining_locked == 0 {
+                let reserve = Rent::get()?.minimum_balance(locking_account.data_len());
+                locking_account.lamports().saturating_sub(reserve).max(total_amount_to_transfer)
+            } else {
+                total_amount_to_transfer
+            };
+            let locking_account_lamports = locking_account
+                .lamports()
+                .checked_sub(payout)
+                .ok_or(ProgramError::InsufficientFunds)?;
+            let destination_lamports = destination_token_account
+                .lamports()
+                .checked_add(payout)
+                .ok_or(ProgramError::InvalidArgument)?;
+            **locking_account.try_borrow_mut_lamports()? = locking_account_lamports;
+            **destination_token_account.try_borrow_mut_lamports()? = destination_lamports;
+
+            emit(&LockUnlocked {
+                locking_account: locking_account_key,
+                destination: header_state.destination_address,
+                amount: total_amount_to_transfer,
+                remaining_locked,
+            });
+        } else {
+            let schedule_amount = other_payouts
+                .iter()
+                .try_fold(0u64, |total, (_, amount)| total.checked_add(*amount))
+                .ok_or(LockTokenError::MathOverflow)?;
+            let mut destination_amount = total_amount_to_transfer - schedule_amount;
+            // The first split destination is the destination of the lock and keeps the rounding dust
+            if header_state.has_split() {
+                let split = split_amounts(destination_amount, header_state.split())?;
+                destination_amount = split[0].1;
+                other_payouts.extend_from_slice(&split[1..]);
+            }
+            let mut payouts = vec![(destination_token_account, destination_amount)];
+            for (destination, amount) in other_payouts.iter() {
+                if *amount == 0 {
+                    continue;
+                }
+                let other_destination_account = other_destination_accounts
+                    .iter()
+                    .find(|account| account.key == destination)
+                    .ok_or_else(|| {
+                        msg!("The destination token account {} of the unlocked amount is missing", destination);
+                        ProgramError::NotEnoughAccountKeys
+                    })?;
+                assert_writable(other_destination_account, "destination token account")?;
+                let other_destination_data =
+                    unpack_token_account(other_destination_account, spl_token_account.key)?;
+                assert_valid_destination(&other_destination_data, &header_state.mint_address)?;
+                payouts.push((other_destination_account, *amount));
+            }
+
+            let signer_seeds = lock_signer.signed_seeds_for(locking_account.key, program_id)?;
+            for (destination_account, amount) in payouts {
+                if amount == 0 {
+                    continue;
+                }
+                let transfer_tokens_from_locking_account = transfer_checked_instruction(
+                    spl_token_account.key,
+                    locking_token_account.key,
+                    mint_account.key,
+                    destination_account.key,
+                    &locking_account_key,
+                    &[],
+                    amount,
+                    mint.decimals,
+                )?;
+
+                invoke_signed(
+                    &transfer_tokens_from_locking_account,
+                    &[
+                        spl_token_account.clone(),
+                        locking_token_account.clone(),
+                        mint_account.clone(),
+                        destination_account.clone(),
+                        locking_account.clone(),
+                    ],
+                    &[&signer_seeds],
+                )?;
+
+                emit(&LockUnlocked {
+                    locking_account: locking_account_key,
+                    destination: *destination_account.key,
+                    amount,
+                    remaining_locked,
+                });
+            }
+
+            // A drained native lock closes its wrapped SOL account into the destination, which syncs the rent
+            // exempt reserve of the closed account into its balance
+            if header_state.mint_address == native_mint::id() && remaining_locked == 0 {
+                let close_locking_token_account = close_account(
+                    spl_token_account.key,
+                    locking_token_account.key,
+                    destination_token_account.key,
+                    &locking_account_key,
+                    &[],
+                )?;
+
+                invoke_signed(
+                    &close_locking_token_account,
+                    &[
+                        spl_token_account.clone(),
+                        locking_token_account.clone(),
+                        destination_token_account.clone(),
+                        locking_account.clone(),
+                    ],
+                    &[&signer_seeds],
+                )?;
+
+                let sync_destination = sync_native(spl_token_account.key, destination_token_account.key)?;
+                invoke(&sync_destination, &[spl_token_account.clone(), destination_token_account.clone()])?;
+            }
+        }
+
+        Ok((total_amount_to_transfer, remaining))
+    }
+
+    // Checks the program state account, the locking account and that the owner of the current
+    // destination signed. Returns the program state, the header of the lock and its length.
+    fn load_lock_as_beneficiary(
+        program_id: &Pubkey,
+        program_state_account: &AccountInfo,
+        locking_account: &AccountInfo,
+        destination_token_account: &AccountInfo,
+        destination_token_account_owner: &AccountInfo,
+        seeds: LockSeed,
+    ) -> Result<(LockGlobalState, LockScheduleHeader, usize), ProgramError> {
+        let (program_global_state, state, header_len) = Self::load_lock_as_owner_of(
+            program_id,
+            program_state_account,
+            locking_account,
+            destination_token_account,
+            destination_token_account_owner,
+            seeds,
+        )?;
+
+        if state.destination_address != *destination_token_account.key {
+            msg!("Contract destination account does not matched provided account");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        Ok((program_global_state, state, header_len))
+    }
+
+    // Like load_lock_as_beneficiary, for any token account signed for by its owner. The caller checks what
+    // the token account is to the lock.
+    fn load_lock_as_owner_of(
+        program_id: &Pubkey,
+        program_state_account: &AccountInfo,
+        locking_account: &AccountInfo,
+        destination_token_account: &AccountInfo,
+        destination_token_account_owner: &AccountInfo,
+        seeds: LockSeed,
+    ) -> Result<(LockGlobalState, LockScheduleHeader, usize), ProgramError> {
+        let loaded = Self::load_lock_for_signer(
+            program_id,
+            program_state_account,
+            locking_account,
+            destination_token_account,
+            destination_token_account_owner,
+            seeds,
+        )?;
+        Self::assert_token_account_owner_signed(destination_token_account, destination_token_account_owner)?;
+
+        Ok(loaded)
+    }
+
+    // Like load_lock_as_owner_of, also accepting the delegate approved for the current destination
+    fn load_lock_as_owner_or_delegate_of(
+        program_id: &Pubkey,
+        program_state_account: &AccountInfo,
+        locking_account: &AccountInfo,
+        destination_token_account: &AccountInfo,
+        destination_token_account_owner: &AccountInfo,
+        seeds: LockSeed,
+    ) -> Result<(LockGlobalState, LockScheduleHeader, usize), ProgramError> {
+        let (program_global_state, state, header_len) = Self::load_lock_for_signer(
+            program_id,
+            program_state_account,
+            locking_account,
+            destination_token_account,
+            destination_token_account_owner,
+            seeds,
+        )?;
+
+        let is_delegate = state.has_delegate()
+            && state.delegate_address == *destination_token_account_owner.key
+            && state.destination_address == *destination_token_account.key;
+        if is_delegate {
+            if !destination_token_account_owner.is_signer {
+                msg!("The delegate of the lock should be a signer.");
+                return Err(ProgramError::InvalidArgument);
+            }
+        } else {
+            Self::assert_token_account_owner_signed(destination_token_account, destination_token_account_owner)?;
+        }
+
+        Ok((program_global_state, state, header_len))
+    }
+
+    // Checks the program state account and the locking account, leaving the signer to the caller
+    fn load_lock_for_signer(
+        program_id: &Pubkey,
+        program_state_account: &AccountInfo,
+        locking_account: &AccountInfo,
+        destination_token_account: &AccountInfo,
+        destination_token_account_owner: &AccountInfo,
+        seeds: LockSeed,
+    ) -> Result<(LockGlobalState, LockScheduleHeader, usize), ProgramError> {
+        assert_distinct_accounts(&[
+            ("program state account", program_state_account.key),
+            ("locking account", locking_account.key),
+            ("destination token account", destination_token_account.key),
+            ("destination token account owner", destination_token_account_owner.key),
+        ])?;
+
+        assert_writable(locking_account, "locking account")?;
+
+        let program_global_state = load_global_state(program_id, program_state_account)?;
+        let (_, state, header_len) = load_lock(program_id, locking_account, seeds)?;
+
+        Ok((program_global_state, state, header_len))
+    }
+
+    fn assert_token_account_owner_signed(
+        destination_token_account: &AccountInfo,
+        destination_token_account_owner: &AccountInfo,
+    ) -> ProgramResult {
+        if !destination_token_account_owner.is_signer {
+            msg!("Destination token account owner should be a signer.");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let destination_token_account = Account::unpack(&destination_token_account.data.borrow())?;
+
+        if destination_token_account.owner != *destination_token_account_owner.key {
+            msg!("The current destination token account isn't owned by the provided owner");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        Ok(())
+    }
+
+    // Checks a new destination of the lock and returns the account which controls it.
+    // Native locks can also pay out lamports to a system account, see process_unlock, which controls itself.
+    fn assert_new_destination(
+        header: &LockScheduleHeader,
+        new_destination_token_account: &AccountInfo,
+    ) -> Result<Pubkey, ProgramError> {
+        let is_destination = *new_destination_token_account.key == header.destination_address
+            || header.split().iter().any(|split| split.destination_address == *new_destination_token_account.key);
+        if is_destination {
+            msg!("The lock already pays out to {}", new_destination_token_account.key);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let pays_out_lamports = header.mint_address == native_mint::id()
+            && *new_destination_token_account.owner == system_program::id();
+        if pays_out_lamports {
+            return Ok(*new_destination_token_account.key);
+        }
+
+        Ok(assert_valid_new_destination(new_destination_token_account, &header.mint_address)?.owner)
+    }
+
+    pub fn process_transfer_locks(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let program_state_account = next_account_info(accounts_iter)?;
+        let locking_account = next_account_info(accounts_iter)?;
+        let destination_token_account = next_account_info(accounts_iter)?;
+        let destination_token_account_owner = next_account_info(accounts_iter)?;
+        let new_destination_token_account = next_account_info(accounts_iter)?;
+
+        let (program_global_state, state, header_len) = Self::load_lock_as_owner_or_delegate_of(
+            program_id,
+            program_state_account,
+            locking_account,
+            destination_token_account,
+            destination_token_account_owner,
+            seeds,
+        )?;
+
+        // The owner of a split destination other than the first one only moves its own share
+        let split_index = state
+            .split()
+            .iter()
+            .skip(1)
+            .position(|split| split.destination_address == *destination_token_account.key)
+            .map(|index| index + 1);
+        if state.destination_address != *destination_token_account.key && split_index.is_none() {
+            msg!("Contract destination account does not matched provided account");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        assert_not_paused(&program_global_state, PAUSE_TRANSFER)?;
+
+        Self::assert_mutable_lock(&state)?;
+        Self::assert_new_destination(&state, new_destination_token_account)?;
+
+        emit(&LockTransferred {
+            locking_account: *locking_account.key,
+            old_destination: *destination_token_account.key,
+            new_destination: *new_destination_token_account.key,
+        });
+
+        let mut new_state = state;
+        match split_index {
+            Some(index) => {
+                new_state.split_destinations[index].destination_address = *new_destination_token_account.key;
+            }
+            None => {
+                // A transfer proposed by the previous beneficiary is withdrawn along with the lock, and so is a
+                // rescue, which the beneficiary has just shown not to need
+                new_state.set_destination(*new_destination_token_account.key);
+                new_state.pending_destination_address = Pubkey::default();
+                new_state.clear_pending_rescue();
+            }
+        }
+        new_state
+            .pack_into_slice(&mut locking_account.data.borrow_mut()[..header_len]);
+
+        Ok(())
+    }
+
+    pub fn process_propose_transfer_locks(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+        new_destination: &Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let program_state_account = next_account_info(accounts_iter)?;
+        let locking_account = next_account_info(accounts_iter)?;
+        let destination_token_account = next_account_info(accounts_iter)?;
+        let destination_token_account_owner = next_account_info(accounts_iter)?;
+
+        let (program_global_state, mut state, header_len) = Self::load_lock_as_beneficiary(
+            program_id,
+            program_state_account,
+            locking_account,
+            destination_token_account,
+            destination_token_account_owner,
+            seeds,
+        )?;
+
+        assert_not_paused(&program_global_state, PAUSE_TRANSFER)?;
+
+        if header_len < LockScheduleHeader::V1_LEN {
+            msg!("The locking account was created before pending transfers and can only use TransferLocks");
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+
+        Self::assert_mutable_lock(&state)?;
+
+        if *new_destination == state.destination_address || *new_destination == Pubkey::default() {
+            msg!("Invalid new destination {}", new_destination);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        state.pending_destination_address = *new_destination;
+        state.pack_into_slice(&mut locking_account.data.borrow_mut()[..header_len]);
+
+        Ok(())
+    }
+
+    pub fn process_accept_transfer_locks(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let program_state_account = next_account_info(accounts_iter)?;
+        let locking_account = next_account_info(accounts_iter)?;
+        let new_destination_token_account = next_account_info(accounts_iter)?;
+        let new_destination_token_account_owner = next_account_info(accounts_iter)?;
+
+        assert_distinct_accounts(&[
+            ("program state account", program_state_account.key),
+            ("locking account", locking_account.key),
+            ("new destination token account", new_destination_token_account.key),
+        ])?;
+
+        assert_writable(locking_account, "locking account")?;
+
+        let program_global_state = load_global_state(program_id, program_state_account)?;
+
+        assert_not_paused(&program_global_state, PAUSE_TRANSFER)?;
+
+        let (_, mut state, header_len) = load_lock(program_id, locking_account, seeds)?;
+        let locking_account_key = *locking_account.key;
+
+        Self::assert_mutable_lock(&state)?;
+
+        if !state.has_pending_destination() || state.pending_destination_address != *new_destination_token_account.key {
+            msg!("The new destination token account is not the proposed destination");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let new_destination_owner = Self::assert_new_destination(&state, new_destination_token_account)?;
+
+        if new_destination_owner != *new_destination_token_account_owner.key || !new_destination_token_account_owner.is_signer {
+            msg!("The owner of the proposed destination should sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        emit(&LockTransferred {
+            locking_account: locking_account_key,
+            old_destination: state.destination_address,
+            new_destination: state.pending_destination_address,
+        });
+
+        state.set_destination(state.pending_destination_address);
+        state.pending_destination_address = Pubkey::default();
+        state.clear_pending_rescue();
+        state.pack_into_slice(&mut locking_account.data.borrow_mut()[..header_len]);
+
+        Ok(())
+    }
+
+    // Not blocked by the pause, so a proposal can always be withdrawn
+    pub fn process_cancel_transfer_locks(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let program_state_account = next_account_info(accounts_iter)?;
+        let locking_account = next_account_info(accounts_iter)?;
+        let destination_token_account = next_account_info(accounts_iter)?;
+        let destination_token_account_owner = next_account_info(accounts_iter)?;
+
+        let (_, mut state, header_len) = Self::load_lock_as_beneficiary(
+            program_id,
+            program_state_account,
+            locking_account,
+            destination_token_account,
+            destination_token_account_owner,
+            seeds,
+        )?;
+
+        if !state.has_pending_destination() {
+            msg!("There is no pending transfer of the lock");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        state.pending_destination_address = Pubkey::default();
+        state.pack_into_slice(&mut locking_account.data.borrow_mut()[..header_len]);
+
+        Ok(())
+    }
+
+    pub fn process_approve_lock_delegate(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+        delegate: &Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let program_state_account = next_account_info(accounts_iter)?;
+        let locking_account = next_account_info(accounts_iter)?;
+        let destination_token_account = next_account_info(accounts_iter)?;
+        let destination_token_account_owner = next_account_info(accounts_iter)?;
+
+        let (program_global_state, mut state, header_len) = Self::load_lock_as_beneficiary(
+            program_id,
+            program_state_account,
+            locking_account,
+            destination_token_account,
+            destination_token_account_owner,
+            seeds,
+        )?;
+
+        assert_not_paused(&program_global_state, PAUSE_TRANSFER)?;
+
+        if header_len < LockScheduleHeader::V6_LEN {
+            msg!("The locking account was created before lock delegates, it has to be migrated first");
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+
+        Self::assert_mutable_lock(&state)?;
+
+        if *delegate == Pubkey::default() || *delegate == *destination_token_account_owner.key {
+            msg!("Invalid delegate {}", delegate);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        state.delegate_address = *delegate;
+        state.pack_into_slice(&mut locking_account.data.borrow_mut()[..header_len]);
+
+        Ok(())
+    }
+
+    // Not blocked by the pause, so a delegate can always be withdrawn
+    pub fn process_revoke_lock_delegate(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let program_state_account = next_account_info(accounts_iter)?;
+        let locking_account = next_account_info(accounts_iter)?;
+        let destination_token_account = next_account_info(accounts_iter)?;
+        let destination_token_account_owner = next_account_info(accounts_iter)?;
+
+        let (_, mut state, header_len) = Self::load_lock_as_beneficiary(
+            program_id,
+            program_state_account,
+            locking_account,
+            destination_token_account,
+            destination_token_account_owner,
+            seeds,
+        )?;
+
+        if !state.has_delegate() {
+            msg!("The lock has no delegate");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        state.delegate_address = Pubkey::default();
+        state.pack_into_slice(&mut locking_account.data.borrow_mut()[..header_len]);
+
+        Ok(())
+    }
+
+    // Moves a locking account with an older header to the current layout. The schedules are shifted
+    // behind the longer header and the payer covers the extra rent.
+    pub fn process_migrate_lock(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let system_program_account = next_account_info(accounts_iter)?;
+        let payer = next_account_info(accounts_iter)?;
+        let locking_account = next_account_info(accounts_iter)?;
+
+        assert_distinct_accounts(&[
+            ("payer", payer.key),
+            ("locking account", locking_account.key),
+        ])?;
+
+        assert_writable(payer, "payer")?;
+        assert_writable(locking_account, "locking account")?;
+
+        assert_lock(program_id, locking_account, seeds)?;
+
+        if !payer.is_signer {
+            msg!("The payer should sign, either directly or through invoke_signed of the calling program");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let old_len = locking_account.data_len();
+        let header_len = LockScheduleHeader::header_len(old_len);
+        if header_len == LockScheduleHeader::LEN {
+            msg!("The locking account already has the current layout");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if old_len < header_len {
+            return Err(ProgramError::InvalidAccountData)
+        }
+
+        let mut header = {
+            let data = locking_account.data.borrow();
+            let mut header = LockScheduleHeader::unpack_from_slice(&data[..header_len])?;
+            if !header.is_initialized {
+                msg!("The locking account has no lock to migrate");
+                return Err(ProgramError::UninitializedAccount);
+            }
+            // What was claimed before the totals were added is unknown for schedules, so the totals start
+            // from what is still locked. Vesting locks keep their own totals.
+            if header.is_vesting {
+                let vesting = VestingSchedule::unpack_from_slice(&data[header_len..])?;
+                header.total_amount = vesting.total_amount;
+                header.claimed_amount = vesting.claimed_amount;
+            } else if header_len < LockScheduleHeader::V3_LEN {
+                // V3_LEN headers already count their schedules and only gain the emergency unlock fields
+                let schedules = unpack_schedules(&data[header_len..])?;
+                // Unlock relies on the schedules of the current layout being sorted
+                if schedules.windows(2).any(|pair| pair[0].release_time > pair[1].release_time) {
+                    msg!("Locks with unsorted schedules keep their layout");
+                    return Err(LockTokenError::SchedulesNotSorted.into());
+                }
+                if header_len < LockScheduleHeader::V2_LEN {
+                    header.total_amount = schedules
+                        .iter()
+                        .try_fold(0u64, |total, s| total.checked_add(s.amount))
+                        .ok_or(LockTokenError::MathOverflow)?;
+                    header.claimed_amount = 0;
+                }
+                // Unlocked schedules are zeroed from the first one on
+                header.schedule_count = schedules.len() as u32;
+                header.unlocked_count = schedules.iter().take_while(|s| s.amount == 0).count() as u32;
+            }
+            header
+        };
+
+        // Native locks may hold unwrapped lamports on top of the rent, so only the difference is paid
+        let new_len = old_len - header_len + LockScheduleHeader::LEN;
+        let rent = Rent::get()?;
+        let extra_rent = rent
+            .minimum_balance(new_len)
+            .saturating_sub(rent.minimum_balance(old_len));
+        invoke(
+            &transfer_sol(payer.key, locking_account.key, extra_rent),
+            &[
+                payer.clone(),
+                locking_account.clone(),
+                system_program_account.clone(),
+            ],
+        )?;
+
+        locking_account.realloc(new_len, false)?;
+        let mut data = locking_account.data.borrow_mut();
+        data.copy_within(header_len..old_len, LockScheduleHeader::LEN);
+        header.pack_into_slice(&mut data[..LockScheduleHeader::LEN]);
+
+        Ok(())
+    }
+
+    // Only the admin schedules a rescue, which waits EMERGENCY_UNLOCK_DELAY_SECS so the beneficiary
+    // can still cancel it, see process_cancel_emergency_unlock
+    pub fn process_schedule_emergency_unlock(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+        rescue_destination: &Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let program_admin_account = next_account_info(accounts_iter)?;
+        let program_state_account = next_account_info(accounts_iter)?;
+        let locking_account = next_account_info(accounts_iter)?;
+
+        assert_writable(locking_account, "locking account")?;
+
+        Self::load_global_state_as_admin(program_id, program_admin_account, program_state_account, accounts_iter.as_slice())?;
+
+        assert_lock(program_id, locking_account, seeds)?;
+        let locking_account_key = *locking_account.key;
+
+        let header_len = LockScheduleHeader::header_len(locking_account.data_len());
+        if header_len < LockScheduleHeader::V4_LEN {
+            msg!("The locking account was created before emergency unlocks and has to be migrated first");
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+
+        let mut state = LockScheduleHeader::unpack_from_slice(&locking_account.data.borrow()[..header_len])?;
+        if !state.is_initialized {
+            msg!("The locking account has no lock to rescue");
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        Self::assert_mutable_lock(&state)?;
+
+        if state.claimed_amount >= state.total_amount {
+            msg!("Everything the lock held has already been unlocked");
+            return Err(LockTokenError::LockFullyUnlocked.into());
+        }
+
+        if *rescue_destination == state.destination_address || *rescue_destination == Pubkey::default() {
+            msg!("Invalid rescue destination {}", rescue_destination);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let now = now_u64(&Clock::get()?)?;
+        state.rescue_destination_address = *rescue_destination;
+        state.rescue_execute_after = now
+            .checked_add(EMERGENCY_UNLOCK_DELAY_SECS)
+            .ok_or(LockTokenError::MathOverflow)?;
+        state.pack_into_slice(&mut locking_account.data.borrow_mut()[..header_len]);
+
+        emit(&EmergencyUnlockScheduled {
+            locking_account: locking_account_key,
+            destination: state.destination_address,
+            rescue_destination: state.rescue_destination_address,
+            execute_after: state.rescue_execute_after,
+        });
+
+        Ok(())
+    }
+
+    // Anyone can execute a rescue once its delay has passed. Whatever the locking token account holds goes
+    // to the rescue destination and the lock is left fully unlocked.
+    pub fn process_execute_emergency_unlock(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let spl_token_account = next_account_info(accounts_iter)?;
+        let program_state_account = next_account_info(accounts_iter)?;
+        let locking_account = next_account_info(accounts_iter)?;
+        let locking_token_account = next_account_info(accounts_iter)?;
+        let rescue_destination_token_account = next_account_info(accounts_iter)?;
+        let mint_account = next_account_info(accounts_iter)?;
+        let token_state_account = next_account_info(accounts_iter)?;
+
+        assert_distinct_accounts(&[
+            ("program state account", program_state_account.key),
+            ("locking account", locking_account.key),
+            ("locking token account", locking_token_account.key),
+            ("rescue destination token account", rescue_destination_token_account.key),
+            ("token state account", token_state_account.key),
+        ])?;
+
+        assert_writable(locking_account, "locking account")?;
+        assert_writable(locking_token_account, "locking token account")?;
+        assert_writable(rescue_destination_token_account, "rescue destination token account")?;
+
+        assert_global_state(program_id, program_state_account)?;
+
+        assert_unlock_not_paused(program_state_account)?;
+
+        let lock_signer = assert_lock(program_id, locking_account, seeds)?;
+        let locking_account_key = *locking_account.key;
+
+        assert_token_program(spl_token_account)?;
+
+        // Only headers of the current layout can hold a rescue
+        let header_len = LockScheduleHeader::header_len(locking_account.data_len());
+        let mut state = LockScheduleHeader::unpack_from_slice(&locking_account.data.borrow()[..header_len])?;
+        if !state.has_pending_rescue() {
+            msg!("There is no pending emergency unlock of the lock");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        if state.rescue_destination_address != *rescue_destination_token_account.key {
+            msg!("Provided destination account is not the rescue destination");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let now = now_u64(&Clock::get()?)?;
+        if !is_matured(state.rescue_execute_after, now) {
+            msg!("The emergency unlock can not be executed before {}", state.rescue_execute_after);
+            return Err(LockTokenError::EmergencyUnlockTimelock.into());
+        }
+
+        let locking_token_account_data = unpack_token_account(locking_token_account, spl_token_account.key)?;
+        assert_valid_vault(&locking_token_account_data, &locking_account_key, &state.mint_address)?;
+
+        let rescue_destination_token_account_data =
+            unpack_token_account(rescue_destination_token_account, spl_token_account.key)?;
+        assert_valid_destination(&rescue_destination_token_account_data, &state.mint_address)?;
+
+        if state.mint_address != *mint_account.key {
+            msg!("Provided mint account does not match the locked mint");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let mint = unpack_mint(mint_account, spl_token_account.key)?;
+
+        // Every schedule counts as unlocked from now on
+        let remaining_locked = state.total_amount.saturating_sub(state.claimed_amount);
+        {
+            let mut data = locking_account.data.borrow_mut();
+            if state.is_vesting {
+                let mut vesting = VestingSchedule::unpack_from_slice(&data[header_len..])?;
+                vesting.claimed_amount = vesting.total_amount;
+                vesting.pack_into_slice(&mut data[header_len..]);
+            } else if state.is_multi {
+                for record in data[header_len..].chunks_exact_mut(MultiLockSchedule::LEN) {
+                    let s = MultiLockSchedule::unpack_from_slice(record)?;
+                    MultiLockSchedule { amount: 0, ..s }.pack_into_slice(record);
+                }
+                state.unlocked_count = state.schedule_count;
+            } else {
+                let schedules = PodLockSchedule::try_cast_slice_mut(&mut data[header_len..])?;
+                for s in schedules.iter_mut() {
+                    s.amount = PodU64::from(0);
+                }
+                state.unlocked_count = state.schedule_count;
+            }
+        }
+
+        let old_destination = state.destination_address;
+        state.claimed_amount = state.total_amount;
+        state.split_destinations = Default::default();
+        state.set_destination(state.rescue_destination_address);
+        state.pending_destination_address = Pubkey::default();
+        state.clear_pending_rescue();
+        state.pack_into_slice(&mut locking_account.data.borrow_mut()[..header_len]);
+
+        if state.is_registered {
+            let token_state_account_key = token_state_address(program_id, &state.mint_address)?;
+            if token_state_account_key != *token_state_account.key {
+                msg!("Provided token state account is invalid");
+                return Err(ProgramError::InvalidArgument);
+            }
+            assert_writable(token_state_account, "token state account")?;
+
+            let mut token_state_data = TokenState::unpack_from_slice(&token_state_account.data.borrow())?;
+            token_state_data.total_locked_amount = token_state_data
+                .total_locked_amount
+                .checked_sub(remaining_locked)
+                .ok_or(LockTokenError::MathOverflow)?;
+            token_state_data.pack_into_slice(&mut token_state_account.data.borrow_mut());
+        }
+
+        let amount = locking_token_account_data.amount;
+        if amount > 0 {
+            let transfer_tokens_from_locking_account = transfer_checked_instruction(
+                spl_token_account.key,
+                locking_token_account.key,
+                mint_account.key,
+                rescue_destination_token_account.key,
+                &locking_account_key,
+                &[],
+                amount,
+                mint.decimals,
+            )?;
+
+            let signer_seeds = lock_signer.signed_seeds_for(locking_account.key, program_id)?;
+            invoke_signed(
+                &transfer_tokens_from_locking_account,
+                &[
+                    spl_token_account.clone(),
+                    locking_token_account.clone(),
+                    mint_account.clone(),
+                    rescue_destination_token_account.clone(),
+                    locking_account.clone(),
+                ],
+                &[&signer_seeds],
+            )?;
+        }
+
+        emit(&EmergencyUnlockExecuted {
+            locking_account: locking_account_key,
+            old_destination,
+            rescue_destination: state.destination_address,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    // Not blocked by the pause, so the beneficiary can always stop a rescue
+    pub fn process_cancel_emergency_unlock(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let program_state_account = next_account_info(accounts_iter)?;
+        let locking_account = next_account_info(accounts_iter)?;
+        let destination_token_account = next_account_info(accounts_iter)?;
+        let destination_token_account_owner = next_account_info(accounts_iter)?;
+
+        let (_, mut state, header_len) = Self::load_lock_as_beneficiary(
+            program_id,
+            program_state_account,
+            locking_account,
+            destination_token_account,
+            destination_token_account_owner,
+            seeds,
+        )?;
+
+        if !state.has_pending_rescue() {
+            msg!("There is no pending emergency unlock of the lock");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        emit(&EmergencyUnlockCancelled {
+            locking_account: *locking_account.key,
+            rescue_destination: state.rescue_destination_address,
+        });
+
+        state.clear_pending_rescue();
+        state.pack_into_slice(&mut locking_account.data.borrow_mut()[..header_len]);
+
+        Ok(())
+    }
+
+    // What an Unlock would release now. Read-only, so it neither checks the pause flags nor needs the program state
+    pub fn process_get_claimable_amount(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let locking_account = next_account_info(accounts_iter)?;
+
+        assert_lock(program_id, locking_account, seeds)?;
+        let locking_account_key = *locking_account.key;
+
+        let data = locking_account.try_borrow_data()?;
+        let header_len = LockScheduleHeader::header_len(data.len());
+        let header = LockScheduleHeader::unpack_from_slice(data.get(..header_len).ok_or(ProgramError::InvalidAccountData)?)?;
+        if !header.is_initialized {
+            msg!("The locking account has no lock");
+            return Err(ProgramError::UninitializedAccount);
+        }
+        let now = header.time_basis.now(&Clock::get()?)?;
+        let amount = claimable_amount(&header, &data[header_len..], now)?;
+
+        emit(&ClaimableAmount {
+            locking_account: locking_account_key,
+            amount,
+            now,
+        });
+        set_return_data(&amount.to_le_bytes());
+
+        Ok(())
+    }
+
+    // Checks the locking account and that the authority is the creator of the lock or the owner of its destination.
+    // Returns the key of the locking account.
+    fn load_lock_as_describer(
+        program_id: &Pubkey,
+        locking_account: &AccountInfo,
+        destination_token_account: &AccountInfo,
+        authority: &AccountInfo,
+        seeds: LockSeed,
+    ) -> Result<Pubkey, ProgramError> {
+        assert_lock(program_id, locking_account, seeds)?;
+        let locking_account_key = *locking_account.key;
+
+        let data = locking_account.try_borrow_data()?;
+        let header_len = LockScheduleHeader::header_len(data.len());
+        let header = LockScheduleHeader::unpack_from_slice(data.get(..header_len).ok_or(ProgramError::InvalidAccountData)?)?;
+        if !header.is_initialized {
+            msg!("The locking account has no lock");
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        if !authority.is_signer {
+            msg!("The creator of the lock or the owner of its destination should sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // Locks created before the creator was recorded hold the default key
+        if header.creator_address != Pubkey::default() && header.creator_address == *authority.key {
+            return Ok(locking_account_key);
+        }
+
+        if header.destination_address != *destination_token_account.key {
+            msg!("Contract destination account does not matched provided account");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        // A native lock paying out lamports to a system account is controlled by that account, see process_unlock
+        let destination_owner = if header.mint_address == native_mint::id()
+            && *destination_token_account.owner == system_program::id()
+        {
+            *destination_token_account.key
+        } else {
+            // The lock pins the address, so either token program may own it
+            unpack_token_account(destination_token_account, destination_token_account.owner)?.owner
+        };
+
+        if destination_owner != *authority.key {
+            msg!("Only the creator of the lock or the owner of its destination can describe it");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        Ok(locking_account_key)
+    }
+
+    // Checks the metadata account is the one of the lock and returns its bump seed
+    fn assert_lock_metadata_address(
+        program_id: &Pubkey,
+        locking_account_key: &Pubkey,
+        lock_metadata_account: &AccountInfo,
+    ) -> Result<u8, ProgramError> {
+        let (lock_metadata_key, bump_seed) = find_lock_metadata_address(program_id, locking_account_key);
+        if lock_metadata_key != *lock_metadata_account.key {
+            msg!("Provided lock metadata account is invalid");
+            return Err(ProgramError::InvalidArgument);
+        }
+        Ok(bump_seed)
+    }
+
+    // Sums what the given locking token accounts of the mint hold into its token state, see
+    // LockTokenInstruction::SnapshotMintLocked
+    pub fn process_snapshot_mint_locked(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        mint_address: &Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let spl_token_account = next_account_info(accounts_iter)?;
+        let system_program_account = next_account_info(accounts_iter)?;
+        let payer = next_account_info(accounts_iter)?;
+        let token_state_account = next_account_info(accounts_iter)?;
+        let escrow_accounts = accounts_iter.as_slice();
+
+        assert_token_program(spl_token_account)?;
+        assert_system_program(system_program_account)?;
+        assert_writable(token_state_account, "token state account")?;
+
+        if escrow_accounts.len() % 2 != 0 {
+            msg!("Every locking token account has to follow its locking account");
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+
+        let token_state_account_key = token_state_address(program_id, mint_address)?;
+        if token_state_account_key != *token_state_account.key || *token_state_account.owner != *program_id {
+            msg!("Provided token state account is invalid");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let mut token_state_data = TokenState::unpack_from_slice(&token_state_account.data.borrow())?;
+        if !token_state_data.is_initialized {
+            msg!("The token state is uninitialized");
+            return Err(ProgramError::UninitializedAccount);
+        }
+        if token_state_data.mint_address != *mint_address {
+            msg!("Provided token state account is invalid");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let (program_state_account_key, _) = find_global_state_address(program_id);
+        let mut amount: u64 = 0;
+        for (index, pair) in escrow_accounts.chunks_exact(2).enumerate() {
+            let (locking_account, locking_token_account) = (&pair[0], &pair[1]);
+
+            // Every escrow is counted once
+            let is_repeated = escrow_accounts[..index * 2]
+                .iter()
+                .any(|account| account.key == locking_account.key || account.key == locking_token_account.key);
+            if is_repeated || locking_account.key == locking_token_account.key {
+                msg!("The locking account {} is passed more than once", locking_account.key);
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            // Only the program can create accounts it owns, and of those only locks have an initialized header.
+            // The global state is ruled out by address as its bytes could pass for a header.
+            let is_lock = *locking_account.owner == *program_id
+                && *locking_account.key != program_state_account_key
+                && LockScheduleHeader::is_packed_initialized(&locking_account.try_borrow_data()?);
+            if !is_lock {
+                msg!("The account {} is not a locking account", locking_account.key);
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            let header = {
+                let data = locking_account.data.borrow();
+                let header_len = LockScheduleHeader::header_len(data.len());
+                LockScheduleHeader::unpack_from_slice(data.get(..header_len).ok_or(ProgramError::InvalidAccountData)?)?
+            };
+            if header.mint_address != *mint_address {
+                msg!("The locking account {} locks another mint", locking_account.key);
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            let vault = unpack_token_account(locking_token_account, spl_token_account.key)?;
+            assert_valid_vault(&vault, locking_account.key, mint_address)?;
+
+            amount = amount.checked_add(vault.amount).ok_or(LockTokenError::MathOverflow)?;
+        }
+
+        if token_state_account.data_len() < TokenState::LEN {
+            if !payer.is_signer {
+                msg!("The payer should be a signer");
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            Self::grow_token_state(token_state_account, payer, system_program_account, &Rent::get()?)?;
+        }
+
+        token_state_data.last_snapshot_amount = amount;
+        token_state_data.last_snapshot_ts = Clock::get()?.unix_timestamp;
+        token_state_data.pack_into_slice(&mut token_state_account.data.borrow_mut());
+
+        Ok(())
+    }
+
+    // Creates the metadata account of the lock on first use, then overwrites its name and URI
+    pub fn process_set_lock_metadata(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+        name: String,
+        uri: String,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let locking_account = next_account_info(accounts_iter)?;
+        let lock_metadata_account = next_account_info(accounts_iter)?;
+        let destination_token_account = next_account_info(accounts_iter)?;
+        let authority = next_account_info(accounts_iter)?;
+        let payer = next_account_info(accounts_iter)?;
+        let system_program_account = next_account_info(accounts_iter)?;
+
+        assert_distinct_accounts(&[
+            ("locking account", locking_account.key),
+            ("lock metadata account", lock_metadata_account.key),
+            ("payer", payer.key),
+        ])?;
+
+        assert_system_program(system_program_account)?;
+        assert_writable(lock_metadata_account, "lock metadata account")?;
+        LockMetadata::assert_valid_lengths(&name, &uri)?;
+
+        let locking_account_key =
+            Self::load_lock_as_describer(program_id, locking_account, destination_token_account, authority, seeds)?;
+        let bump_seed = Self::assert_lock_metadata_address(program_id, &locking_account_key, lock_metadata_account)?;
+
+        if *lock_metadata_account.owner == *program_id {
+            let metadata = LockMetadata::unpack(&lock_metadata_account.data.borrow())?;
+            if metadata.locking_account != locking_account_key {
+                msg!("The lock metadata account describes another lock");
+                return Err(ProgramError::InvalidAccountData);
+            }
+        } else {
+            if !payer.is_signer {
+                msg!("The payer should sign");
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            let rent = Rent::get()?;
+            let signer_seeds: &[&[u8]] = &[LOCK_METADATA_SEED, locking_account_key.as_ref(), &[bump_seed]];
+            Self::create_program_account(
+                program_id,
+                system_program_account,
+                payer,
+                lock_metadata_account,
+                &rent,
+                LockMetadata::LEN,
+                signer_seeds,
+            )?;
+        }
+
+        let metadata = LockMetadata {
+            locking_account: locking_account_key,
+            name,
+            uri,
+            is_initialized: true,
+        };
+        LockMetadata::pack(metadata, &mut lock_metadata_account.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    // Deletes the metadata account of the lock, the lock itself is left as it is
+    pub fn process_close_lock_metadata(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let locking_account = next_account_info(accounts_iter)?;
+        let lock_metadata_account = next_account_info(accounts_iter)?;
+        let destination_token_account = next_account_info(accounts_iter)?;
+        let authority = next_account_info(accounts_iter)?;
+        let receiver = next_account_info(accounts_iter)?;
+
+        assert_distinct_accounts(&[
+            ("locking account", locking_account.key),
+            ("lock metadata account", lock_metadata_account.key),
+            ("receiver", receiver.key),
+        ])?;
+
+        assert_writable(lock_metadata_account, "lock metadata account")?;
+        assert_writable(receiver, "receiver")?;
+
+        let locking_account_key =
+            Self::load_lock_as_describer(program_id, locking_account, destination_token_account, authority, seeds)?;
+        Self::assert_lock_metadata_address(program_id, &locking_account_key, lock_metadata_account)?;
+
+        if *lock_metadata_account.owner != *program_id {
+            msg!("The lock has no metadata");
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        let lamports = lock_metadata_account.lamports();
+        **receiver.try_borrow_mut_lamports()? = receiver
+            .lamports()
+            .checked_add(lamports)
+            .ok_or(LockTokenError::MathOverflow)?;
+        **lock_metadata_account.try_borrow_mut_lamports()? = 0;
+        lock_metadata_account.data.borrow_mut().fill(0);
+
+        Ok(())
+    }
+
+    // Tokens sent straight to the locking token account are not part of any schedule. Whatever the vault holds
+    // on top of what the schedules still release goes back to the creator of the lock. Released schedules are
+    // zeroed when unlocked, so summing every schedule gives what the vault still owes however far the lock is drained.
+    pub fn process_sweep_excess(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let spl_token_account = next_account_info(accounts_iter)?;
+        let program_state_account = next_account_info(accounts_iter)?;
+        let locking_account = next_account_info(accounts_iter)?;
+        let locking_token_account = next_account_info(accounts_iter)?;
+        let creator_token_account = next_account_info(accounts_iter)?;
+        let mint_account = next_account_info(accounts_iter)?;
+
+        assert_distinct_accounts(&[
+            ("program state account", program_state_account.key),
+            ("locking account", locking_account.key),
+            ("locking token account", locking_token_account.key),
+            ("creator token account", creator_token_account.key),
+        ])?;
+
+        assert_writable(locking_token_account, "locking token account")?;
+        assert_writable(creator_token_account, "creator token account")?;
+
+        assert_global_state(program_id, program_state_account)?;
+
+        assert_unlock_not_paused(program_state_account)?;
+
+        let lock_signer = assert_lock(program_id, locking_account, seeds)?;
+        let locking_account_key = *locking_account.key;
+
+        assert_token_program(spl_token_account)?;
+
+        let (header, remaining_locked) = {
+            let data = locking_account.data.borrow();
+            let header_len = LockScheduleHeader::header_len(data.len());
+            let header = LockScheduleHeader::unpack_from_slice(data.get(..header_len).ok_or(ProgramError::InvalidAccountData)?)?;
+            if !header.is_initialized {
+                msg!("The locking account has no lock");
+                return Err(ProgramError::UninitializedAccount);
+            }
+            if header.creator_address == Pubkey::default() {
+                msg!("The lock was created before its creator was recorded");
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            let remaining_locked = if header.is_vesting {
+                let vesting = VestingSchedule::unpack_from_slice(&data[header_len..])?;
+                vesting
+                    .total_amount
+                    .checked_sub(vesting.claimed_amount)
+                    .ok_or(LockTokenError::MathOverflow)?
+            } else {
+                unpack_lock_schedules(&header, &data[header_len..])?
+                    .iter()
+                    .try_fold(0u64, |total, s| total.checked_add(s.amount))
+                    .ok_or(LockTokenError::MathOverflow)?
+            };
+            (header, remaining_locked)
+        };
+
+        let locking_token_account_data = unpack_token_account(locking_token_account, spl_token_account.key)?;
+        assert_valid_vault(&locking_token_account_data, &locking_account_key, &header.mint_address)?;
+
+        let creator_token_account_data = unpack_token_account(creator_token_account, spl_token_account.key)?;
+        assert_valid_destination(&creator_token_account_data, &header.mint_address)?;
+        if creator_token_account_data.owner != header.creator_address {
+            msg!("The token account is not owned by the creator of the lock {}", header.creator_address);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        if header.mint_address != *mint_account.key {
+            msg!("Provided mint account does not match the locked mint");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let mint = unpack_mint(mint_account, spl_token_account.key)?;
+
+        let excess = locking_token_account_data
+            .amount
+            .checked_sub(remaining_locked)
+            .ok_or(LockTokenError::MathOverflow)?;
+        if excess == 0 {
+            msg!("The locking token account holds no more than the {} still locked", remaining_locked);
+            return Err(LockTokenError::NoExcessTokens.into());
+        }
+
+        let transfer_excess_to_creator = transfer_checked_instruction(
+            spl_token_account.key,
+            locking_token_account.key,
+            mint_account.key,
+            creator_token_account.key,
+            &locking_account_key,
+            &[],
+            excess,
+            mint.decimals,
+        )?;
+
+        let signer_seeds = lock_signer.signed_seeds_for(locking_account.key, program_id)?;
+        invoke_signed(
+            &transfer_excess_to_creator,
+            &[
+                spl_token_account.clone(),
+                locking_token_account.clone(),
+                mint_account.clone(),
+                creator_token_account.clone(),
+                locking_account.clone(),
+            ],
+            &[&signer_seeds],
+        )?;
+        msg!("Swept {} excess tokens to the creator", excess);
+
+        Ok(())
+    }
+
+    pub fn process_extend_lock_duration(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+        index: u32,
+        release_time: u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let program_state_account = next_account_info(accounts_iter)?;
+        let locking_account = next_account_info(accounts_iter)?;
+        let destination_token_account = next_account_info(accounts_iter)?;
+        let destination_token_account_owner = next_account_info(accounts_iter)?;
+
+        assert_distinct_accounts(&[
+            ("program state account", program_state_account.key),
+            ("locking account", locking_account.key),
+            ("destination token account", destination_token_account.key),
+            ("destination token account owner", destination_token_account_owner.key),
+        ])?;
+
+        assert_writable(locking_account, "locking account")?;
+
+        let program_global_state = load_global_state(program_id, program_state_account)?;
+
+        assert_not_paused(&program_global_state, PAUSE_TRANSFER)?;
+
+        let (_, header, header_len) = load_lock(program_id, locking_account, seeds)?;
+        let locking_account_key = *locking_account.key;
+        if locking_account.data_len() < header_len + LockSchedule::LEN * (index as usize + 1) {
+            return Err(ProgramError::InvalidAccountData)
+        }
+        Self::assert_schedules_lock(&header)?;
+        Self::assert_mutable_lock(&header)?;
+        Self::assert_single_destination_lock(&header)?;
+        let state = LockSchedule::unpack(
+            &locking_account.data.borrow()[(header_len + LockSchedule::LEN * index as usize)..(header_len + LockSchedule::LEN * (index as usize + 1))],
+        )?;
+
+        header.time_basis.assert_release_time(release_time)?;
+
+        if state.release_time > release_time {
+            msg!("Can not set shorter release time.");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let now = header.time_basis.now(&Clock::get()?)?;
+        Self::assert_max_lock_duration(&program_global_state, release_time, header.time_basis, now)?;
+
+        // Locks whose schedules are sorted by release time have to stay sorted, which is checked over every
+        // schedule with the extension applied. Legacy locks created with unsorted schedules are left as they are.
+        let mut schedules = unpack_schedules(&locking_account.data.borrow()[header_len..])?;
+        let was_sorted = schedules
+            .windows(2)
+            .all(|pair| pair[0].release_time <= pair[1].release_time);
+        schedules[index as usize].release_time = release_time;
+        if was_sorted {
+            Self::assert_sorted_by_release_time(&schedules)?;
+        }
+
+        if !destination_token_account_owner.is_signer {
+            msg!("Destination token account owner should be a signer.");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let destination_token_account = Account::unpack(&destination_token_account.data.borrow())?;
+
+        if destination_token_account.owner != *destination_token_account_owner.key {
+            msg!("The current destination token account isn't owned by the provided owner");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        emit(&LockExtended {
+            locking_account: locking_account_key,
+            index,
+            old_release_time: state.release_time,
+            new_release_time: release_time,
+        });
+
+        let mut new_state = state;
+        new_state.release_time = release_time;
+        new_state
+            .pack_into_slice(&mut locking_account.data.borrow_mut()[(header_len + LockSchedule::LEN * index as usize)..(header_len + LockSchedule::LEN * (index as usize + 1))]);
+
+        Ok(())
+    }
+
+    pub fn process_extend_lock_durations(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+        extensions: &[(u32, u64)],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let program_state_account = next_account_info(accounts_iter)?;
+        let locking_account = next_account_info(accounts_iter)?;
+        let destination_token_account = next_account_info(accounts_iter)?;
+        let destination_token_account_owner = next_account_info(accounts_iter)?;
+
+        let (program_global_state, header, header_len) = Self::load_lock_as_beneficiary(
+            program_id,
+            program_state_account,
+            locking_account,
+            destination_token_account,
+            destination_token_account_owner,
+            seeds,
+        )?;
+
+        assert_not_paused(&program_global_state, PAUSE_TRANSFER)?;
+
+        Self::assert_schedules_lock(&header)?;
+        Self::assert_mutable_lock(&header)?;
+        Self::assert_single_destination_lock(&header)?;
+
+        if extensions.is_empty() {
+            msg!("No schedule to extend");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let now = header.time_basis.now(&Clock::get()?)?;
+
+        // Every extension is applied to a copy first, so nothing is written when one of them is invalid
+        let mut schedules = unpack_schedules(&locking_account.data.borrow()[header_len..])?;
+        let was_sorted = schedules
+            .windows(2)
+            .all(|pair| pair[0].release_time <= pair[1].release_time);
+        let mut is_extended = vec![false; schedules.len()];
+        let mut events = Vec::with_capacity(extensions.len());
+
+        for &(index, release_time) in extensions.iter() {
+            let schedule = schedules.get_mut(index as usize).ok_or_else(|| {
+                msg!("Schedule {} does not exist", index);
+                ProgramError::InvalidArgument
+            })?;
+
+            if is_extended[index as usize] {
+                msg!("Schedule {} is extended more than once", index);
+                return Err(ProgramError::InvalidArgument);
+            }
+            is_extended[index as usize] = true;
+
+            header.time_basis.assert_release_time(release_time)?;
+
+            if schedule.release_time > release_time {
+                msg!("Can not set shorter release time.");
+                return Err(ProgramError::InvalidArgument);
+            }
+            Self::assert_max_lock_duration(&program_global_state, release_time, header.time_basis, now)?;
+            events.push(LockExtended {
+                locking_account: *locking_account.key,
+                index,
+                old_release_time: schedule.release_time,
+                new_release_time: release_time,
+            });
+            schedule.release_time = release_time;
+        }
+
+        // Locks whose schedules are sorted by release time have to stay sorted.
+        // Legacy locks created with unsorted schedules are left as they are.
+        if was_sorted {
+            Self::assert_sorted_by_release_time(&schedules)?;
+        }
+
+        let mut data = locking_account.data.borrow_mut();
+        for (index, schedule) in schedules.iter().enumerate() {
+            if is_extended[index] {
+                let offset = header_len + LockSchedule::LEN * index;
+                schedule.pack_into_slice(&mut data[offset..offset + LockSchedule::LEN]);
+            }
+        }
+
+        for event in events.iter() {
+            emit(event);
+        }
+
+        Ok(())
+    }
+
+    fn assert_sorted_by_release_time(schedules: &[LockSchedule]) -> ProgramResult {
+        if let Some(index) = schedules
+            .windows(2)
+            .position(|pair| pair[0].release_time > pair[1].release_time)
+        {
+            msg!("Schedule {} can not be released after schedule {}", index, index + 1);
+            return Err(LockTokenError::WouldViolateOrdering.into());
+        }
+        Ok(())
+    }
+
+    pub fn process_relock(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+        index: u32,
+        new_release_time: u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let program_state_account = next_account_info(accounts_iter)?;
+        let locking_account = next_account_info(accounts_iter)?;
+        let destination_token_account = next_account_info(accounts_iter)?;
+        let destination_token_account_owner = next_account_info(accounts_iter)?;
+
+        let (program_global_state, header, header_len) = Self::load_lock_as_beneficiary(
+            program_id,
+            program_state_account,
+            locking_account,
+            destination_token_account,
+            destination_token_account_owner,
+            seeds,
+        )?;
+
+        assert_not_paused(&program_global_state, PAUSE_TRANSFER)?;
+
+        Self::assert_schedules_lock(&header)?;
+        Self::assert_mutable_lock(&header)?;
+        Self::assert_single_destination_lock(&header)?;
+
+        let now = header.time_basis.now(&Clock::get()?)?;
+        let mut schedules = unpack_schedules(&locking_account.data.borrow()[header_len..])?;
+        let was_sorted = schedules
+            .windows(2)
+            .all(|pair| pair[0].release_time <= pair[1].release_time);
+
+        let schedule = schedules.get_mut(index as usize).ok_or_else(|| {
+            msg!("Schedule {} does not exist", index);
+            ProgramError::InvalidArgument
+        })?;
+
+        if !is_matured(schedule.release_time, now) || schedule.amount == 0 {
+            msg!("Only released schedules which have not been unlocked yet can be relocked");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        // A schedule released at the current time is already matured, so the new one has to be later
+        header.time_basis.assert_future(new_release_time, now)?;
+        if is_matured(new_release_time, now) {
+            msg!("The release time {} is not after the current time", new_release_time);
+            return Err(LockTokenError::ReleaseTimeInPast.into());
+        }
+        Self::assert_max_lock_duration(&program_global_state, new_release_time, header.time_basis, now)?;
+
+        let old_release_time = schedule.release_time;
+        schedule.release_time = new_release_time;
+
+        // Unlock stops at the first schedule still locked, so sorted schedules have to stay sorted.
+        // The sort is stable and the schedules before the relocked one matured earlier, so it lands
+        // right after every schedule released before the new time.
+        let mut new_index = index;
+        if was_sorted {
+            schedules.sort_by_key(|s| s.release_time);
+            new_index = schedules
+                .iter()
+                .filter(|s| s.release_time < new_release_time)
+                .count() as u32;
+        }
+
+        let mut data = locking_account.data.borrow_mut();
+        for (offset, schedule) in (header_len..).step_by(LockSchedule::LEN).zip(schedules.iter()) {
+            schedule.pack_into_slice(&mut data[offset..offset + LockSchedule::LEN]);
+        }
+
+        emit(&LockExtended {
+            locking_account: *locking_account.key,
+            index: new_index,
+            old_release_time,
+            new_release_time,
+        });
+
+        Ok(())
+    }
+
+    pub fn process_transfer_schedule_indices(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seeds: LockSeed,
+        new_seeds: LockSeed,
+        indices: &[u32],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let spl_token_account = next_account_info(accounts_iter)?;
+        let program_state_account = next_account_info(accounts_iter)?;
+        let locking_account = next_account_info(accounts_iter)?;
+        let locking_token_account = next_account_info(accounts_iter)?;
+        let destination_token_account = next_account_info(accounts_iter)?;
+        let destination_token_account_owner = next_account_info(accounts_iter)?;
+        let new_locking_account = next_account_info(accounts_iter)?;
+        let new_locking_token_account = next_account_info(accounts_iter)?;
+        let new_destination_token_account = next_account_info(accounts_iter)?;
+        let mint_account = next_account_info(accounts_iter)?;
+
+        let (program_global_state, mut header, header_len) = Self::load_lock_as_beneficiary(
+            program_id,
+            program_state_account,
+            locking_account,
+            destination_token_account,
+            destination_token_account_owner,
+            seeds,
+        )?;
+
+        assert_not_paused(&program_global_state, PAUSE_TRANSFER)?;
+
+        assert_distinct_accounts(&[
+            ("locking account", locking_account.key),
+            ("locking token account", locking_token_account.key),
+            ("new locking account", new_locking_account.key),
+            ("new locking token account", new_locking_token_account.key),
+        ])?;
+        assert_writable(locking_token_account, "locking token account")?;
+        assert_writable(new_locking_account, "new locking account")?;
+        assert_writable(new_locking_token_account, "new locking token account")?;
+
+        Self::assert_schedules_lock(&header)?;
+        Self::assert_mutable_lock(&header)?;
+        Self::assert_single_destination_lock(&header)?;
+        if header.has_split() {
+            msg!("The releases of a split lock are shared by its destinations");
+            return Err(LockTokenError::InvalidSplit.into());
+        }
+        // The vault of a native lock can already be closed into the locking account, see unlock_matured
+        if header.mint_address == native_mint::id() {
+            msg!("Locks of native SOL can not be divided");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        if indices.is_empty() {
+            msg!("No schedule to transfer");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        // Every index is checked on a copy of the schedules before anything is written or transferred
+        let now = header.time_basis.now(&Clock::get()?)?;
+        let mut schedules = unpack_schedules(&locking_account.data.borrow()[header_len..])?;
+        let mut is_moved = vec![false; schedules.len()];
+        let mut moved = Vec::with_capacity(indices.len());
+
+        for &index in indices.iter() {
+            let schedule = schedules.get_mut(index as usize).ok_or_else(|| {
+                msg!("Schedule {} does not exist", index);
+                ProgramError::InvalidArgument
+            })?;
+
+            if is_moved[index as usize] {
+                msg!("Schedule {} is transferred more than once", index);
+                return Err(ProgramError::InvalidArgument);
+            }
+            is_moved[index as usize] = true;
+
+            if schedule.amount == 0 || is_matured(schedule.release_time, now) {
+                msg!("Schedule {} is no longer locked", index);
+                return Err(ProgramError::InvalidArgument);
+            }
+            moved.push(LockSchedule {
+                release_time: schedule.release_time,
+                amount: schedule.amount,
+            });
+            schedule.amount = 0;
+        }
+
+        // The new lock counts its schedules, so they have to be sorted even when the lock holds them unsorted
+        moved.sort_by_key(|s| s.release_time);
+        let amount = moved
+            .iter()
+            .try_fold(0u64, |total, s| total.checked_add(s.amount))
+            .ok_or(LockTokenError::MathOverflow)?;
+
+        let new_lock_signer = assert_lock(program_id, new_locking_account, new_seeds)?;
+        let new_locking_account_key = *new_locking_account.key;
+
+        if LockScheduleHeader::is_packed_initialized(&new_locking_account.try_borrow_data()?) {
+            msg!("Cannot overwrite an existing locking contract.");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let new_header_len = LockScheduleHeader::header_len(new_locking_account.data_len());
+        if new_header_len != LockScheduleHeader::LEN
+            || new_locking_account.data_len() != new_header_len + moved.len() * LockSchedule::LEN
+        {
+            msg!("The new locking account should be initialized with {} schedules", moved.len());
+            return Err(ProgramError::InvalidAccountData);
+        }
+        new_lock_signer.assert_derived_for(&header.creator_address, &header.mint_address)?;
+
+        assert_valid_new_destination(new_destination_token_account, &header.mint_address)?;
+
+        assert_token_program(spl_token_account)?;
+
+        if header.mint_address != *mint_account.key {
+            msg!("Provided mint account does not match the locked mint");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let mint = unpack_mint(mint_account, spl_token_account.key)?;
+
+        let locking_token_account_data = unpack_token_account(locking_token_account, spl_token_account.key)?;
+        assert_valid_vault(&locking_token_account_data, locking_account.key, &header.mint_address)?;
+        let new_locking_token_account_data = unpack_token_account(new_locking_token_account, spl_token_account.key)?;
+        assert_valid_vault(&new_locking_token_account_data, &new_locking_account_key, &header.mint_address)?;
+
+        let new_header = LockScheduleHeader {
+            destination_address: *new_destination_token_account.key,
+            mint_address: header.mint_address,
+            is_initialized: true,
+            pending_destination_address: Pubkey::default(),
+            is_vesting: false,
+            is_nft: header.is_nft,
+            // The moved amount stays counted in the token state of the mint, and is taken off when the new lock unlocks it
+            is_registered: header.is_registered,
+            is_immutable: false,
+            is_multi: false,
+            is_derived: new_lock_signer.is_derived(),
+            total_amount: amount,
+            claimed_amount: 0,
+            schedule_count: moved.len() as u32,
+            unlocked_count: 0,
+            rescue_destination_address: Pubkey::default(),
+            rescue_execute_after: 0,
+            creator_address: header.creator_address,
+            time_basis: header.time_basis,
+            split_destinations: [SplitDestination::default(); MAX_SPLIT_DESTINATIONS],
+            delegate_address: Pubkey::default(),
+            permit_nonce: 0,
+            // The moved schedules are claimed at the same pace, from a fresh cooldown
+            min_claim_interval_secs: header.min_claim_interval_secs,
+            last_claim_ts: 0,
+            lock_nonce: new_lock_signer.nonce(),
+            lock_bump: new_lock_signer.bump(),
+            // The moved schedules were locked when the lock they come from was created
+            created_at: header.created_at,
+        };
+
+        // Locks created before the total was tracked keep a zero total.
+        // The emptied schedules keep their release times, so Unlock drains them in order like any other.
+        header.total_amount = header.total_amount.saturating_sub(amount);
+        {
+            let mut data = locking_account.data.borrow_mut();
+            header.pack_into_slice(&mut data[..header_len]);
+            for (index, schedule) in schedules.iter().enumerate() {
+                if is_moved[index] {
+                    let offset = header_len + LockSchedule::LEN * index;
+                    schedule.pack_into_slice(&mut data[offset..offset + LockSchedule::LEN]);
+                }
+            }
+        }
+        {
+            let mut data = new_locking_account.data.borrow_mut();
+            new_header.pack_into_slice(&mut data[..new_header_len]);
+            for (offset, schedule) in (new_header_len..).step_by(LockSchedule::LEN).zip(moved.iter()) {
+                schedule.pack_into_slice(&mut data[offset..offset + LockSchedule::LEN]);
+            }
+        }
+
+        let transfer_to_new_locking_account = transfer_checked_instruction(
+            spl_token_account.key,
+            locking_token_account.key,
+            mint_account.key,
+            new_locking_token_account.key,
+            locking_account.key,
+            &[],
+            amount,
+            mint.decimals,
+        )?;
+
+        let lock_signer = lock_signer(program_id, locking_account, seeds)?;
+
+        let signer_seeds = lock_signer.signed_seeds_for(locking_account.key, program_id)?;
+        invoke_signed(
+            &transfer_to_new_locking_account,
+            &[
+                spl_token_account.clone(),
+                locking_token_account.clone(),
+                mint_account.clone(),
+                new_locking_token_account.clone(),
+                locking_account.clone(),
+            ],
+            &[&signer_seeds],
+        )?;
+
+        emit(&SchedulesTransferred {
+            locking_account: *locking_account.key,
+            new_locking_account: new_locking_account_key,
+            new_destination: *new_destination_token_account.key,
+            amount,
+            schedule_count: moved.len() as u32,
+        });
+
+        Ok(())
+    }
+
+    // Immutable locks keep their destination and release times
+    fn assert_mutable_lock(header: &LockScheduleHeader) -> ProgramResult {
+        if header.is_immutable {
+            msg!("The lock is immutable");
+            return Err(LockTokenError::ImmutableLock.into());
+        }
+        Ok(())
+    }
+
+    // Vesting locks have no schedules to extend or relock
+    fn assert_schedules_lock(header: &LockScheduleHeader) -> ProgramResult {
+        if header.is_vesting {
+            msg!("The lock is a vesting lock without schedules");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(())
+    }
+
+    // The schedules of a CreateMulti lock belong to several beneficiaries, whose release times the beneficiary
+    // of the lock can not change for them
+    fn assert_single_destination_lock(header: &LockScheduleHeader) -> ProgramResult {
+        if header.is_multi {
+            msg!("The schedules of the lock have their own destinations");
+            return Err(LockTokenError::MultiDestinationLock.into());
+        }
+        Ok(())
+    }
+
+    // Checks the program state account and that the admin stored in it signed,
+    // either directly or through the multisig signer accounts
+    fn load_global_state_as_admin(
+        program_id: &Pubkey,
+        admin_account: &AccountInfo,
+        program_state_account: &AccountInfo,
+        signer_accounts: &[AccountInfo],
+    ) -> Result<LockGlobalState, ProgramError> {
+        assert_distinct_accounts(&[
+            ("program admin account", admin_account.key),
+            ("program state account", program_state_account.key),
+        ])?;
+
+        let program_global_state = load_global_state(program_id, program_state_account)?;
+        // The version 1 layout stores no admin and has no room for the later fields. InitializeGlobalState
+        // migrates it to the current layout, so the admin instructions never write it.
+        if program_state_account.data_len() < LockGlobalState::V2_LEN {
+            msg!("The program state account uses the version 1 layout, which InitializeGlobalState migrates");
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        assert_admin(&program_global_state, admin_account, signer_accounts)?;
+
+        Ok(program_global_state)
+    }
+
+    pub fn process_pause_contract(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        pause_flags: u8,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let program_owner_account = next_account_info(accounts_iter)?;
+        let program_state_account = next_account_info(accounts_iter)?;
+
+        assert_writable(program_state_account, "program state account")?;
+
+        let mut program_global_state = Self::load_global_state_as_admin(program_id, program_owner_account, program_state_account, accounts_iter.as_slice())?;
+
+        // Unlock pauses expire, so they need the pause record of the current layout
+        if pause_flags & PAUSE_UNLOCK != 0 && program_state_account.data_len() < LockGlobalState::V6_LEN {
+            msg!("The program state account uses an older layout which has no room for the pause record");
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+
+        // The pause starts when nothing was paused and keeps its start while flags are added or removed, so
+        // adding PAUSE_UNLOCK to a pause can not stretch it. Resuming keeps the start as well, so toggling the
+        // flags can not restart it either. Unrecorded pauses of older layouts start now.
+        let now = now_u64(&Clock::get()?)?;
+        let was_paused = program_global_state.is_paused();
+        program_global_state.set_pause_flags(pause_flags);
+        if program_global_state.is_paused() && (!was_paused || program_global_state.paused_at == 0) {
+            let paused_at = program_global_state.pause_started_at(now);
+            if paused_at != program_global_state.paused_at {
+                program_global_state.paused_at = paused_at;
+                program_global_state.paused_by = *program_owner_account.key;
+            }
+        }
+        program_global_state.pack_into_slice(&mut program_state_account.data.borrow_mut()[..]);
+
+        let unlock_pause_ends_at = if program_global_state.pause_unlock {
+            program_global_state.paused_at.saturating_add(MAX_PAUSE_DURATION_SECS)
+        } else {
+            0
+        };
+        emit(&PauseChanged {
+            admin: *program_owner_account.key,
+            pause_flags,
+            paused_at: program_global_state.paused_at,
+            unlock_pause_ends_at,
+        });
+
+        Ok(())
+    }
+
+    pub fn process_initialize_global_state(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        admin: &Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let system_program_account = next_account_info(accounts_iter)?;
+        let rent_sysvar_account = next_account_info(accounts_iter)?;
+        let upgrade_authority_account = next_account_info(accounts_iter)?;
+        let program_state_account = next_account_info(accounts_iter)?;
+        let program_data_account = next_account_info(accounts_iter)?;
+        let legacy_program_state_account = next_account_info(accounts_iter).ok();
+
+        assert_distinct_accounts(&[
+            ("upgrade authority", upgrade_authority_account.key),
+            ("program state account", program_state_account.key),
+            ("program data account", program_data_account.key),
+        ])?;
+
+        assert_writable(program_state_account, "program state account")?;
+
+        let rent = Rent::from_account_info(rent_sysvar_account)?;
+
+        let (program_state_account_key, program_state_bump) = find_global_state_address(program_id);
+
+        if program_state_account_key != *program_state_account.key {
+            msg!("Provided program state account is invalid");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        if !upgrade_authority_account.is_signer {
+            msg!("Upgrade authority account should be a signer");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        assert_upgrade_authority(program_id, program_data_account, upgrade_authority_account.key)?;
+
+        if !program_state_account.data_is_empty() {
+            msg!("The state of program is already initialized");
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        // The fee parameters and the pause of a program state account of the version 1 layout are carried over
+        let legacy_program_global_state = match legacy_program_state_account {
+            Some(legacy_program_state_account) => {
+                Some(Self::load_legacy_global_state(program_id, legacy_program_state_account)?)
+            }
+            None => None,
+        };
+
+        let create_program_state_account = create_account(
+            &upgrade_authority_account.key,
+            &program_state_account_key,
+            rent.minimum_balance(LockGlobalState::LEN),
+            LockGlobalState::LEN as u64,
+            &program_id,
+        );
+
+        let bump_seed = [program_state_bump];
+        let signer_seeds = signed_seeds_with_bump_for(program_state_account.key, GLOBAL_STATE_SEED, &bump_seed, program_id)?;
+        invoke_signed(
+            &create_program_state_account,
+            &[
+                system_program_account.clone(),
+                upgrade_authority_account.clone(),
+                program_state_account.clone(),
+            ],
+            &[&signer_seeds],
+        )?;
+
+        let program_state_data = match legacy_program_global_state {
+            // The version 1 layout reads with defaults for everything it does not store, the admin among them
+            Some(legacy_program_global_state) => LockGlobalState {
+                admin: *admin,
+                ..legacy_program_global_state
+            },
+            None => LockGlobalState {
+                price_estimator: Pubkey::default(),
+                usd_token_address: Pubkey::default(),
+                fees_in_usd: 0,
+                company_wallet: Pubkey::default(),
+                max_price_age_slots: 0,
+                candidate_wallets: [Pubkey::default(); MAX_CANDIDATE_WALLETS],
+                admin: *admin,
+                pending_admin: Pubkey::default(),
+                min_lock_duration_secs: 0,
+                min_lock_amount: 0,
+                fee_bps_in_token: 0,
+                owner_token_mint: Pubkey::default(),
+                fee_discount_tiers: [FeeDiscountTier::default(); MAX_FEE_DISCOUNT_TIERS],
+                max_lock_duration_secs: 0,
+                paused_at: 0,
+                paused_by: Pubkey::default(),
+                pending_candidate_wallets: [Pubkey::default(); MAX_CANDIDATE_WALLETS],
+                candidate_wallets_execute_after: 0,
+                pause_create: false,
+                pause_unlock: false,
+                pause_transfer: false,
+                is_initialized: true,
+            },
+        };
+        program_state_data.pack_into_slice(&mut program_state_account.data.borrow_mut());
+
+        // The version 1 account is closed into the upgrade authority once nothing reads it anymore. Its lamports
+        // are moved after the CPI creating the new account, which does not include it.
+        if let Some(legacy_program_state_account) = legacy_program_state_account {
+            let lamports = legacy_program_state_account.lamports();
+            **upgrade_authority_account.try_borrow_mut_lamports()? = upgrade_authority_account
+                .lamports()
+                .checked_add(lamports)
+                .ok_or(LockTokenError::MathOverflow)?;
+            **legacy_program_state_account.try_borrow_mut_lamports()? = 0;
+            legacy_program_state_account.data.borrow_mut().fill(0);
+        }
+
+        Ok(())
+    }
+
+    // Reads the program state account of the version 1 layout, left at the address of LEGACY_GLOBAL_STATE_SEED
+    fn load_legacy_global_state(
+        program_id: &Pubkey,
+        legacy_program_state_account: &AccountInfo,
+    ) -> Result<LockGlobalState, ProgramError> {
+        if legacy_global_state_address(program_id)? != *legacy_program_state_account.key {
+            msg!("Provided version 1 program state account is invalid");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if *legacy_program_state_account.owner != *program_id
+            || legacy_program_state_account.data_len() != LockGlobalState::LEGACY_LEN
+        {
+            msg!("The version 1 program state account is not of the version 1 layout");
+            return Err(LockTokenError::InvalidGlobalState.into());
+        }
+        assert_writable(legacy_program_state_account, "version 1 program state account")?;
+
+        let legacy_program_global_state =
+            LockGlobalState::unpack_from_slice(&legacy_program_state_account.try_borrow_data()?)?;
+        if !legacy_program_global_state.is_initialized {
+            msg!("The version 1 program state account is uninitialized");
+            return Err(LockTokenError::GlobalStateUninitialized.into());
+        }
+        Ok(legacy_program_global_state)
+    }
+
+    pub fn process_set_fee_params(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        price_estimator: &Pubkey,
+        usd_token_address: &Pubkey,
+        fees_in_usd: u64,
+        company_wallet: &Pubkey,
+        candidate_wallets: &[Pubkey; MAX_CANDIDATE_WALLETS],
+        present_fields: u8,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
 
-        if !is_state_initialized {
-            msg!("The state of program is uninitialized");
-            return Err(ProgramError::InvalidArgument);
-        }
+        let program_owner_account = next_account_info(accounts_iter)?;
+        let program_state_account = next_account_info(accounts_iter)?;
 
-        let program_global_state = LockGlobalState::unpack(&program_state_account.data.borrow())?;
+        assert_writable(program_state_account, "program state account")?;
 
-        if program_global_state.is_paused {
-            msg!("The program is paused");
-            return Err(ProgramError::InvalidArgument);
+        if present_fields & !FEE_PARAMS_ALL != 0 {
+            msg!("Unknown fee params in the present fields {:#04x}", present_fields);
+            return Err(ProgramError::InvalidInstructionData);
         }
 
-        let locking_account_key = Pubkey::create_program_address(&[&seeds], program_id)?;
-        if locking_account_key != *locking_account.key {
-            msg!("Invalid locking account key");
-            return Err(ProgramError::InvalidArgument);
-        }
+        let mut program_state_data = Self::load_global_state_as_admin(program_id, program_owner_account, program_state_account, accounts_iter.as_slice())?;
 
-        if spl_token_account.key != &spl_token::id() {
-            msg!("The provided spl token program account is invalid");
-            return Err(ProgramError::InvalidArgument)
+        // The candidate wallets can only be chosen by the first call after initialization
+        if program_state_data.candidate_wallets == [Pubkey::default(); MAX_CANDIDATE_WALLETS] {
+            program_state_data.candidate_wallets = *candidate_wallets;
+        } else if program_state_data.candidate_wallets != *candidate_wallets {
+            msg!("The candidate wallets can not be changed after initialization");
+            return Err(LockTokenError::CandidateWalletsLocked.into());
         }
 
-        let packed_state = &locking_account.data;
-        let header_state =
-            LockScheduleHeader::unpack(&packed_state.borrow()[..LockScheduleHeader::LEN])?;
-
-        if header_state.destination_address != *destination_token_account.key {
-            msg!("Contract destination account does not matched provided account");
-            return Err(ProgramError::InvalidArgument);
+        if present_fields & FEE_PARAM_PRICE_ESTIMATOR != 0 {
+            if *price_estimator == Pubkey::default() {
+                msg!("The price estimator can not be the default pubkey");
+                return Err(ProgramError::InvalidArgument);
+            }
+            program_state_data.price_estimator = *price_estimator;
         }
-
-        let locking_token_account_data = Account::unpack(&locking_token_account.data.borrow())?;
-
-        if locking_token_account_data.owner != locking_account_key {
-            msg!("The locking token account should be owned by the locking account.");
-            return Err(ProgramError::InvalidArgument);
+        if present_fields & FEE_PARAM_USD_TOKEN_ADDRESS != 0 {
+            program_state_data.usd_token_address = *usd_token_address;
         }
-
-        // Unlock the schedules that have reached maturity
-        let clock = Clock::from_account_info(&clock_sysvar_account)?;
-        let mut total_amount_to_transfer = 0;
-        let mut schedules = unpack_schedules(&packed_state.borrow()[LockScheduleHeader::LEN..])?;
-
-        for s in schedules.iter_mut() {
-            if clock.unix_timestamp as u64 >= s.release_time {
-                total_amount_to_transfer += s.amount;
-                s.amount = 0;
-            }
+        if present_fields & FEE_PARAM_FEES_IN_USD != 0 {
+            program_state_data.fees_in_usd = fees_in_usd;
         }
-        if total_amount_to_transfer == 0 {
-            msg!("locking contract has not yet reached release time");
-            return Err(ProgramError::InvalidArgument);
+        if present_fields & FEE_PARAM_COMPANY_WALLET != 0 {
+            if *company_wallet == Pubkey::default() {
+                msg!("The company wallet can not be the default pubkey");
+                return Err(ProgramError::InvalidArgument);
+            }
+            if !program_state_data.is_candidate_wallet(company_wallet) {
+                msg!("The company wallet {} is not a candidate wallet", company_wallet);
+                return Err(LockTokenError::WalletNotWhitelisted.into());
+            }
+            program_state_data.company_wallet = *company_wallet;
         }
 
-        let transfer_tokens_from_locking_account = transfer(
-            &spl_token_account.key,
-            &locking_token_account.key,
-            destination_token_account.key,
-            &locking_account_key,
-            &[],
-            total_amount_to_transfer,
-        )?;
-
-        invoke_signed(
-            &transfer_tokens_from_locking_account,
-            &[
-                spl_token_account.clone(),
-                locking_token_account.clone(),
-                destination_token_account.clone(),
-                locking_account.clone(),
-            ],
-            &[&[&seeds]],
-        )?;
-
-        // Reset released amounts to 0. This makes the simple unlock safe with complex scheduling contracts
-        pack_schedules_into_slice(
-            schedules,
-            &mut packed_state.borrow_mut()[LockScheduleHeader::LEN..],
-        );
+        program_state_data.pack_into_slice(&mut program_state_account.data.borrow_mut());
 
         Ok(())
     }
 
-    pub fn process_transfer_locks(
+    pub fn process_set_fees_in_usd(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        seeds: [u8; 32],
+        fees_in_usd: u64,
     ) -> ProgramResult {
         let accounts_iter = &mut accounts.iter();
 
+        let program_owner_account = next_account_info(accounts_iter)?;
         let program_state_account = next_account_info(accounts_iter)?;
-        let locking_account = next_account_info(accounts_iter)?;
-        let destination_token_account = next_account_info(accounts_iter)?;
-        let destination_token_account_owner = next_account_info(accounts_iter)?;
-        let new_destination_token_account = next_account_info(accounts_iter)?;
-
-        let program_state_account_key = Pubkey::create_program_address(&[String::from(OWNER_TOKEN_MINT_ADDRESS).as_bytes()], program_id)?;
-
-        if program_state_account_key != *program_state_account.key {
-            msg!("Provided program state account is invalid");
-            return Err(ProgramError::InvalidArgument);
-        }
 
-        let is_state_initialized = program_state_account.try_borrow_data()?[LockGlobalState::LEN - 1] == 1;
+        assert_writable(program_state_account, "program state account")?;
 
-        if !is_state_initialized {
-            msg!("The state of program is uninitialized");
-            return Err(ProgramError::InvalidArgument);
-        }
+        let mut program_state_data = Self::load_global_state_as_admin(program_id, program_owner_account, program_state_account, accounts_iter.as_slice())?;
 
-        let program_global_state = LockGlobalState::unpack(&program_state_account.data.borrow())?;
+        program_state_data.fees_in_usd = fees_in_usd;
 
-        if program_global_state.is_paused {
-            msg!("The program is paused");
-            return Err(ProgramError::InvalidArgument);
-        }
+        program_state_data.pack_into_slice(&mut program_state_account.data.borrow_mut()[..]);
 
-        if locking_account.data.borrow().len() < LockScheduleHeader::LEN {
-            return Err(ProgramError::InvalidAccountData)
-        }
-        let locking_account_key = Pubkey::create_program_address(&[&seeds], program_id)?;
-        let state = LockScheduleHeader::unpack(
-            &locking_account.data.borrow()[..LockScheduleHeader::LEN],
-        )?;
+        Ok(())
+    }
 
-        if locking_account_key != *locking_account.key {
-            msg!("Invalid locking account key");
-            return Err(ProgramError::InvalidArgument);
-        }
+    pub fn process_set_max_price_age(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        max_price_age_slots: u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
 
-        if state.destination_address != *destination_token_account.key {
-            msg!("Contract destination account does not matched provided account");
-            return Err(ProgramError::InvalidArgument);
-        }
+        let program_owner_account = next_account_info(accounts_iter)?;
+        let program_state_account = next_account_info(accounts_iter)?;
 
-        if !destination_token_account_owner.is_signer {
-            msg!("Destination token account owner should be a signer.");
-            return Err(ProgramError::InvalidArgument);
-        }
+        assert_writable(program_state_account, "program state account")?;
 
-        let destination_token_account = Account::unpack(&destination_token_account.data.borrow())?;
+        let mut program_state_data = Self::load_global_state_as_admin(program_id, program_owner_account, program_state_account, accounts_iter.as_slice())?;
 
-        if destination_token_account.owner != *destination_token_account_owner.key {
-            msg!("The current destination token account isn't owned by the provided owner");
-            return Err(ProgramError::InvalidArgument);
-        }
+        program_state_data.max_price_age_slots = max_price_age_slots;
 
-        let mut new_state = state;
-        new_state.destination_address = *new_destination_token_account.key;
-        new_state
-            .pack_into_slice(&mut locking_account.data.borrow_mut()[..LockScheduleHeader::LEN]);
+        program_state_data.pack_into_slice(&mut program_state_account.data.borrow_mut()[..]);
 
         Ok(())
     }
 
-    pub fn process_extend_lock_duration(
+    pub fn process_set_lock_minimums(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        seeds: [u8; 32],
-        index: u32,
-        release_time: u64,
+        min_lock_duration_secs: u64,
+        min_lock_amount: u64,
     ) -> ProgramResult {
         let accounts_iter = &mut accounts.iter();
 
+        let program_owner_account = next_account_info(accounts_iter)?;
         let program_state_account = next_account_info(accounts_iter)?;
-        let locking_account = next_account_info(accounts_iter)?;
-        let destination_token_account = next_account_info(accounts_iter)?;
-        let destination_token_account_owner = next_account_info(accounts_iter)?;
 
-        let program_state_account_key = Pubkey::create_program_address(&[String::from(OWNER_TOKEN_MINT_ADDRESS).as_bytes()], program_id)?;
+        assert_writable(program_state_account, "program state account")?;
 
-        if program_state_account_key != *program_state_account.key {
-            msg!("Provided program state account is invalid");
-            return Err(ProgramError::InvalidArgument);
-        }
+        let mut program_state_data = Self::load_global_state_as_admin(program_id, program_owner_account, program_state_account, accounts_iter.as_slice())?;
 
-        let is_state_initialized = program_state_account.try_borrow_data()?[LockGlobalState::LEN - 1] == 1;
+        program_state_data.min_lock_duration_secs = min_lock_duration_secs;
+        program_state_data.min_lock_amount = min_lock_amount;
 
-        if !is_state_initialized {
-            msg!("The state of program is uninitialized");
-            return Err(ProgramError::InvalidArgument);
-        }
+        program_state_data.pack_into_slice(&mut program_state_account.data.borrow_mut()[..]);
 
-        let program_global_state = LockGlobalState::unpack(&program_state_account.data.borrow())?;
+        Ok(())
+    }
 
-        if program_global_state.is_paused {
-            msg!("The program is paused");
-            return Err(ProgramError::InvalidArgument);
-        }
+    pub fn process_set_fee_bps_in_token(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        fee_bps_in_token: u16,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
 
-        if locking_account.data.borrow().len() < LockScheduleHeader::LEN + LockSchedule::LEN * (index as usize + 1) {
-            return Err(ProgramError::InvalidAccountData)
-        }
-        let locking_account_key = Pubkey::create_program_address(&[&seeds], program_id)?;
-        let state = LockSchedule::unpack(
-            &locking_account.data.borrow()[(LockScheduleHeader::LEN + LockSchedule::LEN * index as usize)..(LockScheduleHeader::LEN + LockSchedule::LEN * (index as usize + 1))],
-        )?;
+        let program_owner_account = next_account_info(accounts_iter)?;
+        let program_state_account = next_account_info(accounts_iter)?;
+        let system_program_account = next_account_info(accounts_iter)?;
+        let payer = next_account_info(accounts_iter)?;
 
-        if locking_account_key != *locking_account.key {
-            msg!("Invalid locking account key");
-            return Err(ProgramError::InvalidArgument);
-        }
+        assert_writable(program_state_account, "program state account")?;
+        assert_system_program(system_program_account)?;
 
-        if state.release_time > release_time {
-            msg!("Can not set shorter release time.");
-            return Err(ProgramError::InvalidArgument);
-        }
+        let mut program_state_data = Self::load_global_state_as_admin(program_id, program_owner_account, program_state_account, accounts_iter.as_slice())?;
 
-        if !destination_token_account_owner.is_signer {
-            msg!("Destination token account owner should be a signer.");
-            return Err(ProgramError::InvalidArgument);
+        if fee_bps_in_token > MAX_FEE_BPS_IN_TOKEN {
+            msg!("The token fee of {} basis points is more than {}", fee_bps_in_token, MAX_FEE_BPS_IN_TOKEN);
+            return Err(LockTokenError::InvalidBasisPoints.into());
         }
 
-        let destination_token_account = Account::unpack(&destination_token_account.data.borrow())?;
+        Self::grow_global_state(program_state_account, system_program_account, payer)?;
 
-        if destination_token_account.owner != *destination_token_account_owner.key {
-            msg!("The current destination token account isn't owned by the provided owner");
-            return Err(ProgramError::InvalidArgument);
-        }
+        program_state_data.fee_bps_in_token = fee_bps_in_token;
 
-        let mut new_state = state;
-        new_state.release_time = release_time;
-        new_state
-            .pack_into_slice(&mut locking_account.data.borrow_mut()[(LockScheduleHeader::LEN + LockSchedule::LEN * index as usize)..(LockScheduleHeader::LEN + LockSchedule::LEN * (index as usize + 1))]);
+        program_state_data.pack_into_slice(&mut program_state_account.data.borrow_mut()[..]);
 
         Ok(())
     }
 
-    pub fn process_pause_contract(
+    pub fn process_set_fee_discount_tiers(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        is_pause: bool,
+        fee_discount_tiers: &[FeeDiscountTier; MAX_FEE_DISCOUNT_TIERS],
     ) -> ProgramResult {
         let accounts_iter = &mut accounts.iter();
 
         let program_owner_account = next_account_info(accounts_iter)?;
-        let program_owner_token_account = next_account_info(accounts_iter)?;
         let program_state_account = next_account_info(accounts_iter)?;
+        let system_program_account = next_account_info(accounts_iter)?;
+        let payer = next_account_info(accounts_iter)?;
 
-        let program_state_account_key = Pubkey::create_program_address(&[String::from(OWNER_TOKEN_MINT_ADDRESS).as_bytes()], program_id)?;
+        assert_writable(program_state_account, "program state account")?;
+        assert_system_program(system_program_account)?;
 
-        if program_state_account_key != *program_state_account.key {
-            msg!("Provided program state account is invalid");
-            return Err(ProgramError::InvalidArgument);
-        }
+        let mut program_state_data = Self::load_global_state_as_admin(program_id, program_owner_account, program_state_account, accounts_iter.as_slice())?;
 
-        if !program_owner_account.is_signer {
-            msg!("Program owner account should be a signer");
-            return Err(ProgramError::InvalidArgument);
+        for tier in fee_discount_tiers.iter() {
+            if tier.discount_bps > MAX_BPS {
+                msg!("The fee discount of {} basis points is more than {}", tier.discount_bps, MAX_BPS);
+                return Err(LockTokenError::InvalidBasisPoints.into());
+            }
         }
 
-        if *program_state_account.owner != *program_id {
-            msg!("Program should own program state account");
-            return Err(ProgramError::InvalidArgument);
-        }
+        Self::grow_global_state(program_state_account, system_program_account, payer)?;
 
-        let program_owner_token_account_data = Account::unpack(&program_owner_token_account.data.borrow())?;
+        program_state_data.fee_discount_tiers = *fee_discount_tiers;
 
-        if program_owner_token_account_data.owner != *program_owner_account.key {
-            msg!("Program owner account should own token account.");
-            return Err(ProgramError::InvalidArgument);
-        }
+        program_state_data.pack_into_slice(&mut program_state_account.data.borrow_mut()[..]);
 
-        let owner_token_mint_key = Pubkey::from_str(OWNER_TOKEN_MINT_ADDRESS);
-        match owner_token_mint_key {
-            Ok(v) => { 
-                if (v != program_owner_token_account_data.mint) || (program_owner_token_account_data.amount == 0) {
-                    msg!("Program owner account shold own the specified owner token mint.");
-                    return Err(ProgramError::InvalidArgument);
-                }
-            },
-            Err(_e) => {
-                msg!("Program owner account shold own the specified owner token mint.");
-                return Err(ProgramError::InvalidArgument);
-            },
-        }
+        Ok(())
+    }
+
+    pub fn process_set_owner_token_mint(program_id: &Pubkey, accounts: &[AccountInfo], new_mint: &Pubkey) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let program_owner_account = next_account_info(accounts_iter)?;
+        let program_state_account = next_account_info(accounts_iter)?;
+        let system_program_account = next_account_info(accounts_iter)?;
+        let payer = next_account_info(accounts_iter)?;
+        let spl_token_account = next_account_info(accounts_iter)?;
+        let admin_token_account = next_account_info(accounts_iter)?;
+
+        assert_writable(program_state_account, "program state account")?;
+        assert_system_program(system_program_account)?;
+        assert_token_program(spl_token_account)?;
 
-        let is_state_initialized = program_state_account.try_borrow_data()?[LockGlobalState::LEN - 1] == 1;
+        let mut program_state_data = Self::load_global_state_as_admin(program_id, program_owner_account, program_state_account, accounts_iter.as_slice())?;
 
-        if !is_state_initialized {
-            msg!("The state of program is uninitialized");
+        // The admin holds the new owner token, so the fee discount is not rotated to a mint nobody holds
+        let admin_token_account_data = unpack_token_account(admin_token_account, spl_token_account.key)?;
+        if admin_token_account_data.mint != *new_mint {
+            msg!("The admin's token account is not a token account of the new owner token {}", new_mint);
+            return Err(LockTokenError::FeeMintMismatch.into());
+        }
+        if admin_token_account_data.owner != *program_owner_account.key {
+            msg!("The admin's token account is not owned by the admin");
             return Err(ProgramError::InvalidArgument);
         }
+        if admin_token_account_data.amount == 0 {
+            msg!("The admin holds none of the new owner token {}", new_mint);
+            return Err(ProgramError::InsufficientFunds);
+        }
 
-        let packed_state_data = &program_state_account.data;
-        let mut program_global_state = LockGlobalState::unpack(&packed_state_data.borrow()[..LockGlobalState::LEN])?;
+        Self::grow_global_state(program_state_account, system_program_account, payer)?;
 
-        program_global_state.is_paused = is_pause;
-        program_global_state.pack_into_slice(&mut program_state_account.data.borrow_mut()[..]);
+        program_state_data.owner_token_mint = *new_mint;
+
+        program_state_data.pack_into_slice(&mut program_state_account.data.borrow_mut()[..]);
 
         Ok(())
     }
 
-    pub fn process_set_fee_params(
+    pub fn process_set_max_lock_duration(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        price_estimator: &Pubkey,
-        usd_token_address: &Pubkey,
-        fees_in_usd: u64,
-        company_wallet: &Pubkey,
+        max_lock_duration_secs: u64,
     ) -> ProgramResult {
         let accounts_iter = &mut accounts.iter();
 
-        let system_program_account = next_account_info(accounts_iter)?;
-        let rent_sysvar_account = next_account_info(accounts_iter)?;
         let program_owner_account = next_account_info(accounts_iter)?;
-        let program_owner_token_account = next_account_info(accounts_iter)?;
         let program_state_account = next_account_info(accounts_iter)?;
+        let system_program_account = next_account_info(accounts_iter)?;
+        let payer = next_account_info(accounts_iter)?;
 
-        let rent = Rent::from_account_info(rent_sysvar_account)?;
+        assert_writable(program_state_account, "program state account")?;
+        assert_system_program(system_program_account)?;
 
-        let program_state_account_key = Pubkey::create_program_address(&[String::from(OWNER_TOKEN_MINT_ADDRESS).as_bytes()], program_id)?;
+        let mut program_state_data = Self::load_global_state_as_admin(program_id, program_owner_account, program_state_account, accounts_iter.as_slice())?;
 
-        if program_state_account_key != *program_state_account.key {
-            msg!("Provided program state account is invalid");
+        // No lock could be created if the maximum was below the minimum
+        if max_lock_duration_secs != 0 && max_lock_duration_secs < program_state_data.min_lock_duration_secs {
+            msg!(
+                "The maximum lock duration {} is less than the minimum {}",
+                max_lock_duration_secs,
+                program_state_data.min_lock_duration_secs
+            );
             return Err(ProgramError::InvalidArgument);
         }
 
-        if !program_owner_account.is_signer {
-            msg!("Program owner account should be a signer");
-            return Err(ProgramError::InvalidArgument);
-        }
+        Self::grow_global_state(program_state_account, system_program_account, payer)?;
 
-        if *program_state_account.owner != *program_id {
-            msg!("Program should own program state account");
-            return Err(ProgramError::InvalidArgument);
-        }
+        program_state_data.max_lock_duration_secs = max_lock_duration_secs;
 
-        let program_owner_token_account_data = Account::unpack(&program_owner_token_account.data.borrow())?;
+        program_state_data.pack_into_slice(&mut program_state_account.data.borrow_mut()[..]);
 
-        if program_owner_token_account_data.owner != *program_owner_account.key {
-            msg!("Program owner account should own token account.");
-            return Err(ProgramError::InvalidArgument);
-        }
+        Ok(())
+    }
 
-        let owner_token_mint_key = Pubkey::from_str(OWNER_TOKEN_MINT_ADDRESS);
-        match owner_token_mint_key {
-            Ok(v) => { 
-                if (v != program_owner_token_account_data.mint) || (program_owner_token_account_data.amount == 0) {
-                    msg!("Program owner account shold own the specified owner token mint.");
-                    return Err(ProgramError::InvalidArgument);
-                }
-            },
-            Err(_e) => {
-                msg!("Program owner account shold own the specified owner token mint.");
-                return Err(ProgramError::InvalidArgument);
-            },
+    // Program state accounts of the version 2, 3, 4, 5 and 6 layouts are grown to the current layout, the payer
+    // covering the extra rent
+    fn grow_global_state<'a>(
+        program_state_account: &AccountInfo<'a>,
+        system_program_account: &AccountInfo<'a>,
+        payer: &AccountInfo<'a>,
+    ) -> ProgramResult {
+        if program_state_account.data_len() >= LockGlobalState::LEN {
+            return Ok(());
         }
 
-        let is_state_initialized = program_state_account.try_borrow_data()?[LockGlobalState::LEN - 1] == 1;
-
-        if !is_state_initialized {
-            let create_program_state_account = create_account(
-                &program_owner_account.key,
-                &program_state_account_key,
-                rent.minimum_balance(LockGlobalState::LEN),
-                LockGlobalState::LEN as u64,
-                &program_id,
-            );
-    
-            invoke_signed(
-                &create_program_state_account,
+        if !payer.is_signer {
+            msg!("The payer should be a signer");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let extra_rent = Rent::get()?
+            .minimum_balance(LockGlobalState::LEN)
+            .saturating_sub(program_state_account.lamports());
+        if extra_rent > 0 {
+            assert_writable(payer, "payer")?;
+            invoke(
+                &transfer_sol(payer.key, program_state_account.key, extra_rent),
                 &[
-                    system_program_account.clone(),
-                    program_owner_account.clone(),
+                    payer.clone(),
                     program_state_account.clone(),
+                    system_program_account.clone(),
                 ],
-                &[&[String::from(OWNER_TOKEN_MINT_ADDRESS).as_bytes()]],
             )?;
         }
-
-        let mut program_state_data = LockGlobalState::unpack(&program_state_account.data.borrow())?;
-        program_state_data.price_estimator = *price_estimator;
-        program_state_data.usd_token_address = *usd_token_address;
-        program_state_data.fees_in_usd = fees_in_usd;
-        program_state_data.company_wallet = *company_wallet;
-
-        program_state_data.pack_into_slice(&mut program_state_account.data.borrow_mut());
-
-        Ok(())
+        program_state_account.realloc(LockGlobalState::LEN, true)
     }
 
-    pub fn process_set_fees_in_usd(
+    pub fn process_set_company_wallet(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        fees_in_usd: u64,
+        company_wallet: &Pubkey,
     ) -> ProgramResult {
         let accounts_iter = &mut accounts.iter();
 
         let program_owner_account = next_account_info(accounts_iter)?;
-        let program_owner_token_account = next_account_info(accounts_iter)?;
         let program_state_account = next_account_info(accounts_iter)?;
 
-        let program_state_account_key = Pubkey::create_program_address(&[String::from(OWNER_TOKEN_MINT_ADDRESS).as_bytes()], program_id)?;
+        assert_writable(program_state_account, "program state account")?;
 
-        if program_state_account_key != *program_state_account.key {
-            msg!("Provided program state account is invalid");
-            return Err(ProgramError::InvalidArgument);
-        }
+        let mut program_state_data = Self::load_global_state_as_admin(program_id, program_owner_account, program_state_account, accounts_iter.as_slice())?;
 
-        if !program_owner_account.is_signer {
-            msg!("Program owner account should be a signer");
-            return Err(ProgramError::InvalidArgument);
+        if !program_state_data.is_candidate_wallet(company_wallet) {
+            msg!("The company wallet {} is not a candidate wallet", company_wallet);
+            return Err(LockTokenError::WalletNotWhitelisted.into());
         }
 
-        if *program_state_account.owner != *program_id {
-            msg!("Program should own program state account");
-            return Err(ProgramError::InvalidArgument);
-        }
+        program_state_data.company_wallet = *company_wallet;
 
-        let program_owner_token_account_data = Account::unpack(&program_owner_token_account.data.borrow())?;
+        program_state_data.pack_into_slice(&mut program_state_account.data.borrow_mut()[..]);
 
-        if program_owner_token_account_data.owner != *program_owner_account.key {
-            msg!("Program owner account should own token account.");
-            return Err(ProgramError::InvalidArgument);
-        }
+        Ok(())
+    }
 
-        let owner_token_mint_key = Pubkey::from_str(OWNER_TOKEN_MINT_ADDRESS);
-        match owner_token_mint_key {
-            Ok(v) => { 
-                if (v != program_owner_token_account_data.mint) || (program_owner_token_account_data.amount == 0) {
-                    msg!("Program owner account shold own the specified owner token mint.");
-                    return Err(ProgramError::InvalidArgument);
-                }
-            },
-            Err(_e) => {
-                msg!("Program owner account shold own the specified owner token mint.");
-                return Err(ProgramError::InvalidArgument);
-            },
-        }
+    pub fn process_propose_candidate_wallets(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        candidate_wallets: &[Pubkey; MAX_CANDIDATE_WALLETS],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let program_owner_account = next_account_info(accounts_iter)?;
+        let program_state_account = next_account_info(accounts_iter)?;
+        let system_program_account = next_account_info(accounts_iter)?;
+        let payer = next_account_info(accounts_iter)?;
 
-        let is_state_initialized = program_state_account.try_borrow_data()?[LockGlobalState::LEN - 1] == 1;
+        assert_writable(program_state_account, "program state account")?;
+        assert_system_program(system_program_account)?;
 
-        if !is_state_initialized {
-            msg!("The state of program is uninitialized");
+        let mut program_state_data = Self::load_global_state_as_admin(program_id, program_owner_account, program_state_account, accounts_iter.as_slice())?;
+
+        if *candidate_wallets == [Pubkey::default(); MAX_CANDIDATE_WALLETS] {
+            msg!("At least one candidate wallet has to be proposed");
             return Err(ProgramError::InvalidArgument);
         }
 
-        let mut program_state_data = LockGlobalState::unpack(&program_state_account.data.borrow())?;
-        program_state_data.fees_in_usd = fees_in_usd;
+        Self::grow_global_state(program_state_account, system_program_account, payer)?;
+
+        // A new proposal replaces the pending one and restarts the delay
+        let now = now_u64(&Clock::get()?)?;
+        program_state_data.pending_candidate_wallets = *candidate_wallets;
+        program_state_data.candidate_wallets_execute_after = now
+            .checked_add(CANDIDATE_WALLETS_DELAY_SECS)
+            .ok_or(LockTokenError::MathOverflow)?;
+        msg!("The candidate wallets can be replaced from {}", program_state_data.candidate_wallets_execute_after);
 
         program_state_data.pack_into_slice(&mut program_state_account.data.borrow_mut()[..]);
 
         Ok(())
     }
 
-    pub fn process_set_company_wallet(
+    pub fn process_accept_candidate_wallets(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        company_wallet: &Pubkey,
     ) -> ProgramResult {
         let accounts_iter = &mut accounts.iter();
 
         let program_owner_account = next_account_info(accounts_iter)?;
-        let program_owner_token_account = next_account_info(accounts_iter)?;
         let program_state_account = next_account_info(accounts_iter)?;
 
-        let program_state_account_key = Pubkey::create_program_address(&[String::from(OWNER_TOKEN_MINT_ADDRESS).as_bytes()], program_id)?;
-
-        if program_state_account_key != *program_state_account.key {
-            msg!("Provided program state account is invalid");
-            return Err(ProgramError::InvalidArgument);
-        }
-
-        if !program_owner_account.is_signer {
-            msg!("Program owner account should be a signer");
-            return Err(ProgramError::InvalidArgument);
-        }
-
-        if *program_state_account.owner != *program_id {
-            msg!("Program should own program state account");
-            return Err(ProgramError::InvalidArgument);
-        }
+        assert_writable(program_state_account, "program state account")?;
 
-        let program_owner_token_account_data = Account::unpack(&program_owner_token_account.data.borrow())?;
+        let mut program_state_data = Self::load_global_state_as_admin(program_id, program_owner_account, program_state_account, accounts_iter.as_slice())?;
 
-        if program_owner_token_account_data.owner != *program_owner_account.key {
-            msg!("Program owner account should own token account.");
+        if program_state_data.candidate_wallets_execute_after == 0 {
+            msg!("No candidate wallets are proposed");
             return Err(ProgramError::InvalidArgument);
         }
 
-        let owner_token_mint_key = Pubkey::from_str(OWNER_TOKEN_MINT_ADDRESS);
-        match owner_token_mint_key {
-            Ok(v) => { 
-                if (v != program_owner_token_account_data.mint) || (program_owner_token_account_data.amount == 0) {
-                    msg!("Program owner account shold own the specified owner token mint.");
-                    return Err(ProgramError::InvalidArgument);
-                }
-            },
-            Err(_e) => {
-                msg!("Program owner account shold own the specified owner token mint.");
-                return Err(ProgramError::InvalidArgument);
-            },
-        }
-
-        let is_state_initialized = program_state_account.try_borrow_data()?[LockGlobalState::LEN - 1] == 1;
-
-        if !is_state_initialized {
-            msg!("The state of program is uninitialized");
-            return Err(ProgramError::InvalidArgument);
+        let now = now_u64(&Clock::get()?)?;
+        if !is_matured(program_state_data.candidate_wallets_execute_after, now) {
+            msg!(
+                "The candidate wallets can not be replaced before {}",
+                program_state_data.candidate_wallets_execute_after
+            );
+            return Err(LockTokenError::CandidateWalletsTimelock.into());
         }
 
-        let mut program_state_data = LockGlobalState::unpack(&program_state_account.data.borrow())?;
-        program_state_data.company_wallet = *company_wallet;
+        // The company wallet stays as it is until SetCompanyWallet picks one of the new candidates
+        program_state_data.candidate_wallets = program_state_data.pending_candidate_wallets;
+        program_state_data.pending_candidate_wallets = [Pubkey::default(); MAX_CANDIDATE_WALLETS];
+        program_state_data.candidate_wallets_execute_after = 0;
 
         program_state_data.pack_into_slice(&mut program_state_account.data.borrow_mut()[..]);
 
@@ -807,133 +4907,238 @@ impl Processor {
         let accounts_iter = &mut accounts.iter();
 
         let program_owner_account = next_account_info(accounts_iter)?;
-        let program_owner_token_account = next_account_info(accounts_iter)?;
         let program_state_account = next_account_info(accounts_iter)?;
         let token_state_account = next_account_info(accounts_iter)?;
 
-        let program_state_account_key = Pubkey::create_program_address(&[String::from(OWNER_TOKEN_MINT_ADDRESS).as_bytes()], program_id)?;
+        assert_distinct_accounts(&[
+            ("program state account", program_state_account.key),
+            ("token state account", token_state_account.key),
+        ])?;
 
-        if program_state_account_key != *program_state_account.key {
-            msg!("Provided program state account is invalid");
+        assert_writable(token_state_account, "token state account")?;
+
+        let program_global_state = Self::load_global_state_as_admin(program_id, program_owner_account, program_state_account, accounts_iter.as_slice())?;
+
+        if program_global_state.is_paused() {
+            msg!("The program is paused");
             return Err(ProgramError::InvalidArgument);
         }
 
-        if !program_owner_account.is_signer {
-            msg!("Program owner account should be a signer");
+        let token_state_account_key = token_state_address(program_id, mint_address)?;
+        if token_state_account_key != *token_state_account.key {
+            msg!("Provided token state account is invalid");
             return Err(ProgramError::InvalidArgument);
         }
 
-        if *program_state_account.owner != *program_id {
-            msg!("Program should own program state account");
+        let mut token_state_data = TokenState::unpack_from_slice(&token_state_account.data.borrow())?;
+
+        if !token_state_data.is_initialized {
+            msg!("The token state is uninitialized");
+            return Err(ProgramError::UninitializedAccount);
+        }
+        
+        if token_state_data.mint_address != *mint_address {
+            msg!("Provided token state account is invalid");
             return Err(ProgramError::InvalidArgument);
         }
 
-        let program_owner_token_account_data = Account::unpack(&program_owner_token_account.data.borrow())?;
+        token_state_data.is_free = is_free;
+        token_state_data.pack_into_slice(&mut token_state_account.data.borrow_mut()[..]);
+
+        Ok(())
+    }
+
+    // Creates the wallet state account on first use, then sets whether the wallet pays the Create fee
+    pub fn process_set_fee_exempt_wallet(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        wallet: &Pubkey,
+        is_exempt: bool,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let program_owner_account = next_account_info(accounts_iter)?;
+        let program_state_account = next_account_info(accounts_iter)?;
+        let wallet_state_account = next_account_info(accounts_iter)?;
+        let system_program_account = next_account_info(accounts_iter)?;
+        let payer = next_account_info(accounts_iter)?;
 
-        if program_owner_token_account_data.owner != *program_owner_account.key {
-            msg!("Program owner account should own token account.");
+        assert_distinct_accounts(&[
+            ("program state account", program_state_account.key),
+            ("wallet state account", wallet_state_account.key),
+            ("payer", payer.key),
+        ])?;
+
+        assert_writable(wallet_state_account, "wallet state account")?;
+        assert_system_program(system_program_account)?;
+
+        Self::load_global_state_as_admin(program_id, program_owner_account, program_state_account, accounts_iter.as_slice())?;
+
+        let (wallet_state_key, bump_seed) = find_wallet_state_address(program_id, wallet);
+        if wallet_state_key != *wallet_state_account.key {
+            msg!("Provided wallet state account is invalid");
             return Err(ProgramError::InvalidArgument);
         }
 
-        let owner_token_mint_key = Pubkey::from_str(OWNER_TOKEN_MINT_ADDRESS);
-        match owner_token_mint_key {
-            Ok(v) => { 
-                if (v != program_owner_token_account_data.mint) || (program_owner_token_account_data.amount == 0) {
-                    msg!("Program owner account shold own the specified owner token mint.");
-                    return Err(ProgramError::InvalidArgument);
-                }
-            },
-            Err(_e) => {
-                msg!("Program owner account shold own the specified owner token mint.");
-                return Err(ProgramError::InvalidArgument);
-            },
+        if *wallet_state_account.owner == *program_id {
+            let wallet_state_data = WalletState::unpack_from_slice(&wallet_state_account.data.borrow())?;
+            if wallet_state_data.wallet_address != *wallet {
+                msg!("Provided wallet state account is invalid");
+                return Err(ProgramError::InvalidAccountData);
+            }
+        } else {
+            if !payer.is_signer {
+                msg!("The payer should sign");
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            let rent = Rent::get()?;
+            let signer_seeds: &[&[u8]] = &[WALLET_STATE_SEED, wallet.as_ref(), &[bump_seed]];
+            Self::create_program_account(
+                program_id,
+                system_program_account,
+                payer,
+                wallet_state_account,
+                &rent,
+                WalletState::LEN,
+                signer_seeds,
+            )?;
         }
 
-        let is_state_initialized = program_state_account.try_borrow_data()?[LockGlobalState::LEN - 1] == 1;
+        let wallet_state_data = WalletState {
+            wallet_address: *wallet,
+            is_fee_exempt: is_exempt,
+            is_initialized: true,
+        };
+        wallet_state_data.pack_into_slice(&mut wallet_state_account.data.borrow_mut());
+
+        Ok(())
+    }
 
-        if !is_state_initialized {
-            msg!("The state of program is uninitialized");
-            return Err(ProgramError::InvalidArgument);
-        }
+    pub fn process_set_token_fee(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        mint_address: &Pubkey,
+        fee_in_usd: Option<u64>,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
 
-        let packed_state_data = &program_state_account.data;
-        let program_global_state = LockGlobalState::unpack(&packed_state_data.borrow()[..LockGlobalState::LEN])?;
+        let program_owner_account = next_account_info(accounts_iter)?;
+        let program_state_account = next_account_info(accounts_iter)?;
+        let token_state_account = next_account_info(accounts_iter)?;
 
-        if program_global_state.is_paused {
-            msg!("The program is paused");
-            return Err(ProgramError::InvalidArgument);
-        }
+        assert_distinct_accounts(&[
+            ("program state account", program_state_account.key),
+            ("token state account", token_state_account.key),
+        ])?;
+
+        assert_writable(token_state_account, "token state account")?;
 
-        let token_state_account_key = Pubkey::create_program_address(&[&mint_address.to_bytes()], program_id)?;
+        Self::load_global_state_as_admin(program_id, program_owner_account, program_state_account, accounts_iter.as_slice())?;
+
+        let token_state_account_key = token_state_address(program_id, mint_address)?;
         if token_state_account_key != *token_state_account.key {
             msg!("Provided token state account is invalid");
             return Err(ProgramError::InvalidArgument);
         }
 
-        let mut token_state_data = TokenState::unpack(&token_state_account.data.borrow())?;
-        
+        let mut token_state_data = TokenState::unpack_from_slice(&token_state_account.data.borrow())?;
+
+        if !token_state_data.is_initialized {
+            msg!("The token state is uninitialized");
+            return Err(ProgramError::UninitializedAccount);
+        }
+
         if token_state_data.mint_address != *mint_address {
             msg!("Provided token state account is invalid");
             return Err(ProgramError::InvalidArgument);
         }
 
-        token_state_data.is_free = is_free;
+        if fee_in_usd.is_some() && token_state_account.data_len() < TokenState::V1_LEN {
+            msg!("The token state account uses the legacy layout which has no room for a fee override");
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+
+        token_state_data.fee_override_usd = fee_in_usd.unwrap_or(0);
+        token_state_data.has_override = fee_in_usd.is_some();
         token_state_data.pack_into_slice(&mut token_state_account.data.borrow_mut()[..]);
 
         Ok(())
     }
 
-    pub fn process_transfer_ownership(
+    pub fn process_propose_owner(
+        program_id: &Pubkey,
         accounts: &[AccountInfo],
+        new_admin: &Pubkey,
     ) -> ProgramResult {
         let accounts_iter = &mut accounts.iter();
 
-        let spl_token_account = next_account_info(accounts_iter)?;
-        let old_owner_account = next_account_info(accounts_iter)?;
-        let old_owner_token_account = next_account_info(accounts_iter)?;
-        let new_owner_account = next_account_info(accounts_iter)?;
-        let new_owner_token_account = next_account_info(accounts_iter)?;
+        let program_owner_account = next_account_info(accounts_iter)?;
+        let program_state_account = next_account_info(accounts_iter)?;
 
-        if !old_owner_account.is_signer {
-            msg!("Old owner account should be a signer");
-            return Err(ProgramError::InvalidArgument);
-        }
+        assert_writable(program_state_account, "program state account")?;
 
-        let old_owner_token_account_data = Account::unpack(&old_owner_token_account.data.borrow())?;
-        if old_owner_token_account_data.owner != *old_owner_account.key {
-            msg!("Old owner account and token account are invalid");
-            return Err(ProgramError::InvalidArgument);
-        }
+        let mut program_state_data = Self::load_global_state_as_admin(program_id, program_owner_account, program_state_account, accounts_iter.as_slice())?;
 
-        if old_owner_token_account_data.amount == 0 {
-            msg!("Old owner has no ownership");
+        if *new_admin == Pubkey::default() {
+            msg!("The new admin can not be the default pubkey");
             return Err(ProgramError::InvalidArgument);
         }
 
-        let new_owner_token_account_data = Account::unpack(&new_owner_token_account.data.borrow())?;
-        if new_owner_token_account_data.owner != *new_owner_account.key {
-            msg!("New owner account and token account are invalid");
+        program_state_data.pending_admin = *new_admin;
+        program_state_data.pack_into_slice(&mut program_state_account.data.borrow_mut()[..]);
+
+        Ok(())
+    }
+
+    pub fn process_accept_ownership(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let pending_admin_account = next_account_info(accounts_iter)?;
+        let program_state_account = next_account_info(accounts_iter)?;
+
+        assert_writable(program_state_account, "program state account")?;
+
+        let mut program_state_data = load_global_state(program_id, program_state_account)?;
+
+        assert_authority_signed(pending_admin_account, accounts_iter.as_slice())?;
+
+        if program_state_data.pending_admin == Pubkey::default()
+            || program_state_data.pending_admin != *pending_admin_account.key
+        {
+            msg!("Pending admin account is not the proposed admin of the program");
             return Err(ProgramError::InvalidArgument);
         }
 
-        let transfer_owner_token = transfer(
-            spl_token_account.key,
-            old_owner_token_account.key,
-            new_owner_token_account.key,
-            old_owner_account.key,
-            &[],
-            1,
-        )?;
+        let previous_admin = program_state_data.admin;
+        program_state_data.admin = program_state_data.pending_admin;
+        program_state_data.pending_admin = Pubkey::default();
+        program_state_data.pack_into_slice(&mut program_state_account.data.borrow_mut()[..]);
 
-        invoke(
-            &transfer_owner_token,
-            &[
-                old_owner_token_account.clone(),
-                new_owner_token_account.clone(),
-                spl_token_account.clone(),
-                old_owner_account.clone(),
-            ],
-        )?;
+        emit(&OwnershipTransferred {
+            previous_admin,
+            new_admin: program_state_data.admin,
+        });
+        Ok(())
+    }
+
+    pub fn process_cancel_ownership_transfer(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let program_owner_account = next_account_info(accounts_iter)?;
+        let program_state_account = next_account_info(accounts_iter)?;
+
+        assert_writable(program_state_account, "program state account")?;
+
+        let mut program_state_data = Self::load_global_state_as_admin(program_id, program_owner_account, program_state_account, accounts_iter.as_slice())?;
+
+        program_state_data.pending_admin = Pubkey::default();
+        program_state_data.pack_into_slice(&mut program_state_account.data.borrow_mut()[..]);
 
         Ok(())
     }
@@ -954,19 +5159,60 @@ impl Processor {
                 msg!("Instruction: Init");
                 Self::process_init(program_id, accounts, seeds, number_of_schedules)
             }
-            LockTokenInstruction::Unlock { seeds } => {
+            LockTokenInstruction::CreateNative {
+                seeds,
+                destination_address,
+                schedules,
+            } => {
+                msg!("Instruction: Create Native Schedule");
+                Self::process_create_native(
+                    program_id,
+                    accounts,
+                    seeds,
+                    &destination_address,
+                    schedules,
+                )
+            }
+            LockTokenInstruction::Unlock { seeds, deadline } => {
                 msg!("Instruction: Unlock");
-                Self::process_unlock(program_id, accounts, seeds)
+                Self::process_unlock(program_id, accounts, seeds, deadline)
+            }
+            LockTokenInstruction::UnlockAmount { seeds, amount } => {
+                msg!("Instruction: Unlock Amount");
+                Self::process_unlock_amount(program_id, accounts, seeds, amount)
             }
             LockTokenInstruction::TransferLocks { seeds } => {
                 msg!("Instruction: Transfer Locks");
                 Self::process_transfer_locks(program_id, accounts, seeds)
             }
+            LockTokenInstruction::ProposeTransferLocks { seeds, new_destination } => {
+                msg!("Instruction: Propose Transfer Locks");
+                Self::process_propose_transfer_locks(program_id, accounts, seeds, &new_destination)
+            }
+            LockTokenInstruction::AcceptTransferLocks { seeds } => {
+                msg!("Instruction: Accept Transfer Locks");
+                Self::process_accept_transfer_locks(program_id, accounts, seeds)
+            }
+            LockTokenInstruction::CancelTransferLocks { seeds } => {
+                msg!("Instruction: Cancel Transfer Locks");
+                Self::process_cancel_transfer_locks(program_id, accounts, seeds)
+            }
             LockTokenInstruction::Create {
                 seeds,
                 mint_address,
                 destination_token_address,
                 schedules,
+                deadline,
+                max_total,
+                is_nft,
+                is_immutable,
+                pay_fee_in_usd,
+                pay_fee_in_token,
+                with_fee_discount,
+                time_basis,
+                split_destinations,
+                with_wallet_state,
+                min_claim_interval_secs,
             } => {
                 msg!("Instruction: Create Schedule");
                 Self::process_create(
@@ -976,8 +5222,211 @@ impl Processor {
                     &mint_address,
                     &destination_token_address,
                     schedules,
+                    deadline,
+                    max_total,
+                    is_nft,
+                    is_immutable,
+                    pay_fee_in_usd,
+                    pay_fee_in_token,
+                    with_fee_discount,
+                    time_basis,
+                    split_destinations,
+                    with_wallet_state,
+                    min_claim_interval_secs,
+                )
+            }
+            LockTokenInstruction::CreateRecurring {
+                seeds,
+                mint_address,
+                destination_token_address,
+                start_time,
+                period_secs,
+                periods,
+                amount_per_period,
+            } => {
+                msg!("Instruction: Create Recurring Schedule");
+                let schedules = recurring_schedules(start_time, period_secs, periods, amount_per_period)?;
+                Self::process_create(
+                    program_id,
+                    accounts,
+                    seeds,
+                    &mint_address,
+                    &destination_token_address,
+                    schedules,
+                    0,
+                    None,
+                    false,
+                    false,
+                    false,
+                    false,
+                    false,
+                    TimeBasis::UnixTimestamp,
+                    Vec::new(),
+                    false,
+                    0,
+                )
+            }
+            LockTokenInstruction::CreateVesting {
+                seeds,
+                mint_address,
+                destination_token_address,
+                cliff_time,
+                cliff_bps,
+                vesting_end,
+                total_amount,
+            } => {
+                msg!("Instruction: Create Vesting");
+                Self::process_create_vesting(
+                    program_id,
+                    accounts,
+                    seeds,
+                    &mint_address,
+                    &destination_token_address,
+                    cliff_time,
+                    cliff_bps,
+                    vesting_end,
+                    total_amount,
+                )
+            }
+            LockTokenInstruction::BatchUnlock { seeds_list } => {
+                msg!("Instruction: Batch Unlock");
+                Self::process_batch_unlock(program_id, accounts, &seeds_list)
+            }
+            LockTokenInstruction::MigrateLock { seeds } => {
+                msg!("Instruction: Migrate Lock");
+                Self::process_migrate_lock(program_id, accounts, seeds)
+            }
+            LockTokenInstruction::ScheduleEmergencyUnlock {
+                seeds,
+                rescue_destination,
+            } => {
+                msg!("Instruction: Schedule Emergency Unlock");
+                Self::process_schedule_emergency_unlock(program_id, accounts, seeds, &rescue_destination)
+            }
+            LockTokenInstruction::ExecuteEmergencyUnlock { seeds } => {
+                msg!("Instruction: Execute Emergency Unlock");
+                Self::process_execute_emergency_unlock(program_id, accounts, seeds)
+            }
+            LockTokenInstruction::CancelEmergencyUnlock { seeds } => {
+                msg!("Instruction: Cancel Emergency Unlock");
+                Self::process_cancel_emergency_unlock(program_id, accounts, seeds)
+            }
+            LockTokenInstruction::SweepExcess { seeds } => {
+                msg!("Instruction: Sweep Excess");
+                Self::process_sweep_excess(program_id, accounts, seeds)
+            }
+            LockTokenInstruction::CreateMulti {
+                seeds,
+                mint_address,
+                destination_token_address,
+                schedules,
+            } => {
+                msg!("Instruction: Create Multi");
+                Self::process_create_multi(
+                    program_id,
+                    accounts,
+                    seeds,
+                    &mint_address,
+                    &destination_token_address,
+                    schedules,
                 )
             }
+            LockTokenInstruction::GetClaimableAmount { seeds } => {
+                msg!("Instruction: Get Claimable Amount");
+                Self::process_get_claimable_amount(program_id, accounts, seeds)
+            }
+            LockTokenInstruction::SetLockMetadata { seeds, name, uri } => {
+                msg!("Instruction: Set Lock Metadata");
+                Self::process_set_lock_metadata(program_id, accounts, seeds, name, uri)
+            }
+            LockTokenInstruction::CloseLockMetadata { seeds } => {
+                msg!("Instruction: Close Lock Metadata");
+                Self::process_close_lock_metadata(program_id, accounts, seeds)
+            }
+            LockTokenInstruction::ReclaimUninitializedLock { seeds } => {
+                msg!("Instruction: Reclaim Uninitialized Lock");
+                Self::process_reclaim_uninitialized_lock(program_id, accounts, seeds)
+            }
+            LockTokenInstruction::SetFeeExemptWallet { wallet, is_exempt } => {
+                msg!("Instruction: Set Fee Exempt Wallet");
+                Self::process_set_fee_exempt_wallet(program_id, accounts, &wallet, is_exempt)
+            }
+            LockTokenInstruction::SetMaxLockDuration { max_lock_duration_secs } => {
+                msg!("Instruction: Set Max Lock Duration");
+                Self::process_set_max_lock_duration(program_id, accounts, max_lock_duration_secs)
+            }
+            LockTokenInstruction::TransferScheduleIndices {
+                seeds,
+                new_seeds,
+                indices,
+            } => {
+                msg!("Instruction: Transfer Schedule Indices");
+                Self::process_transfer_schedule_indices(program_id, accounts, seeds, new_seeds, &indices)
+            }
+            LockTokenInstruction::ApproveLockDelegate { seeds, delegate } => {
+                msg!("Instruction: Approve Lock Delegate");
+                Self::process_approve_lock_delegate(program_id, accounts, seeds, &delegate)
+            }
+            LockTokenInstruction::RevokeLockDelegate { seeds } => {
+                msg!("Instruction: Revoke Lock Delegate");
+                Self::process_revoke_lock_delegate(program_id, accounts, seeds)
+            }
+            LockTokenInstruction::ClaimAndClose { seeds } => {
+                msg!("Instruction: Claim And Close");
+                Self::process_claim_and_close(program_id, accounts, seeds)
+            }
+            LockTokenInstruction::SnapshotMintLocked { mint } => {
+                msg!("Instruction: Snapshot Mint Locked");
+                Self::process_snapshot_mint_locked(program_id, accounts, &mint)
+            }
+            LockTokenInstruction::UnlockWithPermit { seeds, expiry } => {
+                msg!("Instruction: Unlock With Permit");
+                Self::process_unlock_with_permit(program_id, accounts, seeds, expiry)
+            }
+            LockTokenInstruction::InitDerived {
+                creator,
+                mint_address,
+                nonce,
+                number_of_schedules,
+            } => {
+                msg!("Instruction: Init Derived");
+                Self::process_init_derived(program_id, accounts, &creator, &mint_address, nonce, number_of_schedules)
+            }
+            LockTokenInstruction::SetOwnerTokenMint { new_mint } => {
+                msg!("Instruction: Set Owner Token Mint");
+                Self::process_set_owner_token_mint(program_id, accounts, &new_mint)
+            }
+            LockTokenInstruction::ProposeCandidateWallets { candidate_wallets } => {
+                msg!("Instruction: Propose Candidate Wallets");
+                Self::process_propose_candidate_wallets(program_id, accounts, &candidate_wallets)
+            }
+            LockTokenInstruction::AcceptCandidateWallets {} => {
+                msg!("Instruction: Accept Candidate Wallets");
+                Self::process_accept_candidate_wallets(program_id, accounts)
+            }
+            LockTokenInstruction::SetFeeBpsInToken { fee_bps_in_token } => {
+                msg!("Instruction: Set Fee Bps In Token");
+                Self::process_set_fee_bps_in_token(program_id, accounts, fee_bps_in_token)
+            }
+            LockTokenInstruction::SetFeeDiscountTiers { fee_discount_tiers } => {
+                msg!("Instruction: Set Fee Discount Tiers");
+                Self::process_set_fee_discount_tiers(program_id, accounts, &fee_discount_tiers)
+            }
+            LockTokenInstruction::Relock {
+                seeds,
+                index,
+                new_release_time,
+            } => {
+                msg!("Instruction: Relock");
+                Self::process_relock(program_id, accounts, seeds, index, new_release_time)
+            }
+            LockTokenInstruction::ExtendLockDurations {
+                seeds,
+                extensions,
+            } => {
+                msg!("Instruction: Extend Lock Durations");
+                Self::process_extend_lock_durations(program_id, accounts, seeds, &extensions)
+            }
             LockTokenInstruction::ExtendLockDuration {
                 seeds,
                 index,
@@ -993,13 +5442,13 @@ impl Processor {
                 )
             }
             LockTokenInstruction::PauseContract {
-                is_pause,
+                pause_flags,
             } => {
-                msg!("Instruction: Pause program: {}", is_pause);
+                msg!("Instruction: Pause program: {:#05b}", pause_flags);
                 Self::process_pause_contract(
                     program_id,
                     accounts,
-                    is_pause
+                    pause_flags
                 )
             }
             LockTokenInstruction::SetFeeParams {
@@ -1007,6 +5456,8 @@ impl Processor {
                 usd_token_address,
                 fees_in_usd,
                 company_wallet,
+                candidate_wallets,
+                present_fields,
             } => {
                 msg!("Instruction: Set Fee Params");
                 Self::process_set_fee_params(
@@ -1016,6 +5467,8 @@ impl Processor {
                     &usd_token_address,
                     fees_in_usd,
                     &company_wallet,
+                    &candidate_wallets,
+                    present_fields,
                 )
             }
             LockTokenInstruction::SetFeesInUSD {
@@ -1028,6 +5481,16 @@ impl Processor {
                     fees_in_usd,
                 )
             }
+            LockTokenInstruction::SetMaxPriceAge {
+                max_price_age_slots,
+            } => {
+                msg!("Instruction: Set Max Price Age");
+                Self::process_set_max_price_age(
+                    program_id,
+                    accounts,
+                    max_price_age_slots,
+                )
+            }
             LockTokenInstruction::SetCompanyWallet {
                 company_wallet,
             } => {
@@ -1050,9 +5513,61 @@ impl Processor {
                     is_free,
                 )
             }
-            LockTokenInstruction::TransferOwnership {} => {
-                msg!("Instruction: Transfer Ownership");
-                Self::process_transfer_ownership(
+            LockTokenInstruction::InitializeGlobalState {
+                admin,
+            } => {
+                msg!("Instruction: Initialize Global State");
+                Self::process_initialize_global_state(
+                    program_id,
+                    accounts,
+                    &admin,
+                )
+            }
+            LockTokenInstruction::SetLockMinimums {
+                min_lock_duration_secs,
+                min_lock_amount,
+            } => {
+                msg!("Instruction: Set Lock Minimums");
+                Self::process_set_lock_minimums(
+                    program_id,
+                    accounts,
+                    min_lock_duration_secs,
+                    min_lock_amount,
+                )
+            }
+            LockTokenInstruction::SetTokenFee {
+                mint_address,
+                fee_in_usd,
+            } => {
+                msg!("Instruction: Set Token Fee");
+                Self::process_set_token_fee(
+                    program_id,
+                    accounts,
+                    &mint_address,
+                    fee_in_usd,
+                )
+            }
+            LockTokenInstruction::ProposeOwner {
+                new_admin,
+            } => {
+                msg!("Instruction: Propose Owner");
+                Self::process_propose_owner(
+                    program_id,
+                    accounts,
+                    &new_admin,
+                )
+            }
+            LockTokenInstruction::AcceptOwnership {} => {
+                msg!("Instruction: Accept Ownership");
+                Self::process_accept_ownership(
+                    program_id,
+                    accounts,
+                )
+            }
+            LockTokenInstruction::CancelOwnershipTransfer {} => {
+                msg!("Instruction: Cancel Ownership Transfer");
+                Self::process_cancel_ownership_transfer(
+                    program_id,
                     accounts,
                 )
             }
@@ -1067,6 +5582,58 @@ impl PrintProgramError for LockTokenError {
     {
         match self {
             LockTokenError::InvalidInstruction => msg!("Error: Invalid instruction!"),
+            LockTokenError::AccountNotWritable => msg!("Error: Account not writable!"),
+            LockTokenError::UnsupportedTokenExtension => msg!("Error: Unsupported token extension!"),
+            LockTokenError::TransactionExpired => msg!("Error: Transaction expired!"),
+            LockTokenError::InvalidOracleAccount => msg!("Error: Invalid oracle account!"),
+            LockTokenError::StaleOraclePrice => msg!("Error: Stale oracle price!"),
+            LockTokenError::InvalidOraclePrice => msg!("Error: Invalid oracle price!"),
+            LockTokenError::MathOverflow => msg!("Error: Math overflow!"),
+            LockTokenError::WalletNotWhitelisted => msg!("Error: Wallet not whitelisted!"),
+            LockTokenError::CandidateWalletsLocked => msg!("Error: Candidate wallets locked!"),
+            LockTokenError::TotalExceedsAuthorized => msg!("Error: Total exceeds authorized amount!"),
+            LockTokenError::WouldViolateOrdering => msg!("Error: Would violate schedule ordering!"),
+            LockTokenError::LockDurationTooShort => msg!("Error: Lock duration too short!"),
+            LockTokenError::LockAmountTooSmall => msg!("Error: Lock amount too small!"),
+            LockTokenError::DestinationMintMismatch => msg!("Error: Destination mint mismatch!"),
+            LockTokenError::DestinationFrozen => msg!("Error: Destination frozen!"),
+            LockTokenError::EmptySchedules => msg!("Error: Empty schedules!"),
+            LockTokenError::SchedulesNotSorted => msg!("Error: Schedules not sorted!"),
+            LockTokenError::ZeroAmountSchedule => msg!("Error: Zero amount schedule!"),
+            LockTokenError::ReleaseTimeInPast => msg!("Error: Release time in past!"),
+            LockTokenError::InvalidTimestamp => msg!("Error: Invalid timestamp!"),
+            LockTokenError::ReleaseTimeTooFar => msg!("Error: Release time too far!"),
+            LockTokenError::SourceMintMismatch => msg!("Error: Source mint mismatch!"),
+            LockTokenError::VaultMintMismatch => msg!("Error: Vault mint mismatch!"),
+            LockTokenError::CompanyWalletMismatch => msg!("Error: Company wallet mismatch!"),
+            LockTokenError::DuplicateAccount => msg!("Error: Duplicate account!"),
+            LockTokenError::InvalidBasisPoints => msg!("Error: Invalid basis points!"),
+            LockTokenError::NotAnNft => msg!("Error: Not an NFT!"),
+            LockTokenError::LockFullyUnlocked => msg!("Error: Lock fully unlocked!"),
+            LockTokenError::TooManySchedules => msg!("Error: Too many schedules!"),
+            LockTokenError::EscrowFrozen => msg!("Error: Escrow frozen!"),
+            LockTokenError::SourceAuthorityMismatch => msg!("Error: Source authority mismatch!"),
+            LockTokenError::InsufficientDelegatedAmount => msg!("Error: Insufficient delegated amount!"),
+            LockTokenError::ImmutableLock => msg!("Error: Immutable lock!"),
+            LockTokenError::EmergencyUnlockTimelock => msg!("Error: Emergency unlock timelock!"),
+            LockTokenError::CandidateWalletsTimelock => msg!("Error: Candidate wallets timelock!"),
+            LockTokenError::NoExcessTokens => msg!("Error: No excess tokens!"),
+            LockTokenError::FeeMintMismatch => msg!("Error: Fee token mint mismatch!"),
+            LockTokenError::MultiDestinationLock => msg!("Error: Multi destination lock!"),
+            LockTokenError::InvalidSplit => msg!("Error: Invalid split!"),
+            LockTokenError::LockNameTooLong => msg!("Error: Lock name too long!"),
+            LockTokenError::LockUriTooLong => msg!("Error: Lock URI too long!"),
+            LockTokenError::DurationTooLong => msg!("Error: Duration too long!"),
+            LockTokenError::PermitExpired => msg!("Error: Permit expired!"),
+            LockTokenError::InvalidPermit => msg!("Error: Invalid permit!"),
+            LockTokenError::ClaimTooSoon => msg!("Error: Claim too soon!"),
+            LockTokenError::InvalidGlobalState => msg!("Error: Invalid global state!"),
+            LockTokenError::GlobalStateUninitialized => msg!("Error: Global state uninitialized!"),
+            LockTokenError::ContractPaused => msg!("Error: Contract paused!"),
+            LockTokenError::NotAdmin => msg!("Error: Not admin!"),
+            LockTokenError::InvalidLockingAccount => msg!("Error: Invalid locking account!"),
+            LockTokenError::InsufficientUnlocked => msg!("Error: Not enough has matured!"),
+            LockTokenError::InvalidClaimCursor => msg!("Error: Invalid claim cursor!"),
         }
     }
 }
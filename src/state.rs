@@ -1,12 +1,115 @@
 use solana_program::{
+    entrypoint::MAX_PERMITTED_DATA_INCREASE,
+    msg,
     program_error::ProgramError,
     program_pack::{IsInitialized, Pack, Sealed},
     pubkey::Pubkey,
 };
 
+use spl_token::native_mint;
+
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+
 use std::convert::TryInto;
 
-pub const OWNER_TOKEN_MINT_ADDRESS: &str = "Token address";
+use crate::{
+    error::LockTokenError,
+    oracle::OraclePrice,
+    pda::LockSigner,
+    time::{is_matured, TimeBasis},
+};
+
+// Seed of the program state account
+pub const GLOBAL_STATE_SEED: &[u8] = b"global_state";
+
+// Seed the program state account of the version 1 layout was created with, before the admin was stored in it
+pub const LEGACY_GLOBAL_STATE_SEED: &[u8] = b"Token address";
+
+// Seed of the metadata account of a lock, followed by the locking account key
+pub const LOCK_METADATA_SEED: &[u8] = b"lock_metadata";
+
+// Seed of the wallet state account of a wallet, followed by the wallet key
+pub const WALLET_STATE_SEED: &[u8] = b"wallet_state";
+
+// Longest name and URI of a lock metadata account, in bytes of UTF-8
+pub const MAX_LOCK_NAME_LEN: usize = 32;
+pub const MAX_LOCK_URI_LEN: usize = 200;
+
+// Decimals of fees_in_usd
+pub const USD_DECIMALS: u32 = 6;
+
+pub const MAX_CANDIDATE_WALLETS: usize = 4;
+
+// Version of the global state layout written by this program.
+// Version 1 is the original layout, which has no version byte and is only recognized by its length.
+// Version 2 is the V2_LEN layout, which has no token fee, version 3 the V3_LEN layout,
+// which has no fee discount tiers, version 4 the V4_LEN layout, which has no maximum lock duration,
+// version 5 the V5_LEN layout, which has no pause record, and version 6 the V6_LEN layout, which has no
+// proposed candidate wallets.
+pub const GLOBAL_STATE_VERSION: u8 = 7;
+
+// Bits of the PauseContract flags
+pub const PAUSE_CREATE: u8 = 1;
+pub const PAUSE_UNLOCK: u8 = 1 << 1;
+pub const PAUSE_TRANSFER: u8 = 1 << 2;
+
+// Bits of the SetFeeParams present fields, the fields of the instruction it sets
+pub const FEE_PARAM_PRICE_ESTIMATOR: u8 = 1;
+pub const FEE_PARAM_USD_TOKEN_ADDRESS: u8 = 1 << 1;
+pub const FEE_PARAM_FEES_IN_USD: u8 = 1 << 2;
+pub const FEE_PARAM_COMPANY_WALLET: u8 = 1 << 3;
+pub const FEE_PARAMS_ALL: u8 =
+    FEE_PARAM_PRICE_ESTIMATOR | FEE_PARAM_USD_TOKEN_ADDRESS | FEE_PARAM_FEES_IN_USD | FEE_PARAM_COMPANY_WALLET;
+
+// Bits of the flags byte of LockScheduleHeader
+pub const HEADER_FLAG_VESTING: u8 = 1;
+pub const HEADER_FLAG_NFT: u8 = 1 << 1;
+pub const HEADER_FLAG_REGISTERED: u8 = 1 << 2;
+pub const HEADER_FLAG_IMMUTABLE: u8 = 1 << 3;
+pub const HEADER_FLAG_MULTI: u8 = 1 << 4;
+pub const HEADER_FLAG_DERIVED: u8 = 1 << 5;
+
+// Version byte of the current LockScheduleHeader layout. The LEGACY_LEN layout is version 0 and
+// the V1_LEN layout version 1, neither of which stores the byte. The V2_LEN layout is version 2,
+// the V3_LEN layout version 3, the V4_LEN layout version 4, the V5_LEN layout version 5, the V6_LEN
+// layout version 6, the V7_LEN layout version 7, the V8_LEN layout version 8 and the V9_LEN layout version 9.
+pub const HEADER_VERSION: u8 = 10;
+
+// Time between ScheduleEmergencyUnlock and the earliest ExecuteEmergencyUnlock, which the
+// beneficiary has to cancel a rescue they did not ask for
+pub const EMERGENCY_UNLOCK_DELAY_SECS: u64 = 7 * 24 * 60 * 60;
+
+// Time between ProposeCandidateWallets and the earliest AcceptCandidateWallets, so a stolen admin key can not
+// whitelist its own wallet for the fees before anyone notices
+pub const CANDIDATE_WALLETS_DELAY_SECS: u64 = 7 * 24 * 60 * 60;
+
+// Longest time PAUSE_UNLOCK blocks unlocks after paused_at, so not even the admin can freeze vested tokens for good
+pub const MAX_PAUSE_DURATION_SECS: u64 = 7 * 24 * 60 * 60;
+// Time after the MAX_PAUSE_DURATION_SECS of a pause before a new pause gets a new start. Pauses in between keep
+// the start of the expired one, so resuming and pausing again can not chain unlock pauses.
+pub const PAUSE_COOLDOWN_SECS: u64 = 7 * 24 * 60 * 60;
+
+pub const MAX_BPS: u16 = 10_000;
+// Highest share of the locked tokens SetFeeBpsInToken accepts
+pub const MAX_FEE_BPS_IN_TOKEN: u16 = 500;
+
+// Tiers of the Create fee discount for holders of the owner token, see SetFeeDiscountTiers
+pub const MAX_FEE_DISCOUNT_TIERS: usize = 3;
+
+// Destinations a lock can split what it releases across, see LockScheduleHeader::split_destinations
+pub const MAX_SPLIT_DESTINATIONS: usize = 4;
+
+// Compute unit ceilings for programs that CPI into this one and need to size their own budget.
+// They cover the worst case, which is the largest number of schedules that fits in a single transaction.
+// Unlock and TransferLocks scale with the schedules already stored in the locking account.
+pub const MAX_CU_CREATE: u64 = 120_000;
+pub const MAX_CU_CREATE_NATIVE: u64 = 140_000;
+pub const MAX_CU_UNLOCK: u64 = 100_000;
+pub const MAX_CU_TRANSFER_LOCKS: u64 = 20_000;
+pub const MAX_CU_EXTEND_LOCK_DURATION: u64 = 30_000;
+// Every admin instruction only rewrites the global state or a token state
+pub const MAX_CU_ADMIN: u64 = 20_000;
 
 #[derive(Debug, PartialEq)]
 pub struct LockGlobalState {
@@ -14,34 +117,209 @@ pub struct LockGlobalState {
     pub usd_token_address: Pubkey,
     pub fees_in_usd: u64,
     pub company_wallet: Pubkey,
-    pub is_paused: bool,
+    pub max_price_age_slots: u64,
+    pub candidate_wallets: [Pubkey; MAX_CANDIDATE_WALLETS],
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+    // Zero disables the minimum
+    pub min_lock_duration_secs: u64,
+    pub min_lock_amount: u64,
+    // Basis points of every schedule taken as fee when Create pays the fee in the locked token.
+    // Zero disables paying in the locked token.
+    pub fee_bps_in_token: u16,
+    // Create fees are discounted for fee payers holding this token, see fee_discount_bps
+    pub owner_token_mint: Pubkey,
+    pub fee_discount_tiers: [FeeDiscountTier; MAX_FEE_DISCOUNT_TIERS],
+    // Latest release time Create and ExtendLockDuration accept, counted from now in the time basis of the lock.
+    // Zero leaves lock durations uncapped.
+    pub max_lock_duration_secs: u64,
+    // Unix timestamp the latest pause started at and the admin who started it, zero and the default pubkey
+    // until the first pause. Resuming keeps them, and a pause only gets a new start PAUSE_COOLDOWN_SECS after
+    // the previous one stopped blocking unlocks, see pause_started_at. Zero for pauses of older layouts.
+    // A u64 like every other timestamp of the program, see time::now_u64.
+    pub paused_at: u64,
+    pub paused_by: Pubkey,
+    // Candidate wallets proposed by ProposeCandidateWallets, which replace candidate_wallets once AcceptCandidateWallets
+    // is called at candidate_wallets_execute_after or later. Zero when nothing is proposed.
+    pub pending_candidate_wallets: [Pubkey; MAX_CANDIDATE_WALLETS],
+    pub candidate_wallets_execute_after: u64,
+    // Blocks Init, Create and CreateNative
+    pub pause_create: bool,
+    // Blocks Unlock, which also freezes tokens that are already vested. Meant for emergencies only, so it stops
+    // blocking MAX_PAUSE_DURATION_SECS after paused_at.
+    pub pause_unlock: bool,
+    // Blocks TransferLocks and ExtendLockDuration
+    pub pause_transfer: bool,
     pub is_initialized: bool,
 }
 
+// Discount of the Create fee for fee payers holding at least min_balance of the owner token.
+// Tiers with a zero discount are unused.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+pub struct FeeDiscountTier {
+    pub min_balance: u64,
+    pub discount_bps: u16,
+}
+
+// Destination receiving share_bps of every amount released by a split lock.
+// Entries with a zero share are unused.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+pub struct SplitDestination {
+    pub destination_address: Pubkey,
+    pub share_bps: u16,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct LockSchedule {
     pub release_time: u64,
     pub amount: u64,
 }
 
+// Schedule of a lock created with CreateMulti. It starts with the fields of LockSchedule, so the release time and
+// amount are read the same way at a wider stride.
+#[derive(Debug, PartialEq)]
+pub struct MultiLockSchedule {
+    pub release_time: u64,
+    pub amount: u64,
+    // The default pubkey releases the schedule to the destination of the lock
+    pub destination_address: Pubkey,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct LockScheduleHeader {
     pub destination_address: Pubkey,
     pub mint_address: Pubkey,
     pub is_initialized: bool,
+    // Destination proposed with ProposeTransferLocks, the default pubkey when there is none
+    pub pending_destination_address: Pubkey,
+    // The header is followed by a VestingSchedule instead of LockSchedules
+    pub is_vesting: bool,
+    // The lock holds a single NFT, checked on Create
+    pub is_nft: bool,
+    // The lock is counted in the token state of its mint, so unlocking it is taken off the mint total
+    pub is_registered: bool,
+    // The destination and release times can not be changed, even by the beneficiary. Set on Create only.
+    pub is_immutable: bool,
+    // The header is followed by MultiLockSchedules, set on CreateMulti only
+    pub is_multi: bool,
+    // Whether the locking account was initialized with InitDerived, see pda::find_lock_address. The creator and the
+    // mint of the derivation are creator_address and mint_address. Only locks of the V9_LEN layout and later are
+    // derived.
+    pub is_derived: bool,
+    // Amount locked on Create and amount released by unlocks since.
+    // Both are zero for locks created before the current layout, which do not track them.
+    pub total_amount: u64,
+    pub claimed_amount: u64,
+    // Number of schedules of the lock and how many of them, from the first, were unlocked.
+    // Schedules are sorted and drained in order, so Unlock starts at unlocked_count.
+    // Both are zero for vesting locks and for locks created before the current layout.
+    pub schedule_count: u32,
+    pub unlocked_count: u32,
+    // Destination of a rescue scheduled by the admin, the default pubkey when there is none,
+    // and the unix timestamp from which it can be executed
+    pub rescue_destination_address: Pubkey,
+    pub rescue_execute_after: u64,
+    // Owner of the source token account of Create, to whom SweepExcess returns tokens sent to the vault on top
+    // of the schedules. The default pubkey for locks created before the current layout.
+    pub creator_address: Pubkey,
+    // What the release times of the schedules count, set on Create only. Unix timestamps for locks created
+    // before the V4_LEN layout. Emergency unlocks are timed in unix timestamps whatever the basis.
+    pub time_basis: TimeBasis,
+    // Destinations every unlock is split across, set on Create only. The first one is the destination of the lock
+    // and receives the rounding dust. Unused for locks which are not split and for locks created before the
+    // current layout.
+    pub split_destinations: [SplitDestination; MAX_SPLIT_DESTINATIONS],
+    // Key approved with ApproveLockDelegate which may call TransferLocks for the beneficiary, the default pubkey when
+    // there is none. Cleared whenever the destination changes. Unused for locks created before the V6_LEN layout.
+    pub delegate_address: Pubkey,
+    // Nonce the next UnlockWithPermit has to be signed for, incremented by every permit used.
+    // Unused for locks created before the V7_LEN layout.
+    pub permit_nonce: u64,
+    // Least time between two Unlocks, in the time basis of the lock, set on Create only. Zero when claims are not
+    // rate limited and for locks created before the current layout.
+    pub min_claim_interval_secs: u32,
+    // Time of the last Unlock which released tokens, in the time basis of the lock. Zero before the first one.
+    pub last_claim_ts: u64,
+    // Nonce and bump seed of the derivation of derived locks, zero for the others
+    pub lock_nonce: u64,
+    pub lock_bump: u8,
+    // Unix timestamp of Create. Zero for locks created before the current layout, migrated ones included, whose
+    // creation time is unknown.
+    pub created_at: i64,
+}
+
+// Cliff plus linear vesting: cliff_bps of the total is released at cliff_time and the rest
+// pro rata until vesting_end
+#[derive(Debug, PartialEq)]
+pub struct VestingSchedule {
+    pub cliff_time: u64,
+    pub vesting_end: u64,
+    pub total_amount: u64,
+    pub claimed_amount: u64,
+    pub cliff_bps: u16,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct TokenState {
     pub mint_address: Pubkey,
     pub is_free: bool,
+    pub fee_override_usd: u64,
+    pub has_override: bool,
+    // Locks of the mint created since the token state counts them, and what they still hold
+    pub active_locks: u32,
+    pub total_locked_amount: u64,
+    // What the locking token accounts passed to the latest SnapshotMintLocked held, and the unix timestamp it ran at.
+    // Zero until the first snapshot.
+    pub last_snapshot_amount: u64,
+    pub last_snapshot_ts: i64,
+    pub is_initialized: bool,
+}
+
+// What a lock still holds after Unlock, which returns it as return data so wallets need not decode the
+// locking account again. next_release_time is zero once nothing is left, and for a vesting lock it is the
+// cliff time until the cliff and the vesting end after it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RemainingSchedules {
+    pub remaining_locked: u64,
+    // Schedules which still hold an amount
+    pub schedule_count: u32,
+    pub next_release_time: u64,
+}
+
+// Wallets which never pay the Create fee, whatever the mint, see SetFeeExemptWallet
+#[derive(Debug, Default, PartialEq)]
+pub struct WalletState {
+    pub wallet_address: Pubkey,
+    pub is_fee_exempt: bool,
+    pub is_initialized: bool,
+}
+
+// Name and link shown for a lock by explorers, see SetLockMetadata
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LockMetadata {
+    pub locking_account: Pubkey,
+    pub name: String,
+    pub uri: String,
     pub is_initialized: bool,
 }
 
 impl Sealed for LockScheduleHeader {}
 
+// Locking accounts created before pending transfers were added have a LEGACY_LEN header, those
+// created before the totals were added a V1_LEN header ending with the flags byte, those created
+// before the schedule counters were added a V2_LEN header, those created before emergency unlocks
+// and the creator were added a V3_LEN header, those created before split destinations were added
+// a V4_LEN header, those created before lock delegates were added a V5_LEN header, those created before
+// claim permits were added a V6_LEN header, those created before claim cooldowns were added a V7_LEN header,
+// those created before derived locking accounts were added a V8_LEN header and those created before the creation
+// time was recorded a V9_LEN header.
+// Older headers are read with the missing fields unset and written back in their own layout.
+// The last byte of every newer layout makes the header lengths leave different remainders
+// modulo LockSchedule::LEN, so the layout can be told from the account length alone.
 impl Pack for LockScheduleHeader {
-    const LEN: usize = 65;
+    const LEN: usize = 407;
 
     fn pack_into_slice(&self, target: &mut [u8]) {
         let destination_address_bytes = self.destination_address.to_bytes();
@@ -55,29 +333,390 @@ impl Pack for LockScheduleHeader {
         }
 
         target[64] = self.is_initialized as u8;
+
+        if target.len() < Self::V1_LEN {
+            return;
+        }
+
+        target[65..97].copy_from_slice(self.pending_destination_address.as_ref());
+        let mut flags = 0;
+        if self.is_vesting {
+            flags |= HEADER_FLAG_VESTING;
+        }
+        if self.is_nft {
+            flags |= HEADER_FLAG_NFT;
+        }
+        if self.is_registered {
+            flags |= HEADER_FLAG_REGISTERED;
+        }
+        if self.is_immutable {
+            flags |= HEADER_FLAG_IMMUTABLE;
+        }
+        if self.is_multi {
+            flags |= HEADER_FLAG_MULTI;
+        }
+        if self.is_derived {
+            flags |= HEADER_FLAG_DERIVED;
+        }
+        target[97] = flags;
+
+        if target.len() < Self::V2_LEN {
+            return;
+        }
+
+        target[98..106].copy_from_slice(&self.total_amount.to_le_bytes());
+        target[106..114].copy_from_slice(&self.claimed_amount.to_le_bytes());
+
+        // The version bytes of the older layouts are left as they were written on Create
+        if target.len() < Self::V3_LEN {
+            return;
+        }
+
+        target[114..118].copy_from_slice(&self.schedule_count.to_le_bytes());
+        target[118..122].copy_from_slice(&self.unlocked_count.to_le_bytes());
+
+        if target.len() < Self::V4_LEN {
+            return;
+        }
+
+        target[122..154].copy_from_slice(self.rescue_destination_address.as_ref());
+        target[154..162].copy_from_slice(&self.rescue_execute_after.to_le_bytes());
+        target[162..194].copy_from_slice(self.creator_address.as_ref());
+        // Also keeps the length from leaving the remainder of V2_LEN, it was padding before slots were supported
+        target[194] = self.time_basis.to_u8();
+
+        if target.len() < Self::V5_LEN {
+            return;
+        }
+
+        for (split, dst) in self.split_destinations.iter().zip(target[195..331].chunks_exact_mut(34)) {
+            dst[..32].copy_from_slice(split.destination_address.as_ref());
+            dst[32..].copy_from_slice(&split.share_bps.to_le_bytes());
+        }
+
+        if target.len() < Self::V6_LEN {
+            return;
+        }
+
+        target[332..364].copy_from_slice(self.delegate_address.as_ref());
+        // Keeps the length from leaving the remainder of V5_LEN
+        target[364] = 0;
+
+        if target.len() < Self::V7_LEN {
+            return;
+        }
+
+        target[365..373].copy_from_slice(&self.permit_nonce.to_le_bytes());
+
+        if target.len() < Self::V8_LEN {
+            return;
+        }
+
+        target[373..377].copy_from_slice(&self.min_claim_interval_secs.to_le_bytes());
+        target[377..385].copy_from_slice(&self.last_claim_ts.to_le_bytes());
+        // Keeps the length from leaving the remainder of an older layout
+        target[385..390].fill(0);
+
+        if target.len() < Self::V9_LEN {
+            return;
+        }
+
+        target[390..398].copy_from_slice(&self.lock_nonce.to_le_bytes());
+        target[398] = self.lock_bump;
+
+        if target.len() < Self::LEN {
+            return;
+        }
+
+        target[331] = HEADER_VERSION;
+        target[399..407].copy_from_slice(&self.created_at.to_le_bytes());
     }
 
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        if src.len() < Self::LEN {
+        if src.len() < Self::LEGACY_LEN {
             return Err(ProgramError::InvalidAccountData)
         }
         let destination_address = Pubkey::new(&src[..32]);
         let mint_address = Pubkey::new(&src[32..64]);
         let is_initialized = src[64] == 1;
+        let (pending_destination_address, flags) = if src.len() >= Self::V1_LEN {
+            (Pubkey::new(&src[65..97]), src[97])
+        } else {
+            (Pubkey::default(), 0)
+        };
+        let (total_amount, claimed_amount) = if src.len() >= Self::V2_LEN {
+            (
+                u64::from_le_bytes(src[98..106].try_into().unwrap()),
+                u64::from_le_bytes(src[106..114].try_into().unwrap()),
+            )
+        } else {
+            (0, 0)
+        };
+        let (schedule_count, unlocked_count) = if src.len() >= Self::V3_LEN {
+            (
+                u32::from_le_bytes(src[114..118].try_into().unwrap()),
+                u32::from_le_bytes(src[118..122].try_into().unwrap()),
+            )
+        } else {
+            (0, 0)
+        };
+        let (rescue_destination_address, rescue_execute_after, creator_address, time_basis) = if src.len() >= Self::V4_LEN {
+            (
+                Pubkey::new(&src[122..154]),
+                u64::from_le_bytes(src[154..162].try_into().unwrap()),
+                Pubkey::new(&src[162..194]),
+                TimeBasis::from_u8(src[194]).ok_or(ProgramError::InvalidAccountData)?,
+            )
+        } else {
+            (Pubkey::default(), 0, Pubkey::default(), TimeBasis::UnixTimestamp)
+        };
+        let mut split_destinations = [SplitDestination::default(); MAX_SPLIT_DESTINATIONS];
+        if src.len() >= Self::V5_LEN {
+            for (split, entry) in split_destinations.iter_mut().zip(src[195..331].chunks_exact(34)) {
+                split.destination_address = Pubkey::new(&entry[..32]);
+                split.share_bps = u16::from_le_bytes(entry[32..].try_into().unwrap());
+            }
+        }
+        let delegate_address = if src.len() >= Self::V6_LEN {
+            Pubkey::new(&src[332..364])
+        } else {
+            Pubkey::default()
+        };
+        let permit_nonce = if src.len() >= Self::V7_LEN {
+            u64::from_le_bytes(src[365..373].try_into().unwrap())
+        } else {
+            0
+        };
+        let (min_claim_interval_secs, last_claim_ts) = if src.len() >= Self::V8_LEN {
+            (
+                u32::from_le_bytes(src[373..377].try_into().unwrap()),
+                u64::from_le_bytes(src[377..385].try_into().unwrap()),
+            )
+        } else {
+            (0, 0)
+        };
+        let (lock_nonce, lock_bump) = if src.len() >= Self::V9_LEN {
+            (u64::from_le_bytes(src[390..398].try_into().unwrap()), src[398])
+        } else {
+            (0, 0)
+        };
+        let created_at = if src.len() >= Self::LEN {
+            i64::from_le_bytes(src[399..407].try_into().unwrap())
+        } else {
+            0
+        };
         Ok(Self {
             destination_address,
             mint_address,
             is_initialized,
+            pending_destination_address,
+            is_vesting: flags & HEADER_FLAG_VESTING != 0,
+            is_nft: flags & HEADER_FLAG_NFT != 0,
+            is_registered: flags & HEADER_FLAG_REGISTERED != 0,
+            is_immutable: flags & HEADER_FLAG_IMMUTABLE != 0,
+            is_multi: flags & HEADER_FLAG_MULTI != 0,
+            is_derived: flags & HEADER_FLAG_DERIVED != 0,
+            total_amount,
+            claimed_amount,
+            schedule_count,
+            unlocked_count,
+            rescue_destination_address,
+            rescue_execute_after,
+            creator_address,
+            time_basis,
+            split_destinations,
+            delegate_address,
+            permit_nonce,
+            min_claim_interval_secs,
+            last_claim_ts,
+            lock_nonce,
+            lock_bump,
+            created_at,
         })
     }
 }
 
+impl LockScheduleHeader {
+    pub const LEGACY_LEN: usize = 65;
+    pub const V1_LEN: usize = 98;
+    pub const V2_LEN: usize = 115;
+    pub const V3_LEN: usize = 123;
+    pub const V4_LEN: usize = 196;
+    pub const V5_LEN: usize = 332;
+    pub const V6_LEN: usize = 365;
+    pub const V7_LEN: usize = 373;
+    pub const V8_LEN: usize = 390;
+    pub const V9_LEN: usize = 399;
+
+    // Length of the header of a locking account with the given data length
+    pub fn header_len(account_data_len: usize) -> usize {
+        [Self::LEN, Self::V9_LEN, Self::V8_LEN, Self::V7_LEN, Self::V6_LEN, Self::V5_LEN, Self::V4_LEN, Self::V3_LEN, Self::V2_LEN, Self::V1_LEN]
+            .iter()
+            .copied()
+            .find(|&len| account_data_len >= len && (account_data_len - len) % LockSchedule::LEN == 0)
+            .unwrap_or(Self::LEGACY_LEN)
+    }
+
+    // is_initialized is at the same offset in every layout
+    pub fn is_packed_initialized(src: &[u8]) -> bool {
+        src.get(64) == Some(&1)
+    }
+
+    // Until Create initializes the header, the first bytes of the locking account hold the payer of Init,
+    // see ReclaimUninitializedLock. Create overwrites them with the destination.
+    pub fn pack_init_payer(payer: &Pubkey, dst: &mut [u8]) {
+        dst[..32].copy_from_slice(payer.as_ref());
+    }
+
+    // The payer of Init of a locking account which has no lock yet. None for locks, and the default pubkey for
+    // locking accounts initialized before the payer was recorded.
+    pub fn unpack_init_payer(src: &[u8]) -> Option<Pubkey> {
+        if src.get(64) != Some(&0) {
+            return None;
+        }
+        src.get(..32).map(Pubkey::new)
+    }
+
+    // InitDerived records the derivation of the locking account in the header fields holding it once Create has run,
+    // so the program signs for the lock from the same bytes before and after Create. Nothing is recorded for
+    // locking accounts of raw seeds.
+    pub fn pack_init_derivation(lock_signer: &LockSigner, dst: &mut [u8]) {
+        if let LockSigner::Derived { creator, mint, nonce, bump } = lock_signer {
+            dst[32..64].copy_from_slice(mint.as_ref());
+            dst[97] = HEADER_FLAG_DERIVED;
+            dst[162..194].copy_from_slice(creator.as_ref());
+            dst[390..398].copy_from_slice(nonce);
+            dst[398] = bump[0];
+        }
+    }
+
+    // How the program signs for the locking account with the given data when it was initialized with InitDerived,
+    // whether Create has run or not. None for locking accounts of raw seeds.
+    pub fn unpack_derivation(src: &[u8]) -> Option<LockSigner> {
+        if Self::header_len(src.len()) < Self::V9_LEN || src[97] & HEADER_FLAG_DERIVED == 0 {
+            return None;
+        }
+        Some(LockSigner::derived(
+            Pubkey::new(&src[162..194]),
+            Pubkey::new(&src[32..64]),
+            u64::from_le_bytes(src[390..398].try_into().unwrap()),
+            src[398],
+        ))
+    }
+
+    pub fn has_pending_destination(&self) -> bool {
+        self.pending_destination_address != Pubkey::default()
+    }
+
+    pub fn has_pending_rescue(&self) -> bool {
+        self.rescue_destination_address != Pubkey::default()
+    }
+
+    pub fn clear_pending_rescue(&mut self) {
+        self.rescue_destination_address = Pubkey::default();
+        self.rescue_execute_after = 0;
+    }
+
+    // Split destinations in use, the first of which is the destination of the lock. Empty when the lock is not split.
+    pub fn split(&self) -> &[SplitDestination] {
+        let count = self.split_destinations.iter().take_while(|split| split.share_bps > 0).count();
+        &self.split_destinations[..count]
+    }
+
+    pub fn has_split(&self) -> bool {
+        !self.split().is_empty()
+    }
+
+    pub fn has_delegate(&self) -> bool {
+        self.delegate_address != Pubkey::default()
+    }
+
+    // The new destination of a split lock takes over the share of the previous one.
+    // The delegate was approved by the previous beneficiary and goes with them.
+    pub fn set_destination(&mut self, destination_address: Pubkey) {
+        self.destination_address = destination_address;
+        self.delegate_address = Pubkey::default();
+        if self.has_split() {
+            self.split_destinations[0].destination_address = destination_address;
+        }
+    }
+
+    // Stride of the schedules following the header
+    pub fn schedule_len(&self) -> usize {
+        if self.is_multi {
+            MultiLockSchedule::LEN
+        } else {
+            LockSchedule::LEN
+        }
+    }
+
+    // Only meaningful for headers of the current layout, see schedule_count
+    pub fn is_drained(&self) -> bool {
+        !self.is_vesting && self.unlocked_count >= self.schedule_count
+    }
+}
+
 impl IsInitialized for LockScheduleHeader {
     fn is_initialized(&self) -> bool {
         self.is_initialized
     }
 }
 
+impl Sealed for VestingSchedule {}
+
+// Padded to a multiple of LockSchedule::LEN, so LockScheduleHeader::header_len works for vesting locks too
+impl Pack for VestingSchedule {
+    const LEN: usize = 48;
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        dst[0..8].copy_from_slice(&self.cliff_time.to_le_bytes());
+        dst[8..16].copy_from_slice(&self.vesting_end.to_le_bytes());
+        dst[16..24].copy_from_slice(&self.total_amount.to_le_bytes());
+        dst[24..32].copy_from_slice(&self.claimed_amount.to_le_bytes());
+        dst[32..34].copy_from_slice(&self.cliff_bps.to_le_bytes());
+        for byte in dst[34..Self::LEN].iter_mut() {
+            *byte = 0;
+        }
+    }
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        if src.len() < Self::LEN {
+            return Err(ProgramError::InvalidAccountData)
+        }
+        Ok(Self {
+            cliff_time: u64::from_le_bytes(src[0..8].try_into().unwrap()),
+            vesting_end: u64::from_le_bytes(src[8..16].try_into().unwrap()),
+            total_amount: u64::from_le_bytes(src[16..24].try_into().unwrap()),
+            claimed_amount: u64::from_le_bytes(src[24..32].try_into().unwrap()),
+            cliff_bps: u16::from_le_bytes(src[32..34].try_into().unwrap()),
+        })
+    }
+}
+
+impl VestingSchedule {
+    // Amount vested at now. Rounds down, so the dust stays locked until vesting_end.
+    pub fn vested_amount(&self, now: u64) -> u64 {
+        if now < self.cliff_time {
+            return 0;
+        }
+        if now >= self.vesting_end {
+            return self.total_amount;
+        }
+        let total = self.total_amount as u128;
+        let cliff_amount = total * self.cliff_bps as u128 / MAX_BPS as u128;
+        let linear_amount = (total - cliff_amount) * (now - self.cliff_time) as u128
+            / (self.vesting_end - self.cliff_time) as u128;
+        (cliff_amount + linear_amount) as u64
+    }
+
+    pub fn claimable_amount(&self, now: u64) -> u64 {
+        self.vested_amount(now).saturating_sub(self.claimed_amount)
+    }
+}
+
+// Number of schedules to Init a locking account with when it is going to hold a vesting schedule
+pub const VESTING_SCHEDULE_SLOTS: usize = VestingSchedule::LEN / LockSchedule::LEN;
+
 impl Sealed for LockSchedule {}
 
 impl Pack for LockSchedule {
@@ -114,6 +753,59 @@ impl IsInitialized for LockSchedule {
     }
 }
 
+impl Sealed for MultiLockSchedule {}
+
+impl Pack for MultiLockSchedule {
+    const LEN: usize = 48;
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        dst[0..8].copy_from_slice(&self.release_time.to_le_bytes());
+        dst[8..16].copy_from_slice(&self.amount.to_le_bytes());
+        dst[16..48].copy_from_slice(self.destination_address.as_ref());
+    }
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        if src.len() < Self::LEN {
+            return Err(ProgramError::InvalidAccountData)
+        }
+        Ok(Self {
+            release_time: u64::from_le_bytes(src[0..8].try_into().unwrap()),
+            amount: u64::from_le_bytes(src[8..16].try_into().unwrap()),
+            destination_address: Pubkey::new(&src[16..48]),
+        })
+    }
+}
+
+impl MultiLockSchedule {
+    pub fn destination(&self, lock_destination: &Pubkey) -> Pubkey {
+        if self.destination_address == Pubkey::default() {
+            *lock_destination
+        } else {
+            self.destination_address
+        }
+    }
+}
+
+// Number of schedules to Init a locking account with for every schedule of a CreateMulti.
+// A multiple of LockSchedule::LEN, so LockScheduleHeader::header_len works for these locks too.
+pub const MULTI_SCHEDULE_SLOTS: usize = MultiLockSchedule::LEN / LockSchedule::LEN;
+
+pub fn get_locking_account_size(number_of_schedules: usize) -> usize {
+    LockScheduleHeader::LEN + number_of_schedules * LockSchedule::LEN
+}
+
+// The locking account is created by the program through the system program, which limits it to
+// MAX_PERMITTED_DATA_INCREASE bytes
+pub const MAX_LOCKING_ACCOUNT_SCHEDULES: usize =
+    (MAX_PERMITTED_DATA_INCREASE - LockScheduleHeader::LEN) / LockSchedule::LEN;
+
+// Schedules following the header, at the stride of the header. Only the release times and amounts are read.
+pub fn unpack_lock_schedules(header: &LockScheduleHeader, body: &[u8]) -> Result<Vec<LockSchedule>, ProgramError> {
+    body.chunks_exact(header.schedule_len())
+        .map(LockSchedule::unpack_from_slice)
+        .collect()
+}
+
 pub fn unpack_schedules(input: &[u8]) -> Result<Vec<LockSchedule>, ProgramError> {
     let number_of_schedules = input.len() / LockSchedule::LEN;
     let mut output: Vec<LockSchedule> = Vec::with_capacity(number_of_schedules);
@@ -127,6 +819,54 @@ pub fn unpack_schedules(input: &[u8]) -> Result<Vec<LockSchedule>, ProgramError>
     Ok(output)
 }
 
+// Amount an Unlock would release at now, in the time basis of the header, read from the data following it: the matured
+// schedules which still hold an amount, or what vested since the last unlock of a vesting lock
+pub fn claimable_amount(header: &LockScheduleHeader, body: &[u8], now: u64) -> Result<u64, ProgramError> {
+    if header.is_vesting {
+        return Ok(VestingSchedule::unpack_from_slice(body)?.claimable_amount(now));
+    }
+    // Locks of the older layouts can hold unsorted schedules, so every schedule is read
+    let mut amount = 0u64;
+    for slot in body.chunks_exact(header.schedule_len()) {
+        let s = LockSchedule::unpack_from_slice(slot)?;
+        if is_matured(s.release_time, now) {
+            amount = amount.checked_add(s.amount).ok_or(LockTokenError::MathOverflow)?;
+        }
+    }
+    Ok(amount)
+}
+
+// Amount the schedules of the lock still hold, read from the data following the header: what was not claimed yet of a
+// vesting lock, or the amounts of every schedule
+pub fn locked_amount(header: &LockScheduleHeader, body: &[u8]) -> Result<u64, ProgramError> {
+    if header.is_vesting {
+        let vesting = VestingSchedule::unpack_from_slice(body)?;
+        return Ok(vesting.total_amount.saturating_sub(vesting.claimed_amount));
+    }
+    let mut amount = 0u64;
+    for slot in body.chunks_exact(header.schedule_len()) {
+        let s = LockSchedule::unpack_from_slice(slot)?;
+        amount = amount.checked_add(s.amount).ok_or(LockTokenError::MathOverflow)?;
+    }
+    Ok(amount)
+}
+
+// Divides amount across the split destinations by their shares, rounded down. The first destination also receives
+// the rounding dust, so the amounts always add up to amount.
+pub fn split_amounts(amount: u64, split: &[SplitDestination]) -> Result<Vec<(Pubkey, u64)>, ProgramError> {
+    let mut amounts = Vec::with_capacity(split.len());
+    let mut rest = amount;
+    for destination in split.iter().skip(1) {
+        let share = (amount as u128 * destination.share_bps as u128 / MAX_BPS as u128) as u64;
+        rest = rest.checked_sub(share).ok_or(LockTokenError::MathOverflow)?;
+        amounts.push((destination.destination_address, share));
+    }
+    if let Some(first) = split.first() {
+        amounts.insert(0, (first.destination_address, rest));
+    }
+    Ok(amounts)
+}
+
 pub fn pack_schedules_into_slice(schedules: Vec<LockSchedule>, target: &mut [u8]) {
     let mut offset = 0;
     for s in schedules.iter() {
@@ -137,32 +877,123 @@ pub fn pack_schedules_into_slice(schedules: Vec<LockSchedule>, target: &mut [u8]
 
 impl Sealed for TokenState {}
 
+// Token state accounts created before the fee override was added are LEGACY_LEN bytes long,
+// those created before locks were counted V1_LEN bytes long and those created before snapshots V2_LEN bytes long.
+// They are read with the missing fields unset and written back in their own layout.
 impl Pack for TokenState {
-    const LEN: usize = 34;
+    const LEN: usize = 71;
 
     fn pack_into_slice(&self, target: &mut [u8]) {
         let mint_address_bytes = self.mint_address.to_bytes();
+        let fee_override_usd_bytes = self.fee_override_usd.to_le_bytes();
 
         for i in 0..32 {
             target[i] = mint_address_bytes[i];
         }
 
         target[32] = self.is_free as u8;
-        target[33] = self.is_initialized as u8;
+
+        if target.len() < Self::V1_LEN {
+            target[33] = self.is_initialized as u8;
+            return;
+        }
+
+        for i in 33..41 {
+            target[i] = fee_override_usd_bytes[i - 33];
+        }
+
+        target[41] = self.has_override as u8;
+
+        if target.len() < Self::V2_LEN {
+            target[42] = self.is_initialized as u8;
+            return;
+        }
+
+        target[42..46].copy_from_slice(&self.active_locks.to_le_bytes());
+        target[46..54].copy_from_slice(&self.total_locked_amount.to_le_bytes());
+
+        if target.len() < Self::LEN {
+            target[54] = self.is_initialized as u8;
+            return;
+        }
+
+        target[54..62].copy_from_slice(&self.last_snapshot_amount.to_le_bytes());
+        target[62..70].copy_from_slice(&self.last_snapshot_ts.to_le_bytes());
+        target[70] = self.is_initialized as u8;
     }
 
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        if src.len() < Self::LEN {
+        if src.len() < Self::LEGACY_LEN {
             return Err(ProgramError::InvalidAccountData)
         }
 
         let mint_address = Pubkey::new(&src[..32]);
         let is_free = src[32] == 1;
-        let is_initialized = src[33] == 1;
+
+        if src.len() < Self::V1_LEN {
+            let is_initialized = src[33] == 1;
+            return Ok(Self {
+                mint_address,
+                is_free,
+                fee_override_usd: 0,
+                has_override: false,
+                active_locks: 0,
+                total_locked_amount: 0,
+                last_snapshot_amount: 0,
+                last_snapshot_ts: 0,
+                is_initialized,
+            });
+        }
+
+        let fee_override_usd = u64::from_le_bytes(src[33..41].try_into().unwrap());
+        let has_override = src[41] == 1;
+
+        if src.len() < Self::V2_LEN {
+            let is_initialized = src[42] == 1;
+            return Ok(Self {
+                mint_address,
+                is_free,
+                fee_override_usd,
+                has_override,
+                active_locks: 0,
+                total_locked_amount: 0,
+                last_snapshot_amount: 0,
+                last_snapshot_ts: 0,
+                is_initialized,
+            });
+        }
+
+        let active_locks = u32::from_le_bytes(src[42..46].try_into().unwrap());
+        let total_locked_amount = u64::from_le_bytes(src[46..54].try_into().unwrap());
+
+        if src.len() < Self::LEN {
+            let is_initialized = src[54] == 1;
+            return Ok(Self {
+                mint_address,
+                is_free,
+                fee_override_usd,
+                has_override,
+                active_locks,
+                total_locked_amount,
+                last_snapshot_amount: 0,
+                last_snapshot_ts: 0,
+                is_initialized,
+            });
+        }
+
+        let last_snapshot_amount = u64::from_le_bytes(src[54..62].try_into().unwrap());
+        let last_snapshot_ts = i64::from_le_bytes(src[62..70].try_into().unwrap());
+        let is_initialized = src[70] == 1;
 
         Ok(Self {
             mint_address,
             is_free,
+            fee_override_usd,
+            has_override,
+            active_locks,
+            total_locked_amount,
+            last_snapshot_amount,
+            last_snapshot_ts,
             is_initialized,
         })
     }
@@ -175,24 +1006,228 @@ impl IsInitialized for TokenState {
 }
 
 impl TokenState {
-    pub fn estimate_fees_in_sol(&self) -> Result<u64, ProgramError> {
-        if self.is_free == false {
+    pub const LEGACY_LEN: usize = 34;
+    pub const V1_LEN: usize = 43;
+    pub const V2_LEN: usize = 55;
+
+    // The per-mint override takes precedence over the global fee
+    pub fn fees_in_usd(&self, global_fees_in_usd: u64) -> u64 {
+        if self.has_override {
+            self.fee_override_usd
+        } else {
+            global_fees_in_usd
+        }
+    }
+
+    pub fn estimate_fees_in_sol(&self, global_fees_in_usd: u64, price: &OraclePrice) -> Result<u64, ProgramError> {
+        if self.is_free {
             return Ok(0);
         }
-        Ok(100)
+        usd_to_lamports(self.fees_in_usd(global_fees_in_usd), price)
+    }
+}
+
+impl Sealed for LockMetadata {}
+
+// The name and the URI are stored as a length byte followed by the UTF-8 bytes, in slots of their maximum length
+// so updates never resize the account
+impl Pack for LockMetadata {
+    const LEN: usize = 32 + 1 + MAX_LOCK_NAME_LEN + 1 + MAX_LOCK_URI_LEN + 1;
+
+    fn pack_into_slice(&self, target: &mut [u8]) {
+        let uri_offset = 33 + MAX_LOCK_NAME_LEN;
+        target[..32].copy_from_slice(self.locking_account.as_ref());
+        target[32] = self.name.len() as u8;
+        target[33..uri_offset].fill(0);
+        target[33..33 + self.name.len()].copy_from_slice(self.name.as_bytes());
+        target[uri_offset] = self.uri.len() as u8;
+        target[uri_offset + 1..Self::LEN - 1].fill(0);
+        target[uri_offset + 1..uri_offset + 1 + self.uri.len()].copy_from_slice(self.uri.as_bytes());
+        target[Self::LEN - 1] = self.is_initialized as u8;
+    }
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        if src.len() < Self::LEN {
+            return Err(ProgramError::InvalidAccountData)
+        }
+
+        let uri_offset = 33 + MAX_LOCK_NAME_LEN;
+        let name_len = src[32] as usize;
+        let uri_len = src[uri_offset] as usize;
+        if name_len > MAX_LOCK_NAME_LEN || uri_len > MAX_LOCK_URI_LEN {
+            return Err(ProgramError::InvalidAccountData)
+        }
+        let name = std::str::from_utf8(&src[33..33 + name_len]).map_err(|_| ProgramError::InvalidAccountData)?;
+        let uri = std::str::from_utf8(&src[uri_offset + 1..uri_offset + 1 + uri_len])
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        Ok(Self {
+            locking_account: Pubkey::new(&src[..32]),
+            name: name.to_string(),
+            uri: uri.to_string(),
+            is_initialized: src[Self::LEN - 1] == 1,
+        })
+    }
+}
+
+impl IsInitialized for LockMetadata {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl LockMetadata {
+    // Fails with LockNameTooLong or LockUriTooLong when the name or the URI does not fit
+    pub fn assert_valid_lengths(name: &str, uri: &str) -> Result<(), ProgramError> {
+        if name.len() > MAX_LOCK_NAME_LEN {
+            msg!("The name takes {} bytes, more than {}", name.len(), MAX_LOCK_NAME_LEN);
+            return Err(LockTokenError::LockNameTooLong.into());
+        }
+        if uri.len() > MAX_LOCK_URI_LEN {
+            msg!("The URI takes {} bytes, more than {}", uri.len(), MAX_LOCK_URI_LEN);
+            return Err(LockTokenError::LockUriTooLong.into());
+        }
+        Ok(())
+    }
+}
+
+impl Sealed for RemainingSchedules {}
+
+impl Pack for RemainingSchedules {
+    const LEN: usize = 20;
+
+    fn pack_into_slice(&self, target: &mut [u8]) {
+        target[..8].copy_from_slice(&self.remaining_locked.to_le_bytes());
+        target[8..12].copy_from_slice(&self.schedule_count.to_le_bytes());
+        target[12..20].copy_from_slice(&self.next_release_time.to_le_bytes());
+    }
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        if src.len() < Self::LEN {
+            return Err(ProgramError::InvalidAccountData)
+        }
+
+        Ok(Self {
+            remaining_locked: u64::from_le_bytes(src[..8].try_into().unwrap()),
+            schedule_count: u32::from_le_bytes(src[8..12].try_into().unwrap()),
+            next_release_time: u64::from_le_bytes(src[12..20].try_into().unwrap()),
+        })
     }
 }
 
+impl Sealed for WalletState {}
+
+impl Pack for WalletState {
+    const LEN: usize = 34;
+
+    fn pack_into_slice(&self, target: &mut [u8]) {
+        target[..32].copy_from_slice(self.wallet_address.as_ref());
+        target[32] = self.is_fee_exempt as u8;
+        target[33] = self.is_initialized as u8;
+    }
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        if src.len() < Self::LEN {
+            return Err(ProgramError::InvalidAccountData)
+        }
+
+        Ok(Self {
+            wallet_address: Pubkey::new(&src[..32]),
+            is_fee_exempt: src[32] == 1,
+            is_initialized: src[33] == 1,
+        })
+    }
+}
+
+impl IsInitialized for WalletState {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+// Converts fees_in_usd to lamports at the SOL/USD oracle price
+pub fn usd_to_lamports(fees_in_usd: u64, price: &OraclePrice) -> Result<u64, ProgramError> {
+    if price.price <= 0 {
+        return Err(LockTokenError::InvalidOraclePrice.into());
+    }
+
+    // lamports = fees_in_usd / 10^USD_DECIMALS / (price * 10^expo) * 10^native decimals
+    let scale = native_mint::DECIMALS as i64 - USD_DECIMALS as i64 - price.expo as i64;
+    let fees_in_usd = fees_in_usd as u128;
+    let price_value = price.price as u128;
+    let lamports = if scale >= 0 {
+        10u128
+            .checked_pow(scale as u32)
+            .and_then(|factor| fees_in_usd.checked_mul(factor))
+            .map(|numerator| numerator / price_value)
+    } else {
+        10u128
+            .checked_pow((-scale) as u32)
+            .and_then(|factor| price_value.checked_mul(factor))
+            .map(|denominator| fees_in_usd / denominator)
+    }
+    .ok_or(LockTokenError::MathOverflow)?;
+
+    lamports
+        .try_into()
+        .map_err(|_| ProgramError::from(LockTokenError::MathOverflow))
+}
+
+// Takes discount_bps off a fee, rounding the discount down so the fee is never undercharged
+pub fn apply_fee_discount(fee: u64, discount_bps: u16) -> Result<u64, ProgramError> {
+    if discount_bps > MAX_BPS {
+        return Err(LockTokenError::InvalidBasisPoints.into());
+    }
+    let discount = (fee as u128)
+        .checked_mul(discount_bps as u128)
+        .ok_or(LockTokenError::MathOverflow)?
+        / MAX_BPS as u128;
+    // The discount is at most the fee, so it fits in u64
+    Ok(fee - discount as u64)
+}
+
+// Converts fees_in_usd to base units of a USD token with the given decimals.
+// Tokens with fewer decimals than USD_DECIMALS are rounded up, so the fee is never undercharged.
+pub fn usd_token_amount(fees_in_usd: u64, decimals: u8) -> Result<u64, ProgramError> {
+    let fees_in_usd = fees_in_usd as u128;
+    let amount = if decimals as u32 >= USD_DECIMALS {
+        10u128
+            .checked_pow(decimals as u32 - USD_DECIMALS)
+            .and_then(|factor| fees_in_usd.checked_mul(factor))
+    } else {
+        10u128
+            .checked_pow(USD_DECIMALS - decimals as u32)
+            .and_then(|factor| fees_in_usd.checked_add(factor - 1).map(|numerator| numerator / factor))
+    }
+    .ok_or(LockTokenError::MathOverflow)?;
+
+    amount
+        .try_into()
+        .map_err(|_| ProgramError::from(LockTokenError::MathOverflow))
+}
+
 impl Sealed for LockGlobalState {}
 
+// Global state accounts created before the layout was versioned are LEGACY_LEN bytes long,
+// those created before the token fee V2_LEN bytes long, those created before the fee discount
+// tiers V3_LEN bytes long, those created before the maximum lock duration V4_LEN bytes long, those
+// created before the pause record V5_LEN bytes long and those created before the candidate wallets could be
+// rotated V6_LEN bytes long.
+// They are read with defaults for the newer fields and written back in their own layout.
+// SetFeeBpsInToken, SetFeeDiscountTiers, SetMaxLockDuration and SetOwnerTokenMint grow older accounts to the
+// current layout.
 impl Pack for LockGlobalState {
-    const LEN: usize = 106;
+    const LEN: usize = 573;
 
     fn pack_into_slice(&self, target: &mut [u8]) {
         let price_estimator_bytes = self.price_estimator.to_bytes();
         let usd_token_address_bytes = self.usd_token_address.to_bytes();
         let fees_in_usd_bytes = self.fees_in_usd.to_le_bytes();
         let company_wallet_bytes = self.company_wallet.to_bytes();
+        let max_price_age_slots_bytes = self.max_price_age_slots.to_le_bytes();
+        let admin_bytes = self.admin.to_bytes();
+        let pending_admin_bytes = self.pending_admin.to_bytes();
+        let min_lock_duration_secs_bytes = self.min_lock_duration_secs.to_le_bytes();
+        let min_lock_amount_bytes = self.min_lock_amount.to_le_bytes();
         
         for i in 0..32 {
             target[i] = price_estimator_bytes[i];
@@ -210,12 +1245,112 @@ impl Pack for LockGlobalState {
             target[i] = company_wallet_bytes[i - 72];
         }
 
-        target[104] = self.is_paused as u8;
-        target[105] = self.is_initialized as u8;
+        if target.len() < Self::V2_LEN {
+            target[104] = self.is_paused() as u8;
+            target[105] = self.is_initialized as u8;
+            return;
+        }
+
+        for i in 104..112 {
+            target[i] = max_price_age_slots_bytes[i - 104];
+        }
+
+        let mut offset = 112;
+        for wallet in self.candidate_wallets.iter() {
+            target[offset..offset + 32].copy_from_slice(&wallet.to_bytes());
+            offset += 32;
+        }
+
+        for i in 240..272 {
+            target[i] = admin_bytes[i - 240];
+        }
+
+        for i in 272..304 {
+            target[i] = pending_admin_bytes[i - 272];
+        }
+
+        for i in 304..312 {
+            target[i] = min_lock_duration_secs_bytes[i - 304];
+        }
+
+        for i in 312..320 {
+            target[i] = min_lock_amount_bytes[i - 312];
+        }
+
+        // The V2_LEN and V3_LEN layouts keep their version byte
+        if target.len() < Self::V3_LEN {
+            target[321] = self.pause_create as u8;
+            target[322] = self.pause_unlock as u8;
+            target[323] = self.pause_transfer as u8;
+            target[324] = self.is_initialized as u8;
+            return;
+        }
+
+        target[320..322].copy_from_slice(&self.fee_bps_in_token.to_le_bytes());
+
+        if target.len() < Self::V4_LEN {
+            target[323] = self.pause_create as u8;
+            target[324] = self.pause_unlock as u8;
+            target[325] = self.pause_transfer as u8;
+            target[326] = self.is_initialized as u8;
+            return;
+        }
+
+        target[322..354].copy_from_slice(&self.owner_token_mint.to_bytes());
+        let mut offset = 354;
+        for tier in self.fee_discount_tiers.iter() {
+            target[offset..offset + 8].copy_from_slice(&tier.min_balance.to_le_bytes());
+            target[offset + 8..offset + 10].copy_from_slice(&tier.discount_bps.to_le_bytes());
+            offset += 10;
+        }
+
+        // The V4_LEN layout keeps its version byte
+        if target.len() < Self::V5_LEN {
+            target[385] = self.pause_create as u8;
+            target[386] = self.pause_unlock as u8;
+            target[387] = self.pause_transfer as u8;
+            target[388] = self.is_initialized as u8;
+            return;
+        }
+
+        target[384..392].copy_from_slice(&self.max_lock_duration_secs.to_le_bytes());
+
+        // The V5_LEN layout keeps its version byte
+        if target.len() < Self::V6_LEN {
+            target[393] = self.pause_create as u8;
+            target[394] = self.pause_unlock as u8;
+            target[395] = self.pause_transfer as u8;
+            target[396] = self.is_initialized as u8;
+            return;
+        }
+
+        target[392..400].copy_from_slice(&self.paused_at.to_le_bytes());
+        target[400..432].copy_from_slice(&self.paused_by.to_bytes());
+
+        // The V6_LEN layout keeps its version byte
+        if target.len() < Self::LEN {
+            target[433] = self.pause_create as u8;
+            target[434] = self.pause_unlock as u8;
+            target[435] = self.pause_transfer as u8;
+            target[436] = self.is_initialized as u8;
+            return;
+        }
+
+        let mut offset = 432;
+        for wallet in self.pending_candidate_wallets.iter() {
+            target[offset..offset + 32].copy_from_slice(&wallet.to_bytes());
+            offset += 32;
+        }
+        target[560..568].copy_from_slice(&self.candidate_wallets_execute_after.to_le_bytes());
+        target[568] = GLOBAL_STATE_VERSION;
+        target[569] = self.pause_create as u8;
+        target[570] = self.pause_unlock as u8;
+        target[571] = self.pause_transfer as u8;
+        target[572] = self.is_initialized as u8;
     }
 
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        if src.len() < Self::LEN {
+        if src.len() < Self::LEGACY_LEN {
             return Err(ProgramError::InvalidAccountData)
         }
 
@@ -223,15 +1358,133 @@ impl Pack for LockGlobalState {
         let usd_token_address = Pubkey::new(&src[32..64]);
         let fees_in_usd = u64::from_le_bytes(src[64..72].try_into().unwrap());
         let company_wallet = Pubkey::new(&src[72..104]);
-        let is_paused = src[104] == 1;
-        let is_initialized = src[105] == 1;
+
+        if src.len() < Self::V2_LEN {
+            let is_paused = src[104] == 1;
+            let is_initialized = src[105] == 1;
+            return Ok(Self {
+                price_estimator,
+                usd_token_address,
+                fees_in_usd,
+                company_wallet,
+                max_price_age_slots: 0,
+                candidate_wallets: [Pubkey::default(); MAX_CANDIDATE_WALLETS],
+                admin: Pubkey::default(),
+                pending_admin: Pubkey::default(),
+                min_lock_duration_secs: 0,
+                min_lock_amount: 0,
+                fee_bps_in_token: 0,
+                owner_token_mint: Pubkey::default(),
+                fee_discount_tiers: [FeeDiscountTier::default(); MAX_FEE_DISCOUNT_TIERS],
+                max_lock_duration_secs: 0,
+                paused_at: 0,
+                paused_by: Pubkey::default(),
+                pending_candidate_wallets: [Pubkey::default(); MAX_CANDIDATE_WALLETS],
+                candidate_wallets_execute_after: 0,
+                pause_create: is_paused,
+                pause_unlock: is_paused,
+                pause_transfer: is_paused,
+                is_initialized,
+            });
+        }
+
+        // A freshly created account has not been stamped with a version yet
+        let (version, is_initialized, expected_version) = if src.len() < Self::V3_LEN {
+            (src[320], src[324] == 1, 2)
+        } else if src.len() < Self::V4_LEN {
+            (src[322], src[326] == 1, 3)
+        } else if src.len() < Self::V5_LEN {
+            (src[384], src[388] == 1, 4)
+        } else if src.len() < Self::V6_LEN {
+            (src[392], src[396] == 1, 5)
+        } else if src.len() < Self::LEN {
+            (src[432], src[436] == 1, 6)
+        } else {
+            (src[568], src[572] == 1, GLOBAL_STATE_VERSION)
+        };
+        if is_initialized && version != expected_version {
+            return Err(ProgramError::InvalidAccountData)
+        }
+
+        let max_price_age_slots = u64::from_le_bytes(src[104..112].try_into().unwrap());
+        let mut candidate_wallets = [Pubkey::default(); MAX_CANDIDATE_WALLETS];
+        for (i, wallet) in candidate_wallets.iter_mut().enumerate() {
+            *wallet = Pubkey::new(&src[112 + i * 32..144 + i * 32]);
+        }
+        let admin = Pubkey::new(&src[240..272]);
+        let pending_admin = Pubkey::new(&src[272..304]);
+        let min_lock_duration_secs = u64::from_le_bytes(src[304..312].try_into().unwrap());
+        let min_lock_amount = u64::from_le_bytes(src[312..320].try_into().unwrap());
+        let fee_bps_in_token = if src.len() < Self::V3_LEN {
+            0
+        } else {
+            u16::from_le_bytes(src[320..322].try_into().unwrap())
+        };
+        let mut owner_token_mint = Pubkey::default();
+        let mut fee_discount_tiers = [FeeDiscountTier::default(); MAX_FEE_DISCOUNT_TIERS];
+        let mut max_lock_duration_secs = 0;
+        let mut paused_at = 0;
+        let mut paused_by = Pubkey::default();
+        let mut pending_candidate_wallets = [Pubkey::default(); MAX_CANDIDATE_WALLETS];
+        let mut candidate_wallets_execute_after = 0;
+        let pauses = if src.len() < Self::V3_LEN {
+            &src[321..324]
+        } else if src.len() < Self::V4_LEN {
+            &src[323..326]
+        } else {
+            owner_token_mint = Pubkey::new(&src[322..354]);
+            for (i, tier) in fee_discount_tiers.iter_mut().enumerate() {
+                let offset = 354 + i * 10;
+                tier.min_balance = u64::from_le_bytes(src[offset..offset + 8].try_into().unwrap());
+                tier.discount_bps = u16::from_le_bytes(src[offset + 8..offset + 10].try_into().unwrap());
+            }
+            if src.len() < Self::V5_LEN {
+                &src[385..388]
+            } else {
+                max_lock_duration_secs = u64::from_le_bytes(src[384..392].try_into().unwrap());
+                if src.len() < Self::V6_LEN {
+                    &src[393..396]
+                } else {
+                    paused_at = u64::from_le_bytes(src[392..400].try_into().unwrap());
+                    paused_by = Pubkey::new(&src[400..432]);
+                    if src.len() < Self::LEN {
+                        &src[433..436]
+                    } else {
+                        for (i, wallet) in pending_candidate_wallets.iter_mut().enumerate() {
+                            *wallet = Pubkey::new(&src[432 + i * 32..464 + i * 32]);
+                        }
+                        candidate_wallets_execute_after = u64::from_le_bytes(src[560..568].try_into().unwrap());
+                        &src[569..572]
+                    }
+                }
+            }
+        };
+        let pause_create = pauses[0] == 1;
+        let pause_unlock = pauses[1] == 1;
+        let pause_transfer = pauses[2] == 1;
 
         Ok(Self {
             price_estimator,
             usd_token_address,
             fees_in_usd,
             company_wallet,
-            is_paused,
+            max_price_age_slots,
+            candidate_wallets,
+            admin,
+            pending_admin,
+            min_lock_duration_secs,
+            min_lock_amount,
+            fee_bps_in_token,
+            owner_token_mint,
+            fee_discount_tiers,
+            max_lock_duration_secs,
+            paused_at,
+            paused_by,
+            pending_candidate_wallets,
+            candidate_wallets_execute_after,
+            pause_create,
+            pause_unlock,
+            pause_transfer,
             is_initialized,
         })
     }
@@ -241,4 +1494,94 @@ impl IsInitialized for LockGlobalState {
     fn is_initialized(&self) -> bool {
         self.is_initialized
     }
-}
\ No newline at end of file
+}
+
+impl LockGlobalState {
+    pub const LEGACY_LEN: usize = 106;
+    pub const V2_LEN: usize = 325;
+    pub const V3_LEN: usize = 327;
+    pub const V4_LEN: usize = 389;
+    pub const V5_LEN: usize = 397;
+    pub const V6_LEN: usize = 437;
+
+    pub fn is_paused(&self) -> bool {
+        self.pause_create || self.pause_unlock || self.pause_transfer
+    }
+
+    pub fn set_pause_flags(&mut self, pause_flags: u8) {
+        self.pause_create = pause_flags & PAUSE_CREATE != 0;
+        self.pause_unlock = pause_flags & PAUSE_UNLOCK != 0;
+        self.pause_transfer = pause_flags & PAUSE_TRANSFER != 0;
+    }
+
+    // Start of a pause beginning at the unix timestamp now. A pause within MAX_PAUSE_DURATION_SECS of the previous
+    // start continues it, and one within the PAUSE_COOLDOWN_SECS after that keeps the expired start, so its
+    // PAUSE_UNLOCK does not block.
+    pub fn pause_started_at(&self, now: u64) -> u64 {
+        let cooldown_ends_at = self
+            .paused_at
+            .saturating_add(MAX_PAUSE_DURATION_SECS)
+            .saturating_add(PAUSE_COOLDOWN_SECS);
+        if self.paused_at == 0 || now >= cooldown_ends_at {
+            now
+        } else {
+            self.paused_at
+        }
+    }
+
+    // Whether PAUSE_UNLOCK still blocks unlocks at the unix timestamp now. Unrecorded pauses of older layouts
+    // block until resumed.
+    pub fn is_unlock_paused_at(pause_unlock: bool, paused_at: u64, now: u64) -> bool {
+        pause_unlock && (paused_at == 0 || now < paused_at.saturating_add(MAX_PAUSE_DURATION_SECS))
+    }
+
+    // is_initialized is the last byte of every layout version
+    pub fn is_packed_initialized(src: &[u8]) -> bool {
+        src.last() == Some(&1)
+    }
+
+    // Best discount of the tiers whose min_balance the balance of the owner token reaches, zero without one
+    pub fn fee_discount_bps(&self, balance: u64) -> u16 {
+        self.fee_discount_tiers
+            .iter()
+            .filter(|tier| tier.discount_bps > 0 && balance >= tier.min_balance)
+            .map(|tier| tier.discount_bps)
+            .max()
+            .unwrap_or(0)
+    }
+
+    // Empty candidate slots hold the default pubkey, which can never be whitelisted
+    pub fn is_candidate_wallet(&self, wallet: &Pubkey) -> bool {
+        *wallet != Pubkey::default() && self.candidate_wallets.contains(wallet)
+    }
+}
+
+pub fn find_lock_metadata_address(program_id: &Pubkey, locking_account: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[LOCK_METADATA_SEED, locking_account.as_ref()], program_id)
+}
+
+pub fn find_wallet_state_address(program_id: &Pubkey, wallet_address: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[WALLET_STATE_SEED, wallet_address.as_ref()], program_id)
+}
+
+pub fn find_global_state_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[GLOBAL_STATE_SEED], program_id)
+}
+
+// The program state account of the version 1 layout is the program address derived from LEGACY_GLOBAL_STATE_SEED
+// alone, without a bump. Programs for which it derives no program address never had one.
+pub fn legacy_global_state_address(program_id: &Pubkey) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(&[LEGACY_GLOBAL_STATE_SEED], program_id).map_err(|_| {
+        msg!("The program has no program state account of the version 1 layout");
+        ProgramError::InvalidSeeds
+    })
+}
+
+// The token state of a mint is the program address derived from the mint alone, without a bump.
+// Mints whose address derives no program address cannot have a token state.
+pub fn token_state_address(program_id: &Pubkey, mint_address: &Pubkey) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(&[&mint_address.to_bytes()], program_id).map_err(|_| {
+        msg!("The mint {} does not derive a token state account", mint_address);
+        ProgramError::InvalidSeeds
+    })
+}
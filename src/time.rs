@@ -0,0 +1,124 @@
+use solana_program::{
+    clock::{Clock, DEFAULT_MS_PER_SLOT},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+};
+
+use crate::error::LockTokenError;
+
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+
+// Release times and deadlines are unix timestamps in seconds stored as u64.
+// Anything above this bound is taken as a timestamp in milliseconds sent by mistake:
+// 10^11 seconds is in the year 5138 while 10^11 milliseconds is in 1973.
+pub const MAX_TIMESTAMP_SECS: u64 = 100_000_000_000;
+
+pub fn now_u64(clock: &Clock) -> Result<u64, ProgramError> {
+    if clock.unix_timestamp < 0 {
+        msg!("The clock timestamp {} is negative", clock.unix_timestamp);
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(clock.unix_timestamp as u64)
+}
+
+pub fn is_matured(release_time: u64, now: u64) -> bool {
+    now >= release_time
+}
+
+pub fn assert_seconds(timestamp: u64) -> Result<(), ProgramError> {
+    if timestamp > MAX_TIMESTAMP_SECS {
+        msg!("The timestamp {} looks like milliseconds, timestamps are in seconds", timestamp);
+        return Err(LockTokenError::InvalidTimestamp.into());
+    }
+    Ok(())
+}
+
+// Checks that the release time is in seconds, not before now and, for a non-zero horizon,
+// at most horizon seconds after now
+pub fn assert_future(release_time: u64, now: u64, horizon: u64) -> Result<(), ProgramError> {
+    assert_seconds(release_time)?;
+    if release_time < now {
+        msg!("The release time {} is in the past", release_time);
+        return Err(LockTokenError::ReleaseTimeInPast.into());
+    }
+    if horizon != 0 && release_time > now.saturating_add(horizon) {
+        msg!("The release time {} is more than {} seconds away", release_time, horizon);
+        return Err(LockTokenError::ReleaseTimeTooFar.into());
+    }
+    Ok(())
+}
+
+// What the release times of a lock count, chosen on Create. Every schedule of a lock shares the basis of its header.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+pub enum TimeBasis {
+    // Unix timestamps in seconds, the basis of every lock created before slots were supported
+    UnixTimestamp,
+    // Slot heights, which do not drift with the cluster clock
+    Slot,
+}
+
+impl Default for TimeBasis {
+    fn default() -> Self {
+        Self::UnixTimestamp
+    }
+}
+
+impl TimeBasis {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::UnixTimestamp),
+            1 => Some(Self::Slot),
+            _ => None,
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Self::UnixTimestamp => 0,
+            Self::Slot => 1,
+        }
+    }
+
+    pub fn now(self, clock: &Clock) -> Result<u64, ProgramError> {
+        match self {
+            Self::UnixTimestamp => now_u64(clock),
+            Self::Slot => Ok(clock.slot),
+        }
+    }
+
+    // Only unix timestamps can be told apart from milliseconds sent by mistake
+    pub fn assert_release_time(self, release_time: u64) -> ProgramResult {
+        match self {
+            Self::UnixTimestamp => assert_seconds(release_time),
+            Self::Slot => Ok(()),
+        }
+    }
+
+    // Checks that the release time is valid for the basis and not before now
+    pub fn assert_future(self, release_time: u64, now: u64) -> ProgramResult {
+        match self {
+            Self::UnixTimestamp => assert_future(release_time, now, 0),
+            Self::Slot if release_time < now => {
+                msg!("The release slot {} is in the past", release_time);
+                Err(LockTokenError::ReleaseTimeInPast.into())
+            }
+            Self::Slot => Ok(()),
+        }
+    }
+
+    // A duration in seconds counted in the basis. Slots are taken at their target duration and rounded up,
+    // so a minimum duration is never shortened.
+    pub fn duration_from_secs(self, secs: u64) -> u64 {
+        match self {
+            Self::UnixTimestamp => secs,
+            Self::Slot => {
+                let ms = secs as u128 * 1_000;
+                let slots = (ms + DEFAULT_MS_PER_SLOT as u128 - 1) / DEFAULT_MS_PER_SLOT as u128;
+                slots.min(u64::MAX as u128) as u64
+            }
+        }
+    }
+}
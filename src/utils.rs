@@ -0,0 +1,446 @@
+use solana_program::{
+    account_info::AccountInfo,
+    bpf_loader_upgradeable,
+    ed25519_program,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program,
+    sysvar::{self, clock::Clock},
+};
+
+use spl_token::{
+    instruction::transfer_checked,
+    state::{Account, Mint, Multisig},
+};
+
+use std::convert::TryInto;
+
+use crate::{error::LockTokenError, instruction::ED25519_DATA_START, time::now_u64};
+
+pub mod spl_token_2022 {
+    solana_program::declare_id!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+}
+
+// Token-2022 extension types which would make the lock receive less than the scheduled amount
+// or let a third party move tokens out of the locking token account.
+const EXTENSION_TRANSFER_FEE_CONFIG: u16 = 1;
+const EXTENSION_TRANSFER_FEE_AMOUNT: u16 = 2;
+const EXTENSION_PERMANENT_DELEGATE: u16 = 12;
+const EXTENSION_TRANSFER_HOOK: u16 = 14;
+const EXTENSION_TRANSFER_HOOK_ACCOUNT: u16 = 15;
+
+// Variant index of UpgradeableLoaderState::ProgramData
+const PROGRAM_DATA_STATE_TAG: u32 = 3;
+
+pub fn assert_writable(account: &AccountInfo, name: &str) -> Result<(), ProgramError> {
+    if !account.is_writable {
+        msg!("The {} should be writable", name);
+        return Err(LockTokenError::AccountNotWritable.into());
+    }
+    Ok(())
+}
+
+// Fails when two roles of an instruction are given the same account, which would otherwise
+// panic on a double borrow or write inconsistent state. Roles that may alias are left out by the caller.
+pub fn assert_distinct_accounts(accounts: &[(&str, &Pubkey)]) -> Result<(), ProgramError> {
+    for (i, (name, key)) in accounts.iter().enumerate() {
+        for (other_name, other_key) in accounts[i + 1..].iter() {
+            if key == other_key {
+                msg!("The {} and the {} are the same account {}", name, other_name, key);
+                return Err(LockTokenError::DuplicateAccount.into());
+            }
+        }
+    }
+    Ok(())
+}
+
+// Re-derives the program address right before signing so that a signature can only ever be
+// produced for the account the seeds actually belong to. Every invoke_signed goes through this.
+pub fn signed_seeds_for<'a>(
+    expected_key: &Pubkey,
+    seed: &'a [u8],
+    program_id: &Pubkey,
+) -> Result<[&'a [u8]; 1], ProgramError> {
+    let derived_key = Pubkey::create_program_address(&[seed], program_id)?;
+    if derived_key != *expected_key {
+        msg!("The signer seeds do not derive the account {}", expected_key);
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok([seed])
+}
+
+// Same as signed_seeds_for, for addresses found with find_program_address
+pub fn signed_seeds_with_bump_for<'a>(
+    expected_key: &Pubkey,
+    seed: &'a [u8],
+    bump_seed: &'a [u8],
+    program_id: &Pubkey,
+) -> Result<[&'a [u8]; 2], ProgramError> {
+    let derived_key = Pubkey::create_program_address(&[seed, bump_seed], program_id)?;
+    if derived_key != *expected_key {
+        msg!("The signer seeds do not derive the account {}", expected_key);
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok([seed, bump_seed])
+}
+
+// Checks the signer against the upgrade authority recorded in the program data account
+// of a program deployed with the upgradeable BPF loader.
+pub fn assert_upgrade_authority(
+    program_id: &Pubkey,
+    program_data_account: &AccountInfo,
+    upgrade_authority: &Pubkey,
+) -> Result<(), ProgramError> {
+    let (program_data_key, _) = Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+    if program_data_key != *program_data_account.key || *program_data_account.owner != bpf_loader_upgradeable::id() {
+        msg!("Provided program data account is invalid");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    // UpgradeableLoaderState::ProgramData { slot: u64, upgrade_authority_address: Option<Pubkey> }
+    let data = program_data_account.try_borrow_data()?;
+    let is_program_data = data.get(..4) == Some(&PROGRAM_DATA_STATE_TAG.to_le_bytes()[..]);
+    let authority = match data.get(12) {
+        Some(1) => data.get(13..45).map(Pubkey::new),
+        _ => None,
+    };
+    if !is_program_data || authority != Some(*upgrade_authority) {
+        msg!("The signer is not the upgrade authority of the program");
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(())
+}
+
+pub fn is_token_multisig(account: &AccountInfo) -> bool {
+    *account.owner == spl_token::id() && account.data_len() == Multisig::LEN
+}
+
+// Checks that the authority signed the transaction. An authority which is a spl token multisig account
+// signs through at least m of its registered signers, passed as the trailing signer accounts. Registered
+// signers passed without signing do not count towards m, and neither does a signer passed twice.
+pub fn assert_authority_signed(authority: &AccountInfo, signer_accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+    if is_token_multisig(authority) {
+        let multisig = Multisig::unpack(&authority.try_borrow_data()?)?;
+        let mut num_signers = 0;
+        let mut matched = [false; spl_token::instruction::MAX_SIGNERS];
+        for signer_account in signer_accounts.iter().filter(|account| account.is_signer) {
+            for (position, key) in multisig.signers[0..multisig.n as usize].iter().enumerate() {
+                if key == signer_account.key && !matched[position] {
+                    matched[position] = true;
+                    num_signers += 1;
+                }
+            }
+        }
+        if num_signers < multisig.m {
+            msg!("Only {} of the {} required multisig signers signed", num_signers, multisig.m);
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        return Ok(());
+    }
+
+    if !authority.is_signer {
+        msg!("The authority {} should be a signer", authority.key);
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    Ok(())
+}
+
+pub fn assert_not_expired(deadline: u64, clock: &Clock) -> Result<(), ProgramError> {
+    if deadline != 0 && now_u64(clock)? > deadline {
+        msg!("The transaction deadline {} has passed", deadline);
+        return Err(LockTokenError::TransactionExpired.into());
+    }
+    Ok(())
+}
+
+// Instructions reading the clock through Clock::get used to take the clock sysvar account at this position,
+// it is skipped when an older client still passes it
+pub fn skip_clock_sysvar<'a, 'b>(accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>) {
+    if accounts_iter.as_slice().first().map_or(false, |account| *account.key == sysvar::clock::id()) {
+        accounts_iter.next();
+    }
+}
+
+pub fn assert_token_program(account: &AccountInfo) -> Result<(), ProgramError> {
+    if *account.key != spl_token::id() && *account.key != spl_token_2022::id() {
+        msg!("The provided spl token program account is invalid");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+
+pub fn assert_system_program(account: &AccountInfo) -> Result<(), ProgramError> {
+    if *account.key != system_program::id() {
+        msg!("The provided system program account is invalid");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+
+pub fn assert_rent_sysvar(account: &AccountInfo) -> Result<(), ProgramError> {
+    if *account.key != sysvar::rent::id() {
+        msg!("The provided rent sysvar account is invalid");
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(())
+}
+
+// Checks that the instruction right before the current one is an ed25519 program instruction verifying a single
+// signature of signer over message, with every offset pointing into that instruction. The runtime has already
+// verified the signature itself, only what was signed is checked here.
+pub fn assert_ed25519_permit(instructions_sysvar: &AccountInfo, signer: &Pubkey, message: &[u8]) -> Result<(), ProgramError> {
+    if *instructions_sysvar.key != sysvar::instructions::id() {
+        msg!("The provided instructions sysvar account is invalid");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let current_index = sysvar::instructions::load_current_index_checked(instructions_sysvar)?;
+    let permit_index = match current_index.checked_sub(1) {
+        Some(index) => index,
+        None => {
+            msg!("The permit should be verified by the instruction right before");
+            return Err(LockTokenError::InvalidPermit.into());
+        }
+    };
+    let permit = sysvar::instructions::load_instruction_at_checked(permit_index as usize, instructions_sysvar)?;
+    if permit.program_id != ed25519_program::id() {
+        msg!("The instruction right before should be an ed25519 program instruction");
+        return Err(LockTokenError::InvalidPermit.into());
+    }
+
+    let data = &permit.data;
+    if data.len() < ED25519_DATA_START || data[0] != 1 {
+        msg!("The ed25519 program instruction should verify a single signature");
+        return Err(LockTokenError::InvalidPermit.into());
+    }
+    let offset = |index: usize| u16::from_le_bytes([data[2 + 2 * index], data[3 + 2 * index]]) as usize;
+    // The signature, the public key and the message all have to be in the ed25519 program instruction
+    if [1, 3, 6].iter().any(|&index| offset(index) != u16::MAX as usize) {
+        msg!("The ed25519 program instruction should hold what it verifies");
+        return Err(LockTokenError::InvalidPermit.into());
+    }
+    let public_key = data.get(offset(2)..offset(2) + 32);
+    let signed_message = data.get(offset(4)..offset(4) + offset(5));
+    if public_key != Some(signer.as_ref()) {
+        msg!("The permit should be signed by {}", signer);
+        return Err(LockTokenError::InvalidPermit.into());
+    }
+    if signed_message != Some(message) {
+        msg!("The permit was signed for another lock, nonce or expiry");
+        return Err(LockTokenError::InvalidPermit.into());
+    }
+    Ok(())
+}
+
+pub fn assert_owned_by_token_program(account: &AccountInfo, token_program_id: &Pubkey) -> Result<(), ProgramError> {
+    if *account.owner != *token_program_id {
+        msg!("Token account {} is not owned by the provided token program", account.key);
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+
+pub fn unpack_token_account(account: &AccountInfo, token_program_id: &Pubkey) -> Result<Account, ProgramError> {
+    assert_owned_by_token_program(account, token_program_id)?;
+    let data = account.try_borrow_data()?;
+    if data.len() < Account::LEN {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    assert_supported_extensions(&data)?;
+    Account::unpack(&data[..Account::LEN])
+}
+
+// The locking token account holds the locked tokens, so nobody but the locking account may move them or close it
+pub fn assert_valid_vault(vault: &Account, locking_account_key: &Pubkey, mint: &Pubkey) -> Result<(), ProgramError> {
+    if vault.mint != *mint {
+        msg!("The locking token account holds {} instead of the locked mint {}", vault.mint, mint);
+        return Err(LockTokenError::VaultMintMismatch.into());
+    }
+
+    if vault.owner != *locking_account_key {
+        msg!("The locking token account should be owned by the locking account.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    if vault.delegate.is_some() {
+        msg!("The locking token account should not have a delegate authority");
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    if vault.close_authority.is_some() {
+        msg!("The locking token account should not have a close authority");
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    // The freeze authority of the mint can freeze the locked tokens, which are then unclaimable until thawed
+    if vault.is_frozen() {
+        msg!("The locking token account is frozen by the freeze authority of the mint");
+        return Err(LockTokenError::EscrowFrozen.into());
+    }
+    Ok(())
+}
+
+// Destination token accounts only have to be able to receive the mint.
+// Delegates and close authorities are set by the beneficiary, e.g. for exchange deposit accounts, and are allowed.
+pub fn assert_valid_destination(destination: &Account, mint: &Pubkey) -> Result<(), ProgramError> {
+    if destination.mint != *mint {
+        msg!("The destination token account holds {} instead of the locked mint {}", destination.mint, mint);
+        return Err(LockTokenError::DestinationMintMismatch.into());
+    }
+
+    if destination.is_frozen() {
+        msg!("The destination token account is frozen");
+        return Err(LockTokenError::DestinationFrozen.into());
+    }
+    Ok(())
+}
+
+// A lock moved to a new destination is claimed by whoever controls that account, and nobody can fix
+// a wrong one but the new beneficiary. So unlike the destination at unlock time, it has to be a plain
+// token account of the locked mint without a delegate or close authority.
+pub fn assert_valid_new_destination(account: &AccountInfo, mint: &Pubkey) -> Result<Account, ProgramError> {
+    if *account.owner != spl_token::id() && *account.owner != spl_token_2022::id() {
+        msg!("The new destination {} is not a token account", account.key);
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let destination = unpack_token_account(account, account.owner)?;
+    assert_valid_destination(&destination, mint)?;
+
+    if destination.delegate.is_some() {
+        msg!("The new destination token account should not have a delegate authority");
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    if destination.close_authority.is_some() {
+        msg!("The new destination token account should not have a close authority");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(destination)
+}
+
+pub fn assert_valid_source(source: &Account, mint: &Pubkey) -> Result<(), ProgramError> {
+    if source.mint != *mint {
+        msg!("The source token account holds {} instead of the locked mint {}", source.mint, mint);
+        return Err(LockTokenError::SourceMintMismatch.into());
+    }
+    Ok(())
+}
+
+// The authority moving tokens out of the source token account is either its owner or its approved delegate,
+// in which case the allowance has to cover the amount
+pub fn assert_source_authority(source: &Account, authority: &Pubkey, amount: u64) -> Result<(), ProgramError> {
+    if source.owner == *authority {
+        return Ok(());
+    }
+    if source.delegate != COption::Some(*authority) {
+        msg!("{} is neither the owner nor the delegate of the source token account", authority);
+        return Err(LockTokenError::SourceAuthorityMismatch.into());
+    }
+    if source.delegated_amount < amount {
+        msg!("The delegated amount {} does not cover the amount {}", source.delegated_amount, amount);
+        return Err(LockTokenError::InsufficientDelegatedAmount.into());
+    }
+    Ok(())
+}
+
+pub fn unpack_mint(account: &AccountInfo, token_program_id: &Pubkey) -> Result<Mint, ProgramError> {
+    assert_owned_by_token_program(account, token_program_id)?;
+    let data = account.try_borrow_data()?;
+    if data.len() < Mint::LEN {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    assert_supported_extensions(&data)?;
+    Mint::unpack(&data[..Mint::LEN])
+}
+
+// Token-2022 stores extensions as TLV entries after the base account (padded to the token
+// account length) and a single account type byte.
+fn assert_supported_extensions(data: &[u8]) -> Result<(), ProgramError> {
+    let mut offset = Account::LEN + 1;
+    while offset + 4 <= data.len() {
+        let extension_type = u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap());
+        let length = u16::from_le_bytes(data[offset + 2..offset + 4].try_into().unwrap()) as usize;
+        match extension_type {
+            EXTENSION_TRANSFER_FEE_CONFIG
+            | EXTENSION_TRANSFER_FEE_AMOUNT
+            | EXTENSION_PERMANENT_DELEGATE
+            | EXTENSION_TRANSFER_HOOK
+            | EXTENSION_TRANSFER_HOOK_ACCOUNT => {
+                msg!("Token extension {} is not supported", extension_type);
+                return Err(LockTokenError::UnsupportedTokenExtension.into());
+            }
+            _ => {}
+        }
+        offset += 4 + length;
+    }
+    Ok(())
+}
+
+pub fn transfer_checked_instruction(
+    token_program_id: &Pubkey,
+    source_key: &Pubkey,
+    mint_key: &Pubkey,
+    destination_key: &Pubkey,
+    authority_key: &Pubkey,
+    signer_keys: &[&Pubkey],
+    amount: u64,
+    decimals: u8,
+) -> Result<Instruction, ProgramError> {
+    // Token-2022 shares the instruction layout of the legacy token program
+    let mut instruction = transfer_checked(
+        &spl_token::id(),
+        source_key,
+        mint_key,
+        destination_key,
+        authority_key,
+        signer_keys,
+        amount,
+        decimals,
+    )?;
+    instruction.program_id = *token_program_id;
+    Ok(instruction)
+}
+
+pub fn get_associated_token_address_with_program_id(
+    wallet_address: &Pubkey,
+    mint_address: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            &wallet_address.to_bytes(),
+            &token_program_id.to_bytes(),
+            &mint_address.to_bytes(),
+        ],
+        &spl_associated_token_account::id(),
+    )
+    .0
+}
+
+pub fn create_associated_token_account_with_program_id(
+    funding_address: &Pubkey,
+    wallet_address: &Pubkey,
+    mint_address: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Instruction {
+    let associated_account_address =
+        get_associated_token_address_with_program_id(wallet_address, mint_address, token_program_id);
+    Instruction {
+        program_id: spl_associated_token_account::id(),
+        accounts: vec![
+            AccountMeta::new(*funding_address, true),
+            AccountMeta::new(associated_account_address, false),
+            AccountMeta::new_readonly(*wallet_address, false),
+            AccountMeta::new_readonly(*mint_address, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(*token_program_id, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+        data: vec![],
+    }
+}
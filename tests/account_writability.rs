@@ -0,0 +1,133 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    error::LockTokenError,
+    instruction::{extend_lock_duration, set_fee_params, transfer_locks, LockSeed, LockTokenInstruction, Schedule},
+    state::{find_global_state_address, token_state_address, FEE_PARAM_FEES_IN_USD, MAX_CANDIDATE_WALLETS, PAUSE_CREATE},
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+const NOT_WRITABLE: InstructionError = InstructionError::Custom(LockTokenError::AccountNotWritable as u32);
+
+// Sends the instruction alone with the account at the index demoted to readonly, as a transaction marks an
+// account writable when any of its instructions does
+async fn process_readonly(
+    context: &mut ProgramTestContext,
+    mut instruction: Instruction,
+    index: usize,
+    signers: &[&Keypair],
+) -> Result<(), TransactionError> {
+    refresh_blockhash(context).await;
+    instruction.accounts[index].is_writable = false;
+    process(context, &[instruction], signers).await
+}
+
+// There is no builder for SetFreeToken, which takes the token state account after the admin accounts
+fn set_free_token_instruction(env: &TestEnv, is_free: bool) -> Instruction {
+    let mint = env.mint.pubkey();
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(env.admin.pubkey(), true),
+            AccountMeta::new(program_state_account_key, false),
+            AccountMeta::new(token_state_address(&env.program_id, &mint).unwrap(), false),
+        ],
+        data: LockTokenInstruction::SetFreeToken { mint_address: mint, is_free }.pack(),
+    }
+}
+
+// The locking account is written by Create, Unlock, TransferLocks and ExtendLockDuration
+#[tokio::test]
+async fn test_readonly_locking_account_is_rejected() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let lock = create_test_lock(&mut env, vec![Schedule { release_time: start + 100, amount: 10 }]).await;
+
+    let payer = env.context.payer.pubkey();
+    let mint = env.mint.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 10).await;
+    let seeds = LockSeed::random(&env.program_id);
+    let instruction = init_instruction(&env, seeds, 1);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    let schedules = vec![Schedule { release_time: start + 100, amount: 10 }];
+    let instruction = create_lock_instruction(&env, seeds, &payer, &source, &lock.destination, schedules, false, &[]);
+    assert_instruction_error(process_readonly(&mut env.context, instruction, 2, &[]).await, NOT_WRITABLE);
+
+    let instruction = extend_lock_duration(
+        &env.program_id,
+        &lock.locking_account,
+        &lock.beneficiary.pubkey(),
+        &lock.destination,
+        lock.seeds,
+        0,
+        start + 200,
+    )
+    .unwrap();
+    let result = process_readonly(&mut env.context, instruction, 1, &[&lock.beneficiary]).await;
+    assert_instruction_error(result, NOT_WRITABLE);
+
+    let new_destination = create_token_account(&mut env.context, &mint, &Pubkey::new_unique()).await;
+    let instruction = transfer_locks(
+        &env.program_id,
+        &lock.locking_account,
+        &lock.beneficiary.pubkey(),
+        &lock.destination,
+        &new_destination,
+        lock.seeds,
+    )
+    .unwrap();
+    let result = process_readonly(&mut env.context, instruction, 1, &[&lock.beneficiary]).await;
+    assert_instruction_error(result, NOT_WRITABLE);
+
+    warp_to_timestamp(&mut env.context, start + 100).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    assert_instruction_error(process_readonly(&mut env.context, instruction, 2, &[]).await, NOT_WRITABLE);
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 0);
+}
+
+// The program state account is written by the setters and PauseContract, the token state by SetFreeToken
+#[tokio::test]
+async fn test_readonly_state_accounts_are_rejected() {
+    let mut env = setup(true).await;
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    let admin = env.admin.pubkey();
+
+    let instruction = set_fee_params(
+        &env.program_id,
+        &admin,
+        &program_state_account_key,
+        &Pubkey::default(),
+        &Pubkey::default(),
+        42,
+        &Pubkey::default(),
+        [Pubkey::default(); MAX_CANDIDATE_WALLETS],
+        FEE_PARAM_FEES_IN_USD,
+    )
+    .unwrap();
+    assert_instruction_error(process_readonly(&mut env.context, instruction, 1, &[&env.admin]).await, NOT_WRITABLE);
+
+    let instruction = pause_contract(&env.program_id, &admin, PAUSE_CREATE);
+    assert_instruction_error(process_readonly(&mut env.context, instruction, 1, &[&env.admin]).await, NOT_WRITABLE);
+
+    let instruction = set_free_token_instruction(&env, false);
+    assert_instruction_error(process_readonly(&mut env.context, instruction, 2, &[&env.admin]).await, NOT_WRITABLE);
+
+    // Every one of them goes through with the accounts writable
+    let instruction = set_free_token_instruction(&env, false);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+    let instruction = pause_contract(&env.program_id, &admin, PAUSE_CREATE);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+}
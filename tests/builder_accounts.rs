@@ -0,0 +1,330 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    error::LockTokenError,
+    instruction::{
+        approve_lock_delegate, batch_unlock, cancel_emergency_unlock, cancel_ownership_transfer, cancel_transfer_locks,
+        claim_and_close, close_lock_metadata, ed25519_permit_instruction, extend_lock_duration, extend_lock_durations,
+        get_claimable_amount, permit_message, propose_owner, propose_transfer_locks, reclaim_uninitialized_lock,
+        relock, revoke_lock_delegate, schedule_emergency_unlock, set_fee_bps_in_token, set_fee_exempt_wallet,
+        set_lock_metadata, set_max_lock_duration, snapshot_mint_locked, sweep_excess, transfer_locks,
+        transfer_schedule_indices, unlock_with_permit, LockSeed, Schedule,
+    },
+    state::find_global_state_address,
+    utils::{create_associated_token_account_with_program_id, get_associated_token_address_with_program_id},
+};
+use solana_program::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_sdk::signature::{Keypair, Signer};
+use std::convert::TryInto;
+
+struct Lock {
+    seeds: LockSeed,
+    locking_account: Pubkey,
+    locking_token_account: Pubkey,
+    destination: Pubkey,
+}
+
+fn lock_of(env: &TestEnv, seeds: LockSeed, destination: Pubkey) -> Lock {
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    Lock {
+        seeds,
+        locking_account,
+        locking_token_account: get_associated_token_address_with_program_id(
+            &locking_account,
+            &env.mint.pubkey(),
+            &spl_token::id(),
+        ),
+        destination,
+    }
+}
+
+// Every instruction below is built by its builder as is and has to go through, so the accounts the builders flag
+// writable and signer are the ones the processors ask for
+#[tokio::test]
+async fn test_lock_builders_satisfy_processors() {
+    let mut env = setup(true).await;
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 601).await;
+    let beneficiary = Keypair::new();
+    let destination = create_token_account(&mut env.context, &mint, &beneficiary.pubkey()).await;
+
+    let start = now(&mut env.context).await;
+    let schedules = vec![
+        Schedule { release_time: start + 100, amount: 100 },
+        Schedule { release_time: start + 200, amount: 200 },
+        Schedule { release_time: start + 300, amount: 300 },
+    ];
+    let lock = lock_of(&env, LockSeed::random(&env.program_id), destination);
+    let instructions = [
+        init_instruction(&env, lock.seeds, schedules.len() as u32),
+        create_lock_instruction(&env, lock.seeds, &payer, &source, &destination, schedules, false, &[]),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+
+    // Described by its creator, who pays for the metadata account
+    let instructions = [
+        set_lock_metadata(
+            &env.program_id,
+            &lock.locking_account,
+            &lock.destination,
+            &payer,
+            &payer,
+            &system_program::id(),
+            lock.seeds,
+            "Lock",
+            "https://example.com/lock.json",
+        )
+        .unwrap(),
+        get_claimable_amount(&env.program_id, &lock.locking_account, lock.seeds).unwrap(),
+        snapshot_mint_locked(
+            &env.program_id,
+            &spl_token::id(),
+            &system_program::id(),
+            &payer,
+            &mint,
+            &[(lock.locking_account, lock.locking_token_account)],
+        )
+        .unwrap(),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+
+    // Changes of the lock by its beneficiary
+    let delegate = Pubkey::new_unique();
+    let proposed_destination = create_token_account(&mut env.context, &mint, &Pubkey::new_unique()).await;
+    let owner = beneficiary.pubkey();
+    let instructions = [
+        extend_lock_duration(&env.program_id, &lock.locking_account, &owner, &destination, lock.seeds, 2, start + 350)
+            .unwrap(),
+        extend_lock_durations(
+            &env.program_id,
+            &lock.locking_account,
+            &owner,
+            &destination,
+            lock.seeds,
+            vec![(2, start + 400)],
+        )
+        .unwrap(),
+        approve_lock_delegate(&env.program_id, &lock.locking_account, &owner, &destination, lock.seeds, &delegate)
+            .unwrap(),
+        revoke_lock_delegate(&env.program_id, &lock.locking_account, &owner, &destination, lock.seeds).unwrap(),
+        propose_transfer_locks(
+            &env.program_id,
+            &lock.locking_account,
+            &owner,
+            &destination,
+            &proposed_destination,
+            lock.seeds,
+        )
+        .unwrap(),
+        cancel_transfer_locks(&env.program_id, &lock.locking_account, &owner, &destination, lock.seeds).unwrap(),
+    ];
+    process(&mut env.context, &instructions, &[&beneficiary]).await.unwrap();
+
+    // An emergency unlock scheduled by the admin and cancelled by the beneficiary
+    let rescue_destination = create_token_account(&mut env.context, &mint, &Pubkey::new_unique()).await;
+    let instructions = [
+        schedule_emergency_unlock(
+            &env.program_id,
+            &env.admin.pubkey(),
+            &lock.locking_account,
+            &rescue_destination,
+            lock.seeds,
+        )
+        .unwrap(),
+        cancel_emergency_unlock(&env.program_id, &lock.locking_account, &owner, &destination, lock.seeds).unwrap(),
+    ];
+    process(&mut env.context, &instructions, &[&env.admin, &beneficiary]).await.unwrap();
+
+    // The first schedule is locked again once it matured, then unlocked
+    warp_to_timestamp(&mut env.context, start + 100).await;
+    let instruction =
+        relock(&env.program_id, &lock.locking_account, &owner, &destination, lock.seeds, 0, start + 150).unwrap();
+    process(&mut env.context, &[instruction], &[&beneficiary]).await.unwrap();
+    warp_to_timestamp(&mut env.context, start + 150).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &destination).await, 100);
+
+    // A token sent straight to the locking token account goes back to the creator
+    let transfer =
+        spl_token::instruction::transfer(&spl_token::id(), &source, &lock.locking_token_account, &payer, &[], 1).unwrap();
+    let sweep = sweep_excess(
+        &env.program_id,
+        &spl_token::id(),
+        &lock.locking_account,
+        &lock.locking_token_account,
+        &source,
+        &mint,
+        lock.seeds,
+    )
+    .unwrap();
+    process(&mut env.context, &[transfer, sweep], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &source).await, 1);
+
+    // The last schedule moves to a new lock
+    let new_owner = Keypair::new();
+    let new_destination = create_token_account(&mut env.context, &mint, &new_owner.pubkey()).await;
+    let new_lock = lock_of(&env, LockSeed::random(&env.program_id), new_destination);
+    let instructions = [
+        init_instruction(&env, new_lock.seeds, 1),
+        create_associated_token_account_with_program_id(&payer, &new_lock.locking_account, &mint, &spl_token::id()),
+        transfer_schedule_indices(
+            &env.program_id,
+            &spl_token::id(),
+            &lock.locking_account,
+            &lock.locking_token_account,
+            &owner,
+            &destination,
+            &new_lock.locking_account,
+            &new_lock.locking_token_account,
+            &new_lock.destination,
+            &mint,
+            lock.seeds,
+            new_lock.seeds,
+            vec![2],
+        )
+        .unwrap(),
+    ];
+    process(&mut env.context, &instructions, &[&beneficiary]).await.unwrap();
+
+    // The rest of the lock goes to the new owner, then is unlocked along with other locks and closed
+    let instruction = transfer_locks(
+        &env.program_id,
+        &lock.locking_account,
+        &owner,
+        &destination,
+        &new_destination,
+        lock.seeds,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[&beneficiary]).await.unwrap();
+    let lock = Lock { destination: new_destination, ..lock };
+
+    warp_to_timestamp(&mut env.context, start + 200).await;
+    let instructions = [
+        batch_unlock(
+            &env.program_id,
+            &spl_token::id(),
+            &mint,
+            &[(lock.seeds, lock.locking_account, lock.locking_token_account, lock.destination)],
+        )
+        .unwrap(),
+        close_lock_metadata(&env.program_id, &lock.locking_account, &lock.destination, &payer, &payer, lock.seeds)
+            .unwrap(),
+        claim_and_close(
+            &env.program_id,
+            &spl_token::id(),
+            &lock.locking_account,
+            &lock.locking_token_account,
+            &lock.destination,
+            &mint,
+            &new_owner.pubkey(),
+            lock.seeds,
+            &[],
+        )
+        .unwrap(),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &new_destination).await, 200);
+    assert!(env.context.banks_client.get_account(lock.locking_account).await.unwrap().is_none());
+
+    // The moved schedule is unlocked on a permit of the new owner
+    warp_to_timestamp(&mut env.context, start + 400).await;
+    let message = permit_message(&env.program_id, &new_lock.locking_account, 0, start + 1_000);
+    let signature: [u8; 64] = new_owner.sign_message(&message).as_ref().try_into().unwrap();
+    let instructions = [
+        ed25519_permit_instruction(&new_owner.pubkey(), &signature, &message),
+        unlock_with_permit(
+            &env.program_id,
+            &spl_token::id(),
+            &new_lock.locking_account,
+            &new_lock.locking_token_account,
+            &new_lock.destination,
+            &mint,
+            new_lock.seeds,
+            start + 1_000,
+            &[],
+        )
+        .unwrap(),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &new_destination).await, 500);
+
+    // A locking account left without a lock gives its rent back
+    let seeds = LockSeed::random(&env.program_id);
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    let instructions = [
+        init_instruction(&env, seeds, 1),
+        reclaim_uninitialized_lock(&env.program_id, &locking_account, &payer, seeds).unwrap(),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_admin_builders_satisfy_processors() {
+    let mut env = setup(true).await;
+    let admin = env.admin.pubkey();
+    let payer = env.context.payer.pubkey();
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    let instructions = [
+        set_fee_bps_in_token(&env.program_id, &system_program::id(), &admin, &payer, 100).unwrap(),
+        set_max_lock_duration(&env.program_id, &system_program::id(), &admin, &payer, 10 * 365 * 24 * 60 * 60).unwrap(),
+        set_fee_exempt_wallet(&env.program_id, &system_program::id(), &admin, &payer, &Pubkey::new_unique(), true)
+            .unwrap(),
+        propose_owner(&env.program_id, &admin, &program_state_account_key, &Pubkey::new_unique()).unwrap(),
+        cancel_ownership_transfer(&env.program_id, &admin, &program_state_account_key).unwrap(),
+    ];
+    process(&mut env.context, &instructions, &[&env.admin]).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_read_only_accounts_are_rejected() {
+    let mut env = setup(true).await;
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let not_writable = InstructionError::Custom(LockTokenError::AccountNotWritable as u32);
+
+    let seeds = LockSeed::random(&env.program_id);
+    let mut instruction = init_instruction(&env, seeds, 1);
+    instruction.accounts[4].is_writable = false;
+    assert_instruction_error(process(&mut env.context, &[instruction], &[]).await, not_writable.clone());
+
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 100).await;
+    let destination = create_token_account(&mut env.context, &mint, &Pubkey::new_unique()).await;
+    let start = now(&mut env.context).await;
+    let schedules = vec![Schedule { release_time: start + 100, amount: 100 }];
+    let lock = lock_of(&env, seeds, destination);
+    let mut create = create_lock_instruction(&env, seeds, &payer, &source, &destination, schedules.clone(), false, &[]);
+    create.accounts[5].is_writable = false;
+    let instructions = [init_instruction(&env, seeds, 1), create];
+    assert_eq!(
+        process(&mut env.context, &instructions, &[]).await.unwrap_err(),
+        solana_sdk::transaction::TransactionError::InstructionError(1, not_writable.clone())
+    );
+    let instructions = [
+        init_instruction(&env, seeds, 1),
+        create_lock_instruction(&env, seeds, &payer, &source, &destination, schedules, false, &[]),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+
+    // Unlocking writes the locking token account and the destination once something has matured
+    warp_to_timestamp(&mut env.context, start + 100).await;
+    for index in [3, 4].iter() {
+        let mut instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+        instruction.accounts[*index].is_writable = false;
+        assert_instruction_error(process(&mut env.context, &[instruction], &[]).await, not_writable.clone());
+    }
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &destination).await, 100);
+}
@@ -0,0 +1,150 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    error::LockTokenError,
+    instruction::{accept_candidate_wallets, propose_candidate_wallets, LockTokenInstruction},
+    state::{find_global_state_address, LockGlobalState, CANDIDATE_WALLETS_DELAY_SECS, MAX_CANDIDATE_WALLETS},
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_sdk::{account::AccountSharedData, signature::Signer, transaction::TransactionError};
+
+// There is no builder for SetCompanyWallet, which takes the accounts of the other admin instructions
+fn set_company_wallet_instruction(env: &TestEnv, company_wallet: &Pubkey) -> Instruction {
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(env.admin.pubkey(), true),
+            AccountMeta::new(program_state_account_key, false),
+        ],
+        data: LockTokenInstruction::SetCompanyWallet { company_wallet: *company_wallet }.pack(),
+    }
+}
+
+async fn set_company_wallet(env: &mut TestEnv, company_wallet: &Pubkey) -> Result<(), TransactionError> {
+    refresh_blockhash(&mut env.context).await;
+    let instruction = set_company_wallet_instruction(env, company_wallet);
+    process(&mut env.context, &[instruction], &[&env.admin]).await
+}
+
+async fn propose(env: &mut TestEnv, candidate_wallets: [Pubkey; MAX_CANDIDATE_WALLETS]) -> Result<(), TransactionError> {
+    let payer = env.context.payer.pubkey();
+    let instruction =
+        propose_candidate_wallets(&env.program_id, &system_program::id(), &env.admin.pubkey(), &payer, candidate_wallets)
+            .unwrap();
+    process(&mut env.context, &[instruction], &[&env.admin]).await
+}
+
+async fn accept(env: &mut TestEnv) -> Result<(), TransactionError> {
+    refresh_blockhash(&mut env.context).await;
+    let instruction = accept_candidate_wallets(&env.program_id, &env.admin.pubkey()).unwrap();
+    process(&mut env.context, &[instruction], &[&env.admin]).await
+}
+
+async fn global_state(env: &mut TestEnv) -> LockGlobalState {
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    let account = get_account(&mut env.context, &program_state_account_key).await;
+    LockGlobalState::unpack_from_slice(&account.data).unwrap()
+}
+
+fn custom_error(error: LockTokenError) -> InstructionError {
+    InstructionError::Custom(error as u32)
+}
+
+#[tokio::test]
+async fn test_company_wallet_is_one_of_the_candidates() {
+    let mut env = setup(true).await;
+
+    let company_wallet = env.company_wallet;
+    set_company_wallet(&mut env, &company_wallet).await.unwrap();
+
+    let attacker_wallet = Pubkey::new_unique();
+    assert_instruction_error(
+        set_company_wallet(&mut env, &attacker_wallet).await,
+        custom_error(LockTokenError::WalletNotWhitelisted),
+    );
+    assert_eq!(global_state(&mut env).await.company_wallet, company_wallet);
+}
+
+#[tokio::test]
+async fn test_candidate_wallets_rotation_is_timelocked() {
+    let mut env = setup(true).await;
+    let new_wallet = Pubkey::new_unique();
+    let mut candidate_wallets = [Pubkey::default(); MAX_CANDIDATE_WALLETS];
+    candidate_wallets[0] = new_wallet;
+
+    // Nothing to accept before a proposal
+    assert_instruction_error(accept(&mut env).await, InstructionError::InvalidArgument);
+
+    let start = now(&mut env.context).await;
+    propose(&mut env, candidate_wallets).await.unwrap();
+    let state = global_state(&mut env).await;
+    assert_eq!(state.pending_candidate_wallets, candidate_wallets);
+    assert_eq!(state.candidate_wallets_execute_after, start + CANDIDATE_WALLETS_DELAY_SECS);
+
+    // The proposed wallet is no candidate until the proposal is accepted, which fails before the delay has passed
+    assert_instruction_error(accept(&mut env).await, custom_error(LockTokenError::CandidateWalletsTimelock));
+    assert_instruction_error(
+        set_company_wallet(&mut env, &new_wallet).await,
+        custom_error(LockTokenError::WalletNotWhitelisted),
+    );
+    warp_to_timestamp(&mut env.context, start + CANDIDATE_WALLETS_DELAY_SECS - 1).await;
+    assert_instruction_error(accept(&mut env).await, custom_error(LockTokenError::CandidateWalletsTimelock));
+
+    warp_to_timestamp(&mut env.context, start + CANDIDATE_WALLETS_DELAY_SECS).await;
+    accept(&mut env).await.unwrap();
+    let state = global_state(&mut env).await;
+    assert_eq!(state.candidate_wallets, candidate_wallets);
+    assert_eq!(state.pending_candidate_wallets, [Pubkey::default(); MAX_CANDIDATE_WALLETS]);
+    assert_eq!(state.candidate_wallets_execute_after, 0);
+    // The company wallet stays until it is moved to a new candidate
+    assert_eq!(state.company_wallet, env.company_wallet);
+
+    set_company_wallet(&mut env, &new_wallet).await.unwrap();
+    let old_wallet = env.company_wallet;
+    assert_instruction_error(
+        set_company_wallet(&mut env, &old_wallet).await,
+        custom_error(LockTokenError::WalletNotWhitelisted),
+    );
+    assert_instruction_error(accept(&mut env).await, InstructionError::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_propose_candidate_wallets_rejects_an_empty_list() {
+    let mut env = setup(true).await;
+    assert_instruction_error(
+        propose(&mut env, [Pubkey::default(); MAX_CANDIDATE_WALLETS]).await,
+        InstructionError::InvalidArgument,
+    );
+}
+
+// ProposeCandidateWallets grows a program state account of the version 6 layout, which has no room for a proposal
+#[tokio::test]
+async fn test_propose_candidate_wallets_grows_version_6_state() {
+    let mut env = setup(true).await;
+    let state = global_state(&mut env).await;
+    let mut data = vec![0; LockGlobalState::V6_LEN];
+    data[432] = 6;
+    state.pack_into_slice(&mut data);
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    let mut account = get_account(&mut env.context, &program_state_account_key).await;
+    account.data = data;
+    env.context.set_account(&program_state_account_key, &AccountSharedData::from(account));
+    assert_eq!(global_state(&mut env).await, state);
+
+    propose(&mut env, [Pubkey::new_unique(); MAX_CANDIDATE_WALLETS]).await.unwrap();
+    let account = get_account(&mut env.context, &program_state_account_key).await;
+    assert_eq!(account.data.len(), LockGlobalState::LEN);
+    let grown = LockGlobalState::unpack_from_slice(&account.data).unwrap();
+    assert_eq!(grown.admin, state.admin);
+    assert_eq!(grown.candidate_wallets, state.candidate_wallets);
+    assert_ne!(grown.candidate_wallets_execute_after, 0);
+}
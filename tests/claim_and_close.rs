@@ -0,0 +1,132 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    instruction::{claim_and_close, sweep_excess, Schedule},
+    state::{unpack_schedules, LockScheduleHeader},
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+};
+use solana_sdk::signature::Signer;
+
+// Locks 300 tokens for a fresh beneficiary in schedules of 100 and 200 released 100 seconds apart
+async fn create_lock(env: &mut TestEnv, start: u64) -> TestLock {
+    let schedules = vec![
+        Schedule { release_time: start + 100, amount: 100 },
+        Schedule { release_time: start + 200, amount: 200 },
+    ];
+    create_test_lock(env, schedules).await
+}
+
+fn claim_and_close_instruction(env: &TestEnv, lock: &TestLock, refund: &Pubkey) -> Instruction {
+    claim_and_close(
+        &env.program_id,
+        &spl_token::id(),
+        &lock.locking_account,
+        &lock.locking_token_account,
+        &lock.destination,
+        &env.mint.pubkey(),
+        refund,
+        lock.seeds,
+        &[],
+    )
+    .unwrap()
+}
+
+async fn lamports(env: &mut TestEnv, address: &Pubkey) -> u64 {
+    env.context.banks_client.get_balance(*address).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_claim_and_close_partially_vested_lock() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let lock = create_lock(&mut env, start).await;
+
+    // Nothing has matured yet
+    let instruction = claim_and_close_instruction(&env, &lock, &lock.beneficiary.pubkey());
+    assert_instruction_error(process(&mut env.context, &[instruction], &[]).await, InstructionError::InvalidArgument);
+
+    warp_to_timestamp(&mut env.context, start + 100).await;
+    let instruction = claim_and_close_instruction(&env, &lock, &lock.beneficiary.pubkey());
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    // Behaves like Unlock and leaves the lock in place
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 100);
+    assert_eq!(token_balance(&mut env.context, &lock.locking_token_account).await, 200);
+    let data = get_account(&mut env.context, &lock.locking_account).await.data;
+    let header_len = LockScheduleHeader::header_len(data.len());
+    let schedules = unpack_schedules(&data[header_len..]).unwrap();
+    assert_eq!(schedules.iter().map(|s| s.amount).collect::<Vec<_>>(), vec![0, 200]);
+    assert_eq!(lamports(&mut env, &lock.beneficiary.pubkey()).await, 0);
+}
+
+#[tokio::test]
+async fn test_claim_and_close_fully_vested_lock() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let lock = create_lock(&mut env, start).await;
+    let rent = lamports(&mut env, &lock.locking_account).await + lamports(&mut env, &lock.locking_token_account).await;
+
+    warp_to_timestamp(&mut env.context, start + 200).await;
+
+    // The rent only goes to the owner of the destination
+    let instruction = claim_and_close_instruction(&env, &lock, &env.context.payer.pubkey());
+    assert_instruction_error(process(&mut env.context, &[instruction], &[]).await, InstructionError::InvalidArgument);
+
+    let instruction = claim_and_close_instruction(&env, &lock, &lock.beneficiary.pubkey());
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 300);
+    assert!(env.context.banks_client.get_account(lock.locking_account).await.unwrap().is_none());
+    assert!(env.context.banks_client.get_account(lock.locking_token_account).await.unwrap().is_none());
+    assert_eq!(lamports(&mut env, &lock.beneficiary.pubkey()).await, rent);
+}
+
+// A lock drained while tokens sent to its locking token account kept it open is closed once they are swept
+#[tokio::test]
+async fn test_claim_and_close_drained_lock() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let lock = create_lock(&mut env, start).await;
+
+    // A token sent to the locking token account keeps the lock open
+    let payer = env.context.payer.pubkey();
+    let extra = create_token_account(&mut env.context, &env.mint.pubkey(), &payer).await;
+    mint_to(&mut env.context, &env.mint.pubkey(), &extra, 1).await;
+    let transfer = spl_token::instruction::transfer(&spl_token::id(), &extra, &lock.locking_token_account, &payer, &[], 1)
+        .unwrap();
+    process(&mut env.context, &[transfer], &[]).await.unwrap();
+
+    warp_to_timestamp(&mut env.context, start + 200).await;
+    let instruction = claim_and_close_instruction(&env, &lock, &lock.beneficiary.pubkey());
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 300);
+    assert_eq!(token_balance(&mut env.context, &lock.locking_token_account).await, 1);
+
+    // Nothing matured is left, and the extra token still keeps the lock open
+    refresh_blockhash(&mut env.context).await;
+    let instruction = claim_and_close_instruction(&env, &lock, &lock.beneficiary.pubkey());
+    assert_instruction_error(process(&mut env.context, &[instruction], &[]).await, InstructionError::InvalidArgument);
+    assert_eq!(get_account(&mut env.context, &lock.locking_account).await.owner, env.program_id);
+
+    let sweep = sweep_excess(
+        &env.program_id,
+        &spl_token::id(),
+        &lock.locking_account,
+        &lock.locking_token_account,
+        &extra,
+        &env.mint.pubkey(),
+        lock.seeds,
+    )
+    .unwrap();
+    let instruction = claim_and_close_instruction(&env, &lock, &lock.beneficiary.pubkey());
+    process(&mut env.context, &[sweep, instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &extra).await, 1);
+    assert!(env.context.banks_client.get_account(lock.locking_account).await.unwrap().is_none());
+    assert!(env.context.banks_client.get_account(lock.locking_token_account).await.unwrap().is_none());
+}
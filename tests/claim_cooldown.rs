@@ -0,0 +1,143 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    client::unpack_lock_header,
+    error::LockTokenError,
+    instruction::{create, CreateFeePayment, LockSeed, Schedule},
+    time::TimeBasis,
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_program::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+use solana_sdk::signature::{Keypair, Signer};
+
+struct Lock {
+    seeds: LockSeed,
+    locking_account: Pubkey,
+    destination: Pubkey,
+}
+
+// Locks 300 tokens in three schedules of 100 released at start + 100, start + 150 and start + 300
+async fn create_lock(env: &mut TestEnv, start: u64, min_claim_interval_secs: u32) -> Lock {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 300).await;
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+
+    let seeds = LockSeed::random(&env.program_id);
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    let schedules = vec![
+        Schedule { release_time: start + 100, amount: 100 },
+        Schedule { release_time: start + 150, amount: 100 },
+        Schedule { release_time: start + 300, amount: 100 },
+    ];
+    let instructions = [
+        init_instruction(env, seeds, schedules.len() as u32),
+        create(
+            &env.program_id,
+            &spl_token::id(),
+            &system_program::id(),
+            &sysvar::rent::id(),
+            &spl_associated_token_account::id(),
+            &env.price_estimator,
+            &locking_account,
+            &get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id()),
+            &payer,
+            &source,
+            &payer,
+            &destination,
+            &env.company_wallet,
+            &mint,
+            schedules,
+            seeds,
+            0,
+            false,
+            TimeBasis::UnixTimestamp,
+            Vec::new(),
+            min_claim_interval_secs,
+            CreateFeePayment::Sol,
+            None,
+            false,
+            &[],
+        )
+        .unwrap(),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+
+    Lock { seeds, locking_account, destination }
+}
+
+async fn last_claim_ts(env: &mut TestEnv, lock: &Lock) -> u64 {
+    let data = get_account(&mut env.context, &lock.locking_account).await.data;
+    unpack_lock_header(&data).unwrap().last_claim_ts
+}
+
+#[tokio::test]
+async fn test_back_to_back_claims_are_rejected() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let lock = create_lock(&mut env, start, 100).await;
+
+    warp_to_timestamp(&mut env.context, start + 100).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 100);
+    assert_eq!(last_claim_ts(&mut env, &lock).await, start + 100);
+
+    // The second schedule has matured but the cooldown has not passed
+    warp_to_timestamp(&mut env.context, start + 150).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::Custom(LockTokenError::ClaimTooSoon as u32),
+    );
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 100);
+    assert_eq!(last_claim_ts(&mut env, &lock).await, start + 100);
+}
+
+#[tokio::test]
+async fn test_claim_at_the_end_of_the_cooldown() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let lock = create_lock(&mut env, start, 100).await;
+
+    warp_to_timestamp(&mut env.context, start + 100).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    warp_to_timestamp(&mut env.context, start + 199).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::Custom(LockTokenError::ClaimTooSoon as u32),
+    );
+
+    // Exactly min_claim_interval_secs after the previous claim
+    warp_to_timestamp(&mut env.context, start + 200).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 200);
+    assert_eq!(last_claim_ts(&mut env, &lock).await, start + 200);
+}
+
+#[tokio::test]
+async fn test_zero_interval_does_not_limit_claims() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let lock = create_lock(&mut env, start, 0).await;
+
+    warp_to_timestamp(&mut env.context, start + 100).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    warp_to_timestamp(&mut env.context, start + 150).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 200);
+}
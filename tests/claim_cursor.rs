@@ -0,0 +1,199 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    client::unpack_lock_header,
+    error::LockTokenError,
+    instruction::{relock, unlock_amount, LockSeed, Schedule},
+    pod::{validate_cursor, PodLockSchedule},
+    state::{unpack_schedules, LockSchedule, LockScheduleHeader},
+    time::is_matured,
+};
+use proptest::{collection::vec, prelude::*};
+use solana_program::{instruction::InstructionError, program_pack::Pack};
+use solana_sdk::signature::Signer;
+
+#[derive(Clone, Debug)]
+enum Operation {
+    // Moves the clock forward by the seconds
+    Advance(u64),
+    Unlock,
+    UnlockAmount(u64),
+    // Relocks one of the matured schedules, picked by the index among them, for the seconds from now
+    Relock(usize, u64),
+}
+
+fn operation() -> impl Strategy<Value = Operation> {
+    prop_oneof![
+        (1..150u64).prop_map(Operation::Advance),
+        Just(Operation::Unlock),
+        (1..150u64).prop_map(Operation::UnlockAmount),
+        (any::<usize>(), 1..300u64).prop_map(|(index, delay)| Operation::Relock(index, delay)),
+    ]
+}
+
+struct Snapshot {
+    header: LockScheduleHeader,
+    schedules: Vec<LockSchedule>,
+    paid: u64,
+    vault: u64,
+}
+
+async fn snapshot(env: &mut TestEnv, lock: &TestLock) -> Snapshot {
+    let data = get_account(&mut env.context, &lock.locking_account).await.data;
+    let header_len = LockScheduleHeader::header_len(data.len());
+    Snapshot {
+        header: unpack_lock_header(&data).unwrap(),
+        schedules: unpack_schedules(&data[header_len..]).unwrap(),
+        paid: token_balance(&mut env.context, &lock.destination).await,
+        vault: token_balance(&mut env.context, &lock.locking_token_account).await,
+    }
+}
+
+// Nothing is lost or paid twice, and the cursor only skips emptied schedules
+fn assert_consistent(snapshot: &Snapshot, total: u64) {
+    assert_eq!(snapshot.paid + snapshot.vault, total);
+    assert_eq!(snapshot.header.claimed_amount, snapshot.paid);
+    assert_eq!(snapshot.schedules.iter().map(|s| s.amount).sum::<u64>(), total - snapshot.paid);
+    let mut data = vec![0; snapshot.schedules.len() * LockSchedule::LEN];
+    for (slot, schedule) in data.chunks_exact_mut(LockSchedule::LEN).zip(snapshot.schedules.iter()) {
+        schedule.pack_into_slice(slot);
+    }
+    validate_cursor(&snapshot.header, PodLockSchedule::try_cast_slice_mut(&mut data).unwrap()).unwrap();
+}
+
+fn matured_amount(schedules: &[LockSchedule], now: u64) -> u64 {
+    schedules.iter().filter(|s| is_matured(s.release_time, now)).map(|s| s.amount).sum()
+}
+
+async fn run(amounts: Vec<u64>, operations: Vec<Operation>) {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let schedules = amounts
+        .iter()
+        .enumerate()
+        .map(|(i, amount)| Schedule { release_time: start + 100 * (i as u64 + 1), amount: *amount })
+        .collect();
+    let lock = create_test_lock(&mut env, schedules).await;
+    let total: u64 = amounts.iter().sum();
+    let mut time = start;
+
+    for operation in operations {
+        refresh_blockhash(&mut env.context).await;
+        let before = snapshot(&mut env, &lock).await;
+        let matured = matured_amount(&before.schedules, time);
+        match operation {
+            Operation::Advance(secs) => {
+                time += secs;
+                warp_to_timestamp(&mut env.context, time).await;
+            }
+            Operation::Unlock => {
+                let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+                let result = process(&mut env.context, &[instruction], &[]).await;
+                assert_eq!(result.is_ok(), matured > 0);
+                let after = snapshot(&mut env, &lock).await;
+                assert_eq!(after.paid, before.paid + matured);
+                // No schedule holding tokens is left behind the ones released
+                assert_eq!(matured_amount(&after.schedules, time), 0);
+            }
+            Operation::UnlockAmount(amount) => {
+                let instruction = unlock_amount(
+                    &env.program_id,
+                    &spl_token::id(),
+                    &lock.locking_account,
+                    &lock.locking_token_account,
+                    &lock.destination,
+                    &env.mint.pubkey(),
+                    lock.seeds,
+                    amount,
+                    &[],
+                )
+                .unwrap();
+                let result = process(&mut env.context, &[instruction], &[]).await;
+                let after = snapshot(&mut env, &lock).await;
+                if amount <= matured {
+                    result.unwrap();
+                    assert_eq!(after.paid, before.paid + amount);
+                    assert_eq!(matured_amount(&after.schedules, time), matured - amount);
+                } else {
+                    assert!(result.is_err());
+                    assert_eq!(after.paid, before.paid);
+                }
+            }
+            Operation::Relock(index, delay) => {
+                let candidates: Vec<usize> = (0..before.schedules.len())
+                    .filter(|&i| before.schedules[i].amount > 0 && is_matured(before.schedules[i].release_time, time))
+                    .collect();
+                if candidates.is_empty() {
+                    continue;
+                }
+                let index = candidates[index % candidates.len()];
+                let instruction = relock(
+                    &env.program_id,
+                    &lock.locking_account,
+                    &lock.beneficiary.pubkey(),
+                    &lock.destination,
+                    lock.seeds,
+                    index as u32,
+                    time + delay,
+                )
+                .unwrap();
+                process(&mut env.context, &[instruction], &[&lock.beneficiary]).await.unwrap();
+                let after = snapshot(&mut env, &lock).await;
+                assert_eq!(matured_amount(&after.schedules, time), matured - before.schedules[index].amount);
+                assert!(after.schedules.windows(2).all(|pair| pair[0].release_time <= pair[1].release_time));
+            }
+        }
+        assert_consistent(&snapshot(&mut env, &lock).await, total);
+    }
+
+    // Everything left is paid out once the last schedule has matured
+    let last = snapshot(&mut env, &lock).await;
+    if last.vault > 0 {
+        time += 1000;
+        warp_to_timestamp(&mut env.context, time).await;
+        let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+        process(&mut env.context, &[instruction], &[]).await.unwrap();
+    }
+    let last = snapshot(&mut env, &lock).await;
+    assert_consistent(&last, total);
+    assert_eq!(last.paid, total);
+    assert!(last.header.is_drained());
+}
+
+proptest! {
+    // Every case starts a program test, so there are fewer of them than in the other property tests
+    #![proptest_config(ProptestConfig::with_cases(16))]
+
+    #[test]
+    fn test_claim_cursor_interleaved(amounts in vec(1..100u64, 1..5), operations in vec(operation(), 1..10)) {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(run(amounts, operations));
+    }
+}
+
+// A cursor stored past a schedule that still holds tokens is rejected rather than skipping the schedule
+#[tokio::test]
+async fn test_unlock_rejects_a_cursor_past_a_locked_schedule() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let destination = create_token_account(&mut env.context, &env.mint.pubkey(), &Default::default()).await;
+    let lock = set_lock(&mut env, &destination, LockScheduleHeader::LEN, 3, 2, start).await;
+
+    let mut account = get_account(&mut env.context, &lock.locking_account).await;
+    let mut header = unpack_lock_header(&account.data).unwrap();
+    header.unlocked_count = 1;
+    header.pack_into_slice(&mut account.data[..LockScheduleHeader::LEN]);
+    env.context.set_account(&lock.locking_account, &account.into());
+
+    let instruction = unlock_instruction(&env, lock.seeds, &destination);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::Custom(LockTokenError::InvalidClaimCursor as u32),
+    );
+}
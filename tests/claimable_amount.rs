@@ -0,0 +1,120 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    events::{decode, ClaimableAmount},
+    instruction::{get_claimable_amount, unlock, Schedule},
+    state::{PAUSE_CREATE, PAUSE_TRANSFER, PAUSE_UNLOCK},
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_sdk::{
+    signature::Signer,
+    transaction::Transaction,
+};
+use std::convert::TryInto;
+
+// Locks 300 of the context payer's tokens in two schedules of 100 and 200, returning the lock and its start
+async fn create_lock(env: &mut TestEnv) -> (TestLock, u64) {
+    let start = now(&mut env.context).await;
+    let schedules = vec![
+        Schedule { release_time: start + 100, amount: 100 },
+        Schedule { release_time: start + 200, amount: 200 },
+    ];
+    (create_test_lock(env, schedules).await, start)
+}
+
+// Simulates GetClaimableAmount and returns the amount of the return data, checking it against the logged event
+async fn simulate_claimable_amount(env: &mut TestEnv, lock: &TestLock) -> u64 {
+    let instruction = get_claimable_amount(&env.program_id, &lock.locking_account, lock.seeds).unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&env.context.payer.pubkey()),
+        &[&env.context.payer],
+        env.context.last_blockhash,
+    );
+    let simulation = env.context.banks_client.simulate_transaction(transaction).await.unwrap();
+    simulation.result.unwrap().unwrap();
+    let details = simulation.simulation_details.unwrap();
+
+    let return_data = details.return_data.unwrap();
+    assert_eq!(return_data.program_id, env.program_id);
+    let amount = u64::from_le_bytes(return_data.data.as_slice().try_into().unwrap());
+
+    let event = details.logs.iter().find_map(|log| decode::<ClaimableAmount>(log)).unwrap();
+    assert_eq!(event.locking_account, lock.locking_account);
+    assert_eq!(event.amount, amount);
+    amount
+}
+
+async fn unlock_lock(env: &mut TestEnv, lock: &TestLock) {
+    let mint = env.mint.pubkey();
+    let instruction = unlock(
+        &env.program_id,
+        &spl_token::id(),
+        &lock.locking_account,
+        &get_associated_token_address_with_program_id(&lock.locking_account, &mint, &spl_token::id()),
+        &lock.destination,
+        &mint,
+        lock.seeds,
+        0,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_claimable_amount_of_locked_lock() {
+    let mut env = setup(true).await;
+    let (lock, _) = create_lock(&mut env).await;
+
+    assert_eq!(simulate_claimable_amount(&mut env, &lock).await, 0);
+}
+
+#[tokio::test]
+async fn test_claimable_amount_of_matured_schedules() {
+    let mut env = setup(true).await;
+    let (lock, start) = create_lock(&mut env).await;
+
+    warp_to_timestamp(&mut env.context, start + 100).await;
+    assert_eq!(simulate_claimable_amount(&mut env, &lock).await, 100);
+
+    // Unlocked schedules are not claimable again
+    unlock_lock(&mut env, &lock).await;
+    assert_eq!(simulate_claimable_amount(&mut env, &lock).await, 0);
+
+    warp_to_timestamp(&mut env.context, start + 200).await;
+    assert_eq!(simulate_claimable_amount(&mut env, &lock).await, 200);
+}
+
+#[tokio::test]
+async fn test_claimable_amount_of_drained_lock() {
+    let mut env = setup(true).await;
+    let (lock, start) = create_lock(&mut env).await;
+
+    warp_to_timestamp(&mut env.context, start + 200).await;
+    assert_eq!(simulate_claimable_amount(&mut env, &lock).await, 300);
+
+    unlock_lock(&mut env, &lock).await;
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 300);
+    assert_eq!(simulate_claimable_amount(&mut env, &lock).await, 0);
+}
+
+#[tokio::test]
+async fn test_claimable_amount_is_read_only() {
+    let mut env = setup(true).await;
+    let (lock, start) = create_lock(&mut env).await;
+    warp_to_timestamp(&mut env.context, start + 100).await;
+
+    // Works while everything is paused
+    let instruction = pause_contract(&env.program_id, &env.admin.pubkey(), PAUSE_CREATE | PAUSE_UNLOCK | PAUSE_TRANSFER);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+
+    assert_eq!(simulate_claimable_amount(&mut env, &lock).await, 100);
+
+    let before = get_account(&mut env.context, &lock.locking_account).await;
+    let instruction = get_claimable_amount(&env.program_id, &lock.locking_account, lock.seeds).unwrap();
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(get_account(&mut env.context, &lock.locking_account).await, before);
+}
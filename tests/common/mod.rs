@@ -0,0 +1,570 @@
+// Setup shared by the integration tests: a program test with the global state and a free token state
+// already in place, plus helpers to create mints and token accounts and to move the clock.
+
+#![allow(dead_code)]
+
+use lock_token::{
+    instruction::{create, init, unlock, unlock_multi, CreateFeePayment, LockSeed, LockTokenInstruction, Schedule},
+    processor::Processor,
+    state::{
+        find_global_state_address, pack_schedules_into_slice, token_state_address, FeeDiscountTier, LockGlobalState,
+        LockSchedule, LockScheduleHeader, TokenState, MAX_CANDIDATE_WALLETS, MAX_FEE_DISCOUNT_TIERS,
+    },
+    time::TimeBasis,
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction, system_program,
+    sysvar::{self, clock::Clock},
+};
+use solana_program_test::{processor, BanksTransactionResultWithMetadata, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token::state::{Account as TokenAccount, AccountState};
+
+pub struct TestEnv {
+    pub context: ProgramTestContext,
+    pub program_id: Pubkey,
+    pub admin: Keypair,
+    pub company_wallet: Pubkey,
+    pub price_estimator: Pubkey,
+    pub mint: Keypair,
+}
+
+pub fn global_state(admin: &Pubkey, company_wallet: &Pubkey, price_estimator: &Pubkey) -> LockGlobalState {
+    LockGlobalState {
+        price_estimator: *price_estimator,
+        usd_token_address: Pubkey::new_unique(),
+        fees_in_usd: 0,
+        company_wallet: *company_wallet,
+        max_price_age_slots: 0,
+        candidate_wallets: [*company_wallet; MAX_CANDIDATE_WALLETS],
+        admin: *admin,
+        pending_admin: Pubkey::default(),
+        min_lock_duration_secs: 0,
+        min_lock_amount: 0,
+        fee_bps_in_token: 0,
+        owner_token_mint: Pubkey::default(),
+        fee_discount_tiers: [FeeDiscountTier::default(); MAX_FEE_DISCOUNT_TIERS],
+        max_lock_duration_secs: 0,
+        paused_at: 0,
+        paused_by: Pubkey::default(),
+        pending_candidate_wallets: [Pubkey::default(); MAX_CANDIDATE_WALLETS],
+        candidate_wallets_execute_after: 0,
+        pause_create: false,
+        pause_unlock: false,
+        pause_transfer: false,
+        is_initialized: true,
+    }
+}
+
+// Not every mint derives a token state account, so keep drawing keypairs until one does. Derives the address
+// directly, as token_state_address logs its failures, which panics outside of a program once ProgramTest runs.
+pub fn mint_keypair(program_id: &Pubkey) -> Keypair {
+    loop {
+        let mint = Keypair::new();
+        if Pubkey::create_program_address(&[&mint.pubkey().to_bytes()], program_id).is_ok() {
+            return mint;
+        }
+    }
+}
+
+pub fn program_account<T: Pack>(program_id: &Pubkey, state: &T) -> Account {
+    let mut data = vec![0; T::LEN];
+    state.pack_into_slice(&mut data);
+    Account {
+        lamports: 1_000_000_000,
+        data,
+        owner: *program_id,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+pub struct StoredLock {
+    pub seeds: LockSeed,
+    pub locking_account: Pubkey,
+    pub locking_token_account: Pubkey,
+}
+
+pub fn program_owned_account(owner: &Pubkey, lamports: u64, data: Vec<u8>) -> AccountSharedData {
+    AccountSharedData::from(Account {
+        lamports,
+        data,
+        owner: *owner,
+        executable: false,
+        rent_epoch: 0,
+    })
+}
+
+// Locks with hundreds of schedules do not fit in a Create transaction, so the lock and its vault are written
+// straight into the accounts. Every schedule locks 1 token, the first `matured_count` have matured at `now`.
+pub async fn set_lock(
+    env: &mut TestEnv,
+    destination: &Pubkey,
+    header_len: usize,
+    schedule_count: usize,
+    matured_count: usize,
+    now: u64,
+) -> StoredLock {
+    let mint = env.mint.pubkey();
+    let seeds = LockSeed::random(&env.program_id);
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    let locking_token_account = get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id());
+    let rent = env.context.banks_client.get_rent().await.unwrap();
+
+    let header = LockScheduleHeader {
+        destination_address: *destination,
+        mint_address: mint,
+        is_initialized: true,
+        pending_destination_address: Pubkey::default(),
+        is_vesting: false,
+        is_nft: false,
+        is_registered: false,
+        is_immutable: false,
+        is_multi: false,
+        is_derived: false,
+        total_amount: schedule_count as u64,
+        claimed_amount: 0,
+        schedule_count: schedule_count as u32,
+        unlocked_count: 0,
+        rescue_destination_address: Pubkey::default(),
+        rescue_execute_after: 0,
+        creator_address: Pubkey::default(),
+        time_basis: TimeBasis::UnixTimestamp,
+        split_destinations: Default::default(),
+        delegate_address: Pubkey::default(),
+        permit_nonce: 0,
+        min_claim_interval_secs: 0,
+        last_claim_ts: 0,
+        lock_nonce: 0,
+        lock_bump: 0,
+        created_at: 0,
+    };
+    let schedules = (0..schedule_count as u64)
+        .map(|i| LockSchedule {
+            release_time: if i < matured_count as u64 { now } else { now + 1000 + i },
+            amount: 1,
+        })
+        .collect();
+    let mut data = vec![0; header_len + schedule_count * LockSchedule::LEN];
+    header.pack_into_slice(&mut data[..header_len]);
+    pack_schedules_into_slice(schedules, &mut data[header_len..]);
+    let lamports = rent.minimum_balance(data.len());
+    env.context
+        .set_account(&locking_account, &program_owned_account(&env.program_id, lamports, data));
+
+    let vault = TokenAccount {
+        mint,
+        owner: locking_account,
+        amount: schedule_count as u64,
+        state: AccountState::Initialized,
+        ..TokenAccount::default()
+    };
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount::pack(vault, &mut data).unwrap();
+    let lamports = rent.minimum_balance(data.len());
+    env.context
+        .set_account(&locking_token_account, &program_owned_account(&spl_token::id(), lamports, data));
+
+    StoredLock {
+        seeds,
+        locking_account,
+        locking_token_account,
+    }
+}
+
+// Starts the program with an initialized global state when `with_global_state` is set.
+// The mint is free, so creating a lock needs no price oracle.
+pub async fn setup(with_global_state: bool) -> TestEnv {
+    let program_id = Pubkey::new_unique();
+    let admin = Keypair::new();
+    let company_wallet = Pubkey::new_unique();
+    let price_estimator = Pubkey::new_unique();
+    let mint = mint_keypair(&program_id);
+
+    let mut program_test = ProgramTest::new("lock_token", program_id, processor!(Processor::process_instruction));
+
+    if with_global_state {
+        let (program_state_account_key, _) = find_global_state_address(&program_id);
+        program_test.add_account(
+            program_state_account_key,
+            program_account(&program_id, &global_state(&admin.pubkey(), &company_wallet, &price_estimator)),
+        );
+    }
+
+    let token_state = TokenState {
+        mint_address: mint.pubkey(),
+        is_free: true,
+        fee_override_usd: 0,
+        has_override: false,
+        active_locks: 0,
+        total_locked_amount: 0,
+        last_snapshot_amount: 0,
+        last_snapshot_ts: 0,
+        is_initialized: true,
+    };
+    program_test.add_account(
+        token_state_address(&program_id, &mint.pubkey()).unwrap(),
+        program_account(&program_id, &token_state),
+    );
+
+    let mut context = program_test.start_with_context().await;
+    create_mint(&mut context, &mint).await;
+
+    TestEnv {
+        context,
+        program_id,
+        admin,
+        company_wallet,
+        price_estimator,
+        mint,
+    }
+}
+
+pub fn init_instruction(env: &TestEnv, seeds: LockSeed, number_of_schedules: u32) -> Instruction {
+    init(
+        &system_program::id(),
+        &sysvar::rent::id(),
+        &env.program_id,
+        &env.context.payer.pubkey(),
+        &seeds.locking_account_address(&env.program_id).unwrap(),
+        seeds,
+        number_of_schedules,
+    )
+    .unwrap()
+}
+
+// Create locking the schedules from the source token account, authorized by its owner or delegate.
+// The context payer pays the fee and the rent.
+pub fn create_lock_instruction(
+    env: &TestEnv,
+    seeds: LockSeed,
+    authority: &Pubkey,
+    source: &Pubkey,
+    destination: &Pubkey,
+    schedules: Vec<Schedule>,
+    is_immutable: bool,
+    signers: &[Pubkey],
+) -> Instruction {
+    let mint = env.mint.pubkey();
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    create(
+        &env.program_id,
+        &spl_token::id(),
+        &system_program::id(),
+        &sysvar::rent::id(),
+        &spl_associated_token_account::id(),
+        &env.price_estimator,
+        &locking_account,
+        &get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id()),
+        authority,
+        source,
+        &env.context.payer.pubkey(),
+        destination,
+        &env.company_wallet,
+        &mint,
+        schedules,
+        seeds,
+        0,
+        is_immutable,
+        TimeBasis::UnixTimestamp,
+        Vec::new(),
+        0,
+        CreateFeePayment::Sol,
+        None,
+        false,
+        signers,
+    )
+    .unwrap()
+}
+
+pub struct TestLock {
+    pub seeds: LockSeed,
+    pub locking_account: Pubkey,
+    pub locking_token_account: Pubkey,
+    pub destination: Pubkey,
+    pub beneficiary: Keypair,
+}
+
+// Locks the schedules from a token account of the context payer, minted for the occasion, for a fresh beneficiary
+pub async fn create_test_lock(env: &mut TestEnv, schedules: Vec<Schedule>) -> TestLock {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, schedules.iter().map(|s| s.amount).sum()).await;
+    let beneficiary = Keypair::new();
+    let destination = create_token_account(&mut env.context, &mint, &beneficiary.pubkey()).await;
+
+    let seeds = LockSeed::random(&env.program_id);
+    let instructions = [
+        init_instruction(env, seeds, schedules.len() as u32),
+        create_lock_instruction(env, seeds, &payer, &source, &destination, schedules, false, &[]),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    TestLock {
+        seeds,
+        locking_account,
+        locking_token_account: get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id()),
+        destination,
+        beneficiary,
+    }
+}
+
+// Unlock of the lock of the seeds into the destination, without a deadline
+pub fn unlock_instruction(env: &TestEnv, seeds: LockSeed, destination: &Pubkey) -> Instruction {
+    let mint = env.mint.pubkey();
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    unlock(
+        &env.program_id,
+        &spl_token::id(),
+        &locking_account,
+        &get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id()),
+        destination,
+        &mint,
+        seeds,
+        0,
+    )
+    .unwrap()
+}
+
+// Unlock of a CreateMulti or split lock, which also takes the destinations of its schedules or split shares
+pub fn unlock_multi_instruction(
+    env: &TestEnv,
+    seeds: LockSeed,
+    destination: &Pubkey,
+    other_destinations: &[Pubkey],
+) -> Instruction {
+    let mint = env.mint.pubkey();
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    unlock_multi(
+        &env.program_id,
+        &spl_token::id(),
+        &locking_account,
+        &get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id()),
+        destination,
+        &mint,
+        seeds,
+        0,
+        other_destinations,
+    )
+    .unwrap()
+}
+
+// Sends the instructions in one transaction paid by the context payer
+pub async fn process(
+    context: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), TransactionError> {
+    let mut all_signers = vec![&context.payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&context.payer.pubkey()),
+        &all_signers,
+        context.last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|error| error.unwrap())
+}
+
+// Processes the transaction and returns its logs and compute units. The banks server reports the status of a
+// transaction before it releases the accounts, so one processed right behind it may briefly find them in use.
+pub async fn process_with_metadata(
+    context: &mut ProgramTestContext,
+    transaction: Transaction,
+) -> BanksTransactionResultWithMetadata {
+    loop {
+        let result = context
+            .banks_client
+            .process_transaction_with_metadata(transaction.clone())
+            .await
+            .unwrap();
+        if result.result != Err(TransactionError::AccountInUse) {
+            return result;
+        }
+    }
+}
+
+pub fn assert_instruction_error(result: Result<(), TransactionError>, expected: InstructionError) {
+    assert_eq!(result.unwrap_err(), TransactionError::InstructionError(0, expected));
+}
+
+// The context payer is the mint and freeze authority
+pub async fn create_mint(context: &mut ProgramTestContext, mint: &Keypair) {
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let payer = context.payer.pubkey();
+    process(
+        context,
+        &[
+            system_instruction::create_account(
+                &payer,
+                &mint.pubkey(),
+                rent.minimum_balance(spl_token::state::Mint::LEN),
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_mint(&spl_token::id(), &mint.pubkey(), &payer, Some(&payer), 0).unwrap(),
+        ],
+        &[mint],
+    )
+    .await
+    .unwrap();
+}
+
+pub async fn create_token_account(context: &mut ProgramTestContext, mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+    let account = Keypair::new();
+    create_token_account_at(context, &account, mint, owner).await;
+    account.pubkey()
+}
+
+pub async fn create_token_account_at(context: &mut ProgramTestContext, account: &Keypair, mint: &Pubkey, owner: &Pubkey) {
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let payer = context.payer.pubkey();
+    process(
+        context,
+        &[
+            system_instruction::create_account(
+                &payer,
+                &account.pubkey(),
+                rent.minimum_balance(spl_token::state::Account::LEN),
+                spl_token::state::Account::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_account(&spl_token::id(), &account.pubkey(), mint, owner).unwrap(),
+        ],
+        &[account],
+    )
+    .await
+    .unwrap();
+}
+
+// An m of n spl token multisig account of the signers
+pub async fn create_multisig(context: &mut ProgramTestContext, signers: &[&Pubkey], m: u8) -> Pubkey {
+    let multisig = Keypair::new();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let payer = context.payer.pubkey();
+    process(
+        context,
+        &[
+            system_instruction::create_account(
+                &payer,
+                &multisig.pubkey(),
+                rent.minimum_balance(spl_token::state::Multisig::LEN),
+                spl_token::state::Multisig::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_multisig(&spl_token::id(), &multisig.pubkey(), signers, m).unwrap(),
+        ],
+        &[&multisig],
+    )
+    .await
+    .unwrap();
+    multisig.pubkey()
+}
+
+pub async fn mint_to(context: &mut ProgramTestContext, mint: &Pubkey, account: &Pubkey, amount: u64) {
+    let payer = context.payer.pubkey();
+    process(
+        context,
+        &[spl_token::instruction::mint_to(&spl_token::id(), mint, account, &payer, &[], amount).unwrap()],
+        &[],
+    )
+    .await
+    .unwrap();
+}
+
+// The account is owned by the context payer
+pub async fn approve(context: &mut ProgramTestContext, account: &Pubkey, delegate: &Pubkey, amount: u64) {
+    let payer = context.payer.pubkey();
+    process(
+        context,
+        &[spl_token::instruction::approve(&spl_token::id(), account, delegate, &payer, &[], amount).unwrap()],
+        &[],
+    )
+    .await
+    .unwrap();
+}
+
+pub async fn freeze_account(context: &mut ProgramTestContext, mint: &Pubkey, account: &Pubkey) {
+    let payer = context.payer.pubkey();
+    process(
+        context,
+        &[spl_token::instruction::freeze_account(&spl_token::id(), account, mint, &payer, &[]).unwrap()],
+        &[],
+    )
+    .await
+    .unwrap();
+}
+
+pub async fn get_account(context: &mut ProgramTestContext, address: &Pubkey) -> Account {
+    context.banks_client.get_account(*address).await.unwrap().unwrap()
+}
+
+pub async fn get_token_account(context: &mut ProgramTestContext, address: &Pubkey) -> spl_token::state::Account {
+    let account = get_account(context, address).await;
+    spl_token::state::Account::unpack(&account.data).unwrap()
+}
+
+pub async fn token_balance(context: &mut ProgramTestContext, address: &Pubkey) -> u64 {
+    get_token_account(context, address).await.amount
+}
+
+pub async fn now(context: &mut ProgramTestContext) -> u64 {
+    let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp as u64
+}
+
+// Moves the clock to the given time and refreshes the blockhash
+pub async fn warp_to_timestamp(context: &mut ProgramTestContext, unix_timestamp: u64) {
+    let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp = unix_timestamp as i64;
+    context.set_sysvar(&clock);
+    refresh_blockhash(context).await;
+}
+
+// Lets a transaction identical to an earlier one be sent again
+pub async fn refresh_blockhash(context: &mut ProgramTestContext) {
+    context.last_blockhash = context.get_new_latest_blockhash().await.unwrap();
+}
+
+// There is no builder for admin instructions taking no extra accounts, so build PauseContract here
+pub fn pause_contract(program_id: &Pubkey, admin: &Pubkey, pause_flags: u8) -> Instruction {
+    let (program_state_account_key, _) = find_global_state_address(program_id);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(program_state_account_key, false),
+        ],
+        data: LockTokenInstruction::PauseContract { pause_flags }.pack(),
+    }
+}
+
+// Writes a SOL/USD price account of the Pyth layout the program reads at the price estimator, published at the
+// current slot
+pub async fn set_sol_price(env: &mut TestEnv, price: i64, expo: i32) {
+    let clock: Clock = env.context.banks_client.get_sysvar().await.unwrap();
+    let mut data = vec![0; 240];
+    data[0..4].copy_from_slice(&0xa1b2c3d4u32.to_le_bytes());
+    data[4..8].copy_from_slice(&2u32.to_le_bytes());
+    data[8..12].copy_from_slice(&3u32.to_le_bytes());
+    data[20..24].copy_from_slice(&expo.to_le_bytes());
+    data[208..216].copy_from_slice(&price.to_le_bytes());
+    data[224..228].copy_from_slice(&1u32.to_le_bytes());
+    data[232..240].copy_from_slice(&clock.slot.to_le_bytes());
+    let price_estimator = env.price_estimator;
+    env.context.set_account(&price_estimator, &program_owned_account(&Pubkey::new_unique(), 1_000_000_000, data));
+}
@@ -0,0 +1,107 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    instruction::{unlock, LockSeed, Schedule},
+    state::LockScheduleHeader,
+};
+use solana_program::{instruction::Instruction, program_pack::Pack, pubkey::Pubkey};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+// Most compute units Create may use by number of schedules, and Unlock by number of matured schedules.
+// Lower them as optimizations land, the default budget of an instruction is 200_000.
+const CREATE_COMPUTE_UNIT_BOUNDS: [(usize, u64); 3] = [(1, 60_000), (10, 70_000), (100, 140_000)];
+const UNLOCK_COMPUTE_UNIT_BOUNDS: [(usize, u64); 4] = [(1, 40_000), (10, 45_000), (100, 70_000), (500, 180_000)];
+
+// The budget of the transaction is raised to the maximum, so a bound that is exceeded fails the assertion
+// rather than the transaction
+async fn compute_units(env: &mut TestEnv, instruction: Instruction) -> u64 {
+    refresh_blockhash(&mut env.context).await;
+    let transaction = Transaction::new_signed_with_payer(
+        &[ComputeBudgetInstruction::set_compute_unit_limit(1_400_000), instruction],
+        Some(&env.context.payer.pubkey()),
+        &[&env.context.payer],
+        env.context.last_blockhash,
+    );
+    let result = process_with_metadata(&mut env.context, transaction).await;
+    result.result.unwrap();
+    result.metadata.unwrap().compute_units_consumed
+}
+
+// A Create of 100 schedules is larger than a transaction packet and is only reachable through a CPI,
+// program-test does not limit the size of the transactions it processes
+async fn create_compute_units(env: &mut TestEnv, schedule_count: usize) -> u64 {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, schedule_count as u64).await;
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+
+    let start = now(&mut env.context).await;
+    let schedules = (0..schedule_count as u64)
+        .map(|i| Schedule { release_time: start + 1000 + i, amount: 1 })
+        .collect();
+    let seeds = LockSeed::random(&env.program_id);
+    let instruction = init_instruction(env, seeds, schedule_count as u32);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    let instruction = create_lock_instruction(env, seeds, &payer, &source, &destination, schedules, false, &[]);
+    compute_units(env, instruction).await
+}
+
+async fn unlock_compute_units(env: &mut TestEnv, destination: &Pubkey, matured_count: usize) -> u64 {
+    let start = now(&mut env.context).await;
+    // One schedule past the matured ones, so the unlock also has to find where they end
+    let lock = set_lock(env, destination, LockScheduleHeader::LEN, matured_count + 1, matured_count, start).await;
+    let instruction = unlock(
+        &env.program_id,
+        &spl_token::id(),
+        &lock.locking_account,
+        &lock.locking_token_account,
+        destination,
+        &env.mint.pubkey(),
+        lock.seeds,
+        0,
+    )
+    .unwrap();
+    compute_units(env, instruction).await
+}
+
+#[tokio::test]
+async fn test_compute_units_within_bounds() {
+    let mut env = setup(true).await;
+    let mint = env.mint.pubkey();
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+
+    let mut measurements = Vec::new();
+    for &(schedule_count, bound) in CREATE_COMPUTE_UNIT_BOUNDS.iter() {
+        let used = create_compute_units(&mut env, schedule_count).await;
+        measurements.push(("Create", schedule_count, used, bound));
+    }
+    for &(matured_count, bound) in UNLOCK_COMPUTE_UNIT_BOUNDS.iter() {
+        let used = unlock_compute_units(&mut env, &destination, matured_count).await;
+        measurements.push(("Unlock", matured_count, used, bound));
+    }
+
+    println!("{:<12}{:>10}{:>16}{:>10}", "instruction", "schedules", "compute units", "bound");
+    for &(name, schedule_count, used, bound) in measurements.iter() {
+        println!("{:<12}{:>10}{:>16}{:>10}", name, schedule_count, used, bound);
+    }
+    for &(name, schedule_count, used, bound) in measurements.iter() {
+        assert!(
+            used <= bound,
+            "{} with {} schedules used {} compute units, more than its bound of {}",
+            name,
+            schedule_count,
+            used,
+            bound
+        );
+    }
+    assert_eq!(token_balance(&mut env.context, &destination).await, 611);
+}
@@ -0,0 +1,90 @@
+#![cfg(all(feature = "test-bpf", feature = "verbose"))]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    instruction::{LockSeed, Schedule},
+    state::{find_global_state_address, token_state_address, TokenState},
+};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{
+    account::AccountSharedData,
+    signature::Signer,
+    transaction::Transaction,
+};
+
+// 10 USD at 100 USD per SOL
+const FEES_IN_USD: u64 = 10_000_000;
+const FEE_LAMPORTS: u64 = 100_000_000;
+
+// Charges FEES_IN_USD for locking the mint of the test environment
+fn charge_fees(env: &mut TestEnv) {
+    let mut state = global_state(&env.admin.pubkey(), &env.company_wallet, &env.price_estimator);
+    state.fees_in_usd = FEES_IN_USD;
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    let account = program_account(&env.program_id, &state);
+    env.context.set_account(&program_state_account_key, &AccountSharedData::from(account));
+
+    let mint = env.mint.pubkey();
+    let token_state = TokenState {
+        mint_address: mint,
+        is_free: false,
+        fee_override_usd: 0,
+        has_override: false,
+        active_locks: 0,
+        total_locked_amount: 0,
+        last_snapshot_amount: 0,
+        last_snapshot_ts: 0,
+        is_initialized: true,
+    };
+    let account = program_account(&env.program_id, &token_state);
+    env.context.set_account(&token_state_address(&env.program_id, &mint).unwrap(), &AccountSharedData::from(account));
+}
+
+// The verbose build logs what the client sent along with the computed fee, before moving any funds
+#[tokio::test]
+async fn test_create_logs_a_summary() {
+    let mut env = setup(true).await;
+    charge_fees(&mut env);
+    set_sol_price(&mut env, 10_000, -2).await;
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 600).await;
+    let destination = create_token_account(&mut env.context, &mint, &Pubkey::new_unique()).await;
+
+    let start = now(&mut env.context).await;
+    let seeds = LockSeed::random(&env.program_id);
+    let schedules = vec![
+        Schedule { release_time: start + 100, amount: 100 },
+        Schedule { release_time: start + 200, amount: 200 },
+        Schedule { release_time: start + 300, amount: 300 },
+    ];
+    let instructions = [
+        init_instruction(&env, seeds, 3),
+        create_lock_instruction(&env, seeds, &payer, &source, &destination, schedules, false, &[]),
+    ];
+    let transaction = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&payer),
+        &[&env.context.payer],
+        env.context.last_blockhash,
+    );
+    let simulation = env.context.banks_client.simulate_transaction(transaction).await.unwrap();
+    simulation.result.unwrap().unwrap();
+    let logs = simulation.simulation_details.unwrap().logs;
+
+    let summary = format!(
+        "Program log: Create summary: schedules=3 total_amount=600 first_release_time={} last_release_time={} \
+         mint={} destination={} fee_lamports={}",
+        start + 100,
+        start + 300,
+        mint,
+        destination,
+        FEE_LAMPORTS,
+    );
+    let position = logs.iter().position(|log| *log == summary).unwrap();
+    // and before any transfer
+    assert!(logs[..position].iter().all(|log| !log.contains(&spl_token::id().to_string())));
+}
@@ -0,0 +1,130 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    error::LockTokenError,
+    instruction::{create, unlock, CreateFeePayment, LockSeed, Schedule},
+    time::TimeBasis,
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+use solana_sdk::{signature::Signer, transaction::TransactionError};
+
+const EXPIRED: InstructionError = InstructionError::Custom(LockTokenError::TransactionExpired as u32);
+
+// Create of 10 tokens released at release_time from a token account of the context payer, valid until the deadline
+fn create_with_deadline(
+    env: &TestEnv,
+    seeds: LockSeed,
+    source: &Pubkey,
+    destination: &Pubkey,
+    release_time: u64,
+    deadline: u64,
+) -> Instruction {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    create(
+        &env.program_id,
+        &spl_token::id(),
+        &system_program::id(),
+        &sysvar::rent::id(),
+        &spl_associated_token_account::id(),
+        &env.price_estimator,
+        &locking_account,
+        &get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id()),
+        &payer,
+        source,
+        &payer,
+        destination,
+        &env.company_wallet,
+        &mint,
+        vec![Schedule { release_time, amount: 10 }],
+        seeds,
+        deadline,
+        false,
+        TimeBasis::UnixTimestamp,
+        Vec::new(),
+        0,
+        CreateFeePayment::Sol,
+        None,
+        false,
+        &[],
+    )
+    .unwrap()
+}
+
+fn unlock_with_deadline(env: &TestEnv, lock: &TestLock, deadline: u64) -> Instruction {
+    unlock(
+        &env.program_id,
+        &spl_token::id(),
+        &lock.locking_account,
+        &lock.locking_token_account,
+        &lock.destination,
+        &env.mint.pubkey(),
+        lock.seeds,
+        deadline,
+    )
+    .unwrap()
+}
+
+// A Create landing after its deadline is rejected, one landing exactly at it goes through
+#[tokio::test]
+async fn test_create_deadline_boundary() {
+    let mut env = setup(true).await;
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 10).await;
+    let destination = create_token_account(&mut env.context, &mint, &Pubkey::new_unique()).await;
+    let start = now(&mut env.context).await;
+
+    let seeds = LockSeed::random(&env.program_id);
+    let instructions = [
+        init_instruction(&env, seeds, 1),
+        create_with_deadline(&env, seeds, &source, &destination, start + 100, start - 1),
+    ];
+    assert_eq!(
+        process(&mut env.context, &instructions, &[]).await.unwrap_err(),
+        TransactionError::InstructionError(1, EXPIRED)
+    );
+
+    let instructions = [
+        init_instruction(&env, seeds, 1),
+        create_with_deadline(&env, seeds, &source, &destination, start + 100, start),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &source).await, 0);
+}
+
+// An Unlock landing after its deadline is rejected, one landing exactly at it or without one goes through
+#[tokio::test]
+async fn test_unlock_deadline_boundary() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let lock = create_test_lock(&mut env, vec![
+        Schedule { release_time: start + 100, amount: 10 },
+        Schedule { release_time: start + 200, amount: 20 },
+    ])
+    .await;
+
+    warp_to_timestamp(&mut env.context, start + 100).await;
+    let instruction = unlock_with_deadline(&env, &lock, start + 99);
+    assert_instruction_error(process(&mut env.context, &[instruction], &[]).await, EXPIRED);
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 0);
+
+    let instruction = unlock_with_deadline(&env, &lock, start + 100);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 10);
+
+    warp_to_timestamp(&mut env.context, start + 200).await;
+    let instruction = unlock_with_deadline(&env, &lock, 0);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 30);
+}
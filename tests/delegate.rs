@@ -0,0 +1,154 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    error::LockTokenError,
+    instruction::{LockSeed, Schedule},
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_program::{instruction::InstructionError, program_option::COption, pubkey::Pubkey};
+use solana_sdk::signature::{Keypair, Signer};
+
+struct Treasury {
+    source: Pubkey,
+    destination: Pubkey,
+    launchpad: Keypair,
+}
+
+// A treasury of the context payer with an allowance of `delegated_amount` for a launchpad delegate
+async fn setup_treasury(env: &mut TestEnv, delegated_amount: u64) -> Treasury {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 1000).await;
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+
+    let launchpad = Keypair::new();
+    approve(&mut env.context, &source, &launchpad.pubkey(), delegated_amount).await;
+
+    Treasury {
+        source,
+        destination,
+        launchpad,
+    }
+}
+
+async fn init_lock(env: &mut TestEnv, schedules: &[Schedule]) -> LockSeed {
+    let seeds = LockSeed::random(&env.program_id);
+    let instruction = init_instruction(env, seeds, schedules.len() as u32);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    seeds
+}
+
+fn schedules(now: u64) -> Vec<Schedule> {
+    vec![
+        Schedule { release_time: now + 100, amount: 300 },
+        Schedule { release_time: now + 200, amount: 300 },
+    ]
+}
+
+#[tokio::test]
+async fn test_create_as_delegate() {
+    let mut env = setup(true).await;
+    let treasury = setup_treasury(&mut env, 800).await;
+    let schedules = schedules(now(&mut env.context).await);
+    let seeds = init_lock(&mut env, &schedules).await;
+
+    let launchpad = treasury.launchpad.pubkey();
+    let instruction = create_lock_instruction(
+        &env,
+        seeds,
+        &launchpad,
+        &treasury.source,
+        &treasury.destination,
+        schedules,
+        false,
+        &[],
+    );
+    process(&mut env.context, &[instruction], &[&treasury.launchpad]).await.unwrap();
+
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    let locking_token_account = get_associated_token_address_with_program_id(&locking_account, &env.mint.pubkey(), &spl_token::id());
+    assert_eq!(token_balance(&mut env.context, &locking_token_account).await, 600);
+    let source = get_token_account(&mut env.context, &treasury.source).await;
+    assert_eq!(source.amount, 400);
+    assert_eq!(source.delegate, COption::Some(launchpad));
+    assert_eq!(source.delegated_amount, 200);
+}
+
+// The owner still locks without touching the allowance of the delegate
+#[tokio::test]
+async fn test_create_as_owner_with_delegate() {
+    let mut env = setup(true).await;
+    let treasury = setup_treasury(&mut env, 800).await;
+    let schedules = schedules(now(&mut env.context).await);
+    let seeds = init_lock(&mut env, &schedules).await;
+
+    let owner = env.context.payer.pubkey();
+    let instruction = create_lock_instruction(
+        &env,
+        seeds,
+        &owner,
+        &treasury.source,
+        &treasury.destination,
+        schedules,
+        false,
+        &[],
+    );
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    let source = get_token_account(&mut env.context, &treasury.source).await;
+    assert_eq!(source.amount, 400);
+    assert_eq!(source.delegated_amount, 800);
+}
+
+#[tokio::test]
+async fn test_create_as_delegate_over_allowance() {
+    let mut env = setup(true).await;
+    let treasury = setup_treasury(&mut env, 500).await;
+    let schedules = schedules(now(&mut env.context).await);
+    let seeds = init_lock(&mut env, &schedules).await;
+
+    let launchpad = treasury.launchpad.pubkey();
+    let instruction = create_lock_instruction(
+        &env,
+        seeds,
+        &launchpad,
+        &treasury.source,
+        &treasury.destination,
+        schedules,
+        false,
+        &[],
+    );
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&treasury.launchpad]).await,
+        InstructionError::Custom(LockTokenError::InsufficientDelegatedAmount as u32),
+    );
+}
+
+#[tokio::test]
+async fn test_create_as_stranger() {
+    let mut env = setup(true).await;
+    let treasury = setup_treasury(&mut env, 800).await;
+    let schedules = schedules(now(&mut env.context).await);
+    let seeds = init_lock(&mut env, &schedules).await;
+
+    let stranger = Keypair::new();
+
+    let instruction = create_lock_instruction(
+        &env,
+        seeds,
+        &stranger.pubkey(),
+        &treasury.source,
+        &treasury.destination,
+        schedules,
+        false,
+        &[],
+    );
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&stranger]).await,
+        InstructionError::Custom(LockTokenError::SourceAuthorityMismatch as u32),
+    );
+}
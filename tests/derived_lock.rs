@@ -0,0 +1,173 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    client::{derived_lock_filters, derived_lock_nonce, unpack_lock_header},
+    instruction::{create, init_derived, transfer_locks, unlock, CreateFeePayment, LockSeed, Schedule},
+    pda::find_lock_address,
+    time::TimeBasis,
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+use solana_sdk::signature::{Keypair, Signer};
+
+// Create of the derived lock, sending no seeds
+fn create_instruction(
+    env: &TestEnv,
+    locking_account: &Pubkey,
+    authority: &Pubkey,
+    source: &Pubkey,
+    destination: &Pubkey,
+    schedules: Vec<Schedule>,
+) -> Instruction {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    create(
+        &env.program_id,
+        &spl_token::id(),
+        &system_program::id(),
+        &sysvar::rent::id(),
+        &spl_associated_token_account::id(),
+        &env.price_estimator,
+        locking_account,
+        &get_associated_token_address_with_program_id(locking_account, &mint, &spl_token::id()),
+        authority,
+        source,
+        &payer,
+        destination,
+        &env.company_wallet,
+        &mint,
+        schedules,
+        LockSeed::default(),
+        0,
+        false,
+        TimeBasis::UnixTimestamp,
+        Vec::new(),
+        0,
+        CreateFeePayment::Sol,
+        None,
+        false,
+        &[],
+    )
+    .unwrap()
+}
+
+fn init_derived_instruction(env: &TestEnv, creator: &Pubkey, nonce: u64, number_of_schedules: u32) -> Instruction {
+    init_derived(
+        &system_program::id(),
+        &sysvar::rent::id(),
+        &env.program_id,
+        &env.context.payer.pubkey(),
+        creator,
+        &env.mint.pubkey(),
+        nonce,
+        number_of_schedules,
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_derived_lock_lifecycle_without_seeds() {
+    let mut env = setup(true).await;
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 100).await;
+    let beneficiary = Keypair::new();
+    let destination = create_token_account(&mut env.context, &mint, &beneficiary.pubkey()).await;
+
+    let start = now(&mut env.context).await;
+    let (locking_account, bump) = find_lock_address(&env.program_id, &payer, &mint, 7);
+    let instructions = [
+        init_derived_instruction(&env, &payer, 7, 1),
+        create_instruction(
+            &env,
+            &locking_account,
+            &payer,
+            &source,
+            &destination,
+            vec![Schedule { release_time: start + 100, amount: 100 }],
+        ),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+
+    let data = get_account(&mut env.context, &locking_account).await.data;
+    let header = unpack_lock_header(&data).unwrap();
+    assert!(header.is_derived);
+    assert_eq!((header.lock_nonce, header.lock_bump), (7, bump));
+    assert_eq!(header.creator_address, payer);
+    assert_eq!(derived_lock_nonce(&data), Some(7));
+    for (offset, bytes) in derived_lock_filters(&payer, &mint).iter() {
+        assert_eq!(&data[*offset..*offset + bytes.len()], &bytes[..]);
+    }
+
+    // The program signs for the lock from its header
+    let new_destination = create_token_account(&mut env.context, &mint, &beneficiary.pubkey()).await;
+    let transfer = transfer_locks(
+        &env.program_id,
+        &locking_account,
+        &beneficiary.pubkey(),
+        &destination,
+        &new_destination,
+        LockSeed::default(),
+    )
+    .unwrap();
+    process(&mut env.context, &[transfer], &[&beneficiary]).await.unwrap();
+
+    warp_to_timestamp(&mut env.context, start + 100).await;
+    let unlock = unlock(
+        &env.program_id,
+        &spl_token::id(),
+        &locking_account,
+        &get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id()),
+        &new_destination,
+        &mint,
+        LockSeed::default(),
+        0,
+    )
+    .unwrap();
+    process(&mut env.context, &[unlock], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &new_destination).await, 100);
+}
+
+#[tokio::test]
+async fn test_derived_lock_is_created_by_its_creator_only() {
+    let mut env = setup(true).await;
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 100).await;
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+    let start = now(&mut env.context).await;
+
+    // Derived for another creator than the owner of the source token account
+    let creator = Pubkey::new_unique();
+    let (locking_account, _) = find_lock_address(&env.program_id, &creator, &mint, 0);
+    let instruction = init_derived_instruction(&env, &creator, 0, 1);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    let data = get_account(&mut env.context, &locking_account).await.data;
+    assert_eq!(derived_lock_nonce(&data), Some(0));
+
+    let instruction = create_instruction(
+        &env,
+        &locking_account,
+        &payer,
+        &source,
+        &destination,
+        vec![Schedule { release_time: start + 100, amount: 100 }],
+    );
+    assert_instruction_error(process(&mut env.context, &[instruction], &[]).await, InstructionError::InvalidArgument);
+    assert_eq!(token_balance(&mut env.context, &source).await, 100);
+
+    // The locking account of another nonce is not the derived one
+    let (other_locking_account, _) = find_lock_address(&env.program_id, &creator, &mint, 1);
+    let mut instruction = init_derived_instruction(&env, &creator, 0, 1);
+    instruction.accounts[4].pubkey = other_locking_account;
+    assert_instruction_error(process(&mut env.context, &[instruction], &[]).await, InstructionError::InvalidArgument);
+}
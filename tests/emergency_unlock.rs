@@ -0,0 +1,431 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    client::{mint_lock_stats, pending_emergency_unlock, unpack_lock_header},
+    error::LockTokenError,
+    instruction::{
+        cancel_emergency_unlock, execute_emergency_unlock, migrate_lock, schedule_emergency_unlock, transfer_locks,
+        unlock, LockSeed, Schedule,
+    },
+    state::{token_state_address, LockScheduleHeader, EMERGENCY_UNLOCK_DELAY_SECS},
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+struct RescuableLock {
+    seeds: LockSeed,
+    locking_account: Pubkey,
+    locking_token_account: Pubkey,
+    destination: Pubkey,
+    beneficiary: Keypair,
+    rescue_destination: Pubkey,
+    start: u64,
+}
+
+async fn create_rescuable_lock(env: &mut TestEnv, is_immutable: bool) -> RescuableLock {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 300).await;
+    let beneficiary = Keypair::new();
+    let destination = create_token_account(&mut env.context, &mint, &beneficiary.pubkey()).await;
+    let rescue_destination = create_token_account(&mut env.context, &mint, &Pubkey::new_unique()).await;
+
+    let start = now(&mut env.context).await;
+    let schedules = vec![
+        Schedule { release_time: start + 100, amount: 100 },
+        Schedule { release_time: start + 200, amount: 200 },
+    ];
+    let seeds = LockSeed::random(&env.program_id);
+    let instructions = [
+        init_instruction(env, seeds, schedules.len() as u32),
+        create_lock_instruction(env, seeds, &payer, &source, &destination, schedules, is_immutable, &[]),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    RescuableLock {
+        seeds,
+        locking_account,
+        locking_token_account: get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id()),
+        destination,
+        beneficiary,
+        rescue_destination,
+        start,
+    }
+}
+
+fn schedule_instruction(env: &TestEnv, lock: &RescuableLock, rescue_destination: &Pubkey) -> Instruction {
+    schedule_emergency_unlock(
+        &env.program_id,
+        &env.admin.pubkey(),
+        &lock.locking_account,
+        rescue_destination,
+        lock.seeds,
+    )
+    .unwrap()
+}
+
+fn execute_instruction(env: &TestEnv, lock: &RescuableLock, rescue_destination: &Pubkey) -> Instruction {
+    execute_emergency_unlock(
+        &env.program_id,
+        &spl_token::id(),
+        &lock.locking_account,
+        &lock.locking_token_account,
+        rescue_destination,
+        &env.mint.pubkey(),
+        lock.seeds,
+    )
+    .unwrap()
+}
+
+fn cancel_instruction(env: &TestEnv, lock: &RescuableLock) -> Instruction {
+    cancel_emergency_unlock(
+        &env.program_id,
+        &lock.locking_account,
+        &lock.beneficiary.pubkey(),
+        &lock.destination,
+        lock.seeds,
+    )
+    .unwrap()
+}
+
+// Schedules the rescue to the rescue destination of the lock and returns when it can be executed
+async fn schedule(env: &mut TestEnv, lock: &RescuableLock) -> u64 {
+    let instruction = schedule_instruction(env, lock, &lock.rescue_destination);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+    let data = get_account(&mut env.context, &lock.locking_account).await.data;
+    let (rescue_destination, execute_after) = pending_emergency_unlock(&data).unwrap().unwrap();
+    assert_eq!(rescue_destination, lock.rescue_destination);
+    execute_after
+}
+
+async fn read_header(env: &mut TestEnv, lock: &RescuableLock) -> LockScheduleHeader {
+    let data = get_account(&mut env.context, &lock.locking_account).await.data;
+    unpack_lock_header(&data).unwrap()
+}
+
+async fn total_locked_amount(env: &mut TestEnv) -> u64 {
+    let mint = env.mint.pubkey();
+    let token_state = token_state_address(&env.program_id, &mint).unwrap();
+    let data = get_account(&mut env.context, &token_state).await.data;
+    mint_lock_stats(&mint, &data).unwrap().total_locked_amount
+}
+
+fn assert_timelock_error(result: Result<(), TransactionError>) {
+    assert_instruction_error(result, InstructionError::Custom(LockTokenError::EmergencyUnlockTimelock as u32));
+}
+
+#[tokio::test]
+async fn test_emergency_unlock_after_timelock() {
+    let mut env = setup(true).await;
+    let lock = create_rescuable_lock(&mut env, false).await;
+    let scheduled_at = now(&mut env.context).await;
+    let execute_after = schedule(&mut env, &lock).await;
+    assert_eq!(execute_after, scheduled_at + EMERGENCY_UNLOCK_DELAY_SECS);
+
+    let instruction = execute_instruction(&env, &lock, &lock.rescue_destination);
+    assert_timelock_error(process(&mut env.context, &[instruction.clone()], &[]).await);
+
+    warp_to_timestamp(&mut env.context, execute_after - 1).await;
+    assert_timelock_error(process(&mut env.context, &[instruction.clone()], &[]).await);
+
+    warp_to_timestamp(&mut env.context, execute_after).await;
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.rescue_destination).await, 300);
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 0);
+    assert_eq!(total_locked_amount(&mut env).await, 0);
+
+    let header = read_header(&mut env, &lock).await;
+    assert_eq!(header.destination_address, lock.rescue_destination);
+    assert_eq!(header.claimed_amount, 300);
+    assert_eq!(header.unlocked_count, 2);
+    assert!(!header.has_pending_rescue());
+
+    // The lock is drained, so neither a second rescue nor an unlock has anything left to move
+    let instruction = unlock(
+        &env.program_id,
+        &spl_token::id(),
+        &lock.locking_account,
+        &lock.locking_token_account,
+        &lock.rescue_destination,
+        &env.mint.pubkey(),
+        lock.seeds,
+        0,
+    )
+    .unwrap();
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::Custom(LockTokenError::LockFullyUnlocked as u32),
+    );
+    let instruction = schedule_instruction(&env, &lock, &Pubkey::new_unique());
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&env.admin]).await,
+        InstructionError::Custom(LockTokenError::LockFullyUnlocked as u32),
+    );
+}
+
+// What the beneficiary unlocked before the rescue stays with them
+#[tokio::test]
+async fn test_emergency_unlock_after_partial_unlock() {
+    let mut env = setup(true).await;
+    let lock = create_rescuable_lock(&mut env, false).await;
+    let execute_after = schedule(&mut env, &lock).await;
+
+    warp_to_timestamp(&mut env.context, lock.start + 150).await;
+    let instruction = unlock(
+        &env.program_id,
+        &spl_token::id(),
+        &lock.locking_account,
+        &lock.locking_token_account,
+        &lock.destination,
+        &env.mint.pubkey(),
+        lock.seeds,
+        0,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 100);
+
+    warp_to_timestamp(&mut env.context, execute_after).await;
+    let instruction = execute_instruction(&env, &lock, &lock.rescue_destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.rescue_destination).await, 200);
+    assert_eq!(total_locked_amount(&mut env).await, 0);
+}
+
+#[tokio::test]
+async fn test_cancel_emergency_unlock_before_timelock() {
+    let mut env = setup(true).await;
+    let lock = create_rescuable_lock(&mut env, false).await;
+    let execute_after = schedule(&mut env, &lock).await;
+
+    let instruction = cancel_instruction(&env, &lock);
+    process(&mut env.context, &[instruction], &[&lock.beneficiary]).await.unwrap();
+    let data = get_account(&mut env.context, &lock.locking_account).await.data;
+    assert_eq!(pending_emergency_unlock(&data).unwrap(), None);
+
+    warp_to_timestamp(&mut env.context, execute_after).await;
+    let instruction = execute_instruction(&env, &lock, &lock.rescue_destination);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::InvalidArgument,
+    );
+    assert_eq!(token_balance(&mut env.context, &lock.rescue_destination).await, 0);
+
+    // There is nothing left to cancel
+    let instruction = cancel_instruction(&env, &lock);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&lock.beneficiary]).await,
+        InstructionError::InvalidArgument,
+    );
+}
+
+// The timelock only gates the execution, the beneficiary can still cancel once it has elapsed
+#[tokio::test]
+async fn test_cancel_emergency_unlock_after_timelock() {
+    let mut env = setup(true).await;
+    let lock = create_rescuable_lock(&mut env, false).await;
+    let execute_after = schedule(&mut env, &lock).await;
+    warp_to_timestamp(&mut env.context, execute_after + 1).await;
+
+    let instruction = cancel_instruction(&env, &lock);
+    process(&mut env.context, &[instruction], &[&lock.beneficiary]).await.unwrap();
+
+    let instruction = execute_instruction(&env, &lock, &lock.rescue_destination);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::InvalidArgument,
+    );
+    assert_eq!(token_balance(&mut env.context, &lock.rescue_destination).await, 0);
+    assert_eq!(read_header(&mut env, &lock).await.destination_address, lock.destination);
+}
+
+// Whichever of the cancel and the execution lands first wins and the other one fails as a whole
+#[tokio::test]
+async fn test_cancel_and_execute_in_one_transaction() {
+    let mut env = setup(true).await;
+    let lock = create_rescuable_lock(&mut env, false).await;
+    let execute_after = schedule(&mut env, &lock).await;
+    warp_to_timestamp(&mut env.context, execute_after).await;
+
+    let cancel = cancel_instruction(&env, &lock);
+    let execute = execute_instruction(&env, &lock, &lock.rescue_destination);
+
+    // Cancelled first: the execution fails and takes the cancel down with it
+    assert_eq!(
+        process(&mut env.context, &[cancel.clone(), execute.clone()], &[&lock.beneficiary]).await.unwrap_err(),
+        TransactionError::InstructionError(1, InstructionError::InvalidArgument),
+    );
+    let data = get_account(&mut env.context, &lock.locking_account).await.data;
+    assert!(pending_emergency_unlock(&data).unwrap().is_some());
+
+    // Executed first: the lock pays out to the rescue destination, which the old beneficiary does not own
+    assert_eq!(
+        process(&mut env.context, &[execute.clone(), cancel.clone()], &[&lock.beneficiary]).await.unwrap_err(),
+        TransactionError::InstructionError(1, InstructionError::InvalidArgument),
+    );
+    assert_eq!(token_balance(&mut env.context, &lock.rescue_destination).await, 0);
+
+    // Apart, the first one sent wins
+    process(&mut env.context, &[cancel], &[&lock.beneficiary]).await.unwrap();
+    assert_instruction_error(
+        process(&mut env.context, &[execute], &[]).await,
+        InstructionError::InvalidArgument,
+    );
+    assert_eq!(token_balance(&mut env.context, &lock.locking_token_account).await, 300);
+}
+
+#[tokio::test]
+async fn test_reschedule_restarts_timelock() {
+    let mut env = setup(true).await;
+    let lock = create_rescuable_lock(&mut env, false).await;
+    let first_execute_after = schedule(&mut env, &lock).await;
+
+    warp_to_timestamp(&mut env.context, first_execute_after - 10).await;
+    let mint = env.mint.pubkey();
+    let new_rescue_destination = create_token_account(&mut env.context, &mint, &Pubkey::new_unique()).await;
+    let instruction = schedule_instruction(&env, &lock, &new_rescue_destination);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+
+    warp_to_timestamp(&mut env.context, first_execute_after).await;
+    let instruction = execute_instruction(&env, &lock, &lock.rescue_destination);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::InvalidArgument,
+    );
+    let instruction = execute_instruction(&env, &lock, &new_rescue_destination);
+    assert_timelock_error(process(&mut env.context, &[instruction.clone()], &[]).await);
+
+    warp_to_timestamp(&mut env.context, first_execute_after - 10 + EMERGENCY_UNLOCK_DELAY_SECS).await;
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &new_rescue_destination).await, 300);
+}
+
+#[tokio::test]
+async fn test_emergency_unlock_signers() {
+    let mut env = setup(true).await;
+    let lock = create_rescuable_lock(&mut env, false).await;
+
+    let stranger = Keypair::new();
+    let instruction = schedule_emergency_unlock(
+        &env.program_id,
+        &stranger.pubkey(),
+        &lock.locking_account,
+        &lock.rescue_destination,
+        lock.seeds,
+    )
+    .unwrap();
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&stranger]).await,
+        InstructionError::Custom(LockTokenError::NotAdmin as u32),
+    );
+
+    schedule(&mut env, &lock).await;
+    let instruction = cancel_emergency_unlock(
+        &env.program_id,
+        &lock.locking_account,
+        &stranger.pubkey(),
+        &lock.destination,
+        lock.seeds,
+    )
+    .unwrap();
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&stranger]).await,
+        InstructionError::InvalidArgument,
+    );
+    let data = get_account(&mut env.context, &lock.locking_account).await.data;
+    assert!(pending_emergency_unlock(&data).unwrap().is_some());
+}
+
+#[tokio::test]
+async fn test_transfer_withdraws_emergency_unlock() {
+    let mut env = setup(true).await;
+    let lock = create_rescuable_lock(&mut env, false).await;
+    let execute_after = schedule(&mut env, &lock).await;
+
+    let mint = env.mint.pubkey();
+    let new_destination = create_token_account(&mut env.context, &mint, &lock.beneficiary.pubkey()).await;
+    let instruction = transfer_locks(
+        &env.program_id,
+        &lock.locking_account,
+        &lock.beneficiary.pubkey(),
+        &lock.destination,
+        &new_destination,
+        lock.seeds,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[&lock.beneficiary]).await.unwrap();
+    let data = get_account(&mut env.context, &lock.locking_account).await.data;
+    assert_eq!(pending_emergency_unlock(&data).unwrap(), None);
+
+    warp_to_timestamp(&mut env.context, execute_after).await;
+    let instruction = execute_instruction(&env, &lock, &lock.rescue_destination);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::InvalidArgument,
+    );
+}
+
+#[tokio::test]
+async fn test_immutable_lock_can_not_be_rescued() {
+    let mut env = setup(true).await;
+    let lock = create_rescuable_lock(&mut env, true).await;
+
+    let instruction = schedule_instruction(&env, &lock, &lock.rescue_destination);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&env.admin]).await,
+        InstructionError::Custom(LockTokenError::ImmutableLock as u32),
+    );
+}
+
+#[tokio::test]
+async fn test_emergency_unlock_needs_current_layout() {
+    let mut env = setup(true).await;
+    let lock = create_rescuable_lock(&mut env, false).await;
+
+    // Rewrite the lock as it was stored before emergency unlocks were added
+    let account = get_account(&mut env.context, &lock.locking_account).await;
+    let mut v3_data = account.data[..LockScheduleHeader::V3_LEN - 1].to_vec();
+    v3_data.push(3);
+    v3_data.extend_from_slice(&account.data[LockScheduleHeader::LEN..]);
+    let rent = env.context.banks_client.get_rent().await.unwrap();
+    let v3_account = Account {
+        lamports: rent.minimum_balance(v3_data.len()),
+        data: v3_data,
+        owner: env.program_id,
+        executable: false,
+        rent_epoch: 0,
+    };
+    env.context.set_account(&lock.locking_account, &AccountSharedData::from(v3_account));
+
+    let instruction = schedule_instruction(&env, &lock, &lock.rescue_destination);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&env.admin]).await,
+        InstructionError::AccountDataTooSmall,
+    );
+
+    let payer = env.context.payer.pubkey();
+    let instruction = migrate_lock(&env.program_id, &system_program::id(), &payer, &lock.locking_account, lock.seeds).unwrap();
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    let header = read_header(&mut env, &lock).await;
+    assert_eq!(header.total_amount, 300);
+    assert_eq!(header.schedule_count, 2);
+
+    // The same schedule instruction failed above, so it needs a new blockhash to be processed again
+    refresh_blockhash(&mut env.context).await;
+    schedule(&mut env, &lock).await;
+}
@@ -0,0 +1,117 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    error::LockTokenError,
+    instruction::{extend_lock_duration, extend_lock_durations, Schedule},
+    state::{pack_schedules_into_slice, unpack_schedules, LockSchedule, LockScheduleHeader},
+};
+use solana_program::instruction::InstructionError;
+use solana_sdk::{signature::Signer, transaction::TransactionError};
+
+// A lock of three schedules released at start + 100, start + 200 and start + 300
+async fn create_three_schedule_lock(env: &mut TestEnv, start: u64) -> TestLock {
+    let schedules = [100, 200, 300]
+        .iter()
+        .map(|offset| Schedule { release_time: start + offset, amount: 10 })
+        .collect();
+    create_test_lock(env, schedules).await
+}
+
+// Rewrites the schedules of the lock, the way locks created before the schedules were sorted may hold them
+async fn set_schedules(env: &mut TestEnv, lock: &TestLock, schedules: Vec<LockSchedule>) {
+    let mut account = get_account(&mut env.context, &lock.locking_account).await;
+    let header_len = LockScheduleHeader::header_len(account.data.len());
+    pack_schedules_into_slice(schedules, &mut account.data[header_len..]);
+    env.context.set_account(&lock.locking_account, &account.into());
+}
+
+async fn release_times(env: &mut TestEnv, lock: &TestLock) -> Vec<u64> {
+    let data = get_account(&mut env.context, &lock.locking_account).await.data;
+    let header_len = LockScheduleHeader::header_len(data.len());
+    unpack_schedules(&data[header_len..]).unwrap().iter().map(|s| s.release_time).collect()
+}
+
+async fn extend(env: &mut TestEnv, lock: &TestLock, index: u32, release_time: u64) -> Result<(), InstructionError> {
+    refresh_blockhash(&mut env.context).await;
+    let instruction = extend_lock_duration(
+        &env.program_id,
+        &lock.locking_account,
+        &lock.beneficiary.pubkey(),
+        &lock.destination,
+        lock.seeds,
+        index,
+        release_time,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[&lock.beneficiary]).await.map_err(instruction_error)
+}
+
+async fn extend_all(env: &mut TestEnv, lock: &TestLock, extensions: Vec<(u32, u64)>) -> Result<(), InstructionError> {
+    refresh_blockhash(&mut env.context).await;
+    let instruction = extend_lock_durations(
+        &env.program_id,
+        &lock.locking_account,
+        &lock.beneficiary.pubkey(),
+        &lock.destination,
+        lock.seeds,
+        extensions,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[&lock.beneficiary]).await.map_err(instruction_error)
+}
+
+fn instruction_error(error: TransactionError) -> InstructionError {
+    match error {
+        TransactionError::InstructionError(0, error) => error,
+        error => panic!("unexpected error {:?}", error),
+    }
+}
+
+const WOULD_VIOLATE_ORDERING: InstructionError = InstructionError::Custom(LockTokenError::WouldViolateOrdering as u32);
+
+#[tokio::test]
+async fn test_extend_keeps_sorted_schedules_sorted() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let lock = create_three_schedule_lock(&mut env, start).await;
+
+    // The middle schedule can not move past its successor, by one extension or several
+    assert_eq!(extend(&mut env, &lock, 1, start + 301).await, Err(WOULD_VIOLATE_ORDERING));
+    assert_eq!(extend_all(&mut env, &lock, vec![(1, start + 301)]).await, Err(WOULD_VIOLATE_ORDERING));
+    assert_eq!(
+        extend_all(&mut env, &lock, vec![(0, start + 250), (1, start + 240)]).await,
+        Err(WOULD_VIOLATE_ORDERING)
+    );
+    // nor before its predecessor, which would shorten it
+    assert_eq!(extend(&mut env, &lock, 1, start + 50).await, Err(InstructionError::InvalidArgument));
+    assert_eq!(release_times(&mut env, &lock).await, vec![start + 100, start + 200, start + 300]);
+
+    // Reaching the release time of the successor keeps the schedules sorted
+    extend(&mut env, &lock, 1, start + 300).await.unwrap();
+    // and moving both past the last schedule together does too
+    extend_all(&mut env, &lock, vec![(2, start + 400), (1, start + 350)]).await.unwrap();
+    assert_eq!(release_times(&mut env, &lock).await, vec![start + 100, start + 350, start + 400]);
+}
+
+#[tokio::test]
+async fn test_extend_leaves_legacy_unsorted_schedules_alone() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let lock = create_three_schedule_lock(&mut env, start).await;
+    let schedules = [300, 100, 200]
+        .iter()
+        .map(|offset| LockSchedule { release_time: start + offset, amount: 10 })
+        .collect();
+    set_schedules(&mut env, &lock, schedules).await;
+
+    // The middle schedule moves past its successor, as the schedules were never sorted
+    extend(&mut env, &lock, 1, start + 250).await.unwrap();
+    extend_all(&mut env, &lock, vec![(1, start + 260), (2, start + 400)]).await.unwrap();
+    assert_eq!(release_times(&mut env, &lock).await, vec![start + 300, start + 260, start + 400]);
+
+    // Shortening is rejected whatever the order
+    assert_eq!(extend(&mut env, &lock, 0, start + 50).await, Err(InstructionError::InvalidArgument));
+}
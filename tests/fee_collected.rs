@@ -0,0 +1,116 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    events::{decode, FeeCollected, LockCreated},
+    instruction::{LockSeed, Schedule},
+    state::{find_global_state_address, token_state_address, TokenState},
+};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{
+    account::AccountSharedData,
+    signature::Signer,
+    transaction::Transaction,
+};
+
+// 10 USD at 150 USD per SOL
+const FEES_IN_USD: u64 = 10_000_000;
+const SOL_PRICE: i64 = 15_000_000_000;
+const SOL_PRICE_EXPO: i32 = -8;
+
+// Charges FEES_IN_USD for locking the mint of the test environment, unless it is free
+fn charge_fees(env: &mut TestEnv, is_free: bool) {
+    let mut state = global_state(&env.admin.pubkey(), &env.company_wallet, &env.price_estimator);
+    state.fees_in_usd = FEES_IN_USD;
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    let account = program_account(&env.program_id, &state);
+    env.context.set_account(&program_state_account_key, &AccountSharedData::from(account));
+
+    let mint = env.mint.pubkey();
+    let token_state = TokenState {
+        mint_address: mint,
+        is_free,
+        fee_override_usd: 0,
+        has_override: false,
+        active_locks: 0,
+        total_locked_amount: 0,
+        last_snapshot_amount: 0,
+        last_snapshot_ts: 0,
+        is_initialized: true,
+    };
+    let account = program_account(&env.program_id, &token_state);
+    env.context.set_account(&token_state_address(&env.program_id, &mint).unwrap(), &AccountSharedData::from(account));
+}
+
+// Locks 500 tokens of the context payer and returns the logs of the transaction
+async fn create_lock(env: &mut TestEnv) -> Vec<String> {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 500).await;
+    let destination = create_token_account(&mut env.context, &mint, &Pubkey::new_unique()).await;
+
+    let start = now(&mut env.context).await;
+    let seeds = LockSeed::random(&env.program_id);
+    let schedules = vec![Schedule { release_time: start + 100, amount: 500 }];
+    let instructions = [
+        init_instruction(env, seeds, 1),
+        create_lock_instruction(env, seeds, &payer, &source, &destination, schedules, false, &[]),
+    ];
+    let transaction = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&payer),
+        &[&env.context.payer],
+        env.context.last_blockhash,
+    );
+    let result = process_with_metadata(&mut env.context, transaction).await;
+    result.result.unwrap();
+    result.metadata.unwrap().log_messages
+}
+
+// The event carries what finance needs to reconcile the fee against the oracle rate, apart from LockCreated
+#[tokio::test]
+async fn test_create_emits_fee_collected() {
+    let mut env = setup(true).await;
+    charge_fees(&mut env, false);
+    set_sol_price(&mut env, SOL_PRICE, SOL_PRICE_EXPO).await;
+    let company_wallet = env.company_wallet;
+    let company_lamports = env.context.banks_client.get_balance(company_wallet).await.unwrap();
+
+    let logs = create_lock(&mut env).await;
+    let event = logs.iter().find_map(|log| decode::<FeeCollected>(log)).unwrap();
+    assert_eq!(
+        event,
+        FeeCollected {
+            // 10 USD of 6 decimals at 150 USD per SOL
+            lamports: 66_666_666,
+            fees_in_usd: FEES_IN_USD,
+            oracle_price: SOL_PRICE,
+            oracle_expo: SOL_PRICE_EXPO,
+            payer: env.context.payer.pubkey(),
+            company_wallet,
+        }
+    );
+    assert_eq!(
+        env.context.banks_client.get_balance(company_wallet).await.unwrap(),
+        company_lamports + event.lamports
+    );
+    let lock_created = logs.iter().find_map(|log| decode::<LockCreated>(log)).unwrap();
+    assert_eq!(lock_created.total_amount, 500);
+}
+
+// Nothing is collected for a free mint, so there is no event either
+#[tokio::test]
+async fn test_create_of_free_mint_emits_no_fee_collected() {
+    let mut env = setup(true).await;
+    charge_fees(&mut env, true);
+    let company_wallet = env.company_wallet;
+    let company_lamports = env.context.banks_client.get_balance(company_wallet).await.unwrap();
+
+    let logs = create_lock(&mut env).await;
+    assert!(logs.iter().all(|log| decode::<FeeCollected>(log).is_none()));
+    assert!(logs.iter().any(|log| decode::<LockCreated>(log).is_some()));
+    assert_eq!(env.context.banks_client.get_balance(company_wallet).await.unwrap(), company_lamports);
+}
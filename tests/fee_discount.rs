@@ -0,0 +1,261 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    error::LockTokenError,
+    instruction::{create, set_fee_bps_in_token, set_fee_discount_tiers, set_owner_token_mint, CreateFeePayment, LockSeed, Schedule},
+    state::{
+        find_global_state_address, token_state_address, FeeDiscountTier, LockGlobalState, TokenState, MAX_BPS,
+        MAX_FEE_DISCOUNT_TIERS,
+    },
+    time::TimeBasis,
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+use solana_sdk::{
+    account::AccountSharedData,
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+// 5% of every schedule, so a lock of 1000 tokens pays 50 before the discount
+const FEE_BPS_IN_TOKEN: u16 = 500;
+
+// 20% off from 100 owner tokens and 50% off from 1000
+const FEE_DISCOUNT_TIERS: [FeeDiscountTier; MAX_FEE_DISCOUNT_TIERS] = [
+    FeeDiscountTier { min_balance: 100, discount_bps: 2_000 },
+    FeeDiscountTier { min_balance: 1_000, discount_bps: 5_000 },
+    FeeDiscountTier { min_balance: 0, discount_bps: 0 },
+];
+
+struct OwnerToken {
+    mint: Pubkey,
+    company_token_account: Pubkey,
+}
+
+// Charges the token fee for locking the mint of the test environment and sets up a new owner token
+async fn setup_owner_token(env: &mut TestEnv) -> OwnerToken {
+    let mint = env.mint.pubkey();
+    let token_state = TokenState {
+        mint_address: mint,
+        is_free: false,
+        fee_override_usd: 0,
+        has_override: false,
+        active_locks: 0,
+        total_locked_amount: 0,
+        last_snapshot_amount: 0,
+        last_snapshot_ts: 0,
+        is_initialized: true,
+    };
+    let account = program_account(&env.program_id, &token_state);
+    env.context.set_account(&token_state_address(&env.program_id, &mint).unwrap(), &AccountSharedData::from(account));
+
+    let instruction = set_fee_bps_in_token(
+        &env.program_id,
+        &system_program::id(),
+        &env.admin.pubkey(),
+        &env.context.payer.pubkey(),
+        FEE_BPS_IN_TOKEN,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+
+    let owner_token_mint = Keypair::new();
+    create_mint(&mut env.context, &owner_token_mint).await;
+    set_tiers(env, FEE_DISCOUNT_TIERS).await.unwrap();
+    let admin = env.admin.pubkey();
+    let admin_token_account = holding(env, &owner_token_mint.pubkey(), &admin, 1).await;
+    let instruction = set_owner_token_mint(
+        &env.program_id,
+        &system_program::id(),
+        &spl_token::id(),
+        &admin,
+        &env.context.payer.pubkey(),
+        &owner_token_mint.pubkey(),
+        &admin_token_account,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+
+    let company_wallet = env.company_wallet;
+    let company_token_account = create_token_account(&mut env.context, &mint, &company_wallet).await;
+    OwnerToken {
+        mint: owner_token_mint.pubkey(),
+        company_token_account,
+    }
+}
+
+async fn set_tiers(
+    env: &mut TestEnv,
+    fee_discount_tiers: [FeeDiscountTier; MAX_FEE_DISCOUNT_TIERS],
+) -> Result<(), TransactionError> {
+    let instruction = set_fee_discount_tiers(
+        &env.program_id,
+        &system_program::id(),
+        &env.admin.pubkey(),
+        &env.context.payer.pubkey(),
+        fee_discount_tiers,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[&env.admin]).await
+}
+
+// A token account of the mint owned by the owner holding the balance
+async fn holding(env: &mut TestEnv, mint: &Pubkey, owner: &Pubkey, balance: u64) -> Pubkey {
+    let account = create_token_account(&mut env.context, mint, owner).await;
+    if balance > 0 {
+        mint_to(&mut env.context, mint, &account, balance).await;
+    }
+    account
+}
+
+// Inits a lock of 1000 tokens and returns the Create paying its fee in the locked token with the discount account
+async fn discounted_create_instruction(
+    env: &mut TestEnv,
+    owner_token: &OwnerToken,
+    fee_discount_token_account: Option<Pubkey>,
+) -> Instruction {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 1_000).await;
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+
+    let start = now(&mut env.context).await;
+    let schedules = vec![Schedule { release_time: start + 100, amount: 1_000 }];
+    let seeds = LockSeed::random(&env.program_id);
+    let instruction = init_instruction(env, seeds, schedules.len() as u32);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    create(
+        &env.program_id,
+        &spl_token::id(),
+        &system_program::id(),
+        &sysvar::rent::id(),
+        &spl_associated_token_account::id(),
+        &env.price_estimator,
+        &locking_account,
+        &get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id()),
+        &payer,
+        &source,
+        &payer,
+        &destination,
+        &env.company_wallet,
+        &mint,
+        schedules,
+        seeds,
+        0,
+        false,
+        TimeBasis::UnixTimestamp,
+        Vec::new(),
+        0,
+        CreateFeePayment::Token {
+            company_token_account: owner_token.company_token_account,
+        },
+        fee_discount_token_account,
+        false,
+        &[],
+    )
+    .unwrap()
+}
+
+// Creates a lock with the discount of the balance and returns the fee the company received
+async fn fee_paid_with_balance(env: &mut TestEnv, owner_token: &OwnerToken, balance: u64) -> u64 {
+    let payer = env.context.payer.pubkey();
+    let fee_discount_token_account = holding(env, &owner_token.mint, &payer, balance).await;
+    let before = token_balance(&mut env.context, &owner_token.company_token_account).await;
+    let instruction = discounted_create_instruction(env, owner_token, Some(fee_discount_token_account)).await;
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    token_balance(&mut env.context, &owner_token.company_token_account).await - before
+}
+
+#[tokio::test]
+async fn test_set_fee_discount_tiers() {
+    let mut env = setup(true).await;
+
+    let mut tiers = FEE_DISCOUNT_TIERS;
+    tiers[2].discount_bps = MAX_BPS + 1;
+    assert_instruction_error(
+        set_tiers(&mut env, tiers).await,
+        InstructionError::Custom(LockTokenError::InvalidBasisPoints as u32),
+    );
+
+    set_tiers(&mut env, FEE_DISCOUNT_TIERS).await.unwrap();
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    let account = get_account(&mut env.context, &program_state_account_key).await;
+    let state = LockGlobalState::unpack_from_slice(&account.data).unwrap();
+    assert_eq!(state.owner_token_mint, Pubkey::default());
+    assert_eq!(state.fee_discount_tiers, FEE_DISCOUNT_TIERS);
+
+    // Only the admin sets them
+    let instruction = set_fee_discount_tiers(
+        &env.program_id,
+        &system_program::id(),
+        &env.context.payer.pubkey(),
+        &env.context.payer.pubkey(),
+        [FeeDiscountTier::default(); MAX_FEE_DISCOUNT_TIERS],
+    )
+    .unwrap();
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::Custom(LockTokenError::NotAdmin as u32),
+    );
+}
+
+#[tokio::test]
+async fn test_create_with_fee_discount_tiers() {
+    let mut env = setup(true).await;
+    let owner_token = setup_owner_token(&mut env).await;
+
+    // Below the first tier the full fee is paid
+    assert_eq!(fee_paid_with_balance(&mut env, &owner_token, 0).await, 50);
+    assert_eq!(fee_paid_with_balance(&mut env, &owner_token, 99).await, 50);
+    // 20% off 50
+    assert_eq!(fee_paid_with_balance(&mut env, &owner_token, 100).await, 40);
+    assert_eq!(fee_paid_with_balance(&mut env, &owner_token, 999).await, 40);
+    // 50% off 50
+    assert_eq!(fee_paid_with_balance(&mut env, &owner_token, 1_000).await, 25);
+}
+
+#[tokio::test]
+async fn test_create_without_fee_discount_account() {
+    let mut env = setup(true).await;
+    let owner_token = setup_owner_token(&mut env).await;
+
+    let instruction = discounted_create_instruction(&mut env, &owner_token, None).await;
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &owner_token.company_token_account).await, 50);
+}
+
+#[tokio::test]
+async fn test_create_with_invalid_fee_discount_account() {
+    let mut env = setup(true).await;
+    let owner_token = setup_owner_token(&mut env).await;
+    let payer = env.context.payer.pubkey();
+
+    // Another mint than the owner token
+    let mint = env.mint.pubkey();
+    let other_mint_account = holding(&mut env, &mint, &payer, 1_000).await;
+    let instruction = discounted_create_instruction(&mut env, &owner_token, Some(other_mint_account)).await;
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::Custom(LockTokenError::FeeMintMismatch as u32),
+    );
+
+    // Owner tokens held by someone else than the fee payer
+    let other_owner_account = holding(&mut env, &owner_token.mint, &Pubkey::new_unique(), 1_000).await;
+    let instruction = discounted_create_instruction(&mut env, &owner_token, Some(other_owner_account)).await;
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::InvalidArgument,
+    );
+    assert_eq!(token_balance(&mut env.context, &owner_token.company_token_account).await, 0);
+}
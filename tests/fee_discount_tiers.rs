@@ -0,0 +1,81 @@
+use lock_token::{
+    error::LockTokenError,
+    state::{apply_fee_discount, FeeDiscountTier, LockGlobalState, MAX_BPS, MAX_CANDIDATE_WALLETS, MAX_FEE_DISCOUNT_TIERS},
+};
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+fn state_with_tiers(fee_discount_tiers: [FeeDiscountTier; MAX_FEE_DISCOUNT_TIERS]) -> LockGlobalState {
+    LockGlobalState {
+        price_estimator: Pubkey::new_unique(),
+        usd_token_address: Pubkey::new_unique(),
+        fees_in_usd: 0,
+        company_wallet: Pubkey::new_unique(),
+        max_price_age_slots: 0,
+        candidate_wallets: [Pubkey::default(); MAX_CANDIDATE_WALLETS],
+        admin: Pubkey::new_unique(),
+        pending_admin: Pubkey::default(),
+        min_lock_duration_secs: 0,
+        min_lock_amount: 0,
+        fee_bps_in_token: 0,
+        owner_token_mint: Pubkey::new_unique(),
+        fee_discount_tiers,
+        max_lock_duration_secs: 0,
+        paused_at: 0,
+        paused_by: Pubkey::default(),
+        pending_candidate_wallets: [Pubkey::default(); MAX_CANDIDATE_WALLETS],
+        candidate_wallets_execute_after: 0,
+        pause_create: false,
+        pause_unlock: false,
+        pause_transfer: false,
+        is_initialized: true,
+    }
+}
+
+fn tier(min_balance: u64, discount_bps: u16) -> FeeDiscountTier {
+    FeeDiscountTier { min_balance, discount_bps }
+}
+
+#[test]
+fn test_fee_discount_tier_boundaries() {
+    let state = state_with_tiers([tier(100, 500), tier(1_000, 1_000), tier(10_000, 2_500)]);
+    assert_eq!(state.fee_discount_bps(0), 0);
+    assert_eq!(state.fee_discount_bps(99), 0);
+    assert_eq!(state.fee_discount_bps(100), 500);
+    assert_eq!(state.fee_discount_bps(999), 500);
+    assert_eq!(state.fee_discount_bps(1_000), 1_000);
+    assert_eq!(state.fee_discount_bps(9_999), 1_000);
+    assert_eq!(state.fee_discount_bps(10_000), 2_500);
+    assert_eq!(state.fee_discount_bps(u64::MAX), 2_500);
+}
+
+#[test]
+fn test_fee_discount_picks_best_tier() {
+    // Tiers are not sorted, and a lower tier may discount more than a higher one
+    let state = state_with_tiers([tier(1_000, 300), tier(10, 700), tier(500, 0)]);
+    assert_eq!(state.fee_discount_bps(9), 0);
+    assert_eq!(state.fee_discount_bps(10), 700);
+    assert_eq!(state.fee_discount_bps(1_000), 700);
+}
+
+#[test]
+fn test_unused_tiers_give_no_discount() {
+    let state = state_with_tiers([FeeDiscountTier::default(); MAX_FEE_DISCOUNT_TIERS]);
+    assert_eq!(state.fee_discount_bps(0), 0);
+    assert_eq!(state.fee_discount_bps(u64::MAX), 0);
+}
+
+#[test]
+fn test_apply_fee_discount() {
+    assert_eq!(apply_fee_discount(10_000_000, 0).unwrap(), 10_000_000);
+    assert_eq!(apply_fee_discount(10_000_000, 500).unwrap(), 9_500_000);
+    // The discount of 249.75 is rounded down
+    assert_eq!(apply_fee_discount(999, 2_500).unwrap(), 750);
+    assert_eq!(apply_fee_discount(1, 9_999).unwrap(), 1);
+    assert_eq!(apply_fee_discount(u64::MAX, MAX_BPS).unwrap(), 0);
+    // The product of the fee and the discount does not fit in u64
+    assert_eq!(apply_fee_discount(u64::MAX, MAX_BPS - 1).unwrap(), 1_844_674_407_370_956);
+    assert_eq!(
+        apply_fee_discount(1, MAX_BPS + 1),
+        Err(ProgramError::from(LockTokenError::InvalidBasisPoints))
+    );
+}
@@ -0,0 +1,253 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    error::LockTokenError,
+    instruction::{create, set_fee_exempt_wallet, CreateFeePayment, LockSeed, Schedule},
+    state::{find_global_state_address, find_wallet_state_address, token_state_address, TokenState, WalletState},
+    time::TimeBasis,
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction, system_program,
+    sysvar::{self, clock::Clock},
+};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    signature::{Keypair, Signer},
+};
+
+// 10 USD at 100 USD per SOL
+const FEES_IN_USD: u64 = 10_000_000;
+const FEE_LAMPORTS: u64 = 100_000_000;
+
+// A Pyth price account quoting SOL at 100 USD, published at the current slot
+async fn set_sol_price(env: &mut TestEnv) {
+    let clock: Clock = env.context.banks_client.get_sysvar().await.unwrap();
+    let mut data = vec![0; 240];
+    data[0..4].copy_from_slice(&0xa1b2c3d4u32.to_le_bytes());
+    data[4..8].copy_from_slice(&2u32.to_le_bytes());
+    data[8..12].copy_from_slice(&3u32.to_le_bytes());
+    data[20..24].copy_from_slice(&(-2i32).to_le_bytes());
+    data[208..216].copy_from_slice(&10_000i64.to_le_bytes());
+    data[224..228].copy_from_slice(&1u32.to_le_bytes());
+    data[232..240].copy_from_slice(&clock.slot.to_le_bytes());
+    let account = Account {
+        lamports: 1_000_000_000,
+        data,
+        owner: Pubkey::new_unique(),
+        executable: false,
+        rent_epoch: 0,
+    };
+    env.context.set_account(&env.price_estimator, &AccountSharedData::from(account));
+}
+
+// Charges FEES_IN_USD for locking the mint of the test environment
+fn charge_fees(env: &mut TestEnv) {
+    let mut state = global_state(&env.admin.pubkey(), &env.company_wallet, &env.price_estimator);
+    state.fees_in_usd = FEES_IN_USD;
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    let account = program_account(&env.program_id, &state);
+    env.context.set_account(&program_state_account_key, &AccountSharedData::from(account));
+
+    let mint = env.mint.pubkey();
+    let token_state = TokenState {
+        mint_address: mint,
+        is_free: false,
+        fee_override_usd: 0,
+        has_override: false,
+        active_locks: 0,
+        total_locked_amount: 0,
+        last_snapshot_amount: 0,
+        last_snapshot_ts: 0,
+        is_initialized: true,
+    };
+    let account = program_account(&env.program_id, &token_state);
+    env.context.set_account(&token_state_address(&env.program_id, &mint).unwrap(), &AccountSharedData::from(account));
+}
+
+fn set_fee_exempt_wallet_instruction(env: &TestEnv, wallet: &Pubkey, is_exempt: bool) -> Instruction {
+    set_fee_exempt_wallet(
+        &env.program_id,
+        &system_program::id(),
+        &env.admin.pubkey(),
+        &env.context.payer.pubkey(),
+        wallet,
+        is_exempt,
+    )
+    .unwrap()
+}
+
+async fn wallet_state(env: &mut TestEnv, wallet: &Pubkey) -> WalletState {
+    let (wallet_state_key, _) = find_wallet_state_address(&env.program_id, wallet);
+    WalletState::unpack(&get_account(&mut env.context, &wallet_state_key).await.data).unwrap()
+}
+
+// A funded partner wallet which owns a token account holding 500 of the mint
+async fn partner(env: &mut TestEnv) -> (Keypair, Pubkey) {
+    let wallet = Keypair::new();
+    let payer = env.context.payer.pubkey();
+    let instruction = system_instruction::transfer(&payer, &wallet.pubkey(), 1_000_000_000);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    let mint = env.mint.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &wallet.pubkey()).await;
+    mint_to(&mut env.context, &mint, &source, 500).await;
+    (wallet, source)
+}
+
+// Initializes a lock and returns the Create of 500 tokens paid by the wallet, which also owns the source
+async fn create_paid_by(env: &mut TestEnv, wallet: &Keypair, source: &Pubkey, with_wallet_state: bool) -> Instruction {
+    let mint = env.mint.pubkey();
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+    let start = now(&mut env.context).await;
+    let schedules = vec![Schedule { release_time: start + 100, amount: 500 }];
+    let seeds = LockSeed::random(&env.program_id);
+    let instruction = init_instruction(env, seeds, 1);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    let locking_token_account = get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id());
+    create(
+        &env.program_id,
+        &spl_token::id(),
+        &system_program::id(),
+        &sysvar::rent::id(),
+        &spl_associated_token_account::id(),
+        &env.price_estimator,
+        &locking_account,
+        &locking_token_account,
+        &wallet.pubkey(),
+        source,
+        &wallet.pubkey(),
+        &destination,
+        &env.company_wallet,
+        &mint,
+        schedules,
+        seeds,
+        0,
+        false,
+        TimeBasis::UnixTimestamp,
+        Vec::new(),
+        0,
+        CreateFeePayment::Sol,
+        None,
+        with_wallet_state,
+        &[],
+    )
+    .unwrap()
+}
+
+async fn company_wallet_lamports(env: &mut TestEnv) -> u64 {
+    let company_wallet = env.company_wallet;
+    env.context
+        .banks_client
+        .get_account(company_wallet)
+        .await
+        .unwrap()
+        .map_or(0, |account| account.lamports)
+}
+
+// The first call creates the wallet state, later calls only flip the exemption
+#[tokio::test]
+async fn test_set_fee_exempt_wallet() {
+    let mut env = setup(true).await;
+    let wallet = Pubkey::new_unique();
+
+    let instruction = set_fee_exempt_wallet_instruction(&env, &wallet, true);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+    assert_eq!(
+        wallet_state(&mut env, &wallet).await,
+        WalletState { wallet_address: wallet, is_fee_exempt: true, is_initialized: true }
+    );
+
+    let instruction = set_fee_exempt_wallet_instruction(&env, &wallet, false);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+    assert_eq!(
+        wallet_state(&mut env, &wallet).await,
+        WalletState { wallet_address: wallet, is_fee_exempt: false, is_initialized: true }
+    );
+}
+
+#[tokio::test]
+async fn test_set_fee_exempt_wallet_requires_admin() {
+    let mut env = setup(true).await;
+    let impostor = Keypair::new();
+    let mut instruction = set_fee_exempt_wallet_instruction(&env, &Pubkey::new_unique(), true);
+    instruction.accounts[0].pubkey = impostor.pubkey();
+
+    let result = process(&mut env.context, &[instruction], &[&impostor]).await;
+    assert_instruction_error(result, InstructionError::Custom(LockTokenError::NotAdmin as u32));
+}
+
+#[tokio::test]
+async fn test_exempt_wallet_pays_no_fee() {
+    let mut env = setup(true).await;
+    charge_fees(&mut env);
+    set_sol_price(&mut env).await;
+    let (wallet, source) = partner(&mut env).await;
+
+    let instruction = set_fee_exempt_wallet_instruction(&env, &wallet.pubkey(), true);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+
+    let instruction = create_paid_by(&mut env, &wallet, &source, true).await;
+    process(&mut env.context, &[instruction], &[&wallet]).await.unwrap();
+
+    assert_eq!(token_balance(&mut env.context, &source).await, 0);
+    assert_eq!(company_wallet_lamports(&mut env).await, 0);
+}
+
+// Without the wallet state, with one never created and with one no longer exempt, the fee is charged
+#[tokio::test]
+async fn test_non_exempt_wallet_pays_fee() {
+    let mut env = setup(true).await;
+    charge_fees(&mut env);
+    set_sol_price(&mut env).await;
+    let (wallet, source) = partner(&mut env).await;
+
+    let instruction = create_paid_by(&mut env, &wallet, &source, true).await;
+    process(&mut env.context, &[instruction], &[&wallet]).await.unwrap();
+    assert_eq!(company_wallet_lamports(&mut env).await, FEE_LAMPORTS);
+
+    let instruction = set_fee_exempt_wallet_instruction(&env, &wallet.pubkey(), true);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+    // Minting the same amount to the source again needs a new blockhash to not repeat the first mint
+    let mint = env.mint.pubkey();
+    refresh_blockhash(&mut env.context).await;
+    mint_to(&mut env.context, &mint, &source, 500).await;
+    let instruction = create_paid_by(&mut env, &wallet, &source, false).await;
+    process(&mut env.context, &[instruction], &[&wallet]).await.unwrap();
+    assert_eq!(company_wallet_lamports(&mut env).await, 2 * FEE_LAMPORTS);
+
+    let instruction = set_fee_exempt_wallet_instruction(&env, &wallet.pubkey(), false);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+    refresh_blockhash(&mut env.context).await;
+    mint_to(&mut env.context, &mint, &source, 500).await;
+    let instruction = create_paid_by(&mut env, &wallet, &source, true).await;
+    process(&mut env.context, &[instruction], &[&wallet]).await.unwrap();
+    assert_eq!(company_wallet_lamports(&mut env).await, 3 * FEE_LAMPORTS);
+}
+
+// The wallet state of an exempt partner does not exempt another fee payer
+#[tokio::test]
+async fn test_create_rejects_wallet_state_of_another_wallet() {
+    let mut env = setup(true).await;
+    charge_fees(&mut env);
+    set_sol_price(&mut env).await;
+    let (wallet, source) = partner(&mut env).await;
+
+    let exempt_partner = Pubkey::new_unique();
+    let instruction = set_fee_exempt_wallet_instruction(&env, &exempt_partner, true);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+
+    let mut instruction = create_paid_by(&mut env, &wallet, &source, true).await;
+    let (wallet_state_key, _) = find_wallet_state_address(&env.program_id, &exempt_partner);
+    instruction.accounts.last_mut().unwrap().pubkey = wallet_state_key;
+    let result = process(&mut env.context, &[instruction], &[&wallet]).await;
+    assert_instruction_error(result, InstructionError::InvalidArgument);
+}
@@ -0,0 +1,153 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    error::LockTokenError,
+    instruction::{LockSeed, LockTokenInstruction, Schedule},
+    state::{find_global_state_address, token_state_address, TokenState},
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use solana_sdk::{
+    account::AccountSharedData,
+    signature::{Keypair, Signer},
+};
+
+// 10 USD at 100 USD per SOL
+const FEES_IN_USD: u64 = 10_000_000;
+const FEE_LAMPORTS: u64 = 100_000_000;
+
+// Charges FEES_IN_USD for locking the mint of the test environment, which has a token state of the given length
+fn charge_fees(env: &mut TestEnv, token_state_len: usize) {
+    let mut state = global_state(&env.admin.pubkey(), &env.company_wallet, &env.price_estimator);
+    state.fees_in_usd = FEES_IN_USD;
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    let account = program_account(&env.program_id, &state);
+    env.context.set_account(&program_state_account_key, &AccountSharedData::from(account));
+
+    let mint = env.mint.pubkey();
+    let token_state = TokenState {
+        mint_address: mint,
+        is_free: false,
+        fee_override_usd: 0,
+        has_override: false,
+        active_locks: 0,
+        total_locked_amount: 0,
+        last_snapshot_amount: 0,
+        last_snapshot_ts: 0,
+        is_initialized: true,
+    };
+    let mut data = vec![0; token_state_len];
+    token_state.pack_into_slice(&mut data);
+    let account = program_owned_account(&env.program_id, 1_000_000_000, data);
+    env.context.set_account(&token_state_address(&env.program_id, &mint).unwrap(), &account);
+}
+
+// There is no builder for SetTokenFee, which takes the token state account after the admin accounts
+fn set_token_fee_instruction(env: &TestEnv, admin: &Pubkey, fee_in_usd: Option<u64>) -> Instruction {
+    let mint = env.mint.pubkey();
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    Instruction {
+        program_id: env.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(program_state_account_key, false),
+            AccountMeta::new(token_state_address(&env.program_id, &mint).unwrap(), false),
+        ],
+        data: LockTokenInstruction::SetTokenFee { mint_address: mint, fee_in_usd }.pack(),
+    }
+}
+
+async fn load_token_state(env: &mut TestEnv) -> TokenState {
+    let token_state_account_key = token_state_address(&env.program_id, &env.mint.pubkey()).unwrap();
+    TokenState::unpack_from_slice(&get_account(&mut env.context, &token_state_account_key).await.data).unwrap()
+}
+
+// Locks 100 tokens of the context payer and returns the lamports the company wallet received
+async fn create_lock(env: &mut TestEnv) -> u64 {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 100).await;
+    let destination = create_token_account(&mut env.context, &mint, &Pubkey::new_unique()).await;
+    let company_wallet = env.company_wallet;
+    let company_lamports = env.context.banks_client.get_balance(company_wallet).await.unwrap();
+
+    let start = now(&mut env.context).await;
+    let seeds = LockSeed::random(&env.program_id);
+    let schedules = vec![Schedule { release_time: start + 100, amount: 100 }];
+    let instructions = [
+        init_instruction(env, seeds, 1),
+        create_lock_instruction(env, seeds, &payer, &source, &destination, schedules, false, &[]),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+    env.context.banks_client.get_balance(company_wallet).await.unwrap() - company_lamports
+}
+
+// The override of the mint is charged instead of the global fee, until it is removed again
+#[tokio::test]
+async fn test_override_is_preferred_over_the_global_fee() {
+    let mut env = setup(true).await;
+    charge_fees(&mut env, TokenState::LEN);
+    set_sol_price(&mut env, 10_000, -2).await;
+    assert_eq!(create_lock(&mut env).await, FEE_LAMPORTS);
+
+    let admin = env.admin.pubkey();
+    let instruction = set_token_fee_instruction(&env, &admin, Some(FEES_IN_USD / 4));
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+    let token_state = load_token_state(&mut env).await;
+    assert!(token_state.has_override);
+    assert_eq!(token_state.fee_override_usd, FEES_IN_USD / 4);
+    assert_eq!(create_lock(&mut env).await, FEE_LAMPORTS / 4);
+
+    // An override of zero makes the mint free of charge
+    let instruction = set_token_fee_instruction(&env, &admin, Some(0));
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+    assert_eq!(create_lock(&mut env).await, 0);
+
+    let instruction = set_token_fee_instruction(&env, &admin, None);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+    assert!(!load_token_state(&mut env).await.has_override);
+    assert_eq!(create_lock(&mut env).await, FEE_LAMPORTS);
+}
+
+#[tokio::test]
+async fn test_only_the_admin_sets_an_override() {
+    let mut env = setup(true).await;
+    charge_fees(&mut env, TokenState::LEN);
+    let other = Keypair::new();
+    let instruction = set_token_fee_instruction(&env, &other.pubkey(), Some(0));
+    let not_admin = InstructionError::Custom(LockTokenError::NotAdmin as u32);
+    assert_instruction_error(process(&mut env.context, &[instruction], &[&other]).await, not_admin);
+    assert!(!load_token_state(&mut env).await.has_override);
+}
+
+// A token state of the layout before the override has no room for one, and is charged the global fee by Create,
+// which grows it to the current layout
+#[tokio::test]
+async fn test_legacy_token_state_is_charged_the_global_fee() {
+    let mut env = setup(true).await;
+    charge_fees(&mut env, TokenState::LEGACY_LEN);
+    set_sol_price(&mut env, 10_000, -2).await;
+
+    let admin = env.admin.pubkey();
+    let instruction = set_token_fee_instruction(&env, &admin, Some(0));
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&env.admin]).await,
+        InstructionError::AccountDataTooSmall,
+    );
+
+    assert_eq!(create_lock(&mut env).await, FEE_LAMPORTS);
+    let token_state_account_key = token_state_address(&env.program_id, &env.mint.pubkey()).unwrap();
+    assert_eq!(get_account(&mut env.context, &token_state_account_key).await.data.len(), TokenState::LEN);
+
+    refresh_blockhash(&mut env.context).await;
+    let instruction = set_token_fee_instruction(&env, &admin, Some(0));
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+    assert_eq!(create_lock(&mut env).await, 0);
+}
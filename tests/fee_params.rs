@@ -0,0 +1,87 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    instruction::set_fee_params,
+    state::{
+        find_global_state_address, LockGlobalState, FEE_PARAMS_ALL, FEE_PARAM_COMPANY_WALLET, FEE_PARAM_FEES_IN_USD,
+        FEE_PARAM_PRICE_ESTIMATOR, MAX_CANDIDATE_WALLETS,
+    },
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use solana_sdk::signature::Signer;
+
+// SetFeeParams of the fields in present_fields, passing default values for every field
+fn set_fee_params_instruction(env: &TestEnv, fees_in_usd: u64, present_fields: u8) -> Instruction {
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    set_fee_params(
+        &env.program_id,
+        &env.admin.pubkey(),
+        &program_state_account_key,
+        &Pubkey::default(),
+        &Pubkey::default(),
+        fees_in_usd,
+        &Pubkey::default(),
+        [env.company_wallet; MAX_CANDIDATE_WALLETS],
+        present_fields,
+    )
+    .unwrap()
+}
+
+async fn global_state(env: &mut TestEnv) -> LockGlobalState {
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    let account = get_account(&mut env.context, &program_state_account_key).await;
+    LockGlobalState::unpack_from_slice(&account.data).unwrap()
+}
+
+#[tokio::test]
+async fn test_partial_update_leaves_the_other_fields_intact() {
+    let mut env = setup(true).await;
+    let before = global_state(&mut env).await;
+
+    let instruction = set_fee_params_instruction(&env, 42, FEE_PARAM_FEES_IN_USD);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+
+    let after = global_state(&mut env).await;
+    assert_eq!(after.fees_in_usd, 42);
+    assert_eq!(after.price_estimator, env.price_estimator);
+    assert_eq!(after.company_wallet, env.company_wallet);
+    assert_eq!(after.usd_token_address, before.usd_token_address);
+    assert_eq!(after.candidate_wallets, before.candidate_wallets);
+}
+
+#[tokio::test]
+async fn test_default_pubkeys_are_rejected() {
+    let mut env = setup(true).await;
+
+    for present_fields in [FEE_PARAM_PRICE_ESTIMATOR, FEE_PARAM_COMPANY_WALLET, FEE_PARAMS_ALL].iter() {
+        let instruction = set_fee_params_instruction(&env, 42, *present_fields);
+        assert_instruction_error(
+            process(&mut env.context, &[instruction], &[&env.admin]).await,
+            InstructionError::InvalidArgument,
+        );
+    }
+
+    let state = global_state(&mut env).await;
+    assert_eq!(state.fees_in_usd, 0);
+    assert_eq!(state.price_estimator, env.price_estimator);
+    assert_eq!(state.company_wallet, env.company_wallet);
+}
+
+#[tokio::test]
+async fn test_unknown_present_fields_are_rejected() {
+    let mut env = setup(true).await;
+
+    let instruction = set_fee_params_instruction(&env, 42, 1 << 7);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&env.admin]).await,
+        InstructionError::InvalidInstructionData,
+    );
+    assert_eq!(global_state(&mut env).await.fees_in_usd, 0);
+}
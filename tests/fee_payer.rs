@@ -0,0 +1,138 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    instruction::{create, CreateFeePayment, LockSeed, Schedule},
+    state::{find_global_state_address, token_state_address, TokenState},
+    time::TimeBasis,
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_program::{
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction, system_program,
+    sysvar::{self, clock::Clock},
+};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    signature::{Keypair, Signer},
+};
+
+// 10 USD at 100 USD per SOL
+const FEES_IN_USD: u64 = 10_000_000;
+const FEE_LAMPORTS: u64 = 100_000_000;
+
+// A Pyth price account quoting SOL at 100 USD, published at the current slot
+async fn set_sol_price(env: &mut TestEnv) {
+    let clock: Clock = env.context.banks_client.get_sysvar().await.unwrap();
+    let mut data = vec![0; 240];
+    data[0..4].copy_from_slice(&0xa1b2c3d4u32.to_le_bytes());
+    data[4..8].copy_from_slice(&2u32.to_le_bytes());
+    data[8..12].copy_from_slice(&3u32.to_le_bytes());
+    data[20..24].copy_from_slice(&(-2i32).to_le_bytes());
+    data[208..216].copy_from_slice(&10_000i64.to_le_bytes());
+    data[224..228].copy_from_slice(&1u32.to_le_bytes());
+    data[232..240].copy_from_slice(&clock.slot.to_le_bytes());
+    let account = Account {
+        lamports: 1_000_000_000,
+        data,
+        owner: Pubkey::new_unique(),
+        executable: false,
+        rent_epoch: 0,
+    };
+    env.context.set_account(&env.price_estimator, &AccountSharedData::from(account));
+}
+
+// Charges FEES_IN_USD for locking the mint of the test environment
+fn charge_fees(env: &mut TestEnv) {
+    let mut state = global_state(&env.admin.pubkey(), &env.company_wallet, &env.price_estimator);
+    state.fees_in_usd = FEES_IN_USD;
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    let account = program_account(&env.program_id, &state);
+    env.context.set_account(&program_state_account_key, &AccountSharedData::from(account));
+
+    let mint = env.mint.pubkey();
+    let token_state = TokenState {
+        mint_address: mint,
+        is_free: false,
+        fee_override_usd: 0,
+        has_override: false,
+        active_locks: 0,
+        total_locked_amount: 0,
+        last_snapshot_amount: 0,
+        last_snapshot_ts: 0,
+        is_initialized: true,
+    };
+    let account = program_account(&env.program_id, &token_state);
+    env.context.set_account(&token_state_address(&env.program_id, &mint).unwrap(), &AccountSharedData::from(account));
+}
+
+// The treasury only signs the token transfer and holds no lamports at all, the ops wallet pays the fee and the
+// rent of the locking token account
+#[tokio::test]
+async fn test_create_with_separate_fee_payer() {
+    let mut env = setup(true).await;
+    charge_fees(&mut env);
+    set_sol_price(&mut env).await;
+    let mint = env.mint.pubkey();
+    let rent = env.context.banks_client.get_rent().await.unwrap();
+
+    let treasury = Keypair::new();
+    let source = create_token_account(&mut env.context, &mint, &treasury.pubkey()).await;
+    mint_to(&mut env.context, &mint, &source, 500).await;
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+
+    let ops_wallet = Keypair::new();
+    let payer = env.context.payer.pubkey();
+    let instruction = system_instruction::transfer(&payer, &ops_wallet.pubkey(), 1_000_000_000);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    let start = now(&mut env.context).await;
+    let schedules = vec![Schedule { release_time: start + 100, amount: 500 }];
+    let seeds = LockSeed::random(&env.program_id);
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    let locking_token_account = get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id());
+    let instructions = [
+        init_instruction(&env, seeds, schedules.len() as u32),
+        create(
+            &env.program_id,
+            &spl_token::id(),
+            &system_program::id(),
+            &sysvar::rent::id(),
+            &spl_associated_token_account::id(),
+            &env.price_estimator,
+            &locking_account,
+            &locking_token_account,
+            &treasury.pubkey(),
+            &source,
+            &ops_wallet.pubkey(),
+            &destination,
+            &env.company_wallet,
+            &mint,
+            schedules,
+            seeds,
+            0,
+            false,
+            TimeBasis::UnixTimestamp,
+            Vec::new(),
+            0,
+            CreateFeePayment::Sol,
+            None,
+            false,
+            &[],
+        )
+        .unwrap(),
+    ];
+    process(&mut env.context, &instructions, &[&treasury, &ops_wallet]).await.unwrap();
+
+    assert_eq!(token_balance(&mut env.context, &source).await, 0);
+    assert_eq!(token_balance(&mut env.context, &locking_token_account).await, 500);
+    assert_eq!(get_account(&mut env.context, &env.company_wallet).await.lamports, FEE_LAMPORTS);
+    assert_eq!(
+        get_account(&mut env.context, &ops_wallet.pubkey()).await.lamports,
+        1_000_000_000 - FEE_LAMPORTS - rent.minimum_balance(spl_token::state::Account::LEN)
+    );
+    assert!(env.context.banks_client.get_account(treasury.pubkey()).await.unwrap().is_none());
+}
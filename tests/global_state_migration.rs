@@ -0,0 +1,170 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    error::LockTokenError,
+    instruction::{initialize_global_state, set_fee_params, Schedule},
+    state::{
+        find_global_state_address, legacy_global_state_address, LockGlobalState, FEE_PARAM_FEES_IN_USD,
+        GLOBAL_STATE_VERSION, LEGACY_GLOBAL_STATE_SEED, MAX_CANDIDATE_WALLETS,
+    },
+};
+use solana_program::{
+    bpf_loader_upgradeable,
+    instruction::{Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+use solana_sdk::signature::Signer;
+
+// Not every program derives the address of the version 1 program state account, so keep starting programs
+// until one does
+async fn setup_with_legacy_address() -> (TestEnv, Pubkey) {
+    loop {
+        let env = setup(false).await;
+        if Pubkey::create_program_address(&[LEGACY_GLOBAL_STATE_SEED], &env.program_id).is_ok() {
+            let legacy_program_state_account_key = legacy_global_state_address(&env.program_id).unwrap();
+            return (env, legacy_program_state_account_key);
+        }
+    }
+}
+
+// The program data account of an upgradeable program, recording the context payer as its upgrade authority
+fn set_program_data(env: &mut TestEnv) -> Pubkey {
+    let (program_data_account_key, _) =
+        Pubkey::find_program_address(&[env.program_id.as_ref()], &bpf_loader_upgradeable::id());
+    let mut data = vec![0; 45];
+    data[..4].copy_from_slice(&3u32.to_le_bytes());
+    data[12] = 1;
+    data[13..45].copy_from_slice(env.context.payer.pubkey().as_ref());
+    env.context.set_account(
+        &program_data_account_key,
+        &program_owned_account(&bpf_loader_upgradeable::id(), 1_000_000_000, data),
+    );
+    program_data_account_key
+}
+
+fn initialize_global_state_instruction(
+    env: &TestEnv,
+    program_data_account_key: &Pubkey,
+    with_legacy_global_state: bool,
+) -> Instruction {
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    initialize_global_state(
+        &env.program_id,
+        &system_program::id(),
+        &sysvar::rent::id(),
+        &env.context.payer.pubkey(),
+        &program_state_account_key,
+        program_data_account_key,
+        &env.admin.pubkey(),
+        with_legacy_global_state,
+    )
+    .unwrap()
+}
+
+// A program state account as the program wrote it before the layout was versioned: the price estimator, the USD
+// token, the USD fee, the company wallet, then the pause and initialized flags
+fn version_1_data(env: &TestEnv, usd_token_address: &Pubkey, fees_in_usd: u64) -> Vec<u8> {
+    let mut data = Vec::with_capacity(LockGlobalState::LEGACY_LEN);
+    data.extend_from_slice(env.price_estimator.as_ref());
+    data.extend_from_slice(usd_token_address.as_ref());
+    data.extend_from_slice(&fees_in_usd.to_le_bytes());
+    data.extend_from_slice(env.company_wallet.as_ref());
+    data.push(0);
+    data.push(1);
+    data
+}
+
+#[tokio::test]
+async fn test_initialize_global_state_migrates_the_version_1_account() {
+    let (mut env, legacy_program_state_account_key) = setup_with_legacy_address().await;
+    let program_data_account_key = set_program_data(&mut env);
+    let usd_token_address = Pubkey::new_unique();
+    let rent = env.context.banks_client.get_rent().await.unwrap();
+    let legacy_lamports = rent.minimum_balance(LockGlobalState::LEGACY_LEN);
+    env.context.set_account(
+        &legacy_program_state_account_key,
+        &program_owned_account(&env.program_id, legacy_lamports, version_1_data(&env, &usd_token_address, 123)),
+    );
+
+    let payer = env.context.payer.pubkey();
+    let payer_lamports = get_account(&mut env.context, &payer).await.lamports;
+    let instruction = initialize_global_state_instruction(&env, &program_data_account_key, true);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    // The new account is written in the current layout with the fields of the version 1 account
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    let data = get_account(&mut env.context, &program_state_account_key).await.data;
+    assert_eq!(data.len(), LockGlobalState::LEN);
+    assert_eq!(data[568], GLOBAL_STATE_VERSION);
+    let state = LockGlobalState::unpack_from_slice(&data).unwrap();
+    assert_eq!(state.admin, env.admin.pubkey());
+    assert_eq!(state.price_estimator, env.price_estimator);
+    assert_eq!(state.usd_token_address, usd_token_address);
+    assert_eq!(state.fees_in_usd, 123);
+    assert_eq!(state.company_wallet, env.company_wallet);
+    assert!(!state.is_paused());
+    assert!(state.is_initialized);
+
+    // The version 1 account is closed into the upgrade authority, which paid for the new one and the fee
+    let legacy_account = env.context.banks_client.get_account(legacy_program_state_account_key).await.unwrap();
+    assert!(legacy_account.is_none());
+    let new_rent = rent.minimum_balance(LockGlobalState::LEN);
+    let fee = payer_lamports + legacy_lamports - new_rent - get_account(&mut env.context, &payer).await.lamports;
+    assert_eq!(fee, 5_000);
+
+    // The admin instructions write the migrated account
+    let instruction = set_fee_params(
+        &env.program_id,
+        &env.admin.pubkey(),
+        &program_state_account_key,
+        &Pubkey::default(),
+        &Pubkey::default(),
+        42,
+        &Pubkey::default(),
+        [env.company_wallet; MAX_CANDIDATE_WALLETS],
+        FEE_PARAM_FEES_IN_USD,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+    let data = get_account(&mut env.context, &program_state_account_key).await.data;
+    let state = LockGlobalState::unpack_from_slice(&data).unwrap();
+    assert_eq!(state.fees_in_usd, 42);
+    assert_eq!(state.usd_token_address, usd_token_address);
+
+    // and locks are created against it
+    let start = now(&mut env.context).await;
+    create_test_lock(&mut env, vec![Schedule { release_time: start + 100, amount: 10 }]).await;
+}
+
+#[tokio::test]
+async fn test_initialize_global_state_rejects_an_uninitialized_version_1_account() {
+    let (mut env, legacy_program_state_account_key) = setup_with_legacy_address().await;
+    let program_data_account_key = set_program_data(&mut env);
+    let mut data = version_1_data(&env, &Pubkey::new_unique(), 0);
+    data[105] = 0;
+    env.context.set_account(
+        &legacy_program_state_account_key,
+        &program_owned_account(&env.program_id, 1_000_000_000, data),
+    );
+
+    let instruction = initialize_global_state_instruction(&env, &program_data_account_key, true);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::Custom(LockTokenError::GlobalStateUninitialized as u32),
+    );
+
+    // Without the version 1 account the program state starts out empty
+    let instruction = initialize_global_state_instruction(&env, &program_data_account_key, false);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    let data = get_account(&mut env.context, &program_state_account_key).await.data;
+    let state = LockGlobalState::unpack_from_slice(&data).unwrap();
+    assert_eq!(state.admin, env.admin.pubkey());
+    assert_eq!(state.fees_in_usd, 0);
+    assert_eq!(state.company_wallet, Pubkey::default());
+}
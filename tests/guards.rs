@@ -0,0 +1,78 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    error::LockTokenError,
+    instruction::{transfer_locks, LockSeed, Schedule},
+    state::{find_global_state_address, PAUSE_CREATE},
+};
+use solana_program::{instruction::InstructionError, pubkey::Pubkey};
+use solana_sdk::{account::AccountSharedData, signature::Signer};
+
+// Locks 300 of the context payer's tokens released at start + 100
+async fn create_lock(env: &mut TestEnv) -> TestLock {
+    let start = now(&mut env.context).await;
+    create_test_lock(env, vec![Schedule { release_time: start + 100, amount: 300 }]).await
+}
+
+// Hands the program state account over to another program, keeping its data
+async fn reassign_global_state(env: &mut TestEnv) {
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    let mut account = get_account(&mut env.context, &program_state_account_key).await;
+    account.owner = Pubkey::new_unique();
+    env.context.set_account(&program_state_account_key, &AccountSharedData::from(account));
+}
+
+#[tokio::test]
+async fn test_global_state_owned_by_another_program_is_rejected() {
+    let mut env = setup(true).await;
+    let lock = create_lock(&mut env).await;
+    let mint = env.mint.pubkey();
+    let new_destination = create_token_account(&mut env.context, &mint, &lock.beneficiary.pubkey()).await;
+    reassign_global_state(&mut env).await;
+
+    let invalid_global_state = InstructionError::Custom(LockTokenError::InvalidGlobalState as u32);
+
+    let instruction = init_instruction(&env, LockSeed::random(&env.program_id), 1);
+    assert_instruction_error(process(&mut env.context, &[instruction], &[]).await, invalid_global_state.clone());
+
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        invalid_global_state.clone(),
+    );
+
+    let instruction = transfer_locks(
+        &env.program_id,
+        &lock.locking_account,
+        &lock.beneficiary.pubkey(),
+        &lock.destination,
+        &new_destination,
+        lock.seeds,
+    )
+    .unwrap();
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&lock.beneficiary]).await,
+        invalid_global_state.clone(),
+    );
+
+    let instruction = pause_contract(&env.program_id, &env.admin.pubkey(), PAUSE_CREATE);
+    assert_instruction_error(process(&mut env.context, &[instruction], &[&env.admin]).await, invalid_global_state);
+}
+
+#[tokio::test]
+async fn test_locking_account_of_other_seeds_is_rejected() {
+    let mut env = setup(true).await;
+    let lock = create_lock(&mut env).await;
+    let other_lock = create_lock(&mut env).await;
+
+    // The locking account of one lock with the seeds of the other
+    let mut instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    instruction.accounts[2].pubkey = other_lock.locking_account;
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::Custom(LockTokenError::InvalidLockingAccount as u32),
+    );
+}
@@ -0,0 +1,328 @@
+#![cfg(feature = "anchor-compat")]
+
+use borsh::BorshSerialize;
+use lock_token::{
+    idl::{idl_json, instruction_index, sighash, write_idl, AccountDiscriminator, INSTRUCTIONS},
+    instruction::{LockSeed, LockTokenInstruction, MultiSchedule, Schedule},
+    state::{
+        FeeDiscountTier, LockGlobalState, LockScheduleHeader, SplitDestination, TokenState, WalletState, MAX_CANDIDATE_WALLETS,
+        MAX_FEE_DISCOUNT_TIERS,
+    },
+    time::TimeBasis,
+};
+use serde_json::{json, Map, Value};
+use solana_program::pubkey::Pubkey;
+use std::collections::BTreeSet;
+
+fn seeds(byte: u8) -> LockSeed {
+    LockSeed([byte; 32])
+}
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+// One instruction of every variant
+fn instructions() -> Vec<LockTokenInstruction> {
+    vec![
+        LockTokenInstruction::Init { seeds: seeds(1), number_of_schedules: 3 },
+        LockTokenInstruction::Create {
+            seeds: seeds(2),
+            mint_address: key(3),
+            destination_token_address: key(4),
+            schedules: vec![
+                Schedule { release_time: 1_700_000_000, amount: 5 },
+                Schedule { release_time: 1_800_000_000, amount: u64::MAX },
+            ],
+            deadline: 7,
+            max_total: Some(u64::MAX),
+            is_nft: false,
+            is_immutable: true,
+            pay_fee_in_usd: false,
+            pay_fee_in_token: true,
+            with_fee_discount: false,
+            time_basis: TimeBasis::Slot,
+            split_destinations: vec![
+                SplitDestination { destination_address: key(4), share_bps: 8_000 },
+                SplitDestination { destination_address: key(5), share_bps: 2_000 },
+            ],
+            with_wallet_state: true,
+            min_claim_interval_secs: 3,
+        },
+        LockTokenInstruction::CreateNative {
+            seeds: seeds(3),
+            destination_address: key(6),
+            schedules: vec![Schedule { release_time: 9, amount: 10 }],
+        },
+        LockTokenInstruction::Unlock { seeds: seeds(4), deadline: 11 },
+        LockTokenInstruction::TransferLocks { seeds: seeds(5) },
+        LockTokenInstruction::ExtendLockDuration { seeds: seeds(6), index: 2, release_time: 12 },
+        LockTokenInstruction::PauseContract { pause_flags: 3 },
+        LockTokenInstruction::SetFeeParams {
+            price_estimator: key(7),
+            usd_token_address: key(8),
+            fees_in_usd: 13,
+            company_wallet: key(9),
+            candidate_wallets: [key(10); MAX_CANDIDATE_WALLETS],
+            present_fields: 13,
+        },
+        LockTokenInstruction::SetFeesInUSD { fees_in_usd: 14 },
+        LockTokenInstruction::SetCompanyWallet { company_wallet: key(11) },
+        LockTokenInstruction::SetMaxPriceAge { max_price_age_slots: 15 },
+        LockTokenInstruction::SetFreeToken { mint_address: key(12), is_free: true },
+        LockTokenInstruction::SetTokenFee { mint_address: key(13), fee_in_usd: None },
+        LockTokenInstruction::InitializeGlobalState { admin: key(14) },
+        LockTokenInstruction::ProposeOwner { new_admin: key(15) },
+        LockTokenInstruction::AcceptOwnership {},
+        LockTokenInstruction::CancelOwnershipTransfer {},
+        LockTokenInstruction::SetLockMinimums { min_lock_duration_secs: 16, min_lock_amount: 17 },
+        LockTokenInstruction::ProposeTransferLocks { seeds: seeds(7), new_destination: key(16) },
+        LockTokenInstruction::AcceptTransferLocks { seeds: seeds(8) },
+        LockTokenInstruction::CancelTransferLocks { seeds: seeds(9) },
+        LockTokenInstruction::ExtendLockDurations { seeds: seeds(10), extensions: vec![(0, 18), (4, 19)] },
+        LockTokenInstruction::Relock { seeds: seeds(11), index: 1, new_release_time: 20 },
+        LockTokenInstruction::BatchUnlock { seeds_list: vec![seeds(12), seeds(13)] },
+        LockTokenInstruction::CreateRecurring {
+            seeds: seeds(14),
+            mint_address: key(17),
+            destination_token_address: key(18),
+            start_time: 21,
+            period_secs: 22,
+            periods: 23,
+            amount_per_period: 24,
+        },
+        LockTokenInstruction::CreateVesting {
+            seeds: seeds(15),
+            mint_address: key(19),
+            destination_token_address: key(20),
+            cliff_time: 25,
+            cliff_bps: 2_500,
+            vesting_end: 26,
+            total_amount: 27,
+        },
+        LockTokenInstruction::MigrateLock { seeds: seeds(16) },
+        LockTokenInstruction::ScheduleEmergencyUnlock { seeds: seeds(17), rescue_destination: key(21) },
+        LockTokenInstruction::ExecuteEmergencyUnlock { seeds: seeds(18) },
+        LockTokenInstruction::CancelEmergencyUnlock { seeds: seeds(19) },
+        LockTokenInstruction::SweepExcess { seeds: seeds(20) },
+        LockTokenInstruction::SetFeeBpsInToken { fee_bps_in_token: 50 },
+        LockTokenInstruction::SetFeeDiscountTiers {
+            fee_discount_tiers: [FeeDiscountTier { min_balance: 28, discount_bps: 1_000 }; MAX_FEE_DISCOUNT_TIERS],
+        },
+        LockTokenInstruction::GetClaimableAmount { seeds: seeds(21) },
+        LockTokenInstruction::CreateMulti {
+            seeds: seeds(22),
+            mint_address: key(23),
+            destination_token_address: key(24),
+            schedules: vec![MultiSchedule { release_time: 29, amount: 30, destination_token_address: key(25) }],
+        },
+        LockTokenInstruction::SetLockMetadata {
+            seeds: seeds(23),
+            name: "Team \"core\"".to_string(),
+            uri: "https://example.com/lock.json".to_string(),
+        },
+        LockTokenInstruction::CloseLockMetadata { seeds: seeds(24) },
+        LockTokenInstruction::ReclaimUninitializedLock { seeds: seeds(25) },
+        LockTokenInstruction::SetFeeExemptWallet { wallet: key(26), is_exempt: true },
+        LockTokenInstruction::SetMaxLockDuration { max_lock_duration_secs: 27 },
+        LockTokenInstruction::TransferScheduleIndices { seeds: seeds(28), new_seeds: seeds(29), indices: vec![3, 0] },
+        LockTokenInstruction::ApproveLockDelegate { seeds: seeds(30), delegate: key(31) },
+        LockTokenInstruction::RevokeLockDelegate { seeds: seeds(32) },
+        LockTokenInstruction::ClaimAndClose { seeds: seeds(33) },
+        LockTokenInstruction::SnapshotMintLocked { mint: key(34) },
+        LockTokenInstruction::UnlockWithPermit { seeds: seeds(35), expiry: 35 },
+        LockTokenInstruction::InitDerived { creator: key(36), mint_address: key(37), nonce: 36, number_of_schedules: 36 },
+        LockTokenInstruction::SetOwnerTokenMint { new_mint: key(38) },
+        LockTokenInstruction::UnlockAmount { seeds: seeds(39), amount: 39 },
+        LockTokenInstruction::ProposeCandidateWallets { candidate_wallets: [key(40); MAX_CANDIDATE_WALLETS] },
+        LockTokenInstruction::AcceptCandidateWallets {},
+    ]
+}
+
+// Name of the variant in snake case, like the IDL names instructions
+fn snake_case_name(instruction: &LockTokenInstruction) -> String {
+    let debug = format!("{:?}", instruction);
+    let variant = debug.split(|c: char| !c.is_alphanumeric()).next().unwrap();
+    let mut name = String::new();
+    let chars: Vec<char> = variant.chars().collect();
+    for (i, c) in chars.iter().enumerate() {
+        let starts_word = i > 0
+            && c.is_uppercase()
+            && (chars[i - 1].is_lowercase() || chars.get(i + 1).map_or(false, |next| next.is_lowercase()));
+        if starts_word {
+            name.push('_');
+        }
+        name.extend(c.to_lowercase());
+    }
+    name
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> &'a [u8] {
+    let (head, rest) = data.split_at(len);
+    *data = rest;
+    head
+}
+
+// Decodes borsh data of the IDL type, looking up defined types in the types of the IDL
+fn decode(idl: &Value, ty: &Value, data: &mut &[u8]) -> Value {
+    if let Some(name) = ty.as_str() {
+        return match name {
+            "bool" => json!(take(data, 1)[0] != 0),
+            "u8" => json!(take(data, 1)[0]),
+            "u16" => json!(u16::from_le_bytes([take(data, 1)[0], take(data, 1)[0]])),
+            "u32" => {
+                let mut bytes = [0; 4];
+                bytes.copy_from_slice(take(data, 4));
+                json!(u32::from_le_bytes(bytes))
+            }
+            "u64" => {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(take(data, 8));
+                json!(u64::from_le_bytes(bytes))
+            }
+            "pubkey" => json!(Pubkey::new(take(data, 32)).to_string()),
+            "string" => {
+                let len = decode(idl, &json!("u32"), data).as_u64().unwrap() as usize;
+                json!(String::from_utf8(take(data, len).to_vec()).unwrap())
+            }
+            _ => panic!("unknown type {}", name),
+        };
+    }
+    if let Some(array) = ty.get("array") {
+        let len = array[1].as_u64().unwrap();
+        return Value::Array((0..len).map(|_| decode(idl, &array[0], data)).collect());
+    }
+    if let Some(item) = ty.get("vec") {
+        let len = decode(idl, &json!("u32"), data).as_u64().unwrap();
+        return Value::Array((0..len).map(|_| decode(idl, item, data)).collect());
+    }
+    if let Some(item) = ty.get("option") {
+        return match take(data, 1)[0] {
+            0 => Value::Null,
+            _ => decode(idl, item, data),
+        };
+    }
+    let name = &ty["defined"]["name"];
+    let type_def = idl["types"].as_array().unwrap().iter().find(|type_def| type_def["name"] == *name).unwrap();
+    match type_def["type"]["kind"].as_str().unwrap() {
+        "struct" => decode_fields(idl, &type_def["type"]["fields"], data),
+        _ => type_def["type"]["variants"][take(data, 1)[0] as usize]["name"].clone(),
+    }
+}
+
+fn decode_fields(idl: &Value, fields: &Value, data: &mut &[u8]) -> Value {
+    let mut values = Map::new();
+    for field in fields.as_array().unwrap() {
+        values.insert(field["name"].as_str().unwrap().to_string(), decode(idl, &field["type"], data));
+    }
+    Value::Object(values)
+}
+
+// Finds the instruction of the discriminator in the IDL and decodes its args
+fn decode_instruction(idl: &Value, data: &[u8]) -> (String, Value) {
+    let discriminator = json!(data[..8].to_vec());
+    let instruction = idl["instructions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|instruction| instruction["discriminator"] == discriminator)
+        .expect("no instruction of the discriminator");
+    let mut args = &data[8..];
+    let values = decode_fields(idl, &instruction["args"], &mut args);
+    assert!(args.is_empty(), "{} bytes left after the args of {}", args.len(), instruction["name"]);
+    (instruction["name"].as_str().unwrap().to_string(), values)
+}
+
+#[test]
+fn test_discriminators() {
+    for instruction in INSTRUCTIONS {
+        assert_eq!(instruction.discriminator, sighash("global", instruction.name), "{}", instruction.name);
+    }
+    let discriminators: BTreeSet<[u8; 8]> = INSTRUCTIONS.iter().map(|instruction| instruction.discriminator).collect();
+    assert_eq!(discriminators.len(), INSTRUCTIONS.len());
+
+    assert_eq!(LockScheduleHeader::DISCRIMINATOR, sighash("account", LockScheduleHeader::NAME));
+    assert_eq!(LockGlobalState::DISCRIMINATOR, sighash("account", LockGlobalState::NAME));
+    assert_eq!(TokenState::DISCRIMINATOR, sighash("account", TokenState::NAME));
+    assert_eq!(WalletState::DISCRIMINATOR, sighash("account", WalletState::NAME));
+}
+
+// The table follows the declaration order of the variants and their legacy tags
+#[test]
+fn test_instruction_table() {
+    let instructions = instructions();
+    let mut indices = BTreeSet::new();
+    for instruction in instructions.iter() {
+        let index = instruction.try_to_vec().unwrap()[0] as usize;
+        assert_eq!(INSTRUCTIONS[index].name, snake_case_name(instruction));
+        assert_eq!(INSTRUCTIONS[index].tag, instruction.pack()[0], "{}", INSTRUCTIONS[index].name);
+        assert_eq!(instruction_index(&instruction.pack_anchor()[..8]), Some(index));
+        indices.insert(index);
+    }
+    assert_eq!(indices.len(), INSTRUCTIONS.len());
+}
+
+#[test]
+fn test_anchor_and_legacy_encodings_decode_alike() {
+    for instruction in instructions() {
+        let anchor = instruction.pack_anchor();
+        assert_eq!(LockTokenInstruction::unpack(&anchor).unwrap(), instruction);
+        assert_eq!(LockTokenInstruction::unpack(&instruction.pack()).unwrap(), instruction);
+        assert_eq!(LockTokenInstruction::unpack(&instruction.pack_borsh()).unwrap(), instruction);
+    }
+
+    // Trailing bytes after the args
+    let mut data = LockTokenInstruction::AcceptOwnership {}.pack_anchor();
+    data.push(0);
+    assert!(LockTokenInstruction::unpack(&data).is_err());
+}
+
+// Decoding the Anchor encoding with nothing but the generated IDL gives back every arg
+#[test]
+fn test_idl_decodes_packed_instructions() {
+    let idl: Value = serde_json::from_str(&idl_json(&key(99))).unwrap();
+    for instruction in instructions() {
+        let (name, _) = decode_instruction(&idl, &instruction.pack_anchor());
+        assert_eq!(name, snake_case_name(&instruction));
+    }
+
+    let create = &instructions()[1];
+    let (name, args) = decode_instruction(&idl, &create.pack_anchor());
+    assert_eq!(name, "create");
+    assert_eq!(args["seeds"], json!(vec![2u8; 32]));
+    assert_eq!(args["mint_address"], json!(key(3).to_string()));
+    assert_eq!(args["schedules"][1], json!({ "release_time": 1_800_000_000u64, "amount": u64::MAX }));
+    assert_eq!(args["max_total"], json!(u64::MAX));
+    assert_eq!(args["is_immutable"], json!(true));
+    assert_eq!(args["time_basis"], json!("Slot"));
+    assert_eq!(
+        args["split_destinations"][1],
+        json!({ "destination_address": key(5).to_string(), "share_bps": 2_000 })
+    );
+
+    let (_, args) = decode_instruction(&idl, &instructions()[12].pack_anchor());
+    assert_eq!(args["fee_in_usd"], Value::Null);
+    let (_, args) = decode_instruction(&idl, &instructions()[21].pack_anchor());
+    assert_eq!(args["extensions"][1], json!({ "index": 4, "release_time": 19 }));
+    let (_, args) = decode_instruction(&idl, &instructions()[35].pack_anchor());
+    assert_eq!(args["name"], json!("Team \"core\""));
+}
+
+#[test]
+fn test_idl_accounts() {
+    let idl: Value = serde_json::from_str(&idl_json(&key(99))).unwrap();
+    assert_eq!(idl["address"], json!(key(99).to_string()));
+    let unlock = idl["instructions"].as_array().unwrap().iter().find(|i| i["name"] == "unlock").unwrap();
+    let accounts = unlock["accounts"].as_array().unwrap();
+    assert_eq!(accounts[0]["name"], json!("token_program"));
+    assert_eq!(accounts[2]["writable"], json!(true));
+    assert_eq!(accounts[2].get("signer"), None);
+}
+
+#[test]
+fn test_write_idl() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("target/idl/lock_token.json");
+    write_idl(&path, &key(99)).unwrap();
+    let idl: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(idl["instructions"].as_array().unwrap().len(), INSTRUCTIONS.len());
+}
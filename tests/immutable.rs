@@ -0,0 +1,174 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    client::{is_immutable_lock, unpack_lock_header},
+    error::LockTokenError,
+    instruction::{
+        accept_transfer_locks, extend_lock_duration, extend_lock_durations, propose_transfer_locks, relock,
+        transfer_locks, unlock, LockSeed, Schedule,
+    },
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_program::{instruction::InstructionError, pubkey::Pubkey};
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+struct ImmutableLock {
+    seeds: LockSeed,
+    locking_account: Pubkey,
+    destination: Pubkey,
+    beneficiary: Keypair,
+    start: u64,
+}
+
+async fn create_immutable_lock(env: &mut TestEnv) -> ImmutableLock {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 300).await;
+    let beneficiary = Keypair::new();
+    let destination = create_token_account(&mut env.context, &mint, &beneficiary.pubkey()).await;
+
+    let start = now(&mut env.context).await;
+    let schedules = vec![
+        Schedule { release_time: start + 100, amount: 100 },
+        Schedule { release_time: start + 200, amount: 200 },
+    ];
+    let seeds = LockSeed::random(&env.program_id);
+    let instructions = [
+        init_instruction(env, seeds, schedules.len() as u32),
+        create_lock_instruction(env, seeds, &payer, &source, &destination, schedules, true, &[]),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+
+    ImmutableLock {
+        seeds,
+        locking_account: seeds.locking_account_address(&env.program_id).unwrap(),
+        destination,
+        beneficiary,
+        start,
+    }
+}
+
+fn assert_immutable_lock_error(result: Result<(), TransactionError>) {
+    assert_instruction_error(result, InstructionError::Custom(LockTokenError::ImmutableLock as u32));
+}
+
+#[tokio::test]
+async fn test_immutable_lock_flag() {
+    let mut env = setup(true).await;
+    let lock = create_immutable_lock(&mut env).await;
+
+    let data = get_account(&mut env.context, &lock.locking_account).await.data;
+    assert!(is_immutable_lock(&data).unwrap());
+    let header = unpack_lock_header(&data).unwrap();
+    assert!(header.is_immutable);
+    assert!(!header.is_nft);
+    assert!(header.is_registered);
+}
+
+#[tokio::test]
+async fn test_immutable_lock_can_not_be_moved() {
+    let mut env = setup(true).await;
+    let lock = create_immutable_lock(&mut env).await;
+    let mint = env.mint.pubkey();
+    let new_beneficiary = Keypair::new();
+    let new_destination = create_token_account(&mut env.context, &mint, &new_beneficiary.pubkey()).await;
+    let owner = lock.beneficiary.pubkey();
+
+    let instruction = transfer_locks(
+        &env.program_id,
+        &lock.locking_account,
+        &owner,
+        &lock.destination,
+        &new_destination,
+        lock.seeds,
+    )
+    .unwrap();
+    assert_immutable_lock_error(process(&mut env.context, &[instruction], &[&lock.beneficiary]).await);
+
+    let instruction = propose_transfer_locks(
+        &env.program_id,
+        &lock.locking_account,
+        &owner,
+        &lock.destination,
+        &new_destination,
+        lock.seeds,
+    )
+    .unwrap();
+    assert_immutable_lock_error(process(&mut env.context, &[instruction], &[&lock.beneficiary]).await);
+
+    let instruction = accept_transfer_locks(
+        &env.program_id,
+        &lock.locking_account,
+        &new_beneficiary.pubkey(),
+        &new_destination,
+        lock.seeds,
+    )
+    .unwrap();
+    assert_immutable_lock_error(process(&mut env.context, &[instruction], &[&new_beneficiary]).await);
+}
+
+#[tokio::test]
+async fn test_immutable_lock_can_not_be_extended() {
+    let mut env = setup(true).await;
+    let lock = create_immutable_lock(&mut env).await;
+    let owner = lock.beneficiary.pubkey();
+
+    let instruction = extend_lock_duration(
+        &env.program_id,
+        &lock.locking_account,
+        &owner,
+        &lock.destination,
+        lock.seeds,
+        1,
+        lock.start + 300,
+    )
+    .unwrap();
+    assert_immutable_lock_error(process(&mut env.context, &[instruction], &[&lock.beneficiary]).await);
+
+    let instruction = extend_lock_durations(
+        &env.program_id,
+        &lock.locking_account,
+        &owner,
+        &lock.destination,
+        lock.seeds,
+        vec![(1, lock.start + 300)],
+    )
+    .unwrap();
+    assert_immutable_lock_error(process(&mut env.context, &[instruction], &[&lock.beneficiary]).await);
+
+    warp_to_timestamp(&mut env.context, lock.start + 150).await;
+    let instruction = relock(
+        &env.program_id,
+        &lock.locking_account,
+        &owner,
+        &lock.destination,
+        lock.seeds,
+        0,
+        lock.start + 300,
+    )
+    .unwrap();
+    assert_immutable_lock_error(process(&mut env.context, &[instruction], &[&lock.beneficiary]).await);
+
+    // Unlocking is not a change of the lock
+    let mint = env.mint.pubkey();
+    let instruction = unlock(
+        &env.program_id,
+        &spl_token::id(),
+        &lock.locking_account,
+        &get_associated_token_address_with_program_id(&lock.locking_account, &mint, &spl_token::id()),
+        &lock.destination,
+        &mint,
+        lock.seeds,
+        0,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 100);
+}
@@ -0,0 +1,104 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    error::LockTokenError,
+    instruction::LockSeed,
+    state::{get_locking_account_size, MAX_LOCKING_ACCOUNT_SCHEDULES},
+};
+use solana_program::{instruction::InstructionError, pubkey::Pubkey, system_instruction};
+use solana_sdk::signature::Signer;
+
+fn custom_error(error: LockTokenError) -> InstructionError {
+    InstructionError::Custom(error as u32)
+}
+
+#[tokio::test]
+async fn test_init_schedule_bounds() {
+    let mut env = setup(true).await;
+
+    let instruction = init_instruction(&env, LockSeed::random(&env.program_id), 0);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        custom_error(LockTokenError::EmptySchedules),
+    );
+
+    let instruction = init_instruction(&env, LockSeed::random(&env.program_id), MAX_LOCKING_ACCOUNT_SCHEDULES as u32 + 1);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        custom_error(LockTokenError::TooManySchedules),
+    );
+
+    let seeds = LockSeed::random(&env.program_id);
+    let instruction = init_instruction(&env, seeds, MAX_LOCKING_ACCOUNT_SCHEDULES as u32);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    let locking_account = get_account(&mut env.context, &seeds.locking_account_address(&env.program_id).unwrap()).await;
+    assert_eq!(locking_account.data.len(), get_locking_account_size(MAX_LOCKING_ACCOUNT_SCHEDULES));
+    assert_eq!(locking_account.owner, env.program_id);
+
+    let instruction = init_instruction(&env, LockSeed::random(&env.program_id), 1);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+}
+
+// Lamports sent to the locking account before Init do not keep it from being created
+#[tokio::test]
+async fn test_init_prefunded_locking_account() {
+    let mut env = setup(true).await;
+    let rent = env.context.banks_client.get_rent().await.unwrap();
+    let payer = env.context.payer.pubkey();
+
+    // The runtime rejects transfers leaving an empty account below its rent exempt minimum, so the smaller amount
+    // is that minimum, which still falls short of the rent of the locking account
+    for &prefunded_lamports in [rent.minimum_balance(0), 1_000_000_000].iter() {
+        let seeds = LockSeed::random(&env.program_id);
+        let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+        let instruction = system_instruction::transfer(&payer, &locking_account, prefunded_lamports);
+        process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+        let instruction = init_instruction(&env, seeds, 3);
+        process(&mut env.context, &[instruction], &[]).await.unwrap();
+        let account = get_account(&mut env.context, &locking_account).await;
+        assert_eq!(account.owner, env.program_id);
+        assert_eq!(account.data.len(), get_locking_account_size(3));
+        assert_eq!(account.lamports, rent.minimum_balance(account.data.len()).max(prefunded_lamports));
+    }
+}
+
+#[tokio::test]
+async fn test_init_twice() {
+    let mut env = setup(true).await;
+    let seeds = LockSeed::random(&env.program_id);
+
+    let instruction = init_instruction(&env, seeds, 3);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    for &number_of_schedules in [3, 5].iter() {
+        refresh_blockhash(&mut env.context).await;
+        let instruction = init_instruction(&env, seeds, number_of_schedules);
+        assert_instruction_error(
+            process(&mut env.context, &[instruction], &[]).await,
+            InstructionError::AccountAlreadyInitialized,
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_init_with_fake_system_program() {
+    let mut env = setup(true).await;
+
+    let mut instruction = init_instruction(&env, LockSeed::random(&env.program_id), 1);
+    instruction.accounts[0].pubkey = Pubkey::new_unique();
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::IncorrectProgramId,
+    );
+
+    let mut instruction = init_instruction(&env, LockSeed::random(&env.program_id), 1);
+    instruction.accounts[2].pubkey = Pubkey::new_unique();
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::InvalidArgument,
+    );
+}
@@ -0,0 +1,469 @@
+use lock_token::{
+    error::LockTokenError,
+    instruction::{LockSeed, LockTokenInstruction, MultiSchedule, Schedule, BORSH_FORMAT, MULTI_SCHEDULE_SIZE, SCHEDULE_SIZE},
+    state::{FeeDiscountTier, SplitDestination, FEE_PARAMS_ALL, MAX_FEE_DISCOUNT_TIERS, MAX_SPLIT_DESTINATIONS},
+    time::TimeBasis,
+};
+use proptest::{collection::vec, prelude::*};
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+fn pubkey() -> impl Strategy<Value = Pubkey> {
+    any::<[u8; 32]>().prop_map(Pubkey::new_from_array)
+}
+
+fn seeds() -> impl Strategy<Value = LockSeed> {
+    any::<[u8; 32]>().prop_map(LockSeed)
+}
+
+fn schedules() -> impl Strategy<Value = Vec<Schedule>> {
+    vec(
+        (any::<u64>(), any::<u64>()).prop_map(|(release_time, amount)| Schedule { release_time, amount }),
+        0..8,
+    )
+}
+
+fn split_destinations() -> impl Strategy<Value = Vec<SplitDestination>> {
+    vec(
+        (pubkey(), any::<u16>()).prop_map(|(destination_address, share_bps)| SplitDestination { destination_address, share_bps }),
+        0..=MAX_SPLIT_DESTINATIONS,
+    )
+}
+
+fn create() -> impl Strategy<Value = LockTokenInstruction> {
+    (seeds(), pubkey(), pubkey(), schedules(), any::<u64>(), any::<Option<u64>>(), any::<(bool, bool, bool, bool, bool, bool, bool)>(), split_destinations(), prop_oneof![Just(0), any::<u32>()]).prop_map(
+        |(
+            seeds,
+            mint_address,
+            destination_token_address,
+            schedules,
+            deadline,
+            max_total,
+            (is_nft, is_immutable, pay_fee_in_usd, pay_fee_in_token, with_fee_discount, is_slot_basis, with_wallet_state),
+            split_destinations,
+            min_claim_interval_secs,
+        )| {
+            LockTokenInstruction::Create {
+                seeds,
+                mint_address,
+                destination_token_address,
+                schedules,
+                deadline,
+                // The flags follow max_total on the wire, so a lock with a flag, a split or a claim interval always carries one
+                max_total: if is_nft || is_immutable || pay_fee_in_usd || pay_fee_in_token || with_fee_discount || is_slot_basis || with_wallet_state || !split_destinations.is_empty() || min_claim_interval_secs != 0 {
+                    Some(max_total.unwrap_or(u64::MAX))
+                } else {
+                    max_total
+                },
+                is_nft,
+                is_immutable,
+                pay_fee_in_usd,
+                pay_fee_in_token,
+                with_fee_discount,
+                time_basis: if is_slot_basis { TimeBasis::Slot } else { TimeBasis::UnixTimestamp },
+                split_destinations,
+                with_wallet_state,
+                min_claim_interval_secs,
+            }
+        },
+    )
+}
+
+fn create_multi() -> impl Strategy<Value = LockTokenInstruction> {
+    let schedules = vec(
+        (any::<u64>(), any::<u64>(), pubkey()).prop_map(|(release_time, amount, destination_token_address)| {
+            MultiSchedule { release_time, amount, destination_token_address }
+        }),
+        0..8,
+    );
+    (seeds(), pubkey(), pubkey(), schedules).prop_map(|(seeds, mint_address, destination_token_address, schedules)| {
+        LockTokenInstruction::CreateMulti {
+            seeds,
+            mint_address,
+            destination_token_address,
+            schedules,
+        }
+    })
+}
+
+fn instruction() -> impl Strategy<Value = LockTokenInstruction> {
+    prop_oneof![
+        (seeds(), any::<u32>()).prop_map(|(seeds, number_of_schedules)| LockTokenInstruction::Init {
+            seeds,
+            number_of_schedules,
+        }),
+        create(),
+        (seeds(), pubkey(), schedules()).prop_map(|(seeds, destination_address, schedules)| {
+            LockTokenInstruction::CreateNative {
+                seeds,
+                destination_address,
+                schedules,
+            }
+        }),
+        (seeds(), any::<u64>()).prop_map(|(seeds, deadline)| LockTokenInstruction::Unlock { seeds, deadline }),
+        seeds().prop_map(|seeds| LockTokenInstruction::TransferLocks { seeds }),
+        (seeds(), any::<u32>(), any::<u64>()).prop_map(|(seeds, index, release_time)| {
+            LockTokenInstruction::ExtendLockDuration {
+                seeds,
+                index,
+                release_time,
+            }
+        }),
+        any::<u8>().prop_map(|pause_flags| LockTokenInstruction::PauseContract { pause_flags }),
+        (pubkey(), pubkey(), any::<u64>(), pubkey(), prop::array::uniform4(pubkey()), any::<u8>()).prop_map(
+            |(price_estimator, usd_token_address, fees_in_usd, company_wallet, candidate_wallets, present_fields)| {
+                LockTokenInstruction::SetFeeParams {
+                    price_estimator,
+                    usd_token_address,
+                    fees_in_usd,
+                    company_wallet,
+                    candidate_wallets,
+                    present_fields,
+                }
+            }
+        ),
+        any::<u64>().prop_map(|fees_in_usd| LockTokenInstruction::SetFeesInUSD { fees_in_usd }),
+        pubkey().prop_map(|company_wallet| LockTokenInstruction::SetCompanyWallet { company_wallet }),
+        any::<u64>().prop_map(|max_price_age_slots| LockTokenInstruction::SetMaxPriceAge { max_price_age_slots }),
+        (pubkey(), any::<bool>()).prop_map(|(mint_address, is_free)| LockTokenInstruction::SetFreeToken {
+            mint_address,
+            is_free,
+        }),
+        (pubkey(), any::<Option<u64>>()).prop_map(|(mint_address, fee_in_usd)| LockTokenInstruction::SetTokenFee {
+            mint_address,
+            fee_in_usd,
+        }),
+        pubkey().prop_map(|admin| LockTokenInstruction::InitializeGlobalState { admin }),
+        pubkey().prop_map(|new_admin| LockTokenInstruction::ProposeOwner { new_admin }),
+        Just(LockTokenInstruction::AcceptOwnership {}),
+        Just(LockTokenInstruction::CancelOwnershipTransfer {}),
+        (any::<u64>(), any::<u64>()).prop_map(|(min_lock_duration_secs, min_lock_amount)| {
+            LockTokenInstruction::SetLockMinimums {
+                min_lock_duration_secs,
+                min_lock_amount,
+            }
+        }),
+        (seeds(), pubkey()).prop_map(|(seeds, new_destination)| LockTokenInstruction::ProposeTransferLocks {
+            seeds,
+            new_destination,
+        }),
+        seeds().prop_map(|seeds| LockTokenInstruction::AcceptTransferLocks { seeds }),
+        seeds().prop_map(|seeds| LockTokenInstruction::CancelTransferLocks { seeds }),
+        seeds().prop_map(|seeds| LockTokenInstruction::MigrateLock { seeds }),
+        (seeds(), pubkey()).prop_map(|(seeds, rescue_destination)| LockTokenInstruction::ScheduleEmergencyUnlock {
+            seeds,
+            rescue_destination,
+        }),
+        seeds().prop_map(|seeds| LockTokenInstruction::ExecuteEmergencyUnlock { seeds }),
+        seeds().prop_map(|seeds| LockTokenInstruction::CancelEmergencyUnlock { seeds }),
+        seeds().prop_map(|seeds| LockTokenInstruction::SweepExcess { seeds }),
+        seeds().prop_map(|seeds| LockTokenInstruction::GetClaimableAmount { seeds }),
+        (seeds(), "\\PC{0,40}", "\\PC{0,60}").prop_map(|(seeds, name, uri)| LockTokenInstruction::SetLockMetadata {
+            seeds,
+            name,
+            uri,
+        }),
+        seeds().prop_map(|seeds| LockTokenInstruction::CloseLockMetadata { seeds }),
+        seeds().prop_map(|seeds| LockTokenInstruction::ReclaimUninitializedLock { seeds }),
+        (pubkey(), any::<bool>()).prop_map(|(wallet, is_exempt)| LockTokenInstruction::SetFeeExemptWallet { wallet, is_exempt }),
+        any::<u64>().prop_map(|max_lock_duration_secs| LockTokenInstruction::SetMaxLockDuration { max_lock_duration_secs }),
+        (seeds(), seeds(), vec(any::<u32>(), 0..8)).prop_map(|(seeds, new_seeds, indices)| {
+            LockTokenInstruction::TransferScheduleIndices { seeds, new_seeds, indices }
+        }),
+        (seeds(), pubkey()).prop_map(|(seeds, delegate)| LockTokenInstruction::ApproveLockDelegate { seeds, delegate }),
+        seeds().prop_map(|seeds| LockTokenInstruction::RevokeLockDelegate { seeds }),
+        seeds().prop_map(|seeds| LockTokenInstruction::ClaimAndClose { seeds }),
+        pubkey().prop_map(|mint| LockTokenInstruction::SnapshotMintLocked { mint }),
+        (seeds(), any::<u64>()).prop_map(|(seeds, expiry)| LockTokenInstruction::UnlockWithPermit { seeds, expiry }),
+        (pubkey(), pubkey(), any::<u64>(), any::<u32>()).prop_map(|(creator, mint_address, nonce, number_of_schedules)| {
+            LockTokenInstruction::InitDerived { creator, mint_address, nonce, number_of_schedules }
+        }),
+        pubkey().prop_map(|new_mint| LockTokenInstruction::SetOwnerTokenMint { new_mint }),
+        (seeds(), any::<u64>()).prop_map(|(seeds, amount)| LockTokenInstruction::UnlockAmount { seeds, amount }),
+        prop::array::uniform4(pubkey())
+            .prop_map(|candidate_wallets| LockTokenInstruction::ProposeCandidateWallets { candidate_wallets }),
+        Just(LockTokenInstruction::AcceptCandidateWallets {}),
+        create_multi(),
+        any::<u16>().prop_map(|fee_bps_in_token| LockTokenInstruction::SetFeeBpsInToken { fee_bps_in_token }),
+        any::<[(u64, u16); MAX_FEE_DISCOUNT_TIERS]>().prop_map(|tiers| LockTokenInstruction::SetFeeDiscountTiers {
+            fee_discount_tiers: tiers.map(|(min_balance, discount_bps)| FeeDiscountTier { min_balance, discount_bps }),
+        }),
+        (seeds(), vec(any::<(u32, u64)>(), 0..8)).prop_map(|(seeds, extensions)| {
+            LockTokenInstruction::ExtendLockDurations { seeds, extensions }
+        }),
+        (seeds(), any::<u32>(), any::<u64>()).prop_map(|(seeds, index, new_release_time)| {
+            LockTokenInstruction::Relock {
+                seeds,
+                index,
+                new_release_time,
+            }
+        }),
+        vec(seeds(), 1..8).prop_map(|seeds_list| LockTokenInstruction::BatchUnlock { seeds_list }),
+        (seeds(), pubkey(), pubkey(), any::<u64>(), any::<u64>(), any::<u32>(), any::<u64>()).prop_map(
+            |(seeds, mint_address, destination_token_address, start_time, period_secs, periods, amount_per_period)| {
+                LockTokenInstruction::CreateRecurring {
+                    seeds,
+                    mint_address,
+                    destination_token_address,
+                    start_time,
+                    period_secs,
+                    periods,
+                    amount_per_period,
+                }
+            }
+        ),
+        (seeds(), pubkey(), pubkey(), any::<u64>(), any::<u16>(), any::<u64>(), any::<u64>()).prop_map(
+            |(seeds, mint_address, destination_token_address, cliff_time, cliff_bps, vesting_end, total_amount)| {
+                LockTokenInstruction::CreateVesting {
+                    seeds,
+                    mint_address,
+                    destination_token_address,
+                    cliff_time,
+                    cliff_bps,
+                    vesting_end,
+                    total_amount,
+                }
+            }
+        ),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn test_pack_unpack_round_trip(instruction in instruction()) {
+        prop_assert_eq!(LockTokenInstruction::unpack(&instruction.pack()).unwrap(), instruction);
+    }
+
+    // Either encoding of an instruction decodes to the same instruction through unpack
+    #[cfg(feature = "borsh")]
+    #[test]
+    fn test_borsh_matches_legacy(instruction in instruction()) {
+        let borsh = instruction.pack_borsh();
+        prop_assert_eq!(borsh[0], BORSH_FORMAT);
+        prop_assert_eq!(LockTokenInstruction::unpack_borsh(&borsh).unwrap(), instruction.clone());
+        prop_assert_eq!(
+            LockTokenInstruction::unpack(&borsh).unwrap(),
+            LockTokenInstruction::unpack(&instruction.pack()).unwrap()
+        );
+    }
+
+    #[cfg(feature = "anchor-compat")]
+    #[test]
+    fn test_anchor_matches_legacy(instruction in instruction()) {
+        prop_assert_eq!(LockTokenInstruction::unpack(&instruction.pack_anchor()).unwrap(), instruction);
+    }
+
+    #[test]
+    fn test_unpack_random_bytes(input in vec(any::<u8>(), 0..512)) {
+        let _ = LockTokenInstruction::unpack(&input);
+    }
+
+    #[cfg(feature = "borsh")]
+    #[test]
+    fn test_unpack_borsh_random_bytes(input in vec(any::<u8>(), 0..512)) {
+        let mut data = vec![BORSH_FORMAT];
+        data.extend(input);
+        let _ = LockTokenInstruction::unpack(&data);
+    }
+
+    // Truncated and padded encodings of valid instructions reach deeper into the parsers than random bytes
+    #[test]
+    fn test_unpack_resized_instruction(instruction in instruction(), len in 0usize..512, fill in any::<u8>()) {
+        let mut input = instruction.pack();
+        input.resize(len, fill);
+        let _ = LockTokenInstruction::unpack(&input);
+    }
+
+    // Data of clients predating present_fields sets every field
+    #[test]
+    fn test_set_fee_params_without_present_fields(
+        price_estimator in pubkey(),
+        company_wallet in pubkey(),
+        candidate_wallets in prop::array::uniform4(pubkey()),
+    ) {
+        let instruction = LockTokenInstruction::SetFeeParams {
+            price_estimator,
+            usd_token_address: Pubkey::new_unique(),
+            fees_in_usd: 1,
+            company_wallet,
+            candidate_wallets,
+            present_fields: FEE_PARAMS_ALL,
+        };
+        let mut input = instruction.pack();
+        input.pop();
+        prop_assert_eq!(LockTokenInstruction::unpack(&input).unwrap(), instruction);
+    }
+
+    #[test]
+    fn test_create_rejects_partial_schedule(instruction in create(), extra in 1..SCHEDULE_SIZE) {
+        let mut input = instruction.pack();
+        input.extend(std::iter::repeat(0).take(extra));
+        prop_assert_eq!(
+            LockTokenInstruction::unpack(&input),
+            Err(ProgramError::from(LockTokenError::InvalidInstruction))
+        );
+    }
+
+    #[test]
+    fn test_create_multi_rejects_partial_schedule(instruction in create_multi(), extra in 1..MULTI_SCHEDULE_SIZE) {
+        let mut input = instruction.pack();
+        input.extend(std::iter::repeat(0).take(extra));
+        prop_assert_eq!(
+            LockTokenInstruction::unpack(&input),
+            Err(ProgramError::from(LockTokenError::InvalidInstruction))
+        );
+    }
+
+    #[test]
+    fn test_create_rejects_too_many_split_destinations(
+        seeds in seeds(),
+        destination_token_address in pubkey(),
+        split in vec(pubkey(), MAX_SPLIT_DESTINATIONS + 1..=6),
+    ) {
+        let instruction = LockTokenInstruction::Create {
+            seeds,
+            mint_address: Pubkey::new_unique(),
+            destination_token_address,
+            schedules: vec![Schedule { release_time: 1, amount: 1 }],
+            deadline: 0,
+            max_total: Some(1),
+            is_nft: false,
+            is_immutable: false,
+            pay_fee_in_usd: false,
+            pay_fee_in_token: false,
+            with_fee_discount: false,
+            time_basis: TimeBasis::UnixTimestamp,
+            split_destinations: split
+                .into_iter()
+                .map(|destination_address| SplitDestination { destination_address, share_bps: 1 })
+                .collect(),
+            with_wallet_state: false,
+            min_claim_interval_secs: 0,
+        };
+        prop_assert_eq!(
+            LockTokenInstruction::unpack(&instruction.pack()),
+            Err(ProgramError::from(LockTokenError::InvalidInstruction))
+        );
+        #[cfg(feature = "borsh")]
+        prop_assert_eq!(
+            LockTokenInstruction::unpack(&instruction.pack_borsh()),
+            Err(ProgramError::from(LockTokenError::InvalidInstruction))
+        );
+    }
+}
+
+// Deployed clients only know the legacy format, which pack keeps producing
+#[test]
+fn test_legacy_format_is_default() {
+    let instruction = LockTokenInstruction::GetClaimableAmount { seeds: LockSeed([7; 32]) };
+    let data = instruction.pack();
+    assert_eq!(data[0], 36);
+    assert_eq!(data.len(), 33);
+    #[cfg(not(feature = "borsh"))]
+    assert_eq!(
+        LockTokenInstruction::unpack(&[BORSH_FORMAT]),
+        Err(ProgramError::from(LockTokenError::InvalidInstruction))
+    );
+}
+
+#[cfg(feature = "borsh")]
+#[test]
+fn test_borsh_rejects_what_legacy_can_not_encode() {
+    let instruction = LockTokenInstruction::CreateMulti {
+        seeds: LockSeed([1; 32]),
+        mint_address: Pubkey::new_unique(),
+        destination_token_address: Pubkey::new_unique(),
+        schedules: vec![
+            MultiSchedule {
+                release_time: 1,
+                amount: 1,
+                destination_token_address: Pubkey::default(),
+            };
+            lock_token::instruction::MAX_SCHEDULES as usize + 1
+        ],
+    };
+    assert_eq!(
+        LockTokenInstruction::unpack(&instruction.pack_borsh()),
+        Err(ProgramError::from(LockTokenError::InvalidInstruction))
+    );
+
+    let instruction = LockTokenInstruction::SetLockMetadata {
+        seeds: LockSeed([1; 32]),
+        name: "n".repeat(256),
+        uri: String::new(),
+    };
+    assert_eq!(
+        LockTokenInstruction::unpack(&instruction.pack_borsh()),
+        Err(ProgramError::from(LockTokenError::InvalidInstruction))
+    );
+
+    // Trailing bytes after the instruction
+    let mut data = LockTokenInstruction::AcceptOwnership {}.pack_borsh();
+    data.push(0);
+    assert_eq!(
+        LockTokenInstruction::unpack(&data),
+        Err(ProgramError::from(LockTokenError::InvalidInstruction))
+    );
+}
+
+// Create carries as many option bytes as it needs: the deadline, max_total, the flags, then the split destinations
+#[test]
+fn test_create_round_trips_every_options_length() {
+    let create = |max_total, is_immutable, split_destinations| LockTokenInstruction::Create {
+        seeds: LockSeed([1; 32]),
+        mint_address: Pubkey::new_unique(),
+        destination_token_address: Pubkey::new_unique(),
+        schedules: vec![Schedule { release_time: 1, amount: 2 }],
+        deadline: 3,
+        max_total,
+        is_nft: false,
+        is_immutable,
+        pay_fee_in_usd: false,
+        pay_fee_in_token: false,
+        with_fee_discount: false,
+        time_basis: TimeBasis::UnixTimestamp,
+        split_destinations,
+        with_wallet_state: false,
+        min_claim_interval_secs: 0,
+    };
+    let split = vec![SplitDestination { destination_address: Pubkey::new_unique(), share_bps: 10_000 }];
+    let cases = [
+        (create(None, false, Vec::new()), 8),
+        (create(Some(4), false, Vec::new()), 16),
+        (create(Some(4), true, Vec::new()), 17),
+        (create(Some(4), true, split), 18 + 34),
+    ];
+    for (instruction, options_len) in cases.iter() {
+        let data = instruction.pack();
+        // Tag, seeds, mint, destination and schedule count come before the options length
+        assert_eq!(data[1 + 32 * 3 + 2] as usize, *options_len);
+        assert_eq!(LockTokenInstruction::unpack(&data).unwrap(), *instruction);
+    }
+}
+
+// The legacy Create and Unlock encodings end before the deadline, which leaves them without one
+#[test]
+fn test_legacy_encodings_have_no_deadline() {
+    let seeds = LockSeed([1; 32]);
+    let mint_address = Pubkey::new_unique();
+    let destination_token_address = Pubkey::new_unique();
+    let mut data = vec![1];
+    data.extend_from_slice(seeds.as_ref());
+    data.extend_from_slice(&mint_address.to_bytes());
+    data.extend_from_slice(&destination_token_address.to_bytes());
+    data.extend_from_slice(&5u64.to_le_bytes());
+    data.extend_from_slice(&6u64.to_le_bytes());
+    match LockTokenInstruction::unpack(&data).unwrap() {
+        LockTokenInstruction::Create { deadline, schedules, max_total, .. } => {
+            assert_eq!(deadline, 0);
+            assert_eq!(max_total, None);
+            assert_eq!(schedules, vec![Schedule { release_time: 5, amount: 6 }]);
+        }
+        instruction => panic!("unexpected instruction {:?}", instruction),
+    }
+
+    let mut data = vec![2];
+    data.extend_from_slice(seeds.as_ref());
+    assert_eq!(LockTokenInstruction::unpack(&data).unwrap(), LockTokenInstruction::Unlock { seeds, deadline: 0 });
+    data.extend_from_slice(&7u64.to_le_bytes());
+    assert_eq!(LockTokenInstruction::unpack(&data).unwrap(), LockTokenInstruction::Unlock { seeds, deadline: 7 });
+}
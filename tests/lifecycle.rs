@@ -0,0 +1,437 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    client::{mint_lock_stats, MintLockStats},
+    error::LockTokenError,
+    instruction::{create, extend_lock_duration, migrate_lock, transfer_locks, CreateFeePayment, LockSeed, Schedule},
+    state::{token_state_address, unpack_schedules, LockSchedule, LockScheduleHeader, PAUSE_CREATE},
+    time::TimeBasis,
+    utils::{create_associated_token_account_with_program_id, get_associated_token_address_with_program_id},
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    signature::{Keypair, Signer},
+};
+
+struct Lock {
+    seeds: LockSeed,
+    locking_account: Pubkey,
+    locking_token_account: Pubkey,
+}
+
+// Init and Create locking the schedules from a token account of the payer, minted for the occasion
+async fn create_lock_instructions(env: &mut TestEnv, destination: &Pubkey, schedules: Vec<Schedule>) -> (Lock, [Instruction; 2]) {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let total_amount = schedules.iter().map(|s| s.amount).sum();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, total_amount).await;
+
+    let seeds = LockSeed::random(&env.program_id);
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    let locking_token_account = get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id());
+    let instructions = [
+        init_instruction(env, seeds, schedules.len() as u32),
+        create(
+            &env.program_id,
+            &spl_token::id(),
+            &system_program::id(),
+            &sysvar::rent::id(),
+            &spl_associated_token_account::id(),
+            &env.price_estimator,
+            &locking_account,
+            &locking_token_account,
+            &payer,
+            &source,
+            &payer,
+            destination,
+            &env.company_wallet,
+            &mint,
+            schedules,
+            seeds,
+            0,
+            false,
+            TimeBasis::UnixTimestamp,
+            Vec::new(),
+            0,
+            CreateFeePayment::Sol,
+            None,
+            false,
+            &[],
+        )
+        .unwrap(),
+    ];
+    let lock = Lock {
+        seeds,
+        locking_account,
+        locking_token_account,
+    };
+    (lock, instructions)
+}
+
+async fn create_lock(env: &mut TestEnv, destination: &Pubkey, schedules: Vec<Schedule>) -> Lock {
+    let (lock, instructions) = create_lock_instructions(env, destination, schedules).await;
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+    lock
+}
+
+async fn read_lock(env: &mut TestEnv, lock: &Lock) -> (LockScheduleHeader, Vec<LockSchedule>) {
+    let data = get_account(&mut env.context, &lock.locking_account).await.data;
+    let header_len = LockScheduleHeader::header_len(data.len());
+    (
+        LockScheduleHeader::unpack_from_slice(&data[..header_len]).unwrap(),
+        unpack_schedules(&data[header_len..]).unwrap(),
+    )
+}
+
+async fn read_mint_lock_stats(env: &mut TestEnv) -> MintLockStats {
+    let mint = env.mint.pubkey();
+    let token_state = token_state_address(&env.program_id, &mint).unwrap();
+    let data = get_account(&mut env.context, &token_state).await.data;
+    mint_lock_stats(&mint, &data).unwrap()
+}
+
+fn three_schedules(now: u64) -> Vec<Schedule> {
+    vec![
+        Schedule { release_time: now + 100, amount: 100 },
+        Schedule { release_time: now + 200, amount: 200 },
+        Schedule { release_time: now + 300, amount: 300 },
+    ]
+}
+
+#[tokio::test]
+async fn test_lock_lifecycle() {
+    let mut env = setup(true).await;
+    let mint = env.mint.pubkey();
+    let beneficiary = Keypair::new();
+    let destination = create_token_account(&mut env.context, &mint, &beneficiary.pubkey()).await;
+
+    let start = now(&mut env.context).await;
+    let lock = create_lock(&mut env, &destination, three_schedules(start)).await;
+    assert_eq!(token_balance(&mut env.context, &lock.locking_token_account).await, 600);
+    assert_eq!(
+        read_mint_lock_stats(&mut env).await,
+        MintLockStats { active_locks: 1, total_locked_amount: 600 }
+    );
+
+    // Nothing is released yet
+    let early_unlock = unlock_instruction(&env, lock.seeds, &destination);
+    assert_instruction_error(
+        process(&mut env.context, &[early_unlock], &[]).await,
+        InstructionError::InvalidArgument,
+    );
+
+    warp_to_timestamp(&mut env.context, start + 150).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &destination).await, 100);
+    assert_eq!(token_balance(&mut env.context, &lock.locking_token_account).await, 500);
+    let (header, schedules) = read_lock(&mut env, &lock).await;
+    assert_eq!(schedules[0].amount, 0);
+    assert_eq!(schedules[1].amount, 200);
+    assert_eq!(header.total_amount, 600);
+    assert_eq!(header.claimed_amount, 100);
+    assert_eq!(header.schedule_count, 3);
+    assert_eq!(header.unlocked_count, 1);
+    assert_eq!(
+        read_mint_lock_stats(&mut env).await,
+        MintLockStats { active_locks: 1, total_locked_amount: 500 }
+    );
+
+    let new_beneficiary = Keypair::new();
+    let new_destination = create_token_account(&mut env.context, &mint, &new_beneficiary.pubkey()).await;
+    let instruction = transfer_locks(
+        &env.program_id,
+        &lock.locking_account,
+        &beneficiary.pubkey(),
+        &destination,
+        &new_destination,
+        lock.seeds,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[&beneficiary]).await.unwrap();
+    let (header, _) = read_lock(&mut env, &lock).await;
+    assert_eq!(header.destination_address, new_destination);
+
+    let instruction = extend_lock_duration(
+        &env.program_id,
+        &lock.locking_account,
+        &new_beneficiary.pubkey(),
+        &new_destination,
+        lock.seeds,
+        1,
+        start + 250,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[&new_beneficiary]).await.unwrap();
+    let (_, schedules) = read_lock(&mut env, &lock).await;
+    assert_eq!(schedules[1].release_time, start + 250);
+
+    // The extended schedule is still locked at its former release time
+    warp_to_timestamp(&mut env.context, start + 220).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &new_destination);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::InvalidArgument,
+    );
+
+    warp_to_timestamp(&mut env.context, start + 260).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &new_destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &new_destination).await, 200);
+    assert_eq!(token_balance(&mut env.context, &lock.locking_token_account).await, 300);
+    let (header, _) = read_lock(&mut env, &lock).await;
+    assert_eq!(header.claimed_amount, 300);
+    assert_eq!(header.unlocked_count, 2);
+}
+
+#[tokio::test]
+async fn test_unlock_drained_lock() {
+    let mut env = setup(true).await;
+    let mint = env.mint.pubkey();
+    let beneficiary = Keypair::new();
+    let destination = create_token_account(&mut env.context, &mint, &beneficiary.pubkey()).await;
+
+    let start = now(&mut env.context).await;
+    let lock = create_lock(&mut env, &destination, three_schedules(start)).await;
+
+    warp_to_timestamp(&mut env.context, start + 300).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &destination).await, 600);
+    let (header, _) = read_lock(&mut env, &lock).await;
+    assert_eq!(header.unlocked_count, 3);
+    assert!(header.is_drained());
+
+    warp_to_timestamp(&mut env.context, start + 400).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &destination);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::Custom(LockTokenError::LockFullyUnlocked as u32),
+    );
+}
+
+// Clients built before Unlock read the clock with Clock::get pass the sysvar Clock account second
+#[tokio::test]
+async fn test_unlock_with_clock_sysvar() {
+    let mut env = setup(true).await;
+    let mint = env.mint.pubkey();
+    let beneficiary = Keypair::new();
+    let destination = create_token_account(&mut env.context, &mint, &beneficiary.pubkey()).await;
+
+    let start = now(&mut env.context).await;
+    let lock = create_lock(&mut env, &destination, three_schedules(start)).await;
+
+    warp_to_timestamp(&mut env.context, start + 150).await;
+    let mut instruction = unlock_instruction(&env, lock.seeds, &destination);
+    instruction.accounts.insert(1, AccountMeta::new_readonly(sysvar::clock::id(), false));
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &destination).await, 100);
+
+    warp_to_timestamp(&mut env.context, start + 250).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &destination).await, 300);
+}
+
+#[tokio::test]
+async fn test_unlock_to_wrong_destination() {
+    let mut env = setup(true).await;
+    let mint = env.mint.pubkey();
+    let beneficiary = Keypair::new();
+    let destination = create_token_account(&mut env.context, &mint, &beneficiary.pubkey()).await;
+    let other_destination = create_token_account(&mut env.context, &mint, &beneficiary.pubkey()).await;
+
+    let start = now(&mut env.context).await;
+    let lock = create_lock(&mut env, &destination, three_schedules(start)).await;
+
+    warp_to_timestamp(&mut env.context, start + 150).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &other_destination);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::InvalidArgument,
+    );
+    assert_eq!(token_balance(&mut env.context, &other_destination).await, 0);
+    assert_eq!(token_balance(&mut env.context, &lock.locking_token_account).await, 600);
+}
+
+// The destination was closed and a token account of another mint created at its address
+#[tokio::test]
+async fn test_unlock_to_recreated_destination() {
+    let mut env = setup(true).await;
+    let mint = env.mint.pubkey();
+    let beneficiary = Keypair::new();
+    let destination = Keypair::new();
+    create_token_account_at(&mut env.context, &destination, &mint, &beneficiary.pubkey()).await;
+
+    let start = now(&mut env.context).await;
+    let lock = create_lock(&mut env, &destination.pubkey(), three_schedules(start)).await;
+
+    let instruction = spl_token::instruction::close_account(
+        &spl_token::id(),
+        &destination.pubkey(),
+        &beneficiary.pubkey(),
+        &beneficiary.pubkey(),
+        &[],
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[&beneficiary]).await.unwrap();
+    let other_mint = Keypair::new();
+    create_mint(&mut env.context, &other_mint).await;
+    create_token_account_at(&mut env.context, &destination, &other_mint.pubkey(), &beneficiary.pubkey()).await;
+
+    warp_to_timestamp(&mut env.context, start + 150).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &destination.pubkey());
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::Custom(LockTokenError::DestinationMintMismatch as u32),
+    );
+    assert_eq!(token_balance(&mut env.context, &lock.locking_token_account).await, 600);
+}
+
+#[tokio::test]
+async fn test_unlock_frozen_escrow() {
+    let mut env = setup(true).await;
+    let mint = env.mint.pubkey();
+    let beneficiary = Keypair::new();
+    let destination = create_token_account(&mut env.context, &mint, &beneficiary.pubkey()).await;
+
+    let start = now(&mut env.context).await;
+    let lock = create_lock(&mut env, &destination, three_schedules(start)).await;
+    freeze_account(&mut env.context, &mint, &lock.locking_token_account).await;
+
+    warp_to_timestamp(&mut env.context, start + 150).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &destination);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::Custom(LockTokenError::EscrowFrozen as u32),
+    );
+    assert_eq!(token_balance(&mut env.context, &lock.locking_token_account).await, 600);
+}
+
+#[tokio::test]
+async fn test_create_into_frozen_escrow() {
+    let mut env = setup(true).await;
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+
+    let start = now(&mut env.context).await;
+    let (lock, [init, create_instruction]) = create_lock_instructions(&mut env, &destination, three_schedules(start)).await;
+    let create_vault =
+        create_associated_token_account_with_program_id(&payer, &lock.locking_account, &mint, &spl_token::id());
+    process(&mut env.context, &[init, create_vault], &[]).await.unwrap();
+    freeze_account(&mut env.context, &mint, &lock.locking_token_account).await;
+
+    assert_instruction_error(
+        process(&mut env.context, &[create_instruction], &[]).await,
+        InstructionError::Custom(LockTokenError::EscrowFrozen as u32),
+    );
+}
+
+#[tokio::test]
+async fn test_pause_and_unpause() {
+    let mut env = setup(true).await;
+
+    let instruction = pause_contract(&env.program_id, &env.admin.pubkey(), PAUSE_CREATE);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+
+    let instruction = init_instruction(&env, LockSeed::random(&env.program_id), 1);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::Custom(LockTokenError::ContractPaused as u32),
+    );
+
+    let instruction = pause_contract(&env.program_id, &env.admin.pubkey(), 0);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+
+    let instruction = init_instruction(&env, LockSeed::random(&env.program_id), 1);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_uninitialized_state() {
+    let mut env = setup(false).await;
+
+    let instruction = init_instruction(&env, LockSeed::random(&env.program_id), 1);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::Custom(LockTokenError::GlobalStateUninitialized as u32),
+    );
+}
+
+#[tokio::test]
+async fn test_migrate_legacy_lock() {
+    let mut env = setup(true).await;
+    let mint = env.mint.pubkey();
+    let beneficiary = Keypair::new();
+    let destination = create_token_account(&mut env.context, &mint, &beneficiary.pubkey()).await;
+
+    let start = now(&mut env.context).await;
+    let lock = create_lock(&mut env, &destination, three_schedules(start)).await;
+
+    // Rewrite the lock as it was stored before pending transfers and totals were added
+    let account = get_account(&mut env.context, &lock.locking_account).await;
+    let mut legacy_data = account.data[..LockScheduleHeader::LEGACY_LEN].to_vec();
+    legacy_data.extend_from_slice(&account.data[LockScheduleHeader::LEN..]);
+    let rent = env.context.banks_client.get_rent().await.unwrap();
+    let legacy_account = Account {
+        lamports: rent.minimum_balance(legacy_data.len()),
+        data: legacy_data,
+        owner: env.program_id,
+        executable: false,
+        rent_epoch: 0,
+    };
+    env.context.set_account(&lock.locking_account, &AccountSharedData::from(legacy_account));
+
+    warp_to_timestamp(&mut env.context, start + 150).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &destination).await, 100);
+    let (header, schedules) = read_lock(&mut env, &lock).await;
+    assert_eq!(header.total_amount, 0);
+    assert_eq!(schedules.len(), 3);
+    assert_eq!(schedules[0].amount, 0);
+
+    let payer = env.context.payer.pubkey();
+    let instruction = migrate_lock(&env.program_id, &system_program::id(), &payer, &lock.locking_account, lock.seeds).unwrap();
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    let account = get_account(&mut env.context, &lock.locking_account).await;
+    assert_eq!(account.data.len(), LockScheduleHeader::LEN + 3 * LockSchedule::LEN);
+    let (header, schedules) = read_lock(&mut env, &lock).await;
+    assert_eq!(header.destination_address, destination);
+    assert_eq!(header.mint_address, mint);
+    assert_eq!(header.total_amount, 500);
+    assert_eq!(header.claimed_amount, 0);
+    assert_eq!(header.schedule_count, 3);
+    assert_eq!(header.unlocked_count, 1);
+    // Neither was recorded by the legacy layout
+    assert_eq!(header.creator_address, Pubkey::default());
+    assert_eq!(header.created_at, 0);
+    assert_eq!(schedules[1].release_time, start + 200);
+    assert_eq!(schedules[2].amount, 300);
+
+    // Migrating twice is rejected
+    let instruction = migrate_lock(&env.program_id, &system_program::id(), &payer, &lock.locking_account, lock.seeds).unwrap();
+    warp_to_timestamp(&mut env.context, start + 250).await;
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::InvalidArgument,
+    );
+
+    let instruction = unlock_instruction(&env, lock.seeds, &destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &destination).await, 300);
+    let (header, _) = read_lock(&mut env, &lock).await;
+    assert_eq!(header.claimed_amount, 200);
+    assert_eq!(header.unlocked_count, 2);
+}
@@ -0,0 +1,169 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    client::unpack_lock_header,
+    instruction::{
+        accept_transfer_locks, approve_lock_delegate, propose_transfer_locks, revoke_lock_delegate, transfer_locks, Schedule,
+    },
+};
+use solana_program::{instruction::InstructionError, pubkey::Pubkey};
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+async fn create_lock(env: &mut TestEnv) -> TestLock {
+    let start = now(&mut env.context).await;
+    let schedules = vec![
+        Schedule { release_time: start + 100, amount: 100 },
+        Schedule { release_time: start + 200, amount: 200 },
+    ];
+    create_test_lock(env, schedules).await
+}
+
+async fn approve(env: &mut TestEnv, lock: &TestLock, delegate: &Pubkey) {
+    let instruction = approve_lock_delegate(
+        &env.program_id,
+        &lock.locking_account,
+        &lock.beneficiary.pubkey(),
+        &lock.destination,
+        lock.seeds,
+        delegate,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[&lock.beneficiary]).await.unwrap();
+}
+
+// Moves the lock to a new token account of a fresh owner, signed by the given signer
+async fn transfer(
+    env: &mut TestEnv,
+    lock: &TestLock,
+    destination: &Pubkey,
+    signer: &Keypair,
+) -> (Pubkey, Result<(), TransactionError>) {
+    let new_destination = create_token_account(&mut env.context, &env.mint.pubkey(), &Keypair::new().pubkey()).await;
+    let instruction = transfer_locks(
+        &env.program_id,
+        &lock.locking_account,
+        &signer.pubkey(),
+        destination,
+        &new_destination,
+        lock.seeds,
+    )
+    .unwrap();
+    (new_destination, process(&mut env.context, &[instruction], &[signer]).await)
+}
+
+async fn delegate_address(env: &mut TestEnv, lock: &TestLock) -> Pubkey {
+    let data = get_account(&mut env.context, &lock.locking_account).await.data;
+    unpack_lock_header(&data).unwrap().delegate_address
+}
+
+#[tokio::test]
+async fn test_delegate_transfers_lock() {
+    let mut env = setup(true).await;
+    let lock = create_lock(&mut env).await;
+    let delegate = Keypair::new();
+    approve(&mut env, &lock, &delegate.pubkey()).await;
+    assert_eq!(delegate_address(&mut env, &lock).await, delegate.pubkey());
+
+    let (new_destination, result) = transfer(&mut env, &lock, &lock.destination, &delegate).await;
+    result.unwrap();
+    let data = get_account(&mut env.context, &lock.locking_account).await.data;
+    let header = unpack_lock_header(&data).unwrap();
+    assert_eq!(header.destination_address, new_destination);
+    // The delegate of the previous beneficiary can not move the lock again
+    assert_eq!(header.delegate_address, Pubkey::default());
+
+    refresh_blockhash(&mut env.context).await;
+    let (_, result) = transfer(&mut env, &lock, &new_destination, &delegate).await;
+    assert_instruction_error(result, InstructionError::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_revoked_delegate_can_not_transfer_lock() {
+    let mut env = setup(true).await;
+    let lock = create_lock(&mut env).await;
+    let delegate = Keypair::new();
+    approve(&mut env, &lock, &delegate.pubkey()).await;
+
+    let instruction = revoke_lock_delegate(
+        &env.program_id,
+        &lock.locking_account,
+        &lock.beneficiary.pubkey(),
+        &lock.destination,
+        lock.seeds,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction.clone()], &[&lock.beneficiary]).await.unwrap();
+    assert_eq!(delegate_address(&mut env, &lock).await, Pubkey::default());
+
+    let (_, result) = transfer(&mut env, &lock, &lock.destination, &delegate).await;
+    assert_instruction_error(result, InstructionError::InvalidArgument);
+
+    // There is nothing left to revoke
+    refresh_blockhash(&mut env.context).await;
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&lock.beneficiary]).await,
+        InstructionError::InvalidArgument,
+    );
+}
+
+#[tokio::test]
+async fn test_delegate_is_cleared_when_destination_changes() {
+    let mut env = setup(true).await;
+    let lock = create_lock(&mut env).await;
+    let delegate = Keypair::new();
+    approve(&mut env, &lock, &delegate.pubkey()).await;
+
+    // Moved by the beneficiary
+    let (new_destination, result) = transfer(&mut env, &lock, &lock.destination, &lock.beneficiary).await;
+    result.unwrap();
+    assert_eq!(delegate_address(&mut env, &lock).await, Pubkey::default());
+    let (_, result) = transfer(&mut env, &lock, &new_destination, &delegate).await;
+    assert_instruction_error(result, InstructionError::InvalidArgument);
+
+    // Moved by accepting a proposed transfer
+    let lock = create_lock(&mut env).await;
+    approve(&mut env, &lock, &delegate.pubkey()).await;
+    let new_owner = Keypair::new();
+    let new_destination = create_token_account(&mut env.context, &env.mint.pubkey(), &new_owner.pubkey()).await;
+    let instructions = [
+        propose_transfer_locks(
+            &env.program_id,
+            &lock.locking_account,
+            &lock.beneficiary.pubkey(),
+            &lock.destination,
+            &new_destination,
+            lock.seeds,
+        )
+        .unwrap(),
+        accept_transfer_locks(&env.program_id, &lock.locking_account, &new_owner.pubkey(), &new_destination, lock.seeds)
+            .unwrap(),
+    ];
+    process(&mut env.context, &instructions, &[&lock.beneficiary, &new_owner]).await.unwrap();
+    assert_eq!(delegate_address(&mut env, &lock).await, Pubkey::default());
+}
+
+#[tokio::test]
+async fn test_approve_lock_delegate_requires_beneficiary() {
+    let mut env = setup(true).await;
+    let lock = create_lock(&mut env).await;
+    let impostor = Keypair::new();
+    let instruction = approve_lock_delegate(
+        &env.program_id,
+        &lock.locking_account,
+        &impostor.pubkey(),
+        &lock.destination,
+        lock.seeds,
+        &impostor.pubkey(),
+    )
+    .unwrap();
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&impostor]).await,
+        InstructionError::InvalidArgument,
+    );
+}
@@ -0,0 +1,78 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    client::{lock_info, LockInfo},
+    instruction::{migrate_lock, Schedule},
+    state::LockScheduleHeader,
+};
+use solana_program::{program_pack::Pack, system_program};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    signature::Signer,
+};
+
+// Locks 300 of the context payer's tokens released at start + 100
+async fn create_lock(env: &mut TestEnv, start: u64) -> TestLock {
+    create_test_lock(env, vec![Schedule { release_time: start + 100, amount: 300 }]).await
+}
+
+#[tokio::test]
+async fn test_create_records_creator_and_creation_time() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let lock = create_lock(&mut env, start).await;
+
+    let data = get_account(&mut env.context, &lock.locking_account).await.data;
+    let info = lock_info(&data).unwrap();
+    assert_eq!(
+        info,
+        LockInfo {
+            mint: env.mint.pubkey(),
+            destination: lock.destination,
+            creator: env.context.payer.pubkey(),
+            created_at: info.created_at,
+            total_amount: 300,
+            claimed_amount: 0,
+        }
+    );
+    assert!(info.created_at >= start as i64);
+    assert!(info.created_at <= now(&mut env.context).await as i64);
+}
+
+#[tokio::test]
+async fn test_migration_keeps_the_creator_of_the_previous_layout() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let lock = create_lock(&mut env, start).await;
+
+    // Rewrite the lock as it was stored before the creation time was recorded
+    let account = get_account(&mut env.context, &lock.locking_account).await;
+    let mut v9_data = account.data[..LockScheduleHeader::V9_LEN].to_vec();
+    v9_data[331] = 9;
+    v9_data.extend_from_slice(&account.data[LockScheduleHeader::LEN..]);
+    let rent = env.context.banks_client.get_rent().await.unwrap();
+    let v9_account = Account {
+        lamports: rent.minimum_balance(v9_data.len()),
+        data: v9_data,
+        owner: env.program_id,
+        executable: false,
+        rent_epoch: 0,
+    };
+    env.context.set_account(&lock.locking_account, &AccountSharedData::from(v9_account));
+    let data = get_account(&mut env.context, &lock.locking_account).await.data;
+    assert_eq!(lock_info(&data).unwrap().created_at, 0);
+
+    let payer = env.context.payer.pubkey();
+    let instruction = migrate_lock(&env.program_id, &system_program::id(), &payer, &lock.locking_account, lock.seeds).unwrap();
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    let data = get_account(&mut env.context, &lock.locking_account).await.data;
+    assert_eq!(LockScheduleHeader::header_len(data.len()), LockScheduleHeader::LEN);
+    let info = lock_info(&data).unwrap();
+    assert_eq!(info.creator, payer);
+    assert_eq!(info.created_at, 0);
+    assert_eq!(info.total_amount, 300);
+}
@@ -0,0 +1,163 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    client::unpack_lock_metadata,
+    error::LockTokenError,
+    instruction::{close_lock_metadata, set_lock_metadata, LockSeed, Schedule},
+    state::{find_lock_metadata_address, LockMetadata, MAX_LOCK_NAME_LEN, MAX_LOCK_URI_LEN},
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_sdk::signature::{Keypair, Signer};
+
+struct Lock {
+    seeds: LockSeed,
+    locking_account: Pubkey,
+    lock_metadata: Pubkey,
+    destination: Pubkey,
+    creator: Keypair,
+    beneficiary: Keypair,
+}
+
+// Locks 100 tokens of the creator for the beneficiary
+async fn create_lock(env: &mut TestEnv) -> Lock {
+    let mint = env.mint.pubkey();
+    let creator = Keypair::new();
+    let source = create_token_account(&mut env.context, &mint, &creator.pubkey()).await;
+    mint_to(&mut env.context, &mint, &source, 100).await;
+    let beneficiary = Keypair::new();
+    let destination = create_token_account(&mut env.context, &mint, &beneficiary.pubkey()).await;
+
+    let start = now(&mut env.context).await;
+    let schedules = vec![Schedule { release_time: start + 100, amount: 100 }];
+    let seeds = LockSeed::random(&env.program_id);
+    let instructions = [
+        init_instruction(env, seeds, 1),
+        create_lock_instruction(env, seeds, &creator.pubkey(), &source, &destination, schedules, false, &[]),
+    ];
+    process(&mut env.context, &instructions, &[&creator]).await.unwrap();
+
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    Lock {
+        seeds,
+        locking_account,
+        lock_metadata: find_lock_metadata_address(&env.program_id, &locking_account).0,
+        destination,
+        creator,
+        beneficiary,
+    }
+}
+
+fn set_instruction(env: &TestEnv, lock: &Lock, authority: &Pubkey, name: &str, uri: &str) -> Instruction {
+    set_lock_metadata(
+        &env.program_id,
+        &lock.locking_account,
+        &lock.destination,
+        authority,
+        &env.context.payer.pubkey(),
+        &system_program::id(),
+        lock.seeds,
+        name,
+        uri,
+    )
+    .unwrap()
+}
+
+fn close_instruction(env: &TestEnv, lock: &Lock, authority: &Pubkey, receiver: &Pubkey) -> Instruction {
+    close_lock_metadata(
+        &env.program_id,
+        &lock.locking_account,
+        &lock.destination,
+        authority,
+        receiver,
+        lock.seeds,
+    )
+    .unwrap()
+}
+
+async fn lock_metadata(env: &mut TestEnv, lock: &Lock) -> LockMetadata {
+    unpack_lock_metadata(&get_account(&mut env.context, &lock.lock_metadata).await.data).unwrap()
+}
+
+#[tokio::test]
+async fn test_creator_and_beneficiary_set_metadata() {
+    let mut env = setup(true).await;
+    let lock = create_lock(&mut env).await;
+
+    let instruction = set_instruction(&env, &lock, &lock.creator.pubkey(), "Team", "https://example.com/team.json");
+    process(&mut env.context, &[instruction], &[&lock.creator]).await.unwrap();
+    let metadata = lock_metadata(&mut env, &lock).await;
+    assert_eq!(metadata.locking_account, lock.locking_account);
+    assert_eq!(metadata.name, "Team");
+    assert_eq!(metadata.uri, "https://example.com/team.json");
+
+    // The beneficiary overwrites both, a shorter URI leaves nothing of the previous one
+    let instruction = set_instruction(&env, &lock, &lock.beneficiary.pubkey(), "Advisors", "ipfs://a");
+    process(&mut env.context, &[instruction], &[&lock.beneficiary]).await.unwrap();
+    let metadata = lock_metadata(&mut env, &lock).await;
+    assert_eq!(metadata.name, "Advisors");
+    assert_eq!(metadata.uri, "ipfs://a");
+}
+
+#[tokio::test]
+async fn test_stranger_can_not_set_metadata() {
+    let mut env = setup(true).await;
+    let lock = create_lock(&mut env).await;
+    let stranger = Keypair::new();
+
+    let instruction = set_instruction(&env, &lock, &stranger.pubkey(), "Team", "");
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&stranger]).await,
+        InstructionError::InvalidArgument,
+    );
+    assert!(env.context.banks_client.get_account(lock.lock_metadata).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_set_metadata_rejects_long_strings() {
+    let mut env = setup(true).await;
+    let lock = create_lock(&mut env).await;
+
+    let name = "n".repeat(MAX_LOCK_NAME_LEN + 1);
+    let instruction = set_instruction(&env, &lock, &lock.creator.pubkey(), &name, "");
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&lock.creator]).await,
+        InstructionError::Custom(LockTokenError::LockNameTooLong as u32),
+    );
+
+    let uri = "u".repeat(MAX_LOCK_URI_LEN + 1);
+    let instruction = set_instruction(&env, &lock, &lock.creator.pubkey(), "Team", &uri);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&lock.creator]).await,
+        InstructionError::Custom(LockTokenError::LockUriTooLong as u32),
+    );
+}
+
+#[tokio::test]
+async fn test_close_metadata_reclaims_rent() {
+    let mut env = setup(true).await;
+    let lock = create_lock(&mut env).await;
+    let instruction = set_instruction(&env, &lock, &lock.creator.pubkey(), "Team", "ipfs://team");
+    process(&mut env.context, &[instruction], &[&lock.creator]).await.unwrap();
+    let rent = get_account(&mut env.context, &lock.lock_metadata).await.lamports;
+    assert_eq!(get_account(&mut env.context, &lock.lock_metadata).await.data.len(), LockMetadata::LEN);
+
+    let receiver = Keypair::new().pubkey();
+    let instruction = close_instruction(&env, &lock, &lock.beneficiary.pubkey(), &receiver);
+    process(&mut env.context, &[instruction], &[&lock.beneficiary]).await.unwrap();
+    assert!(env.context.banks_client.get_account(lock.lock_metadata).await.unwrap().is_none());
+    assert_eq!(get_account(&mut env.context, &receiver).await.lamports, rent);
+
+    // The lock can be described again afterwards
+    refresh_blockhash(&mut env.context).await;
+    let instruction = set_instruction(&env, &lock, &lock.creator.pubkey(), "Team", "ipfs://team");
+    process(&mut env.context, &[instruction], &[&lock.creator]).await.unwrap();
+    assert_eq!(lock_metadata(&mut env, &lock).await.uri, "ipfs://team");
+}
@@ -0,0 +1,78 @@
+use lock_token::{
+    client::unpack_lock_metadata,
+    error::LockTokenError,
+    state::{LockMetadata, MAX_LOCK_NAME_LEN, MAX_LOCK_URI_LEN},
+};
+use proptest::prelude::*;
+use solana_program::{program_error::ProgramError, program_pack::Pack, pubkey::Pubkey};
+
+fn pack(metadata: LockMetadata) -> Vec<u8> {
+    let mut data = vec![0; LockMetadata::LEN];
+    LockMetadata::pack(metadata, &mut data).unwrap();
+    data
+}
+
+#[test]
+fn test_longest_name_and_uri() {
+    let metadata = LockMetadata {
+        locking_account: Pubkey::new_unique(),
+        name: "n".repeat(MAX_LOCK_NAME_LEN),
+        uri: "u".repeat(MAX_LOCK_URI_LEN),
+        is_initialized: true,
+    };
+    assert_eq!(LockMetadata::assert_valid_lengths(&metadata.name, &metadata.uri), Ok(()));
+    assert_eq!(unpack_lock_metadata(&pack(metadata.clone())).unwrap(), metadata);
+}
+
+#[test]
+fn test_too_long() {
+    let name = "n".repeat(MAX_LOCK_NAME_LEN + 1);
+    let uri = "u".repeat(MAX_LOCK_URI_LEN + 1);
+    assert_eq!(
+        LockMetadata::assert_valid_lengths(&name, ""),
+        Err(LockTokenError::LockNameTooLong.into())
+    );
+    assert_eq!(
+        LockMetadata::assert_valid_lengths("", &uri),
+        Err(LockTokenError::LockUriTooLong.into())
+    );
+}
+
+#[test]
+fn test_unpack_rejects_bad_data() {
+    assert_eq!(
+        unpack_lock_metadata(&[0; LockMetadata::LEN]),
+        Err(ProgramError::UninitializedAccount)
+    );
+    assert_eq!(
+        unpack_lock_metadata(&[0; LockMetadata::LEN - 1]),
+        Err(ProgramError::InvalidAccountData)
+    );
+
+    let metadata = LockMetadata {
+        locking_account: Pubkey::new_unique(),
+        name: "Team".to_string(),
+        uri: String::new(),
+        is_initialized: true,
+    };
+    // A name length past the maximum
+    let mut data = pack(metadata.clone());
+    data[32] = MAX_LOCK_NAME_LEN as u8 + 1;
+    assert_eq!(unpack_lock_metadata(&data), Err(ProgramError::InvalidAccountData));
+    // A name which is not UTF-8
+    let mut data = pack(metadata);
+    data[33] = 0xff;
+    assert_eq!(unpack_lock_metadata(&data), Err(ProgramError::InvalidAccountData));
+}
+
+proptest! {
+    #[test]
+    fn test_pack_unpack(
+        locking_account in any::<[u8; 32]>().prop_map(Pubkey::new_from_array),
+        name in "\\PC{0,8}",
+        uri in "\\PC{0,50}",
+    ) {
+        let metadata = LockMetadata { locking_account, name, uri, is_initialized: true };
+        prop_assert_eq!(unpack_lock_metadata(&pack(metadata.clone())).unwrap(), metadata);
+    }
+}
@@ -0,0 +1,212 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    error::LockTokenError,
+    instruction::{extend_lock_duration, set_max_lock_duration, LockSeed, Schedule},
+    state::{find_global_state_address, LockGlobalState, GLOBAL_STATE_VERSION},
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+const MAX_LOCK_DURATION_SECS: u64 = 1_000;
+
+struct Lock {
+    seeds: LockSeed,
+    locking_account: Pubkey,
+    destination: Pubkey,
+    beneficiary: Keypair,
+}
+
+async fn set_max(env: &mut TestEnv, max_lock_duration_secs: u64) -> Result<(), TransactionError> {
+    let instruction = set_max_lock_duration(
+        &env.program_id,
+        &system_program::id(),
+        &env.admin.pubkey(),
+        &env.context.payer.pubkey(),
+        max_lock_duration_secs,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[&env.admin]).await
+}
+
+async fn global_state_account(env: &mut TestEnv) -> Account {
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    get_account(&mut env.context, &program_state_account_key).await
+}
+
+fn set_global_state(env: &mut TestEnv, data: Vec<u8>) {
+    let account = Account {
+        lamports: 1_000_000_000,
+        data,
+        owner: env.program_id,
+        executable: false,
+        rent_epoch: 0,
+    };
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    env.context.set_account(&program_state_account_key, &AccountSharedData::from(account));
+}
+
+fn assert_duration_too_long(result: Result<(), TransactionError>) {
+    assert_instruction_error(result, InstructionError::Custom(LockTokenError::DurationTooLong as u32));
+}
+
+// Inits a lock of 300 of the context payer's tokens, released to a fresh beneficiary, and returns its Create
+async fn init_lock(env: &mut TestEnv, schedules: Vec<Schedule>) -> (Lock, Instruction) {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 300).await;
+    let beneficiary = Keypair::new();
+    let destination = create_token_account(&mut env.context, &mint, &beneficiary.pubkey()).await;
+
+    let seeds = LockSeed::random(&env.program_id);
+    let instruction = init_instruction(env, seeds, schedules.len() as u32);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    let instruction = create_lock_instruction(env, seeds, &payer, &source, &destination, schedules, false, &[]);
+    let lock = Lock {
+        seeds,
+        locking_account: seeds.locking_account_address(&env.program_id).unwrap(),
+        destination,
+        beneficiary,
+    };
+    (lock, instruction)
+}
+
+async fn extend(env: &mut TestEnv, lock: &Lock, index: u32, release_time: u64) -> Result<(), TransactionError> {
+    let instruction = extend_lock_duration(
+        &env.program_id,
+        &lock.locking_account,
+        &lock.beneficiary.pubkey(),
+        &lock.destination,
+        lock.seeds,
+        index,
+        release_time,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[&lock.beneficiary]).await
+}
+
+#[tokio::test]
+async fn test_set_max_lock_duration() {
+    let mut env = setup(true).await;
+    set_max(&mut env, MAX_LOCK_DURATION_SECS).await.unwrap();
+    let account = global_state_account(&mut env).await;
+    let state = LockGlobalState::unpack_from_slice(&account.data).unwrap();
+    assert_eq!(state.max_lock_duration_secs, MAX_LOCK_DURATION_SECS);
+
+    let impostor = Keypair::new();
+    let instruction = set_max_lock_duration(
+        &env.program_id,
+        &system_program::id(),
+        &impostor.pubkey(),
+        &env.context.payer.pubkey(),
+        0,
+    )
+    .unwrap();
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&impostor]).await,
+        InstructionError::Custom(LockTokenError::NotAdmin as u32),
+    );
+}
+
+#[tokio::test]
+async fn test_max_lock_duration_below_minimum_fails() {
+    let mut env = setup(true).await;
+    let mut state = global_state(&env.admin.pubkey(), &env.company_wallet, &env.price_estimator);
+    state.min_lock_duration_secs = 100;
+    let data = program_account(&env.program_id, &state).data;
+    set_global_state(&mut env, data);
+
+    assert_instruction_error(set_max(&mut env, 99).await, InstructionError::InvalidArgument);
+    set_max(&mut env, 100).await.unwrap();
+    // Zero removes the maximum whatever the minimum
+    set_max(&mut env, 0).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_create_beyond_max_lock_duration_fails() {
+    let mut env = setup(true).await;
+    set_max(&mut env, MAX_LOCK_DURATION_SECS).await.unwrap();
+    let start = now(&mut env.context).await;
+
+    let schedules = vec![
+        Schedule { release_time: start + 100, amount: 100 },
+        Schedule { release_time: start + 2 * MAX_LOCK_DURATION_SECS, amount: 200 },
+    ];
+    let (_, instruction) = init_lock(&mut env, schedules).await;
+    assert_duration_too_long(process(&mut env.context, &[instruction], &[]).await);
+
+    let schedules = vec![
+        Schedule { release_time: start + 100, amount: 100 },
+        Schedule { release_time: start + MAX_LOCK_DURATION_SECS / 2, amount: 200 },
+    ];
+    let (_, instruction) = init_lock(&mut env, schedules).await;
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_extend_beyond_max_lock_duration_fails() {
+    let mut env = setup(true).await;
+    set_max(&mut env, MAX_LOCK_DURATION_SECS).await.unwrap();
+    let start = now(&mut env.context).await;
+    let schedules = vec![
+        Schedule { release_time: start + 100, amount: 100 },
+        Schedule { release_time: start + 200, amount: 200 },
+    ];
+    let (lock, instruction) = init_lock(&mut env, schedules).await;
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    assert_duration_too_long(extend(&mut env, &lock, 1, start + 2 * MAX_LOCK_DURATION_SECS).await);
+    extend(&mut env, &lock, 1, start + MAX_LOCK_DURATION_SECS / 2).await.unwrap();
+
+    // The extension is still checked against the ordering of the schedules
+    assert_instruction_error(
+        extend(&mut env, &lock, 0, start + MAX_LOCK_DURATION_SECS / 2 + 1).await,
+        InstructionError::Custom(LockTokenError::WouldViolateOrdering as u32),
+    );
+}
+
+#[tokio::test]
+async fn test_zero_max_lock_duration_is_uncapped() {
+    let mut env = setup(true).await;
+    set_max(&mut env, MAX_LOCK_DURATION_SECS).await.unwrap();
+    set_max(&mut env, 0).await.unwrap();
+    let start = now(&mut env.context).await;
+    let schedules = vec![Schedule { release_time: start + 100, amount: 300 }];
+    let (lock, instruction) = init_lock(&mut env, schedules).await;
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    extend(&mut env, &lock, 0, start + 100 * MAX_LOCK_DURATION_SECS).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_set_max_lock_duration_grows_version_4_state() {
+    let mut env = setup(true).await;
+    let state = global_state(&env.admin.pubkey(), &env.company_wallet, &env.price_estimator);
+    let mut data = vec![0; LockGlobalState::V4_LEN];
+    data[384] = 4;
+    state.pack_into_slice(&mut data);
+    set_global_state(&mut env, data);
+    let account = global_state_account(&mut env).await;
+    assert_eq!(LockGlobalState::unpack_from_slice(&account.data).unwrap(), state);
+
+    set_max(&mut env, MAX_LOCK_DURATION_SECS).await.unwrap();
+    let account = global_state_account(&mut env).await;
+    assert_eq!(account.data.len(), LockGlobalState::LEN);
+    assert_eq!(account.data[568], GLOBAL_STATE_VERSION);
+    let unpacked = LockGlobalState::unpack_from_slice(&account.data).unwrap();
+    assert_eq!(unpacked.max_lock_duration_secs, MAX_LOCK_DURATION_SECS);
+    assert_eq!(unpacked.admin, state.admin);
+}
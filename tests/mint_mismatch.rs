@@ -0,0 +1,67 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::instruction::{LockSeed, Schedule};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+};
+use solana_sdk::{signature::Signer, transaction::TransactionError};
+
+// Swaps the mint account the instruction passes for another one, leaving its data untouched
+fn with_mint_account(mut instruction: Instruction, mint: &Pubkey, other_mint: &Pubkey) -> Instruction {
+    let account = instruction.accounts.iter_mut().find(|account| account.pubkey == *mint).unwrap();
+    account.pubkey = *other_mint;
+    instruction
+}
+
+async fn create_other_mint(env: &mut TestEnv) -> Pubkey {
+    let other_mint = mint_keypair(&env.program_id);
+    create_mint(&mut env.context, &other_mint).await;
+    other_mint.pubkey()
+}
+
+// The mint account passed to Create has to be the mint the instruction locks
+#[tokio::test]
+async fn test_create_rejects_a_mismatched_mint_account() {
+    let mut env = setup(true).await;
+    let mint = env.mint.pubkey();
+    let other_mint = create_other_mint(&mut env).await;
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 10).await;
+    let destination = create_token_account(&mut env.context, &mint, &Pubkey::new_unique()).await;
+
+    let start = now(&mut env.context).await;
+    let seeds = LockSeed::random(&env.program_id);
+    let schedules = vec![Schedule { release_time: start + 100, amount: 10 }];
+    let create = create_lock_instruction(&env, seeds, &payer, &source, &destination, schedules, false, &[]);
+    let instructions = [init_instruction(&env, seeds, 1), with_mint_account(create, &mint, &other_mint)];
+    assert_eq!(
+        process(&mut env.context, &instructions, &[]).await.unwrap_err(),
+        TransactionError::InstructionError(1, InstructionError::InvalidArgument)
+    );
+    assert_eq!(token_balance(&mut env.context, &source).await, 10);
+}
+
+// The mint account passed to Unlock has to be the mint of the lock
+#[tokio::test]
+async fn test_unlock_rejects_a_mismatched_mint_account() {
+    let mut env = setup(true).await;
+    let mint = env.mint.pubkey();
+    let other_mint = create_other_mint(&mut env).await;
+    let start = now(&mut env.context).await;
+    let lock = create_test_lock(&mut env, vec![Schedule { release_time: start + 100, amount: 10 }]).await;
+
+    warp_to_timestamp(&mut env.context, start + 100).await;
+    let unlock = unlock_instruction(&env, lock.seeds, &lock.destination);
+    let instruction = with_mint_account(unlock, &mint, &other_mint);
+    assert_instruction_error(process(&mut env.context, &[instruction], &[]).await, InstructionError::InvalidArgument);
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 0);
+
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 10);
+}
@@ -0,0 +1,175 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    client::unpack_lock_header,
+    error::LockTokenError,
+    instruction::{create_multi, extend_lock_duration, LockSeed, MultiSchedule},
+    state::MULTI_SCHEDULE_SLOTS,
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_program::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    system_program,
+    sysvar,
+};
+use solana_sdk::signature::{Keypair, Signer};
+
+struct MultiLock {
+    seeds: LockSeed,
+    locking_account: Pubkey,
+    locking_token_account: Pubkey,
+    // Destination of the lock and the owner signing for it
+    destination: Pubkey,
+    beneficiary: Keypair,
+    // Destinations of the second and third schedules
+    second_destination: Pubkey,
+    third_destination: Pubkey,
+    start: u64,
+}
+
+// Locks 600 of the context payer's tokens: 100 to the destination of the lock and 200 to the second destination
+// at start + 100, and 300 to the third destination at start + 200
+async fn create_multi_lock(env: &mut TestEnv) -> MultiLock {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 600).await;
+    let beneficiary = Keypair::new();
+    let destination = create_token_account(&mut env.context, &mint, &beneficiary.pubkey()).await;
+    let second_destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+    let third_destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+
+    let start = now(&mut env.context).await;
+    let schedules = vec![
+        MultiSchedule {
+            release_time: start + 100,
+            amount: 100,
+            destination_token_address: Pubkey::default(),
+        },
+        MultiSchedule {
+            release_time: start + 100,
+            amount: 200,
+            destination_token_address: second_destination,
+        },
+        MultiSchedule {
+            release_time: start + 200,
+            amount: 300,
+            destination_token_address: third_destination,
+        },
+    ];
+    let seeds = LockSeed::random(&env.program_id);
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    let locking_token_account = get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id());
+    let instructions = [
+        init_instruction(env, seeds, (schedules.len() * MULTI_SCHEDULE_SLOTS) as u32),
+        create_multi(
+            &env.program_id,
+            &spl_token::id(),
+            &system_program::id(),
+            &sysvar::rent::id(),
+            &spl_associated_token_account::id(),
+            &env.price_estimator,
+            &locking_account,
+            &locking_token_account,
+            &payer,
+            &source,
+            &payer,
+            &destination,
+            &env.company_wallet,
+            &mint,
+            seeds,
+            schedules,
+        )
+        .unwrap(),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+
+    MultiLock {
+        seeds,
+        locking_account,
+        locking_token_account,
+        destination,
+        beneficiary,
+        second_destination,
+        third_destination,
+        start,
+    }
+}
+
+#[tokio::test]
+async fn test_unlock_to_every_destination() {
+    let mut env = setup(true).await;
+    let lock = create_multi_lock(&mut env).await;
+    let header = unpack_lock_header(&get_account(&mut env.context, &lock.locking_account).await.data).unwrap();
+    assert!(header.is_multi);
+    assert_eq!(header.schedule_count, 3);
+    assert_eq!(header.total_amount, 600);
+
+    warp_to_timestamp(&mut env.context, lock.start + 100).await;
+    let instruction = unlock_multi_instruction(&env, lock.seeds, &lock.destination, &[lock.second_destination, lock.third_destination]);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 100);
+    assert_eq!(token_balance(&mut env.context, &lock.second_destination).await, 200);
+    assert_eq!(token_balance(&mut env.context, &lock.third_destination).await, 0);
+    assert_eq!(token_balance(&mut env.context, &lock.locking_token_account).await, 300);
+
+    // Only the destination of the matured schedule is needed
+    warp_to_timestamp(&mut env.context, lock.start + 200).await;
+    let instruction = unlock_multi_instruction(&env, lock.seeds, &lock.destination, &[lock.third_destination]);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.third_destination).await, 300);
+    assert_eq!(token_balance(&mut env.context, &lock.locking_token_account).await, 0);
+
+    let header = unpack_lock_header(&get_account(&mut env.context, &lock.locking_account).await.data).unwrap();
+    assert_eq!(header.claimed_amount, 600);
+    assert_eq!(header.unlocked_count, 3);
+    assert!(header.is_drained());
+}
+
+#[tokio::test]
+async fn test_unlock_requires_schedule_destination() {
+    let mut env = setup(true).await;
+    let lock = create_multi_lock(&mut env).await;
+
+    warp_to_timestamp(&mut env.context, lock.start + 100).await;
+    let instruction = unlock_multi_instruction(&env, lock.seeds, &lock.destination, &[lock.third_destination]);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::NotEnoughAccountKeys,
+    );
+
+    // A token account of the schedule destination owner is not the schedule destination
+    let mint = env.mint.pubkey();
+    let other_destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+    let instruction = unlock_multi_instruction(&env, lock.seeds, &lock.destination, &[other_destination]);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::NotEnoughAccountKeys,
+    );
+    assert_eq!(token_balance(&mut env.context, &lock.locking_token_account).await, 600);
+}
+
+#[tokio::test]
+async fn test_multi_lock_can_not_be_extended() {
+    let mut env = setup(true).await;
+    let lock = create_multi_lock(&mut env).await;
+
+    let instruction = extend_lock_duration(
+        &env.program_id,
+        &lock.locking_account,
+        &lock.beneficiary.pubkey(),
+        &lock.destination,
+        lock.seeds,
+        2,
+        lock.start + 300,
+    )
+    .unwrap();
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&lock.beneficiary]).await,
+        InstructionError::Custom(LockTokenError::MultiDestinationLock as u32),
+    );
+}
@@ -0,0 +1,171 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    instruction::{LockSeed, Schedule},
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+};
+use solana_sdk::signature::{Keypair, Signer};
+
+// A DAO treasury held by a 2 of 3 multisig locks its tokens
+#[tokio::test]
+async fn test_create_from_multisig_treasury() {
+    let mut env = setup(true).await;
+    let mint = env.mint.pubkey();
+    let members = [Keypair::new(), Keypair::new(), Keypair::new()];
+    let member_keys: Vec<Pubkey> = members.iter().map(|member| member.pubkey()).collect();
+    let treasury = create_multisig(&mut env.context, &member_keys.iter().collect::<Vec<_>>(), 2).await;
+    let source = create_token_account(&mut env.context, &mint, &treasury).await;
+    mint_to(&mut env.context, &mint, &source, 1000).await;
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+
+    let start = now(&mut env.context).await;
+    let schedules = vec![
+        Schedule { release_time: start + 100, amount: 400 },
+        Schedule { release_time: start + 200, amount: 600 },
+    ];
+    let seeds = LockSeed::random(&env.program_id);
+    let instruction = init_instruction(&env, seeds, schedules.len() as u32);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    // One of the three members is not enough
+    let instruction = create_lock_instruction(
+        &env,
+        seeds,
+        &treasury,
+        &source,
+        &destination,
+        schedules.clone(),
+        false,
+        &member_keys[..1],
+    );
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&members[0]]).await,
+        InstructionError::MissingRequiredSignature,
+    );
+
+    let instruction = create_lock_instruction(
+        &env,
+        seeds,
+        &treasury,
+        &source,
+        &destination,
+        schedules,
+        false,
+        &[member_keys[0], member_keys[2]],
+    );
+    process(&mut env.context, &[instruction], &[&members[0], &members[2]]).await.unwrap();
+
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    let locking_token_account = get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id());
+    assert_eq!(token_balance(&mut env.context, &locking_token_account).await, 1000);
+    assert_eq!(token_balance(&mut env.context, &source).await, 0);
+}
+
+// A 2 of 3 multisig treasury holding 1000 tokens, with an initialized lock for them
+async fn setup_treasury(env: &mut TestEnv) -> (Vec<Keypair>, Pubkey, Pubkey, LockSeed) {
+    let mint = env.mint.pubkey();
+    let members = vec![Keypair::new(), Keypair::new(), Keypair::new()];
+    let member_keys: Vec<Pubkey> = members.iter().map(|member| member.pubkey()).collect();
+    let treasury = create_multisig(&mut env.context, &member_keys.iter().collect::<Vec<_>>(), 2).await;
+    let source = create_token_account(&mut env.context, &mint, &treasury).await;
+    mint_to(&mut env.context, &mint, &source, 1000).await;
+    let seeds = LockSeed::random(&env.program_id);
+    let instruction = init_instruction(env, seeds, 1);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    (members, treasury, source, seeds)
+}
+
+// Create from the treasury, passing the members as the trailing multisig signers. Only the members marked in
+// `signed` are signer accounts.
+fn create_from_treasury(
+    env: &TestEnv,
+    seeds: LockSeed,
+    treasury: &Pubkey,
+    source: &Pubkey,
+    destination: &Pubkey,
+    release_time: u64,
+    members: &[&Keypair],
+    signed: &[bool],
+) -> Instruction {
+    let member_keys: Vec<Pubkey> = members.iter().map(|member| member.pubkey()).collect();
+    let schedules = vec![Schedule { release_time, amount: 1000 }];
+    let mut instruction =
+        create_lock_instruction(env, seeds, treasury, source, destination, schedules, false, &member_keys);
+    let first_signer = instruction.accounts.len() - members.len();
+    for (account, signed) in instruction.accounts[first_signer..].iter_mut().zip(signed.iter()) {
+        account.is_signer = *signed;
+    }
+    instruction
+}
+
+// A member passed without signing does not count towards the 2 signatures, and does not fail the lock either
+#[tokio::test]
+async fn test_create_from_multisig_skips_members_which_did_not_sign() {
+    let mut env = setup(true).await;
+    let (members, treasury, source, seeds) = setup_treasury(&mut env).await;
+    let destination = create_token_account(&mut env.context, &env.mint.pubkey(), &Keypair::new().pubkey()).await;
+    let release_time = now(&mut env.context).await + 100;
+
+    // One signature and a member which did not sign are too few
+    let members_passed = [&members[0], &members[1]];
+    let instruction = create_from_treasury(
+        &env,
+        seeds,
+        &treasury,
+        &source,
+        &destination,
+        release_time,
+        &members_passed,
+        &[true, false],
+    );
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&members[0]]).await,
+        InstructionError::MissingRequiredSignature,
+    );
+
+    let instruction = create_from_treasury(
+        &env,
+        seeds,
+        &treasury,
+        &source,
+        &destination,
+        release_time,
+        &[&members[0], &members[1], &members[2]],
+        &[true, false, true],
+    );
+    process(&mut env.context, &[instruction], &[&members[0], &members[2]]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &source).await, 0);
+}
+
+// The same member passed twice signs once
+#[tokio::test]
+async fn test_create_from_multisig_counts_a_duplicate_signer_once() {
+    let mut env = setup(true).await;
+    let (members, treasury, source, seeds) = setup_treasury(&mut env).await;
+    let destination = create_token_account(&mut env.context, &env.mint.pubkey(), &Keypair::new().pubkey()).await;
+    let release_time = now(&mut env.context).await + 100;
+
+    let members_passed = [&members[1], &members[1]];
+    let instruction = create_from_treasury(
+        &env,
+        seeds,
+        &treasury,
+        &source,
+        &destination,
+        release_time,
+        &members_passed,
+        &[true, true],
+    );
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&members[1]]).await,
+        InstructionError::MissingRequiredSignature,
+    );
+    assert_eq!(token_balance(&mut env.context, &source).await, 1000);
+}
@@ -0,0 +1,223 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    instruction::{create_native, unlock, LockSeed, Schedule},
+    oracle::OraclePrice,
+    state::{find_global_state_address, token_state_address, usd_to_lamports, TokenState},
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+use solana_sdk::{
+    account::AccountSharedData,
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use spl_token::native_mint;
+
+// 10 USD at 150 USD per SOL
+const FEES_IN_USD: u64 = 10_000_000;
+const SOL_PRICE: i64 = 15_000_000_000;
+const SOL_PRICE_EXPO: i32 = -8;
+
+// Not every program derives a token state account for the native mint, so keep starting programs until one does.
+// The native mint is charged FEES_IN_USD, and there is no token state for it yet.
+async fn setup_native() -> TestEnv {
+    let mut env = loop {
+        let env = setup(true).await;
+        if Pubkey::create_program_address(&[&native_mint::id().to_bytes()], &env.program_id).is_ok() {
+            break env;
+        }
+    };
+    let mut state = global_state(&env.admin.pubkey(), &env.company_wallet, &env.price_estimator);
+    state.fees_in_usd = FEES_IN_USD;
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    env.context.set_account(
+        &program_state_account_key,
+        &AccountSharedData::from(program_account(&env.program_id, &state)),
+    );
+    set_sol_price(&mut env, SOL_PRICE, SOL_PRICE_EXPO).await;
+    env
+}
+
+fn create_native_instruction(
+    env: &TestEnv,
+    seeds: LockSeed,
+    locking_token_account: &Pubkey,
+    destination: &Pubkey,
+    schedules: Vec<Schedule>,
+) -> Instruction {
+    create_native(
+        &env.program_id,
+        &spl_token::id(),
+        &seeds.locking_account_address(&env.program_id).unwrap(),
+        locking_token_account,
+        &env.context.payer.pubkey(),
+        &env.company_wallet,
+        &system_program::id(),
+        &sysvar::rent::id(),
+        &env.price_estimator,
+        destination,
+        schedules,
+        seeds,
+    )
+    .unwrap()
+}
+
+fn unlock_native_instruction(
+    env: &TestEnv,
+    seeds: LockSeed,
+    locking_token_account: &Pubkey,
+    destination: &Pubkey,
+) -> Instruction {
+    unlock(
+        &env.program_id,
+        &spl_token::id(),
+        &seeds.locking_account_address(&env.program_id).unwrap(),
+        locking_token_account,
+        destination,
+        &native_mint::id(),
+        seeds,
+        0,
+    )
+    .unwrap()
+}
+
+// Locks 1 SOL released at start + 100 and 2 SOL released at start + 200 for the destination
+async fn create_native_lock(env: &mut TestEnv, destination: &Pubkey, start: u64) -> (LockSeed, Keypair) {
+    let seeds = LockSeed::random(&env.program_id);
+    let locking_token_account = Keypair::new();
+    let schedules = vec![
+        Schedule { release_time: start + 100, amount: 1_000_000_000 },
+        Schedule { release_time: start + 200, amount: 2_000_000_000 },
+    ];
+    let instructions = [
+        init_instruction(env, seeds, 2),
+        create_native_instruction(env, seeds, &locking_token_account.pubkey(), destination, schedules),
+    ];
+    process(&mut env.context, &instructions, &[&locking_token_account]).await.unwrap();
+    (seeds, locking_token_account)
+}
+
+// A wallet holding lamports, which native locks unlock into
+fn set_wallet(env: &mut TestEnv) -> Pubkey {
+    let wallet = Pubkey::new_unique();
+    env.context.set_account(&wallet, &program_owned_account(&system_program::id(), 1_000_000_000, Vec::new()));
+    wallet
+}
+
+// The first lock of the native mint creates its token state, which was never written before
+#[tokio::test]
+async fn test_create_native_without_a_token_state() {
+    let mut env = setup_native().await;
+    let token_state_account_key = token_state_address(&env.program_id, &native_mint::id()).unwrap();
+    assert!(env.context.banks_client.get_account(token_state_account_key).await.unwrap().is_none());
+    let company_wallet = env.company_wallet;
+    let company_lamports = env.context.banks_client.get_balance(company_wallet).await.unwrap();
+
+    let start = now(&mut env.context).await;
+    let destination = set_wallet(&mut env);
+    let (_, locking_token_account) = create_native_lock(&mut env, &destination, start).await;
+
+    let price = OraclePrice { price: SOL_PRICE, expo: SOL_PRICE_EXPO, publish_slot: 0 };
+    let fee = usd_to_lamports(FEES_IN_USD, &price).unwrap();
+    assert_eq!(env.context.banks_client.get_balance(company_wallet).await.unwrap(), company_lamports + fee);
+
+    let data = get_account(&mut env.context, &token_state_account_key).await.data;
+    let token_state = TokenState::unpack_from_slice(&data).unwrap();
+    assert_eq!(token_state.mint_address, native_mint::id());
+    assert_eq!(token_state.total_locked_amount, 3_000_000_000);
+
+    // The wrapped SOL account holds the locked lamports on top of its rent exempt reserve
+    let rent = env.context.banks_client.get_rent().await.unwrap();
+    let vault = get_account(&mut env.context, &locking_token_account.pubkey()).await;
+    assert_eq!(vault.lamports, rent.minimum_balance(spl_token::state::Account::LEN) + 3_000_000_000);
+    assert_eq!(token_balance(&mut env.context, &locking_token_account.pubkey()).await, 3_000_000_000);
+}
+
+// A token state account cut short is rejected rather than read past its end
+#[tokio::test]
+async fn test_create_native_rejects_a_truncated_token_state() {
+    let mut env = setup_native().await;
+    let token_state_account_key = token_state_address(&env.program_id, &native_mint::id()).unwrap();
+    env.context.set_account(&token_state_account_key, &program_owned_account(&env.program_id, 1_000_000_000, vec![1]));
+
+    let seeds = LockSeed::random(&env.program_id);
+    let locking_token_account = Keypair::new();
+    let destination = set_wallet(&mut env);
+    let start = now(&mut env.context).await;
+    let instructions = [
+        init_instruction(&env, seeds, 1),
+        create_native_instruction(
+            &env,
+            seeds,
+            &locking_token_account.pubkey(),
+            &destination,
+            vec![Schedule { release_time: start + 100, amount: 1_000_000_000 }],
+        ),
+    ];
+    assert_eq!(
+        process(&mut env.context, &instructions, &[&locking_token_account]).await.unwrap_err(),
+        TransactionError::InstructionError(1, InstructionError::InvalidAccountData)
+    );
+}
+
+// Unlocking into a wallet unwraps the lamports, and the last unlock also returns the rent exempt reserve of the
+// wrapped SOL account
+#[tokio::test]
+async fn test_unlock_native_into_a_wallet_recovers_the_wrapped_sol_rent() {
+    let mut env = setup_native().await;
+    let start = now(&mut env.context).await;
+    let destination = set_wallet(&mut env);
+    let (seeds, locking_token_account) = create_native_lock(&mut env, &destination, start).await;
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    let rent = env.context.banks_client.get_rent().await.unwrap();
+    let wrapped_sol_rent = rent.minimum_balance(spl_token::state::Account::LEN);
+
+    warp_to_timestamp(&mut env.context, start + 100).await;
+    let instruction = unlock_native_instruction(&env, seeds, &locking_token_account.pubkey(), &destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(env.context.banks_client.get_balance(destination).await.unwrap(), 2_000_000_000);
+    assert!(env.context.banks_client.get_account(locking_token_account.pubkey()).await.unwrap().is_none());
+
+    warp_to_timestamp(&mut env.context, start + 200).await;
+    let instruction = unlock_native_instruction(&env, seeds, &locking_token_account.pubkey(), &destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(
+        env.context.banks_client.get_balance(destination).await.unwrap(),
+        4_000_000_000 + wrapped_sol_rent
+    );
+    let locking_account = get_account(&mut env.context, &locking_account).await;
+    assert_eq!(locking_account.lamports, rent.minimum_balance(locking_account.data.len()));
+}
+
+// Unlocking into a wrapped SOL token account transfers the tokens, and the last unlock closes the wrapped SOL
+// account of the lock into it
+#[tokio::test]
+async fn test_unlock_native_into_a_token_account_closes_the_wrapped_sol_account() {
+    let mut env = setup_native().await;
+    let start = now(&mut env.context).await;
+    let owner = Pubkey::new_unique();
+    let destination = create_token_account(&mut env.context, &native_mint::id(), &owner).await;
+    let (seeds, locking_token_account) = create_native_lock(&mut env, &destination, start).await;
+    let rent = env.context.banks_client.get_rent().await.unwrap();
+    let wrapped_sol_rent = rent.minimum_balance(spl_token::state::Account::LEN);
+
+    warp_to_timestamp(&mut env.context, start + 100).await;
+    let instruction = unlock_native_instruction(&env, seeds, &locking_token_account.pubkey(), &destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &destination).await, 1_000_000_000);
+    assert_eq!(token_balance(&mut env.context, &locking_token_account.pubkey()).await, 2_000_000_000);
+
+    warp_to_timestamp(&mut env.context, start + 200).await;
+    let instruction = unlock_native_instruction(&env, seeds, &locking_token_account.pubkey(), &destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &destination).await, 3_000_000_000 + wrapped_sol_rent);
+    assert!(env.context.banks_client.get_account(locking_token_account.pubkey()).await.unwrap().is_none());
+}
@@ -0,0 +1,203 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    error::LockTokenError,
+    instruction::{create, CreateFeePayment, LockSeed, Schedule},
+    oracle::DEFAULT_MAX_PRICE_AGE_SLOTS,
+    state::{find_global_state_address, token_state_address, TokenState},
+    time::TimeBasis,
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_program::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    system_program,
+    sysvar::{self, clock::Clock},
+};
+use solana_sdk::{account::AccountSharedData, signature::Signer, transaction::TransactionError};
+
+// 10 USD
+const FEES_IN_USD: u64 = 10_000_000;
+
+// Charges FEES_IN_USD for locking the mint of the test environment, unless it is free, with prices valid for
+// max_price_age_slots
+fn charge_fees(env: &mut TestEnv, is_free: bool, max_price_age_slots: u64) {
+    let mut state = global_state(&env.admin.pubkey(), &env.company_wallet, &env.price_estimator);
+    state.fees_in_usd = FEES_IN_USD;
+    state.max_price_age_slots = max_price_age_slots;
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    let account = program_account(&env.program_id, &state);
+    env.context.set_account(&program_state_account_key, &AccountSharedData::from(account));
+
+    let mint = env.mint.pubkey();
+    let token_state = TokenState {
+        mint_address: mint,
+        is_free,
+        fee_override_usd: 0,
+        has_override: false,
+        active_locks: 0,
+        total_locked_amount: 0,
+        last_snapshot_amount: 0,
+        last_snapshot_ts: 0,
+        is_initialized: true,
+    };
+    let account = program_account(&env.program_id, &token_state);
+    env.context.set_account(&token_state_address(&env.program_id, &mint).unwrap(), &AccountSharedData::from(account));
+}
+
+// Locks 500 tokens of the context payer, reading the price at the given estimator, and returns the lamports the
+// company wallet received
+async fn create_lock(env: &mut TestEnv, price_estimator: &Pubkey) -> Result<u64, TransactionError> {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 500).await;
+    let destination = create_token_account(&mut env.context, &mint, &Pubkey::new_unique()).await;
+    let company_wallet = env.company_wallet;
+    let company_lamports = env.context.banks_client.get_balance(company_wallet).await.unwrap();
+
+    let start = now(&mut env.context).await;
+    let seeds = LockSeed::random(&env.program_id);
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    let instructions = [
+        init_instruction(env, seeds, 1),
+        create(
+            &env.program_id,
+            &spl_token::id(),
+            &system_program::id(),
+            &sysvar::rent::id(),
+            &spl_associated_token_account::id(),
+            price_estimator,
+            &locking_account,
+            &get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id()),
+            &payer,
+            &source,
+            &payer,
+            &destination,
+            &company_wallet,
+            &mint,
+            vec![Schedule { release_time: start + 100, amount: 500 }],
+            seeds,
+            0,
+            false,
+            TimeBasis::UnixTimestamp,
+            Vec::new(),
+            0,
+            CreateFeePayment::Sol,
+            None,
+            false,
+            &[],
+        )
+        .unwrap(),
+    ];
+    process(&mut env.context, &instructions, &[]).await?;
+    Ok(env.context.banks_client.get_balance(company_wallet).await.unwrap() - company_lamports)
+}
+
+// Moves the slot of the clock the program reads forward. Warping the bank instead checks its accounts hash, which
+// the accounts set by the test no longer match.
+async fn advance_clock_slot(env: &mut TestEnv, slots: u64) {
+    let mut clock: Clock = env.context.banks_client.get_sysvar().await.unwrap();
+    clock.slot += slots;
+    env.context.set_sysvar(&clock);
+    refresh_blockhash(&mut env.context).await;
+}
+
+fn create_error(error: LockTokenError) -> TransactionError {
+    TransactionError::InstructionError(1, InstructionError::Custom(error as u32))
+}
+
+// The USD fee is converted at the oracle price, whatever its exponent
+#[tokio::test]
+async fn test_fee_is_converted_at_the_oracle_price() {
+    let mut env = setup(true).await;
+    charge_fees(&mut env, false, 0);
+    let price_estimator = env.price_estimator;
+
+    // 100 USD per SOL makes 10 USD 0.1 SOL
+    set_sol_price(&mut env, 10_000, -2).await;
+    assert_eq!(create_lock(&mut env, &price_estimator).await, Ok(100_000_000));
+
+    // 200 USD per SOL without an exponent, 150 USD per SOL of eight decimals, rounded down
+    set_sol_price(&mut env, 200, 0).await;
+    assert_eq!(create_lock(&mut env, &price_estimator).await, Ok(50_000_000));
+    set_sol_price(&mut env, 15_000_000_000, -8).await;
+    assert_eq!(create_lock(&mut env, &price_estimator).await, Ok(66_666_666));
+}
+
+// A price older than the configured age, or not currently trading, is stale
+#[tokio::test]
+async fn test_stale_price_is_rejected() {
+    let mut env = setup(true).await;
+    charge_fees(&mut env, false, 0);
+    let price_estimator = env.price_estimator;
+
+    set_sol_price(&mut env, 10_000, -2).await;
+    advance_clock_slot(&mut env, DEFAULT_MAX_PRICE_AGE_SLOTS + 1).await;
+    assert_eq!(create_lock(&mut env, &price_estimator).await, Err(create_error(LockTokenError::StaleOraclePrice)));
+
+    // The price of the same age is fresh enough once the program allows older prices
+    charge_fees(&mut env, false, 2 * DEFAULT_MAX_PRICE_AGE_SLOTS);
+    assert_eq!(create_lock(&mut env, &price_estimator).await, Ok(100_000_000));
+
+    set_sol_price(&mut env, 10_000, -2).await;
+    let mut account = get_account(&mut env.context, &price_estimator).await;
+    account.data[224..228].copy_from_slice(&0u32.to_le_bytes());
+    env.context.set_account(&price_estimator, &account.into());
+    assert_eq!(create_lock(&mut env, &price_estimator).await, Err(create_error(LockTokenError::StaleOraclePrice)));
+}
+
+#[tokio::test]
+async fn test_non_positive_price_is_rejected() {
+    let mut env = setup(true).await;
+    charge_fees(&mut env, false, 0);
+    let price_estimator = env.price_estimator;
+
+    for price in [0, -10_000].iter() {
+        set_sol_price(&mut env, *price, -2).await;
+        assert_eq!(
+            create_lock(&mut env, &price_estimator).await,
+            Err(create_error(LockTokenError::InvalidOraclePrice))
+        );
+    }
+}
+
+// The price has to come from the configured estimator, and that account has to be a price account
+#[tokio::test]
+async fn test_other_price_account_is_rejected() {
+    let mut env = setup(true).await;
+    charge_fees(&mut env, false, 0);
+    set_sol_price(&mut env, 10_000, -2).await;
+    let price_estimator = env.price_estimator;
+
+    let other_estimator = Pubkey::new_unique();
+    let account = get_account(&mut env.context, &price_estimator).await;
+    env.context.set_account(&other_estimator, &account.into());
+    assert_eq!(
+        create_lock(&mut env, &other_estimator).await,
+        Err(create_error(LockTokenError::InvalidOracleAccount))
+    );
+
+    let mut account = get_account(&mut env.context, &price_estimator).await;
+    account.data[0..4].copy_from_slice(&0u32.to_le_bytes());
+    env.context.set_account(&price_estimator, &account.into());
+    assert_eq!(
+        create_lock(&mut env, &price_estimator).await,
+        Err(create_error(LockTokenError::InvalidOracleAccount))
+    );
+}
+
+// Free tokens pay nothing, and do not read the oracle at all
+#[tokio::test]
+async fn test_free_token_pays_nothing() {
+    let mut env = setup(true).await;
+    charge_fees(&mut env, true, 0);
+    let price_estimator = env.price_estimator;
+    assert_eq!(create_lock(&mut env, &price_estimator).await, Ok(0));
+
+    set_sol_price(&mut env, 0, -2).await;
+    assert_eq!(create_lock(&mut env, &price_estimator).await, Ok(0));
+}
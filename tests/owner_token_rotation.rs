@@ -0,0 +1,155 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    error::LockTokenError,
+    instruction::{set_fee_discount_tiers, set_owner_token_mint},
+    state::{find_global_state_address, FeeDiscountTier, LockGlobalState, MAX_FEE_DISCOUNT_TIERS},
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_sdk::signature::{Keypair, Signer};
+
+// 20% off from 100 owner tokens
+const FEE_DISCOUNT_TIERS: [FeeDiscountTier; MAX_FEE_DISCOUNT_TIERS] = [
+    FeeDiscountTier { min_balance: 100, discount_bps: 2_000 },
+    FeeDiscountTier { min_balance: 0, discount_bps: 0 },
+    FeeDiscountTier { min_balance: 0, discount_bps: 0 },
+];
+
+async fn new_mint(env: &mut TestEnv) -> Pubkey {
+    let mint = Keypair::new();
+    create_mint(&mut env.context, &mint).await;
+    mint.pubkey()
+}
+
+fn set_owner_token_mint_instruction(env: &TestEnv, admin: &Pubkey, new_mint: &Pubkey, admin_token_account: &Pubkey) -> Instruction {
+    set_owner_token_mint(
+        &env.program_id,
+        &system_program::id(),
+        &spl_token::id(),
+        admin,
+        &env.context.payer.pubkey(),
+        new_mint,
+        admin_token_account,
+    )
+    .unwrap()
+}
+
+fn set_tiers_instruction(env: &TestEnv, fee_discount_tiers: [FeeDiscountTier; MAX_FEE_DISCOUNT_TIERS]) -> Instruction {
+    set_fee_discount_tiers(
+        &env.program_id,
+        &system_program::id(),
+        &env.admin.pubkey(),
+        &env.context.payer.pubkey(),
+        fee_discount_tiers,
+    )
+    .unwrap()
+}
+
+async fn global_state(env: &mut TestEnv) -> LockGlobalState {
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    let account = get_account(&mut env.context, &program_state_account_key).await;
+    LockGlobalState::unpack_from_slice(&account.data).unwrap()
+}
+
+#[tokio::test]
+async fn test_owner_token_rotation_round_trip() {
+    let mut env = setup(true).await;
+    let admin = env.admin.pubkey();
+    let first_mint = new_mint(&mut env).await;
+    let second_mint = new_mint(&mut env).await;
+
+    let instruction = set_tiers_instruction(&env, FEE_DISCOUNT_TIERS);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+
+    // The admin holds the new owner token
+    let second_token_account = create_token_account(&mut env.context, &second_mint, &admin).await;
+    mint_to(&mut env.context, &second_mint, &second_token_account, 1).await;
+    let instruction = set_owner_token_mint_instruction(&env, &admin, &second_mint, &second_token_account);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+    let state = global_state(&mut env).await;
+    assert_eq!(state.owner_token_mint, second_mint);
+    assert_eq!(state.fee_discount_tiers, FEE_DISCOUNT_TIERS);
+
+    // On to the first owner token, which the admin does not hold yet
+    let first_token_account = create_token_account(&mut env.context, &first_mint, &admin).await;
+    let instruction = set_owner_token_mint_instruction(&env, &admin, &first_mint, &first_token_account);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction.clone()], &[&env.admin]).await,
+        InstructionError::InsufficientFunds,
+    );
+    assert_eq!(global_state(&mut env).await.owner_token_mint, second_mint);
+
+    mint_to(&mut env.context, &first_mint, &first_token_account, 1).await;
+    // The same transaction again, in a new block
+    refresh_blockhash(&mut env.context).await;
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+    let state = global_state(&mut env).await;
+    assert_eq!(state.owner_token_mint, first_mint);
+    assert_eq!(state.fee_discount_tiers, FEE_DISCOUNT_TIERS);
+}
+
+#[tokio::test]
+async fn test_owner_token_rotation_requires_the_admin_balance_of_the_new_mint() {
+    let mut env = setup(true).await;
+    let admin = env.admin.pubkey();
+    let owner_token_mint = new_mint(&mut env).await;
+    let other_mint = new_mint(&mut env).await;
+
+    // A token account of another mint
+    let other_token_account = create_token_account(&mut env.context, &other_mint, &admin).await;
+    mint_to(&mut env.context, &other_mint, &other_token_account, 1).await;
+    let instruction = set_owner_token_mint_instruction(&env, &admin, &owner_token_mint, &other_token_account);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&env.admin]).await,
+        InstructionError::Custom(LockTokenError::FeeMintMismatch as u32),
+    );
+
+    // A token account of the new mint owned by somebody else
+    let not_admin = Keypair::new();
+    let token_account = create_token_account(&mut env.context, &owner_token_mint, &not_admin.pubkey()).await;
+    mint_to(&mut env.context, &owner_token_mint, &token_account, 1).await;
+    let instruction = set_owner_token_mint_instruction(&env, &admin, &owner_token_mint, &token_account);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&env.admin]).await,
+        InstructionError::InvalidArgument,
+    );
+
+    // Only the admin rotates the owner token, even when holding it
+    let instruction = set_owner_token_mint_instruction(&env, &not_admin.pubkey(), &owner_token_mint, &token_account);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&not_admin]).await,
+        InstructionError::Custom(LockTokenError::NotAdmin as u32),
+    );
+    assert_eq!(global_state(&mut env).await.owner_token_mint, Pubkey::default());
+}
+
+#[tokio::test]
+async fn test_fee_discount_tiers_keep_the_owner_token() {
+    let mut env = setup(true).await;
+    let admin = env.admin.pubkey();
+    let owner_token_mint = new_mint(&mut env).await;
+
+    let admin_token_account = create_token_account(&mut env.context, &owner_token_mint, &admin).await;
+    mint_to(&mut env.context, &owner_token_mint, &admin_token_account, 1).await;
+    let instruction = set_owner_token_mint_instruction(&env, &admin, &owner_token_mint, &admin_token_account);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+
+    // Only SetOwnerTokenMint rotates the owner token, new tiers leave it as it is
+    let instruction = set_tiers_instruction(&env, FEE_DISCOUNT_TIERS);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+    let state = global_state(&mut env).await;
+    assert_eq!(state.owner_token_mint, owner_token_mint);
+    assert_eq!(state.fee_discount_tiers, FEE_DISCOUNT_TIERS);
+
+    let instruction = set_tiers_instruction(&env, [FeeDiscountTier::default(); MAX_FEE_DISCOUNT_TIERS]);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+    assert_eq!(global_state(&mut env).await.owner_token_mint, owner_token_mint);
+}
@@ -0,0 +1,58 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    events::{decode, OwnershipTransferred},
+    instruction::{accept_ownership, propose_owner},
+    state::{find_global_state_address, LockGlobalState},
+};
+use solana_program::{instruction::InstructionError, program_pack::Pack, pubkey::Pubkey};
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+#[tokio::test]
+async fn test_accept_ownership_hands_over_the_admin_and_emits_an_event() {
+    let mut env = setup(true).await;
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    let new_admin = Keypair::new();
+
+    let instruction =
+        propose_owner(&env.program_id, &env.admin.pubkey(), &program_state_account_key, &new_admin.pubkey()).unwrap();
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+
+    // Only the proposed admin accepts
+    let stranger = Keypair::new();
+    let instruction = accept_ownership(&env.program_id, &stranger.pubkey(), &program_state_account_key).unwrap();
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&stranger]).await,
+        InstructionError::InvalidArgument,
+    );
+
+    let instruction = accept_ownership(&env.program_id, &new_admin.pubkey(), &program_state_account_key).unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&env.context.payer.pubkey()),
+        &[&env.context.payer, &new_admin],
+        env.context.last_blockhash,
+    );
+    let result = process_with_metadata(&mut env.context, transaction).await;
+    result.result.unwrap();
+    let metadata = result.metadata.unwrap();
+    let event = metadata.log_messages.iter().find_map(|log| decode::<OwnershipTransferred>(log)).unwrap();
+    assert_eq!(
+        event,
+        OwnershipTransferred {
+            previous_admin: env.admin.pubkey(),
+            new_admin: new_admin.pubkey(),
+        }
+    );
+
+    let data = get_account(&mut env.context, &program_state_account_key).await.data;
+    let state = LockGlobalState::unpack_from_slice(&data).unwrap();
+    assert_eq!(state.admin, new_admin.pubkey());
+    assert_eq!(state.pending_admin, Pubkey::default());
+}
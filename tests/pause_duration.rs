@@ -0,0 +1,161 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    error::LockTokenError,
+    instruction::Schedule,
+    state::{find_global_state_address, LockGlobalState, MAX_PAUSE_DURATION_SECS, PAUSE_COOLDOWN_SECS, PAUSE_CREATE, PAUSE_UNLOCK},
+};
+use solana_program::{
+    instruction::InstructionError,
+    program_pack::Pack,
+};
+use solana_sdk::{
+    account::AccountSharedData,
+    signature::Signer,
+};
+
+// Locks 300 of the context payer's tokens released at start + 100, returning the lock and its start
+async fn create_lock(env: &mut TestEnv) -> (TestLock, u64) {
+    let start = now(&mut env.context).await;
+    (create_test_lock(env, vec![Schedule { release_time: start + 100, amount: 300 }]).await, start)
+}
+
+async fn global_state(env: &mut TestEnv) -> LockGlobalState {
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    let account = get_account(&mut env.context, &program_state_account_key).await;
+    LockGlobalState::unpack_from_slice(&account.data).unwrap()
+}
+
+#[tokio::test]
+async fn test_unlock_proceeds_once_the_pause_outlasts_the_cap() {
+    let mut env = setup(true).await;
+    let (lock, start) = create_lock(&mut env).await;
+    warp_to_timestamp(&mut env.context, start + 100).await;
+
+    let instruction = pause_contract(&env.program_id, &env.admin.pubkey(), PAUSE_UNLOCK);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+    let state = global_state(&mut env).await;
+    let paused_at = state.paused_at;
+    assert!(paused_at >= start + 100);
+    assert_eq!(state.paused_by, env.admin.pubkey());
+
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::Custom(LockTokenError::ContractPaused as u32),
+    );
+
+    warp_to_timestamp(&mut env.context, paused_at + MAX_PAUSE_DURATION_SECS - 1).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::Custom(LockTokenError::ContractPaused as u32),
+    );
+
+    // Still paused, but no longer blocking
+    warp_to_timestamp(&mut env.context, paused_at + MAX_PAUSE_DURATION_SECS).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 300);
+    assert!(global_state(&mut env).await.pause_unlock);
+}
+
+#[tokio::test]
+async fn test_adding_unlock_pause_keeps_the_start_of_the_pause() {
+    let mut env = setup(true).await;
+    let (lock, _) = create_lock(&mut env).await;
+
+    let instruction = pause_contract(&env.program_id, &env.admin.pubkey(), PAUSE_CREATE);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+    let paused_at = global_state(&mut env).await.paused_at;
+
+    warp_to_timestamp(&mut env.context, paused_at + MAX_PAUSE_DURATION_SECS).await;
+    let instruction = pause_contract(&env.program_id, &env.admin.pubkey(), PAUSE_CREATE | PAUSE_UNLOCK);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+    assert_eq!(global_state(&mut env).await.paused_at, paused_at);
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    // Resuming everything keeps the record
+    let instruction = pause_contract(&env.program_id, &env.admin.pubkey(), 0);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+    let state = global_state(&mut env).await;
+    assert!(!state.is_paused());
+    assert_eq!((state.paused_at, state.paused_by), (paused_at, env.admin.pubkey()));
+}
+
+#[tokio::test]
+async fn test_toggling_the_unlock_pause_keeps_the_start_of_the_pause() {
+    let mut env = setup(true).await;
+    let (lock, start) = create_lock(&mut env).await;
+    warp_to_timestamp(&mut env.context, start + 100).await;
+
+    let instruction = pause_contract(&env.program_id, &env.admin.pubkey(), PAUSE_UNLOCK);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+    let paused_at = global_state(&mut env).await.paused_at;
+
+    // Resuming and pausing again in one transaction continues the pause
+    warp_to_timestamp(&mut env.context, paused_at + MAX_PAUSE_DURATION_SECS - 1).await;
+    let instructions = [
+        pause_contract(&env.program_id, &env.admin.pubkey(), 0),
+        pause_contract(&env.program_id, &env.admin.pubkey(), PAUSE_UNLOCK),
+    ];
+    process(&mut env.context, &instructions, &[&env.admin]).await.unwrap();
+    assert_eq!(global_state(&mut env).await.paused_at, paused_at);
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::Custom(LockTokenError::ContractPaused as u32),
+    );
+
+    // Once the pause expired, pausing again during the cooldown keeps the expired start
+    warp_to_timestamp(&mut env.context, paused_at + MAX_PAUSE_DURATION_SECS).await;
+    let instructions = [
+        pause_contract(&env.program_id, &env.admin.pubkey(), 0),
+        pause_contract(&env.program_id, &env.admin.pubkey(), PAUSE_UNLOCK),
+    ];
+    process(&mut env.context, &instructions, &[&env.admin]).await.unwrap();
+    let state = global_state(&mut env).await;
+    assert!(state.pause_unlock);
+    assert_eq!(state.paused_at, paused_at);
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 300);
+
+    // After the cooldown a pause starts anew
+    let cooldown_ends_at = paused_at + MAX_PAUSE_DURATION_SECS + PAUSE_COOLDOWN_SECS;
+    warp_to_timestamp(&mut env.context, cooldown_ends_at).await;
+    let instructions = [
+        pause_contract(&env.program_id, &env.admin.pubkey(), 0),
+        pause_contract(&env.program_id, &env.admin.pubkey(), PAUSE_UNLOCK),
+    ];
+    process(&mut env.context, &instructions, &[&env.admin]).await.unwrap();
+    assert!(global_state(&mut env).await.paused_at >= cooldown_ends_at);
+}
+
+#[tokio::test]
+async fn test_unlock_pause_needs_the_current_layout() {
+    let mut env = setup(true).await;
+    let state = global_state(&mut env).await;
+    let mut data = vec![0; LockGlobalState::V5_LEN];
+    data[392] = 5;
+    state.pack_into_slice(&mut data);
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    let mut account = get_account(&mut env.context, &program_state_account_key).await;
+    account.data = data;
+    env.context.set_account(&program_state_account_key, &AccountSharedData::from(account));
+
+    let instruction = pause_contract(&env.program_id, &env.admin.pubkey(), PAUSE_UNLOCK);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&env.admin]).await,
+        InstructionError::AccountDataTooSmall,
+    );
+
+    // Other pauses have no expiry to record
+    let instruction = pause_contract(&env.program_id, &env.admin.pubkey(), PAUSE_CREATE);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+    assert!(global_state(&mut env).await.pause_create);
+}
@@ -0,0 +1,150 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    error::LockTokenError,
+    instruction::{extend_lock_duration, transfer_locks, LockSeed, Schedule},
+    state::{find_global_state_address, LockGlobalState, PAUSE_CREATE, PAUSE_TRANSFER, PAUSE_UNLOCK},
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use solana_sdk::{
+    signature::Signer,
+    transaction::TransactionError,
+};
+
+const PAUSED: InstructionError = InstructionError::Custom(LockTokenError::ContractPaused as u32);
+
+// PauseContract used to take the is_paused flag, whose value 1 paused the whole program
+const OLD_IS_PAUSED: u8 = 1;
+
+async fn pause(env: &mut TestEnv, pause_flags: u8) {
+    refresh_blockhash(&mut env.context).await;
+    let instruction = pause_contract(&env.program_id, &env.admin.pubkey(), pause_flags);
+    process(&mut env.context, &[instruction], &[&env.admin]).await.unwrap();
+}
+
+// Locks 10 tokens of the context payer released at release_time, returning the Create error if it fails
+async fn try_create(env: &mut TestEnv, release_time: u64) -> Result<(), TransactionError> {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 10).await;
+    let destination = create_token_account(&mut env.context, &mint, &Pubkey::new_unique()).await;
+    let seeds = LockSeed::random(&env.program_id);
+    let schedules = vec![Schedule { release_time, amount: 10 }];
+    let instructions = [
+        init_instruction(env, seeds, 1),
+        create_lock_instruction(env, seeds, &payer, &source, &destination, schedules, false, &[]),
+    ];
+    process(&mut env.context, &instructions, &[]).await
+}
+
+fn transfer_instruction(env: &TestEnv, lock: &TestLock, new_destination: &Pubkey) -> Instruction {
+    transfer_locks(
+        &env.program_id,
+        &lock.locking_account,
+        &lock.beneficiary.pubkey(),
+        &lock.destination,
+        new_destination,
+        lock.seeds,
+    )
+    .unwrap()
+}
+
+fn extend_instruction(env: &TestEnv, lock: &TestLock, release_time: u64) -> Instruction {
+    extend_lock_duration(
+        &env.program_id,
+        &lock.locking_account,
+        &lock.beneficiary.pubkey(),
+        &lock.destination,
+        lock.seeds,
+        0,
+        release_time,
+    )
+    .unwrap()
+}
+
+// Two locks of 10 tokens, one released at start + 100 to unlock and one released at start + 1000 to transfer
+async fn create_locks(env: &mut TestEnv, start: u64) -> (TestLock, TestLock) {
+    let matured = create_test_lock(env, vec![Schedule { release_time: start + 100, amount: 10 }]).await;
+    let locked = create_test_lock(env, vec![Schedule { release_time: start + 1000, amount: 10 }]).await;
+    warp_to_timestamp(&mut env.context, start + 100).await;
+    (matured, locked)
+}
+
+// Clients sending the old flag now only pause Create, so vested tokens can still be unlocked and locks moved
+#[tokio::test]
+async fn test_old_pause_value_only_pauses_create() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let (matured, locked) = create_locks(&mut env, start).await;
+
+    pause(&mut env, OLD_IS_PAUSED).await;
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    let data = get_account(&mut env.context, &program_state_account_key).await.data;
+    let state = LockGlobalState::unpack_from_slice(&data).unwrap();
+    assert!(state.pause_create && !state.pause_unlock && !state.pause_transfer);
+
+    // Init already refuses the new lock
+    assert_instruction_error(try_create(&mut env, start + 1000).await, PAUSED);
+
+    let instruction = unlock_instruction(&env, matured.seeds, &matured.destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &matured.destination).await, 10);
+
+    let instruction = extend_instruction(&env, &locked, start + 2000);
+    process(&mut env.context, &[instruction], &[&locked.beneficiary]).await.unwrap();
+    let new_destination = create_token_account(&mut env.context, &env.mint.pubkey(), &Pubkey::new_unique()).await;
+    let instruction = transfer_instruction(&env, &locked, &new_destination);
+    process(&mut env.context, &[instruction], &[&locked.beneficiary]).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_unlock_pause_only_pauses_unlock() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let (matured, locked) = create_locks(&mut env, start).await;
+
+    pause(&mut env, PAUSE_UNLOCK).await;
+    let instruction = unlock_instruction(&env, matured.seeds, &matured.destination);
+    assert_instruction_error(process(&mut env.context, &[instruction], &[]).await, PAUSED);
+    assert_eq!(token_balance(&mut env.context, &matured.destination).await, 0);
+
+    try_create(&mut env, start + 1000).await.unwrap();
+    let instruction = extend_instruction(&env, &locked, start + 2000);
+    process(&mut env.context, &[instruction], &[&locked.beneficiary]).await.unwrap();
+    let new_destination = create_token_account(&mut env.context, &env.mint.pubkey(), &Pubkey::new_unique()).await;
+    let instruction = transfer_instruction(&env, &locked, &new_destination);
+    process(&mut env.context, &[instruction], &[&locked.beneficiary]).await.unwrap();
+}
+
+// The transfer pause covers both TransferLocks and ExtendLockDuration
+#[tokio::test]
+async fn test_transfer_pause_only_pauses_transfer_and_extend() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let (matured, locked) = create_locks(&mut env, start).await;
+
+    pause(&mut env, PAUSE_TRANSFER).await;
+    let instruction = extend_instruction(&env, &locked, start + 2000);
+    assert_instruction_error(process(&mut env.context, &[instruction], &[&locked.beneficiary]).await, PAUSED);
+    let new_destination = create_token_account(&mut env.context, &env.mint.pubkey(), &Pubkey::new_unique()).await;
+    let instruction = transfer_instruction(&env, &locked, &new_destination);
+    assert_instruction_error(process(&mut env.context, &[instruction], &[&locked.beneficiary]).await, PAUSED);
+
+    try_create(&mut env, start + 1000).await.unwrap();
+    let instruction = unlock_instruction(&env, matured.seeds, &matured.destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &matured.destination).await, 10);
+
+    // Resuming lets the lock move again
+    pause(&mut env, PAUSE_CREATE).await;
+    let instruction = transfer_instruction(&env, &locked, &new_destination);
+    process(&mut env.context, &[instruction], &[&locked.beneficiary]).await.unwrap();
+}
@@ -0,0 +1,207 @@
+use lock_token::{
+    pod::{PodLockGlobalState, PodLockSchedule, PodLockScheduleHeader, ZeroCopy},
+    state::{
+        FeeDiscountTier, LockGlobalState, LockSchedule, LockScheduleHeader, SplitDestination, GLOBAL_STATE_VERSION,
+        HEADER_VERSION, MAX_CANDIDATE_WALLETS, MAX_FEE_DISCOUNT_TIERS,
+    },
+    time::TimeBasis,
+};
+use proptest::prelude::*;
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+
+fn pubkey() -> impl Strategy<Value = Pubkey> {
+    any::<[u8; 32]>().prop_map(Pubkey::new_from_array)
+}
+
+fn header() -> impl Strategy<Value = LockScheduleHeader> {
+    (
+        (pubkey(), pubkey(), pubkey(), pubkey(), pubkey()),
+        any::<(bool, bool, bool, bool, bool, bool, bool)>(),
+        any::<(u64, u64, u32, u32, u64)>(),
+        prop_oneof![Just(TimeBasis::UnixTimestamp), Just(TimeBasis::Slot)],
+        prop::array::uniform4((pubkey(), any::<u16>())),
+        pubkey(),
+        any::<(u64, u32, u64, u64, u8, i64)>(),
+    )
+        .prop_map(
+            |(
+                (
+                    destination_address,
+                    mint_address,
+                    pending_destination_address,
+                    rescue_destination_address,
+                    creator_address,
+                ),
+                (is_initialized, is_vesting, is_nft, is_registered, is_immutable, is_multi, is_derived),
+                (total_amount, claimed_amount, schedule_count, unlocked_count, rescue_execute_after),
+                time_basis,
+                split,
+                delegate_address,
+                (permit_nonce, min_claim_interval_secs, last_claim_ts, lock_nonce, lock_bump, created_at),
+            )| LockScheduleHeader {
+                destination_address,
+                mint_address,
+                is_initialized,
+                pending_destination_address,
+                is_vesting,
+                is_nft,
+                is_registered,
+                is_immutable,
+                is_multi,
+                is_derived,
+                total_amount,
+                claimed_amount,
+                schedule_count,
+                unlocked_count,
+                rescue_destination_address,
+                rescue_execute_after,
+                creator_address,
+                time_basis,
+                split_destinations: split.map(|(destination_address, share_bps)| SplitDestination {
+                    destination_address,
+                    share_bps,
+                }),
+                delegate_address,
+                permit_nonce,
+                min_claim_interval_secs,
+                last_claim_ts,
+                lock_nonce,
+                lock_bump,
+                created_at,
+            },
+        )
+}
+
+proptest! {
+    #[test]
+    fn test_schedule_view(release_time in any::<u64>(), amount in any::<u64>()) {
+        let mut data = [0; LockSchedule::LEN];
+        LockSchedule { release_time, amount }.pack_into_slice(&mut data);
+        let view = PodLockSchedule::try_from_bytes(&data).unwrap();
+        prop_assert_eq!(u64::from(view.release_time), release_time);
+        prop_assert_eq!(u64::from(view.amount), amount);
+    }
+
+    // The view starts at any offset, like the header read from the data of a locking account
+    #[test]
+    fn test_header_view(header in header(), offset in 0usize..8) {
+        let mut data = vec![0; offset + LockScheduleHeader::LEN];
+        header.pack_into_slice(&mut data[offset..]);
+        let view = PodLockScheduleHeader::try_from_bytes(&data[offset..]).unwrap();
+        prop_assert_eq!(view.destination_address, header.destination_address);
+        prop_assert_eq!(view.mint_address, header.mint_address);
+        prop_assert_eq!(view.is_initialized == 1, header.is_initialized);
+        prop_assert_eq!(view.pending_destination_address, header.pending_destination_address);
+        prop_assert_eq!(u64::from(view.total_amount), header.total_amount);
+        prop_assert_eq!(u64::from(view.claimed_amount), header.claimed_amount);
+        prop_assert_eq!(u32::from(view.schedule_count), header.schedule_count);
+        prop_assert_eq!(u32::from(view.unlocked_count), header.unlocked_count);
+        prop_assert_eq!(view.rescue_destination_address, header.rescue_destination_address);
+        prop_assert_eq!(u64::from(view.rescue_execute_after), header.rescue_execute_after);
+        prop_assert_eq!(view.creator_address, header.creator_address);
+        prop_assert_eq!(TimeBasis::from_u8(view.time_basis), Some(header.time_basis));
+        for (view_split, split) in view.split_destinations.iter().zip(header.split_destinations.iter()) {
+            prop_assert_eq!(view_split.destination_address, split.destination_address);
+            prop_assert_eq!(u16::from(view_split.share_bps), split.share_bps);
+        }
+        prop_assert_eq!(view.version, HEADER_VERSION);
+        prop_assert_eq!(view.delegate_address, header.delegate_address);
+        prop_assert_eq!(u64::from(view.permit_nonce), header.permit_nonce);
+        prop_assert_eq!(u32::from(view.min_claim_interval_secs), header.min_claim_interval_secs);
+        prop_assert_eq!(u64::from(view.last_claim_ts), header.last_claim_ts);
+        prop_assert_eq!(u64::from(view.lock_nonce), header.lock_nonce);
+        prop_assert_eq!(view.lock_bump, header.lock_bump);
+        prop_assert_eq!(i64::from(view.created_at), header.created_at);
+
+        let view = PodLockScheduleHeader::try_from_bytes_mut(&mut data[offset..]).unwrap();
+        view.unlocked_count = header.schedule_count.into();
+        let unpacked = LockScheduleHeader::unpack_from_slice(&data[offset..]).unwrap();
+        prop_assert_eq!(unpacked.unlocked_count, header.schedule_count);
+        prop_assert_eq!(unpacked.time_basis, header.time_basis);
+        prop_assert_eq!(unpacked.is_multi, header.is_multi);
+        prop_assert_eq!(unpacked.is_derived, header.is_derived);
+        prop_assert_eq!(unpacked.split_destinations, header.split_destinations);
+    }
+
+    // Headers of the V9_LEN layout keep everything but the creation time, which is unknown
+    #[test]
+    fn test_header_round_trip(mut header in header()) {
+        let mut data = [0; LockScheduleHeader::LEN];
+        header.pack_into_slice(&mut data);
+        prop_assert_eq!(&LockScheduleHeader::unpack_from_slice(&data).unwrap(), &header);
+
+        let mut data = [0; LockScheduleHeader::V9_LEN];
+        header.pack_into_slice(&mut data);
+        header.created_at = 0;
+        prop_assert_eq!(LockScheduleHeader::unpack_from_slice(&data).unwrap(), header);
+    }
+
+    #[test]
+    fn test_global_state_view(
+        admin in pubkey(),
+        fees_in_usd in any::<u64>(),
+        min_lock_amount in any::<u64>(),
+        fee_bps_in_token in any::<u16>(),
+        owner_token_mint in pubkey(),
+        tier in any::<(u64, u16)>(),
+        max_lock_duration_secs in any::<u64>(),
+        paused_at in any::<u64>(),
+        paused_by in pubkey(),
+        pending_candidate_wallets in prop::array::uniform4(pubkey()),
+        candidate_wallets_execute_after in any::<u64>(),
+        pauses in any::<(bool, bool, bool)>(),
+    ) {
+        let mut fee_discount_tiers = [FeeDiscountTier::default(); MAX_FEE_DISCOUNT_TIERS];
+        fee_discount_tiers[MAX_FEE_DISCOUNT_TIERS - 1] = FeeDiscountTier { min_balance: tier.0, discount_bps: tier.1 };
+        let state = LockGlobalState {
+            price_estimator: Pubkey::new_unique(),
+            usd_token_address: Pubkey::new_unique(),
+            fees_in_usd,
+            company_wallet: Pubkey::new_unique(),
+            max_price_age_slots: 0,
+            candidate_wallets: [Pubkey::new_unique(); MAX_CANDIDATE_WALLETS],
+            admin,
+            pending_admin: Pubkey::default(),
+            min_lock_duration_secs: 0,
+            min_lock_amount,
+            fee_bps_in_token,
+            owner_token_mint,
+            fee_discount_tiers,
+            max_lock_duration_secs,
+            paused_at,
+            paused_by,
+            pending_candidate_wallets,
+            candidate_wallets_execute_after,
+            pause_create: pauses.0,
+            pause_unlock: pauses.1,
+            pause_transfer: pauses.2,
+            is_initialized: true,
+        };
+        let mut data = [0; LockGlobalState::LEN];
+        state.pack_into_slice(&mut data);
+        let view = PodLockGlobalState::try_from_bytes(&data).unwrap();
+        prop_assert_eq!(view.admin, admin);
+        prop_assert_eq!(u64::from(view.fees_in_usd), fees_in_usd);
+        prop_assert_eq!(u64::from(view.min_lock_amount), min_lock_amount);
+        prop_assert_eq!(u16::from(view.fee_bps_in_token), fee_bps_in_token);
+        prop_assert_eq!(view.owner_token_mint, owner_token_mint);
+        let view_tier = view.fee_discount_tiers[MAX_FEE_DISCOUNT_TIERS - 1];
+        prop_assert_eq!((u64::from(view_tier.min_balance), u16::from(view_tier.discount_bps)), tier);
+        prop_assert_eq!(view.candidate_wallets, state.candidate_wallets);
+        prop_assert_eq!(u64::from(view.max_lock_duration_secs), max_lock_duration_secs);
+        prop_assert_eq!(u64::from(view.paused_at), paused_at);
+        prop_assert_eq!(view.paused_by, paused_by);
+        prop_assert_eq!(view.pending_candidate_wallets, pending_candidate_wallets);
+        prop_assert_eq!(u64::from(view.candidate_wallets_execute_after), candidate_wallets_execute_after);
+        prop_assert_eq!(view.version, GLOBAL_STATE_VERSION);
+        prop_assert_eq!(view.pause_create == 1, pauses.0);
+        prop_assert_eq!(view.pause_unlock == 1, pauses.1);
+        prop_assert_eq!(view.pause_transfer == 1, pauses.2);
+        prop_assert_eq!(view.is_initialized, 1);
+    }
+
+    #[test]
+    fn test_short_data_is_rejected(len in 0..LockScheduleHeader::LEN) {
+        prop_assert!(PodLockScheduleHeader::try_from_bytes(&vec![0; len]).is_err());
+    }
+}
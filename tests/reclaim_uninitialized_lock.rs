@@ -0,0 +1,110 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    instruction::{init, reclaim_uninitialized_lock, LockSeed, Schedule},
+    state::LockScheduleHeader,
+};
+use solana_program::{instruction::InstructionError, pubkey::Pubkey, system_instruction, system_program, sysvar};
+use solana_sdk::signature::{Keypair, Signer};
+
+const INIT_PAYER_LAMPORTS: u64 = 1_000_000_000;
+
+// Inits a locking account of one schedule paid by a new funded keypair
+async fn init_lock(env: &mut TestEnv) -> (LockSeed, Pubkey, Keypair) {
+    let init_payer = Keypair::new();
+    let instruction = system_instruction::transfer(&env.context.payer.pubkey(), &init_payer.pubkey(), INIT_PAYER_LAMPORTS);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    let seeds = LockSeed::random(&env.program_id);
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    let instruction = init(
+        &system_program::id(),
+        &sysvar::rent::id(),
+        &env.program_id,
+        &init_payer.pubkey(),
+        &locking_account,
+        seeds,
+        1,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[&init_payer]).await.unwrap();
+    (seeds, locking_account, init_payer)
+}
+
+fn reclaim_instruction(env: &TestEnv, seeds: LockSeed, payer: &Pubkey) -> solana_program::instruction::Instruction {
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    reclaim_uninitialized_lock(&env.program_id, &locking_account, payer, seeds).unwrap()
+}
+
+#[tokio::test]
+async fn test_reclaim_after_failed_create() {
+    let mut env = setup(true).await;
+    let (seeds, locking_account, init_payer) = init_lock(&mut env).await;
+    let data = get_account(&mut env.context, &locking_account).await.data;
+    assert_eq!(LockScheduleHeader::unpack_init_payer(&data), Some(init_payer.pubkey()));
+    assert!(get_account(&mut env.context, &init_payer.pubkey()).await.lamports < INIT_PAYER_LAMPORTS);
+
+    // The source holds less than the schedule, so Create fails and leaves the locking account as Init made it
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 10).await;
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+    let start = now(&mut env.context).await;
+    let schedules = vec![Schedule { release_time: start + 100, amount: 100 }];
+    let instruction = create_lock_instruction(&env, seeds, &payer, &source, &destination, schedules, false, &[]);
+    assert!(process(&mut env.context, &[instruction], &[]).await.is_err());
+
+    let instruction = reclaim_instruction(&env, seeds, &init_payer.pubkey());
+    process(&mut env.context, &[instruction], &[&init_payer]).await.unwrap();
+    assert!(env.context.banks_client.get_account(locking_account).await.unwrap().is_none());
+    assert_eq!(get_account(&mut env.context, &init_payer.pubkey()).await.lamports, INIT_PAYER_LAMPORTS);
+
+    // The seeds can be used again
+    let instruction = init_instruction(&env, seeds, 1);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_reclaim_requires_init_payer() {
+    let mut env = setup(true).await;
+    let (seeds, locking_account, _) = init_lock(&mut env).await;
+    let stranger = Keypair::new();
+
+    let instruction = reclaim_instruction(&env, seeds, &stranger.pubkey());
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&stranger]).await,
+        InstructionError::InvalidArgument,
+    );
+    assert_eq!(get_account(&mut env.context, &locking_account).await.owner, env.program_id);
+}
+
+#[tokio::test]
+async fn test_reclaim_rejects_initialized_lock() {
+    let mut env = setup(true).await;
+    let (seeds, locking_account, init_payer) = init_lock(&mut env).await;
+
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 100).await;
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+    let start = now(&mut env.context).await;
+    let schedules = vec![Schedule { release_time: start + 100, amount: 100 }];
+    let instruction = create_lock_instruction(&env, seeds, &payer, &source, &destination, schedules, false, &[]);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    // Create replaced the payer of Init with the destination
+    let data = get_account(&mut env.context, &locking_account).await.data;
+    assert_eq!(LockScheduleHeader::unpack_init_payer(&data), None);
+
+    let instruction = reclaim_instruction(&env, seeds, &init_payer.pubkey());
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&init_payer]).await,
+        InstructionError::AccountAlreadyInitialized,
+    );
+    assert_eq!(get_account(&mut env.context, &locking_account).await.owner, env.program_id);
+}
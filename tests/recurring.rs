@@ -0,0 +1,114 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    client::unpack_lock_header,
+    instruction::{create_recurring, LockSeed, MAX_RECURRING_PERIODS},
+    state::{unpack_schedules, LockSchedule, LockScheduleHeader},
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+use solana_sdk::signature::{Keypair, Signer};
+
+// CreateRecurring locking from a token account of the context payer
+fn create_recurring_instruction(
+    env: &TestEnv,
+    seeds: LockSeed,
+    source: &Pubkey,
+    destination: &Pubkey,
+    start_time: u64,
+    period_secs: u64,
+    periods: u32,
+) -> Instruction {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    create_recurring(
+        &env.program_id,
+        &spl_token::id(),
+        &system_program::id(),
+        &sysvar::rent::id(),
+        &spl_associated_token_account::id(),
+        &env.price_estimator,
+        &locking_account,
+        &get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id()),
+        &payer,
+        source,
+        &payer,
+        destination,
+        &env.company_wallet,
+        &mint,
+        seeds,
+        start_time,
+        period_secs,
+        periods,
+        10,
+    )
+    .unwrap()
+}
+
+// Three periods of 10 tokens, 50 seconds apart, are stored as three schedules released one by one
+#[tokio::test]
+async fn test_create_recurring() {
+    let mut env = setup(true).await;
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 30).await;
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+
+    let start = now(&mut env.context).await;
+    let seeds = LockSeed::random(&env.program_id);
+    let instructions = [
+        init_instruction(&env, seeds, 3),
+        create_recurring_instruction(&env, seeds, &source, &destination, start + 100, 50, 3),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    let data = get_account(&mut env.context, &locking_account).await.data;
+    let header = unpack_lock_header(&data).unwrap();
+    assert_eq!(header.total_amount, 30);
+    assert_eq!(header.schedule_count, 3);
+    assert_eq!(
+        unpack_schedules(&data[LockScheduleHeader::header_len(data.len())..]).unwrap(),
+        vec![
+            LockSchedule { release_time: start + 100, amount: 10 },
+            LockSchedule { release_time: start + 150, amount: 10 },
+            LockSchedule { release_time: start + 200, amount: 10 },
+        ]
+    );
+    assert_eq!(token_balance(&mut env.context, &source).await, 0);
+
+    warp_to_timestamp(&mut env.context, start + 150).await;
+    let instruction = unlock_instruction(&env, seeds, &destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &destination).await, 20);
+}
+
+#[tokio::test]
+async fn test_create_recurring_rejects_invalid_periods() {
+    let mut env = setup(true).await;
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+    let start = now(&mut env.context).await;
+
+    // A zero period, no periods at all, and more periods than a lock holds schedules
+    for &(period_secs, periods) in [(0, 3), (50, 0), (50, MAX_RECURRING_PERIODS + 1)].iter() {
+        let seeds = LockSeed::random(&env.program_id);
+        let instruction =
+            create_recurring_instruction(&env, seeds, &source, &destination, start + 100, period_secs, periods);
+        assert_instruction_error(
+            process(&mut env.context, &[instruction], &[]).await,
+            InstructionError::InvalidArgument,
+        );
+    }
+}
@@ -0,0 +1,68 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    events::{decode, LockExtended},
+    instruction::{relock, LockSeed, Schedule},
+    state::{unpack_schedules, LockSchedule, LockScheduleHeader},
+};
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+#[tokio::test]
+async fn test_relock_emits_lock_extended_with_the_sorted_index() {
+    let mut env = setup(true).await;
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 600).await;
+    let beneficiary = Keypair::new();
+    let destination = create_token_account(&mut env.context, &mint, &beneficiary.pubkey()).await;
+
+    let start = now(&mut env.context).await;
+    let schedules = vec![
+        Schedule { release_time: start + 100, amount: 100 },
+        Schedule { release_time: start + 200, amount: 200 },
+        Schedule { release_time: start + 300, amount: 300 },
+    ];
+    let seeds = LockSeed::random(&env.program_id);
+    let instructions = [
+        init_instruction(&env, seeds, schedules.len() as u32),
+        create_lock_instruction(&env, seeds, &payer, &source, &destination, schedules, false, &[]),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+
+    // The first schedule is relocked past the second one
+    warp_to_timestamp(&mut env.context, start + 100).await;
+    let instruction =
+        relock(&env.program_id, &locking_account, &beneficiary.pubkey(), &destination, seeds, 0, start + 250).unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&env.context.payer.pubkey()),
+        &[&env.context.payer, &beneficiary],
+        env.context.last_blockhash,
+    );
+    let result = process_with_metadata(&mut env.context, transaction).await;
+    result.result.unwrap();
+    let metadata = result.metadata.unwrap();
+    let event = metadata.log_messages.iter().find_map(|log| decode::<LockExtended>(log)).unwrap();
+    assert_eq!(
+        event,
+        LockExtended {
+            locking_account,
+            index: 1,
+            old_release_time: start + 100,
+            new_release_time: start + 250,
+        }
+    );
+
+    let data = get_account(&mut env.context, &locking_account).await.data;
+    let header_len = LockScheduleHeader::header_len(data.len());
+    let schedules = unpack_schedules(&data[header_len..]).unwrap();
+    assert_eq!(schedules[1], LockSchedule { release_time: start + 250, amount: 100 });
+}
@@ -0,0 +1,70 @@
+use lock_token::{
+    instruction::LockSeed,
+    pda::{find_lock_address, LockSigner},
+    utils::{signed_seeds_for, signed_seeds_with_bump_for},
+};
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+// Signer seeds are only handed out for the account they derive, so the wrong pair never reaches invoke_signed
+#[test]
+fn test_signed_seeds_for_rejects_other_accounts() {
+    let program_id = Pubkey::new_unique();
+    let seeds = LockSeed::random(&program_id);
+    let other_seeds = LockSeed::random(&program_id);
+    let locking_account = seeds.locking_account_address(&program_id).unwrap();
+
+    assert_eq!(signed_seeds_for(&locking_account, seeds.as_ref(), &program_id), Ok([seeds.as_ref()]));
+    assert_eq!(
+        signed_seeds_for(&locking_account, other_seeds.as_ref(), &program_id),
+        Err(ProgramError::InvalidSeeds)
+    );
+    assert_eq!(
+        signed_seeds_for(&locking_account, seeds.as_ref(), &Pubkey::new_unique()),
+        Err(ProgramError::InvalidSeeds)
+    );
+    assert_eq!(
+        signed_seeds_for(&Pubkey::new_unique(), seeds.as_ref(), &program_id),
+        Err(ProgramError::InvalidSeeds)
+    );
+}
+
+#[test]
+fn test_signed_seeds_with_bump_for_rejects_other_bumps() {
+    let program_id = Pubkey::new_unique();
+    let seed = b"seed";
+    let (address, bump) = Pubkey::find_program_address(&[seed], &program_id);
+
+    assert_eq!(
+        signed_seeds_with_bump_for(&address, seed, &[bump], &program_id),
+        Ok([&seed[..], &[bump][..]])
+    );
+    assert_eq!(
+        signed_seeds_with_bump_for(&Pubkey::new_unique(), seed, &[bump], &program_id),
+        Err(ProgramError::InvalidSeeds)
+    );
+    assert_eq!(
+        signed_seeds_with_bump_for(&address, b"other", &[bump], &program_id),
+        Err(ProgramError::InvalidSeeds)
+    );
+}
+
+#[test]
+fn test_lock_signer_only_signs_for_its_locking_account() {
+    let program_id = Pubkey::new_unique();
+    let creator = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+    let (locking_account, bump) = find_lock_address(&program_id, &creator, &mint, 3);
+    let signer = LockSigner::derived(creator, mint, 3, bump);
+    assert_eq!(signer.signed_seeds_for(&locking_account, &program_id), Ok(signer.seeds()));
+
+    // The lock of another nonce, and the same derivation under another program
+    let (other_locking_account, _) = find_lock_address(&program_id, &creator, &mint, 4);
+    assert_eq!(signer.signed_seeds_for(&other_locking_account, &program_id), Err(ProgramError::InvalidSeeds));
+    assert!(signer.signed_seeds_for(&locking_account, &Pubkey::new_unique()).is_err());
+
+    let seeds = LockSeed::random(&program_id);
+    let signer = LockSigner::Seeds(seeds);
+    let seeds_locking_account = seeds.locking_account_address(&program_id).unwrap();
+    assert_eq!(signer.signed_seeds_for(&seeds_locking_account, &program_id), Ok(vec![seeds.as_ref()]));
+    assert_eq!(signer.signed_seeds_for(&locking_account, &program_id), Err(ProgramError::InvalidSeeds));
+}
@@ -0,0 +1,226 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    error::LockTokenError,
+    instruction::{create, extend_lock_duration, CreateFeePayment, LockSeed, Schedule},
+    state::{unpack_schedules, LockScheduleHeader},
+    time::TimeBasis,
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program,
+    sysvar::{self, clock::Clock},
+};
+use solana_sdk::signature::{Keypair, Signer};
+
+struct Lock {
+    seeds: LockSeed,
+    locking_account: Pubkey,
+    destination: Pubkey,
+}
+
+async fn current_slot(env: &mut TestEnv) -> u64 {
+    let clock: Clock = env.context.banks_client.get_sysvar().await.unwrap();
+    clock.slot
+}
+
+async fn warp_to_slot(env: &mut TestEnv, slot: u64) {
+    env.context.warp_to_slot(slot).unwrap();
+    refresh_blockhash(&mut env.context).await;
+}
+
+fn create_instruction(
+    env: &TestEnv,
+    seeds: LockSeed,
+    source: &Pubkey,
+    destination: &Pubkey,
+    schedules: Vec<Schedule>,
+    time_basis: TimeBasis,
+) -> Instruction {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    create(
+        &env.program_id,
+        &spl_token::id(),
+        &system_program::id(),
+        &sysvar::rent::id(),
+        &spl_associated_token_account::id(),
+        &env.price_estimator,
+        &locking_account,
+        &get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id()),
+        &payer,
+        source,
+        &payer,
+        destination,
+        &env.company_wallet,
+        &mint,
+        schedules,
+        seeds,
+        0,
+        false,
+        time_basis,
+        Vec::new(),
+        0,
+        CreateFeePayment::Sol,
+        None,
+        false,
+        &[],
+    )
+    .unwrap()
+}
+
+// Locks 300 of the context payer's tokens in two schedules of 100 and 200, beneficiary owning the destination
+async fn create_lock(env: &mut TestEnv, beneficiary: &Pubkey, schedules: Vec<Schedule>, time_basis: TimeBasis) -> Lock {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 300).await;
+    let destination = create_token_account(&mut env.context, &mint, beneficiary).await;
+
+    let seeds = LockSeed::random(&env.program_id);
+    let instructions = [
+        init_instruction(env, seeds, schedules.len() as u32),
+        create_instruction(env, seeds, &source, &destination, schedules, time_basis),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+
+    Lock {
+        seeds,
+        locking_account: seeds.locking_account_address(&env.program_id).unwrap(),
+        destination,
+    }
+}
+
+async fn read_header(env: &mut TestEnv, lock: &Lock) -> LockScheduleHeader {
+    let data = get_account(&mut env.context, &lock.locking_account).await.data;
+    let header_len = LockScheduleHeader::header_len(data.len());
+    LockScheduleHeader::unpack_from_slice(&data[..header_len]).unwrap()
+}
+
+#[tokio::test]
+async fn test_slot_lock_unlocks_at_slot() {
+    let mut env = setup(true).await;
+    let start = current_slot(&mut env).await;
+    let schedules = vec![
+        Schedule { release_time: start + 100, amount: 100 },
+        Schedule { release_time: start + 200, amount: 200 },
+    ];
+    let lock = create_lock(&mut env, &Keypair::new().pubkey(), schedules, TimeBasis::Slot).await;
+    assert_eq!(read_header(&mut env, &lock).await.time_basis, TimeBasis::Slot);
+
+    // The unix timestamp is far past every release slot, which a slot lock ignores
+    let timestamp = now(&mut env.context).await;
+    warp_to_timestamp(&mut env.context, timestamp + 1_000).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::InvalidArgument,
+    );
+
+    warp_to_slot(&mut env, start + 150).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 100);
+
+    warp_to_slot(&mut env, start + 200).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 300);
+    assert_eq!(read_header(&mut env, &lock).await.claimed_amount, 300);
+}
+
+#[tokio::test]
+async fn test_unix_lock_ignores_slot() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let schedules = vec![
+        Schedule { release_time: start + 100, amount: 100 },
+        Schedule { release_time: start + 200, amount: 200 },
+    ];
+    let lock = create_lock(&mut env, &Keypair::new().pubkey(), schedules, TimeBasis::UnixTimestamp).await;
+    assert_eq!(read_header(&mut env, &lock).await.time_basis, TimeBasis::UnixTimestamp);
+
+    // The slot passes the release times of the unix lock, only its timestamp counts
+    let slot = current_slot(&mut env).await;
+    warp_to_slot(&mut env, slot + 1_000).await;
+    warp_to_timestamp(&mut env.context, start + 50).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::InvalidArgument,
+    );
+
+    warp_to_timestamp(&mut env.context, start + 100).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 100);
+}
+
+#[tokio::test]
+async fn test_slot_lock_rejects_past_slot() {
+    let mut env = setup(true).await;
+    let start = current_slot(&mut env).await + 10;
+    warp_to_slot(&mut env, start).await;
+
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 300).await;
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+    let seeds = LockSeed::random(&env.program_id);
+    let schedules = vec![Schedule { release_time: start - 1, amount: 300 }];
+    let instruction = init_instruction(&env, seeds, 1);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    let instruction = create_instruction(&env, seeds, &source, &destination, schedules, TimeBasis::Slot);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::Custom(LockTokenError::ReleaseTimeInPast as u32),
+    );
+}
+
+#[tokio::test]
+async fn test_extend_slot_lock() {
+    let mut env = setup(true).await;
+    let beneficiary = Keypair::new();
+    let start = current_slot(&mut env).await;
+    let schedules = vec![
+        Schedule { release_time: start + 100, amount: 100 },
+        Schedule { release_time: start + 200, amount: 200 },
+    ];
+    let lock = create_lock(&mut env, &beneficiary.pubkey(), schedules, TimeBasis::Slot).await;
+
+    // A slot height is far below the smallest unix timestamp accepted by a unix lock
+    let instruction = extend_lock_duration(
+        &env.program_id,
+        &lock.locking_account,
+        &beneficiary.pubkey(),
+        &lock.destination,
+        lock.seeds,
+        0,
+        start + 150,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[&beneficiary]).await.unwrap();
+    let data = get_account(&mut env.context, &lock.locking_account).await.data;
+    let header_len = LockScheduleHeader::header_len(data.len());
+    assert_eq!(unpack_schedules(&data[header_len..]).unwrap()[0].release_time, start + 150);
+
+    warp_to_slot(&mut env, start + 120).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::InvalidArgument,
+    );
+
+    warp_to_slot(&mut env, start + 150).await;
+    let instruction = unlock_instruction(&env, lock.seeds, &lock.destination);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 100);
+}
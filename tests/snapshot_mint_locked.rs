@@ -0,0 +1,139 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    client::{mint_locked_snapshot, MintLockedSnapshot},
+    error::LockTokenError,
+    instruction::{snapshot_mint_locked, LockSeed, Schedule},
+    state::{token_state_address, TokenState},
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    signature::{Keypair, Signer},
+};
+
+// Locks amount of the mint for a fresh beneficiary and returns its locking account and locking token account
+async fn create_lock(env: &mut TestEnv, amount: u64) -> (Pubkey, Pubkey) {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, amount).await;
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+
+    let start = now(&mut env.context).await;
+    let schedules = vec![Schedule { release_time: start + 100, amount }];
+    let seeds = LockSeed::random(&env.program_id);
+    let instructions = [
+        init_instruction(env, seeds, schedules.len() as u32),
+        create_lock_instruction(env, seeds, &payer, &source, &destination, schedules, false, &[]),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    let locking_token_account = get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id());
+    (locking_account, locking_token_account)
+}
+
+fn snapshot_instruction(env: &TestEnv, locks: &[(Pubkey, Pubkey)]) -> Instruction {
+    snapshot_mint_locked(
+        &env.program_id,
+        &spl_token::id(),
+        &system_program::id(),
+        &env.context.payer.pubkey(),
+        &env.mint.pubkey(),
+        locks,
+    )
+    .unwrap()
+}
+
+async fn snapshot(env: &mut TestEnv) -> Option<MintLockedSnapshot> {
+    let token_state_account_key = token_state_address(&env.program_id, &env.mint.pubkey()).unwrap();
+    let data = get_account(&mut env.context, &token_state_account_key).await.data;
+    mint_locked_snapshot(&env.mint.pubkey(), &data).unwrap()
+}
+
+#[tokio::test]
+async fn test_snapshot_mint_locked() {
+    let mut env = setup(true).await;
+    assert_eq!(snapshot(&mut env).await, None);
+    let first = create_lock(&mut env, 100).await;
+    let second = create_lock(&mut env, 250).await;
+
+    let instruction = snapshot_instruction(&env, &[first, second]);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    let timestamp = now(&mut env.context).await as i64;
+    assert_eq!(snapshot(&mut env).await, Some(MintLockedSnapshot { locked_amount: 350, timestamp }));
+}
+
+#[tokio::test]
+async fn test_snapshot_rejects_fake_escrows() {
+    let mut env = setup(true).await;
+    let lock = create_lock(&mut env, 100).await;
+
+    // A token account of the mint owned by a wallet rather than a lock
+    let wallet = Keypair::new().pubkey();
+    let wallet_token_account = create_token_account(&mut env.context, &env.mint.pubkey(), &wallet).await;
+    mint_to(&mut env.context, &env.mint.pubkey(), &wallet_token_account, 1_000).await;
+    let instruction = snapshot_instruction(&env, &[lock, (wallet, wallet_token_account)]);
+    assert_instruction_error(process(&mut env.context, &[instruction], &[]).await, InstructionError::InvalidArgument);
+
+    // A lock with a token account it does not own
+    let instruction = snapshot_instruction(&env, &[(lock.0, wallet_token_account)]);
+    assert_instruction_error(process(&mut env.context, &[instruction], &[]).await, InstructionError::InvalidArgument);
+
+    // A token account of another mint owned by the lock
+    let other_mint = Keypair::new();
+    create_mint(&mut env.context, &other_mint).await;
+    let other_token_account = create_token_account(&mut env.context, &other_mint.pubkey(), &lock.0).await;
+    let instruction = snapshot_instruction(&env, &[(lock.0, other_token_account)]);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::Custom(LockTokenError::VaultMintMismatch as u32),
+    );
+
+    // Every lock counts once
+    let instruction = snapshot_instruction(&env, &[lock, lock]);
+    assert_instruction_error(process(&mut env.context, &[instruction], &[]).await, InstructionError::InvalidArgument);
+
+    assert_eq!(snapshot(&mut env).await, None);
+}
+
+#[tokio::test]
+async fn test_snapshot_grows_version_2_token_state() {
+    let mut env = setup(true).await;
+    let lock = create_lock(&mut env, 100).await;
+
+    let token_state_account_key = token_state_address(&env.program_id, &env.mint.pubkey()).unwrap();
+    let token_state = TokenState::unpack_from_slice(&get_account(&mut env.context, &token_state_account_key).await.data)
+        .unwrap();
+    let mut data = vec![0; TokenState::V2_LEN];
+    token_state.pack_into_slice(&mut data);
+    let account = Account {
+        lamports: 1_000_000_000,
+        data,
+        owner: env.program_id,
+        executable: false,
+        rent_epoch: 0,
+    };
+    env.context.set_account(&token_state_account_key, &AccountSharedData::from(account));
+    assert_eq!(snapshot(&mut env).await, None);
+
+    let instruction = snapshot_instruction(&env, &[lock]);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    let data = get_account(&mut env.context, &token_state_account_key).await.data;
+    assert_eq!(data.len(), TokenState::LEN);
+    let unpacked = TokenState::unpack_from_slice(&data).unwrap();
+    assert_eq!(unpacked.last_snapshot_amount, 100);
+    assert_eq!(unpacked.total_locked_amount, token_state.total_locked_amount);
+}
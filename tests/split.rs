@@ -0,0 +1,270 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    client::unpack_lock_header,
+    error::LockTokenError,
+    instruction::{create, transfer_locks, CreateFeePayment, LockSeed, Schedule},
+    state::SplitDestination,
+    time::TimeBasis,
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    system_program,
+    sysvar,
+};
+use solana_sdk::signature::{Keypair, Signer};
+
+struct SplitLock {
+    seeds: LockSeed,
+    locking_account: Pubkey,
+    locking_token_account: Pubkey,
+    // The contributor owns the destination of the lock, the treasury the second split destination
+    destination: Pubkey,
+    contributor: Keypair,
+    treasury_destination: Pubkey,
+    treasury: Keypair,
+    start: u64,
+}
+
+fn create_instruction(
+    env: &TestEnv,
+    seeds: LockSeed,
+    source: &Pubkey,
+    destination: &Pubkey,
+    schedules: Vec<Schedule>,
+    split_destinations: Vec<SplitDestination>,
+) -> Instruction {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    create(
+        &env.program_id,
+        &spl_token::id(),
+        &system_program::id(),
+        &sysvar::rent::id(),
+        &spl_associated_token_account::id(),
+        &env.price_estimator,
+        &locking_account,
+        &get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id()),
+        &payer,
+        source,
+        &payer,
+        destination,
+        &env.company_wallet,
+        &mint,
+        schedules,
+        seeds,
+        0,
+        false,
+        TimeBasis::UnixTimestamp,
+        split_destinations,
+        0,
+        CreateFeePayment::Sol,
+        None,
+        false,
+        &[],
+    )
+    .unwrap()
+}
+
+// Locks 1_501 of the context payer's tokens in schedules of 1_001 at start + 100 and 500 at start + 200,
+// split 80% to the contributor and 20% to the treasury
+async fn create_split_lock(env: &mut TestEnv) -> SplitLock {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 1_501).await;
+    let contributor = Keypair::new();
+    let destination = create_token_account(&mut env.context, &mint, &contributor.pubkey()).await;
+    let treasury = Keypair::new();
+    let treasury_destination = create_token_account(&mut env.context, &mint, &treasury.pubkey()).await;
+
+    let start = now(&mut env.context).await;
+    let schedules = vec![
+        Schedule { release_time: start + 100, amount: 1_001 },
+        Schedule { release_time: start + 200, amount: 500 },
+    ];
+    let split_destinations = vec![
+        SplitDestination { destination_address: destination, share_bps: 8_000 },
+        SplitDestination { destination_address: treasury_destination, share_bps: 2_000 },
+    ];
+    let seeds = LockSeed::random(&env.program_id);
+    let instructions = [
+        init_instruction(env, seeds, schedules.len() as u32),
+        create_instruction(env, seeds, &source, &destination, schedules, split_destinations),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    SplitLock {
+        seeds,
+        locking_account,
+        locking_token_account: get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id()),
+        destination,
+        contributor,
+        treasury_destination,
+        treasury,
+        start,
+    }
+}
+
+#[tokio::test]
+async fn test_unlock_splits_by_share() {
+    let mut env = setup(true).await;
+    let lock = create_split_lock(&mut env).await;
+    let header = unpack_lock_header(&get_account(&mut env.context, &lock.locking_account).await.data).unwrap();
+    assert_eq!(header.split().len(), 2);
+    assert_eq!(header.split()[0].destination_address, lock.destination);
+
+    // 20% of 1_001 rounds down to 200, the contributor keeps the dust
+    warp_to_timestamp(&mut env.context, lock.start + 100).await;
+    let instruction = unlock_multi_instruction(&env, lock.seeds, &lock.destination, &[lock.treasury_destination]);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 801);
+    assert_eq!(token_balance(&mut env.context, &lock.treasury_destination).await, 200);
+    assert_eq!(token_balance(&mut env.context, &lock.locking_token_account).await, 500);
+
+    warp_to_timestamp(&mut env.context, lock.start + 200).await;
+    let instruction = unlock_multi_instruction(&env, lock.seeds, &lock.destination, &[lock.treasury_destination]);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 1_201);
+    assert_eq!(token_balance(&mut env.context, &lock.treasury_destination).await, 300);
+    assert_eq!(token_balance(&mut env.context, &lock.locking_token_account).await, 0);
+
+    let header = unpack_lock_header(&get_account(&mut env.context, &lock.locking_account).await.data).unwrap();
+    assert_eq!(header.claimed_amount, 1_501);
+}
+
+#[tokio::test]
+async fn test_unlock_requires_split_destination() {
+    let mut env = setup(true).await;
+    let lock = create_split_lock(&mut env).await;
+
+    warp_to_timestamp(&mut env.context, lock.start + 100).await;
+    let instruction = unlock_multi_instruction(&env, lock.seeds, &lock.destination, &[]);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::NotEnoughAccountKeys,
+    );
+
+    // Another token account of the treasury is not the split destination
+    let mint = env.mint.pubkey();
+    let other_destination = create_token_account(&mut env.context, &mint, &lock.treasury.pubkey()).await;
+    let instruction = unlock_multi_instruction(&env, lock.seeds, &lock.destination, &[other_destination]);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::NotEnoughAccountKeys,
+    );
+    assert_eq!(token_balance(&mut env.context, &lock.locking_token_account).await, 1_501);
+}
+
+#[tokio::test]
+async fn test_create_rejects_invalid_split() {
+    let mut env = setup(true).await;
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 100).await;
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+    let other_destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+    let start = now(&mut env.context).await;
+
+    let invalid_splits = vec![
+        // The shares add up to less than MAX_BPS
+        vec![
+            SplitDestination { destination_address: destination, share_bps: 8_000 },
+            SplitDestination { destination_address: other_destination, share_bps: 1_999 },
+        ],
+        // The first split destination is not the destination of the lock
+        vec![
+            SplitDestination { destination_address: other_destination, share_bps: 8_000 },
+            SplitDestination { destination_address: destination, share_bps: 2_000 },
+        ],
+        // A destination with no share
+        vec![
+            SplitDestination { destination_address: destination, share_bps: 10_000 },
+            SplitDestination { destination_address: other_destination, share_bps: 0 },
+        ],
+        // The same destination twice
+        vec![
+            SplitDestination { destination_address: destination, share_bps: 5_000 },
+            SplitDestination { destination_address: destination, share_bps: 5_000 },
+        ],
+    ];
+    for split_destinations in invalid_splits {
+        let seeds = LockSeed::random(&env.program_id);
+        let instruction = init_instruction(&env, seeds, 1);
+        process(&mut env.context, &[instruction], &[]).await.unwrap();
+        let schedules = vec![Schedule { release_time: start + 100, amount: 100 }];
+        let instruction = create_instruction(&env, seeds, &source, &destination, schedules, split_destinations);
+        assert_instruction_error(
+            process(&mut env.context, &[instruction], &[]).await,
+            InstructionError::Custom(LockTokenError::InvalidSplit as u32),
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_transfer_split_destination() {
+    let mut env = setup(true).await;
+    let lock = create_split_lock(&mut env).await;
+    let mint = env.mint.pubkey();
+    let new_treasury_destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+
+    // The treasury can not move the lock onto a destination it already pays out to
+    let instruction = transfer_locks(
+        &env.program_id,
+        &lock.locking_account,
+        &lock.treasury.pubkey(),
+        &lock.treasury_destination,
+        &lock.destination,
+        lock.seeds,
+    )
+    .unwrap();
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&lock.treasury]).await,
+        InstructionError::InvalidArgument,
+    );
+
+    let instruction = transfer_locks(
+        &env.program_id,
+        &lock.locking_account,
+        &lock.treasury.pubkey(),
+        &lock.treasury_destination,
+        &new_treasury_destination,
+        lock.seeds,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[&lock.treasury]).await.unwrap();
+
+    // Only the split destination of the treasury moved
+    let header = unpack_lock_header(&get_account(&mut env.context, &lock.locking_account).await.data).unwrap();
+    assert_eq!(header.destination_address, lock.destination);
+    assert_eq!(header.split()[1].destination_address, new_treasury_destination);
+    assert_eq!(header.split()[1].share_bps, 2_000);
+
+    // The contributor moving the lock takes the first split destination along
+    let new_destination = create_token_account(&mut env.context, &mint, &lock.contributor.pubkey()).await;
+    let instruction = transfer_locks(
+        &env.program_id,
+        &lock.locking_account,
+        &lock.contributor.pubkey(),
+        &lock.destination,
+        &new_destination,
+        lock.seeds,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[&lock.contributor]).await.unwrap();
+
+    warp_to_timestamp(&mut env.context, lock.start + 100).await;
+    let instruction = unlock_multi_instruction(&env, lock.seeds, &new_destination, &[new_treasury_destination]);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &new_destination).await, 801);
+    assert_eq!(token_balance(&mut env.context, &new_treasury_destination).await, 200);
+    assert_eq!(token_balance(&mut env.context, &lock.treasury_destination).await, 0);
+}
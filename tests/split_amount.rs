@@ -0,0 +1,113 @@
+use lock_token::state::{split_amounts, SplitDestination, MAX_BPS, MAX_SPLIT_DESTINATIONS};
+use proptest::{collection::vec, prelude::*};
+use solana_program::pubkey::Pubkey;
+
+fn split(shares: &[u16]) -> Vec<SplitDestination> {
+    shares
+        .iter()
+        .map(|share_bps| SplitDestination {
+            destination_address: Pubkey::new_unique(),
+            share_bps: *share_bps,
+        })
+        .collect()
+}
+
+// Non-zero shares summing to MAX_BPS, cut at sorted points of the basis points
+fn shares() -> impl Strategy<Value = Vec<u16>> {
+    vec(1..MAX_BPS, 0..MAX_SPLIT_DESTINATIONS).prop_map(|mut cuts| {
+        cuts.sort_unstable();
+        cuts.dedup();
+        cuts.push(MAX_BPS);
+        let mut previous = 0;
+        cuts.into_iter()
+            .map(|cut| {
+                let share = cut - previous;
+                previous = cut;
+                share
+            })
+            .collect()
+    })
+}
+
+// Checks the amounts add up to amount, with every destination but the first rounded down and the first
+// one taking the dust, which is less than one token per destination
+fn assert_split(amount: u64, shares: &[u16]) {
+    let split = split(shares);
+    let amounts = split_amounts(amount, &split).unwrap();
+    assert_eq!(amounts.len(), split.len());
+    for ((destination, _), split) in amounts.iter().zip(split.iter()) {
+        assert_eq!(*destination, split.destination_address);
+    }
+    let total: u128 = amounts.iter().map(|(_, amount)| *amount as u128).sum();
+    assert_eq!(total, amount as u128);
+    for ((_, share), share_bps) in amounts.iter().zip(shares.iter()).skip(1) {
+        assert_eq!(*share as u128, amount as u128 * *share_bps as u128 / MAX_BPS as u128);
+    }
+    let exact_first = amount as u128 * shares[0] as u128 / MAX_BPS as u128;
+    let dust = amounts[0].1 as u128 - exact_first;
+    assert!(dust < shares.len() as u128);
+}
+
+#[test]
+fn test_contributor_and_treasury() {
+    let split = split(&[8_000, 2_000]);
+    let amounts: Vec<u64> = split_amounts(1_000, &split).unwrap().into_iter().map(|(_, amount)| amount).collect();
+    assert_eq!(amounts, vec![800, 200]);
+    // 20% of 7 rounds down to 1, the contributor keeps the dust
+    let amounts: Vec<u64> = split_amounts(7, &split).unwrap().into_iter().map(|(_, amount)| amount).collect();
+    assert_eq!(amounts, vec![6, 1]);
+}
+
+#[test]
+fn test_dust_goes_to_first_destination() {
+    let split = split(&[3_334, 3_333, 3_333]);
+    let amounts: Vec<u64> = split_amounts(2, &split).unwrap().into_iter().map(|(_, amount)| amount).collect();
+    assert_eq!(amounts, vec![2, 0, 0]);
+    let amounts: Vec<u64> = split_amounts(100, &split).unwrap().into_iter().map(|(_, amount)| amount).collect();
+    assert_eq!(amounts, vec![34, 33, 33]);
+}
+
+#[test]
+fn test_single_destination_takes_everything() {
+    assert_split(0, &[MAX_BPS]);
+    assert_split(1, &[MAX_BPS]);
+    assert_split(u64::MAX, &[MAX_BPS]);
+}
+
+#[test]
+fn test_largest_amount() {
+    assert_split(u64::MAX, &[8_000, 2_000]);
+    assert_split(u64::MAX, &[1, 1, 1, MAX_BPS - 3]);
+    assert_split(u64::MAX, &[MAX_BPS - 3, 1, 1, 1]);
+    assert_split(u64::MAX - 1, &[2_500, 2_500, 2_500, 2_500]);
+}
+
+#[test]
+fn test_no_split_releases_nothing() {
+    assert!(split_amounts(100, &[]).unwrap().is_empty());
+}
+
+// Every amount of up to two full rounds of the basis points, across the shares leaving the most dust
+#[test]
+fn test_every_small_amount() {
+    let share_sets: [&[u16]; 6] = [
+        &[8_000, 2_000],
+        &[2_000, 8_000],
+        &[1, MAX_BPS - 1],
+        &[MAX_BPS - 1, 1],
+        &[3_334, 3_333, 3_333],
+        &[9_997, 1, 1, 1],
+    ];
+    for shares in share_sets.iter() {
+        for amount in 0..=2 * MAX_BPS as u64 {
+            assert_split(amount, shares);
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn test_split_balances(amount in any::<u64>(), shares in shares()) {
+        assert_split(amount, &shares);
+    }
+}
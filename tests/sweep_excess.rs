@@ -0,0 +1,171 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    client::unpack_lock_header,
+    error::LockTokenError,
+    instruction::{sweep_excess, unlock, LockSeed, Schedule},
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+};
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+struct Lock {
+    seeds: LockSeed,
+    locking_account: Pubkey,
+    locking_token_account: Pubkey,
+    source: Pubkey,
+    destination: Pubkey,
+    start: u64,
+}
+
+// Locks 300 of the context payer's tokens in two schedules of 100 and 200
+async fn create_lock(env: &mut TestEnv) -> Lock {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 300).await;
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+
+    let start = now(&mut env.context).await;
+    let schedules = vec![
+        Schedule { release_time: start + 100, amount: 100 },
+        Schedule { release_time: start + 200, amount: 200 },
+    ];
+    let seeds = LockSeed::random(&env.program_id);
+    let instructions = [
+        init_instruction(env, seeds, schedules.len() as u32),
+        create_lock_instruction(env, seeds, &payer, &source, &destination, schedules, false, &[]),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    Lock {
+        seeds,
+        locking_account,
+        locking_token_account: get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id()),
+        source,
+        destination,
+        start,
+    }
+}
+
+fn sweep_instruction(env: &TestEnv, lock: &Lock, creator_token_account: &Pubkey) -> Instruction {
+    sweep_excess(
+        &env.program_id,
+        &spl_token::id(),
+        &lock.locking_account,
+        &lock.locking_token_account,
+        creator_token_account,
+        &env.mint.pubkey(),
+        lock.seeds,
+    )
+    .unwrap()
+}
+
+fn assert_no_excess_error(result: Result<(), TransactionError>) {
+    assert_instruction_error(result, InstructionError::Custom(LockTokenError::NoExcessTokens as u32));
+}
+
+#[tokio::test]
+async fn test_sweep_excess() {
+    let mut env = setup(true).await;
+    let lock = create_lock(&mut env).await;
+    let data = get_account(&mut env.context, &lock.locking_account).await.data;
+    assert_eq!(unpack_lock_header(&data).unwrap().creator_address, env.context.payer.pubkey());
+
+    let instruction = sweep_instruction(&env, &lock, &lock.source);
+    assert_no_excess_error(process(&mut env.context, &[instruction], &[]).await);
+
+    let mint = env.mint.pubkey();
+    mint_to(&mut env.context, &mint, &lock.locking_token_account, 42).await;
+    refresh_blockhash(&mut env.context).await;
+    let instruction = sweep_instruction(&env, &lock, &lock.source);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.source).await, 42);
+    assert_eq!(token_balance(&mut env.context, &lock.locking_token_account).await, 300);
+
+    // Only the excess moves, so sweeping again finds nothing
+    refresh_blockhash(&mut env.context).await;
+    let instruction = sweep_instruction(&env, &lock, &lock.source);
+    assert_no_excess_error(process(&mut env.context, &[instruction], &[]).await);
+}
+
+#[tokio::test]
+async fn test_sweep_excess_of_partially_unlocked_lock() {
+    let mut env = setup(true).await;
+    let lock = create_lock(&mut env).await;
+    let mint = env.mint.pubkey();
+    mint_to(&mut env.context, &mint, &lock.locking_token_account, 7).await;
+
+    warp_to_timestamp(&mut env.context, lock.start + 150).await;
+    let instruction = unlock(
+        &env.program_id,
+        &spl_token::id(),
+        &lock.locking_account,
+        &lock.locking_token_account,
+        &lock.destination,
+        &mint,
+        lock.seeds,
+        0,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 100);
+
+    // The schedule still locked keeps its 200, only the 7 sent on top leave
+    let instruction = sweep_instruction(&env, &lock, &lock.source);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.source).await, 7);
+    assert_eq!(token_balance(&mut env.context, &lock.locking_token_account).await, 200);
+
+    warp_to_timestamp(&mut env.context, lock.start + 250).await;
+    let instruction = unlock(
+        &env.program_id,
+        &spl_token::id(),
+        &lock.locking_account,
+        &lock.locking_token_account,
+        &lock.destination,
+        &mint,
+        lock.seeds,
+        0,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 300);
+}
+
+// Matured schedules which were not unlocked yet still belong to the beneficiary
+#[tokio::test]
+async fn test_sweep_excess_keeps_matured_schedules() {
+    let mut env = setup(true).await;
+    let lock = create_lock(&mut env).await;
+    warp_to_timestamp(&mut env.context, lock.start + 250).await;
+
+    let instruction = sweep_instruction(&env, &lock, &lock.source);
+    assert_no_excess_error(process(&mut env.context, &[instruction], &[]).await);
+    assert_eq!(token_balance(&mut env.context, &lock.locking_token_account).await, 300);
+}
+
+#[tokio::test]
+async fn test_sweep_excess_to_other_owner() {
+    let mut env = setup(true).await;
+    let lock = create_lock(&mut env).await;
+    let mint = env.mint.pubkey();
+    mint_to(&mut env.context, &mint, &lock.locking_token_account, 42).await;
+
+    let instruction = sweep_instruction(&env, &lock, &lock.destination);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::InvalidArgument,
+    );
+    assert_eq!(token_balance(&mut env.context, &lock.locking_token_account).await, 342);
+}
@@ -0,0 +1,224 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    error::LockTokenError,
+    instruction::{create, unlock, CreateFeePayment, LockSeed, Schedule},
+    state::{token_state_address, TokenState},
+    time::TimeBasis,
+    utils::{get_associated_token_address_with_program_id, spl_token_2022},
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction, system_program, sysvar,
+};
+use solana_sdk::{
+    account::AccountSharedData,
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use spl_token::state::{Account as TokenAccount, Mint};
+
+// Token-2022 takes the instructions of the legacy token program for mints and accounts without extensions,
+// which the spl token builders only build for the legacy program id
+fn for_token_2022(mut instruction: Instruction) -> Instruction {
+    instruction.program_id = spl_token_2022::id();
+    instruction
+}
+
+// A Token-2022 mint without extensions, free to lock, with the context payer as its mint authority
+async fn create_mint_2022(env: &mut TestEnv) -> Pubkey {
+    let mint = mint_keypair(&env.program_id);
+    let rent = env.context.banks_client.get_rent().await.unwrap();
+    let payer = env.context.payer.pubkey();
+    let instructions = [
+        system_instruction::create_account(
+            &payer,
+            &mint.pubkey(),
+            rent.minimum_balance(Mint::LEN),
+            Mint::LEN as u64,
+            &spl_token_2022::id(),
+        ),
+        for_token_2022(
+            spl_token::instruction::initialize_mint(&spl_token::id(), &mint.pubkey(), &payer, None, 0).unwrap(),
+        ),
+    ];
+    process(&mut env.context, &instructions, &[&mint]).await.unwrap();
+
+    let token_state = TokenState {
+        mint_address: mint.pubkey(),
+        is_free: true,
+        fee_override_usd: 0,
+        has_override: false,
+        active_locks: 0,
+        total_locked_amount: 0,
+        last_snapshot_amount: 0,
+        last_snapshot_ts: 0,
+        is_initialized: true,
+    };
+    env.context.set_account(
+        &token_state_address(&env.program_id, &mint.pubkey()).unwrap(),
+        &AccountSharedData::from(program_account(&env.program_id, &token_state)),
+    );
+    mint.pubkey()
+}
+
+async fn create_token_account_2022(env: &mut TestEnv, mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+    let account = Keypair::new();
+    let rent = env.context.banks_client.get_rent().await.unwrap();
+    let payer = env.context.payer.pubkey();
+    let instructions = [
+        system_instruction::create_account(
+            &payer,
+            &account.pubkey(),
+            rent.minimum_balance(TokenAccount::LEN),
+            TokenAccount::LEN as u64,
+            &spl_token_2022::id(),
+        ),
+        for_token_2022(
+            spl_token::instruction::initialize_account(&spl_token::id(), &account.pubkey(), mint, owner).unwrap(),
+        ),
+    ];
+    process(&mut env.context, &instructions, &[&account]).await.unwrap();
+    account.pubkey()
+}
+
+fn create_instruction(
+    env: &TestEnv,
+    token_program_id: &Pubkey,
+    mint: &Pubkey,
+    seeds: LockSeed,
+    source: &Pubkey,
+    destination: &Pubkey,
+    schedules: Vec<Schedule>,
+) -> Instruction {
+    let payer = env.context.payer.pubkey();
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    create(
+        &env.program_id,
+        token_program_id,
+        &system_program::id(),
+        &sysvar::rent::id(),
+        &spl_associated_token_account::id(),
+        &env.price_estimator,
+        &locking_account,
+        &get_associated_token_address_with_program_id(&locking_account, mint, token_program_id),
+        &payer,
+        source,
+        &payer,
+        destination,
+        &env.company_wallet,
+        mint,
+        schedules,
+        seeds,
+        0,
+        false,
+        TimeBasis::UnixTimestamp,
+        Vec::new(),
+        0,
+        CreateFeePayment::Sol,
+        None,
+        false,
+        &[],
+    )
+    .unwrap()
+}
+
+// Locks 100 tokens of a Token-2022 mint released at start + 100, minted to a source of the context payer
+async fn setup_lock_2022(env: &mut TestEnv, mint: &Pubkey) -> (Pubkey, Pubkey, Instruction, LockSeed) {
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account_2022(env, mint, &payer).await;
+    let instruction = spl_token::instruction::mint_to(&spl_token::id(), mint, &source, &payer, &[], 100).unwrap();
+    process(&mut env.context, &[for_token_2022(instruction)], &[]).await.unwrap();
+    let destination = create_token_account_2022(env, mint, &Pubkey::new_unique()).await;
+
+    let start = now(&mut env.context).await;
+    let seeds = LockSeed::random(&env.program_id);
+    let schedules = vec![Schedule { release_time: start + 100, amount: 100 }];
+    let instruction = create_instruction(env, &spl_token_2022::id(), mint, seeds, &source, &destination, schedules);
+    (source, destination, instruction, seeds)
+}
+
+#[tokio::test]
+async fn test_create_and_unlock_token_2022() {
+    let mut env = setup(true).await;
+    let mint = create_mint_2022(&mut env).await;
+    let (source, destination, create, seeds) = setup_lock_2022(&mut env, &mint).await;
+    let instructions = [init_instruction(&env, seeds, 1), create];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+
+    // The vault is a Token-2022 associated token account of the locking account
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    let vault = get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token_2022::id());
+    let vault_account = get_account(&mut env.context, &vault).await;
+    assert_eq!(vault_account.owner, spl_token_2022::id());
+    assert_eq!(TokenAccount::unpack(&vault_account.data[..TokenAccount::LEN]).unwrap().amount, 100);
+    assert_eq!(token_balance(&mut env.context, &source).await, 0);
+
+    let start = now(&mut env.context).await;
+    warp_to_timestamp(&mut env.context, start + 100).await;
+    let instruction = unlock(
+        &env.program_id,
+        &spl_token_2022::id(),
+        &locking_account,
+        &vault,
+        &destination,
+        &mint,
+        seeds,
+        0,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &destination).await, 100);
+}
+
+// A mint charging a transfer fee would leave the lock short of its schedules, so it is rejected
+#[tokio::test]
+async fn test_create_rejects_a_transfer_fee_mint() {
+    let mut env = setup(true).await;
+    let mint = create_mint_2022(&mut env).await;
+    let (_, _, create, seeds) = setup_lock_2022(&mut env, &mint).await;
+
+    // The mint with a TransferFeeConfig extension: the base mint padded to the length of a token account, the
+    // account type, then the extension type and length before its data
+    let mut account = get_account(&mut env.context, &mint).await;
+    let mut data = account.data.clone();
+    data.resize(TokenAccount::LEN, 0);
+    data.push(1);
+    data.extend_from_slice(&1u16.to_le_bytes());
+    data.extend_from_slice(&108u16.to_le_bytes());
+    data.extend_from_slice(&[0; 108]);
+    account.data = data;
+    env.context.set_account(&mint, &account.into());
+
+    let instructions = [init_instruction(&env, seeds, 1), create];
+    let unsupported = InstructionError::Custom(LockTokenError::UnsupportedTokenExtension as u32);
+    assert_eq!(
+        process(&mut env.context, &instructions, &[]).await.unwrap_err(),
+        TransactionError::InstructionError(1, unsupported)
+    );
+}
+
+// The mint and the token accounts have to belong to the token program the instruction names
+#[tokio::test]
+async fn test_create_rejects_a_token_2022_mint_under_the_legacy_program() {
+    let mut env = setup(true).await;
+    let mint = create_mint_2022(&mut env).await;
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account_2022(&mut env, &mint, &payer).await;
+    let destination = create_token_account_2022(&mut env, &mint, &Pubkey::new_unique()).await;
+
+    let start = now(&mut env.context).await;
+    let seeds = LockSeed::random(&env.program_id);
+    let schedules = vec![Schedule { release_time: start + 100, amount: 100 }];
+    let create = create_instruction(&env, &spl_token::id(), &mint, seeds, &source, &destination, schedules);
+    let instructions = [init_instruction(&env, seeds, 1), create];
+    assert_eq!(
+        process(&mut env.context, &instructions, &[]).await.unwrap_err(),
+        TransactionError::InstructionError(1, InstructionError::IncorrectProgramId)
+    );
+}
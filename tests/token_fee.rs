@@ -0,0 +1,277 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    client::unpack_lock_header,
+    error::LockTokenError,
+    instruction::{create, set_fee_bps_in_token, CreateFeePayment, LockSeed, Schedule},
+    state::{
+        find_global_state_address, token_state_address, unpack_schedules, LockGlobalState, LockScheduleHeader,
+        TokenState, GLOBAL_STATE_VERSION, MAX_FEE_BPS_IN_TOKEN,
+    },
+    time::TimeBasis,
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+// 2.5% of every schedule
+const FEE_BPS_IN_TOKEN: u16 = 250;
+
+struct Lock {
+    locking_account: Pubkey,
+    locking_token_account: Pubkey,
+    source: Pubkey,
+}
+
+// Charges a fee for locking the mint of the test environment
+fn charge_fees(env: &mut TestEnv) {
+    let mint = env.mint.pubkey();
+    let token_state = TokenState {
+        mint_address: mint,
+        is_free: false,
+        fee_override_usd: 0,
+        has_override: false,
+        active_locks: 0,
+        total_locked_amount: 0,
+        last_snapshot_amount: 0,
+        last_snapshot_ts: 0,
+        is_initialized: true,
+    };
+    let account = program_account(&env.program_id, &token_state);
+    env.context.set_account(&token_state_address(&env.program_id, &mint).unwrap(), &AccountSharedData::from(account));
+}
+
+async fn set_fee_bps(env: &mut TestEnv, fee_bps_in_token: u16) -> Result<(), TransactionError> {
+    let instruction = set_fee_bps_in_token(
+        &env.program_id,
+        &system_program::id(),
+        &env.admin.pubkey(),
+        &env.context.payer.pubkey(),
+        fee_bps_in_token,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[&env.admin]).await
+}
+
+async fn global_state_account(env: &mut TestEnv) -> Account {
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    get_account(&mut env.context, &program_state_account_key).await
+}
+
+// Inits a lock of the schedules and returns the Create paying its fee into the given token account
+async fn token_fee_create_instruction(
+    env: &mut TestEnv,
+    schedules: Vec<Schedule>,
+    company_token_account: &Pubkey,
+) -> (Lock, Instruction) {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let total_amount = schedules.iter().map(|s| s.amount).sum();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, total_amount).await;
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+
+    let seeds = LockSeed::random(&env.program_id);
+    let instruction = init_instruction(env, seeds, schedules.len() as u32);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    let locking_token_account = get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id());
+    let instruction = create(
+        &env.program_id,
+        &spl_token::id(),
+        &system_program::id(),
+        &sysvar::rent::id(),
+        &spl_associated_token_account::id(),
+        &env.price_estimator,
+        &locking_account,
+        &locking_token_account,
+        &payer,
+        &source,
+        &payer,
+        &destination,
+        &env.company_wallet,
+        &mint,
+        schedules,
+        seeds,
+        0,
+        false,
+        TimeBasis::UnixTimestamp,
+        Vec::new(),
+        0,
+        CreateFeePayment::Token {
+            company_token_account: *company_token_account,
+        },
+        None,
+        false,
+        &[],
+    )
+    .unwrap();
+    let lock = Lock {
+        locking_account,
+        locking_token_account,
+        source,
+    };
+    (lock, instruction)
+}
+
+#[tokio::test]
+async fn test_set_fee_bps_in_token() {
+    let mut env = setup(true).await;
+
+    assert_instruction_error(
+        set_fee_bps(&mut env, MAX_FEE_BPS_IN_TOKEN + 1).await,
+        InstructionError::Custom(LockTokenError::InvalidBasisPoints as u32),
+    );
+
+    set_fee_bps(&mut env, MAX_FEE_BPS_IN_TOKEN).await.unwrap();
+    let account = global_state_account(&mut env).await;
+    let state = LockGlobalState::unpack_from_slice(&account.data).unwrap();
+    assert_eq!(state.fee_bps_in_token, MAX_FEE_BPS_IN_TOKEN);
+
+    // Only the admin sets it
+    let instruction = set_fee_bps_in_token(
+        &env.program_id,
+        &system_program::id(),
+        &env.context.payer.pubkey(),
+        &env.context.payer.pubkey(),
+        0,
+    )
+    .unwrap();
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::Custom(LockTokenError::NotAdmin as u32),
+    );
+}
+
+#[tokio::test]
+async fn test_set_fee_bps_in_token_grows_version_2_state() {
+    let mut env = setup(true).await;
+    let state = global_state(&env.admin.pubkey(), &env.company_wallet, &env.price_estimator);
+    let mut data = vec![0; LockGlobalState::V2_LEN];
+    data[320] = 2;
+    state.pack_into_slice(&mut data);
+    let account = Account {
+        lamports: 1_000_000_000,
+        data,
+        owner: env.program_id,
+        executable: false,
+        rent_epoch: 0,
+    };
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    env.context.set_account(&program_state_account_key, &AccountSharedData::from(account));
+
+    let account = global_state_account(&mut env).await;
+    assert_eq!(LockGlobalState::unpack_from_slice(&account.data).unwrap().fee_bps_in_token, 0);
+
+    set_fee_bps(&mut env, FEE_BPS_IN_TOKEN).await.unwrap();
+    let account = global_state_account(&mut env).await;
+    assert_eq!(account.data.len(), LockGlobalState::LEN);
+    assert_eq!(account.data[568], GLOBAL_STATE_VERSION);
+    let unpacked = LockGlobalState::unpack_from_slice(&account.data).unwrap();
+    assert_eq!(unpacked.fee_bps_in_token, FEE_BPS_IN_TOKEN);
+    assert_eq!(unpacked.admin, state.admin);
+    assert_eq!(unpacked.company_wallet, state.company_wallet);
+}
+
+#[tokio::test]
+async fn test_create_paying_fee_in_token() {
+    let mut env = setup(true).await;
+    charge_fees(&mut env);
+    set_fee_bps(&mut env, FEE_BPS_IN_TOKEN).await.unwrap();
+    let mint = env.mint.pubkey();
+    let company_wallet = env.company_wallet;
+    let company_token_account = create_token_account(&mut env.context, &mint, &company_wallet).await;
+
+    // 2.5% of 100 is 2.5 and of 301 is 7.525, both rounded down
+    let start = now(&mut env.context).await;
+    let schedules = vec![
+        Schedule { release_time: start + 100, amount: 100 },
+        Schedule { release_time: start + 200, amount: 301 },
+    ];
+    let (lock, instruction) = token_fee_create_instruction(&mut env, schedules, &company_token_account).await;
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    assert_eq!(token_balance(&mut env.context, &company_token_account).await, 9);
+    assert_eq!(token_balance(&mut env.context, &lock.locking_token_account).await, 392);
+    assert_eq!(token_balance(&mut env.context, &lock.source).await, 0);
+
+    let data = get_account(&mut env.context, &lock.locking_account).await.data;
+    assert_eq!(unpack_lock_header(&data).unwrap().total_amount, 392);
+    let schedules = unpack_schedules(&data[LockScheduleHeader::LEN..]).unwrap();
+    assert_eq!(schedules.iter().map(|s| s.amount).collect::<Vec<_>>(), vec![98, 294]);
+}
+
+#[tokio::test]
+async fn test_create_paying_fee_in_token_for_free_token() {
+    let mut env = setup(true).await;
+    set_fee_bps(&mut env, FEE_BPS_IN_TOKEN).await.unwrap();
+    let mint = env.mint.pubkey();
+    let company_wallet = env.company_wallet;
+    let company_token_account = create_token_account(&mut env.context, &mint, &company_wallet).await;
+
+    let start = now(&mut env.context).await;
+    let schedules = vec![Schedule { release_time: start + 100, amount: 400 }];
+    let (lock, instruction) = token_fee_create_instruction(&mut env, schedules, &company_token_account).await;
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    assert_eq!(token_balance(&mut env.context, &company_token_account).await, 0);
+    assert_eq!(token_balance(&mut env.context, &lock.locking_token_account).await, 400);
+}
+
+#[tokio::test]
+async fn test_create_paying_fee_in_token_when_disabled() {
+    let mut env = setup(true).await;
+    charge_fees(&mut env);
+    let mint = env.mint.pubkey();
+    let company_wallet = env.company_wallet;
+    let company_token_account = create_token_account(&mut env.context, &mint, &company_wallet).await;
+
+    let start = now(&mut env.context).await;
+    let schedules = vec![Schedule { release_time: start + 100, amount: 400 }];
+    let (_, instruction) = token_fee_create_instruction(&mut env, schedules, &company_token_account).await;
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::InvalidArgument,
+    );
+}
+
+#[tokio::test]
+async fn test_create_paying_fee_in_token_to_wrong_account() {
+    let mut env = setup(true).await;
+    charge_fees(&mut env);
+    set_fee_bps(&mut env, FEE_BPS_IN_TOKEN).await.unwrap();
+    let company_wallet = env.company_wallet;
+    let other_mint = Keypair::new();
+    create_mint(&mut env.context, &other_mint).await;
+    let other_mint_account = create_token_account(&mut env.context, &other_mint.pubkey(), &company_wallet).await;
+    let mint = env.mint.pubkey();
+    let other_owner_account = create_token_account(&mut env.context, &mint, &Pubkey::new_unique()).await;
+
+    let start = now(&mut env.context).await;
+    let schedules = vec![Schedule { release_time: start + 100, amount: 400 }];
+    let (_, instruction) = token_fee_create_instruction(&mut env, schedules.clone(), &other_mint_account).await;
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::Custom(LockTokenError::FeeMintMismatch as u32),
+    );
+
+    let (lock, instruction) = token_fee_create_instruction(&mut env, schedules, &other_owner_account).await;
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::Custom(LockTokenError::CompanyWalletMismatch as u32),
+    );
+    assert_eq!(token_balance(&mut env.context, &lock.source).await, 400);
+}
@@ -0,0 +1,76 @@
+use lock_token::{
+    error::LockTokenError,
+    oracle::OraclePrice,
+    state::TokenState,
+};
+use solana_program::{program_error::ProgramError, program_pack::Pack, pubkey::Pubkey};
+
+// 10 USD at 100 USD per SOL
+const FEES_IN_USD: u64 = 10_000_000;
+const PRICE: OraclePrice = OraclePrice { price: 10_000, expo: -2, publish_slot: 0 };
+
+fn token_state(is_free: bool) -> TokenState {
+    TokenState {
+        mint_address: Pubkey::new_unique(),
+        is_free,
+        fee_override_usd: 0,
+        has_override: false,
+        active_locks: 0,
+        total_locked_amount: 0,
+        last_snapshot_amount: 0,
+        last_snapshot_ts: 0,
+        is_initialized: true,
+    }
+}
+
+// Free tokens are the ones paying nothing
+#[test]
+fn test_free_token_pays_nothing() {
+    assert_eq!(token_state(true).estimate_fees_in_sol(FEES_IN_USD, &PRICE), Ok(0));
+    // whatever the price
+    let price = OraclePrice { price: 0, ..PRICE };
+    assert_eq!(token_state(true).estimate_fees_in_sol(FEES_IN_USD, &price), Ok(0));
+}
+
+#[test]
+fn test_other_token_pays_the_fee() {
+    assert_eq!(token_state(false).estimate_fees_in_sol(FEES_IN_USD, &PRICE), Ok(100_000_000));
+    assert_eq!(token_state(false).estimate_fees_in_sol(0, &PRICE), Ok(0));
+    let price = OraclePrice { price: 0, ..PRICE };
+    assert_eq!(
+        token_state(false).estimate_fees_in_sol(FEES_IN_USD, &price),
+        Err(ProgramError::from(LockTokenError::InvalidOraclePrice))
+    );
+}
+
+#[test]
+fn test_override_is_preferred_over_the_global_fee() {
+    let mut state = token_state(false);
+    assert_eq!(state.fees_in_usd(FEES_IN_USD), FEES_IN_USD);
+    state.fee_override_usd = FEES_IN_USD / 4;
+    assert_eq!(state.fees_in_usd(FEES_IN_USD), FEES_IN_USD);
+    state.has_override = true;
+    assert_eq!(state.fees_in_usd(FEES_IN_USD), FEES_IN_USD / 4);
+    assert_eq!(state.estimate_fees_in_sol(FEES_IN_USD, &PRICE), Ok(25_000_000));
+}
+
+// Token states written before the override was added are read without one
+#[test]
+fn test_legacy_layout_has_no_override() {
+    let mut state = token_state(false);
+    state.fee_override_usd = FEES_IN_USD;
+    state.has_override = true;
+    let mut data = vec![0; TokenState::LEGACY_LEN];
+    state.pack_into_slice(&mut data);
+    let unpacked = TokenState::unpack_from_slice(&data).unwrap();
+    assert_eq!(unpacked.mint_address, state.mint_address);
+    assert!(!unpacked.has_override);
+    assert!(unpacked.is_initialized);
+    assert_eq!(unpacked.fees_in_usd(FEES_IN_USD / 2), FEES_IN_USD / 2);
+
+    let mut data = vec![0; TokenState::V1_LEN];
+    state.pack_into_slice(&mut data);
+    let unpacked = TokenState::unpack_from_slice(&data).unwrap();
+    assert!(unpacked.has_override);
+    assert_eq!(unpacked.fees_in_usd(FEES_IN_USD / 2), FEES_IN_USD);
+}
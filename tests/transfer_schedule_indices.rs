@@ -0,0 +1,216 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    client::unpack_lock_header,
+    instruction::{transfer_schedule_indices, unlock, LockSeed, Schedule},
+    state::{unpack_schedules, LockSchedule, LockScheduleHeader},
+    utils::{create_associated_token_account_with_program_id, get_associated_token_address_with_program_id},
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+};
+use solana_sdk::signature::{Keypair, Signer};
+
+struct Lock {
+    seeds: LockSeed,
+    locking_account: Pubkey,
+    locking_token_account: Pubkey,
+    destination: Pubkey,
+}
+
+fn lock_of(env: &TestEnv, seeds: LockSeed, destination: Pubkey) -> Lock {
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    Lock {
+        seeds,
+        locking_account,
+        locking_token_account: get_associated_token_address_with_program_id(
+            &locking_account,
+            &env.mint.pubkey(),
+            &spl_token::id(),
+        ),
+        destination,
+    }
+}
+
+// Locks 600 tokens for the beneficiary in schedules of 100, 200 and 300 released 100 seconds apart
+async fn create_lock(env: &mut TestEnv, beneficiary: &Keypair, start: u64) -> Lock {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 600).await;
+    let destination = create_token_account(&mut env.context, &mint, &beneficiary.pubkey()).await;
+
+    let schedules = vec![
+        Schedule { release_time: start + 100, amount: 100 },
+        Schedule { release_time: start + 200, amount: 200 },
+        Schedule { release_time: start + 300, amount: 300 },
+    ];
+    let seeds = LockSeed::random(&env.program_id);
+    let instructions = [
+        init_instruction(env, seeds, schedules.len() as u32),
+        create_lock_instruction(env, seeds, &payer, &source, &destination, schedules, false, &[]),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+    lock_of(env, seeds, destination)
+}
+
+// Inits the locking account of a new lock with its locking token account, released to a new beneficiary
+async fn init_new_lock(env: &mut TestEnv, number_of_schedules: u32) -> Lock {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+    let seeds = LockSeed::random(&env.program_id);
+    let lock = lock_of(env, seeds, destination);
+    let instructions = [
+        init_instruction(env, seeds, number_of_schedules),
+        create_associated_token_account_with_program_id(&payer, &lock.locking_account, &mint, &spl_token::id()),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+    lock
+}
+
+fn transfer_instruction(env: &TestEnv, lock: &Lock, owner: &Keypair, new_lock: &Lock, indices: Vec<u32>) -> Instruction {
+    transfer_schedule_indices(
+        &env.program_id,
+        &spl_token::id(),
+        &lock.locking_account,
+        &lock.locking_token_account,
+        &owner.pubkey(),
+        &lock.destination,
+        &new_lock.locking_account,
+        &new_lock.locking_token_account,
+        &new_lock.destination,
+        &env.mint.pubkey(),
+        lock.seeds,
+        new_lock.seeds,
+        indices,
+    )
+    .unwrap()
+}
+
+async fn unlock_lock(env: &mut TestEnv, lock: &Lock) {
+    let instruction = unlock(
+        &env.program_id,
+        &spl_token::id(),
+        &lock.locking_account,
+        &lock.locking_token_account,
+        &lock.destination,
+        &env.mint.pubkey(),
+        lock.seeds,
+        0,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+}
+
+async fn lock_state(env: &mut TestEnv, lock: &Lock) -> (LockScheduleHeader, Vec<LockSchedule>) {
+    let data = get_account(&mut env.context, &lock.locking_account).await.data;
+    let header = unpack_lock_header(&data).unwrap();
+    let header_len = LockScheduleHeader::header_len(data.len());
+    (header, unpack_schedules(&data[header_len..]).unwrap())
+}
+
+#[tokio::test]
+async fn test_transfer_schedule_indices() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let beneficiary = Keypair::new();
+    let lock = create_lock(&mut env, &beneficiary, start).await;
+    let new_lock = init_new_lock(&mut env, 2).await;
+
+    let instruction = transfer_instruction(&env, &lock, &beneficiary, &new_lock, vec![2, 0]);
+    process(&mut env.context, &[instruction], &[&beneficiary]).await.unwrap();
+
+    assert_eq!(token_balance(&mut env.context, &lock.locking_token_account).await, 200);
+    assert_eq!(token_balance(&mut env.context, &new_lock.locking_token_account).await, 400);
+
+    let (header, schedules) = lock_state(&mut env, &lock).await;
+    assert_eq!(header.total_amount, 200);
+    assert_eq!(
+        schedules,
+        vec![
+            LockSchedule { release_time: start + 100, amount: 0 },
+            LockSchedule { release_time: start + 200, amount: 200 },
+            LockSchedule { release_time: start + 300, amount: 0 },
+        ]
+    );
+
+    // The moved schedules are sorted whatever the order of the indices
+    let (new_header, new_schedules) = lock_state(&mut env, &new_lock).await;
+    assert_eq!(new_header.destination_address, new_lock.destination);
+    assert_eq!(new_header.total_amount, 400);
+    assert_eq!(new_header.schedule_count, 2);
+    assert_eq!(new_header.time_basis, header.time_basis);
+    assert_eq!(
+        new_schedules,
+        vec![
+            LockSchedule { release_time: start + 100, amount: 100 },
+            LockSchedule { release_time: start + 300, amount: 300 },
+        ]
+    );
+
+    warp_to_timestamp(&mut env.context, start + 300).await;
+    unlock_lock(&mut env, &lock).await;
+    unlock_lock(&mut env, &new_lock).await;
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 200);
+    assert_eq!(token_balance(&mut env.context, &new_lock.destination).await, 400);
+}
+
+// Nothing is moved when one of the indices is invalid
+#[tokio::test]
+async fn test_transfer_schedule_indices_rejects_invalid_indices() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let beneficiary = Keypair::new();
+    let lock = create_lock(&mut env, &beneficiary, start).await;
+    let new_lock = init_new_lock(&mut env, 2).await;
+
+    for indices in vec![vec![1, 1], vec![0, 3], vec![]] {
+        let instruction = transfer_instruction(&env, &lock, &beneficiary, &new_lock, indices.clone());
+        let expected = if indices.is_empty() {
+            InstructionError::InvalidInstructionData
+        } else {
+            InstructionError::InvalidArgument
+        };
+        assert_instruction_error(process(&mut env.context, &[instruction], &[&beneficiary]).await, expected);
+        refresh_blockhash(&mut env.context).await;
+    }
+
+    // A released schedule stays with the lock
+    warp_to_timestamp(&mut env.context, start + 100).await;
+    let instruction = transfer_instruction(&env, &lock, &beneficiary, &new_lock, vec![0, 1]);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&beneficiary]).await,
+        InstructionError::InvalidArgument,
+    );
+
+    // The new locking account has to hold exactly the moved schedules
+    let instruction = transfer_instruction(&env, &lock, &beneficiary, &new_lock, vec![2]);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&beneficiary]).await,
+        InstructionError::InvalidAccountData,
+    );
+
+    assert_eq!(token_balance(&mut env.context, &lock.locking_token_account).await, 600);
+    assert_eq!(token_balance(&mut env.context, &new_lock.locking_token_account).await, 0);
+}
+
+#[tokio::test]
+async fn test_transfer_schedule_indices_requires_beneficiary() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let beneficiary = Keypair::new();
+    let lock = create_lock(&mut env, &beneficiary, start).await;
+    let new_lock = init_new_lock(&mut env, 1).await;
+
+    let impostor = Keypair::new();
+    let instruction = transfer_instruction(&env, &lock, &impostor, &new_lock, vec![2]);
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[&impostor]).await,
+        InstructionError::InvalidArgument,
+    );
+}
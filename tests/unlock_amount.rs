@@ -0,0 +1,173 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    error::LockTokenError,
+    instruction::{unlock, unlock_amount, LockSeed, Schedule},
+    state::{unpack_schedules, LockSchedule, LockScheduleHeader},
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+};
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+struct Lock {
+    seeds: LockSeed,
+    locking_account: Pubkey,
+    destination: Pubkey,
+    start: u64,
+}
+
+// Locks 600 of the context payer's tokens in schedules of 100, 200 and 300 released 100 seconds apart
+async fn create_lock(env: &mut TestEnv) -> Lock {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 600).await;
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+
+    let start = now(&mut env.context).await;
+    let schedules = vec![
+        Schedule { release_time: start + 100, amount: 100 },
+        Schedule { release_time: start + 200, amount: 200 },
+        Schedule { release_time: start + 300, amount: 300 },
+    ];
+    let seeds = LockSeed::random(&env.program_id);
+    let instructions = [
+        init_instruction(env, seeds, schedules.len() as u32),
+        create_lock_instruction(env, seeds, &payer, &source, &destination, schedules, false, &[]),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+
+    Lock {
+        seeds,
+        locking_account: seeds.locking_account_address(&env.program_id).unwrap(),
+        destination,
+        start,
+    }
+}
+
+fn locking_token_account(env: &TestEnv, lock: &Lock) -> Pubkey {
+    get_associated_token_address_with_program_id(&lock.locking_account, &env.mint.pubkey(), &spl_token::id())
+}
+
+fn unlock_amount_instruction(env: &TestEnv, lock: &Lock, amount: u64) -> Instruction {
+    unlock_amount(
+        &env.program_id,
+        &spl_token::id(),
+        &lock.locking_account,
+        &locking_token_account(env, lock),
+        &lock.destination,
+        &env.mint.pubkey(),
+        lock.seeds,
+        amount,
+        &[],
+    )
+    .unwrap()
+}
+
+async fn unlock_amount_of(env: &mut TestEnv, lock: &Lock, amount: u64) -> Result<(), TransactionError> {
+    let instruction = unlock_amount_instruction(env, lock, amount);
+    process(&mut env.context, &[instruction], &[]).await
+}
+
+async fn read_schedules(env: &mut TestEnv, lock: &Lock) -> Vec<LockSchedule> {
+    let data = get_account(&mut env.context, &lock.locking_account).await.data;
+    let header_len = LockScheduleHeader::header_len(data.len());
+    unpack_schedules(&data[header_len..]).unwrap()
+}
+
+fn schedules(lock: &Lock, amounts: [u64; 3]) -> Vec<LockSchedule> {
+    amounts
+        .iter()
+        .enumerate()
+        .map(|(i, amount)| LockSchedule { release_time: lock.start + 100 * (i as u64 + 1), amount: *amount })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_unlock_amount_at_schedule_boundaries() {
+    let mut env = setup(true).await;
+    let lock = create_lock(&mut env).await;
+    warp_to_timestamp(&mut env.context, lock.start + 200).await;
+
+    // 300 has matured
+    assert_instruction_error(
+        unlock_amount_of(&mut env, &lock, 301).await,
+        InstructionError::Custom(LockTokenError::InsufficientUnlocked as u32),
+    );
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 0);
+
+    // Exactly the first schedule
+    unlock_amount_of(&mut env, &lock, 100).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 100);
+    assert_eq!(read_schedules(&mut env, &lock).await, schedules(&lock, [0, 200, 300]));
+
+    // Exactly what is left matured
+    unlock_amount_of(&mut env, &lock, 200).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 300);
+    assert_eq!(read_schedules(&mut env, &lock).await, schedules(&lock, [0, 0, 300]));
+
+    assert_instruction_error(
+        unlock_amount_of(&mut env, &lock, 1).await,
+        InstructionError::Custom(LockTokenError::InsufficientUnlocked as u32),
+    );
+    assert_instruction_error(unlock_amount_of(&mut env, &lock, 0).await, InstructionError::InvalidInstructionData);
+}
+
+#[tokio::test]
+async fn test_unlock_amount_spans_schedules_and_leaves_the_rest() {
+    let mut env = setup(true).await;
+    let lock = create_lock(&mut env).await;
+    warp_to_timestamp(&mut env.context, lock.start + 300).await;
+
+    // The first two schedules and 50 of the third
+    unlock_amount_of(&mut env, &lock, 350).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 350);
+    assert_eq!(read_schedules(&mut env, &lock).await, schedules(&lock, [0, 0, 250]));
+
+    // The rest of the partially unlocked schedule, with its past release time, is still claimable
+    assert_instruction_error(
+        unlock_amount_of(&mut env, &lock, 251).await,
+        InstructionError::Custom(LockTokenError::InsufficientUnlocked as u32),
+    );
+    unlock_amount_of(&mut env, &lock, 250).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 600);
+    assert_instruction_error(
+        unlock_amount_of(&mut env, &lock, 1).await,
+        InstructionError::Custom(LockTokenError::LockFullyUnlocked as u32),
+    );
+}
+
+#[tokio::test]
+async fn test_unlock_releases_the_rest_of_a_partial_schedule() {
+    let mut env = setup(true).await;
+    let lock = create_lock(&mut env).await;
+    warp_to_timestamp(&mut env.context, lock.start + 200).await;
+
+    unlock_amount_of(&mut env, &lock, 120).await.unwrap();
+    assert_eq!(read_schedules(&mut env, &lock).await, schedules(&lock, [0, 180, 300]));
+
+    let mint = env.mint.pubkey();
+    let instruction = unlock(
+        &env.program_id,
+        &spl_token::id(),
+        &lock.locking_account,
+        &locking_token_account(&env, &lock),
+        &lock.destination,
+        &mint,
+        lock.seeds,
+        0,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 300);
+    assert_eq!(read_schedules(&mut env, &lock).await, schedules(&lock, [0, 0, 300]));
+}
@@ -0,0 +1,63 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{instruction::unlock, state::LockScheduleHeader};
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+async fn unlock_compute_units(env: &mut TestEnv, destination: &Pubkey, header_len: usize, schedule_count: usize) -> u64 {
+    let start = now(&mut env.context).await;
+    let lock = set_lock(env, destination, header_len, schedule_count, 1, start).await;
+    let instruction = unlock(
+        &env.program_id,
+        &spl_token::id(),
+        &lock.locking_account,
+        &lock.locking_token_account,
+        destination,
+        &env.mint.pubkey(),
+        lock.seeds,
+        0,
+    )
+    .unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[ComputeBudgetInstruction::set_compute_unit_limit(1_400_000), instruction],
+        Some(&env.context.payer.pubkey()),
+        &[&env.context.payer],
+        env.context.last_blockhash,
+    );
+    let result = process_with_metadata(&mut env.context, transaction).await;
+    result.result.unwrap();
+    result.metadata.unwrap().compute_units_consumed
+}
+
+// Headers without the schedule counters are unlocked by reading every schedule, those of the current layout
+// by a binary search over the release times. A processor run natively is only charged for its CPIs, so the
+// difference shows when ProgramTest loads the program built with cargo test-sbf.
+#[tokio::test]
+async fn test_unlock_compute_units() {
+    let runs_as_bpf = std::env::var("SBF_OUT_DIR").is_ok() || std::env::var("BPF_OUT_DIR").is_ok();
+    let mut env = setup(true).await;
+    let mint = env.mint.pubkey();
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+
+    for &schedule_count in [100, 500, 1000].iter() {
+        let scan = unlock_compute_units(&mut env, &destination, LockScheduleHeader::V2_LEN, schedule_count).await;
+        let binary_search = unlock_compute_units(&mut env, &destination, LockScheduleHeader::LEN, schedule_count).await;
+        println!(
+            "{} schedules: {} compute units scanning, {} with binary search",
+            schedule_count, scan, binary_search
+        );
+        if runs_as_bpf {
+            assert!(binary_search < scan);
+        } else {
+            assert!(binary_search <= scan);
+        }
+    }
+    assert_eq!(token_balance(&mut env.context, &destination).await, 6);
+}
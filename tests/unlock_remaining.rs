@@ -0,0 +1,205 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    events::{decode, LockRemaining},
+    instruction::{unlock, LockSeed, Schedule},
+    state::{pack_schedules_into_slice, LockSchedule, LockScheduleHeader, RemainingSchedules},
+    time::TimeBasis,
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use spl_token::state::{Account as TokenAccount, AccountState};
+
+struct Lock {
+    seeds: LockSeed,
+    locking_account: Pubkey,
+    destination: Pubkey,
+}
+
+fn program_owned_account(owner: &Pubkey, lamports: u64, data: Vec<u8>) -> AccountSharedData {
+    AccountSharedData::from(Account {
+        lamports,
+        data,
+        owner: *owner,
+        executable: false,
+        rent_epoch: 0,
+    })
+}
+
+// Locks 600 of the context payer's tokens in schedules of 100, 200 and 300 released 100 seconds apart
+async fn create_lock(env: &mut TestEnv, start: u64) -> Lock {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 600).await;
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+
+    let schedules = vec![
+        Schedule { release_time: start + 100, amount: 100 },
+        Schedule { release_time: start + 200, amount: 200 },
+        Schedule { release_time: start + 300, amount: 300 },
+    ];
+    let seeds = LockSeed::random(&env.program_id);
+    let instructions = [
+        init_instruction(env, seeds, schedules.len() as u32),
+        create_lock_instruction(env, seeds, &payer, &source, &destination, schedules, false, &[]),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+
+    Lock {
+        seeds,
+        locking_account: seeds.locking_account_address(&env.program_id).unwrap(),
+        destination,
+    }
+}
+
+// Unlocks the lock and returns its return data, checking it against the logged event
+async fn unlock_remaining(env: &mut TestEnv, lock: &Lock) -> RemainingSchedules {
+    let mint = env.mint.pubkey();
+    let instruction = unlock(
+        &env.program_id,
+        &spl_token::id(),
+        &lock.locking_account,
+        &get_associated_token_address_with_program_id(&lock.locking_account, &mint, &spl_token::id()),
+        &lock.destination,
+        &mint,
+        lock.seeds,
+        0,
+    )
+    .unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&env.context.payer.pubkey()),
+        &[&env.context.payer],
+        env.context.last_blockhash,
+    );
+    let result = process_with_metadata(&mut env.context, transaction).await;
+    result.result.unwrap();
+    let metadata = result.metadata.unwrap();
+
+    let return_data = metadata.return_data.unwrap();
+    assert_eq!(return_data.program_id, env.program_id);
+    assert_eq!(return_data.data.len(), RemainingSchedules::LEN);
+    let remaining = RemainingSchedules::unpack_from_slice(&return_data.data).unwrap();
+
+    let event = metadata.log_messages.iter().find_map(|log| decode::<LockRemaining>(log)).unwrap();
+    assert_eq!(
+        event,
+        LockRemaining {
+            locking_account: lock.locking_account,
+            remaining_locked: remaining.remaining_locked,
+            schedule_count: remaining.schedule_count,
+            next_release_time: remaining.next_release_time,
+        }
+    );
+    remaining
+}
+
+#[tokio::test]
+async fn test_unlock_returns_remaining_schedules() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let lock = create_lock(&mut env, start).await;
+
+    warp_to_timestamp(&mut env.context, start + 150).await;
+    assert_eq!(
+        unlock_remaining(&mut env, &lock).await,
+        RemainingSchedules { remaining_locked: 500, schedule_count: 2, next_release_time: start + 200 }
+    );
+
+    warp_to_timestamp(&mut env.context, start + 250).await;
+    assert_eq!(
+        unlock_remaining(&mut env, &lock).await,
+        RemainingSchedules { remaining_locked: 300, schedule_count: 1, next_release_time: start + 300 }
+    );
+
+    warp_to_timestamp(&mut env.context, start + 300).await;
+    assert_eq!(unlock_remaining(&mut env, &lock).await, RemainingSchedules::default());
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 600);
+}
+
+// Headers without the schedule counters can hold unsorted schedules, the earliest one still locked is returned
+#[tokio::test]
+async fn test_unlock_returns_remaining_schedules_of_legacy_layout() {
+    let mut env = setup(true).await;
+    let mint = env.mint.pubkey();
+    let start = now(&mut env.context).await;
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+    let seeds = LockSeed::random(&env.program_id);
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    let locking_token_account = get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id());
+    let rent = env.context.banks_client.get_rent().await.unwrap();
+
+    let header = LockScheduleHeader {
+        destination_address: destination,
+        mint_address: mint,
+        is_initialized: true,
+        pending_destination_address: Pubkey::default(),
+        is_vesting: false,
+        is_nft: false,
+        is_registered: false,
+        is_immutable: false,
+        is_multi: false,
+        is_derived: false,
+        total_amount: 600,
+        claimed_amount: 0,
+        schedule_count: 0,
+        unlocked_count: 0,
+        rescue_destination_address: Pubkey::default(),
+        rescue_execute_after: 0,
+        creator_address: Pubkey::default(),
+        time_basis: TimeBasis::UnixTimestamp,
+        split_destinations: Default::default(),
+        delegate_address: Pubkey::default(),
+        permit_nonce: 0,
+        min_claim_interval_secs: 0,
+        last_claim_ts: 0,
+        lock_nonce: 0,
+        lock_bump: 0,
+        created_at: 0,
+    };
+    let schedules = vec![
+        LockSchedule { release_time: start, amount: 100 },
+        LockSchedule { release_time: start + 500, amount: 200 },
+        LockSchedule { release_time: start + 300, amount: 300 },
+    ];
+    let header_len = LockScheduleHeader::V2_LEN;
+    let mut data = vec![0; header_len + schedules.len() * LockSchedule::LEN];
+    header.pack_into_slice(&mut data[..header_len]);
+    pack_schedules_into_slice(schedules, &mut data[header_len..]);
+    let lamports = rent.minimum_balance(data.len());
+    env.context
+        .set_account(&locking_account, &program_owned_account(&env.program_id, lamports, data));
+
+    let vault = TokenAccount {
+        mint,
+        owner: locking_account,
+        amount: 600,
+        state: AccountState::Initialized,
+        ..TokenAccount::default()
+    };
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount::pack(vault, &mut data).unwrap();
+    let lamports = rent.minimum_balance(data.len());
+    env.context
+        .set_account(&locking_token_account, &program_owned_account(&spl_token::id(), lamports, data));
+
+    let lock = Lock {
+        seeds,
+        locking_account,
+        destination,
+    };
+    assert_eq!(
+        unlock_remaining(&mut env, &lock).await,
+        RemainingSchedules { remaining_locked: 500, schedule_count: 2, next_release_time: start + 300 }
+    );
+    assert_eq!(token_balance(&mut env.context, &destination).await, 100);
+}
@@ -0,0 +1,164 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    client::unpack_lock_header,
+    error::LockTokenError,
+    instruction::{ed25519_permit_instruction, permit_message, unlock_with_permit, LockSeed, Schedule},
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+};
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use std::convert::TryInto;
+
+struct Lock {
+    seeds: LockSeed,
+    locking_account: Pubkey,
+    locking_token_account: Pubkey,
+    destination: Pubkey,
+    beneficiary: Keypair,
+}
+
+// Locks 300 tokens for a fresh beneficiary in schedules of 100 and 200 released 100 seconds apart
+async fn create_lock(env: &mut TestEnv, start: u64) -> Lock {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 300).await;
+    let beneficiary = Keypair::new();
+    let destination = create_token_account(&mut env.context, &mint, &beneficiary.pubkey()).await;
+
+    let schedules = vec![
+        Schedule { release_time: start + 100, amount: 100 },
+        Schedule { release_time: start + 200, amount: 200 },
+    ];
+    let seeds = LockSeed::random(&env.program_id);
+    let instructions = [
+        init_instruction(env, seeds, schedules.len() as u32),
+        create_lock_instruction(env, seeds, &payer, &source, &destination, schedules, false, &[]),
+    ];
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    Lock {
+        seeds,
+        locking_account,
+        locking_token_account: get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id()),
+        destination,
+        beneficiary,
+    }
+}
+
+async fn permit_nonce(env: &mut TestEnv, lock: &Lock) -> u64 {
+    let data = get_account(&mut env.context, &lock.locking_account).await.data;
+    unpack_lock_header(&data).unwrap().permit_nonce
+}
+
+// The ed25519 program instruction verifying the permit signed by signer, followed by the UnlockWithPermit,
+// both sent by the payer of the test context
+fn permit_instructions(env: &TestEnv, lock: &Lock, signer: &Keypair, nonce: u64, expiry: u64) -> [Instruction; 2] {
+    let message = permit_message(&env.program_id, &lock.locking_account, nonce, expiry);
+    let signature: [u8; 64] = signer.sign_message(&message).as_ref().try_into().unwrap();
+    [
+        ed25519_permit_instruction(&signer.pubkey(), &signature, &message),
+        unlock_with_permit(
+            &env.program_id,
+            &spl_token::id(),
+            &lock.locking_account,
+            &lock.locking_token_account,
+            &lock.destination,
+            &env.mint.pubkey(),
+            lock.seeds,
+            expiry,
+            &[],
+        )
+        .unwrap(),
+    ]
+}
+
+fn assert_permit_error(result: Result<(), TransactionError>, expected: LockTokenError) {
+    assert_eq!(
+        result.unwrap_err(),
+        TransactionError::InstructionError(1, InstructionError::Custom(expected as u32))
+    );
+}
+
+#[tokio::test]
+async fn test_unlock_with_permit_relayed_by_anyone() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let lock = create_lock(&mut env, start).await;
+    assert_eq!(permit_nonce(&mut env, &lock).await, 0);
+
+    warp_to_timestamp(&mut env.context, start + 100).await;
+    let instructions = permit_instructions(&env, &lock, &lock.beneficiary, 0, start + 1_000);
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 100);
+    assert_eq!(token_balance(&mut env.context, &lock.locking_token_account).await, 200);
+    assert_eq!(permit_nonce(&mut env, &lock).await, 1);
+}
+
+#[tokio::test]
+async fn test_permit_can_not_be_replayed() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let lock = create_lock(&mut env, start).await;
+
+    warp_to_timestamp(&mut env.context, start + 100).await;
+    let instructions = permit_instructions(&env, &lock, &lock.beneficiary, 0, start + 1_000);
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+
+    // The same permit once the next schedule has matured
+    warp_to_timestamp(&mut env.context, start + 200).await;
+    assert_permit_error(process(&mut env.context, &instructions, &[]).await, LockTokenError::InvalidPermit);
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 100);
+
+    // A permit for the next nonce goes through
+    let instructions = permit_instructions(&env, &lock, &lock.beneficiary, 1, start + 1_000);
+    process(&mut env.context, &instructions, &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 300);
+    assert_eq!(permit_nonce(&mut env, &lock).await, 2);
+}
+
+#[tokio::test]
+async fn test_permit_of_wrong_signer_is_rejected() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let lock = create_lock(&mut env, start).await;
+    warp_to_timestamp(&mut env.context, start + 100).await;
+
+    let impostor = Keypair::new();
+    let instructions = permit_instructions(&env, &lock, &impostor, 0, start + 1_000);
+    assert_permit_error(process(&mut env.context, &instructions, &[]).await, LockTokenError::InvalidPermit);
+
+    // Without the ed25519 program instruction
+    let [_, unlock] = permit_instructions(&env, &lock, &lock.beneficiary, 0, start + 1_000);
+    assert_instruction_error(
+        process(&mut env.context, &[unlock], &[]).await,
+        InstructionError::Custom(LockTokenError::InvalidPermit as u32),
+    );
+
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 0);
+    assert_eq!(permit_nonce(&mut env, &lock).await, 0);
+}
+
+#[tokio::test]
+async fn test_expired_permit_is_rejected() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let lock = create_lock(&mut env, start).await;
+    warp_to_timestamp(&mut env.context, start + 100).await;
+
+    let instructions = permit_instructions(&env, &lock, &lock.beneficiary, 0, start + 99);
+    assert_permit_error(process(&mut env.context, &instructions, &[]).await, LockTokenError::PermitExpired);
+    assert_eq!(permit_nonce(&mut env, &lock).await, 0);
+}
@@ -0,0 +1,147 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    instruction::{init, unlock, LockSeed, Schedule},
+    state::{get_locking_account_size, unpack_schedules, LockSchedule, LockScheduleHeader},
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_program::{
+    instruction::InstructionError, program_pack::Pack, pubkey::Pubkey, system_instruction, system_program, sysvar,
+};
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+struct Lock {
+    seeds: LockSeed,
+    locking_account: Pubkey,
+    source: Pubkey,
+    destination: Pubkey,
+    schedules: Vec<Schedule>,
+}
+
+// Three schedules of 100 released 100 seconds apart, from a source token account of the context payer
+async fn new_lock(env: &mut TestEnv, start: u64) -> Lock {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 300).await;
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+    let seeds = LockSeed::random(&env.program_id);
+
+    Lock {
+        seeds,
+        locking_account: seeds.locking_account_address(&env.program_id).unwrap(),
+        source,
+        destination,
+        schedules: (1..=3).map(|i| Schedule { release_time: start + 100 * i, amount: 100 }).collect(),
+    }
+}
+
+async fn create(env: &mut TestEnv, lock: &Lock) -> Result<(), TransactionError> {
+    let payer = env.context.payer.pubkey();
+    let instruction = create_lock_instruction(
+        env,
+        lock.seeds,
+        &payer,
+        &lock.source,
+        &lock.destination,
+        lock.schedules.clone(),
+        false,
+        &[],
+    );
+    process(&mut env.context, &[instruction], &[]).await
+}
+
+#[tokio::test]
+async fn test_create_cuts_off_unused_schedule_slots() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let lock = new_lock(&mut env, start).await;
+    let rent = env.context.banks_client.get_rent().await.unwrap();
+
+    let instruction = init_instruction(&env, lock.seeds, 10);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    let initialized = get_account(&mut env.context, &lock.locking_account).await;
+    assert_eq!(initialized.data.len(), get_locking_account_size(10));
+
+    create(&mut env, &lock).await.unwrap();
+
+    // The rent of the 7 unused slots went back to the payer of Init
+    let account = get_account(&mut env.context, &lock.locking_account).await;
+    assert_eq!(account.data.len(), get_locking_account_size(3));
+    assert_eq!(account.lamports, rent.minimum_balance(get_locking_account_size(3)));
+    let header_len = LockScheduleHeader::header_len(account.data.len());
+    let header = LockScheduleHeader::unpack_from_slice(&account.data[..header_len]).unwrap();
+    assert_eq!(header.schedule_count, 3);
+    let schedules = unpack_schedules(&account.data[header_len..]).unwrap();
+    assert_eq!(
+        schedules,
+        lock.schedules
+            .iter()
+            .map(|s| LockSchedule { release_time: s.release_time, amount: s.amount })
+            .collect::<Vec<_>>()
+    );
+
+    warp_to_timestamp(&mut env.context, start + 300).await;
+    let mint = env.mint.pubkey();
+    let instruction = unlock(
+        &env.program_id,
+        &spl_token::id(),
+        &lock.locking_account,
+        &get_associated_token_address_with_program_id(&lock.locking_account, &mint, &spl_token::id()),
+        &lock.destination,
+        &mint,
+        lock.seeds,
+        0,
+    )
+    .unwrap();
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut env.context, &lock.destination).await, 300);
+}
+
+#[tokio::test]
+async fn test_unused_rent_stays_when_another_account_paid_init() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let lock = new_lock(&mut env, start).await;
+    let rent = env.context.banks_client.get_rent().await.unwrap();
+
+    let init_payer = Keypair::new();
+    let payer = env.context.payer.pubkey();
+    let instructions = [
+        system_instruction::transfer(&payer, &init_payer.pubkey(), 1_000_000_000),
+        init(
+            &system_program::id(),
+            &sysvar::rent::id(),
+            &env.program_id,
+            &init_payer.pubkey(),
+            &lock.locking_account,
+            lock.seeds,
+            10,
+        )
+        .unwrap(),
+    ];
+    process(&mut env.context, &instructions, &[&init_payer]).await.unwrap();
+
+    create(&mut env, &lock).await.unwrap();
+
+    let account = get_account(&mut env.context, &lock.locking_account).await;
+    assert_eq!(account.data.len(), get_locking_account_size(3));
+    assert_eq!(account.lamports, rent.minimum_balance(get_locking_account_size(10)));
+}
+
+#[tokio::test]
+async fn test_create_rejects_more_schedules_than_initialized() {
+    let mut env = setup(true).await;
+    let start = now(&mut env.context).await;
+    let lock = new_lock(&mut env, start).await;
+
+    let instruction = init_instruction(&env, lock.seeds, 2);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+    assert_instruction_error(create(&mut env, &lock).await, InstructionError::InvalidAccountData);
+}
@@ -0,0 +1,239 @@
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use lock_token::{
+    error::LockTokenError,
+    instruction::{create, CreateFeePayment, LockSeed, Schedule, UsdFeePayment},
+    state::{find_global_state_address, token_state_address, TokenState},
+    time::TimeBasis,
+    utils::get_associated_token_address_with_program_id,
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction, system_program, sysvar,
+};
+use solana_sdk::{
+    account::AccountSharedData,
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+// 10 USD
+const FEES_IN_USD: u64 = 10_000_000;
+
+struct UsdToken {
+    mint: Pubkey,
+    payer_token_account: Pubkey,
+    company_token_account: Pubkey,
+}
+
+// A USD token with 6 decimals, the context payer holding enough of it to pay the fee once
+async fn create_usd_token(env: &mut TestEnv) -> UsdToken {
+    let mint = Keypair::new();
+    let rent = env.context.banks_client.get_rent().await.unwrap();
+    let payer = env.context.payer.pubkey();
+    process(
+        &mut env.context,
+        &[
+            system_instruction::create_account(
+                &payer,
+                &mint.pubkey(),
+                rent.minimum_balance(spl_token::state::Mint::LEN),
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_mint(&spl_token::id(), &mint.pubkey(), &payer, None, 6).unwrap(),
+        ],
+        &[&mint],
+    )
+    .await
+    .unwrap();
+
+    let payer_token_account = create_token_account(&mut env.context, &mint.pubkey(), &payer).await;
+    mint_to(&mut env.context, &mint.pubkey(), &payer_token_account, FEES_IN_USD).await;
+    let company_wallet = env.company_wallet;
+    let company_token_account = create_token_account(&mut env.context, &mint.pubkey(), &company_wallet).await;
+    UsdToken {
+        mint: mint.pubkey(),
+        payer_token_account,
+        company_token_account,
+    }
+}
+
+// Charges FEES_IN_USD for locking the mint of the test environment, payable in the given USD token
+fn charge_fees(env: &mut TestEnv, usd_mint: &Pubkey) {
+    let mut state = global_state(&env.admin.pubkey(), &env.company_wallet, &env.price_estimator);
+    state.fees_in_usd = FEES_IN_USD;
+    state.usd_token_address = *usd_mint;
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    let account = program_account(&env.program_id, &state);
+    env.context.set_account(&program_state_account_key, &AccountSharedData::from(account));
+
+    let mint = env.mint.pubkey();
+    let token_state = TokenState {
+        mint_address: mint,
+        is_free: false,
+        fee_override_usd: 0,
+        has_override: false,
+        active_locks: 0,
+        total_locked_amount: 0,
+        last_snapshot_amount: 0,
+        last_snapshot_ts: 0,
+        is_initialized: true,
+    };
+    let account = program_account(&env.program_id, &token_state);
+    env.context.set_account(&token_state_address(&env.program_id, &mint).unwrap(), &AccountSharedData::from(account));
+}
+
+// Inits a lock of 500 tokens and returns the Create paying its fee with the given USD token accounts
+async fn usd_fee_create_instruction(env: &mut TestEnv, usd_fee_payment: UsdFeePayment) -> Instruction {
+    let mint = env.mint.pubkey();
+    let payer = env.context.payer.pubkey();
+    let source = create_token_account(&mut env.context, &mint, &payer).await;
+    mint_to(&mut env.context, &mint, &source, 500).await;
+    let destination = create_token_account(&mut env.context, &mint, &Keypair::new().pubkey()).await;
+
+    let start = now(&mut env.context).await;
+    let schedules = vec![Schedule { release_time: start + 100, amount: 500 }];
+    let seeds = LockSeed::random(&env.program_id);
+    let instruction = init_instruction(env, seeds, schedules.len() as u32);
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    let locking_account = seeds.locking_account_address(&env.program_id).unwrap();
+    create(
+        &env.program_id,
+        &spl_token::id(),
+        &system_program::id(),
+        &sysvar::rent::id(),
+        &spl_associated_token_account::id(),
+        &env.price_estimator,
+        &locking_account,
+        &get_associated_token_address_with_program_id(&locking_account, &mint, &spl_token::id()),
+        &payer,
+        &source,
+        &payer,
+        &destination,
+        &env.company_wallet,
+        &mint,
+        schedules,
+        seeds,
+        0,
+        false,
+        TimeBasis::UnixTimestamp,
+        Vec::new(),
+        0,
+        CreateFeePayment::Usd(usd_fee_payment),
+        None,
+        false,
+        &[],
+    )
+    .unwrap()
+}
+
+fn usd_fee_payment(usd_token: &UsdToken) -> UsdFeePayment {
+    UsdFeePayment {
+        payer_token_account: usd_token.payer_token_account,
+        usd_mint: usd_token.mint,
+        company_token_account: usd_token.company_token_account,
+    }
+}
+
+fn assert_fee_mint_mismatch(result: Result<(), TransactionError>) {
+    assert_instruction_error(result, InstructionError::Custom(LockTokenError::FeeMintMismatch as u32));
+}
+
+// The price estimator is never set up, so the fee can only be paid in the USD token
+#[tokio::test]
+async fn test_create_paying_fee_in_usd() {
+    let mut env = setup(true).await;
+    let usd_token = create_usd_token(&mut env).await;
+    charge_fees(&mut env, &usd_token.mint);
+
+    let instruction = usd_fee_create_instruction(&mut env, usd_fee_payment(&usd_token)).await;
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    assert_eq!(token_balance(&mut env.context, &usd_token.payer_token_account).await, 0);
+    assert_eq!(token_balance(&mut env.context, &usd_token.company_token_account).await, FEES_IN_USD);
+    assert!(env.context.banks_client.get_account(env.company_wallet).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_create_paying_fee_in_usd_for_free_token() {
+    let mut env = setup(true).await;
+    let usd_token = create_usd_token(&mut env).await;
+    let mut state = global_state(&env.admin.pubkey(), &env.company_wallet, &env.price_estimator);
+    state.fees_in_usd = FEES_IN_USD;
+    state.usd_token_address = usd_token.mint;
+    let (program_state_account_key, _) = find_global_state_address(&env.program_id);
+    let account = program_account(&env.program_id, &state);
+    env.context.set_account(&program_state_account_key, &AccountSharedData::from(account));
+
+    let instruction = usd_fee_create_instruction(&mut env, usd_fee_payment(&usd_token)).await;
+    process(&mut env.context, &[instruction], &[]).await.unwrap();
+
+    assert_eq!(token_balance(&mut env.context, &usd_token.payer_token_account).await, FEES_IN_USD);
+    assert_eq!(token_balance(&mut env.context, &usd_token.company_token_account).await, 0);
+}
+
+#[tokio::test]
+async fn test_create_paying_fee_in_other_token() {
+    let mut env = setup(true).await;
+    let usd_token = create_usd_token(&mut env).await;
+    let other_token = create_usd_token(&mut env).await;
+    charge_fees(&mut env, &usd_token.mint);
+
+    // Another mint than the USD token of the program state
+    let instruction = usd_fee_create_instruction(&mut env, usd_fee_payment(&other_token)).await;
+    assert_fee_mint_mismatch(process(&mut env.context, &[instruction], &[]).await);
+
+    // The right mint with a payer token account of another mint
+    let instruction = usd_fee_create_instruction(
+        &mut env,
+        UsdFeePayment {
+            payer_token_account: other_token.payer_token_account,
+            ..usd_fee_payment(&usd_token)
+        },
+    )
+    .await;
+    assert_fee_mint_mismatch(process(&mut env.context, &[instruction], &[]).await);
+
+    // The right mint with a company token account of another mint
+    let instruction = usd_fee_create_instruction(
+        &mut env,
+        UsdFeePayment {
+            company_token_account: other_token.company_token_account,
+            ..usd_fee_payment(&usd_token)
+        },
+    )
+    .await;
+    assert_fee_mint_mismatch(process(&mut env.context, &[instruction], &[]).await);
+
+    assert_eq!(token_balance(&mut env.context, &usd_token.payer_token_account).await, FEES_IN_USD);
+    assert_eq!(token_balance(&mut env.context, &other_token.payer_token_account).await, FEES_IN_USD);
+}
+
+#[tokio::test]
+async fn test_create_paying_fee_in_usd_to_other_wallet() {
+    let mut env = setup(true).await;
+    let usd_token = create_usd_token(&mut env).await;
+    charge_fees(&mut env, &usd_token.mint);
+    let other_token_account = create_token_account(&mut env.context, &usd_token.mint, &Pubkey::new_unique()).await;
+
+    let instruction = usd_fee_create_instruction(
+        &mut env,
+        UsdFeePayment {
+            company_token_account: other_token_account,
+            ..usd_fee_payment(&usd_token)
+        },
+    )
+    .await;
+    assert_instruction_error(
+        process(&mut env.context, &[instruction], &[]).await,
+        InstructionError::Custom(LockTokenError::CompanyWalletMismatch as u32),
+    );
+    assert_eq!(token_balance(&mut env.context, &other_token_account).await, 0);
+}